@@ -1,6 +1,7 @@
 use std::net::SocketAddr;
 
 use lwk_jade::TIMEOUT;
+use lwk_wollet::elements::secp256k1_zkp::PublicKey;
 use lwk_wollet::UnvalidatedRecipient;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
@@ -15,14 +16,48 @@ use crate::{request, response};
 
 pub struct Client {
     client: jsonrpc::Client,
+
+    /// If some, the signatures of the responses made by the server identity are verified
+    identity: Option<PublicKey>,
 }
 
 impl Client {
     pub fn new(addr: SocketAddr) -> Result<Self, Error> {
-        let url = format!("http://{addr}");
-        let transport = ReqwestHttpTransport::new(url, TIMEOUT);
+        Self::with_url(format!("http://{addr}"), None)
+    }
+
+    /// Create a client for the server at `url`, sending `token` as bearer authorization if some
+    pub fn with_url(url: String, token: Option<String>) -> Result<Self, Error> {
+        Self::with_root_certificate(url, token, None)
+    }
+
+    /// Like [`Client::with_url()`], also trusting the PEM certificate `root_certificate`, which
+    /// is needed for servers using a self-signed certificate
+    pub fn with_root_certificate(
+        url: String,
+        token: Option<String>,
+        root_certificate: Option<Vec<u8>>,
+    ) -> Result<Self, Error> {
+        let transport = ReqwestHttpTransport::new(url, TIMEOUT)
+            .with_token(token)
+            .with_root_certificate(root_certificate);
         let client = jsonrpc::Client::with_transport(transport);
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            identity: None,
+        })
+    }
+
+    /// Require the responses of methods returning critical data, such as addresses and
+    /// balances, to be signed by the server with the given `identity`
+    pub fn with_identity(mut self, identity: PublicKey) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// A random nonce for signed requests, if signatures are verified
+    fn nonce(&self) -> Option<String> {
+        self.identity.map(|_| crate::identity::nonce())
     }
 
     pub(crate) fn make_request<Req, Res>(
@@ -35,13 +70,24 @@ impl Client {
         Res: DeserializeOwned,
     {
         let params = req.map(|req| to_raw_value(&req)).transpose()?;
-        let method = method.to_string();
-        let request = self.client.build_request(&method, params.as_deref());
+        let method_name = method.to_string();
+        let request = self.client.build_request(&method_name, params.as_deref());
         tracing::trace!("---> {}", serde_json::to_string(&request)?);
         let response = self.client.send_request(request)?;
         tracing::trace!("<--- {}", serde_json::to_string(&response)?);
         match response.result.as_ref() {
-            Some(result) => Ok(serde_json::from_str(result.get())?),
+            Some(result) => match self.identity.as_ref() {
+                Some(identity) if method.is_signed() => {
+                    let value: Value = serde_json::from_str(result.get())?;
+                    let params: Value = match params.as_deref() {
+                        Some(params) => serde_json::from_str(params.get())?,
+                        None => Value::Null,
+                    };
+                    crate::identity::verify_response(identity, &method_name, &params, &value)?;
+                    Ok(serde_json::from_value(value)?)
+                }
+                _ => Ok(serde_json::from_str(result.get())?),
+            },
             None => match response.error {
                 Some(rpc_err) => Err(Error::RpcError(rpc_err)),
                 None => Err(Error::NeitherResultNorErrorSet),
@@ -62,11 +108,13 @@ impl Client {
         name: String,
         mnemonic: String,
         persist: bool,
+        mnemonic_file: Option<String>,
     ) -> Result<response::Signer, Error> {
         let req = request::SignerLoadSoftware {
             name,
             mnemonic,
             persist,
+            mnemonic_file,
         };
         self.make_request(Method::SignerLoadSoftware, Some(req))
     }
@@ -81,6 +129,15 @@ impl Client {
         self.make_request(Method::SignerLoadJade, Some(req))
     }
 
+    pub fn signer_load_ledger(
+        &self,
+        name: String,
+        emulator: u16,
+    ) -> Result<response::Signer, Error> {
+        let req = request::SignerLoadLedger { name, emulator };
+        self.make_request(Method::SignerLoadLedger, Some(req))
+    }
+
     pub fn signer_load_external(
         &self,
         name: String,
@@ -90,12 +147,41 @@ impl Client {
         self.make_request(Method::SignerLoadExternal, Some(req))
     }
 
+    pub fn signer_load_remote(
+        &self,
+        name: String,
+        url: String,
+        remote_name: String,
+        token: Option<String>,
+    ) -> Result<response::Signer, Error> {
+        let req = request::SignerLoadRemote {
+            name,
+            url,
+            remote_name,
+            token,
+        };
+        self.make_request(Method::SignerLoadRemote, Some(req))
+    }
+
     pub fn wallet_list(&self) -> Result<response::WalletList, Error> {
         self.make_request(Method::WalletList, None::<Box<RawValue>>)
     }
 
-    pub fn wallet_load(&self, descriptor: String, name: String) -> Result<response::Wallet, Error> {
-        let req = request::WalletLoad { descriptor, name };
+    pub fn wallet_sync_status(&self) -> Result<response::WalletSyncStatus, Error> {
+        self.make_request(Method::WalletSyncStatus, None::<Box<RawValue>>)
+    }
+
+    pub fn wallet_load(
+        &self,
+        descriptor: String,
+        name: String,
+        gap_limit: Option<u32>,
+    ) -> Result<response::Wallet, Error> {
+        let req = request::WalletLoad {
+            descriptor,
+            name,
+            gap_limit,
+        };
         self.make_request(Method::WalletLoad, Some(req))
     }
 
@@ -118,10 +204,19 @@ impl Client {
         name: String,
         with_tickers: bool,
     ) -> Result<response::WalletBalance, Error> {
-        let req = request::WalletBalance { name, with_tickers };
+        let req = request::WalletBalance {
+            name,
+            with_tickers,
+            nonce: self.nonce(),
+        };
         self.make_request(Method::WalletBalance, Some(req))
     }
 
+    pub fn wallet_summary(&self, name: String) -> Result<response::WalletSummary, Error> {
+        let req = request::WalletSummary { name };
+        self.make_request(Method::WalletSummary, Some(req))
+    }
+
     pub fn wallet_address(
         &self,
         name: String,
@@ -129,6 +224,7 @@ impl Client {
         signer: Option<String>,
         with_text_qr: bool,
         with_uri_qr: Option<u8>,
+        skip_used: bool,
     ) -> Result<response::WalletAddress, Error> {
         let req = request::WalletAddress {
             name,
@@ -136,20 +232,69 @@ impl Client {
             signer,
             with_text_qr,
             with_uri_qr,
+            skip_used,
+            nonce: self.nonce(),
         };
         self.make_request(Method::WalletAddress, Some(req))
     }
 
+    pub fn wallet_addresses(
+        &self,
+        name: String,
+        change: bool,
+        offset: u32,
+        limit: Option<u32>,
+    ) -> Result<response::WalletAddresses, Error> {
+        let req = request::WalletAddresses {
+            name,
+            change,
+            offset,
+            limit,
+        };
+        self.make_request(Method::WalletAddresses, Some(req))
+    }
+
+    pub fn wallet_address_reserve(
+        &self,
+        name: String,
+        count: u32,
+        index: Option<u32>,
+    ) -> Result<response::WalletAddressReserve, Error> {
+        let req = request::WalletAddressReserve { name, count, index };
+        self.make_request(Method::WalletAddressReserve, Some(req))
+    }
+
+    pub fn wallet_recovery_scan(
+        &self,
+        mnemonic: String,
+        max_account: Option<u32>,
+    ) -> Result<response::WalletRecoveryScan, Error> {
+        let req = request::WalletRecoveryScan {
+            mnemonic,
+            max_account,
+        };
+        self.make_request(Method::WalletRecoveryScan, Some(req))
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn wallet_send_many(
         &self,
         name: String,
         addressees: Vec<UnvalidatedRecipient>,
         fee_rate: Option<f32>,
+        fee_target: Option<u16>,
+        locktime: Option<String>,
+        draft: Option<String>,
+        force: bool,
     ) -> Result<response::Pset, Error> {
         let req = request::WalletSendMany {
             addressees: addressees.into_iter().map(unvalidate_addressee).collect(),
             fee_rate,
+            fee_target,
             name,
+            locktime,
+            draft,
+            force,
         };
         self.make_request(Method::WalletSendMany, Some(req))
     }
@@ -159,11 +304,15 @@ impl Client {
         name: String,
         address: String,
         fee_rate: Option<f32>,
+        assets: Vec<String>,
+        force: bool,
     ) -> Result<response::Pset, Error> {
         let req = request::WalletDrain {
             address,
             fee_rate,
             name,
+            assets,
+            force,
         };
         self.make_request(Method::WalletDrain, Some(req))
     }
@@ -188,12 +337,14 @@ impl Client {
         multisig_kind: String,
         threshold: u32,
         keyorigin_xpubs: Vec<String>,
+        signers: Vec<String>,
     ) -> Result<response::WalletMultisigDescriptor, Error> {
         let req = request::WalletMultisigDescriptor {
             descriptor_blinding_key,
             multisig_kind,
             threshold,
             keyorigin_xpubs,
+            signers,
         };
         self.make_request(Method::WalletMultisigDescriptor, Some(req))
     }
@@ -216,11 +367,34 @@ impl Client {
         self.make_request(Method::SignerRegisterMultisig, Some(req))
     }
 
-    pub fn signer_sign(&self, name: String, pset: String) -> Result<response::Pset, Error> {
-        let req = request::SignerSign { name, pset };
+    pub fn signer_sign(
+        &self,
+        name: String,
+        pset: String,
+        wallet: Option<String>,
+    ) -> Result<response::Pset, Error> {
+        let req = request::SignerSign { name, pset, wallet };
         self.make_request(Method::SignerSign, Some(req))
     }
 
+    pub fn signer_session_start(
+        &self,
+        name: String,
+        pset: String,
+    ) -> Result<response::SigningSession, Error> {
+        let req = request::SignerSessionStart { name, pset };
+        self.make_request(Method::SignerSessionStart, Some(req))
+    }
+
+    pub fn signer_session_complete(
+        &self,
+        id: String,
+        signatures: Vec<request::SessionSignature>,
+    ) -> Result<response::Pset, Error> {
+        let req = request::SignerSessionComplete { id, signatures };
+        self.make_request(Method::SignerSessionComplete, Some(req))
+    }
+
     pub fn wallet_broadcast(
         &self,
         name: String,
@@ -259,11 +433,13 @@ impl Client {
         name: String,
         pset: String,
         with_tickers: bool,
+        max_fee_basis_points: Option<u32>,
     ) -> Result<response::WalletPsetDetails, Error> {
         let req = request::WalletPsetDetails {
             name,
             pset,
             with_tickers,
+            max_fee_basis_points,
         };
         self.make_request(Method::WalletPsetDetails, Some(req))
     }
@@ -273,12 +449,34 @@ impl Client {
         self.make_request(Method::WalletUtxos, Some(req))
     }
 
+    pub fn wallet_utxos_export(&self, name: String) -> Result<response::WalletUtxosExport, Error> {
+        let req = request::WalletUtxosExport { name };
+        self.make_request(Method::WalletUtxosExport, Some(req))
+    }
+
     pub fn wallet_txs(
         &self,
         name: String,
         with_tickers: bool,
     ) -> Result<response::WalletTxs, Error> {
-        let req = request::WalletTxs { name, with_tickers };
+        let req = request::WalletTxs {
+            name,
+            with_tickers,
+            offset: 0,
+            limit: None,
+            asset: None,
+            min_height: None,
+            max_height: None,
+            direction: None,
+        };
+        self.wallet_txs_filtered(req)
+    }
+
+    /// Get a page of the wallet transactions matching the filters in `req`
+    pub fn wallet_txs_filtered(
+        &self,
+        req: request::WalletTxs,
+    ) -> Result<response::WalletTxs, Error> {
         self.make_request(Method::WalletTxs, Some(req))
     }
 
@@ -296,6 +494,69 @@ impl Client {
         self.make_request(Method::WalletTx, Some(req))
     }
 
+    pub fn wallet_utxo_lock(
+        &self,
+        name: String,
+        txid: String,
+        vout: u32,
+    ) -> Result<response::Empty, Error> {
+        let req = request::WalletUtxoLock { name, txid, vout };
+        self.make_request(Method::WalletUtxoLock, Some(req))
+    }
+
+    pub fn wallet_utxo_unlock(
+        &self,
+        name: String,
+        txid: String,
+        vout: u32,
+    ) -> Result<response::Empty, Error> {
+        let req = request::WalletUtxoLock { name, txid, vout };
+        self.make_request(Method::WalletUtxoUnlock, Some(req))
+    }
+
+    pub fn wallet_set_label(
+        &self,
+        name: String,
+        type_: String,
+        ref_: String,
+        label: Option<String>,
+    ) -> Result<response::Empty, Error> {
+        let req = request::WalletSetLabel {
+            name,
+            type_,
+            ref_,
+            label,
+        };
+        self.make_request(Method::WalletSetLabel, Some(req))
+    }
+
+    pub fn wallet_get_label(
+        &self,
+        name: String,
+        type_: String,
+        ref_: String,
+    ) -> Result<response::WalletLabel, Error> {
+        let req = request::WalletGetLabel { name, type_, ref_ };
+        self.make_request(Method::WalletGetLabel, Some(req))
+    }
+
+    pub fn wallet_export_labels(
+        &self,
+        name: String,
+    ) -> Result<response::WalletExportLabels, Error> {
+        let req = request::WalletExportLabels { name };
+        self.make_request(Method::WalletExportLabels, Some(req))
+    }
+
+    pub fn wallet_import_labels(
+        &self,
+        name: String,
+        labels: String,
+    ) -> Result<response::WalletImportLabels, Error> {
+        let req = request::WalletImportLabels { name, labels };
+        self.make_request(Method::WalletImportLabels, Some(req))
+    }
+
     pub fn wallet_set_tx_memo(
         &self,
         name: String,
@@ -330,7 +591,8 @@ impl Client {
         address_token: Option<String>,
         contract: Option<String>,
         fee_rate: Option<f32>,
-    ) -> Result<response::Pset, Error> {
+        locktime: Option<String>,
+    ) -> Result<response::WalletIssue, Error> {
         let req = request::WalletIssue {
             name,
             satoshi_asset,
@@ -339,6 +601,7 @@ impl Client {
             address_token,
             contract,
             fee_rate,
+            locktime,
         };
         self.make_request(Method::WalletIssue, Some(req))
     }
@@ -377,6 +640,54 @@ impl Client {
         self.make_request(Method::WalletBurn, Some(req))
     }
 
+    pub fn wallet_pegout(
+        &self,
+        name: String,
+        mainchain_address: String,
+        satoshi: u64,
+        fee_rate: Option<f32>,
+    ) -> Result<response::Pset, Error> {
+        let req = request::WalletPegout {
+            name,
+            mainchain_address,
+            satoshi,
+            fee_rate,
+        };
+        self.make_request(Method::WalletPegout, Some(req))
+    }
+
+    pub fn wallet_pegin_address(
+        &self,
+        name: String,
+        fedpegscript: String,
+        index: Option<u32>,
+    ) -> Result<response::WalletPeginAddress, Error> {
+        let req = request::WalletPeginAddress {
+            name,
+            fedpegscript,
+            index,
+        };
+        self.make_request(Method::WalletPeginAddress, Some(req))
+    }
+
+    pub fn wallet_claim_pegin(
+        &self,
+        name: String,
+        fedpegscript: String,
+        mainchain_tx: String,
+        txout_proof: String,
+        fee_rate: Option<f32>,
+    ) -> Result<response::Pset, Error> {
+        let req = request::WalletClaimPegin {
+            name,
+            fedpegscript,
+            mainchain_tx,
+            txout_proof,
+            fee_rate,
+        };
+        self.make_request(Method::WalletClaimPegin, Some(req))
+    }
+
     pub fn asset_contract(
         &self,
         domain: String,
@@ -406,6 +717,10 @@ impl Client {
         self.make_request(Method::AssetList, None::<Box<RawValue>>)
     }
 
+    pub fn asset_events(&self) -> Result<response::AssetEvents, Error> {
+        self.make_request(Method::AssetEvents, None::<Box<RawValue>>)
+    }
+
     pub fn asset_insert(
         &self,
         asset_id: String,
@@ -435,6 +750,97 @@ impl Client {
         self.make_request(Method::AssetPublish, Some(req))
     }
 
+    pub fn contact_add(
+        &self,
+        name: String,
+        address: Option<String>,
+        descriptor: Option<String>,
+        index: Option<u32>,
+    ) -> Result<response::Contact, Error> {
+        let req = request::ContactAdd {
+            name,
+            address,
+            descriptor,
+            index,
+        };
+        self.make_request(Method::ContactAdd, Some(req))
+    }
+
+    pub fn contact_list(&self) -> Result<response::ContactList, Error> {
+        self.make_request(Method::ContactList, None::<Box<RawValue>>)
+    }
+
+    pub fn contact_remove(&self, name: String) -> Result<response::Empty, Error> {
+        let req = request::ContactRemove { name };
+        self.make_request(Method::ContactRemove, Some(req))
+    }
+
+    pub fn draft_save(
+        &self,
+        name: String,
+        pset: String,
+        wallet: String,
+        creator: Option<String>,
+        replace: bool,
+    ) -> Result<response::DraftInfo, Error> {
+        let req = request::DraftSave {
+            name,
+            pset,
+            wallet,
+            creator,
+            replace,
+            timestamp: None,
+        };
+        self.make_request(Method::DraftSave, Some(req))
+    }
+
+    pub fn draft_list(&self, wallet: Option<String>) -> Result<response::DraftList, Error> {
+        let req = request::DraftList { wallet };
+        self.make_request(Method::DraftList, Some(req))
+    }
+
+    pub fn draft_load(&self, name: String) -> Result<response::Draft, Error> {
+        let req = request::DraftLoad { name };
+        self.make_request(Method::DraftLoad, Some(req))
+    }
+
+    pub fn draft_delete(&self, name: String) -> Result<response::Empty, Error> {
+        let req = request::DraftLoad { name };
+        self.make_request(Method::DraftDelete, Some(req))
+    }
+
+    pub fn pset_send_nostr(
+        &self,
+        signer: String,
+        recipient: String,
+        pset: String,
+        relays: Vec<String>,
+    ) -> Result<response::PsetSendNostr, Error> {
+        let req = request::PsetSendNostr {
+            signer,
+            recipient,
+            pset,
+            relays,
+        };
+        self.make_request(Method::PsetSendNostr, Some(req))
+    }
+
+    pub fn pset_inbox(
+        &self,
+        signer: String,
+        senders: Vec<String>,
+        relays: Vec<String>,
+        since: u64,
+    ) -> Result<response::PsetInbox, Error> {
+        let req = request::PsetInbox {
+            signer,
+            senders,
+            relays,
+            since,
+        };
+        self.make_request(Method::PsetInbox, Some(req))
+    }
+
     pub fn schema(&self, arg: Method, direction: request::Direction) -> Result<Value, Error> {
         let req = request::Schema {
             method: arg.to_string(),
@@ -452,6 +858,24 @@ impl Client {
         self.make_request(Method::Scan, None::<Box<RawValue>>)
     }
 
+    pub fn subscribe(
+        &self,
+        wallet: Option<String>,
+        since: u64,
+        timeout: u64,
+    ) -> Result<response::Subscribe, Error> {
+        let req = request::Subscribe {
+            wallet,
+            since,
+            timeout,
+        };
+        self.make_request(Method::Subscribe, Some(req))
+    }
+
+    pub fn fee_rates(&self) -> Result<response::FeeRates, Error> {
+        self.make_request(Method::FeeRates, None::<Box<RawValue>>)
+    }
+
     pub fn stop(&self) -> Result<Value, Error> {
         // TODO discriminate only stop error
         let _: Result<Value, Error> = self.make_request(Method::Stop, None::<Box<RawValue>>);
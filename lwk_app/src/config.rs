@@ -28,6 +28,27 @@ pub struct Config {
     pub registry_url: String,
     pub timeout: Duration,
     pub scanning_interval: Duration,
+
+    /// Interval between checks of the registry entries of the assets held by loaded wallets
+    pub registry_check_interval: Duration,
+
+    /// URL receiving a POST with every asset event as json
+    pub webhook_url: Option<String>,
+
+    /// Sign critical responses, such as addresses and balances, with the server identity key
+    pub sign_responses: bool,
+
+    /// PEM certificate of the RPC server, if set with `tls_key` the server accepts only https
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the RPC server certificate
+    pub tls_key: Option<PathBuf>,
+
+    /// If set, all requests must authenticate with this token, see [`lwk_tiny_jrpc::Config`]
+    pub auth_token: Option<String>,
+
+    /// If set, building a payment identical to one built within this window fails unless forced
+    pub duplicate_payment_window: Option<Duration>,
 }
 
 impl Config {
@@ -44,6 +65,13 @@ impl Config {
             registry_url: "https://assets-testnet.blockstream.info/".into(),
             timeout: TIMEOUT,
             scanning_interval: consts::SCANNING_INTERVAL,
+            registry_check_interval: consts::REGISTRY_CHECK_INTERVAL,
+            webhook_url: None,
+            sign_responses: false,
+            tls_cert: None,
+            tls_key: None,
+            auth_token: None,
+            duplicate_payment_window: None,
         }
     }
 
@@ -60,6 +88,13 @@ impl Config {
             registry_url: "https://assets.blockstream.info/".into(),
             timeout: TIMEOUT,
             scanning_interval: consts::SCANNING_INTERVAL,
+            registry_check_interval: consts::REGISTRY_CHECK_INTERVAL,
+            webhook_url: None,
+            sign_responses: false,
+            tls_cert: None,
+            tls_key: None,
+            auth_token: None,
+            duplicate_payment_window: None,
         }
     }
 
@@ -81,6 +116,13 @@ impl Config {
             timeout: TIMEOUT,
             // Scan more frequently while testing
             scanning_interval: Duration::from_secs(1),
+            registry_check_interval: Duration::from_secs(1),
+            webhook_url: None,
+            sign_responses: false,
+            tls_cert: None,
+            tls_key: None,
+            auth_token: None,
+            duplicate_payment_window: None,
         }
     }
 
@@ -114,6 +156,13 @@ impl Config {
         Ok(path)
     }
 
+    /// Returns the path of the identity key file under datadir
+    pub fn identity_path(&self) -> Result<PathBuf, Error> {
+        let mut path = self.datadir()?;
+        path.push("identity.key");
+        Ok(path)
+    }
+
     /// True if Liquid mainnet
     pub fn is_mainnet(&self) -> bool {
         matches!(self.network, ElementsNetwork::Liquid)
@@ -125,6 +174,10 @@ impl Config {
 
     pub fn electrum_client(&self) -> Result<lwk_wollet::ElectrumClient, Error> {
         // TODO cache it instead of recreating every time
-        Ok(lwk_wollet::ElectrumClient::new(&self.electrum_url())?)
+        let options = lwk_wollet::ElectrumOptions::default().timeout(consts::ELECTRUM_TIMEOUT);
+        Ok(lwk_wollet::ElectrumClient::with_options(
+            &self.electrum_url(),
+            options,
+        )?)
     }
 }
@@ -6,3 +6,27 @@ pub const DEFAULT_ADDR: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(127, 0, 0
 pub const APP_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub const SCANNING_INTERVAL: Duration = Duration::from_secs(10);
+
+pub const REGISTRY_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Seconds after which a request to the electrum server fails, so that a hung connection doesn't
+/// block the scanning thread
+pub const ELECTRUM_TIMEOUT: u8 = 30;
+
+/// Maximum size of the transactions downloaded by a scan before applying them to the wallet, so
+/// that the progress of a scan failing midway is kept
+pub const SCAN_MEMORY_BUDGET: usize = 1_000_000;
+
+/// Maximum time a request waits for a scan loop to complete, less than the client timeout so that
+/// a stalled scan is reported instead of holding an RPC thread until the client gives up
+pub const SCAN_STALL_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The last account scanned for every derivation scheme when recovering a wallet
+pub const RECOVERY_MAX_ACCOUNT: u32 = 5;
+
+/// The maximum time a `subscribe` request waits for notifications, less than the client timeout
+pub const MAX_SUBSCRIBE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Number of threads serving RPC requests, so that a pending `subscribe` or a request waiting for
+/// a scan doesn't block the other requests
+pub const RPC_THREADS: u8 = 4;
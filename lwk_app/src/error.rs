@@ -1,5 +1,6 @@
 use std::sync::{MutexGuard, PoisonError};
 
+use lwk_common::ErrorCategory;
 use lwk_tiny_jrpc::error::ImplementationDefinedCode;
 use serde_json::json;
 
@@ -20,6 +21,9 @@ pub enum Error {
     #[error("Jade Error: {0}")]
     Jade(#[from] lwk_jade::Error),
 
+    #[error("Ledger Error: {0}")]
+    Ledger(#[from] lwk_ledger::Error),
+
     #[error("Wollet Error: {0}")]
     Wollet(#[from] lwk_wollet::Error),
 
@@ -62,6 +66,9 @@ pub enum Error {
     #[error(transparent)]
     QrError(#[from] lwk_common::QrError),
 
+    #[error(transparent)]
+    Common(#[from] lwk_common::Error),
+
     #[error("Wallet '{0}' does not exist")]
     WalletNotExist(String),
 
@@ -89,6 +96,38 @@ pub enum Error {
     #[error("Asset '{0}' already inserted")]
     AssetAlreadyInserted(String),
 
+    #[error("Contact '{0}' does not exist")]
+    ContactNotExist(String),
+
+    #[error("Contact '{0}' already exists")]
+    ContactAlreadyExist(String),
+
+    #[error("Draft '{0}' does not exist")]
+    DraftNotExist(String),
+
+    #[error("Draft '{0}' already exists")]
+    DraftAlreadyExist(String),
+
+    #[error("Draft '{0}' is not of wallet '{1}'")]
+    DraftOfOtherWallet(String, String),
+
+    #[error(
+        "An identical payment was built or broadcast {0} seconds ago, force it to build it again"
+    )]
+    DuplicatePayment(u64),
+
+    #[error("Signing session '{0}' does not exist")]
+    SigningSessionNotExist(String),
+
+    #[error("Method '{0}' is accepted only while restoring the state at startup")]
+    StartupOnlyMethod(String),
+
+    #[error("The response is not signed by the server")]
+    MissingResponseSignature,
+
+    #[error("The response signature does not match the server identity")]
+    InvalidResponseSignature,
+
     #[error(transparent)]
     MethodNotExist(#[from] crate::method::MethodNotExist),
 
@@ -98,15 +137,27 @@ pub enum Error {
     #[error("Feature \"serial\" is disabled, enable it to solve this error")]
     FeatSerialDisabled,
 
+    #[error("Feature \"nostr\" is disabled, enable it to solve this error")]
+    FeatNostrDisabled,
+
+    #[error("Nostr error: {0}")]
+    Nostr(String),
+
     #[error("Cannot start the server at \"{0}\". It is probably already running.")]
     ServerStart(String),
 
     #[error("Error re-applying start request\nError {0}\nAt line {1} from file {2}\nConsider: (a) correct the line (b) remove the line (c) remove the file")]
     StartStateLoad(String, usize, String),
 
+    #[error("The TLS certificate and key must be both set")]
+    TlsCertOrKeyMissing,
+
     #[error("Received stop command")]
     Stop,
 
+    #[error("The wallets scan did not complete within {0:?}")]
+    ScanStalled(std::time::Duration),
+
     // TODO remove into specific errors
     #[error("Generic error {0}")]
     Generic(String),
@@ -117,6 +168,7 @@ impl Error {
     pub fn as_impl_defined_code(&self) -> ImplementationDefinedCode {
         match self {
             Error::Jade(_) => ImplementationDefinedCode::new(-32_013).expect("static"),
+            Error::Ledger(_) => ImplementationDefinedCode::new(-32_014).expect("static"),
             Error::Wollet(_) => ImplementationDefinedCode::new(-32_005).expect("static"),
             Error::SignerNew(_) => ImplementationDefinedCode::new(-32_006).expect("static"),
             Error::Signer(_) => ImplementationDefinedCode::new(-32_007).expect("static"),
@@ -133,6 +185,62 @@ impl Error {
         }
     }
 
+    /// The category of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Error::Wollet(e) => e.category(),
+            Error::Signer(e) => e.category(),
+            Error::SignerNew(e) => e.category(),
+            Error::Common(e) => e.category(),
+            Error::Jade(_) | Error::Ledger(_) => ErrorCategory::Signer,
+            Error::TinyHttp(_)
+            | Error::JsonRpcHttp(_)
+            | Error::JsonRpcClient(_)
+            | Error::Reqwest(_)
+            | Error::RpcError(_)
+            | Error::NeitherResultNorErrorSet
+            | Error::ServerStart(_)
+            | Error::ScanStalled(_)
+            | Error::Nostr(_) => ErrorCategory::Network,
+            Error::SerdeJson(_)
+            | Error::Address(_)
+            | Error::HexArray(_)
+            | Error::Hex(_)
+            | Error::ElementsEncode(_)
+            | Error::QrError(_)
+            | Error::MethodNotExist(_)
+            | Error::WalletNotExist(_)
+            | Error::WalletAlreadyLoaded(_)
+            | Error::SignerNotExist(_)
+            | Error::SignerAlreadyLoaded(_)
+            | Error::AssetNotExist(_)
+            | Error::AssetAlreadyInserted(_)
+            | Error::ContactNotExist(_)
+            | Error::ContactAlreadyExist(_)
+            | Error::DraftNotExist(_)
+            | Error::DraftAlreadyExist(_)
+            | Error::DraftOfOtherWallet(_, _)
+            | Error::SigningSessionNotExist(_)
+            | Error::StartupOnlyMethod(_)
+            | Error::InvalidIssuanceTxtForAsset(_)
+            | Error::FeatSerialDisabled
+            | Error::FeatNostrDisabled
+            | Error::TlsCertOrKeyMissing => ErrorCategory::Input,
+            Error::InvalidContractForAsset(_)
+            | Error::MissingResponseSignature
+            | Error::InvalidResponseSignature => ErrorCategory::Consensus,
+            Error::WalletTxNotFound(_, _)
+            | Error::Io(_)
+            | Error::AlreadyStarted
+            | Error::NotStarted
+            | Error::PoisonError(_)
+            | Error::StartStateLoad(_, _, _)
+            | Error::Stop
+            | Error::DuplicatePayment(_)
+            | Error::Generic(_) => ErrorCategory::Wallet,
+        }
+    }
+
     /// Used to create error as structured data, easily parsable by the caller
     pub fn as_error_value(&self) -> Option<serde_json::Value> {
         match self {
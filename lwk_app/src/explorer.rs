@@ -23,6 +23,30 @@ pub fn get_registry_data(registry_url: &str, asset: &AssetId) -> Result<Registry
     Ok(data)
 }
 
+/// Get the registry data of an asset, `None` if the registry does not have it
+pub fn get_registry_entry(
+    registry_url: &str,
+    asset: &AssetId,
+) -> Result<Option<RegistryData>, Error> {
+    let url = format!("{registry_url}{asset}");
+    tracing::debug!("checking registry data {url}");
+    let response = reqwest::blocking::get(url)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    Ok(Some(response.error_for_status()?.json()?))
+}
+
+pub fn post_webhook<T: Serialize>(webhook_url: &str, data: &T) -> Result<(), Error> {
+    tracing::debug!("posting to webhook {webhook_url}");
+    reqwest::blocking::Client::new()
+        .post(webhook_url)
+        .json(data)
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
+
 pub fn get_tx(esplora_api_url: &str, txid: &Txid) -> Result<Transaction, Error> {
     let url = format!("{esplora_api_url}tx/{txid}/hex");
     tracing::debug!("getting tx {url}");
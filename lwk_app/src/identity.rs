@@ -0,0 +1,184 @@
+//! Signatures of RPC responses made with the identity key of the server
+//!
+//! A client connecting to a remote server through an untrusted network could receive responses
+//! tampered by a man-in-the-middle, for instance a replaced receiving address. If the server is
+//! started with `sign_responses`, critical responses contain a `signature` field, made with the
+//! server identity key over the method, the params of the request and the rest of the response.
+//! Clients knowing the identity public key verify it with [`verify_response()`]. Since the
+//! params contain a random `nonce` chosen by the client, a signed response can't be replayed as
+//! the answer to another request.
+
+use std::path::Path;
+use std::str::FromStr;
+
+use lwk_wollet::elements::hex::ToHex;
+use lwk_wollet::elements::secp256k1_zkp::{ecdsa::Signature, Message, Secp256k1};
+use lwk_wollet::elements::secp256k1_zkp::{PublicKey, SecretKey};
+use lwk_wollet::hashes::{sha256t_hash_newtype, Hash};
+use serde_json::{json, Value};
+
+use crate::Error;
+
+sha256t_hash_newtype! {
+    /// The tag of the hash
+    pub struct SignedResponseTag = hash_str("LWK-Signed-Response/2.0");
+
+    /// A tagged hash of the response, signed by the server identity key
+    #[hash_newtype(forward)]
+    pub struct SignedResponseHash(_);
+}
+
+/// The field of the response containing the signature
+const SIGNATURE_FIELD: &str = "signature";
+
+/// The message signed for `response` to the request of `method` with `params`, the response must
+/// not contain the signature field
+///
+/// Objects in [`Value`] keep the keys sorted, so the serialization does not depend on the field
+/// order of the struct the response was created from.
+fn message(method: &str, params: &Value, response: &Value) -> Result<Message, Error> {
+    let signed = json!({ "method": method, "params": params, "response": response });
+    let bytes = serde_json::to_vec(&signed)?;
+    let hash = SignedResponseHash::hash(&bytes);
+    Ok(Message::from_digest(hash.to_byte_array()))
+}
+
+/// A random nonce for the params of requests whose response is signed
+pub fn nonce() -> String {
+    rand::random::<[u8; 16]>().to_hex()
+}
+
+/// Sign `response` to the request of `method` with `params` with the server `identity` key,
+/// adding the `signature` field
+pub fn sign_response(
+    identity: &SecretKey,
+    method: &str,
+    params: &Value,
+    response: &mut Value,
+) -> Result<(), Error> {
+    let secp = Secp256k1::signing_only();
+    let signature = secp.sign_ecdsa(&message(method, params, response)?, identity);
+    match response.as_object_mut() {
+        Some(obj) => {
+            obj.insert(SIGNATURE_FIELD.into(), Value::String(signature.to_string()));
+            Ok(())
+        }
+        None => Err(Error::Generic("Only objects can be signed".into())),
+    }
+}
+
+/// Verify the signature of a response created with [`sign_response()`] by the server with the
+/// given `identity` public key, for the request of `method` with `params`
+pub fn verify_response(
+    identity: &PublicKey,
+    method: &str,
+    params: &Value,
+    response: &Value,
+) -> Result<(), Error> {
+    let mut response = response.clone();
+    let signature = response
+        .as_object_mut()
+        .and_then(|obj| obj.remove(SIGNATURE_FIELD))
+        .ok_or(Error::MissingResponseSignature)?;
+    let signature = signature
+        .as_str()
+        .and_then(|s| Signature::from_str(s).ok())
+        .ok_or(Error::InvalidResponseSignature)?;
+    let secp = Secp256k1::verification_only();
+    secp.verify_ecdsa(&message(method, params, &response)?, &signature, identity)
+        .map_err(|_| Error::InvalidResponseSignature)
+}
+
+/// Load the identity key stored at `path`, creating a new one if the file does not exist
+pub fn load_or_create(path: &Path) -> Result<SecretKey, Error> {
+    match std::fs::read_to_string(path) {
+        Ok(s) => SecretKey::from_str(s.trim())
+            .map_err(|e| Error::Generic(format!("Invalid identity key in {path:?}: {e}"))),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            let key = SecretKey::new(&mut rand::thread_rng());
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            let mut file = options.open(path)?;
+            std::io::Write::write_all(&mut file, key.secret_bytes().to_hex().as_bytes())?;
+            tracing::info!("Created new identity key at {path:?}");
+            Ok(key)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_response() {
+        let secp = Secp256k1::new();
+        let identity = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let other = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let identity_pub = PublicKey::from_secret_key(&secp, &identity);
+        let other_pub = PublicKey::from_secret_key(&secp, &other);
+        let method = "wallet_address";
+        let params = json!({"name": "w1", "index": null, "nonce": nonce()});
+        let verify = |pubkey: &PublicKey, method: &str, params: &Value, response: &Value| {
+            verify_response(pubkey, method, params, response)
+        };
+
+        let mut response = json!({"address": "el1...", "index": 0});
+        let err = verify(&identity_pub, method, &params, &response).unwrap_err();
+        assert!(matches!(err, Error::MissingResponseSignature));
+
+        sign_response(&identity, method, &params, &mut response).unwrap();
+        verify(&identity_pub, method, &params, &response).unwrap();
+        let err = verify(&other_pub, method, &params, &response).unwrap_err();
+        assert!(matches!(err, Error::InvalidResponseSignature));
+
+        // The key order of the received response does not matter
+        let s = response.to_string();
+        let reordered = format!(r#"{{"index":0,{}"#, s[1..].replace(r#","index":0"#, ""));
+        assert_ne!(s, reordered);
+        let reordered: Value = serde_json::from_str(&reordered).unwrap();
+        verify(&identity_pub, method, &params, &reordered).unwrap();
+
+        let mut added = response.clone();
+        added["memo"] = json!("");
+        let err = verify(&identity_pub, method, &params, &added).unwrap_err();
+        assert!(matches!(err, Error::InvalidResponseSignature));
+
+        let mut tampered = response.clone();
+        tampered["address"] = json!("el1other");
+        let err = verify(&identity_pub, method, &params, &tampered).unwrap_err();
+        assert!(matches!(err, Error::InvalidResponseSignature));
+
+        // Replayed for another request, method or wallet
+        let mut replayed = params.clone();
+        replayed["nonce"] = json!(nonce());
+        let err = verify(&identity_pub, method, &replayed, &response).unwrap_err();
+        assert!(matches!(err, Error::InvalidResponseSignature));
+        let err = verify(&identity_pub, "wallet_balance", &params, &response).unwrap_err();
+        assert!(matches!(err, Error::InvalidResponseSignature));
+        let mut other_wallet = params.clone();
+        other_wallet["name"] = json!("w2");
+        let err = verify(&identity_pub, method, &other_wallet, &response).unwrap_err();
+        assert!(matches!(err, Error::InvalidResponseSignature));
+
+        let mut not_object = json!([]);
+        assert!(sign_response(&identity, method, &params, &mut not_object).is_err());
+    }
+
+    #[test]
+    fn test_load_or_create() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("identity.key");
+        let key = load_or_create(&path).unwrap();
+        assert_eq!(load_or_create(&path).unwrap(), key);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+}
@@ -11,42 +11,44 @@
 //!
 //! All the requests and responses data model are in the [`lwk_rpc_model`] crate.
 
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::net::SocketAddr;
 use std::num::NonZeroU8;
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use lwk_common::{
-    address_to_text_qr, address_to_uri_qr, keyorigin_xpub_from_str, multisig_desc, singlesig_desc,
-    InvalidBipVariant, InvalidBlindingKeyVariant, InvalidMultisigVariant, InvalidSinglesigVariant,
-    Signer,
+    address_to_text_qr, address_to_uri_qr, keyorigin_xpub_from_str, multisig_desc,
+    pset_output_asset_amount, singlesig_desc, singlesig_desc_with_scheme, DerivationScheme,
+    DescriptorBlindingKey, InvalidBipVariant, InvalidBlindingKeyVariant, InvalidMultisigVariant,
+    InvalidSinglesigVariant, OutputKind, PendingSignatures, Signer, Singlesig,
 };
-use lwk_jade::derivation_path_to_vec;
-use lwk_jade::get_receive_address::Variant;
-use lwk_jade::register_multisig::{JadeDescriptor, RegisterMultisigParams};
 use lwk_jade::Jade;
-use lwk_signer::{AnySigner, SwSigner};
+use lwk_signer::SwSigner;
 use lwk_tiny_jrpc::{tiny_http, JsonRpcServer, Request, Response};
-use lwk_wollet::bitcoin::bip32::Fingerprint;
-use lwk_wollet::bitcoin::XKeyIdentifier;
+use lwk_wollet::bitcoin::bip32::{Fingerprint, KeySource};
+use lwk_wollet::bitcoin::{PublicKey, ScriptBuf, XKeyIdentifier};
 use lwk_wollet::elements::encode::serialize;
 use lwk_wollet::elements::hex::{FromHex, ToHex};
 use lwk_wollet::elements::pset::PartiallySignedTransaction;
-use lwk_wollet::elements::{Address, AssetId, Txid};
+use lwk_wollet::elements::secp256k1_zkp::ecdsa::Signature;
+use lwk_wollet::elements::{Address, AssetId, LockTime, OutPoint, Txid};
 use lwk_wollet::elements_miniscript::descriptor::{Descriptor, DescriptorType, WshInner};
 use lwk_wollet::elements_miniscript::miniscript::decode::Terminal;
 use lwk_wollet::elements_miniscript::{DescriptorPublicKey, ForEachKey};
-use lwk_wollet::{full_scan_with_electrum_client, Wollet};
-use lwk_wollet::{BlockchainBackend, WolletDescriptor};
-use serde_json::Value;
+use lwk_wollet::{fetch_asset_info, full_scan_with_electrum_client, Wollet};
+use lwk_wollet::{BlockchainBackend, ElementsNetwork, LabelRef, WolletDescriptor};
+use lwk_wollet::{Chain, TxDirection, TxFilter};
 
-use crate::explorer::{get_registry_data, get_tx};
+use crate::explorer::{get_registry_data, get_registry_entry, get_tx, post_webhook};
 use crate::method::Method;
-use crate::state::{AppAsset, AppSigner, State};
+use crate::state::{
+    AppAsset, AppContact, AppDraft, AppSigner, AppSigningSession, PaymentRecipients, State,
+    WolletSnapshot,
+};
 use lwk_rpc_model::{request, response};
 
 pub use client::Client;
@@ -59,7 +61,11 @@ mod config;
 pub mod consts;
 mod error;
 mod explorer;
+pub mod identity;
 pub mod method;
+#[cfg(feature = "nostr")]
+mod nostr;
+mod remote_signer;
 mod reqwest_transport;
 mod state;
 
@@ -86,17 +92,46 @@ impl App {
         })
     }
 
-    fn apply_request(&self, client: &Client, line: &str) -> Result<(), Error> {
+    /// Apply a request of the saved state, calling the handler directly so that the server
+    /// authentication and TLS don't matter
+    fn apply_request(state: &Arc<Mutex<State>>, line: &str) -> Result<(), Error> {
         let r: Request = serde_json::from_str(line)?;
-        let method: Method = r.method.parse()?;
-        let _value: Value = client.make_request(method, r.params)?;
-        Ok(())
+        let response = inner_method_handler(r, state.clone())?;
+        match response.error {
+            Some(err) => Err(Error::Generic(err.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn http_server(&self) -> Result<tiny_http::Server, Error> {
+        let addr = self.config.addr;
+        let server = match (self.config.tls_cert.as_ref(), self.config.tls_key.as_ref()) {
+            (None, None) => tiny_http::Server::http(addr),
+            (Some(cert), Some(key)) => {
+                let ssl = tiny_http::SslConfig {
+                    certificate: std::fs::read(cert)?,
+                    private_key: std::fs::read(key)?,
+                };
+                tiny_http::Server::https(addr, ssl)
+            }
+            _ => return Err(Error::TlsCertOrKeyMissing),
+        };
+        server.map_err(|_| Error::ServerStart(addr.to_string()))
     }
 
     pub fn run(&mut self) -> Result<(), Error> {
         if self.rpc.is_some() {
             return Err(error::Error::AlreadyStarted);
         }
+        let identity = if self.config.sign_responses {
+            let key = identity::load_or_create(&self.config.identity_path()?)?;
+            let secp = lwk_wollet::elements::secp256k1_zkp::Secp256k1::signing_only();
+            let pubkey = key.public_key(&secp);
+            tracing::info!("Signing responses with identity {pubkey}");
+            Some(key)
+        } else {
+            None
+        };
         let mut state = State {
             config: self.config.clone(),
             wollets: Default::default(),
@@ -104,20 +139,29 @@ impl App {
             assets: Default::default(),
             tx_memos: Default::default(),
             addr_memos: Default::default(),
+            reserved_addrs: Default::default(),
+            verified_addrs: Default::default(),
+            registry_entries: Default::default(),
+            contacts: Default::default(),
+            drafts: Default::default(),
+            asset_events: Default::default(),
+            notifications: Default::default(),
             do_persist: false,
+            identity,
             scan_loops_started: 0,
             scan_loops_completed: 0,
+            last_syncs: Default::default(),
+            signing_sessions: Default::default(),
             interrupt_wait: false,
+            recent_payments: Default::default(),
         };
         state.insert_policy_asset();
         let state = Arc::new(Mutex::new(state));
-        let server = tiny_http::Server::http(self.config.addr)
-            .map_err(|_| Error::ServerStart(self.config.addr.to_string()))?;
+        let server = self.http_server()?;
 
-        // TODO, for some reasons, using the default number of threads (4) cause a request to be
-        // replied after 15 seconds, using 1 instead seems to not have that issue.
         let config = lwk_tiny_jrpc::Config::builder()
-            .with_num_threads(NonZeroU8::new(1).expect("static"))
+            .with_num_threads(NonZeroU8::new(consts::RPC_THREADS).expect("static"))
+            .with_auth_token(self.config.auth_token.clone())
             .build();
 
         let rpc = lwk_tiny_jrpc::JsonRpcServer::new(server, config, state.clone(), method_handler);
@@ -129,10 +173,8 @@ impl App {
                     string.lines().count()
                 );
 
-                let client = self.client()?;
-
                 for (n, line) in string.lines().enumerate() {
-                    self.apply_request(&client, line).map_err(|err| {
+                    Self::apply_request(&state, line).map_err(|err| {
                         Error::StartStateLoad(err.to_string(), n + 1, path.display().to_string())
                     })?
                 }
@@ -152,6 +194,8 @@ impl App {
         let scanning_interval = self.config.scanning_interval;
         let stop_interval = Duration::from_millis(100);
         let mut interval = Duration::ZERO; // Do not wait in the first scan loop
+        let registry_check_interval = self.config.registry_check_interval;
+        let mut last_registry_check: Option<Instant> = None;
         let scanning_handle = std::thread::spawn(move || 'scan: loop {
             // Sleep for scanning_interval, but check stop signal every stop_interval
             'stop: loop {
@@ -175,13 +219,30 @@ impl App {
                 s.interrupt_wait = false;
                 s.scan_loops_started += 1;
                 if let Ok(mut electrum_client) = s.config.electrum_client() {
-                    for (_name, wollet) in s.wollets.iter_mut() {
+                    let s = &mut *s;
+                    for (name, wollet) in s.wollets.iter_mut() {
                         // TODO: release lock when doing network calls
-                        let _ = full_scan_with_electrum_client(wollet, &mut electrum_client);
+                        let before = WolletSnapshot::new(wollet);
+                        let config = &s.config;
+                        match scan_wollet(wollet, &mut electrum_client, || config.electrum_client())
+                        {
+                            Ok(()) => {
+                                s.last_syncs.insert(name.clone(), now());
+                            }
+                            Err(e) => tracing::warn!("scan of wallet {name} failed: {e}"),
+                        }
+                        if let (Ok(before), Ok(after)) = (before, WolletSnapshot::new(wollet)) {
+                            s.notifications.wollet_changes(name, &before, &after, now());
+                        }
                     }
                 }
                 s.scan_loops_completed += 1;
             }
+
+            if last_registry_check.map_or(true, |t| t.elapsed() >= registry_check_interval) {
+                last_registry_check = Some(Instant::now());
+                check_registry(&state_scanning);
+            }
         });
         self.scanning_handle = Some(scanning_handle);
 
@@ -220,9 +281,53 @@ impl App {
         }
         Ok(())
     }
+}
 
-    fn client(&self) -> Result<Client, Error> {
-        Client::new(self.config.addr)
+/// Check the registry entries of the assets held by the loaded wallets, recording an event for
+/// every change and sending it to the webhook if configured
+fn check_registry(state: &Mutex<State>) {
+    let (held, registry_url, webhook_url) = match state.lock() {
+        Ok(s) => (
+            s.held_registry_assets(),
+            s.config.registry_url.clone(),
+            s.config.webhook_url.clone(),
+        ),
+        Err(_) => return,
+    };
+    if registry_url.is_empty() {
+        return;
+    }
+    for (asset_id, contract) in held {
+        // Do not hold the lock during network calls
+        let entry = match get_registry_entry(&registry_url, &asset_id) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!("Cannot check the registry entry of {asset_id}: {e}");
+                continue;
+            }
+        };
+        let timestamp = now();
+        let event = match state.lock() {
+            Ok(mut s) => {
+                let registry = entry.as_ref().map(|e| &e.contract);
+                let event = s
+                    .registry_entries
+                    .update(asset_id, &contract, registry, timestamp);
+                if let Some(event) = event.as_ref() {
+                    s.asset_events.push(event.clone());
+                }
+                event
+            }
+            Err(_) => return,
+        };
+        if let Some(event) = event {
+            tracing::warn!("Registry entry changed: {event:?}");
+            if let Some(url) = webhook_url.as_ref() {
+                if let Err(e) = post_webhook(url, &event) {
+                    tracing::warn!("Cannot post to webhook {url}: {e}");
+                }
+            }
+        }
     }
 }
 
@@ -265,12 +370,16 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             )
         }
         Method::Version => {
-            let network = state.lock()?.config.network.as_str().to_string();
+            let s = state.lock()?;
+            let network = s.config.network.as_str().to_string();
+            let secp = lwk_wollet::elements::secp256k1_zkp::Secp256k1::signing_only();
+            let identity = s.identity.map(|k| k.public_key(&secp).to_string());
             Response::result(
                 request.id,
                 serde_json::to_value(response::Version {
                     version: consts::APP_VERSION.into(),
                     network,
+                    identity,
                 })?,
             )
         }
@@ -280,7 +389,10 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             // TODO recognize different name same descriptor?
 
             let desc: WolletDescriptor = r.descriptor.parse()?;
-            let wollet = Wollet::with_fs_persist(s.config.network, desc, &s.config.datadir)?;
+            let mut wollet = Wollet::with_fs_persist(s.config.network, desc, &s.config.datadir)?;
+            if let Some(gap_limit) = r.gap_limit {
+                wollet.set_gap_limit(gap_limit)?;
+            }
             s.wollets.insert(&r.name, wollet)?;
 
             s.persist(&request)?;
@@ -299,6 +411,10 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let removed = s.wollets.remove(&r.name)?;
             s.tx_memos.remove(&r.name);
             s.addr_memos.remove(&r.name);
+            s.reserved_addrs.remove(&r.name);
+            s.verified_addrs.remove(&r.name);
+            s.drafts.remove_wollet(&r.name);
+            s.last_syncs.remove(&r.name);
             s.persist_all()?;
 
             Response::result(
@@ -306,7 +422,7 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 serde_json::to_value(response::WalletUnload {
                     unloaded: response::Wallet {
                         name: r.name,
-                        descriptor: removed.descriptor().to_string(),
+                        descriptor: removed.wollet_descriptor().to_string(),
                     },
                 })?,
             )
@@ -317,17 +433,45 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 .wollets
                 .iter()
                 .map(|(name, wollet)| response::Wallet {
-                    descriptor: wollet.descriptor().to_string(),
+                    descriptor: wollet.wollet_descriptor().to_string(),
                     name: name.clone(),
                 })
                 .collect();
             let r = response::WalletList { wallets };
             Response::result(request.id, serde_json::to_value(r)?)
         }
+        Method::WalletSyncStatus => {
+            let s = state.lock()?;
+            let wallets = s
+                .wollets
+                .iter()
+                .map(|(name, wollet)| {
+                    let tip = wollet.tip();
+                    response::WalletSyncInfo {
+                        name: name.clone(),
+                        tip_height: tip.height(),
+                        tip_hash: tip.hash().to_string(),
+                        tip_timestamp: tip.timestamp(),
+                        last_sync: s.last_syncs.get(name).copied(),
+                    }
+                })
+                .collect();
+            let r = response::WalletSyncStatus { wallets };
+            Response::result(request.id, serde_json::to_value(r)?)
+        }
         Method::SignerLoadSoftware => {
             let r: request::SignerLoadSoftware = serde_json::from_value(params)?;
             let mut s = state.lock()?;
-            let signer = AppSigner::new_sw(&r.mnemonic, s.config.is_mainnet(), r.persist)?;
+            let is_mainnet = s.config.is_mainnet();
+            let signer = match (r.mnemonic.is_empty(), r.mnemonic_file.as_deref()) {
+                (false, None) => AppSigner::new_sw(&r.mnemonic, is_mainnet, r.persist)?,
+                (true, Some(path)) => AppSigner::new_sw_file(path, is_mainnet, r.persist)?,
+                _ => {
+                    return Err(Error::Generic(
+                        "Specify either the mnemonic or the mnemonic file".into(),
+                    ))
+                }
+            };
             let resp: response::Signer = signer_response_from(&r.name, &signer)?;
             s.signers.insert(&r.name, signer)?;
             if r.persist {
@@ -345,6 +489,15 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             s.persist(&request)?;
             Response::result(request.id, serde_json::to_value(resp)?)
         }
+        Method::SignerLoadLedger => {
+            let r: request::SignerLoadLedger = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            let signer = AppSigner::new_ledger(r.emulator)?;
+            let resp: response::Signer = signer_response_from(&r.name, &signer)?;
+            s.signers.insert(&r.name, signer)?;
+            s.persist(&request)?;
+            Response::result(request.id, serde_json::to_value(resp)?)
+        }
         Method::SignerLoadExternal => {
             let r: request::SignerLoadExternal = serde_json::from_value(params)?;
             let mut s = state.lock()?;
@@ -356,11 +509,22 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             s.persist(&request)?;
             Response::result(request.id, serde_json::to_value(resp)?)
         }
+        Method::SignerLoadRemote => {
+            let r: request::SignerLoadRemote = serde_json::from_value(params)?;
+            // Contacting the remote server doesn't need the state lock
+            let signer = AppSigner::new_remote(r.url, r.remote_name, r.token)?;
+            let mut s = state.lock()?;
+            let resp: response::Signer = signer_response_from(&r.name, &signer)?;
+            s.signers.insert(&r.name, signer)?;
+            s.persist(&request)?;
+            Response::result(request.id, serde_json::to_value(resp)?)
+        }
         Method::SignerUnload => {
             let r: request::SignerUnload = serde_json::from_value(params)?;
             let mut s = state.lock()?;
             let removed = s.signers.remove(&r.name)?;
             let signer: response::Signer = signer_response_from(&r.name, &removed)?;
+            s.verified_addrs.remove_signer(&r.name);
             s.persist_all()?;
             Response::result(
                 request.id,
@@ -390,11 +554,24 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let r: request::WalletAddress = serde_json::from_value(params)?;
             let mut s = state.lock()?;
 
+            let index = match r.index {
+                Some(index) => index,
+                None => {
+                    // Skip the reserved addresses
+                    let mut index = s.wollets.get(&r.name)?.address(None)?.index();
+                    s.reserved_addrs.release_below(&r.name, index);
+                    while s.reserved_addrs.is_reserved(&r.name, index) {
+                        index += 1;
+                    }
+                    index
+                }
+            };
             let wollet = s.wollets.get_mut(&r.name)?;
-            let addr = wollet.address(r.index)?;
-            let definite_desc = wollet
-                .wollet_descriptor()
-                .definite_descriptor(lwk_wollet::Chain::External, addr.index())?;
+            let addr = if r.skip_used {
+                wollet.address_skip_used(Some(index))?
+            } else {
+                wollet.address(Some(index))?
+            };
 
             let text_qr = r
                 .with_text_qr
@@ -408,138 +585,397 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 })
                 .transpose()?;
 
-            if let Some(signer) = r.signer {
-                let signer = s.get_available_signer(&signer)?;
-                if let AnySigner::Jade(jade, _id) = signer {
-                    let fingerprint = signer.fingerprint()?;
-
-                    // Get the derivation paths for all signers
-                    let mut paths: Vec<Vec<u32>> = vec![];
-                    // Get the full path for the signer
-                    let mut full_path: Vec<u32> = vec![];
-                    definite_desc.for_each_key(|k| {
-                        if k.master_fingerprint() == fingerprint {
-                            if let Some(path) = k.full_derivation_path() {
-                                full_path = derivation_path_to_vec(&path);
-                            }
-                        }
-                        if let DescriptorPublicKey::XPub(x) = k.as_descriptor_public_key() {
-                            paths.push(derivation_path_to_vec(&x.derivation_path));
-                        }
-                        true
-                    });
-
-                    if full_path.is_empty() {
-                        return Err(Error::Generic("Signer is not in wallet".into()));
+            if let Some(signer_name) = r.signer {
+                let descriptor = wollet.descriptor().clone();
+                let signer = s.get_available_signer(&signer_name)?;
+                match signer.display_address(&descriptor, addr.index())? {
+                    Some(shown) if shown == addr.address().to_string() => {
+                        s.verified_addrs.set(&r.name, addr.index(), &signer_name);
+                        let params = request::WalletSetAddrVerified {
+                            name: r.name.clone(),
+                            index: addr.index(),
+                            signer: signer_name,
+                        };
+                        s.persist(&Request {
+                            method: Method::WalletSetAddrVerified.to_string(),
+                            params: Some(serde_json::to_value(params)?),
+                            ..request.clone()
+                        })?;
                     }
-                    let jade_addr = match paths.len() {
-                        0 => return Err(Error::Generic("Unsupported signer or descriptor".into())),
-                        1 => {
-                            // Single sig
-                            match definite_desc.desc_type() {
-                                DescriptorType::Wpkh => {
-                                    jade.get_receive_address_single(Variant::Wpkh, full_path)?
-                                }
-                                DescriptorType::ShWpkh => {
-                                    jade.get_receive_address_single(Variant::ShWpkh, full_path)?
-                                }
-                                _ => {
-                                    return Err(Error::Generic(
-                                        "Unsupported signer or descriptor".into(),
-                                    ))
-                                }
-                            }
-                        }
-                        _ => {
-                            // Multi sig
-                            jade.get_receive_address_multi(&r.name, paths)?
-                        }
-                    };
-                    if jade_addr != addr.address().to_string() {
+                    Some(_) => {
                         return Err(Error::Generic(
                             "Mismatching addresses between wallet and jade".into(),
-                        ));
+                        ))
+                    }
+                    None => {
+                        return Err(Error::Generic(
+                            "Cannot display address with software signer".into(),
+                        ))
                     }
-                } else {
-                    return Err(Error::Generic(
-                        "Cannot display address with software signer".into(),
-                    ));
                 }
             };
 
             let address = addr.address();
             let memos = s.addr_memos.for_wollet(&r.name);
             let memo = memos.get(address).cloned().unwrap_or_default();
+            let verified_by = s.verified_addrs.get(&r.name, addr.index()).cloned();
+            let mut result = serde_json::to_value(response::WalletAddress {
+                address: address.to_string(),
+                index: addr.index(),
+                memo,
+                text_qr,
+                uri_qr,
+                blinding_pubkey: address.blinding_pubkey.map(|k| k.to_string()),
+                verified: verified_by.is_some(),
+                verified_by,
+                signature: None,
+            })?;
+            s.sign_response(&request, &mut result)?;
+            Response::result(request.id, result)
+        }
+        Method::WalletAddressReserve => {
+            let r: request::WalletAddressReserve = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            if r.count == 0 {
+                return Err(Error::Generic("count must be at least 1".into()));
+            }
+            let wollet = s.wollets.get(&r.name)?;
+            let first_unused = wollet.address(None)?.index();
+            let gap_limit = wollet.gap_limit();
+            s.reserved_addrs.release_below(&r.name, first_unused);
+            let index = match r.index {
+                Some(index) => index,
+                None => first_unused.max(s.reserved_addrs.next(&r.name)),
+            };
+            let indexes = index
+                .checked_add(r.count)
+                .map(|end| index..end)
+                .ok_or_else(|| Error::Generic("Invalid count".into()))?;
+
+            // The scan stops `gap_limit` addresses after the last used one, the reserved addresses
+            // and the address handed out after them must be within that window to be seen. The
+            // window is not enforced while restoring the state, which must not fail.
+            let window_end = first_unused.saturating_add(gap_limit - 1);
+            if s.do_persist && indexes.end > window_end {
+                let available = window_end.saturating_sub(index);
+                return Err(Error::Generic(format!(
+                    "Cannot reserve addresses beyond the gap limit of the wallet ({gap_limit}), \
+                     at most {available} more can be reserved from index {index}"
+                )));
+            }
+
+            let wollet = s.wollets.get(&r.name)?;
+            let memos = s.addr_memos.for_wollet(&r.name);
+            let mut addresses = vec![];
+            for index in indexes.clone() {
+                let addr = wollet.address(Some(index))?;
+                let address = addr.address();
+                let verified_by = s.verified_addrs.get(&r.name, index).cloned();
+                addresses.push(response::WalletAddress {
+                    address: address.to_string(),
+                    index,
+                    memo: memos.get(address).cloned().unwrap_or_default(),
+                    text_qr: None,
+                    uri_qr: None,
+                    blinding_pubkey: address.blinding_pubkey.map(|k| k.to_string()),
+                    verified: verified_by.is_some(),
+                    verified_by,
+                    signature: None,
+                });
+            }
+            // The addresses before the first unused one are already covered by the scan
+            let reserved = indexes.filter(|i| *i >= first_unused);
+            s.reserved_addrs.reserve(&r.name, reserved);
+
+            // Persist the actual indexes, so that reloading the state reserves the same addresses
+            let params = request::WalletAddressReserve {
+                index: Some(index),
+                ..r
+            };
+            s.persist(&Request {
+                params: Some(serde_json::to_value(params)?),
+                ..request.clone()
+            })?;
+
             Response::result(
                 request.id,
-                serde_json::to_value(response::WalletAddress {
-                    address: address.to_string(),
-                    index: addr.index(),
-                    memo,
-                    text_qr,
-                    uri_qr,
-                })?,
+                serde_json::to_value(response::WalletAddressReserve { addresses })?,
+            )
+        }
+        Method::WalletAddresses => {
+            let r: request::WalletAddresses = serde_json::from_value(params)?;
+            let s = state.lock()?;
+            let wollet = s.wollets.get(&r.name)?;
+            let memos = s.addr_memos.for_wollet(&r.name);
+            let chain = if r.change {
+                Chain::Internal
+            } else {
+                Chain::External
+            };
+            let limit = r.limit.unwrap_or(u32::MAX);
+            let addresses = wollet
+                .addresses(chain, r.offset, limit)?
+                .into_iter()
+                .map(|a| response::AddressDetails {
+                    address: a.address.to_string(),
+                    index: a.wildcard_index,
+                    used: a.used,
+                    tx_count: a.tx_count,
+                    balance: a
+                        .balance
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v))
+                        .collect(),
+                    memo: memos.get(&a.address).cloned().unwrap_or_default(),
+                })
+                .collect();
+            Response::result(
+                request.id,
+                serde_json::to_value(response::WalletAddresses { addresses })?,
             )
         }
+        Method::WalletRecoveryScan => {
+            let r: request::WalletRecoveryScan = serde_json::from_value(params)?;
+            // Do not hold the lock while scanning
+            let config = state.lock()?.config.clone();
+            let signer = SwSigner::new(&r.mnemonic, config.is_mainnet())?;
+            let max_account = r.max_account.unwrap_or(consts::RECOVERY_MAX_ACCOUNT);
+            let mut electrum_client = config.electrum_client()?;
+            let mut schemes = vec![];
+            for scheme in DerivationScheme::recovery_schemes(config.is_mainnet(), max_account) {
+                let descriptor =
+                    singlesig_desc_with_scheme(&signer, &scheme, DescriptorBlindingKey::Slip77)?;
+                let mut wollet = Wollet::without_persist(config.network, descriptor.parse()?)?;
+                full_scan_with_electrum_client(&mut wollet, &mut electrum_client)?;
+                let tx_count = wollet.transactions()?.len();
+                let balance = wollet
+                    .balance()?
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect();
+                let kind = match scheme.script_variant {
+                    Singlesig::Wpkh => "wpkh",
+                    Singlesig::ShWpkh => "shwpkh",
+                };
+                schemes.push(response::RecoveryScheme {
+                    path: format!("m/{}", scheme.path()),
+                    kind: kind.to_string(),
+                    descriptor,
+                    has_history: tx_count > 0,
+                    tx_count,
+                    balance,
+                });
+            }
+            let r = response::WalletRecoveryScan { schemes };
+            Response::result(request.id, serde_json::to_value(r)?)
+        }
         Method::WalletBalance => {
             let r: request::WalletBalance = serde_json::from_value(params)?;
             let mut s = state.lock()?;
             let wollet = s.wollets.get_mut(&r.name)?;
-            let mut balance = wollet
-                .balance()?
+            let detailed = wollet.detailed_balance()?;
+            let by_asset = |f: fn(&lwk_wollet::Balance) -> u64| -> HashMap<String, i64> {
+                detailed
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), f(v) as i64))
+                    .collect()
+            };
+            let mut balance = by_asset(lwk_wollet::Balance::total);
+            let mut confirmed = by_asset(|b| b.confirmed);
+            let mut unconfirmed_incoming = by_asset(|b| b.unconfirmed_incoming);
+            let mut unconfirmed_outgoing = by_asset(|b| b.unconfirmed_outgoing);
+            let mut verified: HashMap<String, bool> = detailed
+                .keys()
+                .filter_map(|a| Some((a.to_string(), wollet.asset_info(a)?.verified)))
+                .collect();
+            let mut reissuance_tokens: HashMap<String, String> = wollet
+                .reissuance_tokens()?
                 .into_iter()
-                .map(|(k, v)| (k.to_string(), v as i64))
+                .map(|t| (t.token.to_string(), t.asset.to_string()))
                 .collect();
             if r.with_tickers {
                 balance = s.replace_id_with_ticker(balance);
+                confirmed = s.replace_id_with_ticker(confirmed);
+                unconfirmed_incoming = s.replace_id_with_ticker(unconfirmed_incoming);
+                unconfirmed_outgoing = s.replace_id_with_ticker(unconfirmed_outgoing);
+                verified = s.replace_id_with_ticker(verified);
+                reissuance_tokens = s
+                    .replace_id_with_ticker(reissuance_tokens)
+                    .into_iter()
+                    .map(|(token, asset)| (token, s.ticker_or_id(asset)))
+                    .collect();
             }
-            Response::result(
-                request.id,
-                serde_json::to_value(response::WalletBalance { balance })?,
-            )
+            let mut result = serde_json::to_value(response::WalletBalance {
+                balance,
+                confirmed,
+                unconfirmed_incoming,
+                unconfirmed_outgoing,
+                reissuance_tokens,
+                verified,
+                signature: None,
+            })?;
+            s.sign_response(&request, &mut result)?;
+            Response::result(request.id, result)
+        }
+        Method::WalletSummary => {
+            let r: request::WalletSummary = serde_json::from_value(params)?;
+            let s = state.lock()?;
+            let summary = s.wollets.get(&r.name)?.summary()?;
+            let by_asset = |m: BTreeMap<AssetId, u64>| -> HashMap<String, u64> {
+                m.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+            };
+            let r = response::WalletSummary {
+                txs: summary.txs,
+                utxos: summary.utxos,
+                used_addresses: summary.used_addresses,
+                first_activity: summary.first_activity,
+                last_activity: summary.last_activity,
+                received: by_asset(summary.received),
+                sent: by_asset(summary.sent),
+                tip_height: summary.tip_height,
+                tip_lag: s.notifications.block().saturating_sub(summary.tip_height),
+            };
+            Response::result(request.id, serde_json::to_value(r)?)
         }
         Method::WalletSendMany => {
             let r: request::WalletSendMany = serde_json::from_value(params)?;
+            let estimates = match (r.fee_rate, r.fee_target) {
+                (None, Some(_)) => {
+                    // Do not hold the lock while requesting the estimates
+                    let config = state.lock()?.config.clone();
+                    config.electrum_client()?.fee_estimates()?
+                }
+                _ => BTreeMap::new(),
+            };
             let mut s = state.lock()?;
-            let wollet: &mut Wollet = s.wollets.get_mut(&r.name)?;
+            // Make sure the wallet exists before deriving addresses of contacts
+            let _wollet = s.wollets.get(&r.name)?;
 
-            let recipients: Vec<_> = r
-                .addressees
-                .into_iter()
-                .map(unvalidated_addressee)
-                .collect();
-            let mut tx = wollet
-                .tx_builder()
+            let mut recipients = vec![];
+            let mut payees = vec![];
+            for a in r.addressees {
+                payees.push(payment_recipient(&a.address));
+                let address = s.resolve_contact(&a.address)?;
+                recipients.push(unvalidated_addressee(request::UnvalidatedAddressee {
+                    address,
+                    ..a
+                }));
+            }
+            // Replacing a draft pays the same recipients on purpose
+            let duplicate_window = match r.draft {
+                None => s.config.duplicate_payment_window,
+                Some(_) => None,
+            };
+            let payment = duplicate_window.map(|_| {
+                let policy_asset = s.config.network.policy_asset().to_string();
+                let mut payment: PaymentRecipients = recipients
+                    .iter()
+                    .zip(payees)
+                    .map(|(r, payee)| {
+                        let asset = match r.asset.as_str() {
+                            "" => policy_asset.clone(),
+                            asset => asset.to_string(),
+                        };
+                        (payee, r.satoshi, asset)
+                    })
+                    .collect();
+                payment.sort();
+                payment
+            });
+            if let (Some(window), Some(payment)) = (duplicate_window, payment.as_ref()) {
+                let ago = s
+                    .recent_payments
+                    .duplicate(payment, window.as_secs(), now());
+                if let (Some(ago), false) = (ago, r.force) {
+                    return Err(Error::DuplicatePayment(ago));
+                }
+            }
+            let replaced = match r.draft.as_deref() {
+                Some(draft_name) => {
+                    let draft = s.drafts.get(draft_name)?;
+                    if draft.wallet != r.name {
+                        return Err(Error::DraftOfOtherWallet(
+                            draft_name.to_string(),
+                            r.name.clone(),
+                        ));
+                    }
+                    Some(draft.pset.clone())
+                }
+                None => None,
+            };
+            let wollet: &mut Wollet = s.wollets.get_mut(&r.name)?;
+            let mut builder = wollet.tx_builder();
+            if let (None, Some(target)) = (r.fee_rate, r.fee_target) {
+                builder = builder.fee_target(target, &estimates);
+            }
+            if let Some(pset) = replaced.as_ref() {
+                builder = builder.reuse_change_of(pset);
+            }
+            let (mut tx, warnings) = builder
                 .set_unvalidated_recipients(&recipients)?
                 .fee_rate(r.fee_rate)
-                .finish()?;
+                .locktime(r.locktime.as_deref().map(parse_locktime).transpose()?)
+                .finish_with_warnings()?;
+            if let Some(payment) = payment {
+                s.record_payment(payment, now())?;
+            }
 
             add_contracts(&mut tx, s.assets.iter());
             Response::result(
                 request.id,
                 serde_json::to_value(response::Pset {
                     pset: tx.to_string(),
+                    warnings: warnings
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", "),
                 })?,
             )
         }
         Method::WalletDrain => {
             let r: request::WalletDrain = serde_json::from_value(params)?;
             let mut s = state.lock()?;
+            let _wollet = s.wollets.get(&r.name)?;
+            let address = Address::from_str(&s.resolve_contact(&r.address)?)?;
+            let payee = payment_recipient(&r.address);
             let wollet: &mut Wollet = s.wollets.get_mut(&r.name)?;
-
-            let address = Address::from_str(&r.address)?;
-            let mut tx = wollet
-                .tx_builder()
+            let mut builder = wollet.tx_builder();
+            for asset in r.assets.iter() {
+                builder = builder.drain_asset(AssetId::from_str(asset)?, address.clone());
+            }
+            let (mut tx, warnings) = builder
                 .drain_lbtc_wallet()
-                .drain_lbtc_to(address)
+                .drain_lbtc_to(address.clone())
                 .fee_rate(r.fee_rate)
-                .finish()?;
+                .finish_with_warnings()?;
+            if let Some(window) = s.config.duplicate_payment_window {
+                // The amounts are known only once the transaction is built
+                let script_pubkey = address.script_pubkey();
+                let payment: PaymentRecipients = pset_payment(&tx, s.wollets.get(&r.name)?)
+                    .into_iter()
+                    .filter(|(payee, _, _)| *payee == script_pubkey.to_hex())
+                    .map(|(_, satoshi, asset)| (payee.clone(), satoshi, asset))
+                    .collect();
+                let ago = s
+                    .recent_payments
+                    .duplicate(&payment, window.as_secs(), now());
+                if let (Some(ago), false) = (ago, r.force) {
+                    return Err(Error::DuplicatePayment(ago));
+                }
+                s.record_payment(payment, now())?;
+            }
 
             add_contracts(&mut tx, s.assets.iter());
             Response::result(
                 request.id,
                 serde_json::to_value(response::Pset {
                     pset: tx.to_string(),
+                    warnings: warnings
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", "),
                 })?,
             )
         }
@@ -580,6 +1016,16 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 .map_err(|e: InvalidBlindingKeyVariant| e.to_string())?;
 
             let mut keyorigin_xpubs = vec![];
+            if !r.signers.is_empty() {
+                let mut s = state.lock()?;
+                for name in &r.signers {
+                    let keyorigin_xpub = signer_keyorigin_xpub(&mut s, name, "bip87")?;
+                    keyorigin_xpubs.push(
+                        keyorigin_xpub_from_str(&keyorigin_xpub)
+                            .map_err(|e| Error::Generic(e.to_string()))?,
+                    );
+                }
+            }
             for keyorigin_xpub in r.keyorigin_xpubs {
                 keyorigin_xpubs.push(
                     keyorigin_xpub_from_str(&keyorigin_xpub)
@@ -602,33 +1048,15 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let r: request::SignerRegisterMultisig = serde_json::from_value(params)?;
             let mut s = state.lock()?;
 
-            let network = s.config.jade_network();
             let descriptor = s.wollets.get(&r.wallet)?.descriptor().clone();
             let signer = s.get_available_signer(&r.name)?;
-
-            if let AnySigner::Jade(jade, _id) = signer {
-                let descriptor: JadeDescriptor = (&descriptor).try_into()?;
-                jade.register_multisig(RegisterMultisigParams {
-                    network,
-                    multisig_name: r.wallet,
-                    descriptor,
-                })?;
-            }
+            signer.register_multisig(&r.wallet, &descriptor)?;
             Response::result(request.id, serde_json::to_value(response::Empty {})?)
         }
         Method::SignerXpub => {
             let r: request::SignerXpub = serde_json::from_value(params)?;
             let mut s = state.lock()?;
-            let is_mainnet = s.config.is_mainnet();
-
-            let signer = s.get_available_signer(&r.name)?;
-
-            let bip = r
-                .xpub_kind
-                .parse()
-                .map_err(|e: InvalidBipVariant| e.to_string())?;
-
-            let keyorigin_xpub = signer.keyorigin_xpub(bip, is_mainnet)?;
+            let keyorigin_xpub = signer_keyorigin_xpub(&mut s, &r.name, &r.xpub_kind)?;
             Response::result(
                 request.id,
                 serde_json::to_value(response::SignerXpub { keyorigin_xpub })?,
@@ -636,14 +1064,46 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
         }
         Method::SignerSign => {
             let r: request::SignerSign = serde_json::from_value(params)?;
-            let mut s = state.lock()?;
-
-            let signer = s.get_available_signer(&r.name)?;
-
             let mut pset =
                 PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
+            // A previous signer may have cached the sighashes, they must match the transaction
+            lwk_common::pset_check_cached_sighashes(&pset)?;
+
+            // The remote server is contacted without holding the state lock, it may be slow or
+            // forward the request back to this server
+            let (descriptor, remote) = {
+                let s = state.lock()?;
+                let descriptor = match r.wallet {
+                    Some(wallet) => Some(s.wollets.get(&wallet)?.descriptor().clone()),
+                    None => None,
+                };
+                (descriptor, s.signers.get(&r.name)?.remote().cloned())
+            };
+
+            match remote {
+                Some(remote) => {
+                    // The remote server doesn't know our wallets, check the change here
+                    if let Some(descriptor) = descriptor {
+                        lwk_common::pset_check_change(&pset, &descriptor, &remote.fingerprint())?;
+                    }
+                    remote.sign(&mut pset)?;
+                }
+                None => {
+                    let mut s = state.lock()?;
+                    let signer = s.get_available_signer(&r.name)?;
+                    match descriptor {
+                        Some(descriptor) => {
+                            signer.sign_with_change_check(&mut pset, &descriptor)?
+                        }
+                        None => signer.sign(&mut pset)?,
+                    };
+                }
+            }
 
-            signer.sign(&mut pset)?;
+            // Spare the following signers from computing the sighashes again
+            if let Err(e) = lwk_common::pset_cache_sighashes(&mut pset) {
+                tracing::warn!("cannot cache the sighashes: {e}");
+            }
 
             // TODO we may want to return other details such as if signatures have been added
 
@@ -651,6 +1111,68 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 request.id,
                 serde_json::to_value(response::Pset {
                     pset: pset.to_string(),
+                    warnings: String::new(),
+                })?,
+            )
+        }
+        Method::SignerSessionStart => {
+            let r: request::SignerSessionStart = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            let pset = PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
+            let fingerprint = s.signers.get(&r.name)?.fingerprint()?;
+            // The digests to sign are returned to the RPC client, which forwards them to the
+            // external signer and adds the signatures with `signer_session_complete`
+            let id = rand::random::<[u8; 16]>().to_hex();
+            let pending = PendingSignatures::new(id, pset, &fingerprint)?;
+            let session = AppSigningSession {
+                fingerprint,
+                pending: pending.clone(),
+                timestamp: now(),
+            };
+            s.persist(&Request {
+                method: Method::SignerSessionRecord.to_string(),
+                params: Some(serde_json::to_value(session.record())?),
+                ..request.clone()
+            })?;
+            let id = pending.id;
+            s.signing_sessions.insert(id.clone(), session);
+            let requests = pending
+                .requests
+                .into_iter()
+                .map(|r| response::SighashRequest {
+                    index: r.index as u32,
+                    sighash: r.sighash.as_ref().to_hex(),
+                    public_key: r.public_key.to_string(),
+                    fingerprint: r.key_source.0.to_string(),
+                    derivation_path: r.key_source.1.to_string(),
+                })
+                .collect();
+            Response::result(
+                request.id,
+                serde_json::to_value(response::SigningSession { id, requests })?,
+            )
+        }
+        Method::SignerSessionComplete => {
+            let r: request::SignerSessionComplete = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            let session = s.signing_sessions.get(&r.id, now())?;
+            let mut signatures = vec![];
+            for sig in r.signatures {
+                let public_key = PublicKey::from_str(&sig.public_key)
+                    .map_err(|e| Error::Generic(e.to_string()))?;
+                let signature = Signature::from_der(&Vec::<u8>::from_hex(&sig.signature)?)
+                    .map_err(|e| Error::Generic(e.to_string()))?;
+                signatures.push((sig.index as usize, public_key, signature));
+            }
+            let pset = session.pending.complete(&signatures)?;
+            // Remove the session only if all the signatures are valid
+            s.signing_sessions.remove(&r.id);
+            s.persist_all()?;
+            Response::result(
+                request.id,
+                serde_json::to_value(response::Pset {
+                    pset: pset.to_string(),
+                    warnings: String::new(),
                 })?,
             )
         }
@@ -662,10 +1184,13 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let mut pset =
                 PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
             let tx = wollet.finalize(&mut pset)?;
+            let payment = pset_payment(&pset, wollet);
             let electrum_client = s.config.electrum_client()?;
 
             if !r.dry_run {
                 electrum_client.broadcast(&tx)?;
+                s.record_payment(payment, now())?;
+                s.contacts_paid(&tx)?;
             }
 
             Response::result(
@@ -680,7 +1205,7 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let mut s = state.lock()?;
             let wollet = s.wollets.get_mut(&r.name)?;
 
-            let descriptor = wollet.descriptor().to_string();
+            let descriptor = wollet.wollet_descriptor().to_string();
             let type_ = match wollet.descriptor().descriptor.desc_type() {
                 DescriptorType::Wpkh => response::WalletType::Wpkh,
                 DescriptorType::ShWpkh => response::WalletType::ShWpkh,
@@ -732,29 +1257,49 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
         }
         Method::WalletCombine => {
             let r: request::WalletCombine = serde_json::from_value(params)?;
-            let mut s = state.lock()?;
-            let wollet = s.wollets.get_mut(&r.name)?;
+            let s = state.lock()?;
+            let wollet = s.wollets.get(&r.name)?;
 
             let mut psets = vec![];
             for pset in r.pset {
                 psets.push(PartiallySignedTransaction::from_str(&pset).map_err(|e| e.to_string())?);
             }
-            let pset = wollet.combine(&psets)?;
+            let combined = wollet.combine_and_finalize(&psets)?;
+            let mut warnings = vec![];
+            let missing_signatures = combined
+                .missing_signatures
+                .iter()
+                .map(|(vin, fingerprints)| response::InputMissingSignatures {
+                    vin: *vin as u32,
+                    missing_signatures_from: fingerprints
+                        .iter()
+                        .map(|f| response::SignerShortDetails {
+                            name: s.signers.name_from_fingerprint(f, &mut warnings),
+                            fingerprint: f.to_string(),
+                        })
+                        .collect(),
+                })
+                .collect();
             Response::result(
                 request.id,
                 serde_json::to_value(response::WalletCombine {
-                    pset: pset.to_string(),
+                    pset: combined.pset.to_string(),
+                    tx: combined.tx.as_ref().map(|tx| serialize(tx).to_hex()),
+                    missing_signatures,
                 })?,
             )
         }
         Method::WalletPsetDetails => {
             let r: request::WalletPsetDetails = serde_json::from_value(params)?;
             let mut s = state.lock()?;
+            let params = s.config.network.address_params();
             let wollet = s.wollets.get_mut(&r.name)?;
 
             let pset = PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
+            wollet.set_max_fee_basis_points(r.max_fee_basis_points);
             let details = wollet.get_details(&pset)?;
-            let mut warnings = vec![];
+            let mut warnings: Vec<String> =
+                details.warnings.iter().map(ToString::to_string).collect();
             let has_signatures_from = details
                 .fingerprints_has()
                 .iter()
@@ -763,6 +1308,7 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                     fingerprint: f.to_string(),
                 })
                 .collect();
+            let signing_order = s.signers.signing_order(&details.fingerprints_missing());
             let missing_signatures_from = details
                 .fingerprints_missing()
                 .iter()
@@ -771,6 +1317,55 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                     fingerprint: f.to_string(),
                 })
                 .collect();
+            let change_outputs = details.change_outputs();
+            let recipients = details
+                .outputs
+                .iter()
+                .zip(pset.outputs())
+                .enumerate()
+                .filter(|(_, (kind, _))| **kind == OutputKind::External)
+                .map(|(vout, (_, output))| {
+                    let proven = pset_output_asset_amount(output);
+                    if proven.is_none() && (output.asset.is_some() || output.amount.is_some()) {
+                        warnings.push(format!(
+                            "Asset and amount of output #{vout} are not proven, they are omitted"
+                        ));
+                    }
+                    response::PsetRecipient {
+                        vout: vout as u32,
+                        address: Address::from_script(
+                            &output.script_pubkey,
+                            output.blinding_key.map(|k| k.inner),
+                            params,
+                        )
+                        .map(|a| a.to_string()),
+                        asset: proven.map(|(asset, _)| asset.to_string()),
+                        satoshi: proven.map(|(_, satoshi)| satoshi),
+                    }
+                })
+                .collect();
+            let inputs = details
+                .sig_details
+                .iter()
+                .enumerate()
+                .map(|(vin, sigs)| {
+                    let mut signers = |keys: &[(PublicKey, KeySource)]| {
+                        let fingerprints: BTreeSet<_> = keys.iter().map(|(_, (f, _))| *f).collect();
+                        fingerprints
+                            .iter()
+                            .map(|f| response::SignerShortDetails {
+                                name: s.signers.name_from_fingerprint(f, &mut warnings),
+                                fingerprint: f.to_string(),
+                            })
+                            .collect()
+                    };
+                    response::PsetInputSigners {
+                        vin: vin as u32,
+                        has_signatures_from: signers(&sigs.has_signature),
+                        missing_signatures_from: signers(&sigs.missing_signature),
+                    }
+                })
+                .collect();
             let mut balance: HashMap<String, i64> = details
                 .balance
                 .balances
@@ -815,10 +1410,14 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 serde_json::to_value(response::WalletPsetDetails {
                     has_signatures_from,
                     missing_signatures_from,
+                    signing_order,
                     balance,
                     fee: details.balance.fee,
                     issuances,
                     reissuances,
+                    change_outputs,
+                    recipients,
+                    inputs,
                     warnings: warnings.join(", "),
                 })?,
             )
@@ -827,20 +1426,95 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let r: request::WalletUtxos = serde_json::from_value(params)?;
             let mut s = state.lock()?;
             let wollet = s.wollets.get_mut(&r.name)?;
-            let utxos: Vec<response::Utxo> = wollet.utxos()?.iter().map(convert_utxo).collect();
+            let utxos: Vec<response::Utxo> = wollet
+                .utxos()?
+                .iter()
+                .map(|u| convert_utxo(u, wollet.is_utxo_locked(&u.outpoint)))
+                .collect();
             Response::result(
                 request.id,
                 serde_json::to_value(response::WalletUtxos { utxos })?,
             )
         }
+        Method::WalletUtxosExport => {
+            let r: request::WalletUtxosExport = serde_json::from_value(params)?;
+            let (config, utxos, txs) = {
+                let s = state.lock()?;
+                let wollet = s.wollets.get(&r.name)?;
+                let mut utxos = vec![];
+                let mut txs = BTreeMap::new();
+                for u in wollet.utxos()? {
+                    let tx = wollet
+                        .transaction(&u.outpoint.txid)?
+                        .ok_or_else(|| format!("missing transaction {}", u.outpoint.txid))?
+                        .tx;
+                    let txout = tx
+                        .output
+                        .get(u.outpoint.vout as usize)
+                        .ok_or_else(|| format!("missing output {}", u.outpoint))?;
+                    utxos.push(response::ExportedUtxo {
+                        txid: u.outpoint.txid.to_string(),
+                        vout: u.outpoint.vout,
+                        height: u.height,
+                        script_pubkey: u.script_pubkey.to_hex(),
+                        witness_utxo: serialize(txout).to_hex(),
+                        asset: u.unblinded.asset.to_string(),
+                        value: u.unblinded.value,
+                        asset_bf: u.unblinded.asset_bf.to_string(),
+                        value_bf: u.unblinded.value_bf.to_string(),
+                    });
+                    txs.insert(u.outpoint.txid, (u.height, tx));
+                }
+                (s.config.clone(), utxos, txs)
+            };
+
+            // Do not hold the lock while fetching the proofs
+            let electrum_client = config.electrum_client()?;
+            let mut pruned_funds = vec![];
+            for (txid, (height, tx)) in txs {
+                let Some(height) = height else {
+                    continue;
+                };
+                let proof = electrum_client.txout_proof(&txid, height)?;
+                pruned_funds.push(response::PrunedFunds {
+                    txid: txid.to_string(),
+                    rawtransaction: serialize(&tx).to_hex(),
+                    txoutproof: proof.to_hex(),
+                });
+            }
+            Response::result(
+                request.id,
+                serde_json::to_value(response::WalletUtxosExport {
+                    utxos,
+                    pruned_funds,
+                })?,
+            )
+        }
         Method::WalletTxs => {
             let r: request::WalletTxs = serde_json::from_value(params)?;
-            let mut s = state.lock()?;
+            let s = state.lock()?;
             let explorer_url = s.config.explorer_url.clone();
             let memos = s.tx_memos.for_wollet(&r.name);
-            let wollet = s.wollets.get_mut(&r.name)?;
+            let wollet = s.wollets.get(&r.name)?;
+            // Transactions between loaded wallets are self-transfers
+            let accounts: Vec<_> = s
+                .wollets
+                .iter()
+                .filter(|(name, _)| **name != r.name)
+                .map(|(_, w)| w)
+                .collect();
+            let filter = TxFilter {
+                asset: r.asset.as_deref().map(AssetId::from_str).transpose()?,
+                min_height: r.min_height,
+                max_height: r.max_height,
+                direction: r.direction.map(|d| match d {
+                    request::TxDirection::Incoming => TxDirection::Incoming,
+                    request::TxDirection::Outgoing => TxDirection::Outgoing,
+                }),
+            };
+            let limit = r.limit.unwrap_or(usize::MAX);
             let mut txs: Vec<response::Tx> = wollet
-                .transactions()?
+                .transactions_paginated_with_accounts(r.offset, limit, &filter, &accounts)?
                 .iter()
                 .map(|tx| convert_tx(tx, &explorer_url, &memos))
                 .collect();
@@ -869,6 +1543,61 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let tx = serialize(&tx).to_hex();
             Response::result(request.id, serde_json::to_value(response::WalletTx { tx })?)
         }
+        Method::WalletUtxoLock | Method::WalletUtxoUnlock => {
+            let r: request::WalletUtxoLock = serde_json::from_value(params)?;
+            let txid = Txid::from_str(&r.txid).map_err(|e| Error::Generic(e.to_string()))?;
+            let outpoint = OutPoint::new(txid, r.vout);
+            let mut s = state.lock()?;
+            let wollet = s.wollets.get_mut(&r.name)?;
+            // The lock is persisted by the wallet persister
+            if method == Method::WalletUtxoLock {
+                wollet.lock_utxo(outpoint)?;
+            } else {
+                wollet.unlock_utxo(outpoint)?;
+            }
+            Response::result(request.id, serde_json::to_value(response::Empty {})?)
+        }
+        Method::WalletSetLabel => {
+            let r: request::WalletSetLabel = serde_json::from_value(params)?;
+            let item = label_ref(&r.type_, &r.ref_)?;
+            let mut s = state.lock()?;
+            let wollet = s.wollets.get_mut(&r.name)?;
+            // Labels are persisted by the wallet persister
+            match r.label {
+                Some(label) => wollet.set_label(item, &label)?,
+                None => wollet.remove_label(&item)?,
+            }
+            Response::result(request.id, serde_json::to_value(response::Empty {})?)
+        }
+        Method::WalletGetLabel => {
+            let r: request::WalletGetLabel = serde_json::from_value(params)?;
+            let item = label_ref(&r.type_, &r.ref_)?;
+            let s = state.lock()?;
+            let wollet = s.wollets.get(&r.name)?;
+            let label = wollet.label(&item).map(ToString::to_string);
+            Response::result(
+                request.id,
+                serde_json::to_value(response::WalletLabel { label })?,
+            )
+        }
+        Method::WalletExportLabels => {
+            let r: request::WalletExportLabels = serde_json::from_value(params)?;
+            let s = state.lock()?;
+            let labels = s.wollets.get(&r.name)?.export_labels();
+            Response::result(
+                request.id,
+                serde_json::to_value(response::WalletExportLabels { labels })?,
+            )
+        }
+        Method::WalletImportLabels => {
+            let r: request::WalletImportLabels = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            let imported = s.wollets.get_mut(&r.name)?.import_labels(&r.labels)?;
+            Response::result(
+                request.id,
+                serde_json::to_value(response::WalletImportLabels { imported })?,
+            )
+        }
         Method::WalletSetTxMemo => {
             let r: request::WalletSetTxMemo = serde_json::from_value(params)?;
             let mut s = state.lock()?;
@@ -891,10 +1620,59 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             s.persist(&request)?;
             Response::result(request.id, serde_json::to_value(response::Empty {})?)
         }
+        Method::WalletSetAddrVerified => {
+            let r: request::WalletSetAddrVerified = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            // Only `wallet_address` records a verification, after the signer displayed the address
+            if s.do_persist {
+                return Err(Error::StartupOnlyMethod(request.method));
+            }
+            // Make sure the wallet exists
+            let _wollet = s.wollets.get(&r.name)?;
+            s.verified_addrs.set(&r.name, r.index, &r.signer);
+            s.persist(&request)?;
+            Response::result(request.id, serde_json::to_value(response::Empty {})?)
+        }
+        Method::SignerSessionRecord => {
+            let r: request::SignerSessionRecord = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            // Only starting a session records it
+            if s.do_persist {
+                return Err(Error::StartupOnlyMethod(request.method));
+            }
+            let fingerprint =
+                Fingerprint::from_str(&r.fingerprint).map_err(|e| Error::Generic(e.to_string()))?;
+            let pset = PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
+            let session = AppSigningSession {
+                pending: PendingSignatures::new(r.id.clone(), pset, &fingerprint)?,
+                fingerprint,
+                timestamp: r.timestamp,
+            };
+            s.signing_sessions.restore(r.id, session, now());
+            Response::result(request.id, serde_json::to_value(response::Empty {})?)
+        }
+        Method::PaymentRecord => {
+            let r: request::PaymentRecord = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            // Only building and broadcasting payments records them
+            if s.do_persist {
+                return Err(Error::StartupOnlyMethod(request.method));
+            }
+            if let Some(window) = s.config.duplicate_payment_window {
+                let (recipients, timestamp) = state::payment_from_record(r);
+                s.recent_payments
+                    .insert(recipients, timestamp, window.as_secs());
+            }
+            Response::result(request.id, serde_json::to_value(response::Empty {})?)
+        }
         Method::WalletIssue => {
             let r: request::WalletIssue = serde_json::from_value(params)?;
             let mut s = state.lock()?;
             let wollet = s.wollets.get_mut(&r.name)?;
+            let contract = r
+                .contract
+                .map(|c| lwk_wollet::Contract::from_str(&c))
+                .transpose()?;
             let tx = wollet
                 .tx_builder()
                 .issue_asset(
@@ -902,16 +1680,36 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                     r.address_asset.map(|a| Address::from_str(&a)).transpose()?,
                     r.satoshi_token,
                     r.address_token.map(|a| Address::from_str(&a)).transpose()?,
-                    r.contract
-                        .map(|c| lwk_wollet::Contract::from_str(&c))
-                        .transpose()?,
+                    contract.clone(),
                 )?
                 .fee_rate(r.fee_rate)
+                .locktime(r.locktime.as_deref().map(parse_locktime).transpose()?)
                 .finish()?;
+            let input = tx
+                .inputs()
+                .iter()
+                .find(|i| i.has_issuance())
+                .ok_or_else(|| Error::Generic("Missing issuance input".to_string()))?;
+            let (asset_id, token_id) = input.issuance_ids();
+            let issuance_prevout =
+                format!("{}:{}", input.previous_txid, input.previous_output_index);
+            let contract_hash = contract
+                .as_ref()
+                .map(|c| c.contract_hash())
+                .transpose()?
+                .map(|h| h.to_string());
             Response::result(
                 request.id,
-                serde_json::to_value(response::Pset {
+                serde_json::to_value(response::WalletIssue {
                     pset: tx.to_string(),
+                    warnings: String::new(),
+                    asset_id: asset_id.to_string(),
+                    token_id: token_id.to_string(),
+                    issuance_prevout,
+                    contract_hash,
+                    contract: contract
+                        .map(|c| serde_json::from_value(serde_json::to_value(c)?))
+                        .transpose()?,
                 })?,
             )
         }
@@ -938,6 +1736,7 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 request.id,
                 serde_json::to_value(response::Pset {
                     pset: pset.to_string(),
+                    warnings: String::new(),
                 })?,
             )
         }
@@ -947,17 +1746,78 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let asset_id = AssetId::from_str(&r.asset)?;
             let wollet = s.wollets.get_mut(&r.name)?;
 
-            let mut pset = wollet
+            let (mut pset, warnings) = wollet
                 .tx_builder()
                 .add_burn(r.satoshi_asset, asset_id)?
                 .fee_rate(r.fee_rate)
-                .finish()?;
+                .finish_with_warnings()?;
 
             add_contracts(&mut pset, s.assets.iter());
             Response::result(
                 request.id,
                 serde_json::to_value(response::Pset {
                     pset: pset.to_string(),
+                    warnings: warnings
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                })?,
+            )
+        }
+        Method::WalletPegout => {
+            let r: request::WalletPegout = serde_json::from_value(params)?;
+            let s = state.lock()?;
+            let wollet = s.wollets.get(&r.name)?;
+            let address = lwk_wollet::bitcoin::Address::from_str(&r.mainchain_address)
+                .map_err(|e| Error::Generic(e.to_string()))?;
+            let pset = wollet
+                .tx_builder()
+                .add_pegout(&address, r.satoshi)?
+                .fee_rate(r.fee_rate)
+                .finish()?;
+            Response::result(
+                request.id,
+                serde_json::to_value(response::Pset {
+                    pset: pset.to_string(),
+                    warnings: String::new(),
+                })?,
+            )
+        }
+        Method::WalletPeginAddress => {
+            let r: request::WalletPeginAddress = serde_json::from_value(params)?;
+            let s = state.lock()?;
+            let wollet = s.wollets.get(&r.name)?;
+            let fedpegscript = ScriptBuf::from_bytes(Vec::<u8>::from_hex(&r.fedpegscript)?);
+            let pegin = wollet.pegin_address(&fedpegscript, r.index)?;
+            Response::result(
+                request.id,
+                serde_json::to_value(response::WalletPeginAddress {
+                    mainchain_address: pegin.mainchain_address.to_string(),
+                    claim_script: pegin.claim_script.to_hex(),
+                    index: pegin.index,
+                })?,
+            )
+        }
+        Method::WalletClaimPegin => {
+            let r: request::WalletClaimPegin = serde_json::from_value(params)?;
+            let s = state.lock()?;
+            let wollet = s.wollets.get(&r.name)?;
+            let fedpegscript = ScriptBuf::from_bytes(Vec::<u8>::from_hex(&r.fedpegscript)?);
+            let mainchain_tx =
+                lwk_wollet::bitcoin::consensus::deserialize(&Vec::<u8>::from_hex(&r.mainchain_tx)?)
+                    .map_err(|e| Error::Generic(e.to_string()))?;
+            let txout_proof = Vec::<u8>::from_hex(&r.txout_proof)?;
+            let pset = wollet
+                .tx_builder()
+                .claim_pegin(fedpegscript, mainchain_tx, txout_proof)
+                .fee_rate(r.fee_rate)
+                .finish()?;
+            Response::result(
+                request.id,
+                serde_json::to_value(response::Pset {
+                    pset: pset.to_string(),
+                    warnings: String::new(),
                 })?,
             )
         }
@@ -977,17 +1837,49 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
         }
         Method::AssetDetails => {
             let r: request::AssetDetails = serde_json::from_value(params)?;
-            let s = state.lock()?;
             let asset_id = lwk_wollet::elements::AssetId::from_str(&r.asset_id)
                 .map_err(|e| Error::Generic(e.to_string()))?;
-            let asset = s.get_asset(&asset_id)?;
-            Response::result(
-                request.id,
-                serde_json::to_value(response::AssetDetails {
-                    name: asset.name(),
-                    ticker: asset.ticker(),
-                })?,
-            )
+            let (err, config) = {
+                let s = state.lock()?;
+                let err = match s.get_asset(&asset_id) {
+                    Ok(asset) => {
+                        let details = response::AssetDetails {
+                            name: asset.name(),
+                            ticker: asset.ticker(),
+                            precision: asset.precision(),
+                            domain: asset.domain(),
+                        };
+                        return Ok(Response::result(request.id, serde_json::to_value(details)?));
+                    }
+                    Err(e) => e,
+                };
+                // Not inserted, use the registry info cached by the wallets or fetch it
+                let cached = s
+                    .wollets
+                    .iter()
+                    .find_map(|(_, w)| w.asset_info(&asset_id).cloned());
+                match cached {
+                    Some(info) => {
+                        let details = asset_details_from_info(info);
+                        return Ok(Response::result(request.id, serde_json::to_value(details)?));
+                    }
+                    None if s.config.registry_url.is_empty() => return Err(err),
+                    None => (err, s.config.clone()),
+                }
+            };
+
+            // Fetch without holding the lock, the requests may take a while
+            let client = config.electrum_client()?;
+            let info = fetch_asset_info(&client, &config.registry_url, &asset_id)?.ok_or(err)?;
+
+            let mut s = state.lock()?;
+            for (_, wollet) in s.wollets.iter_mut() {
+                if wollet.assets_without_info()?.contains(&asset_id) {
+                    wollet.set_asset_info(asset_id, info.clone())?;
+                }
+            }
+            let details = asset_details_from_info(info);
+            Response::result(request.id, serde_json::to_value(details)?)
         }
         Method::AssetList => {
             let s = state.lock()?;
@@ -1003,6 +1895,13 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let r = response::AssetList { assets };
             Response::result(request.id, serde_json::to_value(r)?)
         }
+        Method::AssetEvents => {
+            let s = state.lock()?;
+            let r = response::AssetEvents {
+                events: s.asset_events.clone(),
+            };
+            Response::result(request.id, serde_json::to_value(r)?)
+        }
         Method::AssetInsert => {
             let r: request::AssetInsert = serde_json::from_value(params)?;
             let mut s = state.lock()?;
@@ -1081,6 +1980,19 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             scan(&state)?;
             Response::result(request.id, serde_json::to_value(response::Empty {})?)
         }
+        Method::Subscribe => {
+            let r: request::Subscribe = serde_json::from_value(params)?;
+            let r = subscribe(&state, &r)?;
+            Response::result(request.id, serde_json::to_value(r)?)
+        }
+        Method::FeeRates => {
+            let config = state.lock()?.config.clone();
+            let estimates = config.electrum_client()?.fee_estimates()?;
+            Response::result(
+                request.id,
+                serde_json::to_value(response::FeeRates { estimates })?,
+            )
+        }
         Method::Stop => {
             return Err(Error::Stop);
         }
@@ -1088,20 +2000,40 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
             let r: request::AssetPublish = serde_json::from_value(params)?;
             let asset_id =
                 AssetId::from_str(&r.asset_id).map_err(|e| Error::Generic(e.to_string()))?;
-            let s = state.lock()?;
-            let asset = s.get_asset(&asset_id)?;
-            if let AppAsset::RegistryAsset(asset) = asset {
-                let client = reqwest::blocking::Client::new();
-                let url = &s.config.registry_url;
-                let contract = asset.contract();
-                let data = serde_json::json!({"asset_id": asset_id, "contract": contract});
-                tracing::debug!("posting {data:?} as json to {url} ");
-                let response = client.post(url).json(&data).send()?;
-                let mut result = response.text()?;
-                if result.contains("failed verifying linked entity") {
-                    let domain = contract.entity.domain();
-                    result = format!("https://{domain}/.well-known/liquid-asset-proof-{asset_id} must contain the following 'Authorize linking the domain name {domain} to the Liquid asset {asset_id}'");
-                }
+            // The issuer domain and the registry are contacted without holding the state lock,
+            // a slow server must not block the other requests
+            let (contract, network, registry_url) = {
+                let s = state.lock()?;
+                let contract = match s.get_asset(&asset_id)? {
+                    AppAsset::RegistryAsset(asset) => Some(asset.contract().clone()),
+                    _ => None,
+                };
+                (contract, s.config.network, s.config.registry_url.clone())
+            };
+            if let Some(contract) = contract {
+                let proof_missing = format!(
+                    "{} must contain the following '{}'",
+                    contract.domain_proof_url(&asset_id),
+                    contract.domain_proof(&asset_id)
+                );
+
+                // The registry on regtest is a local instance which does not verify the domain
+                let is_regtest = matches!(network, ElementsNetwork::ElementsRegtest { .. });
+                let result = if !is_regtest && contract.verify_domain_proof(&asset_id).is_err() {
+                    proof_missing
+                } else {
+                    let client = reqwest::blocking::Client::new();
+                    let url = &registry_url;
+                    let data = serde_json::json!({"asset_id": asset_id, "contract": contract});
+                    tracing::debug!("posting {data:?} as json to {url} ");
+                    let response = client.post(url).json(&data).send()?;
+                    let result = response.text()?;
+                    if result.contains("failed verifying linked entity") {
+                        proof_missing
+                    } else {
+                        result
+                    }
+                };
                 Response::result(
                     request.id,
                     serde_json::to_value(response::AssetPublish {
@@ -1115,11 +2047,266 @@ fn inner_method_handler(request: Request, state: Arc<Mutex<State>>) -> Result<Re
                 ));
             }
         }
+        Method::ContactAdd => {
+            let r: request::ContactAdd = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            let network = s.config.network;
+            let contact = match (r.address, r.descriptor) {
+                (Some(address), None) => {
+                    let address = Address::from_str(&address)?;
+                    if address.params != network.address_params() {
+                        return Err(Error::Generic(format!(
+                            "Address {address} is not for network {}",
+                            network.as_str()
+                        )));
+                    }
+                    AppContact::Address(address)
+                }
+                (None, Some(descriptor)) => {
+                    let descriptor: WolletDescriptor = descriptor.parse()?;
+                    if !is_descriptor_for_network(&descriptor, s.config.is_mainnet()) {
+                        return Err(Error::Generic(format!(
+                            "Descriptor keys are not for network {}",
+                            network.as_str()
+                        )));
+                    }
+                    AppContact::Descriptor {
+                        descriptor,
+                        next_index: r.index.unwrap_or(0),
+                    }
+                }
+                _ => {
+                    return Err(Error::Generic(
+                        "Specify either an address or a descriptor".into(),
+                    ))
+                }
+            };
+            let result = contact.response(&r.name);
+            s.contacts.insert(&r.name, contact)?;
+            s.persist(&request)?;
+            Response::result(request.id, serde_json::to_value(result)?)
+        }
+        Method::ContactList => {
+            let s = state.lock()?;
+            let contacts = s
+                .contacts
+                .iter()
+                .map(|(name, contact)| contact.response(name))
+                .collect();
+            let r = response::ContactList { contacts };
+            Response::result(request.id, serde_json::to_value(r)?)
+        }
+        Method::ContactRemove => {
+            let r: request::ContactRemove = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            s.contacts.remove(&r.name)?;
+            s.persist_all()?;
+            Response::result(request.id, serde_json::to_value(response::Empty {})?)
+        }
+        Method::DraftSave => {
+            let r: request::DraftSave = serde_json::from_value(params)?;
+            let pset = PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
+            let mut s = state.lock()?;
+            // Make sure the wallet exists
+            let _wollet = s.wollets.get(&r.wallet)?;
+            let timestamp = r.timestamp.unwrap_or_else(now);
+            let draft = AppDraft {
+                pset,
+                wallet: r.wallet,
+                creator: r.creator,
+                timestamp,
+            };
+            let info = draft.info(&r.name);
+            // Persist the time of the save, which is kept when the state is loaded again
+            let persisted = Request {
+                jsonrpc: "2.0".into(),
+                id: None,
+                method: Method::DraftSave.to_string(),
+                params: Some(serde_json::to_value(draft.request(&r.name, r.replace))?),
+            };
+            s.drafts.insert(&r.name, draft, r.replace)?;
+            s.persist(&persisted)?;
+            Response::result(request.id, serde_json::to_value(info)?)
+        }
+        Method::DraftList => {
+            let r: request::DraftList = serde_json::from_value(params)?;
+            let s = state.lock()?;
+            let drafts = s
+                .drafts
+                .iter()
+                .filter(|(_, d)| r.wallet.as_ref().map_or(true, |w| &d.wallet == w))
+                .map(|(name, d)| d.info(name))
+                .collect();
+            let r = response::DraftList { drafts };
+            Response::result(request.id, serde_json::to_value(r)?)
+        }
+        Method::DraftLoad => {
+            let r: request::DraftLoad = serde_json::from_value(params)?;
+            let s = state.lock()?;
+            let draft = s.drafts.get(&r.name)?.response(&r.name);
+            Response::result(request.id, serde_json::to_value(draft)?)
+        }
+        Method::DraftDelete => {
+            let r: request::DraftLoad = serde_json::from_value(params)?;
+            let mut s = state.lock()?;
+            s.drafts.remove(&r.name)?;
+            s.persist_all()?;
+            Response::result(request.id, serde_json::to_value(response::Empty {})?)
+        }
+        #[cfg(feature = "nostr")]
+        Method::PsetSendNostr => {
+            let r: request::PsetSendNostr = serde_json::from_value(params)?;
+            let pset = PartiallySignedTransaction::from_str(&r.pset).map_err(|e| e.to_string())?;
+            let recipient = r
+                .recipient
+                .parse()
+                .map_err(|e| Error::Generic(format!("Invalid Nostr public key: {e}")))?;
+            let (secret_key, timeout) = {
+                let s = state.lock()?;
+                (
+                    s.signers.get(&r.signer)?.nostr_secret_key()?,
+                    s.config.timeout,
+                )
+            };
+            // Don't hold the lock while talking to the relays
+            let event_id =
+                nostr::send_pset(&secret_key, &recipient, &pset, &r.relays, now(), timeout)?;
+            Response::result(
+                request.id,
+                serde_json::to_value(response::PsetSendNostr {
+                    sender: nostr::public_key(&secret_key).to_string(),
+                    event_id,
+                })?,
+            )
+        }
+        #[cfg(feature = "nostr")]
+        Method::PsetInbox => {
+            let r: request::PsetInbox = serde_json::from_value(params)?;
+            let senders = r
+                .senders
+                .iter()
+                .map(|s| s.parse())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| Error::Generic(format!("Invalid Nostr public key: {e}")))?;
+            let (secret_key, timeout) = {
+                let s = state.lock()?;
+                (
+                    s.signers.get(&r.signer)?.nostr_secret_key()?,
+                    s.config.timeout,
+                )
+            };
+            let psets = nostr::inbox(&secret_key, &senders, &r.relays, r.since, timeout)?
+                .into_iter()
+                .map(|p| response::NostrPset {
+                    sender: p.sender.to_string(),
+                    event_id: p.event_id,
+                    created_at: p.created_at,
+                    pset: p.pset.to_string(),
+                })
+                .collect();
+            Response::result(
+                request.id,
+                serde_json::to_value(response::PsetInbox {
+                    pubkey: nostr::public_key(&secret_key).to_string(),
+                    psets,
+                })?,
+            )
+        }
+        #[cfg(not(feature = "nostr"))]
+        Method::PsetSendNostr | Method::PsetInbox => return Err(Error::FeatNostrDisabled),
     };
     Ok(response)
 }
 
+/// Whether the extended keys in the descriptor are for mainnet or for testnet, according to
+/// `is_mainnet`
+fn is_descriptor_for_network(descriptor: &WolletDescriptor, is_mainnet: bool) -> bool {
+    let is_for_network = |network| (network == lwk_wollet::bitcoin::Network::Bitcoin) == is_mainnet;
+    descriptor.descriptor().for_each_key(|k| match k {
+        DescriptorPublicKey::XPub(x) => is_for_network(x.xkey.network),
+        DescriptorPublicKey::MultiXPub(x) => is_for_network(x.xkey.network),
+        DescriptorPublicKey::Single(_) => true,
+    })
+}
+
+/// The wallet item a label refers to, from its BIP329 type and reference
+fn label_ref(type_: &str, ref_: &str) -> Result<LabelRef, Error> {
+    LabelRef::from_type_ref(type_, ref_)?
+        .ok_or_else(|| Error::Generic(format!("Unsupported label type \"{type_}\"")))
+}
+
+/// Parse a locktime in the form "height:<block height>" or "time:<unix timestamp>"
+fn parse_locktime(s: &str) -> Result<LockTime, Error> {
+    let err = || {
+        Error::Generic(format!(
+            "Invalid locktime \"{s}\", expected \"height:<block height>\" or \"time:<unix timestamp>\""
+        ))
+    };
+    let (kind, value) = s.split_once(':').ok_or_else(err)?;
+    let value: u32 = value.parse().map_err(|_| err())?;
+    match kind {
+        "height" => LockTime::from_height(value),
+        "time" => LockTime::from_time(value),
+        _ => return Err(err()),
+    }
+    .map_err(|e| Error::Generic(format!("Invalid locktime \"{s}\": {e}")))
+}
+
+/// Seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Wait up to `timeout` for notifications more recent than `since`
+fn subscribe(
+    state: &Arc<Mutex<State>>,
+    r: &request::Subscribe,
+) -> Result<response::Subscribe, Error> {
+    let timeout = Duration::from_secs(r.timeout).min(consts::MAX_SUBSCRIBE_TIMEOUT);
+    let start = Instant::now();
+    loop {
+        {
+            let s = state.lock()?;
+            if let Some(name) = r.wallet.as_ref() {
+                // Fail if the wallet has been unloaded while waiting
+                let _wollet = s.wollets.get(name)?;
+            }
+            let notifications = s.notifications.since(r.since, r.wallet.as_deref());
+            if !notifications.is_empty() || start.elapsed() >= timeout {
+                return Ok(response::Subscribe {
+                    notifications,
+                    last_id: s.notifications.last_id(),
+                });
+            }
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
+
+/// Scan `wollet` applying the progress as it goes, so that a scan failing midway keeps the
+/// transactions already downloaded
+///
+/// If the scan fails, for instance because the server stopped responding and the request timed
+/// out, the connection is replaced with the one returned by `reconnect` and the scan retried
+/// once, resuming from the progress kept.
+fn scan_wollet<B: BlockchainBackend>(
+    wollet: &mut Wollet,
+    client: &mut B,
+    reconnect: impl FnOnce() -> Result<B, Error>,
+) -> Result<(), Error> {
+    if let Err(e) = client.full_scan_chunked(wollet, consts::SCAN_MEMORY_BUDGET) {
+        tracing::info!("scan failed: {e}, retrying with a new connection");
+        *client = reconnect()?;
+        client.full_scan_chunked(wollet, consts::SCAN_MEMORY_BUDGET)?;
+    }
+    Ok(())
+}
+
 fn scan(state: &Arc<Mutex<State>>) -> Result<(), Error> {
+    let start = Instant::now();
     let required_scan_loops = {
         let mut s = state.lock()?;
         s.interrupt_wait = true;
@@ -1134,11 +2321,43 @@ fn scan(state: &Arc<Mutex<State>>) -> Result<(), Error> {
         if current_scan_loops > required_scan_loops {
             break;
         }
-        // TODO: fail if waited too much
+        if start.elapsed() > consts::SCAN_STALL_TIMEOUT {
+            return Err(Error::ScanStalled(consts::SCAN_STALL_TIMEOUT));
+        }
     }
     Ok(())
 }
 
+/// How `address` is identified in the recent payments, see [`PaymentRecipients`]
+fn payment_recipient(address: &str) -> String {
+    if address.starts_with("contact:") {
+        return address.to_string();
+    }
+    match Address::from_str(address) {
+        Ok(address) => address.script_pubkey().to_hex(),
+        Err(_) => address.to_string(),
+    }
+}
+
+/// The payment of `pset` to the recipients outside `wollet`, sorted
+///
+/// Outputs whose asset or amount is not in the PSET are skipped.
+fn pset_payment(pset: &PartiallySignedTransaction, wollet: &Wollet) -> PaymentRecipients {
+    let kinds = lwk_common::pset_outputs(pset, wollet.descriptor());
+    let mut payment: PaymentRecipients = pset
+        .outputs()
+        .iter()
+        .zip(kinds)
+        .filter(|(_, kind)| *kind == lwk_common::OutputKind::External)
+        .filter_map(|(output, _)| {
+            let recipient = output.script_pubkey.to_hex();
+            Some((recipient, output.amount?, output.asset?.to_string()))
+        })
+        .collect();
+    payment.sort();
+    payment
+}
+
 fn unvalidated_addressee(a: request::UnvalidatedAddressee) -> lwk_wollet::UnvalidatedRecipient {
     lwk_wollet::UnvalidatedRecipient {
         satoshi: a.satoshi,
@@ -1154,6 +2373,21 @@ fn signer_response_from(name: &str, signer: &AppSigner) -> Result<response::Sign
     })
 }
 
+/// The xpub with key origin of the signer `name` for `xpub_kind`, asking remote signers for it
+fn signer_keyorigin_xpub(s: &mut State, name: &str, xpub_kind: &str) -> Result<String, Error> {
+    let is_mainnet = s.config.is_mainnet();
+    match s.signers.get(name)?.remote().cloned() {
+        Some(remote) => Ok(remote.keyorigin_xpub(xpub_kind)?),
+        None => {
+            let signer = s.get_available_signer(name)?;
+            let bip = xpub_kind
+                .parse()
+                .map_err(|e: InvalidBipVariant| e.to_string())?;
+            Ok(signer.keyorigin_xpub(bip, is_mainnet)?)
+        }
+    }
+}
+
 fn signer_details(name: &str, signer: &AppSigner) -> Result<response::SignerDetails, Error> {
     Ok(response::SignerDetails {
         name: name.to_string(),
@@ -1183,7 +2417,17 @@ fn add_contracts<'a>(
     }
 }
 
-fn convert_utxo(u: &lwk_wollet::WalletTxOut) -> response::Utxo {
+/// The details of an asset known by the wallet but not inserted in the app
+fn asset_details_from_info(info: lwk_wollet::AssetInfo) -> response::AssetDetails {
+    response::AssetDetails {
+        name: info.name,
+        ticker: info.ticker,
+        precision: info.precision,
+        domain: Some(info.domain),
+    }
+}
+
+fn convert_utxo(u: &lwk_wollet::WalletTxOut, locked: bool) -> response::Utxo {
     response::Utxo {
         txid: u.outpoint.txid.to_string(),
         vout: u.outpoint.vout,
@@ -1191,6 +2435,7 @@ fn convert_utxo(u: &lwk_wollet::WalletTxOut) -> response::Utxo {
         script_pubkey: u.script_pubkey.to_hex(),
         asset: u.unblinded.asset.to_string(),
         value: u.unblinded.value,
+        locked,
     }
 }
 
@@ -1214,6 +2459,11 @@ fn convert_tx(
         type_: tx.type_.clone(),
         unblinded_url,
         memo,
+        zero_conf: tx.zero_conf.as_ref().map(|z| response::ZeroConfRisk {
+            fee_rate: z.fee_rate,
+            replaceable: z.replaceable,
+            unconfirmed_depth: z.unconfirmed_depth,
+        }),
     }
 }
 
@@ -1222,6 +2472,7 @@ mod tests {
     use std::net::TcpListener;
 
     use super::*;
+    use lwk_wollet::MockBackend;
 
     fn app_random_port() -> App {
         let addr = TcpListener::bind("127.0.0.1:0")
@@ -1254,4 +2505,209 @@ mod tests {
         app.stop().unwrap();
         app.join_threads().unwrap();
     }
+
+    #[test]
+    fn test_set_addr_verified_startup_only() {
+        let mut app = app_random_port();
+        let url = app.addr().to_string();
+        let client = jsonrpc::Client::simple_http(&url, None, None).unwrap();
+        let params = serde_json::to_value(request::WalletSetAddrVerified {
+            name: "w".to_string(),
+            index: 0,
+            signer: "s".to_string(),
+        })
+        .unwrap();
+        let params = jsonrpc::arg(params);
+        let request = client.build_request("wallet_set_addr_verified", Some(&params));
+        let response = client.send_request(request).unwrap();
+        let err = response.error.unwrap();
+        assert!(err.message.contains("only while restoring the state"));
+
+        app.stop().unwrap();
+        app.join_threads().unwrap();
+    }
+
+    #[test]
+    fn test_payment_record_startup_only() {
+        let mut app = app_random_port();
+        let url = app.addr().to_string();
+        let client = jsonrpc::Client::simple_http(&url, None, None).unwrap();
+        let params = serde_json::to_value(request::PaymentRecord {
+            recipients: vec![],
+            timestamp: 0,
+        })
+        .unwrap();
+        let params = jsonrpc::arg(params);
+        let request = client.build_request("payment_record", Some(&params));
+        let response = client.send_request(request).unwrap();
+        let err = response.error.unwrap();
+        assert!(err.message.contains("only while restoring the state"));
+
+        app.stop().unwrap();
+        app.join_threads().unwrap();
+    }
+
+    #[test]
+    fn test_subscribe_concurrent() {
+        let mut app = app_random_port();
+        let client = Client::new(app.addr()).unwrap();
+        let since = client.subscribe(None, 0, 0).unwrap().last_id;
+
+        let addr = app.addr();
+        let subscriber = std::thread::spawn(move || {
+            let client = Client::new(addr).unwrap();
+            let start = Instant::now();
+            let r = client.subscribe(None, since, 5).unwrap();
+            (r, start.elapsed())
+        });
+        // Let the subscribe request reach the server
+        std::thread::sleep(Duration::from_millis(500));
+
+        let start = Instant::now();
+        let version = client.version().unwrap();
+        assert_eq!(version.version, consts::APP_VERSION);
+        assert!(start.elapsed() < Duration::from_secs(3));
+
+        let (r, elapsed) = subscriber.join().unwrap();
+        assert!(r.notifications.is_empty());
+        assert!(elapsed >= Duration::from_secs(5));
+
+        app.stop().unwrap();
+        app.join_threads().unwrap();
+    }
+
+    /// A remote server with a signer, taking `delay` to answer the sign requests
+    fn slow_remote_signer(delay: Duration) -> JsonRpcServer {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let config = lwk_tiny_jrpc::Config::default();
+        let handler = move |request: Request, _state: Arc<Mutex<()>>| {
+            let result = match request.method.as_str() {
+                "signer_details" => serde_json::to_value(response::SignerDetails {
+                    name: "s".into(),
+                    fingerprint: "11223344".into(),
+                    id: None,
+                    xpub: None,
+                    mnemonic: None,
+                    type_: "software".into(),
+                })
+                .unwrap(),
+                _ => {
+                    std::thread::sleep(delay);
+                    let params = request.params.clone().unwrap_or_default();
+                    let pset = params.get("pset").cloned().unwrap_or_default();
+                    serde_json::json!({ "pset": pset, "warnings": "" })
+                }
+            };
+            Ok(Response::result(request.id, result))
+        };
+        JsonRpcServer::new(server, config, Arc::new(Mutex::new(())), handler)
+    }
+
+    #[test]
+    fn test_remote_sign_unlocked() {
+        let mut remote = slow_remote_signer(Duration::from_secs(5));
+        let url = format!("http://127.0.0.1:{}", remote.port().unwrap());
+        let mut app = app_random_port();
+        let client = Client::new(app.addr()).unwrap();
+        client
+            .signer_load_remote("r".into(), url, "s".into(), None)
+            .unwrap();
+
+        let mut pset = PartiallySignedTransaction::new_v2();
+        let input = lwk_wollet::elements::pset::Input::from_prevout(Default::default());
+        pset.add_input(input);
+        let pset = pset.to_string();
+        let addr = app.addr();
+        let signer = std::thread::spawn(move || {
+            let client = Client::new(addr).unwrap();
+            client.signer_sign("r".into(), pset, None)
+        });
+        // Let the sign request reach the remote server
+        std::thread::sleep(Duration::from_millis(500));
+
+        // Other requests are served while the remote server is signing
+        let start = Instant::now();
+        let signers = client.signer_list().unwrap();
+        assert_eq!(signers.signers.len(), 1);
+        assert!(start.elapsed() < Duration::from_secs(3));
+
+        signer.join().unwrap().unwrap();
+
+        app.stop().unwrap();
+        app.join_threads().unwrap();
+        remote.stop();
+        remote.join_threads();
+    }
+
+    /// A backend of an empty chain at height 1, or failing every request
+    fn mock_backend(fail: bool) -> MockBackend {
+        let header = lwk_test_util::liquid_block_1().header;
+        let mut genesis = header.clone();
+        genesis.height = 0;
+        MockBackend {
+            tip: Some(header.clone()),
+            headers: vec![genesis, header],
+            fail,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_scan_wollet_retry() {
+        let desc: WolletDescriptor = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let network = ElementsNetwork::default_regtest();
+        let mut wollet = Wollet::without_persist(network, desc).unwrap();
+
+        // A working connection is not replaced
+        let mut client = mock_backend(false);
+        scan_wollet(
+            &mut wollet,
+            &mut client,
+            || -> Result<MockBackend, Error> { panic!("reconnected") },
+        )
+        .unwrap();
+        assert_eq!(wollet.tip().height(), 1);
+
+        // A failing connection is replaced once and the scan retried
+        let mut wollet = Wollet::without_persist(network, wollet.wollet_descriptor()).unwrap();
+        let mut client = mock_backend(true);
+        scan_wollet(&mut wollet, &mut client, || Ok(mock_backend(false))).unwrap();
+        assert!(!client.fail);
+        assert_eq!(wollet.tip().height(), 1);
+
+        // The retry fails too
+        let mut client = mock_backend(true);
+        let err = scan_wollet(&mut wollet, &mut client, || Ok(mock_backend(true)));
+        assert!(err.is_err());
+
+        // The new connection cannot be opened
+        let mut client = mock_backend(true);
+        let err = scan_wollet(&mut wollet, &mut client, || {
+            Err(Error::Generic("unreachable".into()))
+        })
+        .unwrap_err();
+        assert!(matches!(err, Error::Generic(e) if e == "unreachable"));
+    }
+
+    #[test]
+    fn test_parse_locktime() {
+        assert_eq!(
+            parse_locktime("height:1000").unwrap(),
+            LockTime::from_height(1000).unwrap()
+        );
+        assert_eq!(
+            parse_locktime("time:1700000000").unwrap(),
+            LockTime::from_time(1700000000).unwrap()
+        );
+        for s in [
+            "1000",
+            "height:",
+            "height:x",
+            "block:1000",
+            "time:1000",
+            "height:1700000000",
+        ] {
+            assert!(parse_locktime(s).is_err(), "{s}");
+        }
+    }
 }
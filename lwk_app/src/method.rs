@@ -20,13 +20,21 @@ pub enum Method {
     Version,
     Scan,
     Stop,
+    Subscribe,
+    FeeRates,
     WalletLoad,
     WalletUnload,
     WalletList,
+    WalletSyncStatus,
     WalletDetails,
     WalletAddress,
+    WalletAddressReserve,
+    WalletAddresses,
+    WalletRecoveryScan,
     WalletBalance,
+    WalletSummary,
     WalletUtxos,
+    WalletUtxosExport,
     WalletTxs,
     WalletTx,
     WalletSendMany,
@@ -34,33 +42,64 @@ pub enum Method {
     WalletIssue,
     WalletReissue,
     WalletBurn,
+    WalletPeginAddress,
+    WalletClaimPegin,
+    WalletPegout,
     WalletCombine,
     WalletBroadcast,
     WalletPsetDetails,
     WalletMultisigDescriptor,
+    WalletUtxoLock,
+    WalletUtxoUnlock,
+    WalletSetLabel,
+    WalletGetLabel,
+    WalletExportLabels,
+    WalletImportLabels,
     WalletSetTxMemo,
     WalletSetAddrMemo,
+    WalletSetAddrVerified,
+    PaymentRecord,
     SignerGenerate,
     SignerJadeId,
     SignerLoadSoftware,
     SignerLoadJade,
+    SignerLoadLedger,
     SignerLoadExternal,
+    SignerLoadRemote,
     SignerUnload,
     SignerList,
     SignerDetails,
     SignerXpub,
     SignerSign,
+    SignerSessionStart,
+    SignerSessionComplete,
+    SignerSessionRecord,
     SignerSinglesigDescriptor,
     SignerRegisterMultisig,
     AssetContract,
     AssetInsert,
     AssetRemove,
     AssetList,
+    AssetEvents,
     AssetDetails,
     AssetFromExplorer,
     AssetPublish,
+    ContactAdd,
+    ContactList,
+    ContactRemove,
+    DraftSave,
+    DraftList,
+    DraftLoad,
+    DraftDelete,
+    PsetSendNostr,
+    PsetInbox,
 }
 impl Method {
+    /// Whether the responses of this method are signed by servers with `sign_responses` enabled
+    pub fn is_signed(&self) -> bool {
+        matches!(self, Method::WalletAddress | Method::WalletBalance)
+    }
+
     pub(crate) fn schema(&self, direction: request::Direction) -> Result<Value, serde_json::Error> {
         serde_json::to_value(match direction {
             Direction::Request => match self {
@@ -68,13 +107,21 @@ impl Method {
                 Method::Version => schema_for!(request::Empty),
                 Method::Scan => schema_for!(request::Empty),
                 Method::Stop => schema_for!(request::Empty),
+                Method::Subscribe => schema_for!(request::Subscribe),
+                Method::FeeRates => schema_for!(request::Empty),
                 Method::WalletLoad => schema_for!(request::WalletLoad),
                 Method::WalletUnload => schema_for!(request::WalletUnload),
                 Method::WalletList => schema_for!(request::Empty),
+                Method::WalletSyncStatus => schema_for!(request::Empty),
                 Method::WalletDetails => schema_for!(request::WalletDetails),
                 Method::WalletAddress => schema_for!(request::WalletAddress),
+                Method::WalletAddressReserve => schema_for!(request::WalletAddressReserve),
+                Method::WalletAddresses => schema_for!(request::WalletAddresses),
+                Method::WalletRecoveryScan => schema_for!(request::WalletRecoveryScan),
                 Method::WalletBalance => schema_for!(request::WalletBalance),
+                Method::WalletSummary => schema_for!(request::WalletSummary),
                 Method::WalletUtxos => schema_for!(request::WalletUtxos),
+                Method::WalletUtxosExport => schema_for!(request::WalletUtxosExport),
                 Method::WalletTxs => schema_for!(request::WalletTxs),
                 Method::WalletTx => schema_for!(request::WalletTx),
                 Method::WalletSendMany => schema_for!(request::WalletSendMany),
@@ -82,22 +129,38 @@ impl Method {
                 Method::WalletIssue => schema_for!(request::WalletIssue),
                 Method::WalletReissue => schema_for!(request::WalletReissue),
                 Method::WalletBurn => schema_for!(request::WalletBurn),
+                Method::WalletPeginAddress => schema_for!(request::WalletPeginAddress),
+                Method::WalletClaimPegin => schema_for!(request::WalletClaimPegin),
+                Method::WalletPegout => schema_for!(request::WalletPegout),
                 Method::WalletCombine => schema_for!(request::WalletCombine),
                 Method::WalletBroadcast => schema_for!(request::WalletBroadcast),
                 Method::WalletPsetDetails => schema_for!(request::WalletPsetDetails),
                 Method::WalletMultisigDescriptor => schema_for!(request::WalletMultisigDescriptor),
+                Method::WalletUtxoLock => schema_for!(request::WalletUtxoLock),
+                Method::WalletUtxoUnlock => schema_for!(request::WalletUtxoLock),
+                Method::WalletSetLabel => schema_for!(request::WalletSetLabel),
+                Method::WalletGetLabel => schema_for!(request::WalletGetLabel),
+                Method::WalletExportLabels => schema_for!(request::WalletExportLabels),
+                Method::WalletImportLabels => schema_for!(request::WalletImportLabels),
                 Method::WalletSetTxMemo => schema_for!(request::WalletSetTxMemo),
                 Method::WalletSetAddrMemo => schema_for!(request::WalletSetAddrMemo),
+                Method::WalletSetAddrVerified => schema_for!(request::WalletSetAddrVerified),
+                Method::PaymentRecord => schema_for!(request::PaymentRecord),
                 Method::SignerGenerate => schema_for!(request::Empty),
                 Method::SignerJadeId => schema_for!(request::Empty),
                 Method::SignerLoadSoftware => schema_for!(request::SignerLoadSoftware),
                 Method::SignerLoadJade => schema_for!(request::SignerLoadJade),
+                Method::SignerLoadLedger => schema_for!(request::SignerLoadLedger),
                 Method::SignerLoadExternal => schema_for!(request::SignerLoadExternal),
+                Method::SignerLoadRemote => schema_for!(request::SignerLoadRemote),
                 Method::SignerUnload => schema_for!(request::SignerUnload),
                 Method::SignerList => schema_for!(request::Empty),
                 Method::SignerDetails => schema_for!(request::SignerDetails),
                 Method::SignerXpub => schema_for!(request::SignerXpub),
                 Method::SignerSign => schema_for!(request::SignerSign),
+                Method::SignerSessionStart => schema_for!(request::SignerSessionStart),
+                Method::SignerSessionComplete => schema_for!(request::SignerSessionComplete),
+                Method::SignerSessionRecord => schema_for!(request::SignerSessionRecord),
                 Method::SignerSinglesigDescriptor => {
                     schema_for!(request::SignerSinglesigDescriptor)
                 }
@@ -106,45 +169,79 @@ impl Method {
                 Method::AssetInsert => schema_for!(request::AssetInsert),
                 Method::AssetRemove => schema_for!(request::AssetRemove),
                 Method::AssetList => schema_for!(request::Empty),
+                Method::AssetEvents => schema_for!(request::Empty),
                 Method::AssetDetails => schema_for!(request::AssetDetails),
                 Method::AssetFromExplorer => schema_for!(request::AssetFromExplorer),
                 Method::AssetPublish => schema_for!(request::AssetPublish),
+                Method::ContactAdd => schema_for!(request::ContactAdd),
+                Method::ContactList => schema_for!(request::Empty),
+                Method::ContactRemove => schema_for!(request::ContactRemove),
+                Method::DraftSave => schema_for!(request::DraftSave),
+                Method::DraftList => schema_for!(request::DraftList),
+                Method::DraftLoad => schema_for!(request::DraftLoad),
+                Method::DraftDelete => schema_for!(request::DraftLoad),
+                Method::PsetSendNostr => schema_for!(request::PsetSendNostr),
+                Method::PsetInbox => schema_for!(request::PsetInbox),
             },
             Direction::Response => match self {
                 Method::Schema => return serde_json::from_str(include_str!("../schema.json")),
                 Method::Version => schema_for!(response::Version),
                 Method::Scan => schema_for!(response::Empty),
                 Method::Stop => schema_for!(request::Empty),
+                Method::Subscribe => schema_for!(response::Subscribe),
+                Method::FeeRates => schema_for!(response::FeeRates),
                 Method::WalletLoad => schema_for!(response::Wallet),
                 Method::WalletUnload => schema_for!(response::WalletUnload),
                 Method::WalletList => schema_for!(response::WalletList),
+                Method::WalletSyncStatus => schema_for!(response::WalletSyncStatus),
                 Method::WalletDetails => schema_for!(response::WalletDetails),
                 Method::WalletAddress => schema_for!(response::WalletAddress),
+                Method::WalletAddressReserve => schema_for!(response::WalletAddressReserve),
+                Method::WalletAddresses => schema_for!(response::WalletAddresses),
+                Method::WalletRecoveryScan => schema_for!(response::WalletRecoveryScan),
                 Method::WalletBalance => schema_for!(response::WalletBalance),
+                Method::WalletSummary => schema_for!(response::WalletSummary),
                 Method::WalletUtxos => schema_for!(response::WalletUtxos),
+                Method::WalletUtxosExport => schema_for!(response::WalletUtxosExport),
                 Method::WalletTxs => schema_for!(response::WalletTxs),
                 Method::WalletTx => schema_for!(response::WalletTx),
                 Method::WalletSendMany => schema_for!(response::Pset),
                 Method::WalletDrain => schema_for!(response::Pset),
-                Method::WalletIssue => schema_for!(response::Pset),
+                Method::WalletIssue => schema_for!(response::WalletIssue),
                 Method::WalletReissue => schema_for!(response::Pset),
                 Method::WalletBurn => schema_for!(response::Pset),
+                Method::WalletPeginAddress => schema_for!(response::WalletPeginAddress),
+                Method::WalletClaimPegin => schema_for!(response::Pset),
+                Method::WalletPegout => schema_for!(response::Pset),
                 Method::WalletCombine => schema_for!(response::WalletCombine),
                 Method::WalletBroadcast => schema_for!(response::WalletBroadcast),
                 Method::WalletPsetDetails => schema_for!(response::WalletPsetDetails),
                 Method::WalletMultisigDescriptor => schema_for!(response::WalletMultisigDescriptor),
+                Method::WalletUtxoLock => schema_for!(response::Empty),
+                Method::WalletUtxoUnlock => schema_for!(response::Empty),
+                Method::WalletSetLabel => schema_for!(response::Empty),
+                Method::WalletGetLabel => schema_for!(response::WalletLabel),
+                Method::WalletExportLabels => schema_for!(response::WalletExportLabels),
+                Method::WalletImportLabels => schema_for!(response::WalletImportLabels),
                 Method::WalletSetTxMemo => schema_for!(response::Empty),
                 Method::WalletSetAddrMemo => schema_for!(response::Empty),
+                Method::WalletSetAddrVerified => schema_for!(response::Empty),
+                Method::PaymentRecord => schema_for!(response::Empty),
                 Method::SignerGenerate => schema_for!(response::SignerGenerate),
                 Method::SignerJadeId => schema_for!(response::JadeId),
                 Method::SignerLoadSoftware => schema_for!(response::Signer),
                 Method::SignerLoadJade => schema_for!(response::Signer),
+                Method::SignerLoadLedger => schema_for!(response::Signer),
                 Method::SignerLoadExternal => schema_for!(response::Signer),
+                Method::SignerLoadRemote => schema_for!(response::Signer),
                 Method::SignerUnload => schema_for!(response::SignerUnload),
                 Method::SignerList => schema_for!(response::SignerList),
                 Method::SignerDetails => schema_for!(response::SignerDetails),
                 Method::SignerXpub => schema_for!(response::SignerXpub),
                 Method::SignerSign => schema_for!(response::Pset),
+                Method::SignerSessionStart => schema_for!(response::SigningSession),
+                Method::SignerSessionComplete => schema_for!(response::Pset),
+                Method::SignerSessionRecord => schema_for!(response::Empty),
                 Method::SignerSinglesigDescriptor => {
                     schema_for!(response::SignerSinglesigDescriptor)
                 }
@@ -153,9 +250,19 @@ impl Method {
                 Method::AssetInsert => schema_for!(response::Empty),
                 Method::AssetRemove => schema_for!(request::Empty),
                 Method::AssetList => schema_for!(response::AssetList),
+                Method::AssetEvents => schema_for!(response::AssetEvents),
                 Method::AssetDetails => schema_for!(response::AssetDetails),
                 Method::AssetFromExplorer => schema_for!(request::Empty),
                 Method::AssetPublish => schema_for!(response::AssetPublish),
+                Method::ContactAdd => schema_for!(response::Contact),
+                Method::ContactList => schema_for!(response::ContactList),
+                Method::ContactRemove => schema_for!(response::Empty),
+                Method::DraftSave => schema_for!(response::DraftInfo),
+                Method::DraftList => schema_for!(response::DraftList),
+                Method::DraftLoad => schema_for!(response::Draft),
+                Method::DraftDelete => schema_for!(response::Empty),
+                Method::PsetSendNostr => schema_for!(response::PsetSendNostr),
+                Method::PsetInbox => schema_for!(response::PsetInbox),
             },
         })
     }
@@ -170,13 +277,21 @@ impl FromStr for Method {
             "version" => Method::Version,
             "scan" => Method::Scan,
             "stop" => Method::Stop,
+            "subscribe" => Method::Subscribe,
+            "fee_rates" => Method::FeeRates,
             "wallet_load" => Method::WalletLoad,
             "wallet_unload" => Method::WalletUnload,
             "wallet_list" => Method::WalletList,
+            "wallet_sync_status" => Method::WalletSyncStatus,
             "wallet_details" => Method::WalletDetails,
             "wallet_address" => Method::WalletAddress,
+            "wallet_address_reserve" => Method::WalletAddressReserve,
+            "wallet_addresses" => Method::WalletAddresses,
+            "wallet_recovery_scan" => Method::WalletRecoveryScan,
             "wallet_balance" => Method::WalletBalance,
+            "wallet_summary" => Method::WalletSummary,
             "wallet_utxos" => Method::WalletUtxos,
+            "wallet_utxos_export" => Method::WalletUtxosExport,
             "wallet_txs" => Method::WalletTxs,
             "wallet_tx" => Method::WalletTx,
             "wallet_send_many" => Method::WalletSendMany,
@@ -184,31 +299,57 @@ impl FromStr for Method {
             "wallet_issue" => Method::WalletIssue,
             "wallet_reissue" => Method::WalletReissue,
             "wallet_burn" => Method::WalletBurn,
+            "wallet_pegin_address" => Method::WalletPeginAddress,
+            "wallet_claim_pegin" => Method::WalletClaimPegin,
+            "wallet_pegout" => Method::WalletPegout,
             "wallet_combine" => Method::WalletCombine,
             "wallet_broadcast" => Method::WalletBroadcast,
             "wallet_pset_details" => Method::WalletPsetDetails,
             "wallet_multisig_descriptor" => Method::WalletMultisigDescriptor,
+            "wallet_utxo_lock" => Method::WalletUtxoLock,
+            "wallet_utxo_unlock" => Method::WalletUtxoUnlock,
+            "wallet_set_label" => Method::WalletSetLabel,
+            "wallet_get_label" => Method::WalletGetLabel,
+            "wallet_export_labels" => Method::WalletExportLabels,
+            "wallet_import_labels" => Method::WalletImportLabels,
             "wallet_set_tx_memo" => Method::WalletSetTxMemo,
             "wallet_set_addr_memo" => Method::WalletSetAddrMemo,
+            "wallet_set_addr_verified" => Method::WalletSetAddrVerified,
+            "payment_record" => Method::PaymentRecord,
             "signer_generate" => Method::SignerGenerate,
             "signer_jade_id" => Method::SignerJadeId,
             "signer_load_software" => Method::SignerLoadSoftware,
             "signer_load_jade" => Method::SignerLoadJade,
+            "signer_load_ledger" => Method::SignerLoadLedger,
             "signer_load_external" => Method::SignerLoadExternal,
+            "signer_load_remote" => Method::SignerLoadRemote,
             "signer_unload" => Method::SignerUnload,
             "signer_list" => Method::SignerList,
             "signer_details" => Method::SignerDetails,
             "signer_xpub" => Method::SignerXpub,
             "signer_sign" => Method::SignerSign,
+            "signer_session_start" => Method::SignerSessionStart,
+            "signer_session_complete" => Method::SignerSessionComplete,
+            "signer_session_record" => Method::SignerSessionRecord,
             "signer_singlesig_descriptor" => Method::SignerSinglesigDescriptor,
             "signer_register_multisig" => Method::SignerRegisterMultisig,
             "asset_contract" => Method::AssetContract,
             "asset_insert" => Method::AssetInsert,
             "asset_remove" => Method::AssetRemove,
             "asset_list" => Method::AssetList,
+            "asset_events" => Method::AssetEvents,
             "asset_details" => Method::AssetDetails,
             "asset_from_explorer" => Method::AssetFromExplorer,
             "asset_publish" => Method::AssetPublish,
+            "contact_add" => Method::ContactAdd,
+            "contact_list" => Method::ContactList,
+            "contact_remove" => Method::ContactRemove,
+            "draft_save" => Method::DraftSave,
+            "draft_list" => Method::DraftList,
+            "draft_load" => Method::DraftLoad,
+            "draft_delete" => Method::DraftDelete,
+            "pset_send_nostr" => Method::PsetSendNostr,
+            "pset_inbox" => Method::PsetInbox,
             _ => {
                 return Err(MethodNotExist {
                     name: s.to_string(),
@@ -225,13 +366,21 @@ impl std::fmt::Display for Method {
             Method::Version => "version",
             Method::Scan => "scan",
             Method::Stop => "stop",
+            Method::Subscribe => "subscribe",
+            Method::FeeRates => "fee_rates",
             Method::WalletLoad => "wallet_load",
             Method::WalletUnload => "wallet_unload",
             Method::WalletList => "wallet_list",
+            Method::WalletSyncStatus => "wallet_sync_status",
             Method::WalletDetails => "wallet_details",
             Method::WalletAddress => "wallet_address",
+            Method::WalletAddressReserve => "wallet_address_reserve",
+            Method::WalletAddresses => "wallet_addresses",
+            Method::WalletRecoveryScan => "wallet_recovery_scan",
             Method::WalletBalance => "wallet_balance",
+            Method::WalletSummary => "wallet_summary",
             Method::WalletUtxos => "wallet_utxos",
+            Method::WalletUtxosExport => "wallet_utxos_export",
             Method::WalletTxs => "wallet_txs",
             Method::WalletTx => "wallet_tx",
             Method::WalletSendMany => "wallet_send_many",
@@ -239,31 +388,57 @@ impl std::fmt::Display for Method {
             Method::WalletIssue => "wallet_issue",
             Method::WalletReissue => "wallet_reissue",
             Method::WalletBurn => "wallet_burn",
+            Method::WalletPeginAddress => "wallet_pegin_address",
+            Method::WalletClaimPegin => "wallet_claim_pegin",
+            Method::WalletPegout => "wallet_pegout",
             Method::WalletCombine => "wallet_combine",
             Method::WalletBroadcast => "wallet_broadcast",
             Method::WalletPsetDetails => "wallet_pset_details",
             Method::WalletMultisigDescriptor => "wallet_multisig_descriptor",
+            Method::WalletUtxoLock => "wallet_utxo_lock",
+            Method::WalletUtxoUnlock => "wallet_utxo_unlock",
+            Method::WalletSetLabel => "wallet_set_label",
+            Method::WalletGetLabel => "wallet_get_label",
+            Method::WalletExportLabels => "wallet_export_labels",
+            Method::WalletImportLabels => "wallet_import_labels",
             Method::WalletSetTxMemo => "wallet_set_tx_memo",
             Method::WalletSetAddrMemo => "wallet_set_addr_memo",
+            Method::WalletSetAddrVerified => "wallet_set_addr_verified",
+            Method::PaymentRecord => "payment_record",
             Method::SignerGenerate => "signer_generate",
             Method::SignerJadeId => "signer_jade_id",
             Method::SignerLoadSoftware => "signer_load_software",
             Method::SignerLoadJade => "signer_load_jade",
+            Method::SignerLoadLedger => "signer_load_ledger",
             Method::SignerLoadExternal => "signer_load_external",
+            Method::SignerLoadRemote => "signer_load_remote",
             Method::SignerUnload => "signer_unload",
             Method::SignerList => "signer_list",
             Method::SignerDetails => "signer_details",
             Method::SignerXpub => "signer_xpub",
             Method::SignerSign => "signer_sign",
+            Method::SignerSessionStart => "signer_session_start",
+            Method::SignerSessionComplete => "signer_session_complete",
+            Method::SignerSessionRecord => "signer_session_record",
             Method::SignerSinglesigDescriptor => "signer_singlesig_descriptor",
             Method::SignerRegisterMultisig => "signer_register_multisig",
             Method::AssetContract => "asset_contract",
             Method::AssetInsert => "asset_insert",
             Method::AssetRemove => "asset_remove",
             Method::AssetList => "asset_list",
+            Method::AssetEvents => "asset_events",
             Method::AssetDetails => "asset_details",
             Method::AssetFromExplorer => "asset_from_explorer",
             Method::AssetPublish => "asset_publish",
+            Method::ContactAdd => "contact_add",
+            Method::ContactList => "contact_list",
+            Method::ContactRemove => "contact_remove",
+            Method::DraftSave => "draft_save",
+            Method::DraftList => "draft_list",
+            Method::DraftLoad => "draft_load",
+            Method::DraftDelete => "draft_delete",
+            Method::PsetSendNostr => "pset_send_nostr",
+            Method::PsetInbox => "pset_inbox",
         };
         write!(f, "{}", s)
     }
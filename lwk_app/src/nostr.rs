@@ -0,0 +1,487 @@
+//! Exchange of PSETs between cosigners with Nostr encrypted direct messages
+//!
+//! Only the small part of the protocol needed is implemented: events as defined in
+//! [NIP-01](https://github.com/nostr-protocol/nips/blob/master/01.md), encrypted direct messages
+//! as defined in [NIP-04](https://github.com/nostr-protocol/nips/blob/master/04.md) and keys
+//! derived from the signer seed as defined in
+//! [NIP-06](https://github.com/nostr-protocol/nips/blob/master/06.md).
+//!
+//! Relays see who is messaging whom and when, but not the PSETs.
+
+use std::net::{TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::time::Duration;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use lwk_wollet::bitcoin::secp256k1::{
+    ecdh::shared_secret_point, schnorr::Signature, Keypair, Message, Parity, Secp256k1, SecretKey,
+    XOnlyPublicKey,
+};
+use lwk_wollet::elements::hex::ToHex;
+use lwk_wollet::elements::pset::PartiallySignedTransaction;
+use lwk_wollet::hashes::{sha256, Hash};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message as WsMessage, WebSocket};
+
+use crate::Error;
+
+/// Derivation path of the Nostr key, see NIP-06
+pub const NOSTR_PATH: &str = "m/44h/1237h/0h/0/0";
+
+/// Kind of the encrypted direct messages events
+const KIND_ENCRYPTED_DM: u16 = 4;
+
+type Socket = WebSocket<MaybeTlsStream<TcpStream>>;
+
+/// A PSET received from a cosigner
+#[derive(Debug, Clone)]
+pub struct ReceivedPset {
+    pub sender: XOnlyPublicKey,
+    pub event_id: String,
+    pub created_at: u64,
+    pub pset: PartiallySignedTransaction,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Event {
+    id: String,
+    pubkey: String,
+    created_at: u64,
+    kind: u16,
+    tags: Vec<Vec<String>>,
+    content: String,
+    sig: String,
+}
+
+impl Event {
+    fn new(keypair: &Keypair, created_at: u64, tags: Vec<Vec<String>>, content: String) -> Self {
+        let pubkey = keypair.x_only_public_key().0.to_string();
+        let id = event_id(&pubkey, created_at, KIND_ENCRYPTED_DM, &tags, &content);
+        let msg = Message::from_digest(id.to_byte_array());
+        let sig = Secp256k1::new().sign_schnorr_with_aux_rand(&msg, keypair, &thread_rng().gen());
+        Event {
+            id: id.to_string(),
+            pubkey,
+            created_at,
+            kind: KIND_ENCRYPTED_DM,
+            tags,
+            content,
+            sig: sig.to_string(),
+        }
+    }
+
+    /// Check the id commits to the event and it's signed by the author
+    fn verify(&self) -> Result<XOnlyPublicKey, Error> {
+        let id = event_id(
+            &self.pubkey,
+            self.created_at,
+            self.kind,
+            &self.tags,
+            &self.content,
+        );
+        if id.to_string() != self.id {
+            return Err(Error::Nostr(format!("event {} has an invalid id", self.id)));
+        }
+        let pubkey = XOnlyPublicKey::from_str(&self.pubkey)
+            .map_err(|e| Error::Nostr(format!("event {}: {e}", self.id)))?;
+        let sig = Signature::from_str(&self.sig)
+            .map_err(|e| Error::Nostr(format!("event {}: {e}", self.id)))?;
+        Secp256k1::new()
+            .verify_schnorr(&sig, &Message::from_digest(id.to_byte_array()), &pubkey)
+            .map_err(|e| Error::Nostr(format!("event {}: {e}", self.id)))?;
+        Ok(pubkey)
+    }
+}
+
+/// The event id is the hash of the serialized event, as defined in NIP-01
+fn event_id(
+    pubkey: &str,
+    created_at: u64,
+    kind: u16,
+    tags: &[Vec<String>],
+    content: &str,
+) -> sha256::Hash {
+    let serialized = json!([0, pubkey, created_at, kind, tags, content]).to_string();
+    sha256::Hash::hash(serialized.as_bytes())
+}
+
+/// The AES key shared by `secret_key` and `pubkey` owners, as defined in NIP-04
+fn shared_key(secret_key: &SecretKey, pubkey: &XOnlyPublicKey) -> [u8; 32] {
+    let point = shared_secret_point(&pubkey.public_key(Parity::Even), secret_key);
+    point[..32].try_into().expect("32 bytes")
+}
+
+fn encrypt(secret_key: &SecretKey, pubkey: &XOnlyPublicKey, plaintext: &str) -> String {
+    let key = shared_key(secret_key, pubkey);
+    let iv: [u8; 16] = thread_rng().gen();
+    let ciphertext = cbc::Encryptor::<aes::Aes256>::new(&key.into(), &iv.into())
+        .encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes());
+    format!(
+        "{}?iv={}",
+        BASE64_STANDARD.encode(ciphertext),
+        BASE64_STANDARD.encode(iv)
+    )
+}
+
+fn decrypt(secret_key: &SecretKey, pubkey: &XOnlyPublicKey, content: &str) -> Option<String> {
+    let (ciphertext, iv) = content.split_once("?iv=")?;
+    let ciphertext = BASE64_STANDARD.decode(ciphertext).ok()?;
+    let iv: [u8; 16] = BASE64_STANDARD.decode(iv).ok()?.try_into().ok()?;
+    let key = shared_key(secret_key, pubkey);
+    let plaintext = cbc::Decryptor::<aes::Aes256>::new(&key.into(), &iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(&ciphertext)
+        .ok()?;
+    String::from_utf8(plaintext).ok()
+}
+
+/// Open a websocket to `relay`, every network operation including the connection fails after
+/// `timeout`
+fn connect(relay: &str, timeout: Duration) -> Result<Socket, Error> {
+    let err = |e: String| Error::Nostr(format!("{relay}: {e}"));
+    let uri: tungstenite::http::Uri = relay.parse().map_err(|e| err(format!("{e}")))?;
+    let host = uri.host().ok_or_else(|| err("missing host".to_string()))?;
+    let port = match (uri.port_u16(), uri.scheme_str()) {
+        (Some(port), _) => port,
+        (None, Some("wss")) => 443,
+        (None, Some("ws")) => 80,
+        _ => return Err(err("scheme must be ws or wss".to_string())),
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let mut last_error = err("no address".to_string());
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                let (socket, _) =
+                    tungstenite::client_tls(relay, stream).map_err(|e| err(e.to_string()))?;
+                return Ok(socket);
+            }
+            Err(e) => last_error = err(e.to_string()),
+        }
+    }
+    Err(last_error)
+}
+
+/// Read the next relay message, as a JSON array
+fn read(socket: &mut Socket, relay: &str) -> Result<Vec<Value>, Error> {
+    loop {
+        match socket
+            .read()
+            .map_err(|e| Error::Nostr(format!("{relay}: {e}")))?
+        {
+            WsMessage::Text(text) => {
+                if let Ok(Value::Array(message)) = serde_json::from_str(&text) {
+                    return Ok(message);
+                }
+            }
+            WsMessage::Close(_) => {
+                return Err(Error::Nostr(format!("{relay}: connection closed")));
+            }
+            _ => {}
+        }
+    }
+}
+
+fn send(socket: &mut Socket, relay: &str, message: Value) -> Result<(), Error> {
+    socket
+        .send(WsMessage::Text(message.to_string()))
+        .map_err(|e| Error::Nostr(format!("{relay}: {e}")))
+}
+
+/// The public key of the Nostr identity of `secret_key`, to be shared with the cosigners
+pub fn public_key(secret_key: &SecretKey) -> XOnlyPublicKey {
+    Keypair::from_secret_key(&Secp256k1::new(), secret_key)
+        .x_only_public_key()
+        .0
+}
+
+/// Send `pset` to `recipient`, returns the id of the published event
+///
+/// The event is published to all the `relays`, succeeds if at least one of them accepted it.
+pub fn send_pset(
+    secret_key: &SecretKey,
+    recipient: &XOnlyPublicKey,
+    pset: &PartiallySignedTransaction,
+    relays: &[String],
+    created_at: u64,
+    timeout: Duration,
+) -> Result<String, Error> {
+    let keypair = Keypair::from_secret_key(&Secp256k1::new(), secret_key);
+    let content = encrypt(secret_key, recipient, &pset.to_string());
+    let tags = vec![vec!["p".to_string(), recipient.to_string()]];
+    let event = Event::new(&keypair, created_at, tags, content);
+
+    let mut accepted = false;
+    let mut errors = vec![];
+    for relay in relays {
+        let result = connect(relay, timeout).and_then(|mut socket| {
+            send(&mut socket, relay, json!(["EVENT", event]))?;
+            loop {
+                let message = read(&mut socket, relay)?;
+                if message.first() == Some(&json!("OK")) && message.get(1) == Some(&json!(event.id))
+                {
+                    let _ = socket.close(None);
+                    return match message.get(2) {
+                        Some(Value::Bool(true)) => Ok(()),
+                        _ => Err(Error::Nostr(format!(
+                            "{relay}: event rejected {}",
+                            message.get(3).unwrap_or(&Value::Null)
+                        ))),
+                    };
+                }
+            }
+        });
+        match result {
+            Ok(()) => accepted = true,
+            Err(e) => {
+                tracing::warn!("{e}");
+                errors.push(e.to_string())
+            }
+        }
+    }
+    if accepted {
+        Ok(event.id)
+    } else {
+        Err(Error::Nostr(format!(
+            "no relay accepted the PSET: {}",
+            errors.join(", ")
+        )))
+    }
+}
+
+/// Get the PSETs sent by `senders` to the owner of `secret_key` since the unix timestamp `since`
+///
+/// Events are deduplicated across relays, messages from other senders or that are not valid
+/// PSETs are skipped.
+pub fn inbox(
+    secret_key: &SecretKey,
+    senders: &[XOnlyPublicKey],
+    relays: &[String],
+    since: u64,
+    timeout: Duration,
+) -> Result<Vec<ReceivedPset>, Error> {
+    let pubkey = public_key(secret_key);
+    let filter = json!({
+        "kinds": [KIND_ENCRYPTED_DM],
+        "authors": senders.iter().map(ToString::to_string).collect::<Vec<_>>(),
+        "#p": [pubkey.to_string()],
+        "since": since,
+    });
+    let subscription_id = thread_rng().gen::<[u8; 8]>().to_hex();
+
+    let mut received: Vec<ReceivedPset> = vec![];
+    let mut errors = vec![];
+    for relay in relays {
+        let result = connect(relay, timeout).and_then(|mut socket| {
+            send(&mut socket, relay, json!(["REQ", subscription_id, filter]))?;
+            let mut events = vec![];
+            loop {
+                let message = read(&mut socket, relay)?;
+                match message.first().and_then(Value::as_str) {
+                    Some("EVENT") => {
+                        if let Some(event) = message.get(2) {
+                            if let Ok(event) = serde_json::from_value::<Event>(event.clone()) {
+                                events.push(event);
+                            }
+                        }
+                    }
+                    Some("EOSE") | Some("CLOSED") => break,
+                    _ => {}
+                }
+            }
+            let _ = send(&mut socket, relay, json!(["CLOSE", subscription_id]));
+            let _ = socket.close(None);
+            Ok(events)
+        });
+        let events = match result {
+            Ok(events) => events,
+            Err(e) => {
+                errors.push(e.to_string());
+                continue;
+            }
+        };
+        for event in events {
+            if event.kind != KIND_ENCRYPTED_DM || received.iter().any(|r| r.event_id == event.id) {
+                continue;
+            }
+            let sender = match event.verify() {
+                // Relays may ignore the authors filter
+                Ok(sender) if senders.contains(&sender) => sender,
+                Ok(_) => continue,
+                Err(e) => {
+                    tracing::warn!("skipping nostr event: {e}");
+                    continue;
+                }
+            };
+            let pset = decrypt(secret_key, &sender, &event.content)
+                .and_then(|s| PartiallySignedTransaction::from_str(&s).ok());
+            if let Some(pset) = pset {
+                received.push(ReceivedPset {
+                    sender,
+                    event_id: event.id,
+                    created_at: event.created_at,
+                    pset,
+                });
+            }
+        }
+    }
+    if received.is_empty() && errors.len() == relays.len() && !relays.is_empty() {
+        return Err(Error::Nostr(format!(
+            "no relay answered: {}",
+            errors.join(", ")
+        )));
+    }
+    received.sort_by_key(|r| r.created_at);
+    Ok(received)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// A relay storing the events in memory and answering to `REQ` filtering only on `#p`
+    fn mock_relay() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let events: Arc<Mutex<Vec<Value>>> = Arc::default();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let events = events.clone();
+                std::thread::spawn(move || {
+                    let mut socket = tungstenite::accept(stream.unwrap()).unwrap();
+                    while let Ok(WsMessage::Text(text)) = socket.read() {
+                        let message: Vec<Value> = serde_json::from_str(&text).unwrap();
+                        let reply = match message[0].as_str().unwrap() {
+                            "EVENT" => {
+                                events.lock().unwrap().push(message[1].clone());
+                                vec![json!(["OK", message[1]["id"], true, ""])]
+                            }
+                            "REQ" => {
+                                let p = &message[2]["#p"][0];
+                                let mut reply: Vec<_> = events
+                                    .lock()
+                                    .unwrap()
+                                    .iter()
+                                    .filter(|e| &e["tags"][0][1] == p)
+                                    .map(|e| json!(["EVENT", message[1], e]))
+                                    .collect();
+                                reply.push(json!(["EOSE", message[1]]));
+                                reply
+                            }
+                            _ => vec![],
+                        };
+                        for r in reply {
+                            socket.send(WsMessage::Text(r.to_string())).unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn test_nostr_relay() {
+        let alice = SecretKey::new(&mut thread_rng());
+        let bob = SecretKey::new(&mut thread_rng());
+        let carol = SecretKey::new(&mut thread_rng());
+        let relays = vec![mock_relay()];
+        let timeout = Duration::from_secs(10);
+        let pset = PartiallySignedTransaction::new_v2();
+
+        let event_id = send_pset(
+            &alice,
+            &public_key(&bob),
+            &pset,
+            &relays,
+            1700000000,
+            timeout,
+        )
+        .unwrap();
+        send_pset(
+            &carol,
+            &public_key(&bob),
+            &pset,
+            &relays,
+            1700000001,
+            timeout,
+        )
+        .unwrap();
+
+        // Only the PSET from the allowed sender is returned
+        let received = inbox(&bob, &[public_key(&alice)], &relays, 0, timeout).unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].sender, public_key(&alice));
+        assert_eq!(received[0].event_id, event_id);
+        assert_eq!(received[0].pset, pset);
+
+        let senders = [public_key(&alice), public_key(&carol)];
+        let received = inbox(&bob, &senders, &relays, 0, timeout).unwrap();
+        assert_eq!(received.len(), 2);
+
+        // Events sent to others are not returned
+        let received = inbox(&alice, &[public_key(&bob)], &relays, 0, timeout).unwrap();
+        assert!(received.is_empty());
+
+        // Relays accepting the connection and never answering don't hang
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let silent = vec![format!("ws://{}", listener.local_addr().unwrap())];
+        let timeout = Duration::from_millis(500);
+        let start = std::time::Instant::now();
+        assert!(inbox(&bob, &[public_key(&alice)], &silent, 0, timeout).is_err());
+        assert!(send_pset(&alice, &public_key(&bob), &pset, &silent, 0, timeout).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_nostr_encryption() {
+        let alice = SecretKey::new(&mut thread_rng());
+        let bob = SecretKey::new(&mut thread_rng());
+        let carol = SecretKey::new(&mut thread_rng());
+
+        let content = encrypt(&alice, &public_key(&bob), "pset");
+        assert_eq!(
+            decrypt(&bob, &public_key(&alice), &content).as_deref(),
+            Some("pset")
+        );
+        assert_eq!(
+            decrypt(&alice, &public_key(&bob), &content).as_deref(),
+            Some("pset")
+        );
+        assert_ne!(
+            decrypt(&carol, &public_key(&alice), &content).as_deref(),
+            Some("pset")
+        );
+
+        let keypair = Keypair::from_secret_key(&Secp256k1::new(), &alice);
+        let tags = vec![vec!["p".to_string(), public_key(&bob).to_string()]];
+        let mut event = Event::new(&keypair, 1700000000, tags, content);
+        assert_eq!(event.verify().unwrap(), public_key(&alice));
+        event.created_at += 1;
+        assert!(event.verify().is_err());
+    }
+
+    #[test]
+    fn test_nostr_event_id() {
+        // The compact JSON serialization defined in NIP-01
+        let id = event_id(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            1700000000,
+            KIND_ENCRYPTED_DM,
+            &[],
+            "hello",
+        );
+        let expected = sha256::Hash::hash(
+            br#"[0,"79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",1700000000,4,[],"hello"]"#,
+        );
+        assert_eq!(id, expected);
+    }
+}
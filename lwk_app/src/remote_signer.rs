@@ -0,0 +1,174 @@
+use std::fmt;
+use std::str::FromStr;
+
+use lwk_rpc_model::request;
+use lwk_wollet::bitcoin::bip32::{Fingerprint, Xpub};
+use lwk_wollet::bitcoin::XKeyIdentifier;
+use lwk_wollet::elements::pset::PartiallySignedTransaction;
+
+use crate::client::Client;
+use crate::Error;
+
+/// A signer loaded in another server, xpub derivation and signing requests are forwarded to it
+///
+/// This allows to keep the keys on a different host than the one holding the wallets.
+#[derive(Clone)]
+pub struct RemoteSigner {
+    url: String,
+    remote_name: String,
+    token: Option<String>,
+    fingerprint: Fingerprint,
+    xpub: Option<Xpub>,
+    id: Option<XKeyIdentifier>,
+}
+
+impl fmt::Debug for RemoteSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the token is omitted on purpose
+        f.debug_struct("RemoteSigner")
+            .field("url", &self.url)
+            .field("remote_name", &self.remote_name)
+            .field("fingerprint", &self.fingerprint)
+            .finish()
+    }
+}
+
+impl RemoteSigner {
+    /// Connect to the server at `url` and get the details of the signer named `remote_name`
+    pub fn new(url: String, remote_name: String, token: Option<String>) -> Result<Self, Error> {
+        let client = Client::with_url(url.clone(), token.clone())?;
+        let details = client.signer_details(remote_name.clone())?;
+        let fingerprint = Fingerprint::from_str(&details.fingerprint)
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        let xpub = details
+            .xpub
+            .map(|x| Xpub::from_str(&x))
+            .transpose()
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        let id = details
+            .id
+            .map(|i| XKeyIdentifier::from_str(&i))
+            .transpose()
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        Ok(Self {
+            url,
+            remote_name,
+            token,
+            fingerprint,
+            xpub,
+            id,
+        })
+    }
+
+    fn client(&self) -> Result<Client, Error> {
+        Client::with_url(self.url.clone(), self.token.clone())
+    }
+
+    pub fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint
+    }
+
+    pub fn xpub(&self) -> Option<Xpub> {
+        self.xpub
+    }
+
+    pub fn id(&self) -> Option<XKeyIdentifier> {
+        self.id
+    }
+
+    /// Get the xpub with key origin of the given kind from the remote signer
+    pub fn keyorigin_xpub(&self, xpub_kind: &str) -> Result<String, Error> {
+        let r = self
+            .client()?
+            .signer_xpub(self.remote_name.clone(), xpub_kind.to_string())?;
+        Ok(r.keyorigin_xpub)
+    }
+
+    /// Sign the PSET with the remote signer, returning the number of signatures added
+    ///
+    /// Only the signatures are taken from the PSET returned by the remote server, after checking
+    /// that it signs the same transaction.
+    pub fn sign(&self, pset: &mut PartiallySignedTransaction) -> Result<u32, Error> {
+        let r = self
+            .client()?
+            .signer_sign(self.remote_name.clone(), pset.to_string(), None)?;
+        let signed = PartiallySignedTransaction::from_str(&r.pset)
+            .map_err(|e| Error::Generic(e.to_string()))?;
+        merge_signatures(pset, &signed)
+    }
+
+    /// The request to load this signer again, including the token
+    pub fn request(&self, name: &str) -> request::SignerLoadRemote {
+        request::SignerLoadRemote {
+            name: name.to_string(),
+            url: self.url.clone(),
+            remote_name: self.remote_name.clone(),
+            token: self.token.clone(),
+        }
+    }
+}
+
+/// Add to `pset` the signatures of `signed`, which must have the same unsigned transaction
+fn merge_signatures(
+    pset: &mut PartiallySignedTransaction,
+    signed: &PartiallySignedTransaction,
+) -> Result<u32, Error> {
+    let txid = |p: &PartiallySignedTransaction| {
+        p.extract_tx()
+            .map(|tx| tx.txid())
+            .map_err(|e| Error::Generic(e.to_string()))
+    };
+    if txid(pset)? != txid(signed)? {
+        return Err(Error::Generic(
+            "The remote signer returned a different transaction".into(),
+        ));
+    }
+    let mut n_sigs = 0;
+    for (input, signed_input) in pset.inputs_mut().iter_mut().zip(signed.inputs()) {
+        for (public_key, sig) in signed_input.partial_sigs.iter() {
+            if input
+                .partial_sigs
+                .insert(*public_key, sig.clone())
+                .is_none()
+            {
+                n_sigs += 1;
+            }
+        }
+    }
+    Ok(n_sigs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lwk_wollet::bitcoin::PublicKey;
+    use lwk_wollet::elements::pset::{Input, Output};
+    use lwk_wollet::elements::{AssetId, OutPoint, Script};
+
+    #[test]
+    fn test_merge_signatures() {
+        let mut pset = PartiallySignedTransaction::new_v2();
+        pset.add_input(Input::from_prevout(OutPoint::default()));
+        let output = Output::new_explicit(Script::new(), 1_000, AssetId::default(), None);
+        pset.add_output(output);
+
+        let key = PublicKey::from_str(
+            "02ebc62c20f1e09e169a88745f60f6dac878c92db5c7ed78c6703d2d0426a01f94",
+        )
+        .unwrap();
+        let mut signed = pset.clone();
+        signed.inputs_mut()[0].partial_sigs.insert(key, vec![1, 2]);
+
+        // A remote changing the transaction is rejected
+        let mut changed = signed.clone();
+        changed.outputs_mut()[0].amount = Some(2_000);
+        let err = merge_signatures(&mut pset, &changed).unwrap_err();
+        assert!(err.to_string().contains("different transaction"));
+        assert!(pset.inputs()[0].partial_sigs.is_empty());
+
+        // Only the signatures are taken
+        assert_eq!(merge_signatures(&mut pset, &signed).unwrap(), 1);
+        assert_eq!(pset.inputs()[0].partial_sigs[&key], vec![1, 2]);
+        assert_eq!(merge_signatures(&mut pset, &signed).unwrap(), 0);
+    }
+}
@@ -8,20 +8,46 @@ pub struct ReqwestHttpTransport {
     url: String,
     /// timeout only supports second granularity.
     timeout: Duration,
+    /// Bearer token sent in the authorization header, if any.
+    token: Option<String>,
+    /// PEM certificate trusted as root, to connect to servers with self-signed certificates.
+    root_certificate: Option<Vec<u8>>,
 }
 
 impl ReqwestHttpTransport {
     pub fn new(url: String, timeout: Duration) -> Self {
-        ReqwestHttpTransport { url, timeout }
+        ReqwestHttpTransport {
+            url,
+            timeout,
+            token: None,
+            root_certificate: None,
+        }
+    }
+
+    pub fn with_token(mut self, token: Option<String>) -> Self {
+        self.token = token;
+        self
+    }
+
+    pub fn with_root_certificate(mut self, pem: Option<Vec<u8>>) -> Self {
+        self.root_certificate = pem;
+        self
     }
+
     fn request<R>(&self, req: impl serde::Serialize) -> Result<R, crate::Error>
     where
         R: for<'a> serde::de::Deserialize<'a>,
     {
-        let client = reqwest::blocking::ClientBuilder::new()
-            .timeout(self.timeout)
-            .build()?;
-        let response = client.post(&self.url).json(&req).send()?;
+        let mut client = reqwest::blocking::ClientBuilder::new().timeout(self.timeout);
+        if let Some(pem) = self.root_certificate.as_ref() {
+            client = client.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+        }
+        let client = client.build()?;
+        let mut builder = client.post(&self.url).json(&req);
+        if let Some(token) = self.token.as_ref() {
+            builder = builder.bearer_auth(token);
+        }
+        let response = builder.send()?;
         Ok(response.json()?)
     }
 }
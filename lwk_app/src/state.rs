@@ -1,13 +1,13 @@
-use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs::OpenOptions;
 use std::io::Write;
 use std::net::SocketAddr;
 use std::str::FromStr;
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use lwk_common::Signer;
+use lwk_common::{PendingSignatures, Signer};
 use lwk_jade::{Jade, Network};
-use lwk_rpc_model::request;
+use lwk_rpc_model::{request, response};
 use lwk_signer::AnySigner;
 use lwk_signer::SwSigner;
 use lwk_tiny_jrpc::Request;
@@ -17,13 +17,17 @@ use lwk_wollet::bitcoin::XKeyIdentifier;
 use lwk_wollet::elements::encode::serialize;
 use lwk_wollet::elements::hex::ToHex;
 use lwk_wollet::elements::pset::elip100::AssetMetadata;
-use lwk_wollet::elements::{Address, AssetId, OutPoint, Transaction, Txid};
+use lwk_wollet::elements::pset::PartiallySignedTransaction;
+use lwk_wollet::elements::secp256k1_zkp::SecretKey;
+use lwk_wollet::elements::{Address, AddressParams, AssetId, OutPoint, Transaction, Txid};
 use lwk_wollet::Contract;
 use lwk_wollet::Wollet;
+use lwk_wollet::WolletDescriptor;
 use serde::Serialize;
 
 use crate::config::Config;
 use crate::method::Method;
+use crate::remote_signer::RemoteSigner;
 use crate::Error;
 
 #[derive(Debug)]
@@ -31,19 +35,36 @@ enum AppSignerInner {
     JadeId(XKeyIdentifier, Network),
     AvailableSigner(AnySigner),
     ExternalSigner(Fingerprint),
+    RemoteSigner(RemoteSigner),
 }
 
 #[derive(Debug)]
 pub struct AppSigner {
     inner: AppSignerInner,
     persist: bool,
+
+    /// The file the mnemonic of a software signer has been read from
+    mnemonic_file: Option<String>,
 }
 
 impl AppSigner {
     pub fn new_sw(mnemonic: &str, is_mainnet: bool, persist: bool) -> Result<Self, Error> {
         let sw = SwSigner::new(mnemonic, is_mainnet)?;
         let inner = AppSignerInner::AvailableSigner(AnySigner::Software(sw));
-        Ok(AppSigner { inner, persist })
+        Ok(AppSigner {
+            inner,
+            persist,
+            mnemonic_file: None,
+        })
+    }
+
+    /// A software signer with the mnemonic contained in the file at `path`
+    pub fn new_sw_file(path: &str, is_mainnet: bool, persist: bool) -> Result<Self, Error> {
+        let mnemonic = std::fs::read_to_string(path)?;
+        Ok(AppSigner {
+            mnemonic_file: Some(path.to_string()),
+            ..Self::new_sw(mnemonic.trim(), is_mainnet, persist)?
+        })
     }
 
     pub fn new_jade(
@@ -61,6 +82,18 @@ impl AppSigner {
         Ok(AppSigner {
             inner,
             persist: true,
+            mnemonic_file: None,
+        })
+    }
+
+    pub fn new_ledger(emulator: u16) -> Result<Self, Error> {
+        // Like for the Jade emulator, we don't aim to handle connection/disconnection
+        let ledger = lwk_ledger::Ledger::new(emulator)?;
+        let id = ledger.identifier()?;
+        Ok(AppSigner {
+            inner: AppSignerInner::AvailableSigner(AnySigner::Ledger(ledger, id)),
+            persist: true,
+            mnemonic_file: None,
         })
     }
 
@@ -68,6 +101,27 @@ impl AppSigner {
         AppSigner {
             inner: AppSignerInner::ExternalSigner(fingerprint),
             persist: false,
+            mnemonic_file: None,
+        }
+    }
+
+    pub fn new_remote(
+        url: String,
+        remote_name: String,
+        token: Option<String>,
+    ) -> Result<Self, Error> {
+        Ok(AppSigner {
+            inner: AppSignerInner::RemoteSigner(RemoteSigner::new(url, remote_name, token)?),
+            persist: true,
+            mnemonic_file: None,
+        })
+    }
+
+    /// The remote signer, if this signer is loaded in another server
+    pub fn remote(&self) -> Option<&RemoteSigner> {
+        match &self.inner {
+            AppSignerInner::RemoteSigner(r) => Some(r),
+            _ => None,
         }
     }
 
@@ -76,12 +130,14 @@ impl AppSigner {
             AppSignerInner::AvailableSigner(s) => s.fingerprint()?,
             AppSignerInner::ExternalSigner(f) => *f,
             AppSignerInner::JadeId(id, _) => id_to_fingerprint(id),
+            AppSignerInner::RemoteSigner(r) => r.fingerprint(),
         })
     }
 
     pub fn xpub(&self) -> Result<Option<Xpub>, Error> {
         Ok(match &self.inner {
             AppSignerInner::AvailableSigner(s) => Some(s.xpub()?),
+            AppSignerInner::RemoteSigner(r) => r.xpub(),
             _ => None,
         })
     }
@@ -90,6 +146,7 @@ impl AppSigner {
         Ok(match &self.inner {
             AppSignerInner::AvailableSigner(s) => Some(s.identifier()?),
             AppSignerInner::JadeId(id, _) => Some(*id),
+            AppSignerInner::RemoteSigner(r) => r.id(),
             _ => None,
         })
     }
@@ -103,14 +160,47 @@ impl AppSigner {
         }
     }
 
+    /// The key of the Nostr identity of this signer, derived as defined in NIP-06
+    #[cfg(feature = "nostr")]
+    pub fn nostr_secret_key(&self) -> Result<lwk_wollet::bitcoin::secp256k1::SecretKey, Error> {
+        match &self.inner {
+            AppSignerInner::AvailableSigner(AnySigner::Software(s)) => {
+                let path = crate::nostr::NOSTR_PATH.parse().expect("static");
+                let xprv = s
+                    .derive_xprv(&path)
+                    .map_err(|e| Error::Generic(e.to_string()))?;
+                Ok(xprv.private_key)
+            }
+            _ => Err(Error::Generic(
+                "Nostr keys can be derived only from software signers".to_string(),
+            )),
+        }
+    }
+
+    /// The position of this signer in the recommended signing order, lower signs first
+    ///
+    /// Software and remote signers sign immediately, while devices need the user to connect and
+    /// confirm, Ledger requires the wallet to be registered and is the slowest, external signers
+    /// sign outside the app.
+    fn signing_rank(&self) -> u8 {
+        match &self.inner {
+            AppSignerInner::AvailableSigner(AnySigner::Software(_)) => 0,
+            AppSignerInner::RemoteSigner(_) => 1,
+            AppSignerInner::AvailableSigner(AnySigner::Jade(_, _)) => 2,
+            AppSignerInner::JadeId(_, _) => 3,
+            AppSignerInner::AvailableSigner(AnySigner::Ledger(_, _)) => 4,
+            AppSignerInner::ExternalSigner(_) => 5,
+        }
+    }
+
     pub fn type_(&self) -> String {
         match &self.inner {
             AppSignerInner::ExternalSigner(_) => "external".into(),
             AppSignerInner::JadeId(_, _) => "jade-id".into(),
+            AppSignerInner::RemoteSigner(_) => "remote".into(),
             AppSignerInner::AvailableSigner(AnySigner::Software(_)) => "software".into(),
             AppSignerInner::AvailableSigner(AnySigner::Jade(_, _)) => "jade".into(),
-            #[allow(unreachable_patterns)]
-            _ => todo!(),
+            AppSignerInner::AvailableSigner(AnySigner::Ledger(_, _)) => "ledger".into(),
         }
     }
 }
@@ -199,6 +289,23 @@ impl AppAsset {
         }
     }
 
+    pub fn precision(&self) -> u8 {
+        match self {
+            AppAsset::PolicyAsset(_) => 8,
+            AppAsset::RegistryAsset(d) => d.contract.precision,
+            AppAsset::ReissuanceToken(_) => 0,
+        }
+    }
+
+    pub fn domain(&self) -> Option<String> {
+        match self {
+            AppAsset::PolicyAsset(_) => None,
+            AppAsset::RegistryAsset(d) | AppAsset::ReissuanceToken(d) => {
+                Some(d.contract.entity.domain().to_string())
+            }
+        }
+    }
+
     pub fn asset_metadata(&self) -> Option<AssetMetadata> {
         match self {
             AppAsset::PolicyAsset(_) => None,
@@ -261,6 +368,165 @@ pub struct TxMemos(HashMap<String, HashMap<Txid, String>>);
 #[derive(Default)]
 pub struct AddrMemos(HashMap<String, HashMap<Address, String>>);
 
+#[derive(Default)]
+pub struct ReservedAddrs(HashMap<String, BTreeSet<u32>>);
+
+/// The addresses verified on a signer device, by wallet and derivation index, with the name of
+/// the signer
+#[derive(Default)]
+pub struct VerifiedAddrs(HashMap<String, BTreeMap<u32, String>>);
+
+/// A contact of the address book
+pub enum AppContact {
+    /// A contact paid always at the same address
+    Address(Address),
+
+    /// A contact paid at a fresh address for every payment
+    Descriptor {
+        descriptor: WolletDescriptor,
+        next_index: u32,
+    },
+}
+
+impl AppContact {
+    pub fn response(&self, name: &str) -> response::Contact {
+        let (address, descriptor, index) = match self {
+            AppContact::Address(a) => (Some(a.to_string()), None, None),
+            AppContact::Descriptor {
+                descriptor,
+                next_index,
+            } => (None, Some(descriptor.to_string()), Some(*next_index)),
+        };
+        response::Contact {
+            name: name.to_string(),
+            address,
+            descriptor,
+            index,
+        }
+    }
+
+    fn request(&self, name: &str) -> request::ContactAdd {
+        let c = self.response(name);
+        request::ContactAdd {
+            name: c.name,
+            address: c.address,
+            descriptor: c.descriptor,
+            index: c.index,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Contacts(BTreeMap<String, AppContact>);
+
+/// A PSET waiting for the signatures of an external signer
+pub struct AppSigningSession {
+    /// The PSET and the requested signatures
+    pub pending: PendingSignatures,
+
+    /// The fingerprint of the signer whose signatures are requested
+    pub fingerprint: Fingerprint,
+
+    /// Unix time of the start of the session
+    pub timestamp: u64,
+}
+
+impl AppSigningSession {
+    pub fn record(&self) -> request::SignerSessionRecord {
+        request::SignerSessionRecord {
+            id: self.pending.id.clone(),
+            fingerprint: self.fingerprint.to_string(),
+            pset: self.pending.pset.to_string(),
+            timestamp: self.timestamp,
+        }
+    }
+}
+
+/// Maximum number of signing sessions kept, starting more drops the oldest one
+const MAX_SIGNING_SESSIONS: usize = 100;
+
+/// Seconds after which a signing session expires
+const SIGNING_SESSION_EXPIRY: u64 = 24 * 60 * 60;
+
+/// The sessions waiting for the signatures of external signers, by identifier
+#[derive(Default)]
+pub struct SigningSessions(HashMap<String, AppSigningSession>);
+
+/// A PSET saved to be completed later, for instance while waiting for the other signers
+pub struct AppDraft {
+    pub pset: PartiallySignedTransaction,
+    pub wallet: String,
+    pub creator: Option<String>,
+    pub timestamp: u64,
+}
+
+impl AppDraft {
+    pub fn info(&self, name: &str) -> response::DraftInfo {
+        response::DraftInfo {
+            name: name.to_string(),
+            wallet: self.wallet.clone(),
+            creator: self.creator.clone(),
+            timestamp: self.timestamp,
+        }
+    }
+
+    pub fn response(&self, name: &str) -> response::Draft {
+        response::Draft {
+            name: name.to_string(),
+            wallet: self.wallet.clone(),
+            creator: self.creator.clone(),
+            timestamp: self.timestamp,
+            pset: self.pset.to_string(),
+        }
+    }
+
+    pub fn request(&self, name: &str, replace: bool) -> request::DraftSave {
+        request::DraftSave {
+            name: name.to_string(),
+            pset: self.pset.to_string(),
+            wallet: self.wallet.clone(),
+            creator: self.creator.clone(),
+            replace,
+            timestamp: Some(self.timestamp),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Drafts(BTreeMap<String, AppDraft>);
+
+/// The recipients of a payment as (recipient, satoshi, asset), sorted
+///
+/// The recipient is the hex script pubkey, so that the confidential and unconfidential forms of an
+/// address match, or "contact:<name>" for contacts, which may get a new address for every payment.
+pub type PaymentRecipients = Vec<(String, u64, String)>;
+
+/// The payments built recently with their unix time, to detect accidental duplicates
+#[derive(Default)]
+pub struct RecentPayments(Vec<(PaymentRecipients, u64)>);
+
+/// Maximum number of notifications kept, older ones are discarded
+const MAX_NOTIFICATIONS: usize = 1000;
+
+/// The state of a wallet, compared before and after a scan to find what changed
+pub struct WolletSnapshot {
+    status: u64,
+    tip: u32,
+    txs: BTreeMap<Txid, Option<u32>>,
+}
+
+/// The most recent notifications emitted by the scanning thread
+#[derive(Default)]
+pub struct Notifications {
+    last_id: u64,
+    block: u32,
+    events: VecDeque<response::Notification>,
+}
+
+/// The last seen ticker and domain of registry entries, `None` if the entry was missing
+#[derive(Default)]
+pub struct RegistryEntries(HashMap<AssetId, Option<(String, String)>>);
+
 pub struct State {
     // TODO: config is read-only, so it's not useful to wrap it in a mutex.
     // Ideally it should be in _another_ struct accessible by method_handler.
@@ -270,16 +536,37 @@ pub struct State {
     pub assets: Assets,
     pub tx_memos: TxMemos,
     pub addr_memos: AddrMemos,
+    pub reserved_addrs: ReservedAddrs,
+    pub verified_addrs: VerifiedAddrs,
+    pub registry_entries: RegistryEntries,
+    pub contacts: Contacts,
+    pub drafts: Drafts,
+    pub asset_events: Vec<response::AssetEvent>,
+    pub notifications: Notifications,
     pub do_persist: bool,
 
+    /// The key signing critical responses, if `sign_responses` is enabled
+    pub identity: Option<SecretKey>,
+
     /// Number of scan loops started
     pub scan_loops_started: u32,
 
     /// Number of scan loops completed
     pub scan_loops_completed: u32,
 
+    /// Unix time of the last successful scan of each wallet
+    pub last_syncs: HashMap<String, u64>,
+
+    /// Sessions waiting for the signatures of external signers
+    ///
+    /// Sessions are not persisted, after a restart they must be started again.
+    pub signing_sessions: SigningSessions,
+
     /// Signal the scanning thread that we don't want to wait anymore
     pub interrupt_wait: bool,
+
+    /// Payments built recently, tracked if `duplicate_payment_window` is set
+    pub recent_payments: RecentPayments,
 }
 
 impl Wollets {
@@ -389,6 +676,7 @@ impl Signers {
             let signer = AppSigner {
                 inner,
                 persist: true,
+                mnemonic_file: None,
             };
             // replace the existing AppSignerInner::JadeId with AppSignerInner::AvailableSigner
             self.0.insert(name.to_string(), signer);
@@ -399,6 +687,9 @@ impl Signers {
             AppSignerInner::ExternalSigner(_) => Err(Error::Generic(
                 "Invalid operation for external signer".to_string(),
             )),
+            AppSignerInner::RemoteSigner(_) => Err(Error::Generic(
+                "Invalid operation for remote signer".to_string(),
+            )),
             AppSignerInner::JadeId(_, _) => Err(Error::Generic(
                 "Invalid operation jade is not connected".to_string(),
             )),
@@ -447,6 +738,17 @@ impl Signers {
             .collect())
     }
 
+    /// The names of the loaded signers with `fingerprints`, in the recommended signing order
+    pub fn signing_order(&self, fingerprints: &BTreeSet<Fingerprint>) -> Vec<String> {
+        let mut signers: Vec<_> = self
+            .iter()
+            .filter(|(_, s)| s.fingerprint().map_or(false, |f| fingerprints.contains(&f)))
+            .map(|(name, s)| (s.signing_rank(), name.clone()))
+            .collect();
+        signers.sort();
+        signers.into_iter().map(|(_, name)| name).collect()
+    }
+
     /// Get a name from the fingerprint
     pub fn name_from_fingerprint(
         &self,
@@ -526,7 +828,466 @@ impl AddrMemos {
     }
 }
 
+impl ReservedAddrs {
+    pub fn is_reserved(&self, wollet: &str, index: u32) -> bool {
+        self.0
+            .get(wollet)
+            .map(|r| r.contains(&index))
+            .unwrap_or(false)
+    }
+
+    /// The index following the highest reserved index
+    pub fn next(&self, wollet: &str) -> u32 {
+        self.0
+            .get(wollet)
+            .and_then(|r| r.last())
+            .map(|i| i + 1)
+            .unwrap_or(0)
+    }
+
+    pub fn reserve(&mut self, wollet: &str, indexes: impl IntoIterator<Item = u32>) {
+        self.0
+            .entry(wollet.to_string())
+            .or_default()
+            .extend(indexes);
+    }
+
+    /// Release the reservations below `first_unused`, the first address after the last used one
+    ///
+    /// These addresses are covered by the wallet scan and are never handed out again without an
+    /// explicit index.
+    pub fn release_below(&mut self, wollet: &str, first_unused: u32) {
+        if let Some(indexes) = self.0.get_mut(wollet) {
+            indexes.retain(|i| *i >= first_unused);
+            if indexes.is_empty() {
+                self.0.remove(wollet);
+            }
+        }
+    }
+
+    /// The reserved indexes of `wollet` as ranges of consecutive indexes, with their first index
+    /// and length
+    pub fn ranges(&self, wollet: &str) -> Vec<(u32, u32)> {
+        let mut ranges: Vec<(u32, u32)> = vec![];
+        for index in self.0.get(wollet).into_iter().flatten() {
+            match ranges.last_mut() {
+                Some((start, count)) if *start + *count == *index => *count += 1,
+                _ => ranges.push((*index, 1)),
+            }
+        }
+        ranges
+    }
+
+    pub fn remove(&mut self, wollet: &str) {
+        self.0.remove(wollet);
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.0.keys()
+    }
+}
+
+impl VerifiedAddrs {
+    /// The name of the signer which verified the address of `wollet` at `index`, if any
+    pub fn get(&self, wollet: &str, index: u32) -> Option<&String> {
+        self.0.get(wollet).and_then(|v| v.get(&index))
+    }
+
+    pub fn set(&mut self, wollet: &str, index: u32, signer: &str) {
+        self.0
+            .entry(wollet.to_string())
+            .or_default()
+            .insert(index, signer.to_string());
+    }
+
+    pub fn remove(&mut self, wollet: &str) {
+        self.0.remove(wollet);
+    }
+
+    /// Remove the addresses verified by `signer`, which may be replaced by another device
+    pub fn remove_signer(&mut self, signer: &str) {
+        for verified in self.0.values_mut() {
+            verified.retain(|_, s| s != signer);
+        }
+        self.0.retain(|_, verified| !verified.is_empty());
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &BTreeMap<u32, String>)> {
+        self.0.iter()
+    }
+}
+
+impl Contacts {
+    pub fn get(&self, name: &str) -> Result<&AppContact, Error> {
+        self.0
+            .get(name)
+            .ok_or_else(|| Error::ContactNotExist(name.to_string()))
+    }
+
+    pub fn insert(&mut self, name: &str, contact: AppContact) -> Result<(), Error> {
+        if self.0.contains_key(name) {
+            return Err(Error::ContactAlreadyExist(name.to_string()));
+        }
+        self.0.insert(name.to_string(), contact);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<AppContact, Error> {
+        self.0
+            .remove(name)
+            .ok_or_else(|| Error::ContactNotExist(name.to_string()))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &AppContact)> {
+        self.0.iter()
+    }
+
+    /// Move the descriptor contacts paid by `tx` to their next address
+    ///
+    /// Returns true if any contact was updated.
+    pub fn advance_paid(
+        &mut self,
+        tx: &Transaction,
+        params: &'static AddressParams,
+    ) -> Result<bool, Error> {
+        let mut advanced = false;
+        for contact in self.0.values_mut() {
+            if let AppContact::Descriptor {
+                descriptor,
+                next_index,
+            } = contact
+            {
+                let script_pubkey = descriptor.address(*next_index, params)?.script_pubkey();
+                if tx.output.iter().any(|o| o.script_pubkey == script_pubkey) {
+                    *next_index += 1;
+                    advanced = true;
+                }
+            }
+        }
+        Ok(advanced)
+    }
+}
+
+impl Drafts {
+    pub fn get(&self, name: &str) -> Result<&AppDraft, Error> {
+        self.0
+            .get(name)
+            .ok_or_else(|| Error::DraftNotExist(name.to_string()))
+    }
+
+    pub fn insert(&mut self, name: &str, draft: AppDraft, replace: bool) -> Result<(), Error> {
+        if !replace && self.0.contains_key(name) {
+            return Err(Error::DraftAlreadyExist(name.to_string()));
+        }
+        self.0.insert(name.to_string(), draft);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<AppDraft, Error> {
+        self.0
+            .remove(name)
+            .ok_or_else(|| Error::DraftNotExist(name.to_string()))
+    }
+
+    /// Remove the drafts involving `wollet`
+    pub fn remove_wollet(&mut self, wollet: &str) {
+        self.0.retain(|_, d| d.wallet != wollet);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &AppDraft)> {
+        self.0.iter()
+    }
+}
+
+impl WolletSnapshot {
+    pub fn new(wollet: &Wollet) -> Result<Self, Error> {
+        Ok(Self {
+            status: wollet.status(),
+            tip: wollet.tip().height(),
+            txs: wollet
+                .transactions()?
+                .into_iter()
+                .map(|tx| (tx.txid, tx.height))
+                .collect(),
+        })
+    }
+}
+
+impl SigningSessions {
+    /// Insert a session, dropping the expired sessions and the oldest one if there are too many
+    pub fn insert(&mut self, id: String, session: AppSigningSession) {
+        let now = session.timestamp;
+        self.0
+            .retain(|_, s| now.saturating_sub(s.timestamp) < SIGNING_SESSION_EXPIRY);
+        if self.0.len() >= MAX_SIGNING_SESSIONS {
+            let oldest = self
+                .0
+                .iter()
+                .min_by_key(|(_, s)| s.timestamp)
+                .map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                self.0.remove(&oldest);
+            }
+        }
+        self.0.insert(id, session);
+    }
+
+    /// Insert a session restored from the persisted state, unless it is expired at `now`
+    pub fn restore(&mut self, id: String, session: AppSigningSession, now: u64) {
+        if now.saturating_sub(session.timestamp) < SIGNING_SESSION_EXPIRY {
+            self.insert(id, session);
+        }
+    }
+
+    /// The session `id`, if not expired at `now`
+    pub fn get(&self, id: &str, now: u64) -> Result<&AppSigningSession, Error> {
+        self.0
+            .get(id)
+            .filter(|s| now.saturating_sub(s.timestamp) < SIGNING_SESSION_EXPIRY)
+            .ok_or_else(|| Error::SigningSessionNotExist(id.to_string()))
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.0.remove(id);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AppSigningSession> {
+        self.0.values()
+    }
+}
+
+impl RecentPayments {
+    /// How many seconds before `now` an identical payment was built, if within `window` seconds
+    ///
+    /// Payments older than `window` are discarded.
+    pub fn duplicate(
+        &mut self,
+        recipients: &PaymentRecipients,
+        window: u64,
+        now: u64,
+    ) -> Option<u64> {
+        self.0.retain(|(_, t)| now.saturating_sub(*t) <= window);
+        self.0
+            .iter()
+            .rev()
+            .find(|(r, _)| r == recipients)
+            .map(|(_, t)| now.saturating_sub(*t))
+    }
+
+    /// Record `recipients` paid at `timestamp`, discarding the payments older than `window`
+    pub fn insert(&mut self, recipients: PaymentRecipients, timestamp: u64, window: u64) {
+        self.0
+            .retain(|(_, t)| timestamp.saturating_sub(*t) <= window);
+        self.0.push((recipients, timestamp));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(PaymentRecipients, u64)> {
+        self.0.iter()
+    }
+}
+
+/// The recipients and the timestamp of a payment restored from `record`
+pub fn payment_from_record(record: request::PaymentRecord) -> (PaymentRecipients, u64) {
+    let mut recipients: PaymentRecipients = record
+        .recipients
+        .into_iter()
+        .map(|r| (r.recipient, r.satoshi, r.asset))
+        .collect();
+    recipients.sort();
+    (recipients, record.timestamp)
+}
+
+fn payment_record(recipients: &PaymentRecipients, timestamp: u64) -> request::PaymentRecord {
+    request::PaymentRecord {
+        recipients: recipients
+            .iter()
+            .map(
+                |(recipient, satoshi, asset)| request::PaymentRecordRecipient {
+                    recipient: recipient.clone(),
+                    satoshi: *satoshi,
+                    asset: asset.clone(),
+                },
+            )
+            .collect(),
+        timestamp,
+    }
+}
+
+impl Notifications {
+    /// The height of the most recent block seen by the scanning thread
+    pub fn block(&self) -> u32 {
+        self.block
+    }
+
+    fn push(
+        &mut self,
+        kind: response::NotificationKind,
+        wallet: Option<&str>,
+        txid: Option<&Txid>,
+        height: Option<u32>,
+        timestamp: u64,
+    ) {
+        self.last_id += 1;
+        if self.events.len() == MAX_NOTIFICATIONS {
+            self.events.pop_front();
+        }
+        self.events.push_back(response::Notification {
+            id: self.last_id,
+            kind,
+            wallet: wallet.map(ToString::to_string),
+            txid: txid.map(ToString::to_string),
+            height,
+            timestamp,
+        });
+    }
+
+    /// Record the notifications for the changes of the wallet `name` between two snapshots
+    ///
+    /// Transactions found by the first scan of a wallet are not notified.
+    pub fn wollet_changes(
+        &mut self,
+        name: &str,
+        before: &WolletSnapshot,
+        after: &WolletSnapshot,
+        timestamp: u64,
+    ) {
+        use response::NotificationKind::*;
+        if after.tip > self.block {
+            self.block = after.tip;
+            self.push(Block, None, None, Some(after.tip), timestamp);
+        }
+        if before.status == after.status {
+            return;
+        }
+        if before.tip != 0 {
+            for (txid, height) in after.txs.iter() {
+                match before.txs.get(txid) {
+                    None => self.push(TxReceived, Some(name), Some(txid), *height, timestamp),
+                    Some(None) if height.is_some() => {
+                        self.push(TxConfirmed, Some(name), Some(txid), *height, timestamp)
+                    }
+                    _ => (),
+                }
+            }
+        }
+        self.push(WalletSynced, Some(name), None, Some(after.tip), timestamp);
+    }
+
+    /// The notifications with identifier greater than `since`, about `wallet` if some
+    pub fn since(&self, since: u64, wallet: Option<&str>) -> Vec<response::Notification> {
+        self.events
+            .iter()
+            .filter(|n| n.id > since)
+            .filter(|n| wallet.is_none() || n.wallet.is_none() || n.wallet.as_deref() == wallet)
+            .cloned()
+            .collect()
+    }
+
+    pub fn last_id(&self) -> u64 {
+        self.last_id
+    }
+}
+
+impl RegistryEntries {
+    /// Record the `registry` entry of an asset with the given `local` contract
+    ///
+    /// Returns an event if the entry differs from the contract and it has changed since the
+    /// last time it was seen.
+    pub fn update(
+        &mut self,
+        asset_id: AssetId,
+        local: &Contract,
+        registry: Option<&Contract>,
+        timestamp: u64,
+    ) -> Option<response::AssetEvent> {
+        let metadata = |c: &Contract| (c.ticker.clone(), c.entity.domain().to_string());
+        let local_metadata = metadata(local);
+        let seen = registry.map(metadata);
+        let last_seen = self
+            .0
+            .insert(asset_id, seen.clone())
+            .unwrap_or_else(|| Some(local_metadata.clone()));
+        if seen == last_seen || seen.as_ref() == Some(&local_metadata) {
+            return None;
+        }
+        let kind = match seen {
+            Some(_) => response::AssetEventKind::Changed,
+            None => response::AssetEventKind::Revoked,
+        };
+        let (registry_ticker, registry_domain) = seen.unzip();
+        Some(response::AssetEvent {
+            asset_id: asset_id.to_string(),
+            kind,
+            ticker: local_metadata.0,
+            domain: local_metadata.1,
+            registry_ticker,
+            registry_domain,
+            timestamp,
+        })
+    }
+}
+
 impl State {
+    /// The registry assets held by the loaded wallets, with their contract
+    pub fn held_registry_assets(&self) -> Vec<(AssetId, Contract)> {
+        let mut held = HashSet::new();
+        for (_, wollet) in self.wollets.iter() {
+            if let Ok(balance) = wollet.balance() {
+                held.extend(balance.into_iter().filter(|(_, v)| *v > 0).map(|(a, _)| a));
+            }
+        }
+        held.into_iter()
+            .filter_map(|asset_id| match self.get_asset(&asset_id) {
+                Ok(AppAsset::RegistryAsset(d)) => Some((asset_id, d.contract().clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Resolve `contact:<name>` to the address of the contact, other strings are returned as
+    /// they are
+    ///
+    /// For descriptor contacts the address at the next index is returned, the same address is
+    /// returned until a transaction paying it is broadcast, see [`State::contacts_paid`].
+    pub fn resolve_contact(&self, address: &str) -> Result<String, Error> {
+        let name = match address.strip_prefix("contact:") {
+            Some(name) => name,
+            None => return Ok(address.to_string()),
+        };
+        let params = self.config.network.address_params();
+        match self.contacts.get(name)? {
+            AppContact::Address(a) => Ok(a.to_string()),
+            AppContact::Descriptor {
+                descriptor,
+                next_index,
+            } => Ok(descriptor.address(*next_index, params)?.to_string()),
+        }
+    }
+
+    /// Advance the descriptor contacts paid by the broadcast transaction `tx`
+    pub fn contacts_paid(&mut self, tx: &Transaction) -> Result<(), Error> {
+        let params = self.config.network.address_params();
+        if self.contacts.advance_paid(tx, params)? {
+            self.persist_all()?;
+        }
+        Ok(())
+    }
+
+    /// Sign the response to `request` if `sign_responses` is enabled
+    pub fn sign_response(
+        &self,
+        request: &Request,
+        response: &mut serde_json::Value,
+    ) -> Result<(), Error> {
+        match self.identity.as_ref() {
+            Some(identity) => {
+                let params = request.params.clone().unwrap_or_default();
+                crate::identity::sign_response(identity, &request.method, &params, response)
+            }
+            None => Ok(()),
+        }
+    }
+
     pub fn insert_policy_asset(&mut self) {
         let asset_id = self.config.network.policy_asset();
         self.assets
@@ -574,24 +1335,55 @@ impl State {
         self.get_asset(asset).ok().and_then(|a| a.issuance_tx())
     }
 
-    pub fn replace_id_with_ticker(
+    pub fn replace_id_with_ticker<V>(
         &self,
-        balance: impl IntoIterator<Item = (String, i64)>,
-    ) -> HashMap<String, i64> {
+        balance: impl IntoIterator<Item = (String, V)>,
+    ) -> HashMap<String, V> {
         balance
             .into_iter()
-            .map(|(k, v)| {
-                let t = self.get_asset_from_str(&k).map(|a| a.ticker()).unwrap_or(k);
-                (t, v)
-            })
+            .map(|(k, v)| (self.ticker_or_id(k), v))
             .collect()
     }
 
+    /// The ticker of the asset `id`, or `id` itself if the asset has no known ticker
+    pub fn ticker_or_id(&self, id: String) -> String {
+        self.get_asset_from_str(&id)
+            .map(|a| a.ticker())
+            .unwrap_or(id)
+    }
+
+    /// Record a payment built or broadcast at `timestamp`, if the duplicate payment window is set
+    pub fn record_payment(
+        &mut self,
+        recipients: PaymentRecipients,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        let Some(window) = self.config.duplicate_payment_window else {
+            return Ok(());
+        };
+        if recipients.is_empty() {
+            return Ok(());
+        }
+        let record = payment_record(&recipients, timestamp);
+        self.recent_payments
+            .insert(recipients, timestamp, window.as_secs());
+        self.persist(Request {
+            jsonrpc: "2.0".into(),
+            id: None,
+            method: Method::PaymentRecord.to_string(),
+            params: Some(serde_json::to_value(record)?),
+        })
+    }
+
+    /// Append `data` to the state file
+    ///
+    /// The state file contains secrets, such as the mnemonics of persisted signers and the tokens
+    /// of remote signers, on unix it is readable only by its owner.
     pub fn persist<T: Serialize>(&mut self, data: T) -> Result<(), Error> {
         if self.do_persist {
             let data = serde_json::to_string(&data)?;
             let path = self.config.state_path()?;
-            let mut file = OpenOptions::new()
+            let mut file = state_file_options()
                 .create_new(!path.exists())
                 .append(true)
                 .open(path)?;
@@ -609,7 +1401,11 @@ impl State {
             .expect("Clock may have gone backwards")
             .as_millis();
         temp.set_file_name(millis.to_string());
-        let mut file = File::create(&temp)?;
+        let mut file = state_file_options()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&temp)?;
         for req in self.as_requests()? {
             let data = serde_json::to_string(&req)?;
             writeln!(file, "{}", data)?;
@@ -626,8 +1422,9 @@ impl State {
         // Wollets
         for (n, w) in self.wollets.iter() {
             let params = request::WalletLoad {
-                descriptor: w.descriptor().to_string(),
+                descriptor: w.wollet_descriptor().to_string(),
                 name: n.to_string(),
+                gap_limit: Some(w.gap_limit()),
             };
             let r = Request {
                 jsonrpc: "2.0".into(),
@@ -674,6 +1471,90 @@ impl State {
             }
         }
 
+        // Reserved addresses
+        for name in self.reserved_addrs.names() {
+            for (index, count) in self.reserved_addrs.ranges(name) {
+                let params = request::WalletAddressReserve {
+                    name: name.to_string(),
+                    count,
+                    index: Some(index),
+                };
+                let r = Request {
+                    jsonrpc: "2.0".into(),
+                    id: None,
+                    method: Method::WalletAddressReserve.to_string(),
+                    params: Some(serde_json::to_value(params)?),
+                };
+                requests.push(r);
+            }
+        }
+
+        // Verified addresses
+        for (name, verified) in self.verified_addrs.iter() {
+            for (index, signer) in verified.iter() {
+                let params = request::WalletSetAddrVerified {
+                    name: name.to_string(),
+                    index: *index,
+                    signer: signer.to_string(),
+                };
+                let r = Request {
+                    jsonrpc: "2.0".into(),
+                    id: None,
+                    method: Method::WalletSetAddrVerified.to_string(),
+                    params: Some(serde_json::to_value(params)?),
+                };
+                requests.push(r);
+            }
+        }
+
+        // Recent payments
+        for (recipients, timestamp) in self.recent_payments.iter() {
+            let r = Request {
+                jsonrpc: "2.0".into(),
+                id: None,
+                method: Method::PaymentRecord.to_string(),
+                params: Some(serde_json::to_value(payment_record(
+                    recipients, *timestamp,
+                ))?),
+            };
+            requests.push(r);
+        }
+
+        // Signing sessions
+        let mut sessions: Vec<_> = self.signing_sessions.iter().collect();
+        sessions.sort_by_key(|s| s.timestamp);
+        for session in sessions {
+            let r = Request {
+                jsonrpc: "2.0".into(),
+                id: None,
+                method: Method::SignerSessionRecord.to_string(),
+                params: Some(serde_json::to_value(session.record())?),
+            };
+            requests.push(r);
+        }
+
+        // Contacts
+        for (name, contact) in self.contacts.iter() {
+            let r = Request {
+                jsonrpc: "2.0".into(),
+                id: None,
+                method: Method::ContactAdd.to_string(),
+                params: Some(serde_json::to_value(contact.request(name))?),
+            };
+            requests.push(r);
+        }
+
+        // Drafts
+        for (name, draft) in self.drafts.iter() {
+            let r = Request {
+                jsonrpc: "2.0".into(),
+                id: None,
+                method: Method::DraftSave.to_string(),
+                params: Some(serde_json::to_value(draft.request(name, false))?),
+            };
+            requests.push(r);
+        }
+
         // Signers
         for (n, s) in self.signers.iter() {
             let (params, method) = match &s.inner {
@@ -687,13 +1568,18 @@ impl State {
                 }
                 AppSignerInner::AvailableSigner(a) => match a {
                     AnySigner::Software(a) => {
-                        let params = request::SignerLoadSoftware {
-                            name: n.to_string(),
-                            mnemonic: a
+                        let mnemonic = match s.mnemonic_file {
+                            Some(_) => String::new(),
+                            None => a
                                 .mnemonic()
                                 .expect("we only create signers from mnemonic")
                                 .to_string(),
+                        };
+                        let params = request::SignerLoadSoftware {
+                            name: n.to_string(),
+                            mnemonic,
                             persist: s.persist,
+                            mnemonic_file: s.mnemonic_file.clone(),
                         };
                         (serde_json::to_value(params)?, Method::SignerLoadSoftware)
                     }
@@ -705,8 +1591,13 @@ impl State {
                         };
                         (serde_json::to_value(params)?, Method::SignerLoadJade)
                     }
-                    #[allow(unreachable_patterns)]
-                    _ => todo!(),
+                    AnySigner::Ledger(l, _) => {
+                        let params = request::SignerLoadLedger {
+                            name: n.to_string(),
+                            emulator: l.port(),
+                        };
+                        (serde_json::to_value(params)?, Method::SignerLoadLedger)
+                    }
                 },
                 AppSignerInner::ExternalSigner(f) => {
                     let params = request::SignerLoadExternal {
@@ -715,6 +1606,10 @@ impl State {
                     };
                     (serde_json::to_value(params)?, Method::SignerLoadExternal)
                 }
+                AppSignerInner::RemoteSigner(r) => {
+                    let params = r.request(n);
+                    (serde_json::to_value(params)?, Method::SignerLoadRemote)
+                }
             };
 
             let r = Request {
@@ -744,3 +1639,302 @@ impl State {
         self.signers.get_available(name, timeout)
     }
 }
+
+/// The options to open the state file, readable and writable only by the owner on unix
+fn state_file_options() -> OpenOptions {
+    let mut options = OpenOptions::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use lwk_wollet::elements::TxOut;
+    use lwk_wollet::Entity;
+
+    use super::*;
+
+    #[test]
+    fn test_registry_entries() {
+        let contract = |ticker: &str, domain: &str| Contract {
+            entity: Entity::Domain(domain.to_string()),
+            issuer_pubkey: vec![2; 33],
+            name: "Asset".to_string(),
+            precision: 8,
+            ticker: ticker.to_string(),
+            version: 0,
+        };
+        let asset_id = AssetId::default();
+        let local = contract("TCKR", "example.com");
+        let changed = contract("TCKR", "example.org");
+        let mut entries = RegistryEntries::default();
+
+        // Unchanged
+        assert!(entries.update(asset_id, &local, Some(&local), 1).is_none());
+
+        let event = entries.update(asset_id, &local, Some(&changed), 2).unwrap();
+        assert_eq!(event.kind, response::AssetEventKind::Changed);
+        assert_eq!(event.domain, "example.com");
+        assert_eq!(event.registry_domain.as_deref(), Some("example.org"));
+        assert_eq!(event.timestamp, 2);
+        // Only notified once
+        assert!(entries
+            .update(asset_id, &local, Some(&changed), 3)
+            .is_none());
+
+        let event = entries.update(asset_id, &local, None, 4).unwrap();
+        assert_eq!(event.kind, response::AssetEventKind::Revoked);
+        assert_eq!(event.registry_ticker, None);
+        assert!(entries.update(asset_id, &local, None, 5).is_none());
+
+        // Restored
+        assert!(entries.update(asset_id, &local, Some(&local), 6).is_none());
+        assert!(entries.update(asset_id, &local, None, 7).is_some());
+    }
+
+    #[test]
+    fn test_drafts() {
+        let draft = |wallet: &str, timestamp: u64| AppDraft {
+            pset: PartiallySignedTransaction::new_v2(),
+            wallet: wallet.to_string(),
+            creator: Some("alice".to_string()),
+            timestamp,
+        };
+        let mut drafts = Drafts::default();
+        drafts.insert("a", draft("w1", 1), false).unwrap();
+        drafts.insert("b", draft("w2", 2), false).unwrap();
+        let err = drafts.insert("a", draft("w1", 3), false).unwrap_err();
+        assert_eq!(err.to_string(), "Draft 'a' already exists");
+        drafts.insert("a", draft("w1", 3), true).unwrap();
+        assert_eq!(drafts.get("a").unwrap().timestamp, 3);
+
+        // The persisted request keeps the time of the save
+        let r = drafts.get("a").unwrap().request("a", false);
+        assert_eq!(r.timestamp, Some(3));
+        assert_eq!(r.pset, PartiallySignedTransaction::new_v2().to_string());
+
+        drafts.remove_wollet("w1");
+        let err = drafts.get("a").err().unwrap();
+        assert_eq!(err.to_string(), "Draft 'a' does not exist");
+        assert_eq!(drafts.iter().count(), 1);
+        drafts.remove("b").unwrap();
+        assert!(drafts.remove("b").is_err());
+    }
+
+    #[test]
+    fn test_recent_payments() {
+        let payment = |satoshi: u64| vec![("addr".to_string(), satoshi, "asset".to_string())];
+        let mut recent = RecentPayments::default();
+        assert_eq!(recent.duplicate(&payment(1), 60, 100), None);
+        recent.insert(payment(1), 100, 60);
+
+        assert_eq!(recent.duplicate(&payment(1), 60, 130), Some(30));
+        assert_eq!(recent.duplicate(&payment(2), 60, 130), None);
+        recent.insert(payment(1), 140, 60);
+        // The most recent one is reported
+        assert_eq!(recent.duplicate(&payment(1), 60, 150), Some(10));
+
+        // Out of the window
+        assert_eq!(recent.duplicate(&payment(1), 60, 180), Some(40));
+        assert_eq!(recent.duplicate(&payment(1), 60, 201), None);
+        assert!(recent.0.is_empty());
+
+        // Inserting discards the old payments too
+        recent.insert(payment(1), 300, 60);
+        recent.insert(payment(2), 400, 60);
+        assert_eq!(recent.iter().count(), 1);
+
+        // Roundtrip through the request persisted
+        let record = payment_record(&payment(2), 400);
+        let (recipients, timestamp) = payment_from_record(record);
+        assert_eq!(recipients, payment(2));
+        assert_eq!(timestamp, 400);
+    }
+
+    #[test]
+    fn test_signing_sessions() {
+        let session = |timestamp| AppSigningSession {
+            pending: PendingSignatures {
+                id: String::new(),
+                pset: PartiallySignedTransaction::new_v2(),
+                requests: vec![],
+            },
+            fingerprint: Fingerprint::default(),
+            timestamp,
+        };
+        let mut sessions = SigningSessions::default();
+        sessions.insert("a".to_string(), session(100));
+        assert!(sessions.get("a", 100).is_ok());
+        assert!(sessions.get("b", 100).is_err());
+        assert!(sessions.get("a", 100 + SIGNING_SESSION_EXPIRY).is_err());
+
+        // Expired sessions are dropped
+        sessions.insert("b".to_string(), session(100 + SIGNING_SESSION_EXPIRY));
+        assert_eq!(sessions.0.len(), 1);
+
+        // The oldest session is dropped
+        for i in 0..MAX_SIGNING_SESSIONS as u64 {
+            sessions.insert(i.to_string(), session(200 + SIGNING_SESSION_EXPIRY + i));
+        }
+        assert_eq!(sessions.0.len(), MAX_SIGNING_SESSIONS);
+        assert!(!sessions.0.contains_key("b"));
+
+        sessions.remove("0");
+        assert!(sessions.get("0", 200 + SIGNING_SESSION_EXPIRY).is_err());
+
+        // Expired sessions are not restored
+        let now = 300 + 2 * SIGNING_SESSION_EXPIRY;
+        sessions.restore("old".to_string(), session(300), now);
+        assert!(!sessions.0.contains_key("old"));
+        sessions.restore("new".to_string(), session(now), now);
+        assert!(sessions.get("new", now).is_ok());
+    }
+
+    #[test]
+    fn test_verified_addrs() {
+        let mut verified = VerifiedAddrs::default();
+        verified.set("w1", 0, "jade");
+        verified.set("w1", 1, "ledger");
+        verified.set("w2", 0, "jade");
+
+        verified.remove_signer("jade");
+        assert_eq!(verified.get("w1", 0), None);
+        assert_eq!(verified.get("w1", 1), Some(&"ledger".to_string()));
+        assert_eq!(verified.get("w2", 0), None);
+        assert_eq!(verified.iter().count(), 1);
+
+        verified.remove("w1");
+        assert_eq!(verified.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_reserved_addrs() {
+        let mut reserved = ReservedAddrs::default();
+        reserved.reserve("w1", 0..3);
+        reserved.reserve("w1", 5..7);
+        reserved.reserve("w2", [4]);
+        assert_eq!(reserved.ranges("w1"), vec![(0, 3), (5, 2)]);
+        assert_eq!(reserved.ranges("w2"), vec![(4, 1)]);
+        assert_eq!(reserved.next("w1"), 7);
+
+        reserved.release_below("w1", 2);
+        assert_eq!(reserved.ranges("w1"), vec![(2, 1), (5, 2)]);
+        assert!(!reserved.is_reserved("w1", 1));
+
+        reserved.release_below("w2", 5);
+        assert!(reserved.ranges("w2").is_empty());
+        assert_eq!(reserved.names().collect::<Vec<_>>(), vec!["w1"]);
+    }
+
+    #[test]
+    fn test_contacts_advance_paid() {
+        let params = &AddressParams::ELEMENTS;
+        let desc = "ct(slip77(ab5824f4477b4ebb00a132adfd8eb0b7935cf24f6ac151add5d1913db374ce92),elwpkh([759db348/84'/1'/0']tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/<0;1>/*))";
+        let descriptor = WolletDescriptor::from_str(desc).unwrap();
+        let address = |i| descriptor.address(i, params).unwrap();
+        let tx_paying = |address: Address| Transaction {
+            version: 2,
+            lock_time: lwk_wollet::elements::LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut {
+                script_pubkey: address.script_pubkey(),
+                ..Default::default()
+            }],
+        };
+        let index = |contacts: &Contacts| match contacts.get("bob").unwrap() {
+            AppContact::Descriptor { next_index, .. } => *next_index,
+            AppContact::Address(_) => unreachable!(),
+        };
+        let mut contacts = Contacts::default();
+        contacts
+            .insert(
+                "bob",
+                AppContact::Descriptor {
+                    descriptor: descriptor.clone(),
+                    next_index: 0,
+                },
+            )
+            .unwrap();
+
+        // A transaction not paying the contact does not advance it
+        assert!(!contacts
+            .advance_paid(&tx_paying(address(1)), params)
+            .unwrap());
+        assert_eq!(index(&contacts), 0);
+
+        assert!(contacts
+            .advance_paid(&tx_paying(address(0)), params)
+            .unwrap());
+        assert_eq!(index(&contacts), 1);
+
+        // Paying an old address again does not advance it
+        assert!(!contacts
+            .advance_paid(&tx_paying(address(0)), params)
+            .unwrap());
+        assert_eq!(index(&contacts), 1);
+    }
+
+    #[test]
+    fn test_notifications() {
+        use response::NotificationKind::*;
+        let txid = |n: u8| Txid::from_str(&format!("{n:02x}").repeat(32)).unwrap();
+        let snapshot = |status: u64, tip: u32, txs: &[(u8, Option<u32>)]| WolletSnapshot {
+            status,
+            tip,
+            txs: txs.iter().map(|(n, h)| (txid(*n), *h)).collect(),
+        };
+        let kinds =
+            |v: Vec<response::Notification>| v.into_iter().map(|n| n.kind).collect::<Vec<_>>();
+        let mut notifications = Notifications::default();
+
+        // First scan, existing transactions are not notified
+        let s0 = snapshot(0, 0, &[]);
+        let s1 = snapshot(1, 100, &[(1, Some(90))]);
+        notifications.wollet_changes("w1", &s0, &s1, 1);
+        assert_eq!(
+            kinds(notifications.since(0, None)),
+            vec![Block, WalletSynced]
+        );
+        assert_eq!(notifications.last_id(), 2);
+
+        // Nothing changed
+        notifications.wollet_changes("w1", &s1, &s1, 2);
+        assert_eq!(notifications.last_id(), 2);
+
+        let s2 = snapshot(2, 100, &[(1, Some(90)), (2, None)]);
+        notifications.wollet_changes("w1", &s1, &s2, 3);
+        let received = notifications.since(2, None);
+        assert_eq!(kinds(received.clone()), vec![TxReceived, WalletSynced]);
+        assert_eq!(received[0].txid, Some(txid(2).to_string()));
+        assert_eq!(received[0].wallet.as_deref(), Some("w1"));
+
+        let s3 = snapshot(3, 101, &[(1, Some(90)), (2, Some(101))]);
+        notifications.wollet_changes("w1", &s2, &s3, 4);
+        let confirmed = notifications.since(4, None);
+        assert_eq!(
+            kinds(confirmed.clone()),
+            vec![Block, TxConfirmed, WalletSynced]
+        );
+        assert_eq!(confirmed[1].height, Some(101));
+
+        // The block is notified once for all the wallets
+        notifications.wollet_changes("w2", &s0, &s3, 5);
+        assert_eq!(kinds(notifications.since(7, None)), vec![WalletSynced]);
+        assert_eq!(
+            kinds(notifications.since(4, Some("w2"))),
+            vec![Block, WalletSynced]
+        );
+        assert!(notifications
+            .since(notifications.last_id(), None)
+            .is_empty());
+
+        for i in 0..MAX_NOTIFICATIONS as u64 {
+            notifications.wollet_changes("w1", &s1, &s2, i);
+        }
+        assert_eq!(notifications.since(0, None).len(), MAX_NOTIFICATIONS);
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{LwkError, Wollet, WolletDescriptor};
+
+/// A client syncing client-side encrypted backups of the wallet labels and of the application
+/// memos to a WebDAV server or to an S3 bucket accepting unsigned requests, wrapper over
+/// [`lwk_wollet::BackupClient`]
+#[derive(uniffi::Object)]
+pub struct BackupClient {
+    inner: lwk_wollet::BackupClient,
+}
+
+#[uniffi::export]
+impl BackupClient {
+    /// Construct a client storing the backups under `base_url`, adding `headers` to every
+    /// request, for instance `Authorization`
+    #[uniffi::constructor]
+    pub fn new(base_url: &str, headers: HashMap<String, String>) -> Arc<Self> {
+        let inner = headers.iter().fold(
+            lwk_wollet::BackupClient::new(base_url),
+            |c, (name, value)| c.with_header(name, value),
+        );
+        Arc::new(Self { inner })
+    }
+
+    /// The last revision uploaded for the wallet with the given descriptor, if any
+    pub fn latest_revision(&self, descriptor: &WolletDescriptor) -> Result<Option<u32>, LwkError> {
+        Ok(self.inner.latest_revision(&descriptor.into())?)
+    }
+
+    /// Encrypt and upload the labels of `wollet` and `memos` as a new revision, returns the
+    /// revision number
+    pub fn upload(&self, wollet: &Wollet, memos: HashMap<String, String>) -> Result<u32, LwkError> {
+        let backup = wollet.inner_wollet()?.backup(memos.into_iter().collect());
+        Ok(self.inner.upload(&backup)?)
+    }
+
+    /// Download the backup of `wollet` at `revision`, or the latest one if missing, and restore
+    /// its labels in `wollet`, returns the memos of the backup
+    ///
+    /// The wallet must have been synced at least once.
+    pub fn restore(
+        &self,
+        wollet: &Wollet,
+        revision: Option<u32>,
+    ) -> Result<HashMap<String, String>, LwkError> {
+        let descriptor = wollet.inner_wollet()?.wollet_descriptor();
+        let backup = self.inner.download(&descriptor, revision)?;
+        wollet.inner_wollet()?.restore_backup(&backup)?;
+        Ok(backup.memos.into_iter().collect())
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{LwkError, Script};
-use std::{fmt::Display, sync::Arc};
+use std::{fmt::Display, str::FromStr, sync::Arc};
 
 #[derive(uniffi::Object)]
 #[uniffi::export(Display)]
@@ -7,6 +7,55 @@ pub struct Address {
     inner: elements::Address,
 }
 
+/// The type of script an address pays to, see [`lwk_common::AddressScriptType`]
+#[derive(uniffi::Enum, Debug, PartialEq, Eq)]
+pub enum AddressScriptType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    Unknown,
+}
+
+impl From<lwk_common::AddressScriptType> for AddressScriptType {
+    fn from(value: lwk_common::AddressScriptType) -> Self {
+        match value {
+            lwk_common::AddressScriptType::P2pkh => AddressScriptType::P2pkh,
+            lwk_common::AddressScriptType::P2sh => AddressScriptType::P2sh,
+            lwk_common::AddressScriptType::P2wpkh => AddressScriptType::P2wpkh,
+            lwk_common::AddressScriptType::P2wsh => AddressScriptType::P2wsh,
+            lwk_common::AddressScriptType::P2tr => AddressScriptType::P2tr,
+            lwk_common::AddressScriptType::Unknown => AddressScriptType::Unknown,
+        }
+    }
+}
+
+/// Details of an address, see [`lwk_common::AddressKind`]
+#[derive(uniffi::Record, Debug, PartialEq, Eq)]
+pub struct AddressKind {
+    pub script_type: AddressScriptType,
+
+    pub is_confidential: bool,
+
+    /// One of "liquid", "liquid-testnet" or "elements-regtest", none for custom networks
+    pub network: Option<String>,
+
+    /// The blinding public key in hex, if the address is confidential
+    pub blinding_pubkey: Option<String>,
+}
+
+impl From<lwk_common::AddressKind> for AddressKind {
+    fn from(value: lwk_common::AddressKind) -> Self {
+        Self {
+            script_type: value.script_type.into(),
+            is_confidential: value.is_confidential,
+            network: value.network.map(|n| n.to_string()),
+            blinding_pubkey: value.blinding_pubkey.map(|p| p.to_string()),
+        }
+    }
+}
+
 impl From<elements::Address> for Address {
     fn from(inner: elements::Address) -> Self {
         Self { inner }
@@ -58,6 +107,19 @@ impl Address {
         Arc::new(self.inner.to_unconfidential().into())
     }
 
+    /// Details of the address, such as its script type and network
+    pub fn kind(&self) -> AddressKind {
+        lwk_common::address_kind(&self.inner).into()
+    }
+
+    /// Check a proof, as returned by `Wollet::blinding_key_proof()`, that the owner of the blinding
+    /// private key of this confidential address has signed its script
+    pub fn verify_blinding_key_proof(&self, proof: &str) -> Result<(), LwkError> {
+        let proof = elements::secp256k1_zkp::ecdsa::Signature::from_str(proof)
+            .map_err(|e| LwkError::Input { msg: e.to_string() })?;
+        Ok(lwk_common::verify_blinding_key_proof(&self.inner, &proof)?)
+    }
+
     /// Returns a string encoding an image in a uri
     ///
     /// The string can be open in the browser or be used as `src` field in `img` in HTML
@@ -81,7 +143,7 @@ impl Address {
 #[cfg(test)]
 mod tests {
 
-    use super::Address;
+    use super::{Address, AddressScriptType};
 
     #[test]
     fn address() {
@@ -97,6 +159,12 @@ mod tests {
 
         assert!(address.is_blinded());
 
+        let kind = address.kind();
+        assert_eq!(kind.script_type, AddressScriptType::P2wpkh);
+        assert!(kind.is_confidential);
+        assert_eq!(kind.network.as_deref(), Some("liquid-testnet"));
+        assert!(kind.blinding_pubkey.is_some());
+
         assert_eq!(
             address.to_unconfidential().to_string(),
             "tex1q6rz28mcfaxtmd6v789l9rrlrusdprr9p634wu8"
@@ -13,6 +13,12 @@ impl From<elements::OutPoint> for OutPoint {
     }
 }
 
+impl From<&OutPoint> for elements::OutPoint {
+    fn from(out_point: &OutPoint) -> Self {
+        out_point.inner
+    }
+}
+
 impl Display for OutPoint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.inner)
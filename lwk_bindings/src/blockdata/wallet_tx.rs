@@ -1,6 +1,81 @@
-use crate::{types::AssetId, Transaction, Txid, WalletTxOut};
+use crate::{types::AssetId, AssetInfo, Transaction, Txid, WalletTxOut};
 use std::{collections::HashMap, sync::Arc};
 
+/// Heuristics on an unconfirmed transaction, see [`lwk_wollet::ZeroConfRisk`]
+#[derive(uniffi::Record, Debug, PartialEq, Eq)]
+pub struct ZeroConfRisk {
+    /// Fee rate in satoshi per 1000 virtual bytes
+    pub fee_rate: u64,
+
+    /// Whether any input signals replaceability
+    pub replaceable: bool,
+
+    /// The length of the longest chain of unconfirmed ancestors known by the wallet
+    pub unconfirmed_depth: u32,
+}
+
+impl From<lwk_wollet::ZeroConfRisk> for ZeroConfRisk {
+    fn from(value: lwk_wollet::ZeroConfRisk) -> Self {
+        Self {
+            fee_rate: value.fee_rate,
+            replaceable: value.replaceable,
+            unconfirmed_depth: value.unconfirmed_depth,
+        }
+    }
+}
+
+/// The balance of an asset split by confirmation state, see [`lwk_wollet::Balance`]
+#[derive(uniffi::Record, Debug, PartialEq, Eq)]
+pub struct Balance {
+    /// Amount of the outputs created by confirmed transactions and not spent by confirmed
+    /// transactions
+    pub confirmed: u64,
+
+    /// Amount of the outputs created by unconfirmed transactions and not spent yet
+    pub unconfirmed_incoming: u64,
+
+    /// Amount of the outputs created by confirmed transactions and spent by unconfirmed
+    /// transactions
+    pub unconfirmed_outgoing: u64,
+}
+
+impl From<lwk_wollet::Balance> for Balance {
+    fn from(value: lwk_wollet::Balance) -> Self {
+        Self {
+            confirmed: value.confirmed,
+            unconfirmed_incoming: value.unconfirmed_incoming,
+            unconfirmed_outgoing: value.unconfirmed_outgoing,
+        }
+    }
+}
+
+/// Whether a wallet transaction is confirmed, in the mempool or conflicted, see
+/// [`lwk_wollet::TxStatus`]
+#[derive(uniffi::Enum, Debug, PartialEq, Eq)]
+pub enum TxStatus {
+    /// Unconfirmed, the transaction is in the mempool
+    Mempool,
+
+    /// Confirmed in the block at the given height
+    Confirmed { height: u32 },
+
+    /// Evicted from the mempool or, if `replaced_by` is present, replaced by another wallet
+    /// transaction
+    Conflicted { replaced_by: Option<Arc<Txid>> },
+}
+
+impl From<lwk_wollet::TxStatus> for TxStatus {
+    fn from(value: lwk_wollet::TxStatus) -> Self {
+        match value {
+            lwk_wollet::TxStatus::Mempool => TxStatus::Mempool,
+            lwk_wollet::TxStatus::Confirmed(height) => TxStatus::Confirmed { height },
+            lwk_wollet::TxStatus::Conflicted { replaced_by } => TxStatus::Conflicted {
+                replaced_by: replaced_by.map(|t| Arc::new(t.into())),
+            },
+        }
+    }
+}
+
 #[derive(uniffi::Object, Debug)]
 pub struct WalletTx {
     inner: lwk_wollet::WalletTx,
@@ -63,9 +138,38 @@ impl WalletTx {
             .collect()
     }
 
+    /// Heuristics on the risk of relying on this transaction, present only if unconfirmed
+    pub fn zero_conf(&self) -> Option<ZeroConfRisk> {
+        self.inner.zero_conf.clone().map(Into::into)
+    }
+
+    /// Whether the output with the same index has been received on an already used wallet address
+    pub fn address_reused(&self) -> Vec<bool> {
+        self.inner.address_reused.clone()
+    }
+
+    /// Registry info of the assets in `balance`, if cached in the wallet
+    pub fn assets(&self) -> HashMap<AssetId, AssetInfo> {
+        self.inner
+            .assets
+            .iter()
+            .map(|(k, v)| (AssetId::from(*k), v.clone().into()))
+            .collect()
+    }
+
+    /// Whether the transaction is confirmed, in the mempool or conflicted
+    pub fn status(&self) -> TxStatus {
+        self.inner.status.clone().into()
+    }
+
     pub fn unblinded_url(&self, explorer_url: &str) -> String {
         self.inner.unblinded_url(explorer_url)
     }
+
+    /// The data of the OP_RETURN memo outputs of the transaction
+    pub fn memos_opreturn(&self) -> Vec<Vec<u8>> {
+        self.inner.memos_opreturn()
+    }
 }
 
 #[cfg(test)]
@@ -105,6 +209,10 @@ mod tests {
             timestamp: Some(124),
             inputs: vec![Some(tx_out.clone())],
             outputs: vec![None, Some(tx_out.clone())],
+            zero_conf: None,
+            address_reused: vec![false, true],
+            assets: BTreeMap::new(),
+            status: lwk_wollet::TxStatus::Confirmed(4),
         };
 
         let wallet_tx: WalletTx = el.clone().into();
@@ -124,5 +232,13 @@ mod tests {
         assert_eq!(wallet_tx.inputs().len(), 1);
 
         assert_eq!(wallet_tx.outputs().len(), 2);
+
+        assert_eq!(wallet_tx.zero_conf(), None);
+
+        assert_eq!(wallet_tx.address_reused(), vec![false, true]);
+
+        assert_eq!(wallet_tx.status(), crate::TxStatus::Confirmed { height: 4 });
+
+        assert_eq!(wallet_tx.assets(), HashMap::new());
     }
 }
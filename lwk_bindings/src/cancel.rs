@@ -0,0 +1,36 @@
+use std::sync::Arc;
+
+/// Wrapper over [`lwk_wollet::CancellationToken`]
+///
+/// Pass it to the `*_cancellable` methods of the clients and call [`CancellationToken::cancel()`]
+/// to abort them, for instance when the app goes to background. These methods are blocking, run
+/// them in a background thread and cancel the token from another one; they return an error at
+/// the next check of the token, a request already sent to the server is not interrupted.
+#[derive(uniffi::Object, Debug, Clone, Default)]
+pub struct CancellationToken {
+    inner: lwk_wollet::CancellationToken,
+}
+
+impl AsRef<lwk_wollet::CancellationToken> for CancellationToken {
+    fn as_ref(&self) -> &lwk_wollet::CancellationToken {
+        &self.inner
+    }
+}
+
+#[uniffi::export]
+impl CancellationToken {
+    /// Construct a new, not cancelled, token
+    #[uniffi::constructor]
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Request the cancellation of the operations using this token
+    pub fn cancel(&self) {
+        self.inner.cancel()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.is_cancelled()
+    }
+}
@@ -29,6 +29,48 @@ impl From<&Contract> for lwk_wollet::Contract {
     }
 }
 
+/// Registry info of an asset, see [`lwk_wollet::AssetInfo`]
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq)]
+pub struct AssetInfo {
+    pub name: String,
+
+    pub ticker: String,
+
+    /// Number of decimal digits of the asset amounts
+    pub precision: u8,
+
+    /// Domain of the asset issuer
+    pub domain: String,
+
+    /// Whether the contract has been verified to commit to the asset with the issuance
+    /// transaction, unverified info may spoof the ticker of another asset
+    pub verified: bool,
+}
+
+impl From<lwk_wollet::AssetInfo> for AssetInfo {
+    fn from(value: lwk_wollet::AssetInfo) -> Self {
+        Self {
+            name: value.name,
+            ticker: value.ticker,
+            precision: value.precision,
+            domain: value.domain,
+            verified: value.verified,
+        }
+    }
+}
+
+impl From<AssetInfo> for lwk_wollet::AssetInfo {
+    fn from(value: AssetInfo) -> Self {
+        Self {
+            name: value.name,
+            ticker: value.ticker,
+            precision: value.precision,
+            domain: value.domain,
+            verified: value.verified,
+        }
+    }
+}
+
 impl std::fmt::Display for Contract {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let json = serde_json::to_string(&self.inner).expect("contain simple types");
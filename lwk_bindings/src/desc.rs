@@ -1,6 +1,6 @@
 use std::{fmt, str::FromStr, sync::Arc};
 
-use crate::LwkError;
+use crate::{Chain, LwkError, Script};
 
 /// The output descriptors, wrapper over [`lwk_wollet::WolletDescriptor`]
 #[derive(uniffi::Object)]
@@ -34,6 +34,62 @@ impl WolletDescriptor {
         let inner = lwk_wollet::WolletDescriptor::from_str(descriptor)?;
         Ok(Arc::new(WolletDescriptor { inner }))
     }
+
+    /// Whether the descriptor has a blinding key, false for descriptors without `ct()`
+    pub fn is_confidential(&self) -> bool {
+        self.inner.is_confidential()
+    }
+
+    /// Derive the scripts at the wildcard indexes from `start` to `end` excluded, with their
+    /// blinding keys, first the external scripts and then the internal ones
+    pub fn scripts(&self, start: u32, end: u32) -> Result<Vec<Arc<DerivedScript>>, LwkError> {
+        Ok(self
+            .inner
+            .scripts(start..end)?
+            .into_iter()
+            .map(Into::into)
+            .map(Arc::new)
+            .collect())
+    }
+}
+
+/// A script of the wallet with its blinding keys, wrapper over [`lwk_wollet::DerivedScript`]
+#[derive(uniffi::Object)]
+pub struct DerivedScript {
+    inner: lwk_wollet::DerivedScript,
+}
+
+impl From<lwk_wollet::DerivedScript> for DerivedScript {
+    fn from(inner: lwk_wollet::DerivedScript) -> Self {
+        Self { inner }
+    }
+}
+
+#[uniffi::export]
+impl DerivedScript {
+    pub fn ext_int(&self) -> Chain {
+        self.inner.ext_int.into()
+    }
+
+    pub fn wildcard_index(&self) -> u32 {
+        self.inner.wildcard_index
+    }
+
+    pub fn script_pubkey(&self) -> Arc<Script> {
+        Arc::new(self.inner.script_pubkey.clone().into())
+    }
+
+    /// The blinding public key in hex, if the descriptor is confidential
+    pub fn blinding_pubkey(&self) -> Option<String> {
+        self.inner.blinding_pubkey.map(|k| k.to_string())
+    }
+
+    /// The blinding private key in hex, if the descriptor has it
+    pub fn blinding_key(&self) -> Option<String> {
+        self.inner
+            .blinding_key
+            .map(|k| k.display_secret().to_string())
+    }
 }
 
 impl fmt::Display for WolletDescriptor {
@@ -62,5 +118,11 @@ mod tests {
         let wollet_desc = lwk_wollet::WolletDescriptor::from_str(exp).unwrap();
         let desc: WolletDescriptor = wollet_desc.into();
         assert_eq!(desc.to_string(), exp);
+
+        let scripts = desc.scripts(0, 2).unwrap();
+        assert_eq!(scripts.len(), 4);
+        assert_eq!(scripts[3].ext_int(), crate::Chain::Internal);
+        assert_eq!(scripts[3].wildcard_index(), 1);
+        assert_eq!(scripts[3].blinding_key().unwrap().len(), 64);
     }
 }
@@ -1,8 +1,9 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use lwk_wollet::BlockchainBackend;
 
-use crate::{LwkError, Transaction, Txid, Update, Wollet};
+use crate::{CancellationToken, LwkError, Transaction, Txid, Update, Wollet};
 
 /// Wrapper over [`lwk_wollet::ElectrumClient`]
 #[derive(uniffi::Object, Debug)]
@@ -30,9 +31,54 @@ impl ElectrumClient {
         Ok(Arc::new(self.inner.lock()?.broadcast(tx.as_ref())?.into()))
     }
 
+    /// Fee rates estimated by the server in sat/kvb, by confirmation target in blocks
+    pub fn fee_estimates(&self) -> Result<HashMap<u16, f32>, LwkError> {
+        Ok(self.inner.lock()?.fee_estimates()?.into_iter().collect())
+    }
+
+    /// Broadcast the transaction, unless `cancel` has been cancelled before sending it
+    pub fn broadcast_cancellable(
+        &self,
+        tx: &Transaction,
+        cancel: &CancellationToken,
+    ) -> Result<Arc<Txid>, LwkError> {
+        let txid = self
+            .inner
+            .lock()?
+            .broadcast_cancellable(tx.as_ref(), cancel.as_ref())?;
+        Ok(Arc::new(txid.into()))
+    }
+
     pub fn full_scan(&self, wollet: &Wollet) -> Result<Option<Arc<Update>>, LwkError> {
         let wollet = wollet.inner_wollet()?;
         let update: Option<lwk_wollet::Update> = self.inner.lock()?.full_scan(&wollet)?;
         Ok(update.map(Into::into).map(Arc::new))
     }
+
+    /// Scan the blockchain like `full_scan`, but abort once `cancel` is cancelled
+    ///
+    /// The token is checked between the batches of requests to the server, a batch already sent
+    /// is completed before the scan is aborted. The wallet is not modified by a cancelled scan.
+    pub fn full_scan_cancellable(
+        &self,
+        wollet: &Wollet,
+        cancel: &CancellationToken,
+    ) -> Result<Option<Arc<Update>>, LwkError> {
+        let wollet = wollet.inner_wollet()?;
+        let update: Option<lwk_wollet::Update> = self
+            .inner
+            .lock()?
+            .full_scan_cancellable(&wollet, cancel.as_ref())?;
+        Ok(update.map(Into::into).map(Arc::new))
+    }
+
+    /// Scan the blockchain and apply the result to the wallet in chunks, holding at most about
+    /// `memory_budget` bytes of new transactions at once
+    ///
+    /// Useful for large wallets on devices with little memory. Returns whether the wallet changed.
+    pub fn full_scan_chunked(&self, wollet: &Wollet, memory_budget: u64) -> Result<bool, LwkError> {
+        let mut wollet = wollet.inner_wollet()?;
+        let budget = usize::try_from(memory_budget).unwrap_or(usize::MAX);
+        Ok(self.inner.lock()?.full_scan_chunked(&mut wollet, budget)?)
+    }
 }
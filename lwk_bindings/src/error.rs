@@ -1,8 +1,12 @@
 use std::sync::{MutexGuard, PoisonError};
 
 use elements::pset::ParseError;
+use lwk_common::ErrorCategory;
 
 /// Possible errors emitted
+///
+/// Errors coming from the library are split by category, so that the caller can decide how
+/// to react without parsing the message.
 #[derive(uniffi::Error, thiserror::Error, Debug)]
 pub enum LwkError {
     #[error("{msg}")]
@@ -10,100 +14,140 @@ pub enum LwkError {
 
     #[error("Poison error: {msg}")]
     PoisonError { msg: String },
+
+    /// Failed communication with a server or a device, retrying may succeed
+    #[error("{msg}")]
+    Network { msg: String },
+
+    /// Data violating the protocol rules, for instance proofs that do not verify
+    #[error("{msg}")]
+    Consensus { msg: String },
+
+    /// The state of the wallet doesn't allow the operation, for instance insufficient funds
+    #[error("{msg}")]
+    Wallet { msg: String },
+
+    /// The signer failed or refused to sign
+    #[error("{msg}")]
+    Signer { msg: String },
+
+    /// Invalid data given by the caller
+    #[error("{msg}")]
+    Input { msg: String },
+}
+
+impl LwkError {
+    fn with_category(category: ErrorCategory, msg: String) -> Self {
+        match category {
+            ErrorCategory::Network => LwkError::Network { msg },
+            ErrorCategory::Consensus => LwkError::Consensus { msg },
+            ErrorCategory::Wallet => LwkError::Wallet { msg },
+            ErrorCategory::Signer => LwkError::Signer { msg },
+            ErrorCategory::Input => LwkError::Input { msg },
+        }
+    }
 }
 
 impl From<lwk_wollet::Error> for LwkError {
     fn from(value: lwk_wollet::Error) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
-        }
+        LwkError::with_category(value.category(), value.to_string())
+    }
+}
+
+impl From<lwk_common::Error> for LwkError {
+    fn from(value: lwk_common::Error) -> Self {
+        LwkError::with_category(value.category(), value.to_string())
     }
 }
 
 impl From<ParseError> for LwkError {
     fn from(value: ParseError) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
 
 impl From<elements::pset::Error> for LwkError {
     fn from(value: elements::pset::Error) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
 
 impl From<elements::encode::Error> for LwkError {
     fn from(value: elements::encode::Error) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
 
 impl From<elements::bitcoin::transaction::ParseOutPointError> for LwkError {
     fn from(value: elements::bitcoin::transaction::ParseOutPointError) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
 
 impl From<elements::hashes::hex::HexToBytesError> for LwkError {
     fn from(value: elements::hashes::hex::HexToBytesError) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
 
 impl From<elements::hashes::hex::HexToArrayError> for LwkError {
     fn from(value: elements::hashes::hex::HexToArrayError) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
 
 impl From<elements::AddressError> for LwkError {
     fn from(value: elements::AddressError) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
+        }
+    }
+}
+
+impl From<elements::bitcoin::address::ParseError> for LwkError {
+    fn from(value: elements::bitcoin::address::ParseError) -> Self {
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
 
 impl From<lwk_signer::bip39::Error> for LwkError {
     fn from(value: lwk_signer::bip39::Error) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
 
 impl From<lwk_signer::NewError> for LwkError {
     fn from(value: lwk_signer::NewError) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
-        }
+        LwkError::with_category(value.category(), value.to_string())
     }
 }
 
 impl From<lwk_signer::SignError> for LwkError {
     fn from(value: lwk_signer::SignError) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
-        }
+        LwkError::with_category(value.category(), value.to_string())
     }
 }
 
 impl From<lwk_common::QrError> for LwkError {
     fn from(value: lwk_common::QrError) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
@@ -130,8 +174,23 @@ impl<T> From<PoisonError<MutexGuard<'_, T>>> for LwkError {
 
 impl From<lwk_common::precision::Error> for LwkError {
     fn from(value: lwk_common::precision::Error) -> Self {
-        LwkError::Generic {
-            msg: format!("{:?}", value),
+        LwkError::Input {
+            msg: value.to_string(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::LwkError;
+
+    #[test]
+    fn test_error_category() {
+        let err: LwkError = lwk_wollet::Error::InsufficientFunds.into();
+        assert!(matches!(err, LwkError::Wallet { .. }));
+        assert_eq!(err.to_string(), "Insufficient funds");
+
+        let err: LwkError = lwk_wollet::Error::InvalidAmount.into();
+        assert!(matches!(err, LwkError::Input { .. }));
+    }
+}
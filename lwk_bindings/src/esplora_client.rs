@@ -1,8 +1,47 @@
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
 use lwk_wollet::BlockchainBackend;
 
-use crate::{LwkError, Transaction, Txid, Update, Wollet};
+use crate::{types::AssetId, CancellationToken, LwkError, Transaction, Txid, Update, Wollet};
+
+/// Statistics of an asset over the whole chain, see [`lwk_wollet::AssetStats`]
+#[derive(uniffi::Record, Debug, Clone, PartialEq, Eq)]
+pub struct AssetStats {
+    pub asset: AssetId,
+
+    /// Number of confirmed transactions involving the asset
+    pub tx_count: u64,
+
+    /// Number of issuances, the initial one included
+    pub issuance_count: u64,
+
+    /// Number of reissuances
+    pub reissuance_count: u64,
+
+    /// Total amount issued, none if the amount of some issuance is confidential
+    pub issued_amount: Option<u64>,
+
+    /// Total amount burned
+    pub burned_amount: u64,
+
+    /// Amount issued and not burned, none if the issued amount is unknown
+    pub circulating_amount: Option<u64>,
+}
+
+impl From<lwk_wollet::AssetStats> for AssetStats {
+    fn from(value: lwk_wollet::AssetStats) -> Self {
+        Self {
+            asset: value.asset.into(),
+            tx_count: value.tx_count,
+            issuance_count: value.issuance_count,
+            reissuance_count: value.reissuance_count,
+            issued_amount: value.issued_amount,
+            burned_amount: value.burned_amount,
+            circulating_amount: value.circulating_amount,
+        }
+    }
+}
 
 /// Wrapper over [`lwk_wollet::EsploraClient`]
 #[derive(uniffi::Object, Debug)]
@@ -21,13 +60,82 @@ impl EsploraClient {
         })
     }
 
+    /// Construct an Esplora Client fetching and verifying the registry info of the assets
+    /// received in scans from the asset registry at `registry_url`
+    #[uniffi::constructor]
+    pub fn with_registry_url(url: &str, registry_url: &str) -> Arc<Self> {
+        let client = lwk_wollet::EsploraClient::new(url).with_registry_url(registry_url);
+        Arc::new(Self {
+            inner: Mutex::new(client),
+        })
+    }
+
     pub fn broadcast(&self, tx: &Transaction) -> Result<Arc<Txid>, LwkError> {
         Ok(Arc::new(self.inner.lock()?.broadcast(tx.as_ref())?.into()))
     }
 
+    /// Fee rates estimated by the server in sat/kvb, by confirmation target in blocks
+    pub fn fee_estimates(&self) -> Result<HashMap<u16, f32>, LwkError> {
+        Ok(self.inner.lock()?.fee_estimates()?.into_iter().collect())
+    }
+
+    /// Statistics of `asset` over the whole chain, like the amount in circulation
+    pub fn asset_stats(&self, asset: AssetId) -> Result<AssetStats, LwkError> {
+        Ok(self.inner.lock()?.asset_stats(&asset.into())?.into())
+    }
+
+    /// Broadcast the transaction, unless `cancel` has been cancelled before sending it
+    pub fn broadcast_cancellable(
+        &self,
+        tx: &Transaction,
+        cancel: &CancellationToken,
+    ) -> Result<Arc<Txid>, LwkError> {
+        let txid = self
+            .inner
+            .lock()?
+            .broadcast_cancellable(tx.as_ref(), cancel.as_ref())?;
+        Ok(Arc::new(txid.into()))
+    }
+
     pub fn full_scan(&self, wollet: &Wollet) -> Result<Option<Arc<Update>>, LwkError> {
         let wollet = wollet.inner_wollet()?;
         let update: Option<lwk_wollet::Update> = self.inner.lock()?.full_scan(&wollet)?;
         Ok(update.map(Into::into).map(Arc::new))
     }
+
+    /// Scan the blockchain like `full_scan`, but abort once `cancel` is cancelled
+    ///
+    /// The token is checked between the batches of requests to the server, a batch already sent
+    /// is completed before the scan is aborted. The wallet is not modified by a cancelled scan.
+    pub fn full_scan_cancellable(
+        &self,
+        wollet: &Wollet,
+        cancel: &CancellationToken,
+    ) -> Result<Option<Arc<Update>>, LwkError> {
+        let wollet = wollet.inner_wollet()?;
+        let update: Option<lwk_wollet::Update> = self
+            .inner
+            .lock()?
+            .full_scan_cancellable(&wollet, cancel.as_ref())?;
+        Ok(update.map(Into::into).map(Arc::new))
+    }
+
+    /// Fetch from the asset registry at `registry_url` the info of the assets of `wollet` not
+    /// cached yet, verifying it with the issuance transactions, returns the number of assets
+    /// whose info has been cached
+    pub fn fetch_assets_info(&self, wollet: &Wollet, registry_url: &str) -> Result<u32, LwkError> {
+        let mut wollet = wollet.inner_wollet()?;
+        let client = self.inner.lock()?;
+        Ok(wollet.fetch_assets_info(&*client, registry_url)? as u32)
+    }
+
+    /// Scan the blockchain and apply the result to the wallet in chunks, holding at most about
+    /// `memory_budget` bytes of new transactions at once
+    ///
+    /// Useful for large wallets on devices with little memory. Returns whether the wallet changed.
+    pub fn full_scan_chunked(&self, wollet: &Wollet, memory_budget: u64) -> Result<bool, LwkError> {
+        let mut wollet = wollet.inner_wollet()?;
+        let budget = usize::try_from(memory_budget).unwrap_or(usize::MAX);
+        Ok(self.inner.lock()?.full_scan_chunked(&mut wollet, budget)?)
+    }
 }
@@ -2,7 +2,9 @@
 #![cfg_attr(docsrs, feature(doc_auto_cfg))]
 #![doc = include_str!("../README.md")]
 
+mod backup_client;
 pub mod blockdata;
+mod cancel;
 mod chain;
 mod contract;
 mod desc;
@@ -14,6 +16,7 @@ mod network;
 mod persister;
 mod precision;
 mod pset;
+mod session;
 mod signer;
 mod test_env;
 mod tx_builder;
@@ -21,21 +24,23 @@ pub mod types;
 mod update;
 mod wollet;
 
-pub use blockdata::address::Address;
+pub use blockdata::address::{Address, AddressKind, AddressScriptType};
 pub use blockdata::address_result::AddressResult;
 pub use blockdata::out_point::OutPoint;
 pub use blockdata::script::Script;
 pub use blockdata::transaction::Transaction;
 pub use blockdata::tx_out_secrets::TxOutSecrets;
 pub use blockdata::txid::Txid;
-pub use blockdata::wallet_tx::WalletTx;
+pub use blockdata::wallet_tx::{Balance, TxStatus, WalletTx, ZeroConfRisk};
 pub use blockdata::wallet_tx_out::WalletTxOut;
 
-pub use crate::contract::Contract;
+pub use crate::backup_client::BackupClient;
+pub use crate::cancel::CancellationToken;
+pub use crate::contract::{AssetInfo, Contract};
 pub use crate::signer::Signer;
 pub use crate::wollet::Wollet;
 pub use chain::Chain;
-pub use desc::WolletDescriptor;
+pub use desc::{DerivedScript, WolletDescriptor};
 pub use electrum_client::ElectrumClient;
 pub use error::LwkError;
 pub use esplora_client::EsploraClient;
@@ -43,9 +48,10 @@ pub use mnemonic::Mnemonic;
 pub use network::Network;
 pub use persister::{ForeignPersister, ForeignPersisterLink};
 pub use precision::Precision;
-pub use pset::Pset;
+pub use pset::{InputSighash, Pset, SighashKey};
+pub use session::LwkSession;
 pub use test_env::TestEnv;
-pub use tx_builder::TxBuilder;
+pub use tx_builder::{Recipient, TxBuilder};
 pub use update::Update;
 
 uniffi::setup_scaffolding!();
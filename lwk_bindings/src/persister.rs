@@ -5,9 +5,15 @@ use lwk_wollet::Persister;
 use crate::{LwkError, Update};
 
 /// An exported trait, useful for caller-defined persistence.
+///
+/// Implement it in the foreign language to store the wallet updates in the app storage, for
+/// example encrypting them with [`Update::serialize_encrypted()`].
 #[uniffi::export(with_foreign)]
 pub trait ForeignPersister: Send + Sync {
+    /// Return the `index`th update pushed, or none if there are fewer updates
     fn get(&self, index: u64) -> Result<Option<Arc<Update>>, LwkError>;
+
+    /// Persist an update, consecutive updates with [`Update::only_tip()`] can be coalesced
     fn push(&self, update: Arc<Update>) -> Result<(), LwkError>;
 }
 
@@ -1,5 +1,11 @@
-use crate::{types::AssetId, LwkError, Transaction};
+use crate::{
+    types::{AssetId, Hex},
+    LwkError, Transaction,
+};
+use elements::bitcoin::PublicKey;
 use elements::pset::PartiallySignedTransaction;
+use elements::secp256k1_zkp::ecdsa::Signature;
+use elements::EcdsaSighashType;
 use std::{fmt::Display, sync::Arc};
 
 /// Partially Signed Elements Transaction, wrapper over [`elements::pset::PartiallySignedTransaction`]
@@ -9,6 +15,31 @@ pub struct Pset {
     inner: PartiallySignedTransaction,
 }
 
+/// The digest to sign for an input, see [`Pset::input_sighash()`]
+#[derive(uniffi::Record)]
+pub struct InputSighash {
+    /// The 32 bytes message to sign with ECDSA
+    pub sighash: Hex,
+
+    /// The keys expected to sign the input
+    pub keys: Vec<SighashKey>,
+}
+
+/// A key expected to sign an input, with its origin
+#[derive(uniffi::Record)]
+pub struct SighashKey {
+    pub public_key: Hex,
+
+    pub fingerprint: String,
+
+    pub derivation_path: String,
+}
+
+fn sighash_type(sighash_type: u32) -> Result<EcdsaSighashType, LwkError> {
+    EcdsaSighashType::from_standard(sighash_type)
+        .map_err(|_| LwkError::from(format!("Invalid sighash type {sighash_type}")))
+}
+
 impl From<PartiallySignedTransaction> for Pset {
     fn from(inner: PartiallySignedTransaction) -> Self {
         Self { inner }
@@ -42,6 +73,55 @@ impl Pset {
     pub fn issuance_token(&self, index: u32) -> Option<AssetId> {
         self.issuances_ids(index).map(|e| e.1)
     }
+
+    /// The digest to sign for the input at `index` with the given sighash type (1 for ALL) and
+    /// the keys expected to sign it, wrapper of [`lwk_common::pset_input_sighash()`]
+    ///
+    /// Useful for signing backends which can only sign digests, the signatures are then added
+    /// with [`Pset::add_signature()`].
+    pub fn input_sighash(&self, index: u32, sighash_type: u32) -> Result<InputSighash, LwkError> {
+        let s = lwk_common::pset_input_sighash(
+            &self.inner,
+            index as usize,
+            self::sighash_type(sighash_type)?,
+        )?;
+        Ok(InputSighash {
+            sighash: s.sighash.as_ref().as_slice().into(),
+            keys: s
+                .keys
+                .into_iter()
+                .map(|(public_key, (fingerprint, path))| SighashKey {
+                    public_key: public_key.to_bytes().into(),
+                    fingerprint: fingerprint.to_string(),
+                    derivation_path: path.to_string(),
+                })
+                .collect(),
+        })
+    }
+
+    /// Return a copy of this PSET with the DER encoded `signature` of `public_key` for the input
+    /// at `index`, wrapper of [`lwk_common::pset_add_signature()`]
+    pub fn add_signature(
+        &self,
+        index: u32,
+        public_key: &Hex,
+        signature: &Hex,
+        sighash_type: u32,
+    ) -> Result<Arc<Pset>, LwkError> {
+        let public_key = PublicKey::from_slice(public_key.as_ref())
+            .map_err(|e| LwkError::from(e.to_string()))?;
+        let signature =
+            Signature::from_der(signature.as_ref()).map_err(|e| LwkError::from(e.to_string()))?;
+        let mut inner = self.inner.clone();
+        lwk_common::pset_add_signature(
+            &mut inner,
+            index as usize,
+            public_key,
+            signature,
+            self::sighash_type(sighash_type)?,
+        )?;
+        Ok(Arc::new(inner.into()))
+    }
 }
 
 impl Pset {
@@ -58,6 +138,8 @@ impl Pset {
 #[cfg(test)]
 mod tests {
     use super::Pset;
+    use crate::types::Hex;
+    use elements::secp256k1_zkp::{Message, Secp256k1, SecretKey};
 
     #[test]
     fn pset_roundtrip() {
@@ -72,4 +154,27 @@ mod tests {
 
         assert_eq!(pset_string, pset.to_string());
     }
+
+    #[test]
+    fn pset_add_signature() {
+        let pset_string = include_str!("../../lwk_common/test_data/pset_details/pset.base64");
+        let pset = Pset::new(pset_string).unwrap();
+        let s = pset.input_sighash(0, 1).unwrap();
+        assert!(!s.keys.is_empty());
+        assert!(pset.input_sighash(0, 0xff).is_err());
+
+        let secp = Secp256k1::new();
+        let secret_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let public_key: Hex = secret_key.public_key(&secp).serialize().as_slice().into();
+        let msg = Message::from_digest_slice(s.sighash.as_ref()).unwrap();
+        let signature: Hex = secp
+            .sign_ecdsa(&msg, &secret_key)
+            .serialize_der()
+            .as_ref()
+            .into();
+
+        assert!(pset.add_signature(1, &public_key, &signature, 1).is_err());
+        let signed = pset.add_signature(0, &public_key, &signature, 1).unwrap();
+        assert_ne!(signed.to_string(), pset.to_string());
+    }
 }
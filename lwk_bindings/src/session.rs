@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::types::AssetId;
+use crate::{
+    ElectrumClient, EsploraClient, LwkError, Network, Recipient, Signer, Transaction, Txid, Update,
+    Wollet, WolletDescriptor,
+};
+
+/// The blockchain backend of a [`LwkSession`]
+enum Backend {
+    Electrum(Arc<ElectrumClient>),
+    Esplora(Arc<EsploraClient>),
+
+    #[cfg(test)]
+    Mock(Box<Mutex<lwk_wollet::MockBackend>>),
+}
+
+impl Backend {
+    fn full_scan(&self, wollet: &Wollet) -> Result<Option<Arc<Update>>, LwkError> {
+        match self {
+            Backend::Electrum(client) => client.full_scan(wollet),
+            Backend::Esplora(client) => client.full_scan(wollet),
+
+            #[cfg(test)]
+            Backend::Mock(client) => {
+                use lwk_wollet::BlockchainBackend;
+                let wollet = wollet.inner_wollet()?;
+                let update = client.lock()?.full_scan(&wollet)?;
+                Ok(update.map(Into::into).map(Arc::new))
+            }
+        }
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> Result<Arc<Txid>, LwkError> {
+        match self {
+            Backend::Electrum(client) => client.broadcast(tx),
+            Backend::Esplora(client) => client.broadcast(tx),
+
+            #[cfg(test)]
+            Backend::Mock(client) => {
+                use lwk_wollet::BlockchainBackend;
+                Ok(Arc::new(client.lock()?.broadcast(tx.as_ref())?.into()))
+            }
+        }
+    }
+}
+
+/// A high level object bundling the network, the blockchain backend, the persistence and the
+/// loaded wallets, so that applications don't have to orchestrate them
+#[derive(uniffi::Object)]
+pub struct LwkSession {
+    network: Network,
+    backend: Backend,
+    datadir: Option<String>,
+    wollets: Mutex<BTreeMap<String, Arc<Wollet>>>,
+}
+
+impl LwkSession {
+    fn with_backend(network: &Network, backend: Backend, datadir: Option<String>) -> Arc<Self> {
+        Arc::new(Self {
+            network: *network,
+            backend,
+            datadir,
+            wollets: Mutex::new(BTreeMap::new()),
+        })
+    }
+}
+
+#[uniffi::export]
+impl LwkSession {
+    /// Construct a session using the default Electrum server of `network`, wallets are
+    /// persisted in `datadir` if given
+    #[uniffi::constructor]
+    pub fn new(network: &Network, datadir: Option<String>) -> Result<Arc<Self>, LwkError> {
+        let client = network.default_electrum_client()?;
+        Ok(Self::with_backend(
+            network,
+            Backend::Electrum(client),
+            datadir,
+        ))
+    }
+
+    /// Construct a session using the given Electrum client
+    #[uniffi::constructor]
+    pub fn with_electrum_client(
+        network: &Network,
+        client: Arc<ElectrumClient>,
+        datadir: Option<String>,
+    ) -> Arc<Self> {
+        Self::with_backend(network, Backend::Electrum(client), datadir)
+    }
+
+    /// Construct a session using the given Esplora client
+    #[uniffi::constructor]
+    pub fn with_esplora_client(
+        network: &Network,
+        client: Arc<EsploraClient>,
+        datadir: Option<String>,
+    ) -> Arc<Self> {
+        Self::with_backend(network, Backend::Esplora(client), datadir)
+    }
+
+    /// Load the wallet with the given descriptor, referenced by `name` in the other methods
+    pub fn add_wallet(
+        &self,
+        name: String,
+        descriptor: &WolletDescriptor,
+    ) -> Result<Arc<Wollet>, LwkError> {
+        let mut wollets = self.wollets.lock()?;
+        if wollets.contains_key(&name) {
+            return Err(format!("Wallet '{name}' is already loaded").into());
+        }
+        let wollet = Wollet::new(&self.network, descriptor, self.datadir.clone())?;
+        wollets.insert(name, wollet.clone());
+        Ok(wollet)
+    }
+
+    /// Unload the wallet `name`
+    pub fn remove_wallet(&self, name: String) -> Result<(), LwkError> {
+        self.wollets
+            .lock()?
+            .remove(&name)
+            .map(|_| ())
+            .ok_or_else(|| wallet_not_found(&name))
+    }
+
+    /// The wallet `name`, to access the methods not available in the session
+    pub fn wallet(&self, name: String) -> Result<Arc<Wollet>, LwkError> {
+        self.get(&name)
+    }
+
+    /// The names of the loaded wallets, sorted
+    pub fn wallet_names(&self) -> Result<Vec<String>, LwkError> {
+        Ok(self.wollets.lock()?.keys().cloned().collect())
+    }
+
+    /// Scan the blockchain for all the loaded wallets, returns the names of the wallets that
+    /// changed
+    pub fn sync_all(&self) -> Result<Vec<String>, LwkError> {
+        let wollets: Vec<_> = self
+            .wollets
+            .lock()?
+            .iter()
+            .map(|(name, wollet)| (name.clone(), wollet.clone()))
+            .collect();
+        let mut changed = vec![];
+        for (name, wollet) in wollets {
+            if let Some(update) = self.backend.full_scan(&wollet)? {
+                wollet.apply_update(&update)?;
+                changed.push(name);
+            }
+        }
+        Ok(changed)
+    }
+
+    /// The balance of every loaded wallet, by wallet name
+    pub fn balances(&self) -> Result<HashMap<String, HashMap<AssetId, u64>>, LwkError> {
+        let mut balances = HashMap::new();
+        for (name, wollet) in self.wollets.lock()?.iter() {
+            balances.insert(name.clone(), wollet.balance()?);
+        }
+        Ok(balances)
+    }
+
+    /// Create a transaction from the wallet `name` paying `recipients`, sign it with `signer`
+    /// and broadcast it, returns its txid
+    pub fn send(
+        &self,
+        wallet: String,
+        recipients: Vec<Recipient>,
+        signer: &Signer,
+    ) -> Result<Arc<Txid>, LwkError> {
+        let wollet = self.get(&wallet)?;
+        let builder = self.network.tx_builder();
+        builder.add_recipients(recipients)?;
+        let pset = builder.finish(&wollet)?;
+        let pset = signer.sign(&pset)?;
+        let pset = wollet.finalize(&pset)?;
+        let tx = pset.extract_tx()?;
+        self.backend.broadcast(&tx)
+    }
+}
+
+impl LwkSession {
+    fn get(&self, name: &str) -> Result<Arc<Wollet>, LwkError> {
+        self.wollets
+            .lock()?
+            .get(name)
+            .cloned()
+            .ok_or_else(|| wallet_not_found(name))
+    }
+}
+
+fn wallet_not_found(name: &str) -> LwkError {
+    format!("Wallet '{name}' is not loaded").into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_wallets() {
+        let network = Network::testnet();
+        let backend = Backend::Mock(Box::new(Mutex::new(lwk_wollet::MockBackend {
+            fail: true,
+            ..Default::default()
+        })));
+        let session = LwkSession::with_backend(&network, backend, None);
+        let desc = WolletDescriptor::new(&lwk_test_util::wollet_descriptor_string()).unwrap();
+
+        session.add_wallet("w1".to_string(), &desc).unwrap();
+        let err = session.add_wallet("w1".to_string(), &desc).err().unwrap();
+        assert_eq!(err.to_string(), "Wallet 'w1' is already loaded");
+        assert_eq!(session.wallet_names().unwrap(), vec!["w1".to_string()]);
+
+        let balances = session.balances().unwrap();
+        assert_eq!(balances["w1"][&network.policy_asset()], 0);
+        let address = session
+            .wallet("w1".to_string())
+            .unwrap()
+            .address(Some(0))
+            .unwrap();
+        let recipient = Recipient {
+            address: address.address(),
+            satoshi: 1_000,
+            asset: None,
+        };
+        let mnemonic = crate::Mnemonic::new(lwk_test_util::TEST_MNEMONIC).unwrap();
+        let signer = Signer::new(&mnemonic, &network).unwrap();
+        assert!(session
+            .send("w1".to_string(), vec![recipient], &signer)
+            .is_err());
+
+        session.remove_wallet("w1".to_string()).unwrap();
+        assert!(session.wallet_names().unwrap().is_empty());
+        let err = session.wallet("w1".to_string()).err().unwrap();
+        assert_eq!(err.to_string(), "Wallet 'w1' is not loaded");
+    }
+}
@@ -16,6 +16,18 @@ impl Signer {
         Ok(Arc::new(Self { inner }))
     }
 
+    /// Construct a software signer from SLIP-39 mnemonic shares
+    #[uniffi::constructor]
+    pub fn from_slip39(
+        shares: Vec<String>,
+        passphrase: &str,
+        network: &Network,
+    ) -> Result<Arc<Self>, LwkError> {
+        let shares: Vec<&str> = shares.iter().map(|s| s.as_str()).collect();
+        let inner = lwk_signer::SwSigner::from_slip39(&shares, passphrase, network.is_mainnet())?;
+        Ok(Arc::new(Self { inner }))
+    }
+
     /// Sign the given `pset`
     ///
     /// Note from an API perspective it would be better to consume the `pset` parameter so it would
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     fmt::Display,
     sync::{Arc, Mutex},
 };
@@ -29,6 +30,17 @@ impl Display for TxBuilder {
     }
 }
 
+/// A recipient of a transaction, see [`TxBuilder::add_recipients()`]
+#[derive(uniffi::Record, Clone)]
+pub struct Recipient {
+    pub address: Arc<Address>,
+
+    pub satoshi: u64,
+
+    /// The asset to send, if none L-BTC
+    pub asset: Option<AssetId>,
+}
+
 fn builder_finished() -> LwkError {
     "This transaction builder already called finish or errored".into()
 }
@@ -60,6 +72,22 @@ impl TxBuilder {
         Ok(())
     }
 
+    /// Set the fee rate to confirm within `target_blocks`, wrapper of
+    /// [`lwk_wollet::TxBuilder::fee_target()`]
+    ///
+    /// `estimates` are the ones returned by the `fee_estimates()` method of the clients.
+    pub fn fee_target(
+        &self,
+        target_blocks: u16,
+        estimates: HashMap<u16, f32>,
+    ) -> Result<(), LwkError> {
+        let estimates = estimates.into_iter().collect();
+        let mut lock = self.inner.lock()?;
+        let inner = lock.take().ok_or_else(builder_finished)?;
+        *lock = Some(inner.fee_target(target_blocks, &estimates));
+        Ok(())
+    }
+
     /// Select all available L-BTC inputs
     pub fn drain_lbtc_wallet(&self) -> Result<(), LwkError> {
         let mut lock = self.inner.lock()?;
@@ -76,6 +104,31 @@ impl TxBuilder {
         Ok(())
     }
 
+    /// Spend all the UTXOs of `asset` and send the excess to `address`, wrapper of
+    /// [`lwk_wollet::TxBuilder::drain_asset()`]
+    pub fn drain_asset(&self, asset: &AssetId, address: &Address) -> Result<(), LwkError> {
+        let mut lock = self.inner.lock()?;
+        let inner = lock.take().ok_or_else(builder_finished)?;
+        *lock = Some(inner.drain_asset((*asset).into(), address.into()));
+        Ok(())
+    }
+
+    /// Verify the proofs of the blinded outputs before returning the PSET
+    pub fn paranoid(&self, paranoid: bool) -> Result<(), LwkError> {
+        let mut lock = self.inner.lock()?;
+        let inner = lock.take().ok_or_else(builder_finished)?;
+        *lock = Some(inner.paranoid(paranoid));
+        Ok(())
+    }
+
+    /// Create a transaction without confidential transactions, only on regtest
+    pub fn unblinded(&self, unblinded: bool) -> Result<(), LwkError> {
+        let mut lock = self.inner.lock()?;
+        let inner = lock.take().ok_or_else(builder_finished)?;
+        *lock = Some(inner.unblinded(unblinded));
+        Ok(())
+    }
+
     /// Add a recipient receiving L-BTC
     pub fn add_lbtc_recipient(&self, address: &Address, satoshi: u64) -> Result<(), LwkError> {
         let unvalidated_recipient = UnvalidatedRecipient::lbtc(address.to_string(), satoshi);
@@ -99,6 +152,31 @@ impl TxBuilder {
         self.add_validated_recipient(recipient)
     }
 
+    /// Add several recipients at once, each one receiving the given asset (L-BTC if not specified)
+    ///
+    /// All the recipients are validated before being added, if any is invalid none is added.
+    /// The resulting transaction has a change output for each asset sent.
+    pub fn add_recipients(&self, recipients: Vec<Recipient>) -> Result<(), LwkError> {
+        let recipients = recipients
+            .into_iter()
+            .map(|r| {
+                let unvalidated_recipient = match r.asset {
+                    Some(asset) => UnvalidatedRecipient {
+                        satoshi: r.satoshi,
+                        address: r.address.to_string(),
+                        asset: asset.to_string(),
+                    },
+                    None => UnvalidatedRecipient::lbtc(r.address.to_string(), r.satoshi),
+                };
+                unvalidated_recipient.validate(self.network)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for recipient in recipients {
+            self.add_validated_recipient(recipient)?;
+        }
+        Ok(())
+    }
+
     /// Burn satoshi units of the given asset
     pub fn add_burn(&self, satoshi: u64, asset: &AssetId) -> Result<(), LwkError> {
         let unvalidated_recipient = UnvalidatedRecipient::burn(asset.to_string(), satoshi);
@@ -106,6 +184,25 @@ impl TxBuilder {
         self.add_validated_recipient(recipient)
     }
 
+    /// Peg-out satoshi units of L-BTC to the given Bitcoin address, wrapper of
+    /// [`lwk_wollet::TxBuilder::add_pegout()`]
+    pub fn add_pegout(&self, mainchain_address: &str, satoshi: u64) -> Result<(), LwkError> {
+        let address = mainchain_address.parse()?;
+        let mut lock = self.inner.lock()?;
+        let inner = lock.take().ok_or_else(builder_finished)?;
+        *lock = Some(inner.add_pegout(&address, satoshi)?);
+        Ok(())
+    }
+
+    /// Add an output carrying `data` in an OP_RETURN, wrapper of
+    /// [`lwk_wollet::TxBuilder::add_memo_opreturn()`]
+    pub fn add_memo_opreturn(&self, data: Vec<u8>) -> Result<(), LwkError> {
+        let mut lock = self.inner.lock()?;
+        let inner = lock.take().ok_or_else(builder_finished)?;
+        *lock = Some(inner.add_memo_opreturn(&data)?);
+        Ok(())
+    }
+
     /// Issue an asset, wrapper of [`lwk_wollet::TxBuilder::issue_asset()`]
     pub fn issue_asset(
         &self,
@@ -1,4 +1,4 @@
-use crate::LwkError;
+use crate::{LwkError, WolletDescriptor};
 
 /// Wrapper over [`lwk_wollet::Update`]
 #[derive(uniffi::Object, Clone, PartialEq, Eq)]
@@ -40,15 +40,48 @@ impl Update {
     pub fn serialize(&self) -> Result<Vec<u8>, LwkError> {
         Ok(self.inner.serialize()?)
     }
+
+    /// Deserialize an update encrypted with [`Update::serialize_encrypted()`]
+    #[uniffi::constructor]
+    pub fn deserialize_decrypted(
+        bytes: &[u8],
+        desc: &WolletDescriptor,
+    ) -> Result<Update, LwkError> {
+        Ok(lwk_wollet::Update::deserialize_decrypted(bytes, desc.as_ref())?.into())
+    }
+
+    /// Serialize the update encrypted with a key derived from the descriptor, useful to store it
+    /// with a [`crate::ForeignPersister`]
+    pub fn serialize_encrypted(&self, desc: &WolletDescriptor) -> Result<Vec<u8>, LwkError> {
+        Ok(self.inner.serialize_encrypted(desc.as_ref())?)
+    }
+
+    /// Whether this update only changes the tip, persisters can replace a previous update with
+    /// the same property instead of storing both
+    pub fn only_tip(&self) -> bool {
+        self.inner.only_tip()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::str::FromStr;
 
     #[test]
     fn update() {
         let bytes = lwk_test_util::update_test_vector_bytes();
         let update = crate::Update::new(&bytes).unwrap();
-        assert_eq!(update.serialize().unwrap(), bytes);
+        // The test vector has been encoded with a previous version
+        let serialized = update.serialize().unwrap();
+        assert_eq!(serialized[4], lwk_wollet::UPDATE_VERSION);
+        let back = crate::Update::new(&serialized).unwrap();
+        assert_eq!(back.serialize().unwrap(), serialized);
+
+        let desc = lwk_wollet::WolletDescriptor::from_str(lwk_test_util::TEST_DESCRIPTOR).unwrap();
+        let desc = crate::WolletDescriptor::from(desc);
+        let encrypted = update.serialize_encrypted(&desc).unwrap();
+        let back = crate::Update::deserialize_decrypted(&encrypted, &desc).unwrap();
+        assert!(back == update);
+        assert_eq!(update.only_tip(), update.inner.only_tip());
     }
 }
@@ -3,7 +3,10 @@ use lwk_wollet::NoPersist;
 use crate::desc::WolletDescriptor;
 use crate::network::Network;
 use crate::types::AssetId;
-use crate::{AddressResult, ForeignPersisterLink, LwkError, Pset, Txid, Update, WalletTx};
+use crate::{
+    Address, AddressResult, AssetInfo, Balance, ForeignPersisterLink, LwkError, OutPoint, Pset,
+    Txid, Update, WalletTx, WalletTxOut,
+};
 use std::sync::{MutexGuard, PoisonError};
 use std::{
     collections::HashMap,
@@ -72,6 +75,50 @@ impl Wollet {
         Ok(Arc::new(address.into()))
     }
 
+    /// Set a fee paid to the application in every transaction built by this wallet, proportional
+    /// to the L-BTC sent to the recipients, or drained, and capped at `max_satoshi`
+    pub fn set_service_fee(
+        &self,
+        address: &Address,
+        basis_points: u32,
+        max_satoshi: u64,
+    ) -> Result<(), LwkError> {
+        let mut wollet = self.inner.lock()?;
+        wollet.set_service_fee(Some(lwk_wollet::ServiceFee {
+            address: address.as_ref().clone(),
+            basis_points,
+            max_satoshi,
+        }));
+        Ok(())
+    }
+
+    /// Stop adding the service fee to the transactions built by this wallet
+    pub fn remove_service_fee(&self) -> Result<(), LwkError> {
+        self.inner.lock()?.set_service_fee(None);
+        Ok(())
+    }
+
+    /// Set the number of consecutive unused addresses scanned before giving up, default 20
+    pub fn set_gap_limit(&self, gap_limit: u32) -> Result<(), LwkError> {
+        self.inner.lock()?.set_gap_limit(gap_limit)?;
+        Ok(())
+    }
+
+    /// The number of consecutive unused addresses scanned before giving up
+    pub fn gap_limit(&self) -> Result<u32, LwkError> {
+        Ok(self.inner.lock()?.gap_limit())
+    }
+
+    /// Prove that the wallet knows the blinding private key of the given confidential address,
+    /// returns the hex encoded signature
+    ///
+    /// The format is specific to LWK, the proof can only be checked with
+    /// `Address::verify_blinding_key_proof()`
+    pub fn blinding_key_proof(&self, address: &Address) -> Result<String, LwkError> {
+        let wollet = self.inner.lock()?;
+        Ok(wollet.blinding_key_proof(address.as_ref())?.to_string())
+    }
+
     pub fn apply_update(&self, update: &Update) -> Result<(), LwkError> {
         let mut wollet = self.inner.lock()?;
         wollet.apply_update(update.clone().into())?;
@@ -89,6 +136,60 @@ impl Wollet {
         Ok(m)
     }
 
+    /// Get the wallet balance split by confirmation state
+    pub fn detailed_balance(&self) -> Result<HashMap<AssetId, Balance>, LwkError> {
+        Ok(self
+            .inner
+            .lock()?
+            .detailed_balance()?
+            .into_iter()
+            .map(|(k, v)| (k.into(), v.into()))
+            .collect())
+    }
+
+    /// Lock a wallet UTXO, so that it is not spent by new transactions until unlocked
+    pub fn lock_utxo(&self, outpoint: &OutPoint) -> Result<(), LwkError> {
+        self.inner.lock()?.lock_utxo(outpoint.into())?;
+        Ok(())
+    }
+
+    /// Unlock a UTXO locked with `lock_utxo`
+    pub fn unlock_utxo(&self, outpoint: &OutPoint) -> Result<(), LwkError> {
+        self.inner.lock()?.unlock_utxo(outpoint.into())?;
+        Ok(())
+    }
+
+    /// The locked wallet UTXOs which are not spent yet
+    pub fn locked_utxos(&self) -> Result<Vec<Arc<WalletTxOut>>, LwkError> {
+        Ok(self
+            .inner
+            .lock()?
+            .locked_utxos()?
+            .into_iter()
+            .map(Into::into)
+            .map(Arc::new)
+            .collect())
+    }
+
+    /// The registry info of `asset_id`, if cached in the wallet
+    pub fn asset_info(&self, asset_id: AssetId) -> Result<Option<AssetInfo>, LwkError> {
+        let asset_id = asset_id.into();
+        Ok(self
+            .inner
+            .lock()?
+            .asset_info(&asset_id)
+            .cloned()
+            .map(Into::into))
+    }
+
+    /// Cache the registry info of `asset_id` in the wallet
+    pub fn set_asset_info(&self, asset_id: AssetId, info: AssetInfo) -> Result<(), LwkError> {
+        self.inner
+            .lock()?
+            .set_asset_info(asset_id.into(), info.into())?;
+        Ok(())
+    }
+
     pub fn transactions(&self) -> Result<Vec<Arc<WalletTx>>, LwkError> {
         Ok(self
             .inner
@@ -100,6 +201,18 @@ impl Wollet {
             .collect())
     }
 
+    /// Get the wallet transactions evicted from the mempool or replaced
+    pub fn conflicted_transactions(&self) -> Result<Vec<Arc<WalletTx>>, LwkError> {
+        Ok(self
+            .inner
+            .lock()?
+            .conflicted_transactions()?
+            .into_iter()
+            .map(Into::into)
+            .map(Arc::new)
+            .collect())
+    }
+
     pub fn finalize(&self, pset: &Pset) -> Result<Arc<Pset>, LwkError> {
         let mut pset = pset.inner();
         let wollet = self.inner.lock()?;
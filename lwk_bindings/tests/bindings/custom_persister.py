@@ -2,16 +2,22 @@ from lwk import *
 
 
 class PythonPersister(ForeignPersister):
-  data = []
+  # Updates are stored encrypted, like an app would do in its own storage
+  def __init__(self, desc):
+    self.desc = desc
+    self.data = []
 
   def get(self, i):
     try:
-      return self.data[i]
-    except:
-      None
+      return Update.deserialize_decrypted(self.data[i], self.desc)
+    except IndexError:
+      return None
 
   def push(self, update):
-    self.data.append(update)
+    if update.only_tip() and self.data and Update.deserialize_decrypted(self.data[-1], self.desc).only_tip():
+      self.data[-1] = update.serialize_encrypted(self.desc)
+    else:
+      self.data.append(update.serialize_encrypted(self.desc))
 
 
 desc = WolletDescriptor("ct(slip77(ab5824f4477b4ebb00a132adfd8eb0b7935cf24f6ac151add5d1913db374ce92),elwpkh([759db348/84'/1'/0']tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/<0;1>/*))#cch6wrnp");
@@ -21,7 +27,7 @@ assert(str(network) == "LiquidTestnet")
 
 client = network.default_electrum_client()
 
-persister = ForeignPersisterLink(PythonPersister())
+persister = ForeignPersisterLink(PythonPersister(desc))
 
 wollet = Wollet.with_custom_persister(network, desc, persister)
 update = client.full_scan(wollet)
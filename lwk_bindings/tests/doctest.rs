@@ -0,0 +1,63 @@
+//! Run the code blocks of the bindings documentation as foreign language tests
+//!
+//! Every `python`, `kotlin` and `swift` fenced block is written to its own script and executed
+//! like the scripts in `tests/bindings`, so the examples keep working as the API evolves.
+//! Blocks needing a regtest environment can launch one with `TestEnv`.
+
+#![cfg(feature = "foreign_bindings")]
+
+use std::path::Path;
+
+use lwk_test_util::{write_code_from_markdown, SnippetLanguage};
+
+type RunTest = fn(&str, &str, &str) -> uniffi::deps::anyhow::Result<()>;
+
+fn run_markdown(markdown: &str, name: &str, lang: SnippetLanguage, run_test: RunTest) {
+    // Same semantic of the tests generated by `build_foreign_language_testcases`
+    if let Ok(disabled) = std::env::var("UNIFFI_TESTS_DISABLE_EXTENSIONS") {
+        if disabled.split(',').any(|ext| ext == lang.extension()) {
+            return;
+        }
+    }
+    let tmp_dir = env!("CARGO_TARGET_TMPDIR");
+    let dir = Path::new(tmp_dir).join("doctest");
+    for path in write_code_from_markdown(markdown, lang, &dir, name) {
+        let path = path.to_str().unwrap();
+        if let Err(e) = run_test(tmp_dir, env!("CARGO_PKG_NAME"), path) {
+            panic!("{path} failed: {e:?}");
+        }
+    }
+}
+
+#[test]
+fn readme_python() {
+    let readme = include_str!("../README.md");
+    run_markdown(
+        readme,
+        "readme",
+        SnippetLanguage::Python,
+        uniffi::python_run_test,
+    );
+}
+
+#[test]
+fn readme_kotlin() {
+    let readme = include_str!("../README.md");
+    run_markdown(
+        readme,
+        "readme",
+        SnippetLanguage::Kotlin,
+        uniffi::kotlin_run_test,
+    );
+}
+
+#[test]
+fn readme_swift() {
+    let readme = include_str!("../README.md");
+    run_markdown(
+        readme,
+        "readme",
+        SnippetLanguage::Swift,
+        uniffi::swift_run_test,
+    );
+}
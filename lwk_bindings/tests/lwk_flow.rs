@@ -1,4 +1,6 @@
-use lwk::{Address, ElectrumClient, Mnemonic, Network, Signer, Txid, Wollet};
+use lwk::{
+    Address, ElectrumClient, LwkSession, Mnemonic, Network, Recipient, Signer, Txid, Wollet,
+};
 use lwk_wollet::ElementsNetwork;
 use std::str::FromStr;
 
@@ -84,4 +86,61 @@ fn test_lwk_flow() {
         .broadcast(&finalized_pset.extract_tx().unwrap())
         .unwrap();
     println!("BROADCASTED TX!\nTXID: {:?}", txid);
+
+    let _tx = wollet.wait_for_tx(&txid, &electrum_client).unwrap();
+
+    // Send L-BTC and the asset in a single transaction
+    let builder = network.tx_builder();
+    builder
+        .add_recipients(vec![
+            Recipient {
+                address: out_address.clone(),
+                satoshi: 1_000,
+                asset: None,
+            },
+            Recipient {
+                address: out_address,
+                satoshi: 10,
+                asset: Some(asset.into()),
+            },
+        ])
+        .unwrap();
+    let pset = builder.finish(&wollet).unwrap();
+    let signed_pset = signer.sign(&pset).unwrap();
+    let finalized_pset = wollet.finalize(&signed_pset).unwrap();
+    let txid = electrum_client
+        .broadcast(&finalized_pset.extract_tx().unwrap())
+        .unwrap();
+    println!("BROADCASTED TX!\nTXID: {:?}", txid);
+}
+
+#[test]
+fn test_session_flow() {
+    let mnemonic = lwk_test_util::TEST_MNEMONIC;
+    let network: Network = ElementsNetwork::default_regtest().into();
+    let signer = Signer::new(&Mnemonic::new(mnemonic).unwrap(), &network).unwrap();
+    let server = lwk_test_util::setup(false);
+    let electrum_client = ElectrumClient::new(&server.electrs.electrum_url, false, false).unwrap();
+    let session = LwkSession::with_electrum_client(&network, electrum_client.clone(), None);
+
+    let desc = signer.wpkh_slip77_descriptor().unwrap();
+    let wollet = session.add_wallet("w1".to_string(), &desc).unwrap();
+    let address = wollet.address(Some(0)).unwrap().address();
+    let txid = server.node_sendtoaddress(&address.to_string().parse().unwrap(), 100_000, None);
+    let txid = Txid::from_str(&txid.to_string()).unwrap();
+    let _tx = wollet.wait_for_tx(&txid, &electrum_client).unwrap();
+    assert!(session.sync_all().unwrap().is_empty());
+    let policy_asset = network.policy_asset();
+    assert_eq!(session.balances().unwrap()["w1"][&policy_asset], 100_000);
+
+    let recipient = Recipient {
+        address: wollet.address(Some(1)).unwrap().address(),
+        satoshi: 1_000,
+        asset: None,
+    };
+    let txid = session
+        .send("w1".to_string(), vec![recipient], &signer)
+        .unwrap();
+    let _tx = wollet.wait_for_tx(&txid, &electrum_client).unwrap();
+    assert!(session.balances().unwrap()["w1"][&policy_asset] < 100_000);
 }
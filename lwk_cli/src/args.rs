@@ -23,6 +23,35 @@ pub struct Cli {
     #[arg(long, env)]
     pub addr: Option<SocketAddr>,
 
+    /// Public key of the server identity
+    ///
+    /// If set, the signatures of critical responses, such as addresses and balances, are
+    /// verified. The server must be started with `--sign-responses`.
+    #[arg(long, env)]
+    pub server_identity: Option<String>,
+
+    /// Token authenticating the requests to the server
+    ///
+    /// When starting the server, requests without this token are rejected.
+    #[arg(long, env)]
+    pub auth_token: Option<String>,
+
+    /// PEM certificate of the server, connect using https trusting this certificate
+    ///
+    /// When starting the server, serve https with this certificate and `--tls-key`.
+    #[arg(long, env)]
+    pub tls_cert: Option<PathBuf>,
+
+    /// Print the result as compact json on a single line, with the schema of the RPC responses
+    ///
+    /// Errors returned by the server are printed as json as well, with a non-zero exit code.
+    #[arg(long, global = true, conflicts_with = "quiet")]
+    pub json: bool,
+
+    /// Print nothing on success, errors are printed on stderr with a non-zero exit code
+    #[arg(long, global = true)]
+    pub quiet: bool,
+
     /// The sub command
     #[command(subcommand)]
     pub command: CliCommand,
@@ -42,6 +71,15 @@ pub enum CliCommand {
     /// Asset commands
     Asset(AssetArgs),
 
+    /// Address book commands
+    Contact(ContactArgs),
+
+    /// Transaction draft commands
+    Draft(DraftArgs),
+
+    /// PSET exchange commands
+    Pset(PsetArgs),
+
     /// Print JSON schema of RPC requests and responses
     ///
     /// E.g. `lwk_cli schema response wallet details` returns the response parameters for
@@ -125,6 +163,9 @@ pub enum MainCommand {
     Wallet(WalletSubCommands),
     Signer(SignerSubCommands),
     Asset(AssetSubCommands),
+    Contact(ContactSubCommands),
+    Draft(DraftSubCommands),
+    Pset(PsetSubCommands),
     Schema,
 }
 
@@ -138,6 +179,8 @@ pub struct ServerSubCommands {
 pub enum ServerSubCommandsEnum {
     // Start is a special command
     Scan,
+    Subscribe,
+    FeeRates,
     Stop,
 }
 
@@ -152,17 +195,32 @@ pub enum WalletSubCommandsEnum {
     Load,
     Unload,
     List,
+    SyncStatus,
     Address,
+    AddressReserve,
+    Addresses,
+    RecoveryScan,
     Balance,
+    Summary,
     Send,
     Issue,
     Reissue,
+    PeginAddress,
+    ClaimPegin,
+    Pegout,
     MultisigDesc,
     Broadcast,
     Details,
     Combine,
     PsetDetails,
     Utxos,
+    UtxosExport,
+    LockUtxo,
+    UnlockUtxo,
+    SetLabel,
+    GetLabel,
+    ExportLabels,
+    ImportLabels,
     Txs,
     SetTxMemo,
     SetAddrMemo,
@@ -180,11 +238,15 @@ pub enum SignerSubCommandsEnum {
     JadeId,
     LoadSoftware,
     LoadJade,
+    LoadLedger,
     LoadExternal,
+    LoadRemote,
     Unload,
     Details,
     List,
     Sign,
+    SessionStart,
+    SessionComplete,
     SinglesigDesc,
     Xpub,
 }
@@ -200,11 +262,78 @@ pub enum AssetSubCommandsEnum {
     Contract,
     Details,
     List,
+    Events,
     Insert,
     Remove,
     Publish,
 }
 
+#[derive(Debug, Args)]
+pub struct ContactSubCommands {
+    #[command(subcommand)]
+    pub command: ContactSubCommandsEnum,
+}
+
+#[derive(Debug, Subcommand, ValueEnum, Clone)]
+pub enum ContactSubCommandsEnum {
+    Add,
+    List,
+    Remove,
+}
+
+#[derive(Debug, Args)]
+pub struct DraftSubCommands {
+    #[command(subcommand)]
+    pub command: DraftSubCommandsEnum,
+}
+
+#[derive(Debug, Subcommand, ValueEnum, Clone)]
+pub enum DraftSubCommandsEnum {
+    Save,
+    List,
+    Load,
+    Delete,
+}
+
+#[derive(Debug, Args)]
+pub struct PsetSubCommands {
+    #[command(subcommand)]
+    pub command: PsetSubCommandsEnum,
+}
+
+#[derive(Debug, Subcommand, ValueEnum, Clone)]
+pub enum PsetSubCommandsEnum {
+    SendNostr,
+    Inbox,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PsetFormat {
+    /// Base64 text, as returned by the other commands
+    Base64,
+
+    /// Raw bytes, as in BIP-174 files
+    Binary,
+
+    /// Uniform Resources `ur:crypto-pset/...`, one part per line, to be shown as QR codes
+    Ur,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum TxDirection {
+    Incoming,
+    Outgoing,
+}
+
+impl From<TxDirection> for lwk_rpc_model::request::TxDirection {
+    fn from(value: TxDirection) -> Self {
+        match value {
+            TxDirection::Incoming => Self::Incoming,
+            TxDirection::Outgoing => Self::Outgoing,
+        }
+    }
+}
+
 #[derive(ValueEnum, Clone, Debug)]
 pub enum SignerKind {
     Software,
@@ -251,13 +380,23 @@ pub enum SignerCommand {
         emulator: Option<SocketAddr>,
     },
 
+    /// Load a Ledger signer giving it a name
+    LoadLedger {
+        #[arg(short, long, env)]
+        signer: String,
+
+        /// The local port to connect to the Ledger emulator
+        #[arg(long)]
+        emulator: u16,
+    },
+
     /// Load a software signer giving it a name
     LoadSoftware {
         #[arg(short, long, env)]
         signer: String,
 
-        #[arg(long)]
-        mnemonic: String, // TODO is it right to have the mnemonic as arg?
+        #[arg(long, required_unless_present = "mnemonic_file")]
+        mnemonic: Option<String>, // TODO is it right to have the mnemonic as arg?
 
         /// Specify if the rpc-server should persist the signer
         ///
@@ -267,6 +406,13 @@ pub enum SignerCommand {
         /// availabled.
         #[arg(long, required(true))]
         persist: Option<bool>,
+
+        /// Path of a file containing the mnemonic, read by the server
+        ///
+        /// If the signer is persisted, only the path is saved and the file must be available at
+        /// the following restarts.
+        #[arg(long, conflicts_with = "mnemonic")]
+        mnemonic_file: Option<String>,
     },
 
     /// Load a Jade signer giving it a name
@@ -292,6 +438,27 @@ pub enum SignerCommand {
         fingerprint: String,
     },
 
+    /// Load a signer living in another lwk server giving it a name
+    ///
+    /// Xpub and sign requests are forwarded to the remote server.
+    LoadRemote {
+        #[arg(short, long, env)]
+        signer: String,
+
+        /// The url of the remote server, for example `http://127.0.0.1:32111`
+        #[arg(long)]
+        url: String,
+
+        /// The name of the signer in the remote server
+        #[arg(long)]
+        remote_signer: String,
+
+        /// Token sent as bearer authorization to the remote server, stored in the server state
+        /// file readable only by its owner
+        #[arg(long)]
+        token: Option<String>,
+    },
+
     /// Details of a signer
     Details {
         #[arg(short, long, env)]
@@ -314,6 +481,39 @@ pub enum SignerCommand {
 
         #[arg(long)]
         pset: String,
+
+        /// Wallet name
+        ///
+        /// If specified, refuse to sign if the outputs claimed as change by the signer
+        /// do not belong to this wallet.
+        #[arg(short, long)]
+        wallet: Option<String>,
+    },
+
+    /// Start a session to sign a PSET with an external signer, such as an MPC coordinator
+    ///
+    /// Returns the digests the signer must sign, the signatures are added with
+    /// `session-complete`.
+    SessionStart {
+        #[arg(short, long, env)]
+        signer: String,
+
+        #[arg(long)]
+        pset: String,
+    },
+
+    /// Add the signatures made by the external signer to the PSET of a session
+    SessionComplete {
+        /// The session identifier
+        #[arg(long)]
+        id: String,
+
+        /// A signature in the form "input_index:public_key:signature"
+        ///
+        /// The signature is DER encoded in hex, without the sighash byte.
+        /// Can be specified multiple times.
+        #[arg(long)]
+        signature: Vec<String>,
     },
 
     ///  Prints a singlesig descriptor using this signer key
@@ -413,6 +613,11 @@ pub enum WalletCommand {
 
         #[arg(short, long)]
         descriptor: String,
+
+        /// The number of consecutive unused addresses after which scans stop looking for more,
+        /// increase it if many addresses have been handed out without being paid
+        #[arg(long)]
+        gap_limit: Option<u32>,
     },
 
     /// Unload a wallet
@@ -425,6 +630,11 @@ pub enum WalletCommand {
     /// List existing loaded wallets
     List,
 
+    /// Show the last block seen by the loaded wallets and the time they were last scanned
+    ///
+    /// Wallets are scanned in the background every `--scanning-interval` seconds.
+    SyncStatus,
+
     /// Get an address from the given wallet name
     Address {
         /// Wallet name
@@ -452,6 +662,62 @@ pub enum WalletCommand {
         /// the given number is the number of pixel per qr code module
         #[arg(long)]
         with_uri_qr: Option<u8>,
+
+        /// If the address at the index already received funds, return the first following
+        /// unused one
+        #[arg(long)]
+        skip_used: bool,
+    },
+
+    /// Reserve consecutive unused addresses of the given wallet name
+    ///
+    /// Reserved addresses are skipped when getting an address without an index.
+    AddressReserve {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// The number of addresses to reserve, limited by the gap limit of the wallet
+        #[arg(long)]
+        count: u32,
+
+        /// The derivation index of the first reserved address
+        #[arg(long)]
+        index: Option<u32>,
+    },
+
+    /// List the derived addresses of the given wallet name, with whether they received funds
+    /// and their balance
+    Addresses {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// List the change addresses instead of the receiving ones
+        #[arg(long)]
+        change: bool,
+
+        /// The derivation index of the first address returned
+        #[arg(long, default_value_t = 0)]
+        offset: u32,
+
+        /// Maximum number of addresses returned
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+
+    /// Scan the common derivation schemes of a mnemonic to find the ones with history
+    ///
+    /// Useful to locate the funds of an old wallet created with an unknown derivation scheme,
+    /// the returned descriptors can be loaded with `wallet load`.
+    RecoveryScan {
+        /// Mnemonic of the wallet to recover
+        #[arg(long)]
+        mnemonic: String,
+
+        /// The last account scanned for every script type
+        #[arg(long)]
+        max_account: Option<u32>,
     },
 
     /// Get the balance of the given wallet name
@@ -465,6 +731,13 @@ pub enum WalletCommand {
         with_tickers: bool,
     },
 
+    /// Get counts, totals and activity times of the wallet
+    Summary {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+    },
+
     /// Create an unsigned transaction (PSET)
     Send {
         /// Wallet name
@@ -477,12 +750,37 @@ pub enum WalletCommand {
         ///
         /// Address can either be a valid address or "burn" if you want to burn the asset, i.e.
         /// create a provably unspendable output.
+        ///
+        /// Address can also be "contact:<name>" to pay a contact of the address book.
         #[arg(long, required = true)]
         recipient: Vec<String>,
 
         /// Fee rate to use
         #[arg(long)]
         fee_rate: Option<f32>,
+
+        /// Confirmation target in blocks, the fee rate is estimated by the server
+        ///
+        /// Ignored if `--fee-rate` is specified.
+        #[arg(long)]
+        fee_target: Option<u16>,
+
+        /// Locktime of the transaction, either "height:<block height>" or "time:<unix timestamp>"
+        ///
+        /// The transaction can't be broadcast before the locktime is reached.
+        #[arg(long)]
+        locktime: Option<String>,
+
+        /// Name of the draft of a previous version of this transaction, whose change addresses
+        /// are reused
+        #[arg(long)]
+        draft: Option<String>,
+
+        /// Build the transaction even if an identical payment was built recently
+        ///
+        /// Relevant only if the server is started with `--duplicate-payment-window`.
+        #[arg(long, action)]
+        force: bool,
     },
 
     /// Drain the wallet of the policy asset
@@ -492,12 +790,26 @@ pub enum WalletCommand {
         wallet: String,
 
         /// The address receiving the funds
+        ///
+        /// Can be "contact:<name>" to pay a contact of the address book.
         #[arg(long)]
         address: String,
 
         /// Fee rate to use
         #[arg(long)]
         fee_rate: Option<f32>,
+
+        /// Drain also all the units of this asset to the address
+        ///
+        /// Can be specified multiple times.
+        #[arg(long)]
+        asset: Vec<String>,
+
+        /// Build the transaction even if an identical payment was built recently
+        ///
+        /// Relevant only if the server is started with `--duplicate-payment-window`.
+        #[arg(long, action)]
+        force: bool,
     },
 
     /// Issue an asset
@@ -532,6 +844,10 @@ pub enum WalletCommand {
         /// To optionally specify a fee
         #[arg(long)]
         fee_rate: Option<f32>,
+
+        /// Locktime of the transaction, either "height:<block height>" or "time:<unix timestamp>"
+        #[arg(long)]
+        locktime: Option<String>,
     },
 
     /// Reissue a previously issued asset, needs ownership of the issuance token
@@ -579,6 +895,70 @@ pub enum WalletCommand {
         fee_rate: Option<f32>,
     },
 
+    /// Get a mainchain address to peg-in bitcoin to the wallet
+    PeginAddress {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// The script of the federation in hex, as returned by `getsidechaininfo` of elementsd
+        #[arg(long)]
+        fedpegscript: String,
+
+        /// The index of the wallet address committed by the peg-in address.
+        /// If not specified the last unused address is used
+        #[arg(long)]
+        index: Option<u32>,
+    },
+
+    /// Claim the bitcoin sent to a peg-in address of the wallet
+    ClaimPegin {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// The script of the federation in hex, as returned by `getsidechaininfo` of elementsd
+        #[arg(long)]
+        fedpegscript: String,
+
+        /// The mainchain transaction sending to the peg-in address in hex
+        #[arg(long)]
+        mainchain_tx: String,
+
+        /// The merkle proof of the mainchain transaction in hex, as returned by `gettxoutproof`
+        /// of bitcoind
+        #[arg(long)]
+        txout_proof: String,
+
+        // TODO default value
+        /// To optionally specify a fee
+        #[arg(long)]
+        fee_rate: Option<f32>,
+    },
+
+    /// Create an unsigned transaction (PSET) pegging out L-BTC to the mainchain
+    ///
+    /// Supported only on regtest, since Liquid and Liquid Testnet require a PAK proof which is
+    /// not supported.
+    Pegout {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// The mainchain address receiving the bitcoin
+        #[arg(long)]
+        mainchain_address: String,
+
+        /// The number of satoshi to peg-out
+        #[arg(long)]
+        satoshi: u64,
+
+        // TODO default value
+        /// To optionally specify a fee
+        #[arg(long)]
+        fee_rate: Option<f32>,
+    },
+
     /// Print a multisig descriptor
     MultisigDesc {
         #[arg(long)]
@@ -590,8 +970,13 @@ pub enum WalletCommand {
         #[arg(long)]
         threshold: u32,
 
-        #[arg(long, required = true)]
+        /// The xpubs with key origin of the partecipants
+        #[arg(long, required_unless_present = "signer")]
         keyorigin_xpub: Vec<String>,
+
+        /// The names of loaded signers partecipating, their bip87 xpubs are used
+        #[arg(long)]
+        signer: Vec<String>,
     },
 
     /// Try to finalize the PSET and broadcast the transaction
@@ -615,7 +1000,7 @@ pub enum WalletCommand {
         wallet: String,
     },
 
-    /// Combine PSETs
+    /// Combine PSETs, returning the final transaction if all the inputs have enough signatures
     Combine {
         /// Wallet name
         #[arg(short, long, env)]
@@ -641,6 +1026,11 @@ pub enum WalletCommand {
         /// Replace asset ids with tickers when possible
         #[arg(long, action)]
         with_tickers: bool,
+
+        /// Warn if the fee is higher than these basis points (hundredths of a percent) of the
+        /// L-BTC sent
+        #[arg(long)]
+        max_fee_basis_points: Option<u32>,
     },
 
     /// Get the wallet unspent transaction outputs
@@ -650,6 +1040,98 @@ pub enum WalletCommand {
         wallet: String,
     },
 
+    /// Export the wallet unspent transaction outputs in formats accepted by elements core
+    ///
+    /// For each confirmed transaction creating the UTXOs, the arguments of `importprunedfunds`.
+    /// For each UTXO, the data needed to spend it as a PSET input.
+    UtxosExport {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+    },
+
+    /// Lock a wallet UTXO, so that it is not spent by new transactions until unlocked
+    LockUtxo {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// The transaction id of the UTXO
+        #[arg(long)]
+        txid: String,
+
+        /// The output index of the UTXO
+        #[arg(long)]
+        vout: u32,
+    },
+
+    /// Unlock a wallet UTXO previously locked
+    UnlockUtxo {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// The transaction id of the UTXO
+        #[arg(long)]
+        txid: String,
+
+        /// The output index of the UTXO
+        #[arg(long)]
+        vout: u32,
+    },
+
+    /// Set the label of a transaction, address or output, or remove it if no label is given
+    SetLabel {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// The type of the labelled item: "tx", "addr" or "output"
+        #[arg(long = "type")]
+        type_: String,
+
+        /// The labelled item: txid, address or "txid:vout"
+        #[arg(long = "ref")]
+        ref_: String,
+
+        /// The label
+        #[arg(long)]
+        label: Option<String>,
+    },
+
+    /// Get the label of a transaction, address or output
+    GetLabel {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// The type of the labelled item: "tx", "addr" or "output"
+        #[arg(long = "type")]
+        type_: String,
+
+        /// The labelled item: txid, address or "txid:vout"
+        #[arg(long = "ref")]
+        ref_: String,
+    },
+
+    /// Export the wallet labels in the BIP329 JSON lines format
+    ExportLabels {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+    },
+
+    /// Import wallet labels in the BIP329 JSON lines format
+    ImportLabels {
+        /// Wallet name
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// The labels, one JSON object per line
+        #[arg(long)]
+        labels: String,
+    },
+
     /// Get the wallet transactions
     Txs {
         /// Wallet name
@@ -659,6 +1141,31 @@ pub enum WalletCommand {
         /// Replace asset ids with tickers when possible
         #[arg(long, action)]
         with_tickers: bool,
+
+        /// Number of matching transactions to skip, unconfirmed transactions come first and
+        /// then the most recent ones
+        #[arg(long, default_value_t = 0)]
+        offset: usize,
+
+        /// Maximum number of transactions returned, all the matching ones if missing
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// Only transactions changing the wallet balance of this asset id
+        #[arg(long)]
+        asset: Option<String>,
+
+        /// Only transactions confirmed at this height or above
+        #[arg(long)]
+        min_height: Option<u32>,
+
+        /// Only transactions confirmed at this height or below, excluding unconfirmed ones
+        #[arg(long)]
+        max_height: Option<u32>,
+
+        /// Only transactions moving funds in this direction
+        #[arg(long)]
+        direction: Option<TxDirection>,
     },
 
     /// Get a transaction
@@ -753,6 +1260,12 @@ pub enum AssetCommand {
     /// List assets
     List,
 
+    /// List the changes of the registry entries of the assets held by loaded wallets
+    ///
+    /// Registry entries are checked periodically, an event is recorded when the ticker or the
+    /// domain of an entry differs from the asset contract or when the entry is removed.
+    Events,
+
     /// Insert an asset
     Insert {
         /// Asset ID in hex
@@ -804,6 +1317,191 @@ pub enum AssetCommand {
     },
 }
 
+#[derive(Debug, Args)]
+pub struct ContactArgs {
+    #[command(subcommand)]
+    pub command: ContactCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ContactCommand {
+    /// Add a contact to the address book
+    ///
+    /// Contacts are paid using "contact:<name>" as address, e.g. in `wallet send` and
+    /// `wallet drain`.
+    Add {
+        /// Contact name
+        #[arg(long)]
+        name: String,
+
+        /// The address of the contact, used for every payment
+        #[arg(
+            long,
+            conflicts_with = "descriptor",
+            required_unless_present = "descriptor"
+        )]
+        address: Option<String>,
+
+        /// The CT descriptor of the contact, a fresh address is derived for every broadcast payment
+        #[arg(long)]
+        descriptor: Option<String>,
+
+        /// The derivation index of the address used for the next payment (descriptor only)
+        #[arg(long, requires = "descriptor")]
+        index: Option<u32>,
+    },
+
+    /// List the contacts of the address book
+    List,
+
+    /// Remove a contact from the address book
+    Remove {
+        /// Contact name
+        #[arg(long)]
+        name: String,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct DraftArgs {
+    #[command(subcommand)]
+    pub command: DraftCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DraftCommand {
+    /// Save an in-progress PSET as a named draft
+    ///
+    /// Drafts are kept by the server, so that a PSET can be completed by different signers over
+    /// time.
+    Save {
+        /// Draft name
+        #[arg(long)]
+        name: String,
+
+        /// The PSET in base64
+        #[arg(short, long)]
+        pset: String,
+
+        /// Wallet name involved in the transaction
+        #[arg(short, long, env)]
+        wallet: String,
+
+        /// Who is saving the draft
+        #[arg(long)]
+        creator: Option<String>,
+
+        /// Replace the draft with the same name, if any
+        #[arg(long)]
+        replace: bool,
+    },
+
+    /// List the saved drafts
+    List {
+        /// List only the drafts involving this wallet
+        #[arg(short, long)]
+        wallet: Option<String>,
+    },
+
+    /// Get a saved draft with its PSET
+    Load {
+        /// Draft name
+        #[arg(long)]
+        name: String,
+    },
+
+    /// Delete a saved draft
+    Delete {
+        /// Draft name
+        #[arg(long)]
+        name: String,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct PsetArgs {
+    #[command(subcommand)]
+    pub command: PsetCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum PsetCommand {
+    /// Send a PSET to a cosigner with a Nostr encrypted direct message
+    ///
+    /// The Nostr identity is derived from the seed of the software signer, its public key is
+    /// returned by `pset inbox`. Requires the server built with the "nostr" feature.
+    SendNostr {
+        /// Software signer sending the PSET
+        #[arg(short, long, env)]
+        signer: String,
+
+        /// Nostr public key of the cosigner, in hex
+        #[arg(long)]
+        recipient: String,
+
+        /// The PSET in base64
+        #[arg(short, long)]
+        pset: String,
+
+        /// Nostr relay URL, can be specified multiple times
+        #[arg(long, required = true)]
+        relay: Vec<String>,
+    },
+
+    /// Get the PSETs received with Nostr encrypted direct messages
+    Inbox {
+        /// Software signer receiving the PSETs
+        #[arg(short, long, env)]
+        signer: String,
+
+        /// Nostr public key of a cosigner, in hex, PSETs from other senders are ignored.
+        /// Can be specified multiple times
+        #[arg(long, required = true)]
+        sender: Vec<String>,
+
+        /// Nostr relay URL, can be specified multiple times
+        #[arg(long, required = true)]
+        relay: Vec<String>,
+
+        /// Get only the PSETs sent after this unix timestamp
+        #[arg(long, default_value_t = 0)]
+        since: u64,
+    },
+
+    /// Write a PSET to a file, for instance to sign it on an air-gapped machine
+    ///
+    /// The result contains the PSET as Uniform Resources (`ur:crypto-pset/...`), to be shown as
+    /// QR codes: large PSETs are split in several parts, to be shown in a loop. The file is
+    /// written locally, the server is not contacted.
+    Export {
+        /// The PSET in base64
+        #[arg(short, long)]
+        pset: String,
+
+        /// Path of the file to write
+        #[arg(long)]
+        file: PathBuf,
+
+        /// The encoding of the file
+        #[arg(long, value_enum, default_value_t = PsetFormat::Base64)]
+        format: PsetFormat,
+
+        /// Maximum bytes of PSET data in each Uniform Resource part
+        #[arg(long, default_value_t = lwk_common::DEFAULT_UR_FRAGMENT_LEN)]
+        max_fragment_len: usize,
+    },
+
+    /// Read a PSET from a file, returning it in base64
+    ///
+    /// The file can be binary, base64 or Uniform Resources, one part per line in any order, as
+    /// written by `pset export`. The file is read locally, the server is not contacted.
+    Import {
+        /// Path of the file to read
+        #[arg(long)]
+        file: PathBuf,
+    },
+}
+
 #[derive(Debug, Args)]
 pub struct ServerArgs {
     #[command(subcommand)]
@@ -840,11 +1538,58 @@ pub enum ServerCommand {
         /// Interval between blockchain scans (seconds)
         #[arg(long)]
         scanning_interval: Option<u64>,
+
+        /// Interval between checks of the registry entries of held assets (seconds)
+        #[arg(long)]
+        registry_check_interval: Option<u64>,
+
+        /// Refuse to build a payment identical to one built or broadcast within this window
+        /// (seconds), unless `wallet send --force` or `wallet drain --force` is used
+        ///
+        /// Payments are identical if they have the same recipients, amounts and assets.
+        #[arg(long)]
+        duplicate_payment_window: Option<u64>,
+
+        /// URL receiving a POST with every asset event as json
+        #[arg(long)]
+        webhook_url: Option<String>,
+
+        /// Sign critical responses, such as addresses and balances, with the server identity key
+        ///
+        /// The key is created in the datadir at the first start, its public key is returned by
+        /// the version RPC method and printed in the logs.
+        #[arg(long, action)]
+        sign_responses: bool,
+
+        /// PEM private key of the certificate given with `--tls-cert`
+        #[arg(long)]
+        tls_key: Option<PathBuf>,
     },
 
     /// Wait until an entire blockchain scan has been completed
     Scan,
 
+    /// Wait for notifications about the loaded wallets
+    ///
+    /// Returns the notifications more recent than `since`, waiting up to `timeout` if there are
+    /// none. Pass the returned `last_id` as `since` to receive the following ones.
+    Subscribe {
+        /// Receive only the notifications about this wallet and new blocks
+        #[arg(short, long)]
+        wallet: Option<String>,
+
+        /// Receive only the notifications with an identifier greater than this
+        #[arg(long, default_value = "0")]
+        since: u64,
+
+        /// Seconds to wait for a notification if there are none, at most 60
+        #[arg(long, default_value = "0")]
+        timeout: u64,
+    },
+
+    /// Get the fee rates estimated by the server, by confirmation target in blocks
+    FeeRates,
+
     /// Stop the server
     ///
     /// Alternatively the server can be stopped also with SIGINT (ctrl-c)
@@ -10,19 +10,60 @@ use std::{
 use anyhow::{anyhow, Context};
 use clap::CommandFactory;
 use lwk_app::Config;
+use lwk_rpc_model::request;
 use serde_json::Value;
 use tracing_subscriber::{filter::LevelFilter, EnvFilter, FmtSubscriber};
 
-use crate::args::{AssetCommand, CliCommand, Network, ServerCommand, SignerCommand, WalletCommand};
+use crate::args::{
+    AssetCommand, CliCommand, ContactCommand, DraftCommand, Network, PsetCommand, ServerCommand,
+    SignerCommand, WalletCommand,
+};
 pub use args::Cli;
 
 pub use args::{
-    AssetSubCommandsEnum, ServerSubCommandsEnum, SignerSubCommandsEnum, WalletSubCommandsEnum,
+    AssetSubCommandsEnum, ContactSubCommandsEnum, DraftSubCommandsEnum, PsetSubCommandsEnum,
+    ServerSubCommandsEnum, SignerSubCommandsEnum, WalletSubCommandsEnum,
 };
 
 mod args;
+mod pset_file;
 mod schema;
 
+impl Cli {
+    /// How the result of the command is printed
+    pub fn output_mode(&self) -> OutputMode {
+        if self.json {
+            OutputMode::Json
+        } else if self.quiet {
+            OutputMode::Quiet
+        } else {
+            OutputMode::Pretty
+        }
+    }
+}
+
+/// How the result of a command is printed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+    /// Indented json
+    Pretty,
+
+    /// Compact json on a single line
+    Json,
+
+    /// Nothing on success
+    Quiet,
+}
+
+/// Format the result of a command according to `mode`, `None` if nothing must be printed
+pub fn format_output(value: &Value, mode: OutputMode) -> Option<String> {
+    match mode {
+        OutputMode::Pretty => Some(format!("{value:#}")),
+        OutputMode::Json => Some(value.to_string()),
+        OutputMode::Quiet => None,
+    }
+}
+
 pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
     let directive = if let CliCommand::Server(args::ServerArgs {
         command: ServerCommand::Start { .. },
@@ -58,7 +99,24 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
     let addr = args
         .addr
         .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), default_port));
-    let client = lwk_app::Client::new(addr)?;
+    let mut client = match args.tls_cert.as_ref() {
+        Some(path) => {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("error reading TLS certificate {}", path.display()))?;
+            lwk_app::Client::with_root_certificate(
+                format!("https://{addr}"),
+                args.auth_token.clone(),
+                Some(pem),
+            )?
+        }
+        None => lwk_app::Client::with_url(format!("http://{addr}"), args.auth_token.clone())?,
+    };
+    if let Some(identity) = args.server_identity.as_ref() {
+        let identity = identity
+            .parse()
+            .with_context(|| "error parsing server identity")?;
+        client = client.with_identity(identity);
+    }
 
     // verify the server is up if needed
     if args.command.requires_server_running() {
@@ -85,6 +143,11 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                     datadir,
                     timeout,
                     scanning_interval,
+                    registry_check_interval,
+                    duplicate_payment_window,
+                    webhook_url,
+                    sign_responses,
+                    tls_key,
                 } => {
                     let (tx, rx) = std::sync::mpsc::channel();
                     let _ = ctrlc::try_set_handler(move || {
@@ -106,6 +169,17 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                     if let Some(scanning_interval) = scanning_interval {
                         config.scanning_interval = Duration::from_secs(scanning_interval);
                     };
+                    if let Some(registry_check_interval) = registry_check_interval {
+                        config.registry_check_interval =
+                            Duration::from_secs(registry_check_interval);
+                    };
+                    config.duplicate_payment_window =
+                        duplicate_payment_window.map(Duration::from_secs);
+                    config.webhook_url = webhook_url;
+                    config.sign_responses = sign_responses;
+                    config.tls_cert = args.tls_cert;
+                    config.tls_key = tls_key;
+                    config.auth_token = args.auth_token;
                     if let Some(url) = electrum_url {
                         config.electrum_url = url;
                     } else if let Network::Regtest = args.network {
@@ -148,16 +222,23 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                     app.stop()?;
                     app.join_threads()?;
                     tracing::info!("Threads ended");
+                    Value::Null
                 }
                 ServerCommand::Scan => {
                     client.scan()?;
+                    Value::Null
                 }
+                ServerCommand::Subscribe {
+                    wallet,
+                    since,
+                    timeout,
+                } => serde_json::to_value(client.subscribe(wallet, since, timeout)?)?,
+                ServerCommand::FeeRates => serde_json::to_value(client.fee_rates()?)?,
                 ServerCommand::Stop => {
                     client.stop()?;
+                    Value::Null
                 }
             }
-
-            Value::Null
         }
         CliCommand::Signer(a) => match a.command {
             SignerCommand::Generate => {
@@ -168,17 +249,43 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 let j = client.signer_jade_id(emulator)?;
                 serde_json::to_value(j)?
             }
-            SignerCommand::Sign { signer, pset } => {
-                let r = client.signer_sign(signer, pset)?;
+            SignerCommand::Sign {
+                signer,
+                pset,
+                wallet,
+            } => {
+                let r = client.signer_sign(signer, pset, wallet)?;
                 serde_json::to_value(r)?
             }
+            SignerCommand::SessionStart { signer, pset } => {
+                serde_json::to_value(client.signer_session_start(signer, pset)?)?
+            }
+            SignerCommand::SessionComplete { id, signature } => {
+                let mut signatures = vec![];
+                for s in signature {
+                    let parts: Vec<_> = s.split(':').collect();
+                    let [index, public_key, signature] = parts[..] else {
+                        anyhow::bail!("invalid signature argument \"{s}\"");
+                    };
+                    signatures.push(request::SessionSignature {
+                        index: index
+                            .parse()
+                            .with_context(|| "error parsing signature input index")?,
+                        public_key: public_key.to_string(),
+                        signature: signature.to_string(),
+                    });
+                }
+                serde_json::to_value(client.signer_session_complete(id, signatures)?)?
+            }
             SignerCommand::LoadSoftware {
                 signer,
                 mnemonic,
                 persist,
+                mnemonic_file,
             } => {
                 let persist = persist.expect("required");
-                let j = client.signer_load_software(signer, mnemonic, persist)?;
+                let mnemonic = mnemonic.unwrap_or_default();
+                let j = client.signer_load_software(signer, mnemonic, persist, mnemonic_file)?;
                 serde_json::to_value(j)?
             }
             SignerCommand::LoadJade {
@@ -189,6 +296,10 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 let j = client.signer_load_jade(signer, id, emulator)?;
                 serde_json::to_value(j)?
             }
+            SignerCommand::LoadLedger { signer, emulator } => {
+                let j = client.signer_load_ledger(signer, emulator)?;
+                serde_json::to_value(j)?
+            }
             SignerCommand::LoadExternal {
                 signer,
                 fingerprint,
@@ -196,6 +307,15 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 let j = client.signer_load_external(signer, fingerprint)?;
                 serde_json::to_value(j)?
             }
+            SignerCommand::LoadRemote {
+                signer,
+                url,
+                remote_signer,
+                token,
+            } => {
+                let j = client.signer_load_remote(signer, url, remote_signer, token)?;
+                serde_json::to_value(j)?
+            }
             SignerCommand::List => serde_json::to_value(client.signer_list()?)?,
             SignerCommand::Details { signer } => {
                 let r = client.signer_details(signer)?;
@@ -227,8 +347,12 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
             }
         },
         CliCommand::Wallet(a) => match a.command {
-            WalletCommand::Load { descriptor, wallet } => {
-                let r = client.wallet_load(descriptor, wallet)?;
+            WalletCommand::Load {
+                descriptor,
+                wallet,
+                gap_limit,
+            } => {
+                let r = client.wallet_load(descriptor, wallet, gap_limit)?;
                 serde_json::to_value(r)?
             }
             WalletCommand::Unload { wallet } => {
@@ -242,10 +366,18 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 let r = client.wallet_balance(wallet, with_tickers)?;
                 serde_json::to_value(r)?
             }
+            WalletCommand::Summary { wallet } => {
+                let r = client.wallet_summary(wallet)?;
+                serde_json::to_value(r)?
+            }
             WalletCommand::Send {
                 wallet,
                 recipient,
                 fee_rate,
+                fee_target,
+                locktime,
+                draft,
+                force,
             } => {
                 let mut addressees = vec![];
                 for rec in recipient {
@@ -255,15 +387,19 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                     );
                 }
 
-                let r = client.wallet_send_many(wallet, addressees, fee_rate)?;
+                let r = client.wallet_send_many(
+                    wallet, addressees, fee_rate, fee_target, locktime, draft, force,
+                )?;
                 serde_json::to_value(r)?
             }
             WalletCommand::Drain {
                 wallet,
                 address,
                 fee_rate,
+                asset,
+                force,
             } => {
-                let r = client.wallet_drain(wallet, address, fee_rate)?;
+                let r = client.wallet_drain(wallet, address, fee_rate, asset, force)?;
                 serde_json::to_value(r)?
             }
             WalletCommand::Address {
@@ -272,11 +408,44 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 signer,
                 with_text_qr,
                 with_uri_qr,
+                skip_used,
+            } => {
+                let r = client.wallet_address(
+                    wallet,
+                    index,
+                    signer,
+                    with_text_qr,
+                    with_uri_qr,
+                    skip_used,
+                )?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::Addresses {
+                wallet,
+                change,
+                offset,
+                limit,
+            } => {
+                let r = client.wallet_addresses(wallet, change, offset, limit)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::AddressReserve {
+                wallet,
+                count,
+                index,
             } => {
-                let r = client.wallet_address(wallet, index, signer, with_text_qr, with_uri_qr)?;
+                let r = client.wallet_address_reserve(wallet, count, index)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::RecoveryScan {
+                mnemonic,
+                max_account,
+            } => {
+                let r = client.wallet_recovery_scan(mnemonic, max_account)?;
                 serde_json::to_value(r)?
             }
             WalletCommand::List => serde_json::to_value(client.wallet_list()?)?,
+            WalletCommand::SyncStatus => serde_json::to_value(client.wallet_sync_status()?)?,
             WalletCommand::Issue {
                 wallet,
                 satoshi_asset,
@@ -285,6 +454,7 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 address_token,
                 contract,
                 fee_rate,
+                locktime,
             } => {
                 let r = client.wallet_issue(
                     wallet,
@@ -294,6 +464,7 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                     address_token,
                     contract,
                     fee_rate,
+                    locktime,
                 )?;
                 serde_json::to_value(r)?
             }
@@ -317,17 +488,52 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 let r = client.wallet_burn(wallet, asset, satoshi_asset, fee_rate)?;
                 serde_json::to_value(r)?
             }
+            WalletCommand::Pegout {
+                wallet,
+                mainchain_address,
+                satoshi,
+                fee_rate,
+            } => {
+                let r = client.wallet_pegout(wallet, mainchain_address, satoshi, fee_rate)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::PeginAddress {
+                wallet,
+                fedpegscript,
+                index,
+            } => {
+                let r = client.wallet_pegin_address(wallet, fedpegscript, index)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::ClaimPegin {
+                wallet,
+                fedpegscript,
+                mainchain_tx,
+                txout_proof,
+                fee_rate,
+            } => {
+                let r = client.wallet_claim_pegin(
+                    wallet,
+                    fedpegscript,
+                    mainchain_tx,
+                    txout_proof,
+                    fee_rate,
+                )?;
+                serde_json::to_value(r)?
+            }
             WalletCommand::MultisigDesc {
                 descriptor_blinding_key,
                 kind,
                 threshold,
                 keyorigin_xpub,
+                signer,
             } => {
                 let r = client.wallet_multisig_descriptor(
                     descriptor_blinding_key.to_string(),
                     kind.to_string(),
                     threshold,
                     keyorigin_xpub,
+                    signer,
                 )?;
                 serde_json::to_value(r)?
             }
@@ -351,19 +557,73 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 wallet,
                 pset,
                 with_tickers,
+                max_fee_basis_points,
             } => {
-                let r = client.wallet_pset_details(wallet, pset, with_tickers)?;
+                let r =
+                    client.wallet_pset_details(wallet, pset, with_tickers, max_fee_basis_points)?;
                 serde_json::to_value(r)?
             }
             WalletCommand::Utxos { wallet } => {
                 let r = client.wallet_utxos(wallet)?;
                 serde_json::to_value(r)?
             }
+            WalletCommand::UtxosExport { wallet } => {
+                let r = client.wallet_utxos_export(wallet)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::LockUtxo { wallet, txid, vout } => {
+                let r = client.wallet_utxo_lock(wallet, txid, vout)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::UnlockUtxo { wallet, txid, vout } => {
+                let r = client.wallet_utxo_unlock(wallet, txid, vout)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::SetLabel {
+                wallet,
+                type_,
+                ref_,
+                label,
+            } => {
+                let r = client.wallet_set_label(wallet, type_, ref_, label)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::GetLabel {
+                wallet,
+                type_,
+                ref_,
+            } => {
+                let r = client.wallet_get_label(wallet, type_, ref_)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::ExportLabels { wallet } => {
+                let r = client.wallet_export_labels(wallet)?;
+                serde_json::to_value(r)?
+            }
+            WalletCommand::ImportLabels { wallet, labels } => {
+                let r = client.wallet_import_labels(wallet, labels)?;
+                serde_json::to_value(r)?
+            }
             WalletCommand::Txs {
                 wallet,
                 with_tickers,
+                offset,
+                limit,
+                asset,
+                min_height,
+                max_height,
+                direction,
             } => {
-                let r = client.wallet_txs(wallet, with_tickers)?;
+                let r = client.wallet_txs_filtered(request::WalletTxs {
+                    name: wallet,
+                    with_tickers,
+                    offset,
+                    limit,
+                    asset,
+                    min_height,
+                    max_height,
+                    direction: direction.map(Into::into),
+                })?;
                 serde_json::to_value(r)?
             }
             WalletCommand::Tx {
@@ -411,6 +671,7 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 serde_json::to_value(r)?
             }
             AssetCommand::List => serde_json::to_value(client.asset_list()?)?,
+            AssetCommand::Events => serde_json::to_value(client.asset_events()?)?,
             AssetCommand::Insert {
                 asset,
                 issuance_tx,
@@ -432,6 +693,61 @@ pub fn inner_main(args: args::Cli) -> anyhow::Result<Value> {
                 serde_json::to_value(r)?
             }
         },
+        CliCommand::Contact(a) => match a.command {
+            ContactCommand::Add {
+                name,
+                address,
+                descriptor,
+                index,
+            } => {
+                let r = client.contact_add(name, address, descriptor, index)?;
+                serde_json::to_value(r)?
+            }
+            ContactCommand::List => serde_json::to_value(client.contact_list()?)?,
+            ContactCommand::Remove { name } => {
+                let r = client.contact_remove(name)?;
+                serde_json::to_value(r)?
+            }
+        },
+        CliCommand::Draft(a) => match a.command {
+            DraftCommand::Save {
+                name,
+                pset,
+                wallet,
+                creator,
+                replace,
+            } => {
+                let r = client.draft_save(name, pset, wallet, creator, replace)?;
+                serde_json::to_value(r)?
+            }
+            DraftCommand::List { wallet } => serde_json::to_value(client.draft_list(wallet)?)?,
+            DraftCommand::Load { name } => serde_json::to_value(client.draft_load(name)?)?,
+            DraftCommand::Delete { name } => {
+                let r = client.draft_delete(name)?;
+                serde_json::to_value(r)?
+            }
+        },
+        CliCommand::Pset(a) => match a.command {
+            PsetCommand::SendNostr {
+                signer,
+                recipient,
+                pset,
+                relay,
+            } => serde_json::to_value(client.pset_send_nostr(signer, recipient, pset, relay)?)?,
+            PsetCommand::Inbox {
+                signer,
+                sender,
+                relay,
+                since,
+            } => serde_json::to_value(client.pset_inbox(signer, sender, relay, since)?)?,
+            PsetCommand::Export {
+                pset,
+                file,
+                format,
+                max_fragment_len,
+            } => pset_file::export(&pset, &file, format, max_fragment_len)?,
+            PsetCommand::Import { file } => pset_file::import(&file)?,
+        },
         CliCommand::Schema(a) => schema::schema(a, client)?,
         CliCommand::GenerateCompletion { shell } => {
             let mut result = vec![];
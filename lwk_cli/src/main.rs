@@ -1,22 +1,37 @@
 use clap::Parser;
 use lwk_app::Error;
-use lwk_cli::{inner_main, Cli};
+use lwk_cli::{format_output, inner_main, Cli, OutputMode};
 
 mod args;
 
 fn main() -> anyhow::Result<()> {
     let args = Cli::parse();
+    let mode = args.output_mode();
 
-    let value = match inner_main(args) {
-        Ok(value) => value,
+    match inner_main(args) {
+        Ok(value) => {
+            if let Some(output) = format_output(&value, mode) {
+                println!("{output}");
+            }
+        }
         Err(e) => {
             if let Some(Error::RpcError(e)) = e.downcast_ref::<Error>() {
-                serde_json::to_value(e)?
+                let value = serde_json::to_value(e)?;
+                match mode {
+                    OutputMode::Pretty => println!("{:#}", value),
+                    OutputMode::Json => {
+                        println!("{}", value);
+                        std::process::exit(1);
+                    }
+                    OutputMode::Quiet => {
+                        eprintln!("{}", value);
+                        std::process::exit(1);
+                    }
+                }
             } else {
                 return Err(e);
             }
         }
-    };
-    println!("{:#}", value);
+    }
     Ok(())
 }
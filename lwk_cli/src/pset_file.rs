@@ -0,0 +1,103 @@
+//! PSET files, to move PSETs to and from air-gapped signers without trusting the server with
+//! file system access
+
+use std::path::Path;
+
+use anyhow::Context;
+use elements::encode::{deserialize, serialize};
+use elements::pset::PartiallySignedTransaction;
+use serde_json::{json, Value};
+
+use crate::args::PsetFormat;
+
+/// The magic bytes of binary PSETs
+const PSET_MAGIC: &[u8] = b"pset\xff";
+
+/// Write the base64 `pset` to `file` encoded in `format`, returning the UR parts
+pub fn export(
+    pset: &str,
+    file: &Path,
+    format: PsetFormat,
+    max_fragment_len: usize,
+) -> anyhow::Result<Value> {
+    let pset: PartiallySignedTransaction = pset.parse().context("invalid PSET")?;
+    let bytes = serialize(&pset);
+    let ur = lwk_common::pset_to_ur_parts(&bytes, max_fragment_len);
+    let content = match format {
+        PsetFormat::Base64 => pset.to_string().into_bytes(),
+        PsetFormat::Binary => bytes,
+        PsetFormat::Ur => format!("{}\n", ur.join("\n")).into_bytes(),
+    };
+    std::fs::write(file, content).with_context(|| format!("cannot write {}", file.display()))?;
+    Ok(json!({ "file": file, "ur": ur }))
+}
+
+/// Read the PSET in `file`, detecting its format, returning it in base64
+pub fn import(file: &Path) -> anyhow::Result<Value> {
+    let content = std::fs::read(file).with_context(|| format!("cannot read {}", file.display()))?;
+    let (pset, format) = parse(&content)?;
+    Ok(json!({ "pset": pset.to_string(), "format": format }))
+}
+
+fn parse(content: &[u8]) -> anyhow::Result<(PartiallySignedTransaction, &'static str)> {
+    if content.starts_with(PSET_MAGIC) {
+        return Ok((
+            deserialize(content).context("invalid binary PSET")?,
+            "binary",
+        ));
+    }
+    let text = std::str::from_utf8(content)
+        .context("PSET file is neither binary nor text")?
+        .trim();
+    if text
+        .get(..3)
+        .map_or(false, |p| p.eq_ignore_ascii_case("ur:"))
+    {
+        let parts: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+        let bytes = lwk_common::pset_from_ur_parts(&parts)?;
+        Ok((deserialize(&bytes).context("invalid PSET in UR")?, "ur"))
+    } else {
+        Ok((text.parse().context("invalid base64 PSET")?, "base64"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pset_file() {
+        let pset = include_str!("../../lwk_common/test_data/pset_details/pset.base64").trim();
+        let dir = tempfile::tempdir().unwrap();
+        for (format, name) in [
+            (PsetFormat::Base64, "base64"),
+            (PsetFormat::Binary, "binary"),
+            (PsetFormat::Ur, "ur"),
+        ] {
+            let file = dir.path().join(name);
+            let r = export(pset, &file, format, 100).unwrap();
+            let parts = r["ur"].as_array().unwrap();
+            assert!(parts.len() > 1);
+            let r = import(&file).unwrap();
+            assert_eq!(r["pset"], pset);
+            assert_eq!(r["format"], name);
+        }
+
+        // Parts scanned in any order, uppercase
+        let parts = lwk_common::pset_to_ur_parts(
+            &serialize(&pset.parse::<PartiallySignedTransaction>().unwrap()),
+            100,
+        );
+        let mut shuffled: Vec<String> = parts.iter().rev().map(|p| p.to_uppercase()).collect();
+        let file = dir.path().join("scanned");
+        std::fs::write(&file, shuffled.join("\n")).unwrap();
+        assert_eq!(import(&file).unwrap()["pset"], pset);
+
+        shuffled.pop();
+        std::fs::write(&file, shuffled.join("\n")).unwrap();
+        let err = import(&file).unwrap_err().to_string();
+        assert!(err.contains("Missing parts"), "{err}");
+
+        assert!(export("invalid", &file, PsetFormat::Base64, 100).is_err());
+    }
+}
@@ -3,8 +3,9 @@ use lwk_rpc_model::request::Direction;
 use serde_json::Value;
 
 use crate::args::{
-    AssetSubCommandsEnum, DirectionCommand, MainCommand, SchemaArgs, ServerSubCommandsEnum,
-    SignerSubCommandsEnum, WalletSubCommandsEnum,
+    AssetSubCommandsEnum, ContactSubCommandsEnum, DirectionCommand, DraftSubCommandsEnum,
+    MainCommand, PsetSubCommandsEnum, SchemaArgs, ServerSubCommandsEnum, SignerSubCommandsEnum,
+    WalletSubCommandsEnum,
 };
 
 pub(crate) fn schema(a: SchemaArgs, client: Client) -> Result<Value, anyhow::Error> {
@@ -14,6 +15,9 @@ pub(crate) fn schema(a: SchemaArgs, client: Client) -> Result<Value, anyhow::Err
             MainCommand::Wallet(w) => client.schema(w.command.into(), Direction::Request)?,
             MainCommand::Signer(s) => client.schema(s.command.into(), Direction::Request)?,
             MainCommand::Asset(s) => client.schema(s.command.into(), Direction::Request)?,
+            MainCommand::Contact(s) => client.schema(s.command.into(), Direction::Request)?,
+            MainCommand::Draft(s) => client.schema(s.command.into(), Direction::Request)?,
+            MainCommand::Pset(s) => client.schema(s.command.into(), Direction::Request)?,
             MainCommand::Schema => client.schema(Method::Schema, Direction::Request)?,
         },
         DirectionCommand::Response(res) => match res.command {
@@ -21,6 +25,9 @@ pub(crate) fn schema(a: SchemaArgs, client: Client) -> Result<Value, anyhow::Err
             MainCommand::Wallet(w) => client.schema(w.command.into(), Direction::Response)?,
             MainCommand::Signer(s) => client.schema(s.command.into(), Direction::Response)?,
             MainCommand::Asset(s) => client.schema(s.command.into(), Direction::Response)?,
+            MainCommand::Contact(s) => client.schema(s.command.into(), Direction::Response)?,
+            MainCommand::Draft(s) => client.schema(s.command.into(), Direction::Response)?,
+            MainCommand::Pset(s) => client.schema(s.command.into(), Direction::Response)?,
             MainCommand::Schema => client.schema(Method::Schema, Direction::Response)?,
         },
     })
@@ -30,6 +37,8 @@ impl From<ServerSubCommandsEnum> for Method {
     fn from(value: ServerSubCommandsEnum) -> Self {
         match value {
             ServerSubCommandsEnum::Scan => Method::Scan,
+            ServerSubCommandsEnum::Subscribe => Method::Subscribe,
+            ServerSubCommandsEnum::FeeRates => Method::FeeRates,
             ServerSubCommandsEnum::Stop => Method::Stop,
         }
     }
@@ -41,18 +50,33 @@ impl From<WalletSubCommandsEnum> for Method {
             WalletSubCommandsEnum::Load => Method::WalletLoad,
             WalletSubCommandsEnum::Unload => Method::WalletUnload,
             WalletSubCommandsEnum::List => Method::WalletList,
+            WalletSubCommandsEnum::SyncStatus => Method::WalletSyncStatus,
             WalletSubCommandsEnum::Address => Method::WalletAddress,
+            WalletSubCommandsEnum::AddressReserve => Method::WalletAddressReserve,
+            WalletSubCommandsEnum::Addresses => Method::WalletAddresses,
+            WalletSubCommandsEnum::RecoveryScan => Method::WalletRecoveryScan,
             WalletSubCommandsEnum::Balance => Method::WalletBalance,
+            WalletSubCommandsEnum::Summary => Method::WalletSummary,
             WalletSubCommandsEnum::Send => Method::WalletSendMany,
             WalletSubCommandsEnum::Issue => Method::WalletIssue,
             WalletSubCommandsEnum::Reissue => Method::WalletReissue,
+            WalletSubCommandsEnum::PeginAddress => Method::WalletPeginAddress,
+            WalletSubCommandsEnum::ClaimPegin => Method::WalletClaimPegin,
+            WalletSubCommandsEnum::Pegout => Method::WalletPegout,
             WalletSubCommandsEnum::MultisigDesc => Method::WalletMultisigDescriptor,
             WalletSubCommandsEnum::Broadcast => Method::WalletBroadcast,
             WalletSubCommandsEnum::Details => Method::WalletDetails,
             WalletSubCommandsEnum::Combine => Method::WalletCombine,
             WalletSubCommandsEnum::PsetDetails => Method::WalletPsetDetails,
             WalletSubCommandsEnum::Utxos => Method::WalletUtxos,
+            WalletSubCommandsEnum::UtxosExport => Method::WalletUtxosExport,
             WalletSubCommandsEnum::Txs => Method::WalletTxs,
+            WalletSubCommandsEnum::LockUtxo => Method::WalletUtxoLock,
+            WalletSubCommandsEnum::UnlockUtxo => Method::WalletUtxoUnlock,
+            WalletSubCommandsEnum::SetLabel => Method::WalletSetLabel,
+            WalletSubCommandsEnum::GetLabel => Method::WalletGetLabel,
+            WalletSubCommandsEnum::ExportLabels => Method::WalletExportLabels,
+            WalletSubCommandsEnum::ImportLabels => Method::WalletImportLabels,
             WalletSubCommandsEnum::SetTxMemo => Method::WalletSetTxMemo,
             WalletSubCommandsEnum::SetAddrMemo => Method::WalletSetAddrMemo,
         }
@@ -66,11 +90,15 @@ impl From<SignerSubCommandsEnum> for Method {
             SignerSubCommandsEnum::JadeId => Method::SignerJadeId,
             SignerSubCommandsEnum::LoadSoftware => Method::SignerLoadSoftware,
             SignerSubCommandsEnum::LoadJade => Method::SignerLoadJade,
+            SignerSubCommandsEnum::LoadLedger => Method::SignerLoadLedger,
             SignerSubCommandsEnum::LoadExternal => Method::SignerLoadExternal,
+            SignerSubCommandsEnum::LoadRemote => Method::SignerLoadRemote,
             SignerSubCommandsEnum::Unload => Method::SignerUnload,
             SignerSubCommandsEnum::Details => Method::SignerDetails,
             SignerSubCommandsEnum::List => Method::SignerList,
             SignerSubCommandsEnum::Sign => Method::SignerSign,
+            SignerSubCommandsEnum::SessionStart => Method::SignerSessionStart,
+            SignerSubCommandsEnum::SessionComplete => Method::SignerSessionComplete,
             SignerSubCommandsEnum::SinglesigDesc => Method::SignerSinglesigDescriptor,
             SignerSubCommandsEnum::Xpub => Method::SignerXpub,
         }
@@ -83,9 +111,40 @@ impl From<AssetSubCommandsEnum> for Method {
             AssetSubCommandsEnum::Contract => Method::AssetContract,
             AssetSubCommandsEnum::Details => Method::AssetDetails,
             AssetSubCommandsEnum::List => Method::AssetList,
+            AssetSubCommandsEnum::Events => Method::AssetEvents,
             AssetSubCommandsEnum::Insert => Method::AssetInsert,
             AssetSubCommandsEnum::Remove => Method::AssetRemove,
             AssetSubCommandsEnum::Publish => Method::AssetPublish,
         }
     }
 }
+
+impl From<ContactSubCommandsEnum> for Method {
+    fn from(value: ContactSubCommandsEnum) -> Self {
+        match value {
+            ContactSubCommandsEnum::Add => Method::ContactAdd,
+            ContactSubCommandsEnum::List => Method::ContactList,
+            ContactSubCommandsEnum::Remove => Method::ContactRemove,
+        }
+    }
+}
+
+impl From<DraftSubCommandsEnum> for Method {
+    fn from(value: DraftSubCommandsEnum) -> Self {
+        match value {
+            DraftSubCommandsEnum::Save => Method::DraftSave,
+            DraftSubCommandsEnum::List => Method::DraftList,
+            DraftSubCommandsEnum::Load => Method::DraftLoad,
+            DraftSubCommandsEnum::Delete => Method::DraftDelete,
+        }
+    }
+}
+
+impl From<PsetSubCommandsEnum> for Method {
+    fn from(value: PsetSubCommandsEnum) -> Self {
+        match value {
+            PsetSubCommandsEnum::SendNostr => Method::PsetSendNostr,
+            PsetSubCommandsEnum::Inbox => Method::PsetInbox,
+        }
+    }
+}
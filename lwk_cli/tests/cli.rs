@@ -9,14 +9,16 @@ use std::{
 
 use clap::{Parser, ValueEnum};
 use elements::encode::serialize;
-use elements::hex::ToHex;
+use elements::hex::{FromHex, ToHex};
 use elements::{pset::PartiallySignedTransaction, Address};
-use lwk_containers::{testcontainers::clients, JadeEmulator, EMULATOR_PORT};
+use lwk_containers::{
+    testcontainers::clients, JadeEmulator, LedgerEmulator, EMULATOR_PORT, LEDGER_EMULATOR_PORT,
+};
 use serde_json::Value;
 
 use lwk_cli::{
-    inner_main, AssetSubCommandsEnum, Cli, ServerSubCommandsEnum, SignerSubCommandsEnum,
-    WalletSubCommandsEnum,
+    inner_main, AssetSubCommandsEnum, Cli, ContactSubCommandsEnum, DraftSubCommandsEnum,
+    PsetSubCommandsEnum, ServerSubCommandsEnum, SignerSubCommandsEnum, WalletSubCommandsEnum,
 };
 use lwk_test_util::{setup, TestElectrumServer};
 use tempfile::TempDir;
@@ -296,6 +298,81 @@ fn send(
     complete(cli, wallet, get_str(&r, "pset"), signers)
 }
 
+#[test]
+fn test_sign_change_check() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    singlesig_wallet(&cli, "w2", "s1", "slip77", "shwpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let node_address = server.node_getnewaddress();
+    let recipient = format!("--recipient {node_address}:1000:{policy_asset}");
+    let r = sh(&format!("{cli} wallet send --wallet w1 {recipient}"));
+    let pset = get_str(&r, "pset");
+    let pset_unsigned: PartiallySignedTransaction = pset.parse().unwrap();
+
+    // The change belongs to the wallet which created the transaction
+    let r = sh(&format!(
+        "{cli} signer sign --signer s1 --pset {pset} --wallet w1"
+    ));
+    let pset_signed: PartiallySignedTransaction = get_str(&r, "pset").parse().unwrap();
+    assert_ne!(pset_signed, pset_unsigned);
+
+    // The change claimed by the signer is not of another wallet of the same signer
+    let err = sh_err(&format!(
+        "{cli} signer sign --signer s1 --pset {pset} --wallet w2"
+    ));
+    assert!(err.contains("is claimed as change but does not belong to the wallet"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_signer_mnemonic_file() {
+    let (t, tmp, cli, params, _server, _) = setup_cli(false);
+
+    let mnemonic = lwk_test_util::TEST_MNEMONIC;
+    let path = tmp.path().join("mnemonic.txt");
+    fs::write(&path, format!("{mnemonic}\n")).unwrap();
+    let path = path.display();
+    sh(&format!(
+        "{cli} signer load-software --persist true --mnemonic-file {path} --signer s1"
+    ));
+    let r = sh(&format!("{cli} signer details -s s1"));
+    assert_eq!(get_str(&r, "mnemonic"), mnemonic);
+
+    let err = sh_err(&format!(
+        "{cli} signer load-software --persist true --mnemonic-file {path}.missing --signer s2"
+    ));
+    assert!(err.contains("No such file"));
+
+    // Only the path is persisted
+    let state = fs::read_to_string(tmp.path().join("liquid-regtest").join("state.json")).unwrap();
+    assert!(state.contains("mnemonic.txt"));
+    assert!(!state.contains(mnemonic));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+
+    let t = {
+        let cli = cli.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli} server start {params}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    let r = sh(&format!("{cli} signer details -s s1"));
+    assert_eq!(get_str(&r, "mnemonic"), mnemonic);
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
 #[test]
 fn test_state_regression() {
     let server = setup(false);
@@ -524,6 +601,88 @@ fn test_signer_external() {
     t.join().unwrap();
 }
 
+#[test]
+fn test_signer_remote() {
+    let (t, _tmp, cli, params, server, _) = setup_cli(false);
+
+    // Another server holding the keys
+    let tmp2 = tempfile::tempdir().unwrap();
+    let addr2 = get_available_addr().unwrap();
+    let cli2 = format!("cli --addr {addr2} -n regtest");
+    let params2 = format!(
+        "--datadir {} --electrum-url {}",
+        tmp2.path().display(),
+        server.electrs.electrum_url
+    );
+    let t2 = {
+        let cli2 = cli2.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli2} server start {params2}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    sw_signer(&cli2, "s1");
+    let details = sh(&format!("{cli2} signer details -s s1"));
+
+    let r = sh(&format!(
+        "{cli} signer load-remote --signer r1 --url http://{addr2} --remote-signer s1"
+    ));
+    assert_eq!(get_str(&r, "fingerprint"), get_str(&details, "fingerprint"));
+    let r = sh(&format!("{cli} signer details -s r1"));
+    assert_eq!(get_str(&r, "type"), "remote");
+    assert_eq!(get_str(&r, "xpub"), get_str(&details, "xpub"));
+    assert!(r.get("mnemonic").is_none());
+    assert_eq!(
+        keyorigin(&cli, "r1", "bip84"),
+        keyorigin(&cli2, "s1", "bip84")
+    );
+
+    // The wallet is loaded only in the first server
+    let r = sh(&format!(
+        "{cli2} signer singlesig-desc -s s1 --descriptor-blinding-key slip77 --kind wpkh"
+    ));
+    let desc = get_str(&r, "descriptor");
+    sh(&format!("{cli} wallet load -w w1 -d {desc}"));
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let node_address = server.node_getnewaddress();
+    let recipient = format!("--recipient {node_address}:1000:{policy_asset}");
+    let r = sh(&format!("{cli} wallet send --wallet w1 {recipient}"));
+    let pset = get_str(&r, "pset");
+    let r = sh(&format!(
+        "{cli} signer sign -s r1 --wallet w1 --pset {pset}"
+    ));
+    let pset = get_str(&r, "pset");
+    let r = sh(&format!("{cli} wallet broadcast -w w1 --pset {pset}"));
+    wait_tx(&cli, "w1", get_str(&r, "txid"));
+
+    // Some actions are not possible with the remote signer
+    let err = sh_err(&format!(
+        "{cli} signer singlesig-desc --signer r1 --descriptor-blinding-key slip77 --kind wpkh"
+    ));
+    assert!(err.contains("Invalid operation for remote signer"));
+
+    // The remote signer is persisted
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+    let t = {
+        let cli = cli.clone();
+        let params = params.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli} server start {params}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    let r = sh(&format!("{cli} signer details -s r1"));
+    assert_eq!(get_str(&r, "type"), "remote");
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+    sh(&format!("{cli2} server stop"));
+    t2.join().unwrap();
+}
+
 #[test]
 fn test_wallet_load_unload_list() {
     let (t, _tmp, cli, _params, _server, _) = setup_cli(false);
@@ -546,12 +705,64 @@ fn test_wallet_load_unload_list() {
     let r = sh(&format!("{cli} wallet list"));
     assert_eq!(get_len(&r, "wallets"), 1);
 
+    sh(&format!("{cli} server scan"));
+    let r = sh(&format!("{cli} wallet sync-status"));
+    let wallets = r.get("wallets").unwrap().as_array().unwrap();
+    assert_eq!(wallets.len(), 1);
+    assert_eq!(get_str(&wallets[0], "name"), "custody");
+    assert!(wallets[0].get("tip_height").unwrap().as_u64().unwrap() > 0);
+    assert!(wallets[0].get("last_sync").unwrap().as_u64().unwrap() > 0);
+
     let result = sh(&format!("{cli} wallet unload --wallet custody"));
     let unloaded = result.get("unloaded").unwrap();
     assert_eq!(unloaded.get("name").unwrap().as_str().unwrap(), "custody");
 
     let r = sh(&format!("{cli} wallet list"));
     assert_eq!(get_len(&r, "wallets"), 0);
+    let r = sh(&format!("{cli} wallet sync-status"));
+    assert_eq!(get_len(&r, "wallets"), 0);
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_unconfidential_wallet_persist() {
+    let (t, _tmp, cli, params, _server, _) = setup_cli(false);
+
+    let xpub = "tpubD6NzVbkrYhZ4Was8nwnZi7eiWUNJq2LFpPSCMQLioUfUtT1e72GkRbmVeRAZc26j5MRUz2hRLsaVHJfs6L7ppNfLUrm9btQTuaEsLrT7D87";
+    let desc = format!("elwpkh({xpub}/*)");
+    sh(&format!("{cli} wallet load --wallet plain -d {desc}"));
+
+    // Unloading another wallet rewrites the whole persisted state
+    let ct_desc = format!(
+        "ct(c25deb86fa11e49d651d7eae27c220ef930fbd86ea023eebfa73e54875647963,elwpkh({xpub}/*))"
+    );
+    sh(&format!("{cli} wallet load --wallet other -d {ct_desc}"));
+    sh(&format!("{cli} wallet unload --wallet other"));
+
+    let r = sh(&format!("{cli} wallet list"));
+    let wallets = r.get("wallets").unwrap().as_array().unwrap();
+    let desc = get_str(&wallets[0], "descriptor").to_string();
+    assert!(desc.starts_with(&format!("elwpkh({xpub}/*)#")), "{desc}");
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+
+    let t = {
+        let cli = cli.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli} server start {params}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    let r = sh(&format!("{cli} wallet list"));
+    let wallets = r.get("wallets").unwrap().as_array().unwrap();
+    assert_eq!(wallets.len(), 1);
+    assert_eq!(get_str(&wallets[0], "name"), "plain");
+    assert_eq!(get_str(&wallets[0], "descriptor"), desc);
+    let r = sh(&format!("{cli} wallet details --wallet plain"));
+    assert_eq!(get_str(&r, "descriptor"), desc);
 
     sh(&format!("{cli} server stop"));
     t.join().unwrap();
@@ -660,131 +871,1206 @@ fn test_wallet_memos() {
 }
 
 #[test]
-fn test_wallet_details() {
-    let (t, _tmp, cli, _params, _server, _) = setup_cli(false);
+fn test_wallet_address_reserve() {
+    let (t, _tmp, cli, params, _server, _) = setup_cli(false);
 
     sw_signer(&cli, "s1");
-    sw_signer(&cli, "s2");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
 
-    // Single sig wallet
-    let r = sh(&format!(
-        "{cli} signer singlesig-desc --signer s1 --descriptor-blinding-key slip77 --kind wpkh"
-    ));
-    let desc_ss = get_str(&r, "descriptor");
-    sh(&format!("{cli} wallet load --wallet ss -d {desc_ss}"));
-    assert!(desc_ss.contains(&keyorigin(&cli, "s1", "bip84")));
+    let index = |r: &Value| r.get("index").unwrap().as_u64().unwrap();
+    let reserve = |count: u32| {
+        let r = sh(&format!(
+            "{cli} wallet address-reserve --wallet w1 --count {count}"
+        ));
+        let addresses = r.get("addresses").unwrap().as_array().unwrap().clone();
+        addresses.iter().map(index).collect::<Vec<_>>()
+    };
+
+    assert_eq!(reserve(3), vec![0, 1, 2]);
+    let r = sh(&format!("{cli} wallet address --wallet w1"));
+    assert_eq!(index(&r), 3);
+
+    // Addresses returned by wallet address are not reserved
+    assert_eq!(reserve(2), vec![3, 4]);
+    let r = sh(&format!("{cli} wallet address --wallet w1"));
+    assert_eq!(index(&r), 5);
+
+    // Reserved addresses can still be requested by index
+    let r = sh(&format!("{cli} wallet address --wallet w1 --index 1"));
+    assert_eq!(index(&r), 1);
 
+    // Nothing has been received, so no address is skipped as used
     let r = sh(&format!(
-        "{cli} signer singlesig-desc --signer s1 --descriptor-blinding-key slip77 --kind shwpkh"
+        "{cli} wallet address --wallet w1 --index 1 --skip-used"
     ));
-    let desc_sssh = get_str(&r, "descriptor");
-    sh(&format!("{cli} wallet load --wallet sssh -d {desc_sssh}"));
-    assert!(desc_sssh.contains(&keyorigin(&cli, "s1", "bip49")));
+    assert_eq!(index(&r), 1);
+    let r = sh(&format!("{cli} wallet addresses --wallet w1"));
+    let addresses = r.get("addresses").unwrap().as_array().unwrap();
+    assert_eq!(addresses.len(), 1);
+    assert!(!addresses[0].get("used").unwrap().as_bool().unwrap());
 
     let err = sh_err(&format!(
-        "{cli} signer singlesig-desc -s s1 --descriptor-blinding-key slip77-rand --kind wpkh"
+        "{cli} wallet address-reserve --wallet w1 --count 0"
     ));
-    let exp_err = "Random slip77 key not supported in singlesig descriptor generation";
-    assert!(err.contains(exp_err));
-
-    // Multi sig wallet
-    let r = sh(&format!("{cli} signer xpub --signer s1 --kind bip87"));
-    let xpub1 = get_str(&r, "keyorigin_xpub");
-    let r = sh(&format!("{cli} signer xpub --signer s2 --kind bip87"));
-    let xpub2 = get_str(&r, "keyorigin_xpub");
-    let r = sh(&format!("{cli} wallet multisig-desc --descriptor-blinding-key slip77-rand --kind wsh --threshold 2 --keyorigin-xpub {xpub1} --keyorigin-xpub {xpub2}"));
-    let desc_ms = get_str(&r, "descriptor");
-    sh(&format!("{cli} wallet load --wallet ms -d {desc_ms}"));
-
-    let err = sh_err(&format!("{cli} wallet multisig-desc --descriptor-blinding-key slip77 --kind wsh --threshold 2 --keyorigin-xpub {xpub1} --keyorigin-xpub {xpub2}"));
-    let exp_err = "Deterministic slip77 key not supported in multisig descriptor generation";
-    assert!(err.contains(exp_err));
+    assert!(err.contains("count must be at least 1"));
 
-    // Multi sig wallet, same signers
-    let r = sh(&format!("{cli} wallet multisig-desc --descriptor-blinding-key slip77-rand --kind wsh --threshold 2 --keyorigin-xpub {xpub1} --keyorigin-xpub {xpub1}"));
-    let desc_ms_same_signers = get_str(&r, "descriptor");
-    sh(&format!(
-        "{cli} wallet load --wallet ms_same_signers -d {desc_ms_same_signers}"
+    // Nothing has been received and the gap limit is 20, the addresses up to index 19 can be
+    // handed out, and index 5 is the next one
+    let err = sh_err(&format!(
+        "{cli} wallet address-reserve --wallet w1 --count 15"
     ));
+    assert!(err.contains("at most 14 more can be reserved from index 5"));
 
-    // Details
-    let r = sh(&format!("{cli} wallet details --wallet ss"));
-    assert_eq!(get_desc(&r), remove_checksum(desc_ss));
-    assert!(get_str(&r, "warnings").is_empty());
-    assert_eq!(get_str(&r, "type"), "wpkh");
-    let signers = r.get("signers").unwrap().as_array().unwrap();
-    assert_eq!(signers.len(), 1);
-    assert_eq!(get_str(&signers[0], "name"), "s1");
-
-    let r = sh(&format!("{cli} wallet details --wallet sssh"));
-    assert_eq!(get_desc(&r), remove_checksum(desc_sssh));
-    assert!(get_str(&r, "warnings").is_empty());
-    assert_eq!(get_str(&r, "type"), "sh_wpkh");
-    let signers = r.get("signers").unwrap().as_array().unwrap();
-    assert_eq!(signers.len(), 1);
-    assert_eq!(get_str(&signers[0], "name"), "s1");
+    // Stop and restart to check persistence
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
 
-    let r = sh(&format!("{cli} wallet details --wallet ms"));
-    assert_eq!(get_desc(&r), remove_checksum(desc_ms));
-    assert!(get_str(&r, "warnings").is_empty());
-    assert_eq!(get_str(&r, "type"), "wsh_multi_2of2");
-    let signers = r.get("signers").unwrap().as_array().unwrap();
-    assert_eq!(signers.len(), 2);
-    assert_eq!(get_str(&signers[0], "name"), "s1");
-    assert_eq!(get_str(&signers[1], "name"), "s2");
+    let t = {
+        let cli = cli.clone();
+        let params = params.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli} server start {params}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
 
-    sh(&format!("{cli} signer unload --signer s2"));
-    let r = sh(&format!("{cli} wallet details --wallet ms"));
-    let signers = r.get("signers").unwrap().as_array().unwrap();
-    assert_eq!(signers.len(), 2);
-    assert_eq!(get_str(&signers[0], "name"), "s1");
-    assert!(signers[1].get("name").is_none());
+    let r = sh(&format!("{cli} wallet address --wallet w1"));
+    assert_eq!(index(&r), 5);
 
-    let r = sh(&format!("{cli} wallet details --wallet ms_same_signers"));
-    assert_eq!(
-        get_str(&r, "warnings"),
-        "wallet has multiple signers with the same fingerprint"
-    );
-    assert_eq!(r.get("type").unwrap().as_str().unwrap(), "wsh_multi_2of2");
-    let signers = r.get("signers").unwrap().as_array().unwrap();
-    assert_eq!(signers.len(), 2);
-    assert_eq!(get_str(&signers[0], "name"), "s1");
-    assert_eq!(get_str(&signers[1], "name"), "s1");
+    // Unloading the wallet removes the reservations
+    sh(&format!("{cli} wallet unload --wallet w1"));
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    let r = sh(&format!("{cli} wallet address --wallet w1"));
+    assert_eq!(index(&r), 0);
 
     sh(&format!("{cli} server stop"));
     t.join().unwrap();
 }
 
 #[test]
-fn test_broadcast() {
-    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+fn test_contacts() {
+    let (t, _tmp, cli, params, server, _) = setup_cli(false);
 
     sw_signer(&cli, "s1");
+    sw_signer(&cli, "s2");
     singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    singlesig_wallet(&cli, "w2", "s2", "slip77", "wpkh");
     fund(&server, &cli, "w1", 1_000_000);
 
-    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
-    assert_eq!(1_000_000, get_balance(&cli, "w1", policy_asset));
-    let addr = server.node_getnewaddress().to_string();
-    send(&cli, "w1", &addr, policy_asset, 1000, &["s1"]);
-    assert!(1_000_000 > get_balance(&cli, "w1", policy_asset));
+    let r = sh(&format!("{cli} wallet details --wallet w2"));
+    let desc = get_str(&r, "descriptor").to_string();
+    let addr = address(&cli, "w2");
 
-    sh(&format!("{cli} server stop"));
-    t.join().unwrap();
-}
+    let r = sh(&format!("{cli} contact add --name alice --address {addr}"));
+    assert_eq!(get_str(&r, "address"), addr);
+    let r = sh(&format!("{cli} contact add --name bob --descriptor {desc}"));
+    assert_eq!(r.get("index").unwrap().as_u64().unwrap(), 0);
 
-#[test]
-fn test_issue() {
-    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+    let err = sh_err(&format!("{cli} contact add --name alice --address {addr}"));
+    assert!(err.contains("Contact 'alice' already exists"));
 
-    sw_signer(&cli, "s1");
-    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
-    fund(&server, &cli, "w1", 1_000_000);
+    // Addresses of other networks are rejected
+    let testnet_addr = "tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn";
+    let err = sh_err(&format!(
+        "{cli} contact add --name carol --address {testnet_addr}"
+    ));
+    assert!(err.contains("is not for network"));
 
-    let r = sh(&format!("{cli} asset contract --domain example.com --issuer-pubkey 035d0f7b0207d9cc68870abfef621692bce082084ed3ca0c1ae432dd12d889be01 --name example --ticker EXMP"));
-    let contract = serde_json::to_string(&r).unwrap();
+    // Pay the contacts
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let txid = send(&cli, "w1", "contact:alice", policy_asset, 1_000, &["s1"]);
+    wait_tx(&cli, "w2", &txid);
+    // Building a transaction does not use up the address of the contact
     let r = sh(&format!(
-        "{cli} wallet issue --wallet w1 --satoshi-asset 1000 --satoshi-token 1 --contract '{contract}'"
+        "{cli} wallet send --wallet w1 --recipient contact:bob:1000:{policy_asset}"
+    ));
+    assert!(r.get("pset").is_some());
+    let r = sh(&format!("{cli} contact list"));
+    let contacts = r.get("contacts").unwrap().as_array().unwrap();
+    assert_eq!(contacts[1].get("index").unwrap().as_u64().unwrap(), 0);
+    let txid = send(&cli, "w1", "contact:bob", policy_asset, 1_000, &["s1"]);
+    wait_tx(&cli, "w2", &txid);
+
+    let err = sh_err(&format!(
+        "{cli} wallet send --wallet w1 --recipient contact:carol:1000:{policy_asset}"
+    ));
+    assert!(err.contains("Contact 'carol' does not exist"));
+
+    let r = sh(&format!("{cli} contact list"));
+    let contacts = r.get("contacts").unwrap().as_array().unwrap();
+    assert_eq!(contacts.len(), 2);
+    assert_eq!(get_str(&contacts[0], "name"), "alice");
+    assert_eq!(get_str(&contacts[1], "name"), "bob");
+    // The next payment to bob uses a fresh address
+    assert_eq!(contacts[1].get("index").unwrap().as_u64().unwrap(), 1);
+
+    // Stop and restart to check persistence
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+
+    let t = {
+        let cli = cli.clone();
+        let params = params.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli} server start {params}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    let r = sh(&format!("{cli} contact list"));
+    let contacts = r.get("contacts").unwrap().as_array().unwrap();
+    assert_eq!(contacts.len(), 2);
+    assert_eq!(contacts[1].get("index").unwrap().as_u64().unwrap(), 1);
+
+    sh(&format!("{cli} contact remove --name alice"));
+    let r = sh(&format!("{cli} contact list"));
+    assert_eq!(get_len(&r, "contacts"), 1);
+    let err = sh_err(&format!("{cli} contact remove --name alice"));
+    assert!(err.contains("Contact 'alice' does not exist"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_drafts() {
+    let (t, _tmp, cli, params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let node_address = server.node_getnewaddress();
+    let recipient = format!("--recipient {node_address}:1000:{policy_asset}");
+    let r = sh(&format!("{cli} wallet send --wallet w1 {recipient}"));
+    let pset = get_str(&r, "pset").to_string();
+
+    let r = sh(&format!(
+        "{cli} draft save --name pay -w w1 --pset {pset} --creator alice"
+    ));
+    assert_eq!(get_str(&r, "wallet"), "w1");
+    assert_eq!(get_str(&r, "creator"), "alice");
+    assert!(r.get("timestamp").unwrap().as_u64().unwrap() > 0);
+
+    let err = sh_err(&format!("{cli} draft save --name pay -w w1 --pset {pset}"));
+    assert!(err.contains("Draft 'pay' already exists"));
+    let err = sh_err(&format!(
+        "{cli} draft save --name other -w w2 --pset {pset}"
+    ));
+    assert!(err.contains("Wallet 'w2' does not exist"));
+
+    // Sign and save the progress
+    let r = sh(&format!("{cli} signer sign -s s1 --pset {pset}"));
+    let signed = get_str(&r, "pset").to_string();
+    sh(&format!(
+        "{cli} draft save --name pay -w w1 --pset {signed} --creator bob --replace"
+    ));
+
+    let r = sh(&format!("{cli} draft list"));
+    let drafts = r.get("drafts").unwrap().as_array().unwrap();
+    assert_eq!(drafts.len(), 1);
+    assert_eq!(get_str(&drafts[0], "name"), "pay");
+    assert_eq!(get_str(&drafts[0], "creator"), "bob");
+    assert!(drafts[0].get("pset").is_none());
+    let timestamp = drafts[0].get("timestamp").unwrap().as_u64().unwrap();
+    let r = sh(&format!("{cli} draft list -w w2"));
+    assert_eq!(get_len(&r, "drafts"), 0);
+
+    // Stop and restart to check persistence
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+
+    let t = {
+        let cli = cli.clone();
+        let params = params.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli} server start {params}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    let r = sh(&format!("{cli} draft load --name pay"));
+    assert_eq!(get_str(&r, "pset"), signed);
+    assert_eq!(get_str(&r, "creator"), "bob");
+    assert_eq!(r.get("timestamp").unwrap().as_u64().unwrap(), timestamp);
+
+    let r = sh(&format!("{cli} wallet broadcast -w w1 --pset {signed}"));
+    wait_tx(&cli, "w1", get_str(&r, "txid"));
+
+    // Replacing the draft transaction pays the same change address
+    let scripts = |pset: &str| {
+        let pset: PartiallySignedTransaction = pset.parse().unwrap();
+        let mut scripts: Vec<_> = pset
+            .outputs()
+            .iter()
+            .map(|o| o.script_pubkey.clone())
+            .collect();
+        scripts.sort();
+        scripts
+    };
+    let send = format!("{cli} wallet send --wallet w1 {recipient} --fee-rate 200");
+    let r = sh(&send);
+    assert_ne!(scripts(get_str(&r, "pset")), scripts(&pset));
+    let r = sh(&format!("{send} --draft pay"));
+    assert_eq!(scripts(get_str(&r, "pset")), scripts(&pset));
+    let err = sh_err(&format!("{send} --draft missing"));
+    assert!(err.contains("Draft 'missing' does not exist"));
+
+    sh(&format!("{cli} draft delete --name pay"));
+    let r = sh(&format!("{cli} draft list"));
+    assert_eq!(get_len(&r, "drafts"), 0);
+    let err = sh_err(&format!("{cli} draft load --name pay"));
+    assert!(err.contains("Draft 'pay' does not exist"));
+
+    // Drafts are removed with their wallet
+    sh(&format!("{cli} draft save --name pay -w w1 --pset {pset}"));
+    sh(&format!("{cli} wallet unload --wallet w1"));
+    let r = sh(&format!("{cli} draft list"));
+    assert_eq!(get_len(&r, "drafts"), 0);
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_signing_session() {
+    use elements::bitcoin::bip32::{DerivationPath, Xpub};
+    use elements::secp256k1_zkp::{Message, Secp256k1};
+
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    // The external signer, only its fingerprint and xpub are known to the server
+    let secp = Secp256k1::new();
+    let xprv = lwk_test_util::generate_xprv();
+    let fingerprint = xprv.fingerprint(&secp);
+    let path: DerivationPath = "m/84h/1h/0h".parse().unwrap();
+    let xpub = Xpub::from_priv(&secp, &xprv.derive_priv(&secp, &path).unwrap());
+    let slip77 = lwk_test_util::TEST_MNEMONIC_SLIP77;
+    let desc = format!("ct(slip77({slip77}),elwpkh([{fingerprint}/84h/1h/0h]{xpub}/<0;1>/*))");
+    sh(&format!(
+        "{cli} signer load-external --fingerprint {fingerprint} --signer mpc"
+    ));
+    sh(&format!("{cli} wallet load --wallet w1 -d {desc}"));
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let node_address = server.node_getnewaddress();
+    let r = sh(&format!(
+        "{cli} wallet send --wallet w1 --recipient {node_address}:1000:{}",
+        "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225"
+    ));
+    let pset = get_str(&r, "pset");
+
+    let r = sh(&format!(
+        "{cli} signer session-start --signer mpc --pset {pset}"
+    ));
+    let id = get_str(&r, "id");
+    let requests = r.get("requests").unwrap().as_array().unwrap();
+    assert_eq!(requests.len(), 1);
+
+    // Sign the digests outside of the server
+    let mut args = vec![];
+    for request in requests {
+        assert_eq!(get_str(request, "fingerprint"), fingerprint.to_string());
+        let path: DerivationPath = get_str(request, "derivation_path").parse().unwrap();
+        let key = xprv.derive_priv(&secp, &path).unwrap().private_key;
+        let sighash = Vec::<u8>::from_hex(get_str(request, "sighash")).unwrap();
+        let msg = Message::from_digest_slice(&sighash).unwrap();
+        let signature = secp.sign_ecdsa(&msg, &key).serialize_der().to_hex();
+        let index = request.get("index").unwrap().as_u64().unwrap();
+        let public_key = get_str(request, "public_key");
+        args.push(format!("--signature {index}:{public_key}:{signature}"));
+    }
+    let args = args.join(" ");
+
+    let err = sh_err(&format!(
+        "{cli} signer session-complete --id {id} {}",
+        args.replace("--signature 0:", "--signature 1:")
+    ));
+    assert!(err.contains("was not requested"));
+    let r = sh(&format!("{cli} signer session-complete --id {id} {args}"));
+    let signed = get_str(&r, "pset");
+    let r = sh(&format!("{cli} wallet broadcast -w w1 --pset {signed}"));
+    wait_tx(&cli, "w1", get_str(&r, "txid"));
+
+    // Completed sessions are removed
+    let err = sh_err(&format!("{cli} signer session-complete --id {id} {args}"));
+    assert!(err.contains(&format!("Signing session '{id}' does not exist")));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+/// Wait for a notification of the given kind, returning it and the last seen identifier
+fn wait_notification(cli: &str, wallet: &str, since: u64, kind: &str) -> (Value, u64) {
+    let mut since = since;
+    for _ in 0..5 {
+        let r = sh(&format!(
+            "{cli} server subscribe -w {wallet} --since {since} --timeout 30"
+        ));
+        let notifications = r.get("notifications").unwrap().as_array().unwrap();
+        if let Some(n) = notifications.iter().find(|n| get_str(n, "kind") == kind) {
+            return (n.clone(), r.get("last_id").unwrap().as_u64().unwrap());
+        }
+        since = r.get("last_id").unwrap().as_u64().unwrap();
+    }
+    panic!("notification {kind} not received");
+}
+
+#[test]
+fn test_subscribe() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    sh(&format!("{cli} server scan"));
+    let r = sh(&format!("{cli} server subscribe"));
+    let last_id = r.get("last_id").unwrap().as_u64().unwrap();
+
+    // Nothing happened, wait until the timeout
+    let r = sh(&format!(
+        "{cli} server subscribe --since {last_id} --timeout 1"
+    ));
+    assert_eq!(get_len(&r, "notifications"), 0);
+    assert_eq!(r.get("last_id").unwrap().as_u64().unwrap(), last_id);
+
+    let addr = Address::from_str(&address(&cli, "w1")).unwrap();
+    let txid = server.node_sendtoaddress(&addr, 10_000, None).to_string();
+    let (n, last_id) = wait_notification(&cli, "w1", last_id, "tx_received");
+    assert_eq!(get_str(&n, "wallet"), "w1");
+    assert_eq!(get_str(&n, "txid"), txid);
+    assert!(n.get("height").unwrap().is_null());
+
+    server.generate(1);
+    let (n, _) = wait_notification(&cli, "w1", last_id, "block");
+    assert!(n.get("wallet").unwrap().is_null());
+    let (n, _) = wait_notification(&cli, "w1", last_id, "tx_confirmed");
+    assert_eq!(get_str(&n, "txid"), txid);
+    assert!(n.get("height").unwrap().as_u64().is_some());
+
+    let err = sh_err(&format!("{cli} server subscribe -w w2"));
+    assert!(err.contains("Wallet 'w2' does not exist"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_fee_rates() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    let r = sh(&format!("{cli} server fee-rates"));
+    let estimates = r.get("estimates").unwrap().as_object().unwrap();
+    assert!(estimates.values().all(|v| v.as_f64().unwrap() > 0.0));
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let node_address = server.node_getnewaddress();
+    let recipient = format!("--recipient {node_address}:1000:{policy_asset}");
+
+    // With no estimates the fallback fee rate is used, higher than the default one
+    let r = sh(&format!("{cli} wallet send --wallet w1 {recipient}"));
+    let pset: PartiallySignedTransaction = get_str(&r, "pset").parse().unwrap();
+    let default_fee = pset.extract_tx().unwrap().all_fees()[&policy_asset.parse().unwrap()];
+    let r = sh(&format!(
+        "{cli} wallet send --wallet w1 {recipient} --fee-target 1"
+    ));
+    let pset: PartiallySignedTransaction = get_str(&r, "pset").parse().unwrap();
+    let target_fee = pset.extract_tx().unwrap().all_fees()[&policy_asset.parse().unwrap()];
+    assert!(target_fee >= default_fee);
+    complete(&cli, "w1", get_str(&r, "pset"), &["s1"]);
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_utxos_export() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+    fund(&server, &cli, "w1", 2_000_000);
+    sh(&format!("{cli} server scan"));
+
+    let r = sh(&format!("{cli} wallet utxos-export --wallet w1"));
+    let utxos = r.get("utxos").unwrap().as_array().unwrap();
+    assert_eq!(utxos.len(), 2);
+    let mut values: Vec<u64> = utxos
+        .iter()
+        .map(|u| u.get("value").unwrap().as_u64().unwrap())
+        .collect();
+    values.sort();
+    assert_eq!(values, vec![1_000_000, 2_000_000]);
+    for utxo in utxos {
+        let bytes = Vec::<u8>::from_hex(get_str(utxo, "witness_utxo")).unwrap();
+        let txout: elements::TxOut = elements::encode::deserialize(&bytes).unwrap();
+        assert_eq!(txout.script_pubkey.to_hex(), get_str(utxo, "script_pubkey"));
+    }
+
+    let pruned_funds = r.get("pruned_funds").unwrap().as_array().unwrap();
+    assert_eq!(pruned_funds.len(), 2);
+    for funds in pruned_funds {
+        let txid = get_str(funds, "txid");
+        let committed = server.node_verifytxoutproof(get_str(funds, "txoutproof"));
+        assert_eq!(committed, vec![txid.to_string()]);
+        let bytes = Vec::<u8>::from_hex(get_str(funds, "rawtransaction")).unwrap();
+        let tx: elements::Transaction = elements::encode::deserialize(&bytes).unwrap();
+        assert_eq!(tx.txid().to_string(), txid);
+    }
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_locktime() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let node_address = server.node_getnewaddress();
+    let recipient = format!("--recipient {node_address}:1000:{policy_asset}");
+
+    // A locktime in the past doesn't prevent the broadcast
+    let r = sh(&format!(
+        "{cli} wallet send --wallet w1 {recipient} --locktime height:1"
+    ));
+    let pset: PartiallySignedTransaction = get_str(&r, "pset").parse().unwrap();
+    assert_eq!(
+        pset.extract_tx().unwrap().lock_time,
+        elements::LockTime::from_height(1).unwrap()
+    );
+    complete(&cli, "w1", get_str(&r, "pset"), &["s1"]);
+
+    // A locktime in the future does
+    let r = sh(&format!(
+        "{cli} wallet send --wallet w1 {recipient} --locktime height:400000"
+    ));
+    let r = sh(&format!(
+        "{cli} signer sign --signer s1 --pset {}",
+        get_str(&r, "pset")
+    ));
+    let err = sh_err(&format!(
+        "{cli} wallet broadcast --wallet w1 --pset {}",
+        get_str(&r, "pset")
+    ));
+    assert!(err.contains("non-final"), "{err}");
+
+    let err = sh_err(&format!(
+        "{cli} wallet send --wallet w1 {recipient} --locktime block:1"
+    ));
+    assert!(err.contains("Invalid locktime"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+#[cfg(not(feature = "nostr"))]
+fn test_pset_nostr_disabled() {
+    let (t, _tmp, cli, _params, _server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    let err = sh_err(&format!(
+        "{cli} pset inbox --signer s1 --relay ws://127.0.0.1:1"
+    ));
+    assert!(err.contains("Feature \"nostr\" is disabled"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_pset_export_import() {
+    let (t, tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let node_address = server.node_getnewaddress();
+    let r = sh(&format!(
+        "{cli} wallet send --wallet w1 --recipient {node_address}:1000:{policy_asset}"
+    ));
+    let pset = get_str(&r, "pset").to_string();
+
+    // Every format round trips
+    for format in ["base64", "binary", "ur"] {
+        let file = tmp.path().join(format!("unsigned.{format}"));
+        let file = file.display();
+        let r = sh(&format!(
+            "{cli} pset export --pset {pset} --file {file} --format {format}"
+        ));
+        let parts = r.get("ur").unwrap().as_array().unwrap();
+        // A blinded PSET does not fit in a single QR code
+        assert!(parts.len() > 1);
+        assert!(parts[0].as_str().unwrap().starts_with("ur:crypto-pset/1-"));
+        let r = sh(&format!("{cli} pset import --file {file}"));
+        assert_eq!(get_str(&r, "pset"), pset);
+        assert_eq!(get_str(&r, "format"), format);
+    }
+
+    // Sign the PSET "offline" and bring it back as UR parts, scanned from QR codes in any order
+    let file = tmp.path().join("unsigned.ur");
+    let r = sh(&format!("{cli} pset import --file {}", file.display()));
+    let r = sh(&format!(
+        "{cli} signer sign --signer s1 --pset {}",
+        get_str(&r, "pset")
+    ));
+    let r = sh(&format!(
+        "{cli} pset export --pset {} --file {} --format binary",
+        get_str(&r, "pset"),
+        tmp.path().join("signed.pset").display()
+    ));
+    let file = tmp.path().join("signed.ur");
+    let scanned: Vec<String> = r
+        .get("ur")
+        .unwrap()
+        .as_array()
+        .unwrap()
+        .iter()
+        .rev()
+        .map(|p| p.as_str().unwrap().to_uppercase())
+        .collect();
+    std::fs::write(&file, scanned.join("\n")).unwrap();
+    let r = sh(&format!("{cli} pset import --file {}", file.display()));
+    let r = sh(&format!(
+        "{cli} wallet broadcast --wallet w1 --pset {}",
+        get_str(&r, "pset")
+    ));
+    wait_tx(&cli, "w1", get_str(&r, "txid"));
+
+    let file = tmp.path().join("invalid");
+    std::fs::write(&file, "ur:crypto-psbt/aeadaolazmjendeoti").unwrap();
+    let err = sh_err(&format!("{cli} pset import --file {}", file.display()));
+    assert!(err.contains("Not a UR of type crypto-pset"), "{err}");
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_duplicate_payment() {
+    let (t, _tmp, cli, params, server, _) = setup_cli(false);
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+
+    let t = {
+        let cli = cli.clone();
+        std::thread::spawn(move || {
+            sh(&format!(
+                "{cli} server start --scanning-interval 1 --duplicate-payment-window 600 {params}"
+            ));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let node_address = server.node_getnewaddress();
+    let send = format!("{cli} wallet send --wallet w1 --recipient {node_address}:1000:");
+    sh(&send);
+
+    // The same payment, also with the explicit policy asset, is refused
+    let err = sh_err(&send);
+    assert!(err.contains("An identical payment was built"), "{err}");
+    let err = sh_err(&format!(
+        "{cli} wallet send --wallet w1 --recipient {node_address}:1000:{policy_asset}"
+    ));
+    assert!(err.contains("An identical payment was built"), "{err}");
+
+    // Unless forced
+    sh(&format!("{send} --force"));
+
+    // Other payments are not affected
+    sh(&format!(
+        "{cli} wallet send --wallet w1 --recipient {node_address}:1001:"
+    ));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_utxo_lock() {
+    let (t, _tmp, cli, params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let r = sh(&format!("{cli} wallet utxos --wallet w1"));
+    let utxos = r.get("utxos").unwrap().as_array().unwrap();
+    assert_eq!(utxos.len(), 1);
+    assert!(!utxos[0].get("locked").unwrap().as_bool().unwrap());
+    let txid = get_str(&utxos[0], "txid").to_string();
+    let vout = utxos[0].get("vout").unwrap().as_u64().unwrap();
+
+    sh(&format!(
+        "{cli} wallet lock-utxo -w w1 --txid {txid} --vout {vout}"
+    ));
+    let r = sh(&format!("{cli} wallet utxos --wallet w1"));
+    let utxos = r.get("utxos").unwrap().as_array().unwrap();
+    assert!(utxos[0].get("locked").unwrap().as_bool().unwrap());
+
+    // The only UTXO is locked, the balance is unchanged but it can't be spent
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    assert_eq!(get_balance(&cli, "w1", policy_asset), 1_000_000);
+    let r = sh(&format!("{cli} wallet balance --wallet w1"));
+    let confirmed = r.get("confirmed").unwrap().get(policy_asset).unwrap();
+    assert_eq!(confirmed.as_u64().unwrap(), 1_000_000);
+    let incoming = r
+        .get("unconfirmed_incoming")
+        .unwrap()
+        .get(policy_asset)
+        .unwrap();
+    assert_eq!(incoming.as_u64().unwrap(), 0);
+
+    let r = sh(&format!("{cli} wallet addresses --wallet w1 --limit 1"));
+    let addresses = r.get("addresses").unwrap().as_array().unwrap();
+    assert_eq!(addresses.len(), 1);
+    assert!(addresses[0].get("used").unwrap().as_bool().unwrap());
+    let balance = addresses[0]
+        .get("balance")
+        .unwrap()
+        .get(policy_asset)
+        .unwrap();
+    assert_eq!(balance.as_u64().unwrap(), 1_000_000);
+    let r = sh(&format!(
+        "{cli} wallet address --wallet w1 --index 0 --skip-used"
+    ));
+    assert_eq!(r.get("index").unwrap().as_u64().unwrap(), 1);
+    let node_address = server.node_getnewaddress();
+    let recipient = format!("--recipient {node_address}:1000:{policy_asset}");
+    let err = sh_err(&format!("{cli} wallet send --wallet w1 {recipient}"));
+    assert!(err.contains("Insufficient funds"));
+
+    let err = sh_err(&format!(
+        "{cli} wallet lock-utxo -w w1 --txid {txid} --vout 99"
+    ));
+    assert!(err.contains("is not a wallet UTXO"));
+
+    // Stop and restart to check persistence
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+
+    let t = {
+        let cli = cli.clone();
+        let params = params.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli} server start {params}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    let r = sh(&format!("{cli} wallet utxos --wallet w1"));
+    let utxos = r.get("utxos").unwrap().as_array().unwrap();
+    assert!(utxos[0].get("locked").unwrap().as_bool().unwrap());
+
+    sh(&format!(
+        "{cli} wallet unlock-utxo -w w1 --txid {txid} --vout {vout}"
+    ));
+    let r = sh(&format!("{cli} wallet utxos --wallet w1"));
+    let utxos = r.get("utxos").unwrap().as_array().unwrap();
+    assert!(!utxos[0].get("locked").unwrap().as_bool().unwrap());
+    send(
+        &cli,
+        "w1",
+        &node_address.to_string(),
+        policy_asset,
+        1_000,
+        &["s1"],
+    );
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_wallet_summary() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    let r = sh(&format!("{cli} wallet summary --wallet w1"));
+    assert_eq!(r.get("txs").unwrap().as_u64().unwrap(), 0);
+    assert!(r.get("last_activity").unwrap().is_null());
+
+    fund(&server, &cli, "w1", 1_000_000);
+    let r = sh(&format!("{cli} wallet summary --wallet w1"));
+    assert_eq!(r.get("txs").unwrap().as_u64().unwrap(), 1);
+    assert_eq!(r.get("utxos").unwrap().as_u64().unwrap(), 1);
+    assert_eq!(r.get("used_addresses").unwrap().as_u64().unwrap(), 1);
+    assert!(r.get("last_activity").unwrap().as_u64().is_some());
+    assert_eq!(r.get("tip_lag").unwrap().as_u64().unwrap(), 0);
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let received = r.get("received").unwrap().get(policy_asset).unwrap();
+    assert_eq!(received.as_u64().unwrap(), 1_000_000);
+    assert!(r.get("sent").unwrap().as_object().unwrap().is_empty());
+
+    let err = sh_err(&format!("{cli} wallet summary --wallet notexist"));
+    assert!(err.contains("Wallet 'notexist' does not exist"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_self_transfer() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    singlesig_wallet(&cli, "w2", "s1", "slip77", "shwpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let w2_address = address(&cli, "w2");
+    let txid = send(&cli, "w1", &w2_address, policy_asset, 10_000, &["s1"]);
+    wait_tx(&cli, "w2", &txid);
+    let tx1 = tx(&cli, "w1", &txid).unwrap();
+    assert_eq!(get_str(&tx1, "type"), "self-transfer");
+    let tx2 = tx(&cli, "w2", &txid).unwrap();
+    assert_eq!(get_str(&tx2, "type"), "self-transfer");
+
+    // Once the other wallet is unloaded the transaction is a regular payment
+    sh(&format!("{cli} wallet unload --wallet w2"));
+    let tx1 = tx(&cli, "w1", &txid).unwrap();
+    assert_eq!(get_str(&tx1, "type"), "outgoing");
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_labels() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    singlesig_wallet(&cli, "w2", "s1", "slip77", "shwpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+    fund(&server, &cli, "w2", 1_000_000);
+
+    let r = sh(&format!("{cli} wallet utxos --wallet w1"));
+    let utxos = r.get("utxos").unwrap().as_array().unwrap();
+    let txid = get_str(&utxos[0], "txid").to_string();
+    let vout = utxos[0].get("vout").unwrap().as_u64().unwrap();
+    let addr = address(&cli, "w1");
+
+    sh(&format!(
+        "{cli} wallet set-label -w w1 --type tx --ref {txid} --label funding"
+    ));
+    sh(&format!(
+        "{cli} wallet set-label -w w1 --type output --ref {txid}:{vout} --label coin"
+    ));
+    sh(&format!(
+        "{cli} wallet set-label -w w1 --type addr --ref {addr} --label receive"
+    ));
+    let r = sh(&format!(
+        "{cli} wallet get-label -w w1 --type tx --ref {txid}"
+    ));
+    assert_eq!(get_str(&r, "label"), "funding");
+
+    let err = sh_err(&format!(
+        "{cli} wallet set-label -w w1 --type xpub --ref {txid} --label x"
+    ));
+    assert!(err.contains("Unsupported label type"));
+
+    sh(&format!(
+        "{cli} wallet set-label -w w1 --type tx --ref {txid}"
+    ));
+    let r = sh(&format!(
+        "{cli} wallet get-label -w w1 --type tx --ref {txid}"
+    ));
+    assert!(r.get("label").unwrap().is_null());
+
+    let r = sh(&format!("{cli} wallet export-labels -w w1"));
+    let labels = get_str(&r, "labels").to_string();
+    assert_eq!(labels.lines().count(), 2);
+
+    let r = sh(&format!(
+        "{cli} wallet import-labels -w w2 --labels '{labels}'"
+    ));
+    assert_eq!(r.get("imported").unwrap().as_u64().unwrap(), 2);
+    let r = sh(&format!(
+        "{cli} wallet get-label -w w2 --type addr --ref {addr}"
+    ));
+    assert_eq!(get_str(&r, "label"), "receive");
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_pegin() {
+    use elements::bitcoin::{self, absolute, block, hashes::Hash, transaction};
+    let (t, _tmp, cli, _params, _server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+
+    // A 1-of-1 multisig federation
+    let fedpegscript = "51210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f8179851ae";
+    let r = sh(&format!(
+        "{cli} wallet pegin-address -w w1 --fedpegscript {fedpegscript} --index 0"
+    ));
+    let mainchain_address = get_str(&r, "mainchain_address");
+    assert_eq!(r.get("index").unwrap().as_u64().unwrap(), 0);
+    let mainchain_address = bitcoin::Address::from_str(mainchain_address)
+        .unwrap()
+        .require_network(bitcoin::Network::Regtest)
+        .unwrap();
+
+    // A mainchain block with the deposit, the claim is not broadcast since the node does not use
+    // this federation
+    let mainchain_tx = bitcoin::Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: vec![bitcoin::TxIn::default()],
+        output: vec![bitcoin::TxOut {
+            value: bitcoin::Amount::from_sat(100_000),
+            script_pubkey: mainchain_address.script_pubkey(),
+        }],
+    };
+    let header = block::Header {
+        version: block::Version::TWO,
+        prev_blockhash: bitcoin::BlockHash::all_zeros(),
+        merkle_root: bitcoin::TxMerkleNode::from_byte_array(mainchain_tx.txid().to_byte_array()),
+        time: 0,
+        bits: bitcoin::CompactTarget::from_consensus(0x207fffff),
+        nonce: 0,
+    };
+    let proof = bitcoin::MerkleBlock::from_header_txids_with_predicate(
+        &header,
+        &[mainchain_tx.txid()],
+        |_| true,
+    );
+    let mainchain_tx = bitcoin::consensus::serialize(&mainchain_tx).to_hex();
+    let proof = bitcoin::consensus::serialize(&proof).to_hex();
+
+    let r = sh(&format!(
+        "{cli} wallet claim-pegin -w w1 --fedpegscript {fedpegscript} --mainchain-tx {mainchain_tx} --txout-proof {proof}"
+    ));
+    let pset = get_str(&r, "pset");
+    let r = sh(&format!("{cli} wallet pset-details -w w1 -p {pset}"));
+    let fee = r.get("fee").unwrap().as_u64().unwrap();
+    let balance = r.get("balance").unwrap().as_object().unwrap();
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let received = balance.get(policy_asset).unwrap().as_i64().unwrap();
+    assert_eq!(received, 100_000 - fee as i64);
+
+    let r = sh(&format!("{cli} signer sign -s s1 --pset {pset}"));
+    let signed = get_str(&r, "pset");
+    assert_ne!(signed, pset);
+
+    let err = sh_err(&format!(
+        "{cli} wallet claim-pegin -w w1 --fedpegscript 51 --mainchain-tx {mainchain_tx} --txout-proof {proof}"
+    ));
+    assert!(err.contains("peg-in address"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_pegout() {
+    use elements::bitcoin;
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let script = bitcoin::ScriptBuf::from_bytes(vec![0x51]);
+    let mainchain_address = bitcoin::Address::p2wsh(&script, bitcoin::Network::Regtest);
+    let r = sh(&format!(
+        "{cli} wallet pegout -w w1 --mainchain-address {mainchain_address} --satoshi 10000"
+    ));
+    let pset = get_str(&r, "pset");
+    let r = sh(&format!("{cli} wallet pset-details -w w1 -p {pset}"));
+    let fee = r.get("fee").unwrap().as_u64().unwrap();
+    let balance = r.get("balance").unwrap().as_object().unwrap();
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let sent = balance.get(policy_asset).unwrap().as_i64().unwrap();
+    assert_eq!(sent, -10_000 - fee as i64);
+    let change_outputs = r.get("change_outputs").unwrap().as_array().unwrap();
+    assert_eq!(change_outputs.len(), 1);
+
+    let mainnet_address = bitcoin::Address::p2wsh(&script, bitcoin::Network::Bitcoin);
+    let err = sh_err(&format!(
+        "{cli} wallet pegout -w w1 --mainchain-address {mainnet_address} --satoshi 10000"
+    ));
+    assert!(err.contains("Invalid peg-out"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_signed_responses() {
+    let (t, tmp, cli, params, _server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+
+    // Without --sign-responses the server has no identity
+    let r = sh(&format!("{cli} wallet address --wallet w1"));
+    assert!(r.get("signature").is_none());
+    let other = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+    let err = sh_err(&format!(
+        "{cli} --server-identity {other} wallet address --wallet w1"
+    ));
+    assert!(err.contains("The response is not signed by the server"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+
+    let t = {
+        let cli = cli.clone();
+        let params = params.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli} server start --sign-responses {params}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    // The identity key has been created in the datadir
+    let path = tmp.path().join("liquid-regtest").join("identity.key");
+    let key = fs::read_to_string(path).unwrap();
+    let key = elements::secp256k1_zkp::SecretKey::from_str(&key).unwrap();
+    let identity = key.public_key(&elements::secp256k1_zkp::Secp256k1::new());
+
+    let r = sh(&format!(
+        "{cli} --server-identity {identity} wallet address --wallet w1"
+    ));
+    assert!(r.get("signature").is_some());
+    let r = sh(&format!(
+        "{cli} --server-identity {identity} wallet balance --wallet w1"
+    ));
+    assert!(r.get("signature").is_some());
+
+    // Responses of a different server are rejected
+    let err = sh_err(&format!(
+        "{cli} --server-identity {other} wallet address --wallet w1"
+    ));
+    assert!(err.contains("The response signature does not match the server identity"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_tls_auth_token() {
+    let (t, _tmp, cli, params, _server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+
+    let data = format!("{}/../lwk_tiny_jrpc/test/data", env!("CARGO_MANIFEST_DIR"));
+    let tls = format!("--tls-cert {data}/cert.pem");
+    let t = {
+        let cli = cli.clone();
+        let params = params.clone();
+        let tls = tls.clone();
+        std::thread::spawn(move || {
+            sh(&format!(
+                "{cli} --auth-token secret {tls} server start --tls-key {data}/key.pem {params}"
+            ));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    // The state is restored and the requests are served over https with the token
+    let r = sh(&format!(
+        "{cli} --auth-token secret {tls} wallet details -w w1"
+    ));
+    assert!(r.get("type").is_some());
+
+    let err = sh_err(&format!("{cli} {tls} wallet details -w w1"));
+    assert!(err.contains("missing or has wrong authentication token"));
+    let err = sh_err(&format!(
+        "{cli} --auth-token wrong {tls} wallet details -w w1"
+    ));
+    assert!(err.contains("missing or has wrong authentication token"));
+    let err = sh_err(&format!("{cli} --auth-token secret wallet details -w w1"));
+    assert!(err.contains("Is the server"));
+
+    sh(&format!("{cli} --auth-token secret {tls} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_wallet_recovery_scan() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    let r = sh(&format!("{cli} signer generate"));
+    let mnemonic = get_str(&r, "mnemonic").to_string();
+    sh(&format!(
+        "{cli} signer load-software --persist false --mnemonic \"{mnemonic}\" --signer s1"
+    ));
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "shwpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let r = sh(&format!(
+        "{cli} wallet recovery-scan --mnemonic \"{mnemonic}\" --max-account 1"
+    ));
+    let schemes = r.get("schemes").unwrap().as_array().unwrap();
+    assert_eq!(schemes.len(), 6);
+    let found: Vec<_> = schemes
+        .iter()
+        .filter(|s| s.get("has_history").unwrap().as_bool().unwrap())
+        .collect();
+    assert_eq!(found.len(), 1);
+    assert_eq!(get_str(found[0], "path"), "m/49h/1h/0h");
+    assert_eq!(get_str(found[0], "kind"), "shwpkh");
+    let r = sh(&format!("{cli} wallet details --wallet w1"));
+    assert_eq!(get_desc(found[0]), get_desc(&r));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_wallet_details() {
+    let (t, _tmp, cli, _params, _server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    sw_signer(&cli, "s2");
+
+    // Single sig wallet
+    let r = sh(&format!(
+        "{cli} signer singlesig-desc --signer s1 --descriptor-blinding-key slip77 --kind wpkh"
+    ));
+    let desc_ss = get_str(&r, "descriptor");
+    sh(&format!("{cli} wallet load --wallet ss -d {desc_ss}"));
+    assert!(desc_ss.contains(&keyorigin(&cli, "s1", "bip84")));
+
+    let r = sh(&format!(
+        "{cli} signer singlesig-desc --signer s1 --descriptor-blinding-key slip77 --kind shwpkh"
+    ));
+    let desc_sssh = get_str(&r, "descriptor");
+    sh(&format!("{cli} wallet load --wallet sssh -d {desc_sssh}"));
+    assert!(desc_sssh.contains(&keyorigin(&cli, "s1", "bip49")));
+
+    let err = sh_err(&format!(
+        "{cli} signer singlesig-desc -s s1 --descriptor-blinding-key slip77-rand --kind wpkh"
+    ));
+    let exp_err = "Random slip77 key not supported in singlesig descriptor generation";
+    assert!(err.contains(exp_err));
+
+    // Multi sig wallet
+    let r = sh(&format!("{cli} signer xpub --signer s1 --kind bip87"));
+    let xpub1 = get_str(&r, "keyorigin_xpub");
+    let r = sh(&format!("{cli} signer xpub --signer s2 --kind bip87"));
+    let xpub2 = get_str(&r, "keyorigin_xpub");
+    let r = sh(&format!("{cli} wallet multisig-desc --descriptor-blinding-key slip77-rand --kind wsh --threshold 2 --keyorigin-xpub {xpub1} --keyorigin-xpub {xpub2}"));
+    let desc_ms = get_str(&r, "descriptor");
+    sh(&format!("{cli} wallet load --wallet ms -d {desc_ms}"));
+
+    let err = sh_err(&format!("{cli} wallet multisig-desc --descriptor-blinding-key slip77 --kind wsh --threshold 2 --keyorigin-xpub {xpub1} --keyorigin-xpub {xpub2}"));
+    let exp_err = "Deterministic slip77 key not supported in multisig descriptor generation";
+    assert!(err.contains(exp_err));
+
+    // Multi sig wallet, same signers
+    let r = sh(&format!("{cli} wallet multisig-desc --descriptor-blinding-key slip77-rand --kind wsh --threshold 2 --keyorigin-xpub {xpub1} --keyorigin-xpub {xpub1}"));
+    let desc_ms_same_signers = get_str(&r, "descriptor");
+    sh(&format!(
+        "{cli} wallet load --wallet ms_same_signers -d {desc_ms_same_signers}"
+    ));
+
+    // Details
+    let r = sh(&format!("{cli} wallet details --wallet ss"));
+    assert_eq!(get_desc(&r), remove_checksum(desc_ss));
+    assert!(get_str(&r, "warnings").is_empty());
+    assert_eq!(get_str(&r, "type"), "wpkh");
+    let signers = r.get("signers").unwrap().as_array().unwrap();
+    assert_eq!(signers.len(), 1);
+    assert_eq!(get_str(&signers[0], "name"), "s1");
+
+    let r = sh(&format!("{cli} wallet details --wallet sssh"));
+    assert_eq!(get_desc(&r), remove_checksum(desc_sssh));
+    assert!(get_str(&r, "warnings").is_empty());
+    assert_eq!(get_str(&r, "type"), "sh_wpkh");
+    let signers = r.get("signers").unwrap().as_array().unwrap();
+    assert_eq!(signers.len(), 1);
+    assert_eq!(get_str(&signers[0], "name"), "s1");
+
+    let r = sh(&format!("{cli} wallet details --wallet ms"));
+    assert_eq!(get_desc(&r), remove_checksum(desc_ms));
+    assert!(get_str(&r, "warnings").is_empty());
+    assert_eq!(get_str(&r, "type"), "wsh_multi_2of2");
+    let signers = r.get("signers").unwrap().as_array().unwrap();
+    assert_eq!(signers.len(), 2);
+    assert_eq!(get_str(&signers[0], "name"), "s1");
+    assert_eq!(get_str(&signers[1], "name"), "s2");
+
+    sh(&format!("{cli} signer unload --signer s2"));
+    let r = sh(&format!("{cli} wallet details --wallet ms"));
+    let signers = r.get("signers").unwrap().as_array().unwrap();
+    assert_eq!(signers.len(), 2);
+    assert_eq!(get_str(&signers[0], "name"), "s1");
+    assert!(signers[1].get("name").is_none());
+
+    let r = sh(&format!("{cli} wallet details --wallet ms_same_signers"));
+    assert_eq!(
+        get_str(&r, "warnings"),
+        "wallet has multiple signers with the same fingerprint"
+    );
+    assert_eq!(r.get("type").unwrap().as_str().unwrap(), "wsh_multi_2of2");
+    let signers = r.get("signers").unwrap().as_array().unwrap();
+    assert_eq!(signers.len(), 2);
+    assert_eq!(get_str(&signers[0], "name"), "s1");
+    assert_eq!(get_str(&signers[1], "name"), "s1");
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_broadcast() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    assert_eq!(1_000_000, get_balance(&cli, "w1", policy_asset));
+    let addr = server.node_getnewaddress().to_string();
+    send(&cli, "w1", &addr, policy_asset, 1000, &["s1"]);
+    assert!(1_000_000 > get_balance(&cli, "w1", policy_asset));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_issue() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let r = sh(&format!("{cli} asset contract --domain example.com --issuer-pubkey 035d0f7b0207d9cc68870abfef621692bce082084ed3ca0c1ae432dd12d889be01 --name example --ticker EXMP"));
+    let contract = serde_json::to_string(&r).unwrap();
+    let r = sh(&format!(
+        "{cli} wallet issue --wallet w1 --satoshi-asset 1000 --satoshi-token 1 --contract '{contract}'"
     ));
     let pset = get_str(&r, "pset");
     let pset_unsigned: PartiallySignedTransaction = pset.parse().unwrap();
@@ -904,56 +2190,247 @@ fn test_issue() {
             assert!(tx.get("timestamp").is_some());
         }
 
-        assert!(tx.get("fee").unwrap().as_u64().unwrap() > 0);
-        let types = ["issuance", "reissuance", "burn", "incoming", "outgoing"];
-        assert!(types.contains(&tx.get("type").unwrap().as_str().unwrap()));
-        // Always received or spent L-BTC
-        let url = tx.get("unblinded_url").unwrap().as_str().unwrap();
-        assert!(url.contains(policy_asset));
-    }
+        assert!(tx.get("fee").unwrap().as_u64().unwrap() > 0);
+        let types = ["issuance", "reissuance", "burn", "incoming", "outgoing"];
+        assert!(types.contains(&tx.get("type").unwrap().as_str().unwrap()));
+        // Always received or spent L-BTC
+        let url = tx.get("unblinded_url").unwrap().as_str().unwrap();
+        assert!(url.contains(policy_asset));
+    }
+
+    server.generate(1);
+    sh(&format!("{cli} server scan"));
+
+    let r = sh(&format!("{cli} wallet txs --wallet w1 --with-tickers"));
+    let txs = r.get("txs").unwrap().as_array().unwrap();
+    assert!(!txs.is_empty());
+
+    for tx in txs {
+        assert!(tx.get("height").is_some());
+        assert!(tx.get("timestamp").is_some());
+    }
+
+    let balance = txs[0].get("balance").unwrap().as_object().unwrap();
+    assert!(balance.contains_key("L-BTC"));
+
+    // Move the reissuance token to another wallet and perform an "external" reissuance
+    sw_signer(&cli, "s2");
+    singlesig_wallet(&cli, "w2", "s2", "slip77", "wpkh");
+    fund(&server, &cli, "w2", 1_000_000);
+    let w2_addr = address(&cli, "w2");
+    let txid = send(&cli, "w1", &w2_addr, token, 1, &["s1"]);
+    wait_tx(&cli, "w2", &txid);
+    let r = sh(&format!(
+        "{cli} wallet reissue --wallet w2 --asset {asset} --satoshi-asset 1"
+    ));
+    complete(&cli, "w2", get_str(&r, "pset"), &["s2"]);
+    assert_eq!(1, get_balance(&cli, "w2", asset));
+
+    // Removing the asset will cause the "external" reissuance to fail
+    sh(&format!("{cli} asset remove --asset {asset}"));
+    let err = sh_err(&format!(
+        "{cli} wallet reissue --wallet w2 --asset {asset} --satoshi-asset 1"
+    ));
+    assert!(err.contains("Missing issuance"));
+
+    let err = sh_err(&format!("{cli} wallet tx -w w2 -t {issuance_txid}"));
+    assert!(err.contains("was not found in wallet 'w2'"));
+
+    // w2 can get the tx from the explorer
+    sh(&format!(
+        "{cli} wallet tx -w w2 -t {issuance_txid} --from-explorer"
+    ));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_asset_details() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let r = sh(&format!("{cli} asset details --asset {policy_asset}"));
+    assert_eq!(r.get("precision").unwrap().as_u64().unwrap(), 8);
+    assert!(r.get("domain").is_none());
+
+    let r = sh(&format!("{cli} asset contract --domain example.com --issuer-pubkey 035d0f7b0207d9cc68870abfef621692bce082084ed3ca0c1ae432dd12d889be01 --name example --ticker EXMP --precision 2"));
+    let contract = serde_json::to_string(&r).unwrap();
+    let r = sh(&format!(
+        "{cli} wallet issue --wallet w1 --satoshi-asset 1000 --satoshi-token 1 --contract '{contract}'"
+    ));
+    let asset = get_str(&r, "asset_id").to_string();
+    let txid = complete(&cli, "w1", get_str(&r, "pset"), &["s1"]);
+    let r = sh(&format!("{cli} wallet tx -w w1 -t {txid}"));
+    let tx = get_str(&r, "tx");
+    sh(&format!(
+        "{cli} asset insert --asset {asset} --contract '{contract}' --issuance-tx {tx}"
+    ));
+
+    let r = sh(&format!("{cli} asset details --asset {asset}"));
+    assert_eq!(get_str(&r, "ticker"), "EXMP");
+    assert_eq!(r.get("precision").unwrap().as_u64().unwrap(), 2);
+    assert_eq!(get_str(&r, "domain"), "example.com");
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_issue_registry_data() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let r = sh(&format!("{cli} asset contract --domain example.com --issuer-pubkey 035d0f7b0207d9cc68870abfef621692bce082084ed3ca0c1ae432dd12d889be01 --name example --ticker EXMP"));
+    let contract = serde_json::to_string(&r).unwrap();
+    let r = sh(&format!(
+        "{cli} wallet issue --wallet w1 --satoshi-asset 1000 --satoshi-token 1 --contract '{contract}'"
+    ));
+    let pset = get_str(&r, "pset");
+    let pset_unsigned: PartiallySignedTransaction = pset.parse().unwrap();
+
+    // The data to publish the asset in the registry
+    let (asset, token) = asset_ids_from_issuance_pset(&cli, "w1", pset);
+    assert_eq!(get_str(&r, "asset_id"), asset);
+    assert_eq!(get_str(&r, "token_id"), token);
+    assert_eq!(get_str(&r, "contract_hash").len(), 64);
+    assert_eq!(
+        r.get("contract").unwrap(),
+        &serde_json::from_str::<Value>(&contract).unwrap()
+    );
+    let input = &pset_unsigned.inputs()[0];
+    let prevout = format!("{}:{}", input.previous_txid, input.previous_output_index);
+    assert_eq!(get_str(&r, "issuance_prevout"), prevout);
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_reissuance_tokens() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    fund(&server, &cli, "w1", 1_000_000);
+
+    let r = sh(&format!("{cli} asset contract --domain example.com --issuer-pubkey 035d0f7b0207d9cc68870abfef621692bce082084ed3ca0c1ae432dd12d889be01 --name example --ticker EXMP"));
+    let contract = serde_json::to_string(&r).unwrap();
+    let r = sh(&format!(
+        "{cli} wallet issue --wallet w1 --satoshi-asset 1000 --satoshi-token 1 --contract '{contract}'"
+    ));
+    let asset = get_str(&r, "asset_id").to_string();
+    let token = get_str(&r, "token_id").to_string();
+    let txid = complete(&cli, "w1", get_str(&r, "pset"), &["s1"]);
+
+    let r = sh(&format!("{cli} wallet balance --wallet w1"));
+    let tokens = r.get("reissuance_tokens").unwrap().as_object().unwrap();
+    assert_eq!(tokens.len(), 1);
+    assert_eq!(get_str(&r["reissuance_tokens"], &token), asset);
+
+    // Unknown assets keep their ids
+    let r = sh(&format!("{cli} wallet balance --wallet w1 --with-tickers"));
+    assert_eq!(get_str(&r["reissuance_tokens"], &token), asset);
+
+    let r = sh(&format!("{cli} wallet tx -w w1 -t {txid}"));
+    let tx = get_str(&r, "tx");
+    sh(&format!(
+        "{cli} asset insert --asset {asset} --contract '{contract}' --issuance-tx {tx}"
+    ));
+    let r = sh(&format!("{cli} wallet balance --wallet w1 --with-tickers"));
+    let token_ticker = "reissuance token for EXMP";
+    assert_eq!(get_str(&r["reissuance_tokens"], token_ticker), "EXMP");
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_burn() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    let signers = &["s1"];
+    fund(&server, &cli, "w1", 1_000_000);
 
-    server.generate(1);
-    sh(&format!("{cli} server scan"));
+    let r = sh(&format!(
+        "{cli} wallet issue --wallet w1 --satoshi-asset 1000 --satoshi-token 0"
+    ));
+    let asset = get_str(&r, "asset_id").to_string();
+    complete(&cli, "w1", get_str(&r, "pset"), signers);
 
-    let r = sh(&format!("{cli} wallet txs --wallet w1 --with-tickers"));
-    let txs = r.get("txs").unwrap().as_array().unwrap();
-    assert!(!txs.is_empty());
+    // The burn is an explicit provably unspendable output
+    let r = sh(&format!(
+        "{cli} wallet burn -w w1 --asset {asset} --satoshi-asset 1"
+    ));
+    assert_eq!(get_str(&r, "warnings"), "");
+    let pset: PartiallySignedTransaction = get_str(&r, "pset").parse().unwrap();
+    let burn = pset
+        .outputs()
+        .iter()
+        .find(|o| o.script_pubkey.is_provably_unspendable() && o.amount == Some(1))
+        .unwrap();
+    assert_eq!(burn.asset.unwrap().to_string(), asset);
+    assert!(burn.blinding_key.is_none());
+    complete(&cli, "w1", get_str(&r, "pset"), signers);
+    assert_eq!(get_balance(&cli, "w1", &asset), 999);
 
-    for tx in txs {
-        assert!(tx.get("height").is_some());
-        assert!(tx.get("timestamp").is_some());
-    }
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
 
-    let balance = txs[0].get("balance").unwrap().as_object().unwrap();
-    assert!(balance.contains_key("L-BTC"));
+#[test]
+fn test_txs_pagination() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    singlesig_wallet(&cli, "w1", "s1", "slip77", "wpkh");
+    let signers = &["s1"];
+    fund(&server, &cli, "w1", 1_000_000);
 
-    // Move the reissuance token to another wallet and perform an "external" reissuance
-    sw_signer(&cli, "s2");
-    singlesig_wallet(&cli, "w2", "s2", "slip77", "wpkh");
-    fund(&server, &cli, "w2", 1_000_000);
-    let w2_addr = address(&cli, "w2");
-    let txid = send(&cli, "w1", &w2_addr, token, 1, &["s1"]);
-    wait_tx(&cli, "w2", &txid);
     let r = sh(&format!(
-        "{cli} wallet reissue --wallet w2 --asset {asset} --satoshi-asset 1"
+        "{cli} wallet issue --wallet w1 --satoshi-asset 1000 --satoshi-token 0"
     ));
-    complete(&cli, "w2", get_str(&r, "pset"), &["s2"]);
-    assert_eq!(1, get_balance(&cli, "w2", asset));
+    let asset = get_str(&r, "asset_id").to_string();
+    complete(&cli, "w1", get_str(&r, "pset"), signers);
+    let node_address = server.node_getnewaddress().to_string();
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    send(&cli, "w1", &node_address, policy_asset, 1_000, signers);
+    server.generate(1);
+    sh(&format!("{cli} server scan"));
 
-    // Removing the asset will cause the "external" reissuance to fail
-    sh(&format!("{cli} asset remove --asset {asset}"));
-    let err = sh_err(&format!(
-        "{cli} wallet reissue --wallet w2 --asset {asset} --satoshi-asset 1"
+    let txs = txs(&cli, "w1");
+    assert_eq!(txs.len(), 3);
+
+    let r = sh(&format!(
+        "{cli} wallet txs --wallet w1 --offset 1 --limit 2"
     ));
-    assert!(err.contains("Missing issuance"));
+    let page = r.get("txs").unwrap().as_array().unwrap();
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0].get("txid"), txs[1].get("txid"));
+    assert_eq!(page[1].get("txid"), txs[2].get("txid"));
 
-    let err = sh_err(&format!("{cli} wallet tx -w w2 -t {issuance_txid}"));
-    assert!(err.contains("was not found in wallet 'w2'"));
+    // Only the issuance moves the asset
+    let r = sh(&format!("{cli} wallet txs --wallet w1 --asset {asset}"));
+    assert_eq!(get_len(&r, "txs"), 1);
 
-    // w2 can get the tx from the explorer
-    sh(&format!(
-        "{cli} wallet tx -w w2 -t {issuance_txid} --from-explorer"
+    // The issuance and the send pay a fee
+    let r = sh(&format!(
+        "{cli} wallet txs --wallet w1 --direction incoming"
+    ));
+    assert_eq!(get_len(&r, "txs"), 1);
+    let r = sh(&format!(
+        "{cli} wallet txs --wallet w1 --direction outgoing"
     ));
+    assert_eq!(get_len(&r, "txs"), 2);
 
     sh(&format!("{cli} server stop"));
     t.join().unwrap();
@@ -961,7 +2438,7 @@ fn test_issue() {
 
 #[test]
 fn test_jade_emulator() {
-    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+    let (t, _tmp, cli, params, server, _) = setup_cli(false);
 
     let docker = clients::Cli::default();
     let container = docker.run(JadeEmulator);
@@ -997,12 +2474,79 @@ fn test_jade_emulator() {
     sh(&format!("{cli} wallet address -w ss-shwpkh -s emul"));
     sh(&format!("{cli} wallet address -w multi -s emul"));
 
+    // Verified addresses are recorded
+    let r = sh(&format!(
+        "{cli} wallet address -w ss-wpkh -s emul --index 7"
+    ));
+    assert!(r.get("blinding_pubkey").unwrap().is_string());
+    assert!(r.get("verified").unwrap().as_bool().unwrap());
+    assert_eq!(get_str(&r, "verified_by"), "emul");
+    let r = sh(&format!("{cli} wallet address -w ss-wpkh --index 7"));
+    assert!(r.get("verified").unwrap().as_bool().unwrap());
+    let r = sh(&format!("{cli} wallet address -w ss-wpkh --index 8"));
+    assert!(!r.get("verified").unwrap().as_bool().unwrap());
+    assert!(r.get("verified_by").is_none());
+
     singlesig_wallet(&cli, "ss-sw", "sw", "slip77", "wpkh");
     let err = sh_err(&format!("{cli} wallet address -w ss-sw -s emul"));
-    assert!(err.contains("Signer is not in wallet"));
+    assert!(err.contains("Jade is not a signer of the descriptor"));
 
     let err = sh_err(&format!("{cli} wallet address -w ss-sw -s sw"));
     assert!(err.contains("Cannot display address with software signer"));
+    let r = sh(&format!("{cli} wallet address -w ss-sw"));
+    assert!(!r.get("verified").unwrap().as_bool().unwrap());
+
+    // Stop and restart to check the verified addresses are persisted
+    sh(&format!("{cli} server stop"));
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    t.join().unwrap();
+
+    let t = {
+        let cli = cli.clone();
+        std::thread::spawn(move || {
+            sh(&format!("{cli} server start {params}"));
+        })
+    };
+    std::thread::sleep(std::time::Duration::from_millis(1000));
+
+    let r = sh(&format!("{cli} wallet address -w ss-wpkh --index 7"));
+    assert_eq!(get_str(&r, "verified_by"), "emul");
+
+    sh(&format!("{cli} server stop"));
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_ledger_emulator() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    let docker = clients::Cli::default();
+    let container = docker.run(LedgerEmulator::new().unwrap());
+    let port = container.get_host_port_ipv4(LEDGER_EMULATOR_PORT);
+
+    sh(&format!(
+        "{cli} signer load-ledger --signer emul --emulator {port}"
+    ));
+    let r = sh(&format!("{cli} signer details -s emul"));
+    assert!(r.get("id").is_some());
+    assert!(r.get("mnemonic").is_none());
+    assert_eq!(get_str(&r, "type"), "ledger");
+
+    singlesig_wallet(&cli, "ss-wpkh", "emul", "slip77", "wpkh");
+    fund(&server, &cli, "ss-wpkh", 10_000);
+
+    // The signatures of the device are added to the PSET, which can be broadcast
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let node_address = server.node_getnewaddress().to_string();
+    send(
+        &cli,
+        "ss-wpkh",
+        &node_address,
+        policy_asset,
+        1_000,
+        &["emul"],
+    );
 
     sh(&format!("{cli} server stop"));
     std::thread::sleep(std::time::Duration::from_millis(100));
@@ -1215,6 +2759,168 @@ fn test_multisig() {
     t.join().unwrap();
 }
 
+#[test]
+fn test_multisig_signing_order() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    sw_signer(&cli, "s2");
+    multisig_wallet(&cli, "multi", 2, &["s1", "s2"], "slip77-rand");
+    fund(&server, &cli, "multi", 1_000_000);
+
+    let node_address = server.node_getnewaddress();
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let recipient = format!("{node_address}:1000:{policy_asset}");
+    let r = sh(&format!(
+        "{cli} wallet send --wallet multi --recipient {recipient}"
+    ));
+    let pset_u = get_str(&r, "pset");
+
+    // Signers of the same kind are ordered by name
+    let r = sh(&format!(
+        "{cli} wallet pset-details --wallet multi -p {pset_u}"
+    ));
+    let order = r.get("signing_order").unwrap().as_array().unwrap();
+    assert_eq!(order, &vec![Value::from("s1"), Value::from("s2")]);
+
+    // Signers which already signed are not listed
+    let r = sh(&format!("{cli} signer sign --signer s1 --pset {pset_u}"));
+    let pset_s1 = get_str(&r, "pset");
+
+    // The first signer caches the sighashes for the following ones
+    let pset: PartiallySignedTransaction = pset_s1.parse().unwrap();
+    assert!(lwk_common::pset_cached_sighash(&pset, 0).unwrap().is_some());
+    lwk_common::pset_check_cached_sighashes(&pset).unwrap();
+    let r = sh(&format!(
+        "{cli} wallet pset-details --wallet multi -p {pset_s1}"
+    ));
+    let order = r.get("signing_order").unwrap().as_array().unwrap();
+    assert_eq!(order, &vec![Value::from("s2")]);
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_multisig_desc_signers() {
+    let (t, _tmp, cli, _params, _server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    sw_signer(&cli, "s2");
+    let keyorigin_xpub1 = keyorigin(&cli, "s1", "bip87");
+    let keyorigin_xpub2 = keyorigin(&cli, "s2", "bip87");
+
+    // The same keys can be taken from the loaded signers
+    let r = sh(&format!("{cli} wallet multisig-desc --descriptor-blinding-key elip151 --kind wsh --threshold 2 --keyorigin-xpub {keyorigin_xpub1} --keyorigin-xpub {keyorigin_xpub2}"));
+    let desc_xpubs = get_str(&r, "descriptor").to_string();
+    let r = sh(&format!("{cli} wallet multisig-desc --descriptor-blinding-key elip151 --kind wsh --threshold 2 --signer s1 --signer s2"));
+    assert_eq!(get_str(&r, "descriptor"), desc_xpubs);
+    let r = sh(&format!("{cli} wallet multisig-desc --descriptor-blinding-key elip151 --kind wsh --threshold 2 --signer s1 --keyorigin-xpub {keyorigin_xpub2}"));
+    assert_eq!(get_str(&r, "descriptor"), desc_xpubs);
+    let err = sh_err(&format!("{cli} wallet multisig-desc --descriptor-blinding-key elip151 --kind wsh --threshold 2 --signer s3"));
+    assert!(err.contains("Signer 's3' does not exist"), "{err}");
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_multisig_pset_details() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    sw_signer(&cli, "s2");
+    multisig_wallet(&cli, "multi", 2, &["s1", "s2"], "slip77-rand");
+    fund(&server, &cli, "multi", 1_000_000);
+
+    let node_address = server.node_getnewaddress();
+    let satoshi = 1000;
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let recipient = format!("{node_address}:{satoshi}:{policy_asset}");
+    let r = sh(&format!(
+        "{cli} wallet send --wallet multi --recipient {recipient}"
+    ));
+    let pset_u = get_str(&r, "pset");
+    let r = sh(&format!("{cli} signer sign --signer s1 --pset {pset_u}"));
+    let pset_s1 = get_str(&r, "pset");
+
+    let r = sh(&format!(
+        "{cli} wallet pset-details --wallet multi -p {pset_s1}"
+    ));
+    // The change is not a recipient
+    let recipients = r.get("recipients").unwrap().as_array().unwrap();
+    assert_eq!(recipients.len(), 1);
+    assert_eq!(get_str(&recipients[0], "address"), node_address.to_string());
+    assert_eq!(get_str(&recipients[0], "asset"), policy_asset);
+    assert_eq!(
+        recipients[0].get("satoshi").unwrap().as_u64(),
+        Some(satoshi)
+    );
+    let inputs = r.get("inputs").unwrap().as_array().unwrap();
+    assert!(!inputs.is_empty());
+    for input in inputs {
+        let has_sigs = input.get("has_signatures_from").unwrap();
+        assert_eq!(has_sigs.as_array().unwrap().len(), 1);
+        assert_eq!(get_str(&has_sigs[0], "name"), "s1");
+        let missing_sigs = input.get("missing_signatures_from").unwrap();
+        assert_eq!(missing_sigs.as_array().unwrap().len(), 1);
+        assert_eq!(get_str(&missing_sigs[0], "name"), "s2");
+    }
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_multisig_combine() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "s1");
+    sw_signer(&cli, "s2");
+    multisig_wallet(&cli, "multi", 2, &["s1", "s2"], "slip77-rand");
+    fund(&server, &cli, "multi", 1_000_000);
+
+    let node_address = server.node_getnewaddress();
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    let recipient = format!("{node_address}:1000:{policy_asset}");
+    let r = sh(&format!(
+        "{cli} wallet send --wallet multi --recipient {recipient}"
+    ));
+    let pset_u = get_str(&r, "pset");
+    let r = sh(&format!("{cli} signer sign --signer s1 --pset {pset_u}"));
+    let pset_s1 = get_str(&r, "pset");
+    let r = sh(&format!("{cli} signer sign --signer s2 --pset {pset_u}"));
+    let pset_s2 = get_str(&r, "pset");
+
+    // A single signature is not enough
+    let r = sh(&format!(
+        "{cli} wallet combine --wallet multi -p {pset_u} -p {pset_s1}"
+    ));
+    assert!(r.get("tx").is_none());
+    let missing = r.get("missing_signatures").unwrap().as_array().unwrap();
+    assert!(!missing.is_empty());
+    for input in missing {
+        let missing_from = input.get("missing_signatures_from").unwrap();
+        assert_eq!(missing_from.as_array().unwrap().len(), 1);
+        assert_eq!(get_str(&missing_from[0], "name"), "s2");
+    }
+
+    // With both signatures the PSET is finalized
+    let r = sh(&format!(
+        "{cli} wallet combine --wallet multi -p {pset_s1} -p {pset_s2}"
+    ));
+    assert!(r.get("tx").unwrap().is_string());
+    assert_eq!(get_len(&r, "missing_signatures"), 0);
+    let pset_s = get_str(&r, "pset");
+    let r = sh(&format!(
+        "{cli} wallet broadcast --wallet multi --pset {pset_s}"
+    ));
+    wait_tx(&cli, "multi", get_str(&r, "txid"));
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
 #[test]
 fn test_inconsistent_network() {
     let (_t, _tmp, cli, _params, _server, _) = setup_cli(false);
@@ -1267,6 +2973,36 @@ fn test_schema() {
         assert!(result.get("$schema").is_some(), "failed for {}", cmd);
     }
 
+    for a in DraftSubCommandsEnum::value_variants() {
+        let a = a.to_possible_value();
+        let cmd = a.map(|e| e.get_name().to_string()).unwrap();
+        let result = sh(&format!("{cli} schema request draft {cmd}"));
+        assert!(result.get("$schema").is_some(), "failed for {}", cmd);
+
+        let result = sh(&format!("{cli} schema response draft {cmd}"));
+        assert!(result.get("$schema").is_some(), "failed for {}", cmd);
+    }
+
+    for a in ContactSubCommandsEnum::value_variants() {
+        let a = a.to_possible_value();
+        let cmd = a.map(|e| e.get_name().to_string()).unwrap();
+        let result = sh(&format!("{cli} schema request contact {cmd}"));
+        assert!(result.get("$schema").is_some(), "failed for {}", cmd);
+
+        let result = sh(&format!("{cli} schema response contact {cmd}"));
+        assert!(result.get("$schema").is_some(), "failed for {}", cmd);
+    }
+
+    for a in PsetSubCommandsEnum::value_variants() {
+        let a = a.to_possible_value();
+        let cmd = a.map(|e| e.get_name().to_string()).unwrap();
+        let result = sh(&format!("{cli} schema request pset {cmd}"));
+        assert!(result.get("$schema").is_some(), "failed for {}", cmd);
+
+        let result = sh(&format!("{cli} schema response pset {cmd}"));
+        assert!(result.get("$schema").is_some(), "failed for {}", cmd);
+    }
+
     sh(&format!("{cli} server stop"));
     t.join().unwrap();
 }
@@ -1454,10 +3190,73 @@ fn test_send_all() {
     let r = sh(&format!(
         "{cli} wallet drain -w w1 --address {node_address}"
     ));
+    assert_eq!(get_str(&r, "warnings"), "");
+    complete(&cli, "w1", get_str(&r, "pset"), signers);
+    let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
+    assert_eq!(get_balance(&cli, "w1", policy_asset), 0);
+
+    sh(&format!("{cli} server stop"));
+    t.join().unwrap();
+}
+
+#[test]
+fn test_drain_asset() {
+    let (t, _tmp, cli, _params, server, _) = setup_cli(false);
+
+    sw_signer(&cli, "sw");
+    singlesig_wallet(&cli, "w1", "sw", "slip77", "wpkh");
+    let signers = &["sw"];
+
+    fund(&server, &cli, "w1", 1_000_000);
+    let r = sh(&format!(
+        "{cli} wallet issue --wallet w1 --satoshi-asset 1000 --satoshi-token 0"
+    ));
+    let asset = get_str(&r, "asset_id").to_string();
+    complete(&cli, "w1", get_str(&r, "pset"), signers);
+    assert_eq!(get_balance(&cli, "w1", &asset), 1000);
+
+    let node_address = server.node_getnewaddress();
+    let r = sh(&format!(
+        "{cli} wallet drain -w w1 --address {node_address} --asset {asset}"
+    ));
+    assert_eq!(get_str(&r, "warnings"), "");
     complete(&cli, "w1", get_str(&r, "pset"), signers);
     let policy_asset = "5ac9f65c0efcc4775e0baec4ec03abdde22473cd3cf33c0419ca290e0751b225";
     assert_eq!(get_balance(&cli, "w1", policy_asset), 0);
+    let r = sh(&format!("{cli} wallet balance --wallet w1"));
+    assert!(r.get("balance").unwrap().get(&asset).is_none());
 
     sh(&format!("{cli} server stop"));
     t.join().unwrap();
 }
+
+#[test]
+fn test_output_modes() {
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_lwk_cli"))
+            .args(args)
+            .output()
+            .unwrap()
+    };
+
+    let pretty = run(&["generate-completion", "bash"]);
+    assert!(pretty.status.success());
+
+    let json = run(&["--json", "generate-completion", "bash"]);
+    assert!(json.status.success());
+    let stdout = String::from_utf8(json.stdout).unwrap();
+    assert_eq!(stdout.lines().count(), 1);
+    let value: Value = serde_json::from_str(&stdout).unwrap();
+    assert_eq!(
+        value,
+        serde_json::from_slice::<Value>(&pretty.stdout).unwrap()
+    );
+
+    // global flags are accepted after the sub command as well
+    let quiet = run(&["generate-completion", "bash", "--quiet"]);
+    assert!(quiet.status.success());
+    assert!(quiet.stdout.is_empty());
+
+    let both = run(&["--json", "--quiet", "generate-completion", "bash"]);
+    assert!(!both.status.success());
+}
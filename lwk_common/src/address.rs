@@ -0,0 +1,147 @@
+use std::fmt::Display;
+
+use elements::address::Payload;
+use elements::bitcoin::secp256k1::PublicKey;
+use elements::{Address, AddressParams};
+
+/// The type of script an address pays to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressScriptType {
+    /// Pay to public key hash
+    P2pkh,
+
+    /// Pay to script hash
+    ///
+    /// The inner script can't be derived from the address, singlesig wallets use it for
+    /// sh-wpkh.
+    P2sh,
+
+    /// Pay to witness public key hash (segwit v0)
+    P2wpkh,
+
+    /// Pay to witness script hash (segwit v0)
+    P2wsh,
+
+    /// Pay to taproot (segwit v1)
+    P2tr,
+
+    /// Any other witness program
+    Unknown,
+}
+
+impl Display for AddressScriptType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AddressScriptType::P2pkh => "p2pkh",
+            AddressScriptType::P2sh => "p2sh",
+            AddressScriptType::P2wpkh => "p2wpkh",
+            AddressScriptType::P2wsh => "p2wsh",
+            AddressScriptType::P2tr => "p2tr",
+            AddressScriptType::Unknown => "unknown",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// The network an address belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressNetwork {
+    Liquid,
+    LiquidTestnet,
+    ElementsRegtest,
+}
+
+impl Display for AddressNetwork {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            AddressNetwork::Liquid => "liquid",
+            AddressNetwork::LiquidTestnet => "liquid-testnet",
+            AddressNetwork::ElementsRegtest => "elements-regtest",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// Details of an address, useful to show it to the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressKind {
+    /// The type of the script the address pays to
+    pub script_type: AddressScriptType,
+
+    /// Whether the address contains a blinding public key
+    pub is_confidential: bool,
+
+    /// The network of the address, `None` if it uses custom address params
+    pub network: Option<AddressNetwork>,
+
+    /// The blinding public key, if the address is confidential
+    pub blinding_pubkey: Option<PublicKey>,
+}
+
+/// Inspect an address returning its [`AddressKind`]
+pub fn address_kind(address: &Address) -> AddressKind {
+    let script_type = match &address.payload {
+        Payload::PubkeyHash(_) => AddressScriptType::P2pkh,
+        Payload::ScriptHash(_) => AddressScriptType::P2sh,
+        Payload::WitnessProgram { version, program } => match (version.to_u8(), program.len()) {
+            (0, 20) => AddressScriptType::P2wpkh,
+            (0, 32) => AddressScriptType::P2wsh,
+            (1, 32) => AddressScriptType::P2tr,
+            _ => AddressScriptType::Unknown,
+        },
+    };
+    let network = if address.params == &AddressParams::LIQUID {
+        Some(AddressNetwork::Liquid)
+    } else if address.params == &AddressParams::LIQUID_TESTNET {
+        Some(AddressNetwork::LiquidTestnet)
+    } else if address.params == &AddressParams::ELEMENTS {
+        Some(AddressNetwork::ElementsRegtest)
+    } else {
+        None
+    };
+    AddressKind {
+        script_type,
+        is_confidential: address.is_blinded(),
+        network,
+        blinding_pubkey: address.blinding_pubkey,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_address_kind() {
+        let addr = Address::from_str("tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn").unwrap();
+        let kind = address_kind(&addr);
+        assert_eq!(kind.script_type, AddressScriptType::P2wpkh);
+        assert!(kind.is_confidential);
+        assert_eq!(kind.network, Some(AddressNetwork::LiquidTestnet));
+        assert_eq!(kind.blinding_pubkey, addr.blinding_pubkey);
+
+        let kind = address_kind(&addr.to_unconfidential());
+        assert_eq!(kind.script_type, AddressScriptType::P2wpkh);
+        assert!(!kind.is_confidential);
+        assert_eq!(kind.blinding_pubkey, None);
+
+        let pk = addr.blinding_pubkey.unwrap();
+        let script = elements::Script::new();
+        let params = &AddressParams::LIQUID;
+        let addr = Address::p2sh(&script, Some(pk), params);
+        let kind = address_kind(&addr);
+        assert_eq!(kind.script_type, AddressScriptType::P2sh);
+        assert_eq!(kind.network, Some(AddressNetwork::Liquid));
+        assert_eq!(kind.script_type.to_string(), "p2sh");
+
+        let addr = Address::p2pkh(&elements::bitcoin::PublicKey::new(pk), None, params);
+        assert_eq!(address_kind(&addr).script_type, AddressScriptType::P2pkh);
+
+        let addr = Address::p2wsh(&script, None, &AddressParams::ELEMENTS);
+        let kind = address_kind(&addr);
+        assert_eq!(kind.script_type, AddressScriptType::P2wsh);
+        assert_eq!(kind.network, Some(AddressNetwork::ElementsRegtest));
+    }
+}
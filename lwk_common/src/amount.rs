@@ -0,0 +1,187 @@
+use std::collections::BTreeMap;
+
+use elements::AssetId;
+
+use crate::precision::Precision;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Error {
+    #[error("Cannot combine amounts of different assets {0} and {1}")]
+    AssetMismatch(AssetId, AssetId),
+
+    #[error("Overflow adding {0} to {1} satoshi of asset {2}")]
+    Overflow(u64, u64, AssetId),
+
+    #[error("Underflow subtracting {0} from {1} satoshi of asset {2}")]
+    Underflow(u64, u64, AssetId),
+
+    #[error("The difference between {0} and {1} satoshi of asset {2} is out of the signed range")]
+    SignedOverflow(u64, u64, AssetId),
+}
+
+/// An amount of an asset in satoshi units, with checked arithmetic
+///
+/// Aggregating values of outputs, for instance to compute a balance, can overflow `u64`, in
+/// particular if they come from untrusted data. Operations on `AssetAmount` fail instead of
+/// silently wrapping and refuse to mix different assets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetAmount {
+    pub asset: AssetId,
+    pub sats: u64,
+}
+
+impl AssetAmount {
+    pub fn new(asset: AssetId, sats: u64) -> Self {
+        Self { asset, sats }
+    }
+
+    /// No satoshi of `asset`
+    pub fn zero(asset: AssetId) -> Self {
+        Self::new(asset, 0)
+    }
+
+    /// Add `other`, failing on overflow or if it has a different asset
+    pub fn checked_add(self, other: AssetAmount) -> Result<AssetAmount, Error> {
+        self.check_asset(&other)?;
+        self.checked_add_sats(other.sats)
+    }
+
+    /// Subtract `other`, failing on underflow or if it has a different asset
+    pub fn checked_sub(self, other: AssetAmount) -> Result<AssetAmount, Error> {
+        self.check_asset(&other)?;
+        self.checked_sub_sats(other.sats)
+    }
+
+    /// Add `sats` of the same asset, failing on overflow
+    pub fn checked_add_sats(self, sats: u64) -> Result<AssetAmount, Error> {
+        let result = self
+            .sats
+            .checked_add(sats)
+            .ok_or(Error::Overflow(sats, self.sats, self.asset))?;
+        Ok(Self::new(self.asset, result))
+    }
+
+    /// Subtract `sats` of the same asset, failing on underflow
+    pub fn checked_sub_sats(self, sats: u64) -> Result<AssetAmount, Error> {
+        let result = self
+            .sats
+            .checked_sub(sats)
+            .ok_or(Error::Underflow(sats, self.sats, self.asset))?;
+        Ok(Self::new(self.asset, result))
+    }
+
+    /// The amount formatted according to the precision of the asset
+    ///
+    /// ```
+    /// # use lwk_common::{AssetAmount, Precision};
+    /// # use elements::AssetId;
+    /// let amount = AssetAmount::new(AssetId::default(), 123_456);
+    /// assert_eq!(amount.to_string_with_precision(&Precision::new(2).unwrap()), "1234.56");
+    /// ```
+    pub fn to_string_with_precision(&self, precision: &Precision) -> String {
+        precision.unsigned_sats_to_string(self.sats)
+    }
+
+    fn check_asset(&self, other: &AssetAmount) -> Result<(), Error> {
+        if self.asset != other.asset {
+            return Err(Error::AssetMismatch(self.asset, other.asset));
+        }
+        Ok(())
+    }
+}
+
+/// Sum the given amounts by asset, failing on overflow
+pub fn sum_by_asset(
+    amounts: impl IntoIterator<Item = AssetAmount>,
+) -> Result<BTreeMap<AssetId, u64>, Error> {
+    let mut sums = BTreeMap::new();
+    add_by_asset(&mut sums, amounts)?;
+    Ok(sums)
+}
+
+/// Add the given amounts to the totals by asset in `sums`, failing on overflow
+pub fn add_by_asset(
+    sums: &mut BTreeMap<AssetId, u64>,
+    amounts: impl IntoIterator<Item = AssetAmount>,
+) -> Result<(), Error> {
+    for amount in amounts {
+        let total = sums.entry(amount.asset).or_default();
+        *total = AssetAmount::new(amount.asset, *total)
+            .checked_add(amount)?
+            .sats;
+    }
+    Ok(())
+}
+
+/// The net amount by asset of `incoming` minus `outgoing`, for instance the balance change of a
+/// transaction, failing on overflow
+///
+/// Assets appearing on either side are included, even if the net amount is zero.
+pub fn net_by_asset(
+    incoming: impl IntoIterator<Item = AssetAmount>,
+    outgoing: impl IntoIterator<Item = AssetAmount>,
+) -> Result<BTreeMap<AssetId, i64>, Error> {
+    let incoming = sum_by_asset(incoming)?;
+    let outgoing = sum_by_asset(outgoing)?;
+    let assets: std::collections::BTreeSet<&AssetId> =
+        incoming.keys().chain(outgoing.keys()).collect();
+    assets
+        .into_iter()
+        .map(|asset| {
+            let i = incoming.get(asset).copied().unwrap_or(0);
+            let o = outgoing.get(asset).copied().unwrap_or(0);
+            let net = i64::try_from(i as i128 - o as i128)
+                .map_err(|_| Error::SignedOverflow(i, o, *asset))?;
+            Ok((*asset, net))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::*;
+
+    #[test]
+    fn test_asset_amount() {
+        let a = AssetId::default();
+        let b = AssetId::from_str(&"11".repeat(32)).unwrap();
+
+        let amount = AssetAmount::new(a, 10);
+        assert_eq!(amount.checked_add(AssetAmount::new(a, 5)).unwrap().sats, 15);
+        assert_eq!(amount.checked_sub(AssetAmount::new(a, 5)).unwrap().sats, 5);
+        assert_eq!(
+            amount.checked_add(AssetAmount::new(b, 5)).unwrap_err(),
+            Error::AssetMismatch(a, b)
+        );
+        assert_eq!(
+            amount.checked_sub(AssetAmount::new(a, 11)).unwrap_err(),
+            Error::Underflow(11, 10, a)
+        );
+        assert_eq!(
+            amount.checked_add_sats(u64::MAX).unwrap_err(),
+            Error::Overflow(u64::MAX, 10, a)
+        );
+        assert_eq!(AssetAmount::zero(a).checked_sub_sats(0).unwrap().sats, 0);
+
+        let p = Precision::new(8).unwrap();
+        let max = AssetAmount::new(a, u64::MAX);
+        assert_eq!(max.to_string_with_precision(&p), "184467440737.09551615");
+
+        let sums = sum_by_asset([amount, AssetAmount::new(b, 1), amount]).unwrap();
+        assert_eq!(sums.get(&a), Some(&20));
+        assert_eq!(sums.get(&b), Some(&1));
+        let err = sum_by_asset([amount, max]).unwrap_err();
+        assert_eq!(err, Error::Overflow(u64::MAX, 10, a));
+
+        let net = net_by_asset([amount, AssetAmount::new(b, 1)], [AssetAmount::new(a, 15)]);
+        let net = net.unwrap();
+        assert_eq!(net.get(&a), Some(&-5));
+        assert_eq!(net.get(&b), Some(&1));
+        let net = net_by_asset([], [amount]).unwrap();
+        assert_eq!(net.get(&a), Some(&-10));
+        let err = net_by_asset([max], []).unwrap_err();
+        assert_eq!(err, Error::SignedOverflow(u64::MAX, 0, a));
+    }
+}
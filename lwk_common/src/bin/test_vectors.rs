@@ -0,0 +1,10 @@
+//! Print the canonical test vectors as JSON
+//!
+//! `cargo run -p lwk_common --features test_vectors --bin test_vectors > lwk_common/test_data/vectors.json`
+
+fn main() -> Result<(), String> {
+    let vectors = lwk_common::test_vectors::generate()?;
+    let s = serde_json::to_string_pretty(&vectors).map_err(|e| e.to_string())?;
+    println!("{s}");
+    Ok(())
+}
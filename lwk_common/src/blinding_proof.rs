@@ -0,0 +1,92 @@
+//! Proofs of knowledge of the blinding private key of a confidential address
+//!
+//! Showing the script of an address on a hardware device is not enough to detect a host replacing
+//! the blinding public key. With these proofs the party deriving the blinding private key signs
+//! the address script with it, the other party checks the signature against the blinding public
+//! key in the displayed confidential address, verifying the whole address.
+//!
+//! The format is specific to LWK and not interoperable: the proof is a DER encoded ECDSA
+//! signature of the SHA256 tagged hash, with tag `LWK-Blinding-Key-Proof/1.0`, of the address
+//! script. Devices and hosts can only check proofs made by LWK, in particular the proofs are not
+//! compatible with the ones of the Jade firmware.
+
+use elements::hashes::{sha256t_hash_newtype, Hash};
+use elements::secp256k1_zkp::{ecdsa::Signature, Message, Secp256k1, SecretKey};
+use elements::{Address, Script};
+
+use crate::Error;
+
+sha256t_hash_newtype! {
+    /// The tag of the hash
+    pub struct BlindingKeyProofTag = hash_str("LWK-Blinding-Key-Proof/1.0");
+
+    /// A tagged hash of the script, signed by the blinding private key
+    #[hash_newtype(forward)]
+    pub struct BlindingKeyProofHash(_);
+}
+
+fn message(script_pubkey: &Script) -> Message {
+    let hash = BlindingKeyProofHash::hash(script_pubkey.as_bytes());
+    Message::from_digest(hash.to_byte_array())
+}
+
+/// Create a proof that `blinding_key` is the blinding private key of the confidential address
+/// with the given `script_pubkey`
+///
+/// The proof can only be checked with [`verify_blinding_key_proof()`], see the [module
+/// documentation](self) for its format.
+pub fn blinding_key_proof(blinding_key: &SecretKey, script_pubkey: &Script) -> Signature {
+    let secp = Secp256k1::signing_only();
+    secp.sign_ecdsa(&message(script_pubkey), blinding_key)
+}
+
+/// Verify a proof created with [`blinding_key_proof()`] for the given confidential address
+pub fn verify_blinding_key_proof(address: &Address, proof: &Signature) -> Result<(), Error> {
+    let blinding_pubkey = address
+        .blinding_pubkey
+        .ok_or(Error::AddressNotConfidential)?;
+    let secp = Secp256k1::verification_only();
+    secp.verify_ecdsa(&message(&address.script_pubkey()), proof, &blinding_pubkey)
+        .map_err(|_| Error::InvalidBlindingKeyProof)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use elements::secp256k1_zkp::PublicKey;
+    use elements::AddressParams;
+
+    use super::*;
+
+    #[test]
+    fn test_blinding_key_proof() {
+        let secp = Secp256k1::new();
+        let blinding_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let other_key = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let addr = Address::from_str("tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn").unwrap();
+        let unconfidential = addr.to_unconfidential();
+        let script = unconfidential.script_pubkey();
+        let params = &AddressParams::LIQUID_TESTNET;
+
+        let pk = PublicKey::from_secret_key(&secp, &blinding_key);
+        let address = Address::from_script(&script, Some(pk), params).unwrap();
+        let proof = blinding_key_proof(&blinding_key, &script);
+        verify_blinding_key_proof(&address, &proof).unwrap();
+
+        // Blinding key replaced
+        let other_pk = PublicKey::from_secret_key(&secp, &other_key);
+        let replaced = Address::from_script(&script, Some(other_pk), params).unwrap();
+        let err = verify_blinding_key_proof(&replaced, &proof).unwrap_err();
+        assert!(matches!(err, Error::InvalidBlindingKeyProof));
+
+        // Proof for another script
+        let other_script = Script::from(vec![0x51]);
+        let proof = blinding_key_proof(&blinding_key, &other_script);
+        let err = verify_blinding_key_proof(&address, &proof).unwrap_err();
+        assert!(matches!(err, Error::InvalidBlindingKeyProof));
+
+        let err = verify_blinding_key_proof(&unconfidential, &proof).unwrap_err();
+        assert!(matches!(err, Error::AddressNotConfidential));
+    }
+}
@@ -0,0 +1,46 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A token to abort long running operations made of several requests, such as scanning the
+/// blockchain or a sequence of requests to a hardware signer
+///
+/// The token is checked between the requests: a request already sent, for instance a Jade
+/// waiting for the user confirmation or a call to the server, is not interrupted and the
+/// operation is aborted once it completes.
+/// Clones share the same state, so the token can be passed to the operation while a clone is
+/// kept to call [`CancellationToken::cancel()`] from another thread.
+/// Once cancelled, a token stays cancelled; use a new token for the next operation.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Create a new, not cancelled, token
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the cancellation of the operations using this token
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether the cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cancellation_token() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+        clone.cancel();
+        assert!(token.is_cancelled());
+        assert!(!CancellationToken::new().is_cancelled());
+    }
+}
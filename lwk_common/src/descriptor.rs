@@ -6,7 +6,7 @@ use elements_miniscript::descriptor::checksum::desc_checksum;
 use rand::{thread_rng, Rng};
 use thiserror::Error;
 
-use crate::Signer;
+use crate::{keyorigin_xpub_from_str, Signer};
 
 // TODO impl error handling
 pub fn singlesig_desc<S: Signer>(
@@ -15,11 +15,25 @@ pub fn singlesig_desc<S: Signer>(
     blinding_variant: DescriptorBlindingKey,
     is_mainnet: bool,
 ) -> Result<String, String> {
-    let coin_type = if is_mainnet { 1776 } else { 1 };
-    let (prefix, path, suffix) = match script_variant {
-        Singlesig::Wpkh => ("elwpkh", format!("84h/{coin_type}h/0h"), ""),
-        Singlesig::ShWpkh => ("elsh(wpkh", format!("49h/{coin_type}h/0h"), ")"),
+    let scheme = DerivationScheme {
+        script_variant,
+        coin_type: coin_type(is_mainnet),
+        account: 0,
     };
+    singlesig_desc_with_scheme(signer, &scheme, blinding_variant)
+}
+
+/// Like [`singlesig_desc()`] but deriving the xpub according to the given `scheme`
+pub fn singlesig_desc_with_scheme<S: Signer>(
+    signer: &S,
+    scheme: &DerivationScheme,
+    blinding_variant: DescriptorBlindingKey,
+) -> Result<String, String> {
+    let (prefix, suffix) = match scheme.script_variant {
+        Singlesig::Wpkh => ("elwpkh", ""),
+        Singlesig::ShWpkh => ("elsh(wpkh", ")"),
+    };
+    let path = scheme.path();
 
     let fingerprint = signer.fingerprint().map_err(|e| format!("{:?}", e))?;
 
@@ -48,6 +62,61 @@ pub fn singlesig_desc<S: Signer>(
     Ok(format!("{desc}#{checksum}"))
 }
 
+fn coin_type(is_mainnet: bool) -> u32 {
+    if is_mainnet {
+        1776
+    } else {
+        1
+    }
+}
+
+/// The derivation of the account xpub of a singlesig wallet
+///
+/// `m / purpose' / coin_type' / account'`, with purpose determined by the script variant.
+#[derive(Debug, Clone, Copy)]
+pub struct DerivationScheme {
+    pub script_variant: Singlesig,
+    pub coin_type: u32,
+    pub account: u32,
+}
+
+impl DerivationScheme {
+    /// The schemes to scan to recover a wallet created with an unknown derivation
+    ///
+    /// These are bip84 and bip49 with accounts from 0 to `max_account` included, plus account 0
+    /// with the coin type of the other network, which was used by some legacy wallets.
+    pub fn recovery_schemes(is_mainnet: bool, max_account: u32) -> Vec<Self> {
+        let variants = [Singlesig::Wpkh, Singlesig::ShWpkh];
+        let mut schemes = vec![];
+        for script_variant in variants {
+            for account in 0..=max_account {
+                schemes.push(DerivationScheme {
+                    script_variant,
+                    coin_type: coin_type(is_mainnet),
+                    account,
+                });
+            }
+        }
+        for script_variant in variants {
+            schemes.push(DerivationScheme {
+                script_variant,
+                coin_type: coin_type(!is_mainnet),
+                account: 0,
+            });
+        }
+        schemes
+    }
+
+    /// The derivation path of the account xpub, without the `m/` prefix
+    pub fn path(&self) -> String {
+        let purpose = match self.script_variant {
+            Singlesig::Wpkh => 84,
+            Singlesig::ShWpkh => 49,
+        };
+        format!("{purpose}h/{}h/{}h", self.coin_type, self.account)
+    }
+}
+
 fn fmt_path(path: &DerivationPath) -> String {
     path.to_string().replace("m/", "").replace('\'', "h")
 }
@@ -100,6 +169,103 @@ pub fn multisig_desc(
     Ok(format!("{desc}#{checksum}"))
 }
 
+/// The keys of a 2-of-3 collaborative custody setup
+///
+/// The user holds one key, a service provider (e.g. an exchange or a custodian) holds another
+/// one and the third is a backup key, usually kept offline by the user or by a third party.
+/// Any two of them can spend.
+///
+/// All keys must be derived at the standard bip87 path `m/87h/coin_type'/0h` (see
+/// [`CollaborativeCustody::standard_path()`]), so that every party can reconstruct the
+/// descriptor from the key origins alone.
+#[derive(Debug, Clone)]
+pub struct CollaborativeCustody {
+    user: (KeySource, Xpub),
+    provider: (KeySource, Xpub),
+    backup: (KeySource, Xpub),
+}
+
+impl CollaborativeCustody {
+    /// Create a setup from the key origin and xpubs of the three keys
+    ///
+    /// Fails if a key is not derived at the standard path or if the same key is used twice.
+    pub fn new(
+        user: (KeySource, Xpub),
+        provider: (KeySource, Xpub),
+        backup: (KeySource, Xpub),
+        is_mainnet: bool,
+    ) -> Result<Self, String> {
+        let expected = Self::standard_path(is_mainnet);
+        for (role, ((_, path), _)) in [
+            ("user", &user),
+            ("provider", &provider),
+            ("backup", &backup),
+        ] {
+            if path != &expected {
+                return Err(format!(
+                    "The {role} key is derived at {}, expected {}",
+                    fmt_path(path),
+                    fmt_path(&expected)
+                ));
+            }
+        }
+        if user.1 == provider.1 || user.1 == backup.1 || provider.1 == backup.1 {
+            return Err("The same key cannot have more than one role".into());
+        }
+        Ok(Self {
+            user,
+            provider,
+            backup,
+        })
+    }
+
+    /// Like [`CollaborativeCustody::new()`] but deriving the user key from the given `signer`
+    pub fn with_user_signer<S: Signer>(
+        signer: &S,
+        provider: (KeySource, Xpub),
+        backup: (KeySource, Xpub),
+        is_mainnet: bool,
+    ) -> Result<Self, String> {
+        let path = Self::standard_path(is_mainnet);
+        let fingerprint = signer.fingerprint().map_err(|e| format!("{:?}", e))?;
+        let xpub = signer.derive_xpub(&path).map_err(|e| format!("{:?}", e))?;
+        Self::new(((fingerprint, path), xpub), provider, backup, is_mainnet)
+    }
+
+    /// The derivation path of the keys, `m/87h/coin_type'/0h`
+    pub fn standard_path(is_mainnet: bool) -> DerivationPath {
+        let coin_type = coin_type(is_mainnet);
+        DerivationPath::from_str(&format!("m/87h/{coin_type}h/0h")).expect("static")
+    }
+
+    /// Check the provider key is among the key origin xpubs published by the provider
+    ///
+    /// Providers publish the keys they use in the format `[fingerprint/path]xpub`,
+    /// this step protects the user from setting up a wallet with a key not controlled by the provider.
+    pub fn verify_provider(&self, published: &[String]) -> Result<(), String> {
+        for s in published {
+            let (keyorigin, xpub) = keyorigin_xpub_from_str(s).map_err(|e| e.to_string())?;
+            if xpub == self.provider.1 && keyorigin.as_ref().map_or(true, |k| k == &self.provider.0)
+            {
+                return Ok(());
+            }
+        }
+        Err(format!(
+            "Provider key {} is not among the keys published by the provider",
+            self.provider.1
+        ))
+    }
+
+    /// The 2-of-3 multisig descriptor, with keys in user, provider, backup order
+    pub fn descriptor(&self, blinding_variant: DescriptorBlindingKey) -> Result<String, String> {
+        let xpubs = [&self.user, &self.provider, &self.backup]
+            .into_iter()
+            .map(|(keysource, xpub)| (Some(keysource.clone()), *xpub))
+            .collect();
+        multisig_desc(2, xpubs, Multisig::Wsh, blinding_variant)
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Singlesig {
     /// as defined by bip84
@@ -169,6 +335,7 @@ impl FromStr for DescriptorBlindingKey {
     }
 }
 
+#[derive(Debug, Clone, Copy)]
 pub enum Bip {
     Bip84,
     Bip49,
@@ -210,3 +377,84 @@ impl FromStr for Multisig {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn keyorigin_xpub(s: &str) -> (KeySource, Xpub) {
+        let (keyorigin, xpub) = keyorigin_xpub_from_str(s).unwrap();
+        (keyorigin.unwrap(), xpub)
+    }
+
+    #[test]
+    fn test_collaborative_custody() {
+        let user = "[996febb0/87h/1h/0h]tpubDDR1DaDYEKDCkuZ6eU6orfUZshJDDZNjauQsKeK6SkwqPSnRYRPGuYM5MnCqJo88Az8YX6a9oP45W4fenTyS9kLg1TG3LJBbY1jS36r893V";
+        let provider = "[a5a0841e/87h/1h/0h]tpubDDZCCwQJyHksYEfUHb59Mr4ZCo1ndMt4Ys8rXF7RLhmfttU9AYybscFyCmWRVQUxffjGYQe8dtmGchA91PhLUCkH3H7D7Nx1CJLrv5W9tTs";
+        let backup = "[aa15e1ca/87h/1h/0h]tpubDDsijcL7DGGbS2gckSw23LJYiX5s8XDXy4TnYEe7itCMC9LyooBtAVQCaQygQu7Q3yv91NCiCDaYVkrFTrqdM4QY97kFZFHN1ei72B7EcRt";
+        let other = "[e6b7814d/87h/1h/0h]tpubDDmvBugC5YMK3UDKjcym7ED8Vfv8aLiX83Tcbecc783VFPEDqBigmzF52uFMyh89bXaf7jAporM1LcoaMcLdKeV4m7ixNAchpMQCL569Ldv";
+
+        let custody = CollaborativeCustody::new(
+            keyorigin_xpub(user),
+            keyorigin_xpub(provider),
+            keyorigin_xpub(backup),
+            false,
+        )
+        .unwrap();
+        let desc = custody.descriptor(DescriptorBlindingKey::Elip151).unwrap();
+        assert!(desc.starts_with(&format!(
+            "ct(elip151,elwsh(multi(2,{user}/<0;1>/*,{provider}/<0;1>/*,{backup}/<0;1>/*)))#"
+        )));
+
+        custody
+            .verify_provider(&[other.to_string(), provider.to_string()])
+            .unwrap();
+        let provider_xpub = provider.split(']').nth(1).unwrap();
+        custody
+            .verify_provider(&[provider_xpub.to_string()])
+            .unwrap();
+        custody.verify_provider(&[other.to_string()]).unwrap_err();
+        let wrong_origin = provider.replace("a5a0841e", "00000000");
+        custody.verify_provider(&[wrong_origin]).unwrap_err();
+
+        let err = CollaborativeCustody::new(
+            keyorigin_xpub(user),
+            keyorigin_xpub(provider),
+            keyorigin_xpub(backup),
+            true,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "The user key is derived at 87h/1h/0h, expected 87h/1776h/0h"
+        );
+
+        let err = CollaborativeCustody::new(
+            keyorigin_xpub(user),
+            keyorigin_xpub(provider),
+            keyorigin_xpub(provider),
+            false,
+        )
+        .unwrap_err();
+        assert_eq!(err, "The same key cannot have more than one role");
+    }
+
+    #[test]
+    fn test_recovery_schemes() {
+        let schemes = DerivationScheme::recovery_schemes(true, 5);
+        assert_eq!(schemes.len(), 14);
+        let paths: Vec<_> = schemes.iter().map(|s| s.path()).collect();
+        assert_eq!(paths[0], "84h/1776h/0h");
+        assert_eq!(paths[5], "84h/1776h/5h");
+        assert_eq!(paths[6], "49h/1776h/0h");
+        assert_eq!(paths[12], "84h/1h/0h");
+        assert_eq!(paths[13], "49h/1h/0h");
+
+        let schemes = DerivationScheme::recovery_schemes(false, 0);
+        let paths: Vec<_> = schemes.iter().map(|s| s.path()).collect();
+        assert_eq!(
+            paths,
+            ["84h/1h/0h", "49h/1h/0h", "84h/1776h/0h", "49h/1776h/0h"]
+        );
+    }
+}
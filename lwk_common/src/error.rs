@@ -1,5 +1,39 @@
+use std::fmt::Display;
+
 use elements_miniscript::elements::OutPoint;
 
+/// Broad classification of errors across the workspace crates, useful to decide how to react
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    /// Failed communication with a server or a device, retrying may succeed
+    Network,
+
+    /// Data violating the protocol rules, for instance proofs or commitments that do not verify
+    Consensus,
+
+    /// The state of the wallet doesn't allow the operation, for instance insufficient funds
+    Wallet,
+
+    /// The signer failed or refused to sign
+    Signer,
+
+    /// Invalid data given by the caller
+    Input,
+}
+
+impl Display for ErrorCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            ErrorCategory::Network => "network",
+            ErrorCategory::Consensus => "consensus",
+            ErrorCategory::Wallet => "wallet",
+            ErrorCategory::Signer => "signer",
+            ErrorCategory::Input => "input",
+        };
+        write!(f, "{s}")
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("There is no unblinding information and Input #{idx} is missing witness_utxo of outpoint {previous_outpoint}")]
@@ -8,9 +42,6 @@ pub enum Error {
         previous_outpoint: OutPoint,
     },
 
-    #[error("Input #{idx} has a pegin, but it's not supported")]
-    InputPeginUnsupported { idx: usize },
-
     #[error("Input #{idx} has a blinded issuance, but it's not supported")]
     InputBlindedIssuance { idx: usize },
 
@@ -60,12 +91,78 @@ pub enum Error {
     )]
     OutputCommitmentsMismatch { idx: usize },
 
+    #[error("Output #{idx} is claimed as change but does not belong to the wallet")]
+    OutputChangeNotMine { idx: usize },
+
     #[error("Private blinding key not available")]
     MissingPrivateBlindingKey,
 
+    #[error("Address is not confidential")]
+    AddressNotConfidential,
+
+    #[error("Blinding key proof does not match the address")]
+    InvalidBlindingKeyProof,
+
+    #[error("Input #{idx} does not exist")]
+    InputNotExist { idx: usize },
+
+    #[error("Cannot compute the sighash of input #{idx}: {reason}")]
+    Sighash { idx: usize, reason: String },
+
+    #[error("Input #{idx} signature is not valid")]
+    InvalidSignature { idx: usize },
+
+    #[error("Input #{idx} cached sighash does not match the transaction")]
+    CachedSighashMismatch { idx: usize },
+
+    #[error("Signature of input #{idx} by {public_key} was not requested by the session")]
+    SignatureNotRequested {
+        idx: usize,
+        public_key: elements::bitcoin::PublicKey,
+    },
+
+    #[error(transparent)]
+    Amount(#[from] crate::amount::Error),
+
     #[error(transparent)]
     DescConversion(#[from] elements_miniscript::descriptor::ConversionError),
 
     #[error(transparent)]
     Miniscript(#[from] elements_miniscript::Error),
 }
+
+impl Error {
+    /// The category of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            Error::MissingPreviousOutput { .. }
+            | Error::InputBlindedIssuance { .. }
+            | Error::InputNotBlinded { .. }
+            | Error::OutputAssetNone { .. }
+            | Error::OutputValueNone { .. }
+            | Error::OutputAssetValueNone { .. }
+            | Error::MissingFee
+            | Error::MultipleFee
+            | Error::BlindedFee
+            | Error::OutputNotBlinded { .. }
+            | Error::OutputChangeNotMine { .. }
+            | Error::InputNotExist { .. }
+            | Error::SignatureNotRequested { .. }
+            | Error::Sighash { .. }
+            | Error::Amount(_)
+            | Error::DescConversion(_)
+            | Error::Miniscript(_)
+            | Error::AddressNotConfidential => ErrorCategory::Input,
+            Error::InputCommitmentsMismatch { .. }
+            | Error::InvalidAssetBlindProof { .. }
+            | Error::InvalidValueBlindProof { .. }
+            | Error::OutputCommitmentsMismatch { .. }
+            | Error::InvalidBlindingKeyProof
+            | Error::InvalidSignature { .. }
+            | Error::CachedSighashMismatch { .. } => ErrorCategory::Consensus,
+            Error::InputMineNotUnblindable { .. }
+            | Error::OutputMineNotUnblindable { .. }
+            | Error::MissingPrivateBlindingKey => ErrorCategory::Wallet,
+        }
+    }
+}
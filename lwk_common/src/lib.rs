@@ -9,25 +9,45 @@
 //!
 //!  To avoid circular dependencies this crate must not depend on other crate of the workspace
 
+mod address;
+pub mod amount;
+pub mod blinding_proof;
+mod cancel;
 mod descriptor;
 mod error;
 mod keyorigin_xpub;
 mod model;
 pub mod precision;
 mod qr;
+pub mod sighash;
 mod signer;
+#[cfg(feature = "test_vectors")]
+pub mod test_vectors;
+mod ur;
 
+pub use crate::address::{address_kind, AddressKind, AddressNetwork, AddressScriptType};
+pub use crate::amount::AssetAmount;
+pub use crate::blinding_proof::{blinding_key_proof, verify_blinding_key_proof};
+pub use crate::cancel::CancellationToken;
 pub use crate::descriptor::{
-    multisig_desc, singlesig_desc, Bip, DescriptorBlindingKey, InvalidBipVariant,
-    InvalidBlindingKeyVariant, InvalidMultisigVariant, InvalidSinglesigVariant, Multisig,
-    Singlesig,
+    multisig_desc, singlesig_desc, singlesig_desc_with_scheme, Bip, CollaborativeCustody,
+    DerivationScheme, DescriptorBlindingKey, InvalidBipVariant, InvalidBlindingKeyVariant,
+    InvalidMultisigVariant, InvalidSinglesigVariant, Multisig, Singlesig,
 };
-pub use crate::error::Error;
+pub use crate::error::{Error, ErrorCategory};
 pub use crate::keyorigin_xpub::{keyorigin_xpub_from_str, InvalidKeyOriginXpub};
 pub use crate::model::*;
 pub use crate::precision::Precision;
 pub use crate::qr::*;
+pub use crate::sighash::{
+    pset_add_signature, pset_cache_sighashes, pset_cached_sighash, pset_check_cached_sighashes,
+    pset_input_sighash, pset_sighash_requests, InputSighash, PendingSignatures, SighashRequest,
+};
 pub use crate::signer::Signer;
+pub use crate::ur::{
+    pset_from_ur, pset_from_ur_parts, pset_to_ur, pset_to_ur_parts, UrError,
+    DEFAULT_UR_FRAGMENT_LEN, PSET_UR_TYPE,
+};
 
 use elements::confidential::{Asset, Value};
 use elements_miniscript::confidential::bare::tweak_private_key;
@@ -35,7 +55,10 @@ use elements_miniscript::confidential::Key;
 use elements_miniscript::descriptor::DescriptorSecretKey;
 use elements_miniscript::elements::bitcoin::secp256k1::SecretKey;
 use elements_miniscript::elements::{
-    bitcoin::{bip32::KeySource, key::PublicKey},
+    bitcoin::{
+        bip32::{Fingerprint, KeySource},
+        key::PublicKey,
+    },
     opcodes::all::OP_RETURN,
     pset::PartiallySignedTransaction,
     script::Builder,
@@ -96,6 +119,15 @@ fn is_mine(
     descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
     bip32_derivation: &BTreeMap<PublicKey, KeySource>,
 ) -> Result<bool, Error> {
+    Ok(mine_chain(script_pubkey, descriptor, bip32_derivation)?.is_some())
+}
+
+/// If the script is of the wallet, return whether it belongs to the internal (change) chain
+fn mine_chain(
+    script_pubkey: &Script,
+    descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+    bip32_derivation: &BTreeMap<PublicKey, KeySource>,
+) -> Result<Option<bool>, Error> {
     for (_, path) in bip32_derivation.values() {
         // TODO should I check descriptor derivation path is compatible with given bip32_derivation?
         // TODO consider fingerprint if available
@@ -103,17 +135,46 @@ fn is_mine(
             continue;
         }
         let wildcard_index = path[path.len() - 1];
-        for d in descriptor.descriptor.clone().into_single_descriptors()? {
+        // Multipath descriptors `<0;1>` are split in the external and the internal chain
+        for (i, d) in descriptor
+            .descriptor
+            .clone()
+            .into_single_descriptors()?
+            .into_iter()
+            .enumerate()
+        {
             // TODO improve by checking only the descriptor ending with the given path
             let mine = d
                 .at_derivation_index(wildcard_index.into())?
                 .script_pubkey();
             if &mine == script_pubkey {
-                return Ok(true);
+                return Ok(Some(i == 1));
             }
         }
     }
-    Ok(false)
+    Ok(None)
+}
+
+/// For each output of the PSET, who receives it with respect to the wallet with `descriptor`
+///
+/// Outputs are recognized as the wallet's using their key derivations.
+pub fn pset_outputs(
+    pset: &PartiallySignedTransaction,
+    descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+) -> Vec<OutputKind> {
+    pset.outputs()
+        .iter()
+        .map(|output| {
+            if output.script_pubkey.is_empty() {
+                return OutputKind::Fee;
+            }
+            match mine_chain(&output.script_pubkey, descriptor, &output.bip32_derivation) {
+                Ok(Some(true)) => OutputKind::Change,
+                Ok(Some(false)) => OutputKind::Receive,
+                _ => OutputKind::External,
+            }
+        })
+        .collect()
 }
 
 pub fn pset_balance(
@@ -121,7 +182,8 @@ pub fn pset_balance(
     descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
 ) -> Result<PsetBalance, Error> {
     let secp = Secp256k1::new();
-    let mut balances: BTreeMap<AssetId, i64> = BTreeMap::new();
+    let mut spent = vec![];
+    let mut received = vec![];
     let mut fee: Option<u64> = None;
     for (idx, input) in pset.inputs().iter().enumerate() {
         match input.witness_utxo.as_ref() {
@@ -143,8 +205,9 @@ pub fn pset_balance(
                     continue;
                 }
 
-                if input.is_pegin() {
-                    return Err(Error::InputPeginUnsupported { idx });
+                if input.is_pegin() || input.pegin_value.is_some() {
+                    // Peg-ins claim funds from the mainchain, they are not spent from the wallet
+                    continue;
                 }
                 if input.has_issuance() {
                     let issuance = input.asset_issuance();
@@ -155,9 +218,14 @@ pub fn pset_balance(
                     }
                 }
 
-                // We expect the input to be blinded
+                // We expect the input to be blinded, or explicit as in unblinded regtest
+                // transactions
                 let (asset_comm, amount_comm) = match (txout.asset, txout.value) {
                     (Asset::Confidential(g), Value::Confidential(c)) => (g, c),
+                    (Asset::Explicit(asset), Value::Explicit(value)) => {
+                        spent.push(AssetAmount::new(asset, value));
+                        continue;
+                    }
                     _ => return Err(Error::InputNotBlinded { idx }),
                 };
 
@@ -176,7 +244,7 @@ pub fn pset_balance(
                     return Err(Error::InputCommitmentsMismatch { idx });
                 }
 
-                *balances.entry(txout_secrets.asset).or_default() -= txout_secrets.value as i64;
+                spent.push(AssetAmount::new(txout_secrets.asset, txout_secrets.value));
             }
         }
     }
@@ -203,7 +271,7 @@ pub fn pset_balance(
             continue;
         }
 
-        // Expect all outputs to be blinded and with blind proofs
+        // Expect all outputs to be blinded and with blind proofs, or explicit
         match (
             output.asset,
             output.asset_comm,
@@ -246,16 +314,85 @@ pub fn pset_balance(
                     return Err(Error::OutputCommitmentsMismatch { idx });
                 }
 
-                *balances.entry(asset).or_default() += amount as i64;
+                received.push(AssetAmount::new(asset, amount));
+            }
+            // Explicit output, not meant to be blinded
+            (Some(asset), None, None, Some(amount), None, None)
+                if output.blinding_key.is_none() =>
+            {
+                received.push(AssetAmount::new(asset, amount));
             }
             _ => return Err(Error::OutputNotBlinded { idx }),
         }
     }
     let fee = fee.ok_or(Error::MissingFee)?;
+    let balances = amount::net_by_asset(received, spent)?;
 
     Ok(PsetBalance { fee, balances })
 }
 
+/// The asset and the amount of a PSET output, if they are proven
+///
+/// For a blinded output they are claims of who created the PSET, returned only if the blind
+/// proofs show that they match the commitments. For explicit outputs, and for outputs not blinded
+/// yet, they are the values which are going to be in the transaction.
+pub fn pset_output_asset_amount(output: &elements::pset::Output) -> Option<(AssetId, u64)> {
+    let secp = Secp256k1::new();
+    match (
+        output.asset,
+        output.asset_comm,
+        output.blind_asset_proof.as_ref(),
+        output.amount,
+        output.amount_comm,
+        output.blind_value_proof.as_ref(),
+    ) {
+        (
+            Some(asset),
+            Some(asset_comm),
+            Some(blind_asset_proof),
+            Some(amount),
+            Some(amount_comm),
+            Some(blind_value_proof),
+        ) => {
+            let proven = blind_asset_proof.blind_asset_proof_verify(&secp, asset, asset_comm)
+                && blind_value_proof.blind_value_proof_verify(
+                    &secp,
+                    amount,
+                    asset_comm,
+                    amount_comm,
+                );
+            proven.then_some((asset, amount))
+        }
+        (Some(asset), None, _, Some(amount), None, _) => Some((asset, amount)),
+        _ => None,
+    }
+}
+
+/// Check that the outputs claimed as change, i.e. the outputs having a key derivation with the
+/// given `fingerprint`, belong to the wallet defined by `descriptor`.
+///
+/// Signers can call this before signing to refuse PSETs where a change output has been
+/// substituted with a script they do not control.
+pub fn pset_check_change(
+    pset: &PartiallySignedTransaction,
+    descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+    fingerprint: &Fingerprint,
+) -> Result<(), Error> {
+    for (idx, output) in pset.outputs().iter().enumerate() {
+        let claimed = output
+            .bip32_derivation
+            .values()
+            .any(|(f, _)| f == fingerprint);
+        if claimed
+            && !is_mine(&output.script_pubkey, descriptor, &output.bip32_derivation)
+                .unwrap_or(false)
+        {
+            return Err(Error::OutputChangeNotMine { idx });
+        }
+    }
+    Ok(())
+}
+
 pub fn pset_signatures(pset: &PartiallySignedTransaction) -> Vec<PsetSignatures> {
     pset.inputs()
         .iter()
@@ -291,7 +428,10 @@ mod test {
     use elements::{pset::PartiallySignedTransaction, AssetId};
     use elements_miniscript::{ConfidentialDescriptor, DescriptorPublicKey};
 
-    use crate::pset_balance;
+    use crate::{
+        burn_script, pset_balance, pset_check_change, pset_output_asset_amount, pset_outputs,
+        Error, OutputKind,
+    };
 
     #[test]
     fn test_pset_details() {
@@ -312,4 +452,81 @@ mod test {
         let v = balance.balances.get(&asset_id).unwrap();
         assert_eq!(*v, -1);
     }
+
+    #[test]
+    fn test_pset_check_change() {
+        let desc_str = include_str!("../test_data/pset_details/descriptor");
+        let desc: ConfidentialDescriptor<DescriptorPublicKey> = desc_str.parse().unwrap();
+        let fingerprint = "93970d14".parse().unwrap();
+        let other_fingerprint = "00000000".parse().unwrap();
+
+        let pset_str = include_str!("../test_data/pset_details/pset.base64");
+        let mut pset: PartiallySignedTransaction = pset_str.parse().unwrap();
+        pset_check_change(&pset, &desc, &fingerprint).unwrap();
+
+        // Substitute a change output script
+        let idx = pset
+            .outputs()
+            .iter()
+            .position(|o| !o.bip32_derivation.is_empty())
+            .unwrap();
+        pset.outputs_mut()[idx].script_pubkey = burn_script();
+        let err = pset_check_change(&pset, &desc, &fingerprint).unwrap_err();
+        assert!(matches!(err, Error::OutputChangeNotMine { idx: i } if i == idx));
+
+        // Outputs not claimed by the given fingerprint are not checked
+        pset_check_change(&pset, &desc, &other_fingerprint).unwrap();
+    }
+
+    #[test]
+    fn test_pset_outputs() {
+        use OutputKind::*;
+        let desc_str = include_str!("../test_data/pset_details/descriptor");
+        let desc: ConfidentialDescriptor<DescriptorPublicKey> = desc_str.parse().unwrap();
+
+        // A redeposit to a receive address of the wallet
+        let pset_str = include_str!("../test_data/pset_details/pset.base64");
+        let pset: PartiallySignedTransaction = pset_str.parse().unwrap();
+        let outputs = pset_outputs(&pset, &desc);
+        assert_eq!(outputs, vec![Receive, Change, Change, Fee]);
+
+        let pset_str = include_str!("../test_data/pset_details/pset2.base64");
+        let mut pset: PartiallySignedTransaction = pset_str.parse().unwrap();
+        let outputs = pset_outputs(&pset, &desc);
+        assert_eq!(outputs, vec![External, Change, Change, Fee]);
+
+        // A substituted change script is not reported as change
+        pset.outputs_mut()[1].script_pubkey = burn_script();
+        let outputs = pset_outputs(&pset, &desc);
+        assert_eq!(outputs, vec![External, External, Change, Fee]);
+    }
+
+    #[test]
+    fn test_pset_output_asset_amount() {
+        let pset_str = include_str!("../test_data/pset_details/pset2.base64");
+        let mut pset: PartiallySignedTransaction = pset_str.parse().unwrap();
+        let output = &pset.outputs()[0];
+        let (asset, amount) = pset_output_asset_amount(output).unwrap();
+        assert_eq!((Some(asset), Some(amount)), (output.asset, output.amount));
+
+        // The fee output is explicit
+        let fee = &pset.outputs()[3];
+        assert_eq!(
+            pset_output_asset_amount(fee),
+            Some((fee.asset.unwrap(), fee.amount.unwrap()))
+        );
+
+        // Claims not matching the commitments
+        pset.outputs_mut()[0].amount = Some(amount + 1);
+        assert_eq!(pset_output_asset_amount(&pset.outputs()[0]), None);
+        pset.outputs_mut()[0].amount = Some(amount);
+        pset.outputs_mut()[0].asset = pset.outputs()[3].asset;
+        assert_ne!(pset.outputs()[0].asset, Some(asset));
+        assert_eq!(pset_output_asset_amount(&pset.outputs()[0]), None);
+
+        // Claims without proofs
+        pset.outputs_mut()[0].asset = Some(asset);
+        pset.outputs_mut()[0].blind_value_proof = None;
+        assert_eq!(pset_output_asset_amount(&pset.outputs()[0]), None);
+    }
 }
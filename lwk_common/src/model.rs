@@ -90,6 +90,103 @@ pub struct PsetDetails {
 
     /// For each input, the corresponding issuance
     pub issuances: Vec<Issuance>,
+
+    /// The output paying the fee of the application embedding the wallet, if any
+    pub service_fee: Option<ServiceFeeOutput>,
+
+    /// Suspicious characteristics of the PSET with respect to the wallet
+    pub warnings: Vec<PsetWarning>,
+
+    /// For each output, who receives it with respect to the wallet
+    pub outputs: Vec<OutputKind>,
+}
+
+/// Who receives an output of a PSET, with respect to a wallet
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputKind {
+    /// The output pays the fee
+    Fee,
+
+    /// The output goes to an address of the internal (change) chain of the wallet
+    Change,
+
+    /// The output goes to an address of the external (receive) chain of the wallet
+    Receive,
+
+    /// The output goes to someone else
+    External,
+}
+
+/// A characteristic of a PSET that the user should double check before signing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PsetWarning {
+    /// The output sends to an address of the wallet derived far beyond the last unused index.
+    ///
+    /// A wallet restored from the descriptor stops scanning after a window of unused addresses,
+    /// so funds sent there might not be found. A malicious coordinator could set such a change
+    /// output to make the funds hard to recover.
+    FarAddressIndex {
+        /// The output index
+        vout: u32,
+
+        /// Whether the address is a change address
+        change: bool,
+
+        /// The derivation index of the address
+        index: u32,
+
+        /// The last unused derivation index of the wallet in the same chain
+        last_unused: u32,
+    },
+
+    /// The fee is higher than the configured percentage of the L-BTC sent
+    HighFee {
+        /// The fee in satoshi
+        fee: u64,
+
+        /// The L-BTC sent out of the wallet, excluding the fee
+        sent: u64,
+
+        /// The maximum fee configured, in basis points of the L-BTC sent
+        max_basis_points: u32,
+    },
+}
+
+impl std::fmt::Display for PsetWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PsetWarning::FarAddressIndex {
+                vout,
+                change,
+                index,
+                last_unused,
+            } => {
+                let kind = if *change { "change" } else { "receive" };
+                write!(
+                    f,
+                    "Output {vout} sends to {kind} address with index {index}, far beyond the last unused {last_unused}"
+                )
+            }
+            PsetWarning::HighFee {
+                fee,
+                sent,
+                max_basis_points,
+            } => write!(
+                f,
+                "Fee {fee} is higher than {max_basis_points} basis points of the L-BTC sent {sent}"
+            ),
+        }
+    }
+}
+
+/// An output paying the service fee configured in the wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceFeeOutput {
+    /// The output index
+    pub vout: u32,
+
+    /// The L-BTC amount of the fee
+    pub satoshi: u64,
 }
 
 impl PsetDetails {
@@ -114,4 +211,15 @@ impl PsetDetails {
         }
         r
     }
+
+    /// The indexes of the outputs going back to the wallet change chain, which signer UIs can
+    /// avoid asking the user to confirm
+    pub fn change_outputs(&self) -> Vec<u32> {
+        self.outputs
+            .iter()
+            .enumerate()
+            .filter(|(_, kind)| **kind == OutputKind::Change)
+            .map(|(vout, _)| vout as u32)
+            .collect()
+    }
 }
@@ -42,19 +42,32 @@ impl Precision {
     /// assert_eq!(p.sats_to_string(100), "1.00");
     /// ```
     pub fn sats_to_string(&self, sats: i64) -> String {
+        let negative = if sats < 0 { "-" } else { "" };
+        format!(
+            "{}{}",
+            negative,
+            self.unsigned_sats_to_string(sats.unsigned_abs())
+        )
+    }
+
+    /// Convert the given non-negative `sats` to the formatted value according to our precision
+    ///
+    /// ```
+    /// # use lwk_common::precision::Precision;
+    /// let p = Precision::new(2).unwrap();
+    /// assert_eq!(p.unsigned_sats_to_string(u64::MAX), "184467440737095516.15");
+    /// ```
+    pub fn unsigned_sats_to_string(&self, sats: u64) -> String {
         let precision = self.0 as usize;
+        let sats = sats.to_string();
         if precision == 0 {
-            return sats.to_string();
-        }
-
-        let negative = if sats < 0 { "-" } else { "" };
-        let sats = sats.abs().to_string();
-        if sats.len() > precision {
+            sats
+        } else if sats.len() > precision {
             let over = sats.len() - precision;
-            format!("{}{}.{}", negative, &sats[..over], &sats[over..])
+            format!("{}.{}", &sats[..over], &sats[over..])
         } else {
             let missing = precision - sats.len();
-            format!("{}0.{}{}", negative, "0".repeat(missing), sats)
+            format!("0.{}{}", "0".repeat(missing), sats)
         }
     }
 
@@ -124,6 +137,7 @@ mod test {
         check_sat_to_str(8, 100, "0.00000100");
         check_sat_to_str(8, 100_000_000, "1.00000000");
         check_sat_to_str(8, -100_000_000, "-1.00000000");
+        check_sat_to_str(2, i64::MIN, "-92233720368547758.08");
 
         check_str_to_sat(8, ".1", 10_000_000);
         check_str_to_sat(8, "0.1", 10_000_000);
@@ -0,0 +1,438 @@
+//! Signatures of PSET inputs made outside of the [`crate::Signer`] implementations
+//!
+//! Signing backends which can't handle a PSET, such as HSMs or MPC services, can only sign a
+//! digest. [`pset_input_sighash()`] returns the digest to sign for an input and the keys expected
+//! to sign it, then the signatures produced externally are added to the PSET with
+//! [`pset_add_signature()`], which checks them before insertion.
+//!
+//! Signers which produce the signatures asynchronously, like the coordinators of multi-party
+//! computation (MPC) or threshold signature schemes, are sent the digests of
+//! [`PendingSignatures`], then the signatures they return are injected in the PSET with
+//! [`PendingSignatures::complete()`].
+//!
+//! When several signers sign the same PSET, the first one can cache the `SIGHASH_ALL` digests in
+//! the PSET with [`pset_cache_sighashes()`], so that the following ones, for instance devices
+//! slow at computing the digests of confidential transactions, can take them with
+//! [`pset_cached_sighash()`]. The cache is not authenticated: a signer which does not trust the
+//! party which cached the digests should compare them with [`pset_check_cached_sighashes()`].
+
+use std::collections::HashMap;
+
+use elements::bitcoin::bip32::{Fingerprint, KeySource};
+use elements::bitcoin::PublicKey;
+use elements::hashes::Hash;
+use elements::pset::raw::ProprietaryKey;
+use elements::pset::PartiallySignedTransaction;
+use elements::secp256k1_zkp::{ecdsa::Signature, Message, Secp256k1};
+use elements::sighash::SighashCache;
+use elements::{BlockHash, EcdsaSighashType};
+use elements_miniscript::elementssig_to_rawsig;
+use elements_miniscript::psbt::PsbtExt;
+
+use crate::Error;
+
+/// The digest to sign for an input of a PSET
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputSighash {
+    /// The message to sign with ECDSA
+    pub sighash: Message,
+
+    /// The keys expected to sign the input, with their origin
+    pub keys: Vec<(PublicKey, KeySource)>,
+}
+
+/// A digest to be signed by an external signer with one of the keys of an input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SighashRequest {
+    /// The index of the input
+    pub index: usize,
+
+    /// The message to sign with ECDSA and `SIGHASH_ALL`
+    pub sighash: Message,
+
+    /// The key expected to sign
+    pub public_key: PublicKey,
+
+    /// The origin of the key
+    pub key_source: KeySource,
+}
+
+/// A PSET waiting for the signatures requested to an external signer
+#[derive(Debug, Clone, PartialEq)]
+pub struct PendingSignatures {
+    /// The identifier of the signing session
+    pub id: String,
+
+    /// The PSET to sign
+    pub pset: PartiallySignedTransaction,
+
+    /// The digests to sign
+    pub requests: Vec<SighashRequest>,
+}
+
+impl PendingSignatures {
+    /// The pending signatures of `pset` requested to the signer with `fingerprint` in the session
+    /// `id`, for instance to resume a session after a restart
+    pub fn new(
+        id: String,
+        pset: PartiallySignedTransaction,
+        fingerprint: &Fingerprint,
+    ) -> Result<Self, Error> {
+        let requests = pset_sighash_requests(&pset, fingerprint)?;
+        Ok(Self { id, pset, requests })
+    }
+
+    /// Return the PSET with the `signatures` made with `SIGHASH_ALL`, each must answer one of the
+    /// requests and be valid
+    pub fn complete(
+        &self,
+        signatures: &[(usize, PublicKey, Signature)],
+    ) -> Result<PartiallySignedTransaction, Error> {
+        let mut pset = self.pset.clone();
+        for (index, public_key, signature) in signatures {
+            if !self
+                .requests
+                .iter()
+                .any(|r| r.index == *index && r.public_key == *public_key)
+            {
+                return Err(Error::SignatureNotRequested {
+                    idx: *index,
+                    public_key: *public_key,
+                });
+            }
+            pset_add_signature(
+                &mut pset,
+                *index,
+                *public_key,
+                *signature,
+                EcdsaSighashType::All,
+            )?;
+        }
+        Ok(pset)
+    }
+}
+
+fn sighash(
+    pset: &PartiallySignedTransaction,
+    index: usize,
+    sighash_type: EcdsaSighashType,
+) -> Result<Message, Error> {
+    let mut pset = pset.clone();
+    let input = pset
+        .inputs_mut()
+        .get_mut(index)
+        .ok_or(Error::InputNotExist { idx: index })?;
+    input.sighash_type = Some(sighash_type.into());
+    let tx = pset.extract_tx().map_err(|e| Error::Sighash {
+        idx: index,
+        reason: e.to_string(),
+    })?;
+    let mut cache = SighashCache::new(&tx);
+    // genesis hash is not used for segwit v0 sighashes
+    let msg = pset
+        .sighash_msg(index, &mut cache, None, BlockHash::all_zeros())
+        .map_err(|e| Error::Sighash {
+            idx: index,
+            reason: e.to_string(),
+        })?;
+    Ok(msg.to_secp_msg())
+}
+
+/// The digest to sign for the input at `index` with `sighash_type`
+pub fn pset_input_sighash(
+    pset: &PartiallySignedTransaction,
+    index: usize,
+    sighash_type: EcdsaSighashType,
+) -> Result<InputSighash, Error> {
+    let sighash = sighash(pset, index, sighash_type)?;
+    let keys = pset.inputs()[index]
+        .bip32_derivation
+        .iter()
+        .map(|(pk, ks)| (*pk, ks.clone()))
+        .collect();
+    Ok(InputSighash { sighash, keys })
+}
+
+/// The digests to sign with `SIGHASH_ALL` by the keys of the signer with `fingerprint` which
+/// have not signed yet
+pub fn pset_sighash_requests(
+    pset: &PartiallySignedTransaction,
+    fingerprint: &Fingerprint,
+) -> Result<Vec<SighashRequest>, Error> {
+    let mut requests = vec![];
+    for (index, input) in pset.inputs().iter().enumerate() {
+        let keys: Vec<_> = input
+            .bip32_derivation
+            .iter()
+            .filter(|(pk, (f, _))| f == fingerprint && !input.partial_sigs.contains_key(pk))
+            .collect();
+        if keys.is_empty() {
+            continue;
+        }
+        let sighash = sighash(pset, index, EcdsaSighashType::All)?;
+        for (public_key, key_source) in keys {
+            requests.push(SighashRequest {
+                index,
+                sighash,
+                public_key: *public_key,
+                key_source: key_source.clone(),
+            });
+        }
+    }
+    Ok(requests)
+}
+
+/// Subtype of the input proprietary key caching the `SIGHASH_ALL` digest
+///
+/// The key has the `pset` prefix, since elements drops the input proprietary fields with other
+/// prefixes when parsing a PSET, and a subtype unused by the PSET specification.
+const PSET_IN_LWK_SIGHASH: u8 = 0xf0;
+
+/// Key data of the input proprietary key caching the `SIGHASH_ALL` digest
+const SIGHASH_CACHE_KEY: &[u8] = b"lwk_sighash_all";
+
+fn sighash_cache_key() -> ProprietaryKey {
+    ProprietaryKey::from_pset_pair(PSET_IN_LWK_SIGHASH, SIGHASH_CACHE_KEY.to_vec())
+}
+
+/// The `SIGHASH_ALL` digests of the inputs with keys to sign, by input index
+///
+/// Unlike calling [`pset_input_sighash()`] for every input, the transaction is extracted and the
+/// hashes shared by the inputs are computed once.
+fn sighashes_all(pset: &PartiallySignedTransaction) -> Result<Vec<(usize, Message)>, Error> {
+    let mut pset = pset.clone();
+    for input in pset.inputs_mut() {
+        input.sighash_type = Some(EcdsaSighashType::All.into());
+    }
+    let indexes: Vec<_> = pset
+        .inputs()
+        .iter()
+        .enumerate()
+        .filter(|(_, input)| !input.bip32_derivation.is_empty())
+        .map(|(index, _)| index)
+        .collect();
+    let Some(first) = indexes.first() else {
+        return Ok(vec![]);
+    };
+    let tx = pset.extract_tx().map_err(|e| Error::Sighash {
+        idx: *first,
+        reason: e.to_string(),
+    })?;
+    let mut cache = SighashCache::new(&tx);
+    let mut sighashes = vec![];
+    for index in indexes {
+        // genesis hash is not used for segwit v0 sighashes
+        let msg = pset
+            .sighash_msg(index, &mut cache, None, BlockHash::all_zeros())
+            .map_err(|e| Error::Sighash {
+                idx: index,
+                reason: e.to_string(),
+            })?;
+        sighashes.push((index, msg.to_secp_msg()));
+    }
+    Ok(sighashes)
+}
+
+/// Cache in the proprietary fields of `pset` the `SIGHASH_ALL` digests of the inputs with keys to
+/// sign, replacing the ones already cached
+pub fn pset_cache_sighashes(pset: &mut PartiallySignedTransaction) -> Result<(), Error> {
+    for (index, sighash) in sighashes_all(pset)? {
+        pset.inputs_mut()[index]
+            .proprietary
+            .insert(sighash_cache_key(), sighash.as_ref().to_vec());
+    }
+    Ok(())
+}
+
+/// The `SIGHASH_ALL` digest of the input at `index` cached with [`pset_cache_sighashes()`], if
+/// any
+///
+/// The digest is taken as is, see [`pset_check_cached_sighashes()`].
+pub fn pset_cached_sighash(
+    pset: &PartiallySignedTransaction,
+    index: usize,
+) -> Result<Option<Message>, Error> {
+    let input = pset
+        .inputs()
+        .get(index)
+        .ok_or(Error::InputNotExist { idx: index })?;
+    input
+        .proprietary
+        .get(&sighash_cache_key())
+        .map(|value| {
+            Message::from_digest_slice(value)
+                .map_err(|_| Error::CachedSighashMismatch { idx: index })
+        })
+        .transpose()
+}
+
+/// Compute the `SIGHASH_ALL` digests of `pset` and check that they match the cached ones
+///
+/// Fails if the PSET has been changed after caching the digests, or if they were not computed
+/// correctly.
+pub fn pset_check_cached_sighashes(pset: &PartiallySignedTransaction) -> Result<(), Error> {
+    let cached = (0..pset.inputs().len())
+        .map(|index| pset_cached_sighash(pset, index))
+        .collect::<Result<Vec<_>, _>>()?;
+    if cached.iter().all(Option::is_none) {
+        return Ok(());
+    }
+    let computed: HashMap<_, _> = sighashes_all(pset)?.into_iter().collect();
+    for (index, cached) in cached.into_iter().enumerate() {
+        if let Some(cached) = cached {
+            if computed.get(&index) != Some(&cached) {
+                return Err(Error::CachedSighashMismatch { idx: index });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add the `signature` of `public_key` made with `sighash_type` to the input at `index`
+///
+/// The signature is normalized to low S and must be valid for the digest returned by
+/// [`pset_input_sighash()`].
+pub fn pset_add_signature(
+    pset: &mut PartiallySignedTransaction,
+    index: usize,
+    public_key: PublicKey,
+    mut signature: Signature,
+    sighash_type: EcdsaSighashType,
+) -> Result<(), Error> {
+    let msg = sighash(pset, index, sighash_type)?;
+    signature.normalize_s();
+    Secp256k1::verification_only()
+        .verify_ecdsa(&msg, &signature, &public_key.inner)
+        .map_err(|_| Error::InvalidSignature { idx: index })?;
+    let raw = elementssig_to_rawsig(&(signature, sighash_type));
+    pset.inputs_mut()[index]
+        .partial_sigs
+        .insert(public_key, raw);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use elements::secp256k1_zkp::SecretKey;
+    use elements::Script;
+
+    use super::*;
+
+    fn pset() -> PartiallySignedTransaction {
+        include_str!("../test_data/pset_details/pset.base64")
+            .trim()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_add_signature() {
+        let secp = Secp256k1::new();
+        let mut pset = pset();
+        let sighash_type = EcdsaSighashType::All;
+        let s = pset_input_sighash(&pset, 0, sighash_type).unwrap();
+        assert_eq!(s.keys.len(), pset.inputs()[0].bip32_derivation.len());
+        let single = pset_input_sighash(&pset, 0, EcdsaSighashType::Single).unwrap();
+        assert_ne!(s.sighash, single.sighash);
+
+        let secret_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let public_key = PublicKey::new(secret_key.public_key(&secp));
+        let signature = secp.sign_ecdsa(&s.sighash, &secret_key);
+
+        // A signature of another input or sighash type is rejected
+        let err = pset_add_signature(
+            &mut pset,
+            0,
+            public_key,
+            signature,
+            EcdsaSighashType::Single,
+        )
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidSignature { idx: 0 }));
+        assert_eq!(pset.inputs().len(), 2);
+        let err =
+            pset_add_signature(&mut pset, 1, public_key, signature, sighash_type).unwrap_err();
+        assert!(matches!(err, Error::InvalidSignature { idx: 1 }));
+        assert!(pset.inputs()[0].partial_sigs.is_empty());
+
+        pset_add_signature(&mut pset, 0, public_key, signature, sighash_type).unwrap();
+        let raw = elementssig_to_rawsig(&(signature, sighash_type));
+        assert_eq!(pset.inputs()[0].partial_sigs.get(&public_key), Some(&raw));
+
+        let n = pset.inputs().len();
+        let err = pset_input_sighash(&pset, n, sighash_type).unwrap_err();
+        assert!(matches!(err, Error::InputNotExist { idx } if idx == n));
+    }
+
+    #[test]
+    fn test_sighash_requests() {
+        let pset = pset();
+        let (fingerprint, _) = pset.inputs()[0].bip32_derivation.values().next().unwrap();
+        let requests = pset_sighash_requests(&pset, fingerprint).unwrap();
+        assert!(!requests.is_empty());
+        for r in requests.iter() {
+            assert_eq!(&r.key_source.0, fingerprint);
+            let s = pset_input_sighash(&pset, r.index, EcdsaSighashType::All).unwrap();
+            assert_eq!(r.sighash, s.sighash);
+        }
+
+        let other = Fingerprint::from([0u8; 4]);
+        assert!(pset_sighash_requests(&pset, &other).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sighash_cache() {
+        let mut pset = pset();
+        assert_eq!(pset_cached_sighash(&pset, 0).unwrap(), None);
+        pset_check_cached_sighashes(&pset).unwrap();
+
+        pset_cache_sighashes(&mut pset).unwrap();
+        // The cache survives the serialization of the PSET
+        let pset: PartiallySignedTransaction = pset.to_string().parse().unwrap();
+        assert_eq!(pset.inputs().len(), 2);
+        for index in 0..2 {
+            let s = pset_input_sighash(&pset, index, EcdsaSighashType::All).unwrap();
+            assert_eq!(pset_cached_sighash(&pset, index).unwrap(), Some(s.sighash));
+        }
+        pset_check_cached_sighashes(&pset).unwrap();
+        let err = pset_cached_sighash(&pset, 2).unwrap_err();
+        assert!(matches!(err, Error::InputNotExist { idx: 2 }));
+
+        // Changing the transaction invalidates the cache
+        let mut changed = pset.clone();
+        changed.outputs_mut()[0].script_pubkey = Script::new();
+        let err = pset_check_cached_sighashes(&changed).unwrap_err();
+        assert!(matches!(err, Error::CachedSighashMismatch { idx: 0 }));
+        pset_cache_sighashes(&mut changed).unwrap();
+        pset_check_cached_sighashes(&changed).unwrap();
+    }
+
+    #[test]
+    fn test_pending_signatures() {
+        let secp = Secp256k1::new();
+        let pset = pset();
+        let (fingerprint, _) = pset.inputs()[0].bip32_derivation.values().next().unwrap();
+        let pending = PendingSignatures::new("session".into(), pset.clone(), fingerprint).unwrap();
+        assert_eq!(pending.id, "session");
+        let requests = pset_sighash_requests(&pset, fingerprint).unwrap();
+        assert_eq!(pending.requests, requests);
+
+        // A signature of a key which was not requested is rejected
+        let secret_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let public_key = PublicKey::new(secret_key.public_key(&secp));
+        let request = &pending.requests[0];
+        let signature = secp.sign_ecdsa(&request.sighash, &secret_key);
+        let err = pending
+            .complete(&[(request.index, public_key, signature)])
+            .unwrap_err();
+        assert!(matches!(err, Error::SignatureNotRequested { .. }));
+
+        // An invalid signature of a requested key is rejected
+        let err = pending
+            .complete(&[(request.index, request.public_key, signature)])
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidSignature { .. }));
+
+        assert_eq!(pending.complete(&[]).unwrap(), pset);
+    }
+}
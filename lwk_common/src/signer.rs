@@ -9,6 +9,7 @@ use elements::{
     pset::PartiallySignedTransaction,
 };
 use elements_miniscript::slip77::MasterBlindingKey;
+use elements_miniscript::{ConfidentialDescriptor, DescriptorPublicKey};
 
 use crate::descriptor::Bip;
 
@@ -20,6 +21,40 @@ pub trait Signer {
     /// returns how many signatures were added or overwritten
     fn sign(&self, pset: &mut PartiallySignedTransaction) -> Result<u32, Self::Error>;
 
+    /// Like [`Signer::sign()`], but before signing verify with [`crate::pset_check_change()`]
+    /// that the outputs claimed as change by this signer belong to the wallet `descriptor`
+    fn sign_with_change_check(
+        &self,
+        pset: &mut PartiallySignedTransaction,
+        descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+    ) -> Result<u32, Self::Error>
+    where
+        Self::Error: From<crate::Error>,
+    {
+        crate::pset_check_change(pset, descriptor, &self.fingerprint()?)?;
+        self.sign(pset)
+    }
+
+    /// Return the number of confirmations the user is expected to give on the device to sign the
+    /// given pset, `0` for signers that don't require user interaction
+    ///
+    /// Call it before [`Signer::sign()`] to tell the user how many prompts to expect.
+    fn prompt_estimate(&self, _pset: &PartiallySignedTransaction) -> Result<u32, Self::Error> {
+        Ok(0)
+    }
+
+    /// Show on the device the external address of `descriptor` at derivation `index`, and
+    /// return it, so that the user can check it before receiving funds
+    ///
+    /// Returns `None` for signers without a display.
+    fn display_address(
+        &self,
+        _descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+        _index: u32,
+    ) -> Result<Option<String>, Self::Error> {
+        Ok(None)
+    }
+
     /// Derive an xpub from the master, path can contains hardened derivations
     fn derive_xpub(&self, path: &DerivationPath) -> Result<Xpub, Self::Error>;
 
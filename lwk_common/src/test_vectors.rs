@@ -0,0 +1,139 @@
+//! Canonical test vectors to check other implementations are compatible with this crate
+//!
+//! The vectors are computed from the wallet and the PSETs in `test_data/pset_details`,
+//! [`generate()`] output is committed in `test_data/vectors.json` and printed by the
+//! `test_vectors` binary.
+
+use std::collections::BTreeMap;
+
+use elements::hex::ToHex;
+use elements::pset::PartiallySignedTransaction;
+use elements::{Address, AddressParams};
+use elements_miniscript::{ConfidentialDescriptor, DescriptorPublicKey};
+use serde_json::{json, Value};
+
+use crate::{derive_blinding_key, derive_script_pubkey, pset_balance, pset_signatures};
+
+/// Number of addresses generated for each chain of the descriptor
+const ADDRESSES: u32 = 5;
+
+const DESCRIPTOR: &str = include_str!("../test_data/pset_details/descriptor");
+const PSETS: [&str; 2] = [
+    include_str!("../test_data/pset_details/pset.base64"),
+    include_str!("../test_data/pset_details/pset2.base64"),
+];
+
+/// Generate the test vectors
+pub fn generate() -> Result<Value, String> {
+    let secp = elements::secp256k1_zkp::Secp256k1::new();
+    let descriptor: ConfidentialDescriptor<DescriptorPublicKey> = DESCRIPTOR
+        .trim()
+        .parse()
+        .map_err(|e: elements_miniscript::Error| e.to_string())?;
+
+    let mut chains = vec![];
+    for single in descriptor
+        .descriptor
+        .clone()
+        .into_single_descriptors()
+        .map_err(|e| e.to_string())?
+    {
+        let single = ConfidentialDescriptor {
+            key: descriptor.key.clone(),
+            descriptor: single,
+        };
+        let mut addresses = vec![];
+        for index in 0..ADDRESSES {
+            let script_pubkey = derive_script_pubkey(&single, index).map_err(|e| e.to_string())?;
+            let blinding_key = derive_blinding_key(&single, &script_pubkey)
+                .ok_or_else(|| "Missing blinding key".to_string())?;
+            let address = Address::from_script(
+                &script_pubkey,
+                Some(blinding_key.public_key(&secp)),
+                &AddressParams::LIQUID_TESTNET,
+            )
+            .ok_or_else(|| "Script without address".to_string())?;
+            addresses.push(json!({
+                "index": index,
+                "script_pubkey": script_pubkey.to_hex(),
+                "blinding_key": blinding_key.display_secret().to_string(),
+                "address": address.to_string(),
+            }));
+        }
+        chains.push(json!({
+            "descriptor": single.to_string(),
+            "addresses": addresses,
+        }));
+    }
+
+    let mut psets = vec![];
+    for pset_str in PSETS {
+        let pset: PartiallySignedTransaction =
+            pset_str.trim().parse().map_err(|e| format!("{e:?}"))?;
+        let tx = pset.extract_tx().map_err(|e| e.to_string())?;
+
+        let mut unblinded_outputs = vec![];
+        for (vout, txout) in tx.output.iter().enumerate() {
+            if txout.is_fee() || !txout.value.is_confidential() {
+                continue;
+            }
+            let Some(key) = derive_blinding_key(&descriptor, &txout.script_pubkey) else {
+                continue;
+            };
+            // Outputs not belonging to the wallet can't be unblinded
+            if let Ok(secrets) = txout.unblind(&secp, key) {
+                unblinded_outputs.push(json!({
+                    "vout": vout,
+                    "asset": secrets.asset.to_string(),
+                    "asset_bf": secrets.asset_bf.to_string(),
+                    "value": secrets.value,
+                    "value_bf": secrets.value_bf.to_string(),
+                }));
+            }
+        }
+
+        let balance = pset_balance(&pset, &descriptor).map_err(|e| e.to_string())?;
+        let balances: BTreeMap<String, i64> = balance
+            .balances
+            .iter()
+            .map(|(asset, satoshi)| (asset.to_string(), *satoshi))
+            .collect();
+        let signatures: Vec<_> = pset_signatures(&pset)
+            .iter()
+            .map(|s| {
+                json!({
+                    "has_signature": s.has_signature.len(),
+                    "missing_signature": s.missing_signature.len(),
+                })
+            })
+            .collect();
+        psets.push(json!({
+            "txid": tx.txid().to_string(),
+            "unblinded_outputs": unblinded_outputs,
+            "details": {
+                "fee": balance.fee,
+                "balances": balances,
+                "signatures": signatures,
+            },
+        }));
+    }
+
+    Ok(json!({
+        "network": "liquid-testnet",
+        "descriptor": descriptor.to_string(),
+        "chains": chains,
+        "psets": psets,
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vectors_unchanged() {
+        let expected: Value =
+            serde_json::from_str(include_str!("../test_data/vectors.json")).unwrap();
+        assert_eq!(generate().unwrap(), expected);
+    }
+}
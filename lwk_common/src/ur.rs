@@ -0,0 +1,456 @@
+//! [Uniform Resources](https://github.com/BlockchainCommons/Research/blob/master/papers/bcr-2020-005-ur.md)
+//! (UR), to move PSETs with QR codes between an online and an air-gapped machine.
+//!
+//! The UR text uses only letters, so once uppercased it fits the compact alphanumeric mode of
+//! QR codes. PSETs too large for a single QR code are split in multi-part URs, to be shown as
+//! an animated QR code. Only the first `seq_len` parts of the fountain code are produced and
+//! accepted, each carrying one fragment of the message; parts mixing several fragments are
+//! rejected, the sender must loop over the parts until all of them are scanned.
+
+use std::collections::BTreeMap;
+
+/// The UR type of PSETs, mirroring `crypto-psbt` of Bitcoin PSBTs
+pub const PSET_UR_TYPE: &str = "crypto-pset";
+
+/// A fragment length whose parts fit comfortably in a QR code
+pub const DEFAULT_UR_FRAGMENT_LEN: usize = 200;
+
+/// Fragments shorter than this are not worth splitting into
+const MIN_FRAGMENT_LEN: usize = 10;
+
+#[rustfmt::skip]
+const BYTEWORDS: [&str; 256] = [
+    "able", "acid", "also", "apex", "aqua", "arch", "atom", "aunt", "away", "axis", "back", "bald",
+    "barn", "belt", "beta", "bias", "blue", "body", "brag", "brew", "bulb", "buzz", "calm", "cash",
+    "cats", "chef", "city", "claw", "code", "cola", "cook", "cost", "crux", "curl", "cusp", "cyan",
+    "dark", "data", "days", "deli", "dice", "diet", "door", "down", "draw", "drop", "drum", "dull",
+    "duty", "each", "easy", "echo", "edge", "epic", "even", "exam", "exit", "eyes", "fact", "fair",
+    "fern", "figs", "film", "fish", "fizz", "flap", "flew", "flux", "foxy", "free", "frog", "fuel",
+    "fund", "gala", "game", "gear", "gems", "gift", "girl", "glow", "good", "gray", "grim", "guru",
+    "gush", "gyro", "half", "hang", "hard", "hawk", "heat", "help", "high", "hill", "holy", "hope",
+    "horn", "huts", "iced", "idea", "idle", "inch", "inky", "into", "iris", "iron", "item", "jade",
+    "jazz", "join", "jolt", "jowl", "judo", "jugs", "jump", "junk", "jury", "keep", "keno", "kept",
+    "keys", "kick", "kiln", "king", "kite", "kiwi", "knob", "lamb", "lava", "lazy", "leaf", "legs",
+    "liar", "limp", "lion", "list", "logo", "loud", "love", "luau", "luck", "lung", "main", "many",
+    "math", "maze", "memo", "menu", "meow", "mild", "mint", "miss", "monk", "nail", "navy", "need",
+    "news", "next", "noon", "note", "numb", "obey", "oboe", "omit", "onyx", "open", "oval", "owls",
+    "paid", "part", "peck", "play", "plus", "poem", "pool", "pose", "puff", "puma", "purr", "quad",
+    "quiz", "race", "ramp", "real", "redo", "rich", "road", "rock", "roof", "ruby", "ruin", "runs",
+    "rust", "safe", "saga", "scar", "sets", "silk", "skew", "slot", "soap", "solo", "song", "stub",
+    "surf", "swan", "taco", "task", "taxi", "tent", "tied", "time", "tiny", "toil", "tomb", "toys",
+    "trip", "tuna", "twin", "ugly", "undo", "unit", "urge", "user", "vast", "very", "veto", "vial",
+    "vibe", "view", "visa", "void", "vows", "wall", "wand", "warm", "wasp", "wave", "waxy", "webs",
+    "what", "when", "whiz", "wolf", "work", "yank", "yawn", "yell", "yoga", "yurt", "zaps", "zero",
+    "zest", "zinc", "zone", "zoom",
+];
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum UrError {
+    #[error("Not a UR of type {0}")]
+    InvalidType(String),
+
+    #[error("Multi-part UR, decode all its parts together")]
+    UnexpectedMultipart,
+
+    #[error("Multi-part UR parts mixing several fragments are not supported")]
+    UnsupportedMixedPart,
+
+    #[error("Inconsistent multi-part UR: {0}")]
+    InconsistentParts(String),
+
+    #[error("Missing parts of the multi-part UR: {0:?}")]
+    MissingParts(Vec<u32>),
+
+    #[error("Invalid bytewords")]
+    InvalidBytewords,
+
+    #[error("Invalid UR checksum")]
+    InvalidChecksum,
+
+    #[error("Invalid UR payload")]
+    InvalidPayload,
+}
+
+/// Encode the PSET `bytes` as a single-part UR, e.g. `ur:crypto-pset/...`
+pub fn pset_to_ur(bytes: &[u8]) -> String {
+    format!("ur:{PSET_UR_TYPE}/{}", bytewords_encode(&cbor_bytes(bytes)))
+}
+
+/// Decode the PSET bytes from a single-part UR, case insensitive
+pub fn pset_from_ur(ur: &str) -> Result<Vec<u8>, UrError> {
+    let ur = ur.trim().to_ascii_lowercase();
+    match ur_body(&ur)? {
+        (None, body) => cbor_bytes_decode(&bytewords_decode(body)?),
+        (Some(_), _) => Err(UrError::UnexpectedMultipart),
+    }
+}
+
+/// Encode the PSET `bytes` as URs with fragments up to `max_fragment_len` bytes
+///
+/// If the PSET fits in a single fragment the result is the single-part UR of [`pset_to_ur`],
+/// otherwise the parts are `ur:crypto-pset/<seq>-<len>/...` to be shown in a loop.
+pub fn pset_to_ur_parts(bytes: &[u8], max_fragment_len: usize) -> Vec<String> {
+    let message = cbor_bytes(bytes);
+    let fragment_len = fragment_len(message.len(), max_fragment_len);
+    if fragment_len >= message.len() {
+        return vec![pset_to_ur(bytes)];
+    }
+    let checksum = crc32(&message);
+    let seq_len = message.len().div_ceil(fragment_len);
+    (0..seq_len)
+        .map(|i| {
+            let start = i * fragment_len;
+            let mut fragment = message[start..message.len().min(start + fragment_len)].to_vec();
+            fragment.resize(fragment_len, 0);
+            let mut part = vec![0x85]; // CBOR array of 5 elements
+            for n in [i + 1, seq_len, message.len(), checksum as usize] {
+                part.extend(cbor_uint(n as u64));
+            }
+            part.extend(cbor_bytes(&fragment));
+            format!(
+                "ur:{PSET_UR_TYPE}/{}-{seq_len}/{}",
+                i + 1,
+                bytewords_encode(&part)
+            )
+        })
+        .collect()
+}
+
+/// Decode the PSET bytes from all the parts of a multi-part UR, in any order, or from a
+/// single-part UR, case insensitive
+pub fn pset_from_ur_parts<S: AsRef<str>>(parts: &[S]) -> Result<Vec<u8>, UrError> {
+    if let [single] = parts {
+        let single = single.as_ref().trim().to_ascii_lowercase();
+        if let (None, _) = ur_body(&single)? {
+            return pset_from_ur(&single);
+        }
+    }
+    let mut header = None;
+    let mut fragments: BTreeMap<u32, Vec<u8>> = BTreeMap::new();
+    for part in parts {
+        let part = part.as_ref().trim().to_ascii_lowercase();
+        let (seq, body) = match ur_body(&part)? {
+            (Some(seq), body) => (seq, body),
+            (None, _) => {
+                return Err(UrError::InconsistentParts(
+                    "single-part UR among multiple parts".into(),
+                ))
+            }
+        };
+        let part = Part::decode(&bytewords_decode(body)?)?;
+        if seq != (part.seq_num, part.seq_len) {
+            return Err(UrError::InconsistentParts(format!(
+                "sequence {}-{} does not match the content",
+                seq.0, seq.1
+            )));
+        }
+        if part.seq_num == 0 || part.seq_len == 0 {
+            return Err(UrError::InvalidPayload);
+        }
+        if part.seq_num > part.seq_len {
+            return Err(UrError::UnsupportedMixedPart);
+        }
+        let this_header = (
+            part.seq_len,
+            part.message_len,
+            part.checksum,
+            part.data.len(),
+        );
+        if *header.get_or_insert(this_header) != this_header {
+            return Err(UrError::InconsistentParts(
+                "parts of different messages".into(),
+            ));
+        }
+        fragments.insert(part.seq_num, part.data);
+    }
+    let (seq_len, message_len, checksum, fragment_len) =
+        header.ok_or(UrError::MissingParts(vec![]))?;
+    if (seq_len as usize).saturating_mul(fragment_len) < message_len {
+        return Err(UrError::InvalidPayload);
+    }
+    let missing: Vec<u32> = (1..=seq_len)
+        .filter(|i| !fragments.contains_key(i))
+        .collect();
+    if !missing.is_empty() {
+        return Err(UrError::MissingParts(missing));
+    }
+    let mut message: Vec<u8> = fragments.into_values().flatten().collect();
+    message.truncate(message_len);
+    if crc32(&message) != checksum {
+        return Err(UrError::InvalidChecksum);
+    }
+    cbor_bytes_decode(&message)
+}
+
+/// The sequence number and length of a multi-part UR part
+type Sequence = (u32, u32);
+
+/// The sequence of multi-part URs, with the bytewords
+fn ur_body(ur: &str) -> Result<(Option<Sequence>, &str), UrError> {
+    let prefix = format!("ur:{PSET_UR_TYPE}/");
+    let body = ur
+        .strip_prefix(&prefix)
+        .ok_or_else(|| UrError::InvalidType(PSET_UR_TYPE.to_string()))?;
+    match body.split_once('/') {
+        None => Ok((None, body)),
+        Some((seq, body)) => {
+            let (num, len) = seq.split_once('-').ok_or(UrError::InvalidPayload)?;
+            let num = num.parse().map_err(|_| UrError::InvalidPayload)?;
+            let len = len.parse().map_err(|_| UrError::InvalidPayload)?;
+            Ok((Some((num, len)), body))
+        }
+    }
+}
+
+/// The fragment length of the smallest number of fragments not longer than `max_fragment_len`
+fn fragment_len(message_len: usize, max_fragment_len: usize) -> usize {
+    let max_fragment_len = max_fragment_len.max(MIN_FRAGMENT_LEN);
+    let max_count = (message_len / MIN_FRAGMENT_LEN).max(1);
+    let mut len = message_len;
+    for count in 1..=max_count {
+        len = message_len.div_ceil(count);
+        if len <= max_fragment_len {
+            break;
+        }
+    }
+    len
+}
+
+/// A part of a multi-part UR
+struct Part {
+    seq_num: u32,
+    seq_len: u32,
+    message_len: usize,
+    checksum: u32,
+    data: Vec<u8>,
+}
+
+impl Part {
+    fn decode(cbor: &[u8]) -> Result<Self, UrError> {
+        let (header, mut rest) = cbor.split_first().ok_or(UrError::InvalidPayload)?;
+        if *header != 0x85 {
+            return Err(UrError::InvalidPayload);
+        }
+        let mut uints = [0u64; 4];
+        for n in uints.iter_mut() {
+            (*n, rest) = cbor_uint_decode(rest)?;
+        }
+        let [seq_num, seq_len, message_len, checksum] = uints;
+        let to_u32 = |n: u64| u32::try_from(n).map_err(|_| UrError::InvalidPayload);
+        Ok(Part {
+            seq_num: to_u32(seq_num)?,
+            seq_len: to_u32(seq_len)?,
+            message_len: usize::try_from(message_len).map_err(|_| UrError::InvalidPayload)?,
+            checksum: to_u32(checksum)?,
+            data: cbor_bytes_decode(rest)?,
+        })
+    }
+}
+
+/// Bytewords minimal encoding, the first and the last letter of each word, with a CRC32
+/// checksum appended to the data
+fn bytewords_encode(data: &[u8]) -> String {
+    let checksum = crc32(data).to_be_bytes();
+    data.iter()
+        .chain(checksum.iter())
+        .flat_map(|b| {
+            let word = BYTEWORDS[*b as usize].as_bytes();
+            [word[0] as char, word[3] as char]
+        })
+        .collect()
+}
+
+fn bytewords_decode(s: &str) -> Result<Vec<u8>, UrError> {
+    let s = s.as_bytes();
+    if s.len() % 2 != 0 || s.len() < 8 {
+        return Err(UrError::InvalidBytewords);
+    }
+    let mut data = s
+        .chunks(2)
+        .map(|pair| {
+            BYTEWORDS
+                .iter()
+                .position(|w| w.as_bytes()[0] == pair[0] && w.as_bytes()[3] == pair[1])
+                .map(|i| i as u8)
+                .ok_or(UrError::InvalidBytewords)
+        })
+        .collect::<Result<Vec<u8>, _>>()?;
+    let checksum = data.split_off(data.len() - 4);
+    if checksum != crc32(&data).to_be_bytes() {
+        return Err(UrError::InvalidChecksum);
+    }
+    Ok(data)
+}
+
+/// CRC32 as in ISO-HDLC, used by zip and by URs
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// The CBOR header of `major` type with argument `n`
+fn cbor_header(major: u8, n: u64) -> Vec<u8> {
+    let major = major << 5;
+    if n < 24 {
+        vec![major | n as u8]
+    } else if n <= u8::MAX as u64 {
+        vec![major | 24, n as u8]
+    } else if n <= u16::MAX as u64 {
+        let mut v = vec![major | 25];
+        v.extend((n as u16).to_be_bytes());
+        v
+    } else if n <= u32::MAX as u64 {
+        let mut v = vec![major | 26];
+        v.extend((n as u32).to_be_bytes());
+        v
+    } else {
+        let mut v = vec![major | 27];
+        v.extend(n.to_be_bytes());
+        v
+    }
+}
+
+/// Decode a CBOR header of `major` type, returning its argument and the following bytes
+fn cbor_header_decode(major: u8, cbor: &[u8]) -> Result<(u64, &[u8]), UrError> {
+    let (header, rest) = cbor.split_first().ok_or(UrError::InvalidPayload)?;
+    if header >> 5 != major {
+        return Err(UrError::InvalidPayload);
+    }
+    let size = match header & 0x1f {
+        n @ 0..=23 => return Ok((n as u64, rest)),
+        n @ 24..=27 => 1 << (n - 24),
+        _ => return Err(UrError::InvalidPayload),
+    };
+    if rest.len() < size {
+        return Err(UrError::InvalidPayload);
+    }
+    let (n, rest) = rest.split_at(size);
+    Ok((n.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64), rest))
+}
+
+/// The CBOR encoding of an unsigned integer
+fn cbor_uint(n: u64) -> Vec<u8> {
+    cbor_header(0, n)
+}
+
+fn cbor_uint_decode(cbor: &[u8]) -> Result<(u64, &[u8]), UrError> {
+    cbor_header_decode(0, cbor)
+}
+
+/// The CBOR encoding of a byte string
+fn cbor_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut result = cbor_header(2, bytes.len() as u64);
+    result.extend(bytes);
+    result
+}
+
+fn cbor_bytes_decode(cbor: &[u8]) -> Result<Vec<u8>, UrError> {
+    let (len, rest) = cbor_header_decode(2, cbor)?;
+    if rest.len() as u64 != len {
+        return Err(UrError::InvalidPayload);
+    }
+    Ok(rest.to_vec())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bytewords() {
+        // Test vector of BCR-2020-012
+        let data = [0, 1, 2, 128, 255];
+        assert_eq!(bytewords_encode(&data), "aeadaolazmjendeoti");
+        assert_eq!(bytewords_decode("aeadaolazmjendeoti").unwrap(), data);
+        assert_eq!(
+            bytewords_decode("aeadaolazmjendeotu"),
+            Err(UrError::InvalidBytewords)
+        );
+        assert_eq!(
+            bytewords_decode("aeadaolazmjendeoty"),
+            Err(UrError::InvalidChecksum)
+        );
+    }
+
+    #[test]
+    fn test_pset_ur() {
+        let pset = include_str!("../test_data/pset_details/pset.base64");
+        let pset: elements::pset::PartiallySignedTransaction = pset.parse().unwrap();
+        let bytes = elements::encode::serialize(&pset);
+        let ur = pset_to_ur(&bytes);
+        assert!(ur.starts_with("ur:crypto-pset/"));
+        assert_eq!(pset_from_ur(&ur).unwrap(), bytes);
+        assert_eq!(pset_from_ur(&ur.to_uppercase()).unwrap(), bytes);
+
+        for len in [0, 23, 24, 255, 256, 65536] {
+            let bytes = vec![7u8; len];
+            assert_eq!(pset_from_ur(&pset_to_ur(&bytes)).unwrap(), bytes);
+        }
+
+        assert_eq!(
+            pset_from_ur("ur:crypto-psbt/aeadaolazmjendeoti"),
+            Err(UrError::InvalidType("crypto-pset".to_string()))
+        );
+        assert_eq!(
+            pset_from_ur("ur:crypto-pset/1-3/aeadaolazmjendeoti"),
+            Err(UrError::UnexpectedMultipart)
+        );
+    }
+
+    #[test]
+    fn test_pset_ur_parts() {
+        // A 256 bytes message is a 259 bytes CBOR byte string, split in 3 fragments of 87 bytes
+        let message: Vec<u8> = (0..256u32).map(|i| (i * 7 % 256) as u8).collect();
+        let parts = pset_to_ur_parts(&message, 100);
+        assert_eq!(parts.len(), 3);
+        assert!(parts[0].starts_with("ur:crypto-pset/1-3/"));
+        for part in parts.iter() {
+            assert!(part.len() < 2 * 120 + 30);
+        }
+        assert_eq!(pset_from_ur_parts(&parts).unwrap(), message);
+
+        // In any order and case, repeated parts are fine
+        let shuffled = [&parts[2], &parts[0], &parts[0], &parts[1]].map(|p| p.to_uppercase());
+        assert_eq!(pset_from_ur_parts(&shuffled).unwrap(), message);
+
+        assert_eq!(
+            pset_from_ur_parts(&parts[..2]),
+            Err(UrError::MissingParts(vec![3]))
+        );
+        assert_eq!(pset_from_ur(&parts[0]), Err(UrError::UnexpectedMultipart));
+        let other = pset_to_ur_parts(&message[1..], 100);
+        assert!(matches!(
+            pset_from_ur_parts(&[&parts[0], &parts[1], &other[2]]),
+            Err(UrError::InconsistentParts(_))
+        ));
+
+        // Small messages are single-part
+        let parts = pset_to_ur_parts(&message[..50], 100);
+        assert_eq!(parts, vec![pset_to_ur(&message[..50])]);
+        assert_eq!(pset_from_ur_parts(&parts).unwrap(), &message[..50]);
+
+        // A real PSET
+        let pset = include_str!("../test_data/pset_details/pset.base64");
+        let pset: elements::pset::PartiallySignedTransaction = pset.parse().unwrap();
+        let bytes = elements::encode::serialize(&pset);
+        let parts = pset_to_ur_parts(&bytes, DEFAULT_UR_FRAGMENT_LEN);
+        assert!(parts.len() > 1);
+        assert_eq!(pset_from_ur_parts(&parts).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_fragment_len() {
+        assert_eq!(fragment_len(100, 200), 100);
+        assert_eq!(fragment_len(258, 100), 86);
+        assert_eq!(fragment_len(1000, 200), 200);
+        assert_eq!(fragment_len(1001, 200), 167);
+        assert_eq!(fragment_len(5, 1), 5);
+    }
+}
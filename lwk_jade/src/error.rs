@@ -110,12 +110,30 @@ pub enum Error {
     #[error("Unsupported descriptor variant, only multi or sortedmulti are supported")]
     UnsupportedDescriptorVariant,
 
+    #[error("Jade is not a signer of the descriptor")]
+    NotInDescriptor,
+
+    #[error("Unsupported descriptor, only wpkh, sh(wpkh) and multisig are supported")]
+    UnsupportedAddressDescriptor,
+
+    #[error("The multisig wallet is not registered on Jade")]
+    MultisigNotRegistered,
+
+    #[error(transparent)]
+    Miniscript(#[from] elements_miniscript::Error),
+
+    #[error(transparent)]
+    DescConversion(#[from] elements_miniscript::descriptor::ConversionError),
+
     #[error("Slip 77 master blinding keys must be 32 bytes")]
     Slip77MasterBlindingKeyInvalidSize,
 
     #[error(transparent)]
     HttpReqwest(#[from] reqwest::Error),
 
+    #[error("The operation has been cancelled")]
+    Cancelled,
+
     #[error("{0}")]
     Generic(String),
 }
@@ -114,6 +114,22 @@ pub(crate) fn json_to_cbor(value: &serde_json::Value) -> Result<serde_cbor::Valu
     Ok(serde_cbor::from_slice(&serde_cbor::to_vec(&value)?)?)
 }
 
+/// The number of confirmations needed on Jade to sign `pset` with the signer `my_fingerprint`
+///
+/// Jade shows the whole transaction in a single confirmation, all the inputs belonging to the
+/// signer are then signed without further prompts, regardless of the wallet or account they
+/// belong to. If there are no inputs to sign Jade is not asked to confirm anything.
+#[cfg(any(feature = "sync", feature = "asyncr", test))]
+fn prompt_estimate(pset: &PartiallySignedTransaction, my_fingerprint: Fingerprint) -> u32 {
+    let has_inputs_to_sign = pset.inputs().iter().any(|input| {
+        input
+            .bip32_derivation
+            .values()
+            .any(|(fingerprint, _)| fingerprint == &my_fingerprint)
+    });
+    u32::from(has_inputs_to_sign)
+}
+
 fn create_jade_sign_req(
     pset: &mut PartiallySignedTransaction,
     my_fingerprint: Fingerprint,
@@ -343,7 +359,7 @@ mod test {
 
     use elements::Script;
 
-    use crate::{is_multisig, json_to_cbor};
+    use crate::{is_multisig, json_to_cbor, prompt_estimate};
 
     fn cbor_to_json(value: serde_cbor::Value) -> Result<serde_json::Value, crate::Error> {
         Ok(serde_json::to_value(value)?)
@@ -357,6 +373,38 @@ mod test {
         assert_eq!(json, back);
     }
 
+    #[test]
+    fn test_prompt_estimate() {
+        use elements::bitcoin::bip32::{DerivationPath, Fingerprint};
+        use elements::bitcoin::PublicKey;
+        use elements::pset::{Input, PartiallySignedTransaction};
+        use elements::OutPoint;
+
+        let mine = Fingerprint::from_str("73c5da0a").unwrap();
+        let other = Fingerprint::from_str("deadbeef").unwrap();
+        let key = PublicKey::from_str(
+            "02ebc62c20f1e09e169a88745f60f6dac878c92db5c7ed78c6703d2d0426a01f94",
+        )
+        .unwrap();
+        let input = |fingerprint: Fingerprint, path: &str| {
+            let mut input = Input::from_prevout(OutPoint::default());
+            let path = DerivationPath::from_str(path).unwrap();
+            input.bip32_derivation.insert(key, (fingerprint, path));
+            input
+        };
+
+        let mut pset = PartiallySignedTransaction::new_v2();
+        assert_eq!(prompt_estimate(&pset, mine), 0);
+        pset.add_input(input(other, "m/84h/1h/0h/0/0"));
+        assert_eq!(prompt_estimate(&pset, mine), 0);
+
+        // inputs of different accounts are confirmed together
+        pset.add_input(input(mine, "m/84h/1h/0h/0/0"));
+        pset.add_input(input(mine, "m/84h/1h/1h/0/0"));
+        pset.add_input(input(mine, "m/49h/1h/0h/1/3"));
+        assert_eq!(prompt_estimate(&pset, mine), 1);
+    }
+
     #[test]
     fn test_is_multisig() {
         let multisig = Script::from_str("522102ebc62c20f1e09e169a88745f60f6dac878c92db5c7ed78c6703d2d0426a01f942102c2d59d677122bc292048833003fd5cb19d27d32896b1d0feec654c291f7ede9e52ae").unwrap();
@@ -103,4 +103,23 @@ mod test {
         let result: Value = jade.send(Request::Ping).unwrap();
         assert_eq!(result, text);
     }
+
+    #[test]
+    fn cancelled() {
+        let resp = Response {
+            id: "0".to_string(),
+            result: Some(Value::Bool(true)),
+            error: None,
+        };
+        let mut data = Vec::new();
+        serde_cbor::to_writer(&mut data, &resp).unwrap();
+        let connection = Connection::PartialReadTest { data, status: 0 };
+        let jade = Jade::new(connection, crate::Network::LocaltestLiquid);
+
+        let cancel = lwk_common::CancellationToken::new();
+        jade.set_cancellation_token(cancel.clone()).unwrap();
+        cancel.cancel();
+        let err = jade.send::<Value>(Request::Ping).unwrap_err();
+        assert!(matches!(err, crate::Error::Cancelled));
+    }
 }
@@ -21,8 +21,10 @@ use crate::{
 use connection::Connection;
 use elements::bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
 use elements::pset::PartiallySignedTransaction;
+use elements_miniscript::descriptor::DescriptorType;
 use elements_miniscript::slip77::{self, MasterBlindingKey};
-use lwk_common::Signer;
+use elements_miniscript::{ConfidentialDescriptor, DescriptorPublicKey, ForEachKey};
+use lwk_common::{CancellationToken, Signer};
 use serde::de::DeserializeOwned;
 use serde_bytes::ByteBuf;
 
@@ -45,6 +47,9 @@ pub struct Jade {
 
     /// Cached multisigs details
     multisigs_details: Mutex<Option<Vec<RegisteredMultisigDetails>>>,
+
+    /// Token checked before every request sent to the Jade
+    cancel: Mutex<CancellationToken>,
 }
 
 impl Jade {
@@ -54,9 +59,19 @@ impl Jade {
             network,
             cached_xpubs: Mutex::new(HashMap::new()),
             multisigs_details: Mutex::new(None),
+            cancel: Mutex::new(CancellationToken::new()),
         }
     }
 
+    /// Set the token used to abort the following operations, such as signing or unlocking
+    ///
+    /// Once the token is cancelled, the next request to the Jade returns [`Error::Cancelled`];
+    /// a request already sent is not interrupted.
+    pub fn set_cancellation_token(&self, cancel: CancellationToken) -> Result<()> {
+        *self.cancel.lock()? = cancel;
+        Ok(())
+    }
+
     pub fn generic(&self, method: String, params: serde_cbor::Value) -> Result<serde_cbor::Value> {
         self.send(Request::Generic(GenericMethod { method, params }))
     }
@@ -333,6 +348,69 @@ impl Jade {
         self.get_receive_address(params)
     }
 
+    /// Show on Jade the external address of `descriptor` at `index` and return it
+    ///
+    /// Multisig wallets must be registered with [`Jade::register_multisig()`].
+    pub fn display_address(
+        &self,
+        descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+        index: u32,
+    ) -> Result<String> {
+        let fingerprint = self.fingerprint()?;
+        let external = descriptor
+            .descriptor
+            .clone()
+            .into_single_descriptors()?
+            .into_iter()
+            .next()
+            .ok_or(Error::UnsupportedAddressDescriptor)?;
+        let definite = external.at_derivation_index(index)?;
+
+        // The full path of Jade key and the paths after the xpubs of all the keys
+        let mut full_path: Vec<u32> = vec![];
+        let mut paths: Vec<Vec<u32>> = vec![];
+        definite.for_each_key(|k| {
+            if k.master_fingerprint() == fingerprint {
+                if let Some(path) = k.full_derivation_path() {
+                    full_path = derivation_path_to_vec(&path);
+                }
+            }
+            if let DescriptorPublicKey::XPub(x) = k.as_descriptor_public_key() {
+                paths.push(derivation_path_to_vec(&x.derivation_path));
+            }
+            true
+        });
+        if full_path.is_empty() {
+            return Err(Error::NotInDescriptor);
+        }
+
+        match paths.len() {
+            0 => Err(Error::UnsupportedAddressDescriptor),
+            1 => match definite.desc_type() {
+                DescriptorType::Wpkh => self.get_receive_address_single(Variant::Wpkh, full_path),
+                DescriptorType::ShWpkh => {
+                    self.get_receive_address_single(Variant::ShWpkh, full_path)
+                }
+                _ => Err(Error::UnsupportedAddressDescriptor),
+            },
+            _ => {
+                let witness_script = definite.explicit_script()?;
+                let name = self
+                    .get_cached_registered_multisigs()?
+                    .into_iter()
+                    .find(|details| {
+                        details
+                            .descriptor
+                            .derive_witness_script(false, index)
+                            .map_or(false, |s| s == witness_script)
+                    })
+                    .ok_or(Error::MultisigNotRegistered)?
+                    .multisig_name;
+                self.get_receive_address_multi(&name, paths)
+            }
+        }
+    }
+
     pub fn get_receive_address_multi(&self, name: &str, paths: Vec<Vec<u32>>) -> Result<String> {
         let params = GetReceiveAddressParams {
             network: self.network,
@@ -357,6 +435,10 @@ impl Jade {
         }
         let buf = request.serialize()?;
 
+        if self.cancel.lock()?.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
         let mut conn = self.conn.lock()?;
 
         conn.write_all(&buf)?;
@@ -393,6 +475,23 @@ impl Signer for &Jade {
         Jade::sign(self, pset)
     }
 
+    fn prompt_estimate(
+        &self,
+        pset: &PartiallySignedTransaction,
+    ) -> std::result::Result<u32, Self::Error> {
+        self.unlock()?;
+        Ok(crate::prompt_estimate(pset, Jade::fingerprint(self)?))
+    }
+
+    fn display_address(
+        &self,
+        descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+        index: u32,
+    ) -> std::result::Result<Option<String>, Self::Error> {
+        self.unlock()?;
+        Jade::display_address(self, descriptor, index).map(Some)
+    }
+
     fn derive_xpub(
         &self,
         path: &elements::bitcoin::bip32::DerivationPath,
@@ -428,6 +527,21 @@ impl Signer for Jade {
         Signer::sign(&self, pset)
     }
 
+    fn prompt_estimate(
+        &self,
+        pset: &PartiallySignedTransaction,
+    ) -> std::result::Result<u32, Self::Error> {
+        Signer::prompt_estimate(&self, pset)
+    }
+
+    fn display_address(
+        &self,
+        descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+        index: u32,
+    ) -> std::result::Result<Option<String>, Self::Error> {
+        Signer::display_address(&self, descriptor, index)
+    }
+
     fn derive_xpub(
         &self,
         path: &elements::bitcoin::bip32::DerivationPath,
@@ -2,8 +2,8 @@ use elements::pset::PartiallySignedTransaction;
 use std::collections::HashMap;
 
 use crate::{
-    create_jade_sign_req, derivation_path_to_vec, protocol::GetSignatureParams, script_code_wpkh,
-    sign_liquid_tx::TxInputParams, Error, Jade,
+    create_jade_sign_req, derivation_path_to_vec, prompt_estimate, protocol::GetSignatureParams,
+    script_code_wpkh, sign_liquid_tx::TxInputParams, Error, Jade,
 };
 
 impl Jade {
@@ -11,6 +11,11 @@ impl Jade {
     pub fn sign(&self, pset: &mut PartiallySignedTransaction) -> Result<u32, Error> {
         let my_fingerprint = self.fingerprint()?;
 
+        // All the inputs are signed in a single confirmation, skip it if none is ours
+        if prompt_estimate(pset, my_fingerprint) == 0 {
+            return Ok(0);
+        }
+
         // Singlesig signing don't need this, however, it is simpler to always ask for it and once cached is a
         // fast operation anyway (and in a real scenario you may ask for registered multisigs at the beginning of the session)
         let multisigs_details = self.get_cached_registered_multisigs()?;
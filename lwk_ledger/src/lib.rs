@@ -20,99 +20,165 @@ pub use client::LiquidClient;
 pub use transport::TransportTcp;
 pub use wallet::{AddressType, Version, WalletPolicy, WalletPubKey};
 
+use std::collections::BTreeSet;
+
 use elements_miniscript::confidential::slip77;
 use elements_miniscript::elements::bitcoin::bip32::{DerivationPath, Fingerprint, Xpub};
+use elements_miniscript::elements::bitcoin::ecdsa::Signature;
 use elements_miniscript::elements::pset::PartiallySignedTransaction;
 
 use lwk_common::Signer;
 
+/// A Ledger signer
+///
+/// Only the TCP transport of the Speculos emulator is implemented, physical devices need a USB HID
+/// transport which is not available yet.
 #[derive(Debug)]
 pub struct Ledger {
     /// Ledger Liquid Client
     pub client: LiquidClient<TransportTcp>,
+
+    /// The port of the emulator
+    port: u16,
 }
 
 impl Ledger {
-    pub fn new(port: u16) -> Self {
-        let client = LiquidClient::new(TransportTcp::new(port).expect("TODO"));
-        Self { client }
+    /// Connect to a Ledger emulator listening on `port`
+    pub fn new(port: u16) -> Result<Self, Error> {
+        let transport = TransportTcp::new(port).map_err(error::LiquidClientError::Transport)?;
+        let client = LiquidClient::new(transport);
+        Ok(Self { client, port })
+    }
+
+    /// The port of the emulator this ledger is connected to
+    pub fn port(&self) -> u16 {
+        self.port
     }
 }
 
-pub type Error = error::LiquidClientError<TransportTcp>;
+pub type Error = error::LiquidClientError<std::io::Error>;
+
+/// Returns the account path (`m/purpose'/coin'/account'`) if `path` is a standard single sig
+/// derivation path `m/purpose'/coin'/account'/chain/index`
+fn account_path(path: &DerivationPath) -> Option<DerivationPath> {
+    let path = path.as_ref();
+    if path.len() == 5 && path[..3].iter().all(|c| c.is_hardened()) {
+        Some(DerivationPath::from(&path[..3]))
+    } else {
+        None
+    }
+}
+
+/// The accounts of the signer `master_fp` with inputs to sign in `pset`
+///
+/// The inputs of each account are signed together with a single confirmation on the device, since
+/// the Ledger app signs the inputs of one wallet policy at a time.
+fn signing_accounts(
+    pset: &PartiallySignedTransaction,
+    master_fp: Fingerprint,
+) -> BTreeSet<DerivationPath> {
+    let mut accounts = BTreeSet::new();
+    for input in pset.inputs() {
+        let is_p2wpkh = input
+            .witness_utxo
+            .as_ref()
+            .map(|u| u.script_pubkey.is_v0_p2wpkh())
+            .unwrap_or(false);
+        if !is_p2wpkh {
+            // TODO: add support for other scripts
+            continue;
+        }
+        for (fp, path) in input.bip32_derivation.values() {
+            if fp == &master_fp {
+                if let Some(account) = account_path(path) {
+                    accounts.insert(account);
+                }
+            }
+        }
+    }
+    accounts
+}
+
+/// Add the signatures returned by the device for `account` to the PSET inputs, returning the number
+/// of signatures added
+fn add_signatures(
+    pset: &mut PartiallySignedTransaction,
+    master_fp: Fingerprint,
+    account: &DerivationPath,
+    sigs: Vec<(usize, Signature)>,
+) -> Result<u32, Error> {
+    let mut n_sigs = 0;
+    for (index, sig) in sigs {
+        let input = pset.inputs_mut().get_mut(index).ok_or(Error::InvalidPsbt)?;
+        let public_key = input
+            .bip32_derivation
+            .iter()
+            .find(|(_, (fp, path))| {
+                fp == &master_fp && account_path(path).as_ref() == Some(account)
+            })
+            .map(|(public_key, _)| *public_key)
+            .ok_or(Error::InvalidPsbt)?;
+        if input
+            .partial_sigs
+            .insert(public_key, sig.to_vec())
+            .is_none()
+        {
+            n_sigs += 1;
+        }
+    }
+    Ok(n_sigs)
+}
 
 impl Signer for &Ledger {
     type Error = crate::Error;
 
     fn sign(&self, pset: &mut PartiallySignedTransaction) -> std::result::Result<u32, Self::Error> {
-        let mut wallets = vec![];
-        let mut n_sigs = 0;
         let master_fp = self.fingerprint()?;
 
-        // Figure out which wallets are signing
-        for input in pset.inputs() {
-            let script_pubkey = &input.witness_utxo.as_ref().expect("FIXME").script_pubkey;
-            if !script_pubkey.is_v0_p2wpkh() {
-                // TODO: add support for other scripts
-                continue;
-            }
-            for (_pubkey, (fp, path)) in input.bip32_derivation.iter() {
-                if fp == &master_fp {
-                    // TODO: check path
-                    // path has len 3
-                    // path has all hardened
-                    // path has purpose matching address type
-                    // path has correct coin type
-
-                    // Do we care about the descriptor blinding key here?
-                    let name = "todo".to_string();
-                    let version = Version::V1;
-                    let desc = "wpkh(@0)".to_string();
-                    // TODO: cache xpubs
-                    let xpub = self.client.get_extended_pubkey(path, false).expect("FIXME");
-                    let mut key = WalletPubKey::from(((*fp, path.clone()), xpub));
-                    key.multipath = Some("/**".to_string());
-                    let keys = vec![key];
-                    let wallet_policy = WalletPolicy::new(name, version, desc, keys);
-                    wallets.push(wallet_policy);
-                }
-            }
-        }
-
         // For each wallet, sign
-        for wallet_policy in wallets {
-            let partial_sigs = self
-                .client
-                .sign_psbt(
-                    pset,
-                    &wallet_policy,
-                    None, // hmac
-                )
-                .expect("FIXME");
-            n_sigs += partial_sigs.len();
-
-            // Add sigs to pset
-            // TODO
+        let mut n_sigs = 0;
+        for account in signing_accounts(pset, master_fp) {
+            // Do we care about the descriptor blinding key here?
+            let name = "".to_string();
+            let version = Version::V1;
+            let desc = "wpkh(@0)".to_string();
+            let xpub = self.client.get_extended_pubkey(&account, false)?;
+            let mut key = WalletPubKey::from(((master_fp, account.clone()), xpub));
+            key.multipath = Some("/**".to_string());
+            let wallet_policy = WalletPolicy::new(name, version, desc, vec![key]);
+
+            let partial_sigs = self.client.sign_psbt(
+                pset,
+                &wallet_policy,
+                None, // hmac
+            )?;
+
+            n_sigs += add_signatures(pset, master_fp, &account, partial_sigs)?;
         }
 
-        Ok(n_sigs as u32)
+        Ok(n_sigs)
+    }
+
+    fn prompt_estimate(
+        &self,
+        pset: &PartiallySignedTransaction,
+    ) -> std::result::Result<u32, Self::Error> {
+        let master_fp = self.fingerprint()?;
+        Ok(signing_accounts(pset, master_fp).len() as u32)
     }
 
     fn derive_xpub(&self, path: &DerivationPath) -> std::result::Result<Xpub, Self::Error> {
-        let r = self.client.get_extended_pubkey(path, false).expect("FIXME");
-        Ok(r)
+        self.client.get_extended_pubkey(path, false)
     }
 
     fn slip77_master_blinding_key(
         &self,
     ) -> std::result::Result<slip77::MasterBlindingKey, Self::Error> {
-        let r = self.client.get_master_blinding_key().expect("FIXME");
-        Ok(r)
+        self.client.get_master_blinding_key()
     }
 
     fn fingerprint(&self) -> std::result::Result<Fingerprint, Self::Error> {
-        let r = self.client.get_master_fingerprint().expect("FIXME");
-        Ok(r)
+        self.client.get_master_fingerprint()
     }
 }
 
@@ -123,6 +189,13 @@ impl Signer for Ledger {
         Signer::sign(&self, pset)
     }
 
+    fn prompt_estimate(
+        &self,
+        pset: &PartiallySignedTransaction,
+    ) -> std::result::Result<u32, Self::Error> {
+        Signer::prompt_estimate(&self, pset)
+    }
+
     fn derive_xpub(&self, path: &DerivationPath) -> std::result::Result<Xpub, Self::Error> {
         Signer::derive_xpub(&self, path)
     }
@@ -137,3 +210,85 @@ impl Signer for Ledger {
         Signer::fingerprint(&self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_add_signatures() {
+        let pset_b64 = include_str!("../tests/data/pset_ledger.base64");
+        let mut pset: PartiallySignedTransaction = pset_b64.parse().unwrap();
+        let (public_key, (master_fp, path)) = pset.inputs()[0]
+            .bip32_derivation
+            .iter()
+            .next()
+            .map(|(k, v)| (*k, v.clone()))
+            .unwrap();
+        let account = account_path(&path).unwrap();
+        // From the Liquid Ledger App test vectors
+        let sig = Signature::from_str("3044022071965f8315a264773d8e635fb5bb8dfdb425b849b7aaafa8f1dcf1356e87947a02202eae7f9bdb1f00af3d1662a10b8efc82f9e7ecb1fc4f76a0b7905dab4fc6358801").unwrap();
+
+        let n = add_signatures(&mut pset, master_fp, &account, vec![(0, sig)]).unwrap();
+        assert_eq!(n, 1);
+        let input = &pset.inputs()[0];
+        assert_eq!(input.partial_sigs.get(&public_key), Some(&sig.to_vec()));
+
+        // Adding the same signature again doesn't count it twice
+        let n = add_signatures(&mut pset, master_fp, &account, vec![(0, sig)]).unwrap();
+        assert_eq!(n, 0);
+
+        // Signatures for inputs that are missing or not derived from the account are rejected
+        let n_inputs = pset.inputs().len();
+        let err = add_signatures(&mut pset, master_fp, &account, vec![(n_inputs, sig)]);
+        assert!(matches!(err, Err(Error::InvalidPsbt)));
+        let other_account = DerivationPath::from_str("m/84h/1h/1h").unwrap();
+        let err = add_signatures(&mut pset, master_fp, &other_account, vec![(0, sig)]);
+        assert!(matches!(err, Err(Error::InvalidPsbt)));
+    }
+
+    #[test]
+    fn test_signing_accounts() {
+        use elements_miniscript::elements::bitcoin::PublicKey;
+        use elements_miniscript::elements::hashes::Hash;
+        use elements_miniscript::elements::pset::Input;
+        use elements_miniscript::elements::{OutPoint, Script, ScriptHash, TxOut, WPubkeyHash};
+
+        let mine = Fingerprint::from_str("73c5da0a").unwrap();
+        let other = Fingerprint::from_str("deadbeef").unwrap();
+        let key = PublicKey::from_str(
+            "02ebc62c20f1e09e169a88745f60f6dac878c92db5c7ed78c6703d2d0426a01f94",
+        )
+        .unwrap();
+        let input = |fingerprint: Fingerprint, path: &str, script_pubkey: Script| {
+            let mut input = Input::from_prevout(OutPoint::default());
+            let path = DerivationPath::from_str(path).unwrap();
+            input.bip32_derivation.insert(key, (fingerprint, path));
+            input.witness_utxo = Some(TxOut {
+                script_pubkey,
+                ..Default::default()
+            });
+            input
+        };
+        let wpkh = Script::new_v0_wpkh(&WPubkeyHash::hash(&key.to_bytes()));
+        let sh = Script::new_p2sh(&ScriptHash::hash(wpkh.as_bytes()));
+
+        let mut pset = PartiallySignedTransaction::new_v2();
+        assert!(signing_accounts(&pset, mine).is_empty());
+        pset.add_input(input(other, "m/84h/1h/0h/0/0", wpkh.clone()));
+        pset.add_input(input(mine, "m/49h/1h/0h/0/0", sh));
+        assert!(signing_accounts(&pset, mine).is_empty());
+
+        // Inputs of the same account are signed together, one confirmation per account
+        pset.add_input(input(mine, "m/84h/1h/0h/0/0", wpkh.clone()));
+        pset.add_input(input(mine, "m/84h/1h/0h/1/3", wpkh.clone()));
+        pset.add_input(input(mine, "m/84h/1h/1h/0/0", wpkh));
+        let accounts = signing_accounts(&pset, mine);
+        let expected: BTreeSet<DerivationPath> = ["m/84h/1h/0h", "m/84h/1h/1h"]
+            .iter()
+            .map(|p| DerivationPath::from_str(p).unwrap())
+            .collect();
+        assert_eq!(accounts, expected);
+    }
+}
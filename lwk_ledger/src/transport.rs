@@ -1,8 +1,7 @@
 /// Adapted from
 /// https://github.com/LedgerHQ/app-bitcoin-new/blob/develop/bitcoin_client_rs/examples/ledger_hwi
 use std::convert::TryFrom;
-use std::error::Error;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use std::net::TcpStream;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Mutex;
@@ -20,7 +19,7 @@ pub struct TransportTcp {
 }
 
 impl TransportTcp {
-    pub fn new(port: u16) -> Result<Self, Box<dyn Error>> {
+    pub fn new(port: u16) -> Result<Self, io::Error> {
         let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), port);
         let stream = TcpStream::connect(addr)?;
         Ok(Self {
@@ -30,7 +29,7 @@ impl TransportTcp {
 }
 
 impl Transport for TransportTcp {
-    type Error = Box<dyn Error>;
+    type Error = io::Error;
     fn exchange(&self, command: &APDUCommand) -> Result<(StatusWord, Vec<u8>), Self::Error> {
         if let Ok(mut stream) = self.connection.lock() {
             let command_bytes = command.encode();
@@ -43,18 +42,23 @@ impl Transport for TransportTcp {
             let mut buff = [0u8; 4];
             let len = match stream.read(&mut buff)? {
                 4 => u32::from_be_bytes(buff),
-                _ => return Err("Invalid Length".into()),
+                _ => return Err(invalid_data("Invalid Length")),
             };
 
             let mut resp = vec![0u8; len as usize + 2];
             stream.read_exact(&mut resp)?;
-            let answer = APDUAnswer::from_answer(resp).map_err(|_| "Invalid Answer")?;
+            let answer =
+                APDUAnswer::from_answer(resp).map_err(|_| invalid_data("Invalid Answer"))?;
             Ok((
                 StatusWord::try_from(answer.retcode()).unwrap_or(StatusWord::Unknown),
                 answer.data().to_vec(),
             ))
         } else {
-            Err("unable to get lock".into())
+            Err(io::Error::new(io::ErrorKind::Other, "unable to get lock"))
         }
     }
 }
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
@@ -41,6 +41,11 @@ pub struct WalletLoad {
 
     /// The name given to the wallet, will be needed for calls related to the wallet
     pub name: String,
+
+    /// The number of consecutive unused addresses after which scans stop looking for more, if
+    /// missing 20 is used
+    #[serde(default)]
+    pub gap_limit: Option<u32>,
 }
 
 /// Unload the wallet identified by the given name
@@ -56,11 +61,19 @@ pub struct SignerLoadSoftware {
     /// The name of the signer, will be needed to reference it in other calls
     pub name: String,
 
-    /// The mnemonic (12 or 24 words)
+    /// The mnemonic (12 or 24 words), empty if `mnemonic_file` is specified
+    #[serde(default)]
     pub mnemonic: String,
 
     /// Whether to persist the software signer
     pub persist: bool,
+
+    /// Path of a file containing the mnemonic, alternative to `mnemonic`
+    ///
+    /// If the signer is persisted only the path is saved and the file is read again at the
+    /// following restarts, so that the mnemonic is not copied in the server state.
+    #[serde(default)]
+    pub mnemonic_file: Option<String>,
 }
 
 /// Load a signer in the server
@@ -76,6 +89,16 @@ pub struct SignerLoadJade {
     pub emulator: Option<SocketAddr>,
 }
 
+/// Load a Ledger signer in the server
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SignerLoadLedger {
+    /// The name of the signer, will be needed to reference it in other calls
+    pub name: String,
+
+    /// The local port of the Ledger emulator to connect to, physical devices are not supported yet
+    pub emulator: u16,
+}
+
 /// Load a signer in the server
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct SignerLoadExternal {
@@ -86,6 +109,25 @@ pub struct SignerLoadExternal {
     pub fingerprint: String,
 }
 
+/// Load a signer living in another server
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SignerLoadRemote {
+    /// The name of the signer, will be needed to reference it in other calls
+    pub name: String,
+
+    /// The url of the remote server, for example `http://127.0.0.1:32111`
+    pub url: String,
+
+    /// The name of the signer in the remote server
+    pub remote_name: String,
+
+    /// Token sent as bearer authorization to the remote server
+    ///
+    /// The token is stored in plaintext in the server state file, which on unix is readable only
+    /// by its owner.
+    pub token: Option<String>,
+}
+
 /// Get the signer details
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct SignerDetails {
@@ -117,6 +159,62 @@ pub struct WalletAddress {
 
     /// Return a image QR code encoded as uri with the given pixel per module
     pub with_uri_qr: Option<u8>,
+
+    /// If the address at `index` already received funds, return the first following unused one
+    #[serde(default)]
+    pub skip_used: bool,
+
+    /// Random string chosen by the client, covered by the signature of the response so that it
+    /// can't be replayed
+    #[serde(default)]
+    pub nonce: Option<String>,
+}
+
+/// Request to list the derived addresses of a wallet with their usage
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletAddresses {
+    /// The wallet name
+    pub name: String,
+
+    /// List the change addresses instead of the receiving ones
+    #[serde(default)]
+    pub change: bool,
+
+    /// The derivation index of the first address returned
+    #[serde(default)]
+    pub offset: u32,
+
+    /// Maximum number of addresses returned, all the derived ones if missing
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
+/// Request to reserve receiving addresses
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletAddressReserve {
+    /// The wallet name
+    pub name: String,
+
+    /// The number of consecutive addresses to reserve
+    ///
+    /// The reserved addresses and the next address returned by `wallet_address` must be within
+    /// the gap limit of the wallet from its last used address, so that the wallet scan sees the
+    /// funds they receive. Reservations are released once an address following them is used.
+    pub count: u32,
+
+    /// The derivation index of the first reserved address, if missing the first unused index
+    /// following the already reserved addresses is used
+    pub index: Option<u32>,
+}
+
+/// Request to scan the common derivation schemes of a mnemonic, to find an old wallet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletRecoveryScan {
+    /// The mnemonic of the wallet to recover
+    pub mnemonic: String,
+
+    /// The last account scanned for every script type, if missing 5 is used
+    pub max_account: Option<u32>,
 }
 
 /// The balance of a wallet
@@ -127,6 +225,18 @@ pub struct WalletBalance {
 
     /// Replace asset ids with tickers when possible
     pub with_tickers: bool,
+
+    /// Random string chosen by the client, covered by the signature of the response so that it
+    /// can't be replayed
+    #[serde(default)]
+    pub nonce: Option<String>,
+}
+
+/// Aggregate figures of the wallet activity
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletSummary {
+    /// The wallet name
+    pub name: String,
 }
 
 /// Send a transaction from a wallet
@@ -140,6 +250,29 @@ pub struct WalletSendMany {
 
     /// Optional fee rate in sat/vb
     pub fee_rate: Option<f32>,
+
+    /// Optional confirmation target in blocks, the fee rate is estimated by the server
+    ///
+    /// Ignored if `fee_rate` is specified.
+    #[serde(default)]
+    pub fee_target: Option<u16>,
+
+    /// Optional locktime, either "height:<block height>" or "time:<unix timestamp>"
+    #[serde(default)]
+    pub locktime: Option<String>,
+
+    /// Optional name of the draft of a previous version of this transaction
+    ///
+    /// The change is paid to the same addresses of the draft, so that replacing a transaction
+    /// does not derive new addresses.
+    #[serde(default)]
+    pub draft: Option<String>,
+
+    /// Build the transaction even if an identical payment was built recently
+    ///
+    /// Only relevant if the server is started with a duplicate payment window.
+    #[serde(default)]
+    pub force: bool,
 }
 
 ///  An addressee which has yet to be validated
@@ -150,7 +283,8 @@ pub struct UnvalidatedAddressee {
 
     /// The address to send to
     ///
-    /// If "burn", the output will be burned
+    /// If "burn", the output will be burned.
+    /// If "contact:<name>", the address of the contact in the address book.
     pub address: String,
 
     /// The asset to send
@@ -166,10 +300,22 @@ pub struct WalletDrain {
     pub name: String,
 
     /// Recipient addresse
+    ///
+    /// If "contact:<name>", the address of the contact in the address book.
     pub address: String,
 
     /// Optional fee rate in sat/vb
     pub fee_rate: Option<f32>,
+
+    /// Assets to drain to the same address, in addition to the policy asset
+    #[serde(default)]
+    pub assets: Vec<String>,
+
+    /// Build the transaction even if an identical payment was built recently
+    ///
+    /// Only relevant if the server is started with a duplicate payment window.
+    #[serde(default)]
+    pub force: bool,
 }
 
 /// A request containing information to create a single signature descriptor wallet
@@ -199,6 +345,11 @@ pub struct WalletMultisigDescriptor {
 
     /// The partecipants in the multisig wallet xpubs with key origin
     pub keyorigin_xpubs: Vec<String>,
+
+    /// The names of loaded signers partecipating in the multisig wallet, their bip87 xpubs
+    /// precede `keyorigin_xpubs`
+    #[serde(default)]
+    pub signers: Vec<String>,
 }
 
 /// Request to register a multisig wallet on a signer
@@ -229,6 +380,65 @@ pub struct SignerSign {
 
     /// The PSET in base64
     pub pset: String,
+
+    /// If set, refuse to sign if the outputs claimed as change by the signer
+    /// do not belong to the wallet with this name
+    pub wallet: Option<String>,
+}
+
+/// A request to start a session signing a PSET with an external signer
+///
+/// Signers like MPC coordinators sign only digests, asynchronously. The session returns the
+/// digests to sign, the signatures are added later with [`SignerSessionComplete`].
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SignerSessionStart {
+    /// The signer name
+    pub name: String,
+
+    /// The PSET in base64
+    pub pset: String,
+}
+
+/// A request to add the signatures made by the external signer to the PSET of a session
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SignerSessionComplete {
+    /// The session identifier
+    pub id: String,
+
+    /// The signatures of the digests requested by the session
+    pub signatures: Vec<SessionSignature>,
+}
+
+/// A signature made by an external signer
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SessionSignature {
+    /// The index of the input
+    pub index: u32,
+
+    /// The public key which signed, in hex
+    pub public_key: String,
+
+    /// The DER encoded ECDSA signature in hex, without the sighash byte
+    pub signature: String,
+}
+
+/// Request to record a signing session waiting for the signatures of an external signer
+///
+/// `signer_session_start` records the sessions, this request is accepted only to restore them at
+/// startup
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SignerSessionRecord {
+    /// The session identifier
+    pub id: String,
+
+    /// The fingerprint of the signer whose signatures are requested
+    pub fingerprint: String,
+
+    /// The PSET to sign in base64
+    pub pset: String,
+
+    /// When the session was started, as unix timestamp
+    pub timestamp: u64,
 }
 
 /// Request to broadcast a transaction
@@ -274,6 +484,10 @@ pub struct WalletIssue {
 
     /// The optional fee rate
     pub fee_rate: Option<f32>,
+
+    /// Optional locktime, either "height:<block height>" or "time:<unix timestamp>"
+    #[serde(default)]
+    pub locktime: Option<String>,
 }
 
 /// Request to do a reissuance
@@ -311,6 +525,54 @@ pub struct WalletBurn {
     pub fee_rate: Option<f32>,
 }
 
+/// Request to peg-out L-BTC to the mainchain
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletPegout {
+    /// The wallet name
+    pub name: String,
+
+    /// The mainchain address receiving the bitcoin
+    pub mainchain_address: String,
+
+    /// The number of satoshi to peg-out
+    pub satoshi: u64,
+
+    /// The optional fee rate
+    pub fee_rate: Option<f32>,
+}
+
+/// Request a mainchain address to peg-in bitcoin to a wallet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletPeginAddress {
+    /// The wallet name
+    pub name: String,
+
+    /// The script of the federation in hex, as returned by `getsidechaininfo` of elementsd
+    pub fedpegscript: String,
+
+    /// The index of the wallet address committed by the peg-in address, if missing the last unused is used
+    pub index: Option<u32>,
+}
+
+/// Request to claim the bitcoin sent to a peg-in address of a wallet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletClaimPegin {
+    /// The wallet name
+    pub name: String,
+
+    /// The script of the federation in hex, as returned by `getsidechaininfo` of elementsd
+    pub fedpegscript: String,
+
+    /// The mainchain transaction sending to the peg-in address in hex
+    pub mainchain_tx: String,
+
+    /// The merkle proof of the mainchain transaction in hex, as returned by `gettxoutproof` of bitcoind
+    pub txout_proof: String,
+
+    /// The optional fee rate
+    pub fee_rate: Option<f32>,
+}
+
 /// A request creating a contract in the JSON format expected by the issue call
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct AssetContract {
@@ -354,6 +616,11 @@ pub struct WalletPsetDetails {
 
     /// Replace asset ids with tickers when possible
     pub with_tickers: bool,
+
+    /// Warn if the fee is higher than these basis points (hundredths of a percent) of the L-BTC
+    /// sent
+    #[serde(default)]
+    pub max_fee_basis_points: Option<u32>,
 }
 
 /// Request to get the wallet unspet transaction Outputs
@@ -363,6 +630,78 @@ pub struct WalletUtxos {
     pub name: String,
 }
 
+/// Request to export the wallet UTXOs in formats accepted by elements core
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletUtxosExport {
+    /// The wallet name
+    pub name: String,
+}
+
+/// Request to lock or unlock a wallet UTXO
+///
+/// Locked UTXOs are not selected when creating transactions until they are unlocked.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletUtxoLock {
+    /// The wallet name
+    pub name: String,
+
+    /// Transaction ID of the UTXO
+    pub txid: String,
+
+    /// Output index of the UTXO
+    pub vout: u32,
+}
+
+/// Request to set or remove the label of a transaction, address or output of a wallet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletSetLabel {
+    /// The wallet name
+    pub name: String,
+
+    /// The BIP329 type of the labelled item: "tx", "addr" or "output"
+    #[serde(rename = "type")]
+    pub type_: String,
+
+    /// The BIP329 reference of the labelled item: txid, address or "txid:vout"
+    #[serde(rename = "ref")]
+    pub ref_: String,
+
+    /// The label, if not set the existing label is removed
+    pub label: Option<String>,
+}
+
+/// Request to get the label of a transaction, address or output of a wallet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletGetLabel {
+    /// The wallet name
+    pub name: String,
+
+    /// The BIP329 type of the labelled item: "tx", "addr" or "output"
+    #[serde(rename = "type")]
+    pub type_: String,
+
+    /// The BIP329 reference of the labelled item: txid, address or "txid:vout"
+    #[serde(rename = "ref")]
+    pub ref_: String,
+}
+
+/// Request to export wallet labels
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletExportLabels {
+    /// The wallet name
+    pub name: String,
+}
+
+/// Request to import wallet labels
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletImportLabels {
+    /// The wallet name
+    pub name: String,
+
+    /// The labels in the BIP329 JSON lines format
+    pub labels: String,
+}
+
 /// Request to get the wallet transactions
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct WalletTxs {
@@ -371,6 +710,42 @@ pub struct WalletTxs {
 
     /// Replace asset ids with tickers when possible
     pub with_tickers: bool,
+
+    /// Number of matching transactions to skip, unconfirmed transactions come first and then
+    /// the most recent ones
+    #[serde(default)]
+    pub offset: usize,
+
+    /// Maximum number of transactions returned, all the matching ones if missing
+    #[serde(default)]
+    pub limit: Option<usize>,
+
+    /// Only transactions changing the wallet balance of this asset id
+    #[serde(default)]
+    pub asset: Option<String>,
+
+    /// Only transactions confirmed at this height or above, unconfirmed transactions included
+    #[serde(default)]
+    pub min_height: Option<u32>,
+
+    /// Only transactions confirmed at this height or below, unconfirmed transactions excluded
+    #[serde(default)]
+    pub max_height: Option<u32>,
+
+    /// Only transactions moving funds in this direction
+    #[serde(default)]
+    pub direction: Option<TxDirection>,
+}
+
+/// Whether a transaction moves funds into or out of the wallet
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum TxDirection {
+    /// The transaction does not decrease the wallet balance of any asset
+    Incoming,
+
+    /// The transaction decreases the wallet balance of at least one asset
+    Outgoing,
 }
 
 /// Request to get a transaction
@@ -460,6 +835,125 @@ pub struct WalletSetAddrMemo {
     pub memo: String,
 }
 
+/// Request to record that a wallet address has been verified on a signer device
+///
+/// `wallet_address` records it when the address is displayed on a signer, this request is
+/// accepted only to restore the record at startup
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletSetAddrVerified {
+    /// The wallet name
+    pub name: String,
+
+    /// The derivation index of the verified address
+    pub index: u32,
+
+    /// The name of the signer which displayed the address
+    pub signer: String,
+}
+
+/// Request to record a payment, to refuse building an identical one within the duplicate payment
+/// window
+///
+/// `wallet_send_many`, `wallet_drain` and `wallet_broadcast` record the payments, this request is
+/// accepted only to restore the records at startup
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PaymentRecord {
+    /// The recipients of the payment
+    pub recipients: Vec<PaymentRecordRecipient>,
+
+    /// When the payment was built or broadcast, as unix timestamp
+    pub timestamp: u64,
+}
+
+/// A recipient of a [`PaymentRecord`]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PaymentRecordRecipient {
+    /// The hex script pubkey paid, or "contact:<name>" for a contact of the address book
+    pub recipient: String,
+
+    /// The amount paid in satoshi
+    pub satoshi: u64,
+
+    /// The asset paid
+    pub asset: String,
+}
+
+/// Request to add a contact to the address book
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ContactAdd {
+    /// The contact name
+    pub name: String,
+
+    /// The address of the contact, used for every payment
+    pub address: Option<String>,
+
+    /// The CT descriptor of the contact, a fresh address is derived for every broadcast payment
+    pub descriptor: Option<String>,
+
+    /// The derivation index of the address used for the next payment to a descriptor contact,
+    /// if missing 0 is used
+    pub index: Option<u32>,
+}
+
+/// Request to remove a contact from the address book
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ContactRemove {
+    /// The contact name
+    pub name: String,
+}
+
+/// Request to save an in-progress PSET as a named draft
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DraftSave {
+    /// The draft name
+    pub name: String,
+
+    /// The PSET in base64
+    pub pset: String,
+
+    /// The name of the wallet involved in the transaction
+    pub wallet: String,
+
+    /// Who is saving the draft
+    pub creator: Option<String>,
+
+    /// Replace the draft with the same name, if any
+    pub replace: bool,
+
+    /// The time of the save in seconds since the unix epoch, if missing the current time is used
+    pub timestamp: Option<u64>,
+}
+
+/// Request to list the saved drafts
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DraftList {
+    /// List only the drafts involving this wallet
+    pub wallet: Option<String>,
+}
+
+/// Request to load or delete a draft
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DraftLoad {
+    /// The draft name
+    pub name: String,
+}
+
+/// Request to wait for notifications about the loaded wallets
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Subscribe {
+    /// Receive only the notifications about this wallet and new blocks
+    pub wallet: Option<String>,
+
+    /// Receive only the notifications with an identifier greater than this, usually the
+    /// `last_id` of the previous response
+    #[serde(default)]
+    pub since: u64,
+
+    /// Seconds to wait for a notification if there are none, at most 60
+    #[serde(default)]
+    pub timeout: u64,
+}
+
 #[cfg(test)]
 mod test {
     use schemars::schema_for;
@@ -470,8 +964,41 @@ mod test {
     fn test_json_schema() {
         let schema = schema_for!(WalletLoad);
         assert_eq!(
-            r#"{"$schema":"http://json-schema.org/draft-07/schema#","title":"WalletLoad","description":"Request to load a wallet in the server, returning [`response::Wallet`]","type":"object","required":["descriptor","name"],"properties":{"descriptor":{"description":"The read-only descriptor describing the wallet outputs","type":"string"},"name":{"description":"The name given to the wallet, will be needed for calls related to the wallet","type":"string"}}}"#,
+            r#"{"$schema":"http://json-schema.org/draft-07/schema#","title":"WalletLoad","description":"Request to load a wallet in the server, returning [`response::Wallet`]","type":"object","required":["descriptor","name"],"properties":{"descriptor":{"description":"The read-only descriptor describing the wallet outputs","type":"string"},"gap_limit":{"description":"The number of consecutive unused addresses after which scans stop looking for more, if missing 20 is used","default":null,"type":["integer","null"],"format":"uint32","minimum":0.0},"name":{"description":"The name given to the wallet, will be needed for calls related to the wallet","type":"string"}}}"#,
             serde_json::to_string(&schema).unwrap()
         );
     }
 }
+
+/// Request to send a PSET to a cosigner with a Nostr encrypted direct message
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PsetSendNostr {
+    /// The name of the software signer whose Nostr identity sends the PSET
+    pub signer: String,
+
+    /// The Nostr public key of the cosigner receiving the PSET, in hex
+    pub recipient: String,
+
+    /// The PSET in base64
+    pub pset: String,
+
+    /// The relays the message is published to, e.g. "wss://relay.damus.io"
+    pub relays: Vec<String>,
+}
+
+/// Request to get the PSETs received with Nostr encrypted direct messages
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PsetInbox {
+    /// The name of the software signer whose Nostr identity receives the PSETs
+    pub signer: String,
+
+    /// The Nostr public keys of the cosigners, in hex, PSETs from other senders are ignored
+    pub senders: Vec<String>,
+
+    /// The relays the messages are fetched from
+    pub relays: Vec<String>,
+
+    /// Get only the PSETs sent after this unix timestamp
+    #[serde(default)]
+    pub since: u64,
+}
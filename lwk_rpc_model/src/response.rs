@@ -5,12 +5,33 @@ use crate::request;
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 /// An empty response.
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Empty {}
 
+/// The label of a wallet item
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletLabel {
+    /// The label, if set
+    pub label: Option<String>,
+}
+
+/// Wallet labels export
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletExportLabels {
+    /// The labels in the BIP329 JSON lines format
+    pub labels: String,
+}
+
+/// Wallet labels import
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletImportLabels {
+    /// The number of labels imported
+    pub imported: usize,
+}
+
 /// Server version response
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Version {
@@ -19,6 +40,10 @@ pub struct Version {
 
     /// The server network
     pub network: String,
+
+    /// The public key of the server identity, if the server signs responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
 }
 
 /// Response for generate signer
@@ -52,6 +77,32 @@ pub struct WalletList {
     pub wallets: Vec<Wallet>,
 }
 
+/// The synchronization status of a wallet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletSyncInfo {
+    /// The wallet name
+    pub name: String,
+
+    /// Height of the last block seen by the wallet
+    pub tip_height: u32,
+
+    /// Hash of the last block seen by the wallet
+    pub tip_hash: String,
+
+    /// Timestamp of the last block seen by the wallet, if known
+    pub tip_timestamp: Option<u32>,
+
+    /// Unix time of the last successful scan, none if the wallet has not been scanned yet
+    pub last_sync: Option<u64>,
+}
+
+/// Response for wallet sync status call
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletSyncStatus {
+    /// The synchronization status of the wallets loaded in the server
+    pub wallets: Vec<WalletSyncInfo>,
+}
+
 /// Response for unload wallet call
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct WalletUnload {
@@ -95,6 +146,101 @@ pub struct WalletAddress {
     /// QR code image encoded as uri
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uri_qr: Option<String>,
+
+    /// The blinding public key of the address, in hex
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blinding_pubkey: Option<String>,
+
+    /// Whether the address has been displayed and verified on a signer device
+    #[serde(default)]
+    pub verified: bool,
+
+    /// The name of the signer which verified the address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_by: Option<String>,
+
+    /// Signature of the server identity over the rest of the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Reserved addresses response
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletAddressReserve {
+    /// The reserved addresses, they are not returned by `wallet_address` without an index
+    pub addresses: Vec<WalletAddress>,
+}
+
+/// A derived wallet address with its usage
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AddressDetails {
+    /// The address
+    pub address: String,
+
+    /// The derivation index of the address
+    pub index: u32,
+
+    /// Whether the address received funds
+    pub used: bool,
+
+    /// The number of wallet transactions paying to the address
+    pub tx_count: u32,
+
+    /// The amount of the unspent outputs of the address, by asset
+    pub balance: HashMap<String, u64>,
+
+    /// Memo
+    pub memo: String,
+}
+
+/// Derived addresses response
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletAddresses {
+    /// The derived addresses sorted by derivation index, up to the first unused one following
+    /// all the used ones
+    pub addresses: Vec<AddressDetails>,
+}
+
+/// A mainchain address to peg-in bitcoin to a wallet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletPeginAddress {
+    /// The mainchain address to send bitcoin to
+    pub mainchain_address: String,
+
+    /// The wallet script committed by the address in hex, which signs the claim
+    pub claim_script: String,
+
+    /// The index of the wallet address of the claim script
+    pub index: u32,
+}
+
+/// The result of scanning a derivation scheme
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RecoveryScheme {
+    /// The derivation path of the account xpub
+    pub path: String,
+
+    /// The script type, "wpkh" or "shwpkh"
+    pub kind: String,
+
+    /// The CT descriptor with deterministic slip77 blinding key, to load the wallet if found
+    pub descriptor: String,
+
+    /// Whether the wallet has any transaction
+    pub has_history: bool,
+
+    /// The number of transactions of the wallet
+    pub tx_count: usize,
+
+    /// The balance of every asset in the wallet
+    pub balance: HashMap<String, u64>,
+}
+
+/// Recovery scan response
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletRecoveryScan {
+    /// The scanned derivation schemes
+    pub schemes: Vec<RecoveryScheme>,
 }
 
 /// Balance respone
@@ -102,6 +248,64 @@ pub struct WalletAddress {
 pub struct WalletBalance {
     /// A map of the balance of every asset in the wallet
     pub balance: HashMap<String, i64>,
+
+    /// The amount of the outputs created and not spent by confirmed transactions, including
+    /// `unconfirmed_outgoing`
+    #[serde(default)]
+    pub confirmed: HashMap<String, i64>,
+
+    /// The part of `balance` received by unconfirmed transactions
+    #[serde(default)]
+    pub unconfirmed_incoming: HashMap<String, i64>,
+
+    /// The amount spent by unconfirmed transactions, already subtracted from `balance`
+    #[serde(default)]
+    pub unconfirmed_outgoing: HashMap<String, i64>,
+
+    /// The reissuance tokens included in the balance, mapped to the asset id they can reissue
+    #[serde(default)]
+    pub reissuance_tokens: HashMap<String, String>,
+
+    /// For the assets in `balance` with registry info cached in the wallet, whether the info is
+    /// verified against the issuance transaction. Unverified info could spoof the ticker of
+    /// another asset.
+    #[serde(default)]
+    pub verified: HashMap<String, bool>,
+
+    /// Signature of the server identity over the rest of the response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// Wallet activity summary response
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletSummary {
+    /// The number of transactions of the wallet
+    pub txs: usize,
+
+    /// The number of unspent outputs of the wallet, including the locked ones
+    pub utxos: usize,
+
+    /// The number of wallet addresses that received funds
+    pub used_addresses: usize,
+
+    /// The block time of the first confirmed transaction
+    pub first_activity: Option<u32>,
+
+    /// The block time of the last confirmed transaction
+    pub last_activity: Option<u32>,
+
+    /// The total amount received for every asset
+    pub received: HashMap<String, u64>,
+
+    /// The total amount sent for every asset, fees included
+    pub sent: HashMap<String, u64>,
+
+    /// The height of the last block known by the wallet
+    pub tip_height: u32,
+
+    /// How many blocks the wallet is behind the most recent block seen by the server
+    pub tip_lag: u32,
 }
 
 /// PSET response
@@ -109,6 +313,67 @@ pub struct WalletBalance {
 pub struct Pset {
     /// The PSET in base64 format
     pub pset: String,
+
+    /// Warnings on the created transaction, which is nevertheless valid
+    #[serde(default)]
+    pub warnings: String,
+}
+
+/// A session signing a PSET with an external signer
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SigningSession {
+    /// The session identifier
+    pub id: String,
+
+    /// The digests to sign
+    pub requests: Vec<SighashRequest>,
+}
+
+/// A digest to sign with ECDSA and SIGHASH_ALL
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct SighashRequest {
+    /// The index of the input
+    pub index: u32,
+
+    /// The 32 bytes digest in hex
+    pub sighash: String,
+
+    /// The public key expected to sign, in hex
+    pub public_key: String,
+
+    /// The fingerprint of the master key
+    pub fingerprint: String,
+
+    /// The derivation path of the key from the master key
+    pub derivation_path: String,
+}
+
+/// Issuance response
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletIssue {
+    /// The PSET in base64 format
+    pub pset: String,
+
+    /// Warnings on the created transaction, which is nevertheless valid
+    #[serde(default)]
+    pub warnings: String,
+
+    /// The asset id of the issued asset
+    pub asset_id: String,
+
+    /// The asset id of the reissuance token
+    pub token_id: String,
+
+    /// The outpoint spent by the issuance input, as `txid:vout`
+    pub issuance_prevout: String,
+
+    /// The hash of the contract committed in the issuance, if a contract was given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract_hash: Option<String>,
+
+    /// The contract to publish to the asset registry once the issuance is confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub contract: Option<AssetContract>,
 }
 
 /// Response containing a single signature descriptor
@@ -230,6 +495,57 @@ pub struct WalletDetails {
 pub struct WalletCombine {
     /// PSET in base64 format
     pub pset: String,
+
+    /// The final transaction in hex, present only if all the inputs have enough signatures
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tx: Option<String>,
+
+    /// The inputs that cannot be finalized yet
+    #[serde(default)]
+    pub missing_signatures: Vec<InputMissingSignatures>,
+}
+
+/// An input of a PSET that cannot be finalized yet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct InputMissingSignatures {
+    /// Index of the input
+    pub vin: u32,
+
+    /// The signers that did not sign the input, with a multisig only some of them are needed
+    pub missing_signatures_from: Vec<SignerShortDetails>,
+}
+
+/// An output of a PSET paying someone else than the wallet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PsetRecipient {
+    /// Index of the output
+    pub vout: u32,
+
+    /// The address receiving the output, if the script has an address form
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+
+    /// The asset of the output, if known and proven by the blind proofs for blinded outputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+
+    /// The amount of the output, if known and proven by the blind proofs for blinded outputs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub satoshi: Option<u64>,
+}
+
+/// The signers of an input of a PSET
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PsetInputSigners {
+    /// Index of the input
+    pub vin: u32,
+
+    /// Signatures contained in the input
+    pub has_signatures_from: Vec<SignerShortDetails>,
+
+    /// Signatures required to spend the input but missing, signers with a name are loaded and
+    /// can provide them
+    pub missing_signatures_from: Vec<SignerShortDetails>,
 }
 
 /// Response containing detail of a PSET
@@ -288,6 +604,11 @@ pub struct WalletPsetDetails {
     /// Signature required to spend but missing in the PSET
     pub missing_signatures_from: Vec<SignerShortDetails>,
 
+    /// Loaded signers missing a signature, in the recommended signing order: signers which are
+    /// quick to use first, devices with stricter constraints last
+    #[serde(default)]
+    pub signing_order: Vec<String>,
+
     /// Net balance of the assets for the point of view of the given wallet
     pub balance: HashMap<String, i64>,
 
@@ -300,6 +621,18 @@ pub struct WalletPsetDetails {
     /// Reissuance contained in the PSET
     pub reissuances: Vec<Reissuance>,
 
+    /// Indexes of the outputs going back to the wallet change chain
+    #[serde(default)]
+    pub change_outputs: Vec<u32>,
+
+    /// Outputs paying someone else than the wallet
+    #[serde(default)]
+    pub recipients: Vec<PsetRecipient>,
+
+    /// For each input, the signers which signed it and the ones still missing
+    #[serde(default)]
+    pub inputs: Vec<PsetInputSigners>,
+
     /// Warnings
     pub warnings: String,
 }
@@ -324,6 +657,10 @@ pub struct Utxo {
 
     /// Output value in satoshi
     pub value: u64,
+
+    /// Whether the UTXO is locked and excluded from coin selection
+    #[serde(default)]
+    pub locked: bool,
 }
 
 /// Wallet unspent transaction outputs
@@ -333,6 +670,63 @@ pub struct WalletUtxos {
     pub utxos: Vec<Utxo>,
 }
 
+/// A wallet unspent transaction output with the data to spend it from another wallet
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ExportedUtxo {
+    /// Transction ID
+    pub txid: String,
+
+    /// Output index
+    pub vout: u32,
+
+    /// Height, none if unconfirmed
+    pub height: Option<u32>,
+
+    /// Output script pubkey
+    pub script_pubkey: String,
+
+    /// The serialized transaction output, to set as witness utxo of a PSET input
+    pub witness_utxo: String,
+
+    /// Output asset
+    pub asset: String,
+
+    /// Output value in satoshi
+    pub value: u64,
+
+    /// Asset blinding factor, zero if the output is explicit
+    pub asset_bf: String,
+
+    /// Value blinding factor, zero if the output is explicit
+    pub value_bf: String,
+}
+
+/// The arguments of the `importprunedfunds` RPC of elements core for a transaction
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PrunedFunds {
+    /// Transction ID
+    pub txid: String,
+
+    /// The transaction in hex
+    pub rawtransaction: String,
+
+    /// The proof of inclusion of the transaction in its block in hex, as returned by
+    /// `gettxoutproof`
+    pub txoutproof: String,
+}
+
+/// Wallet unspent transaction outputs in formats accepted by elements core
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct WalletUtxosExport {
+    /// UTXOs
+    pub utxos: Vec<ExportedUtxo>,
+
+    /// The `importprunedfunds` arguments of the confirmed transactions creating the UTXOs
+    ///
+    /// Unconfirmed transactions have no proof and are not included.
+    pub pruned_funds: Vec<PrunedFunds>,
+}
+
 /// Transaction
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Tx {
@@ -360,6 +754,23 @@ pub struct Tx {
 
     /// Memo
     pub memo: String,
+
+    /// Heuristics on the risk of relying on the transaction, present only if unconfirmed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub zero_conf: Option<ZeroConfRisk>,
+}
+
+/// Heuristics helping to decide whether to accept an unconfirmed incoming transaction
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ZeroConfRisk {
+    /// Fee rate in satoshi per 1000 virtual bytes
+    pub fee_rate: u64,
+
+    /// Whether any input signals replaceability
+    pub replaceable: bool,
+
+    /// The length of the longest chain of unconfirmed ancestors known by the wallet
+    pub unconfirmed_depth: u32,
 }
 
 /// Wallet transactions
@@ -384,6 +795,13 @@ pub struct AssetDetails {
 
     /// Ticker of the asset
     pub ticker: String,
+
+    /// Number of decimal digits of the asset amounts
+    pub precision: u8,
+
+    /// Domain of the asset issuer, if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
 }
 
 /// Asset details
@@ -413,6 +831,175 @@ pub struct AssetList {
     pub assets: Vec<Asset>,
 }
 
+/// The kind of change of a registry entry
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetEventKind {
+    /// The registry entry has metadata different from the asset contract
+    Changed,
+
+    /// The registry entry has been removed
+    Revoked,
+}
+
+/// A change of the registry entry of an asset held by a loaded wallet
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct AssetEvent {
+    /// The asset identifier (32 bytes as 64 hex chars)
+    pub asset_id: String,
+
+    /// The kind of change
+    pub kind: AssetEventKind,
+
+    /// The ticker in the asset contract
+    pub ticker: String,
+
+    /// The domain in the asset contract
+    pub domain: String,
+
+    /// The ticker in the registry entry, missing if the entry has been revoked
+    pub registry_ticker: Option<String>,
+
+    /// The domain in the registry entry, missing if the entry has been revoked
+    pub registry_domain: Option<String>,
+
+    /// Unix timestamp of when the change has been detected
+    pub timestamp: u64,
+}
+
+/// A list of asset events
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct AssetEvents {
+    /// The list of events, from the oldest
+    pub events: Vec<AssetEvent>,
+}
+
+/// A contact of the address book
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Contact {
+    /// The contact name
+    pub name: String,
+
+    /// The address of the contact, missing for descriptor contacts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+
+    /// The CT descriptor of the contact, missing for address contacts
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub descriptor: Option<String>,
+
+    /// The derivation index of the address used for the next payment to a descriptor contact
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index: Option<u32>,
+}
+
+/// A list of contacts
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ContactList {
+    /// The contacts, sorted by name
+    pub contacts: Vec<Contact>,
+}
+
+/// The metadata of a saved draft
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DraftInfo {
+    /// The draft name
+    pub name: String,
+
+    /// The name of the wallet involved in the transaction
+    pub wallet: String,
+
+    /// Who saved the draft
+    pub creator: Option<String>,
+
+    /// The time of the last save in seconds since the unix epoch
+    pub timestamp: u64,
+}
+
+/// A list of drafts
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct DraftList {
+    /// The drafts, sorted by name
+    pub drafts: Vec<DraftInfo>,
+}
+
+/// A saved draft
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Draft {
+    /// The draft name
+    pub name: String,
+
+    /// The name of the wallet involved in the transaction
+    pub wallet: String,
+
+    /// Who saved the draft
+    pub creator: Option<String>,
+
+    /// The time of the last save in seconds since the unix epoch
+    pub timestamp: u64,
+
+    /// The PSET in base64
+    pub pset: String,
+}
+
+/// The kind of event of a notification
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// A new transaction involving the wallet has been seen
+    TxReceived,
+
+    /// A transaction of the wallet has been included in a block
+    TxConfirmed,
+
+    /// A new block has been seen
+    Block,
+
+    /// The wallet has been updated by a blockchain scan
+    WalletSynced,
+}
+
+/// An event about the loaded wallets
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct Notification {
+    /// Incremental identifier of the notification
+    pub id: u64,
+
+    /// The kind of event
+    pub kind: NotificationKind,
+
+    /// The wallet involved, missing for blocks
+    pub wallet: Option<String>,
+
+    /// The transaction identifier, only for transaction events
+    pub txid: Option<String>,
+
+    /// The height of the block, or of the block including the transaction
+    pub height: Option<u32>,
+
+    /// Unix timestamp of when the event has been detected
+    pub timestamp: u64,
+}
+
+/// The notifications received while subscribed
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Subscribe {
+    /// The list of notifications, from the oldest
+    pub notifications: Vec<Notification>,
+
+    /// The identifier of the last notification emitted, to use as `since` in the next request
+    pub last_id: u64,
+}
+
+/// Fee rates estimated by the server
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FeeRates {
+    /// Fee rates in sat/kvb by confirmation target in blocks
+    ///
+    /// Empty if the server can't estimate fees, for instance when the mempool is empty.
+    pub estimates: BTreeMap<u16, f32>,
+}
+
 /// Asset details
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct JadeId {
@@ -448,3 +1035,39 @@ impl std::fmt::Display for WalletType {
         }
     }
 }
+
+/// Response of sending a PSET with Nostr
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PsetSendNostr {
+    /// The Nostr public key of the sender, in hex
+    pub sender: String,
+
+    /// The id of the Nostr event containing the encrypted PSET
+    pub event_id: String,
+}
+
+/// A PSET received with Nostr
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct NostrPset {
+    /// The Nostr public key of the sender, in hex
+    pub sender: String,
+
+    /// The id of the Nostr event containing the encrypted PSET
+    pub event_id: String,
+
+    /// When the PSET was sent, as unix timestamp
+    pub created_at: u64,
+
+    /// The PSET in base64
+    pub pset: String,
+}
+
+/// Response containing the PSETs received with Nostr
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct PsetInbox {
+    /// The Nostr public key of the signer, in hex, to be shared with the cosigners
+    pub pubkey: String,
+
+    /// The received PSETs, oldest first
+    pub psets: Vec<NostrPset>,
+}
@@ -5,6 +5,9 @@
 //!
 //! Signers should implement [`lwk_common::Signer`]
 
+#[cfg(feature = "signer_conformance")]
+pub mod signer_conformance;
+pub mod slip39;
 mod software;
 
 pub use crate::software::{NewError, SignError, SwSigner};
@@ -13,7 +16,8 @@ pub use bip39;
 use elements_miniscript::bitcoin::bip32::{self, DerivationPath, Fingerprint};
 use elements_miniscript::elements::bitcoin::bip32::Xpub;
 use elements_miniscript::elements::pset::PartiallySignedTransaction;
-use lwk_common::Signer;
+use elements_miniscript::{ConfidentialDescriptor, DescriptorPublicKey};
+use lwk_common::{ErrorCategory, Signer};
 
 /// Possible errors when signing with [`AnySigner`]
 #[derive(thiserror::Error, Debug)]
@@ -31,6 +35,20 @@ pub enum SignerError {
 
     #[error(transparent)]
     Bip32Error(#[from] bip32::Error),
+
+    #[error(transparent)]
+    Common(#[from] lwk_common::Error),
+}
+
+impl SignerError {
+    /// The category of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            SignerError::Software(e) => e.category(),
+            SignerError::Common(e) => e.category(),
+            _ => ErrorCategory::Signer,
+        }
+    }
 }
 
 /// A signer that can be a software signer [`SwSigner`] or a [`lwk_jade::Jade`]
@@ -48,6 +66,34 @@ pub enum AnySigner {
     ),
 }
 
+impl AnySigner {
+    /// Register on the signer the multisig wallet with `descriptor`, calling it `name`
+    ///
+    /// Jade needs multisig wallets to be registered to show their change addresses and to sign
+    /// without warnings, for the other signers this is a no-op.
+    #[allow(unused_variables)]
+    pub fn register_multisig(
+        &self,
+        name: &str,
+        descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+    ) -> Result<(), SignerError> {
+        match self {
+            #[cfg(feature = "jade")]
+            AnySigner::Jade(jade, _) => {
+                use lwk_jade::register_multisig::{JadeDescriptor, RegisterMultisigParams};
+                let descriptor: JadeDescriptor = descriptor.try_into()?;
+                jade.register_multisig(RegisterMultisigParams {
+                    network: jade.network(),
+                    multisig_name: name.to_string(),
+                    descriptor,
+                })?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
 impl Signer for AnySigner {
     type Error = SignerError;
 
@@ -55,6 +101,18 @@ impl Signer for AnySigner {
         Signer::sign(&self, pset)
     }
 
+    fn prompt_estimate(&self, pset: &PartiallySignedTransaction) -> Result<u32, Self::Error> {
+        Signer::prompt_estimate(&self, pset)
+    }
+
+    fn display_address(
+        &self,
+        descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+        index: u32,
+    ) -> Result<Option<String>, Self::Error> {
+        Signer::display_address(&self, descriptor, index)
+    }
+
     fn derive_xpub(&self, path: &DerivationPath) -> Result<Xpub, Self::Error> {
         Signer::derive_xpub(&self, path)
     }
@@ -85,6 +143,34 @@ impl Signer for &AnySigner {
         })
     }
 
+    fn prompt_estimate(&self, pset: &PartiallySignedTransaction) -> Result<u32, Self::Error> {
+        Ok(match self {
+            AnySigner::Software(signer) => signer.prompt_estimate(pset)?,
+
+            #[cfg(feature = "jade")]
+            AnySigner::Jade(signer, _) => signer.prompt_estimate(pset)?,
+
+            #[cfg(feature = "ledger")]
+            AnySigner::Ledger(signer, _) => signer.prompt_estimate(pset)?,
+        })
+    }
+
+    fn display_address(
+        &self,
+        descriptor: &ConfidentialDescriptor<DescriptorPublicKey>,
+        index: u32,
+    ) -> Result<Option<String>, Self::Error> {
+        Ok(match self {
+            AnySigner::Software(signer) => signer.display_address(descriptor, index)?,
+
+            #[cfg(feature = "jade")]
+            AnySigner::Jade(signer, _) => Signer::display_address(signer, descriptor, index)?,
+
+            #[cfg(feature = "ledger")]
+            AnySigner::Ledger(signer, _) => signer.display_address(descriptor, index)?,
+        })
+    }
+
     fn derive_xpub(&self, path: &DerivationPath) -> Result<Xpub, Self::Error> {
         Ok(match self {
             AnySigner::Software(s) => s.derive_xpub(path)?,
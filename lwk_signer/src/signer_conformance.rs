@@ -0,0 +1,373 @@
+//! A conformance suite to check a [`Signer`] implementation behaves like [`SwSigner`]
+//!
+//! The signer under test must be loaded with the same mnemonic given to [`check()`], which
+//! runs the same scenarios against it and against a [`SwSigner`] built from the mnemonic:
+//! xpub derivations, SLIP77 master blinding key, singlesig descriptors and PSET signing.
+//!
+//! Signatures are not compared byte by byte, since signers are free to choose their nonces,
+//! instead they must be added for the same keys and be valid `SIGHASH_ALL` signatures.
+//!
+//! ```
+//! # use lwk_signer::{signer_conformance, SwSigner};
+//! let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+//! let signer = SwSigner::new(mnemonic, false).unwrap();
+//! let divergences = signer_conformance::check(&signer, mnemonic, false).unwrap();
+//! assert!(divergences.is_empty());
+//! ```
+
+use std::collections::BTreeSet;
+use std::fmt::{self, Debug, Display};
+use std::str::FromStr;
+
+use elements_miniscript::bitcoin::bip32::{DerivationPath, Fingerprint};
+use elements_miniscript::elements::bitcoin::PublicKey;
+use elements_miniscript::elements::confidential::{Asset, Nonce, Value};
+use elements_miniscript::elements::pset::{self, PartiallySignedTransaction};
+use elements_miniscript::elements::secp256k1_zkp::{ecdsa, Secp256k1};
+use elements_miniscript::elements::{
+    Address, AddressParams, AssetId, EcdsaSighashType, OutPoint, Script, TxOut, Txid,
+};
+use lwk_common::{singlesig_desc, Bip, DescriptorBlindingKey, Signer, Singlesig};
+
+use crate::{NewError, SwSigner};
+
+/// Derivation paths checked with [`Signer::derive_xpub()`]
+const PATHS: [&str; 7] = [
+    "m",
+    "m/0h",
+    "m/84h/1h/0h",
+    "m/84h/1776h/0h",
+    "m/49h/1h/0h",
+    "m/87h/1h/0h",
+    "m/84h/1h/0h/0/1",
+];
+
+/// Value of the input of the PSETs built by the suite, in satoshi
+const INPUT_VALUE: u64 = 100_000;
+
+/// Fee of the PSETs built by the suite, in satoshi
+const FEE: u64 = 1_000;
+
+/// A scenario where the signer under test behaved differently from the reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The scenario, like `derive_xpub(m/84'/1'/0')`
+    pub scenario: String,
+
+    /// The result of the reference software signer
+    pub expected: String,
+
+    /// The result of the signer under test
+    pub got: String,
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: expected `{}` got `{}`",
+            self.scenario, self.expected, self.got
+        )
+    }
+}
+
+/// Run the suite against `signer`, loaded with `mnemonic`, returning the divergences found
+///
+/// Errors returned by the signer under test are reported as divergences; this fails only if
+/// `mnemonic` is invalid.
+pub fn check<S: Signer>(
+    signer: &S,
+    mnemonic: &str,
+    is_mainnet: bool,
+) -> Result<Vec<Divergence>, NewError> {
+    let reference = SwSigner::new(mnemonic, is_mainnet)?;
+    let mut c = Checker {
+        divergences: vec![],
+    };
+
+    c.compare("xpub", Signer::xpub(&reference), signer.xpub());
+    c.compare(
+        "fingerprint",
+        Signer::fingerprint(&reference),
+        signer.fingerprint(),
+    );
+    c.compare(
+        "identifier",
+        Signer::identifier(&reference),
+        signer.identifier(),
+    );
+    c.compare(
+        "is_mainnet",
+        Signer::is_mainnet(&reference),
+        signer.is_mainnet(),
+    );
+    for path in PATHS {
+        let path = DerivationPath::from_str(path).expect("static");
+        c.compare(
+            &format!("derive_xpub({path})"),
+            reference.derive_xpub(&path),
+            signer.derive_xpub(&path),
+        );
+    }
+    for bip in [Bip::Bip84, Bip::Bip49, Bip::Bip87] {
+        for mainnet in [false, true] {
+            c.compare(
+                &format!("keyorigin_xpub({bip:?}, mainnet={mainnet})"),
+                reference.keyorigin_xpub(bip, mainnet),
+                signer.keyorigin_xpub(bip, mainnet),
+            );
+        }
+    }
+    c.compare(
+        "slip77_master_blinding_key",
+        reference.slip77_master_blinding_key(),
+        signer.slip77_master_blinding_key(),
+    );
+    for variant in [Singlesig::Wpkh, Singlesig::ShWpkh] {
+        for blinding in [
+            DescriptorBlindingKey::Slip77,
+            DescriptorBlindingKey::Elip151,
+        ] {
+            c.compare(
+                &format!("singlesig_desc({variant:?}, {blinding:?})"),
+                singlesig_desc(&reference, variant, blinding, is_mainnet),
+                singlesig_desc(signer, variant, blinding, is_mainnet),
+            );
+        }
+    }
+
+    for index in [0, 1] {
+        if let Ok(pset) = wpkh_pset(&reference, is_mainnet, index, None) {
+            c.compare_sign(&format!("sign(wpkh/{index})"), &reference, signer, &pset);
+        }
+    }
+    let other = Fingerprint::from([0u8; 4]);
+    if let Ok(pset) = wpkh_pset(&reference, is_mainnet, 0, Some(other)) {
+        c.compare_sign("sign(foreign fingerprint)", &reference, signer, &pset);
+    }
+
+    Ok(c.divergences)
+}
+
+/// Like [`check()`] but panics listing the divergences, if any
+pub fn assert_conformance<S: Signer>(signer: &S, mnemonic: &str, is_mainnet: bool) {
+    let divergences = check(signer, mnemonic, is_mainnet).expect("invalid mnemonic");
+    let lines: Vec<String> = divergences.iter().map(|d| d.to_string()).collect();
+    assert!(
+        lines.is_empty(),
+        "{} divergences from the software signer:\n{}",
+        lines.len(),
+        lines.join("\n")
+    );
+}
+
+struct Checker {
+    divergences: Vec<Divergence>,
+}
+
+impl Checker {
+    fn compare<T, E1, E2>(&mut self, scenario: &str, expected: Result<T, E1>, got: Result<T, E2>)
+    where
+        T: PartialEq + Display,
+        E1: Debug,
+        E2: Debug,
+    {
+        let expected = expected.map_err(|e| format!("{e:?}"));
+        let got = got.map_err(|e| format!("{e:?}"));
+        if expected != got {
+            self.divergences.push(Divergence {
+                scenario: scenario.to_string(),
+                expected: result_to_string(&expected),
+                got: result_to_string(&got),
+            });
+        }
+    }
+
+    fn compare_sign<S: Signer>(
+        &mut self,
+        scenario: &str,
+        reference: &SwSigner,
+        signer: &S,
+        pset: &PartiallySignedTransaction,
+    ) {
+        let mut expected_pset = pset.clone();
+        let expected = reference
+            .sign(&mut expected_pset)
+            .map(|added| SignOutcome::new(added, &expected_pset));
+        let mut got_pset = pset.clone();
+        let got = signer
+            .sign(&mut got_pset)
+            .map(|added| SignOutcome::new(added, &got_pset));
+        self.compare(scenario, expected, got);
+
+        if let Err(e) = verify_signatures(&got_pset) {
+            self.divergences.push(Divergence {
+                scenario: format!("{scenario} signatures"),
+                expected: "valid signatures".to_string(),
+                got: e,
+            });
+        }
+    }
+}
+
+fn result_to_string<T: Display>(r: &Result<T, String>) -> String {
+    match r {
+        Ok(v) => v.to_string(),
+        Err(e) => format!("error {e}"),
+    }
+}
+
+/// The signatures added by a signer, without the signature bytes which may legitimately differ
+#[derive(PartialEq)]
+struct SignOutcome {
+    added: u32,
+    signed_keys: BTreeSet<(usize, PublicKey)>,
+}
+
+impl SignOutcome {
+    fn new(added: u32, pset: &PartiallySignedTransaction) -> Self {
+        let signed_keys = pset
+            .inputs()
+            .iter()
+            .enumerate()
+            .flat_map(|(i, input)| input.partial_sigs.keys().map(move |pk| (i, *pk)))
+            .collect();
+        Self { added, signed_keys }
+    }
+}
+
+impl Display for SignOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let keys: Vec<String> = self
+            .signed_keys
+            .iter()
+            .map(|(i, pk)| format!("{i}:{pk}"))
+            .collect();
+        write!(f, "{} added [{}]", self.added, keys.join(", "))
+    }
+}
+
+/// Check that every signature in `pset` is a valid `SIGHASH_ALL` signature of its key
+fn verify_signatures(pset: &PartiallySignedTransaction) -> Result<(), String> {
+    let secp = Secp256k1::verification_only();
+    for (idx, input) in pset.inputs().iter().enumerate() {
+        if input.partial_sigs.is_empty() {
+            continue;
+        }
+        let msg = lwk_common::pset_input_sighash(pset, idx, EcdsaSighashType::All)
+            .map_err(|e| e.to_string())?
+            .sighash;
+        for (pk, raw) in input.partial_sigs.iter() {
+            let (sighash_type, der) = raw
+                .split_last()
+                .ok_or_else(|| format!("input {idx} empty signature"))?;
+            if *sighash_type != EcdsaSighashType::All as u8 {
+                return Err(format!("input {idx} sighash type {sighash_type}"));
+            }
+            let sig = ecdsa::Signature::from_der(der)
+                .map_err(|e| format!("input {idx} invalid signature encoding: {e}"))?;
+            secp.verify_ecdsa(&msg, &sig, &pk.inner)
+                .map_err(|e| format!("input {idx} invalid signature for {pk}: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// A PSET spending an explicit output of the wpkh wallet of `reference` at external `index`
+/// to its next address, with the key origin claiming `fingerprint`, the reference one if `None`
+fn wpkh_pset(
+    reference: &SwSigner,
+    is_mainnet: bool,
+    index: u32,
+    fingerprint: Option<Fingerprint>,
+) -> Result<PartiallySignedTransaction, String> {
+    let (params, policy_asset) = if is_mainnet {
+        (
+            &AddressParams::LIQUID,
+            "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d",
+        )
+    } else {
+        (
+            &AddressParams::LIQUID_TESTNET,
+            "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49",
+        )
+    };
+    let asset = AssetId::from_str(policy_asset).expect("static");
+    let coin_type = if is_mainnet { 1776 } else { 1 };
+    let key = |i: u32| -> Result<(PublicKey, DerivationPath, Script), String> {
+        let path = DerivationPath::from_str(&format!("m/84h/{coin_type}h/0h/0/{i}"))
+            .map_err(|e| e.to_string())?;
+        let xpub = reference.derive_xpub(&path).map_err(|e| e.to_string())?;
+        let pk = PublicKey::new(xpub.public_key);
+        let script = Address::p2wpkh(&pk, None, params).script_pubkey();
+        Ok((pk, path, script))
+    };
+    let (pk, path, script) = key(index)?;
+    let (_, _, change_script) = key(index + 1)?;
+
+    let txid = Txid::from_str("a4a81707da8e58f1448a27bdb5d26098fb5a3118a9a35e830ebae7bdfb3806f9")
+        .expect("static");
+    let mut input = pset::Input::from_prevout(OutPoint::new(txid, index));
+    input.witness_utxo = Some(TxOut {
+        asset: Asset::Explicit(asset),
+        value: Value::Explicit(INPUT_VALUE),
+        nonce: Nonce::Null,
+        script_pubkey: script,
+        witness: Default::default(),
+    });
+    input
+        .bip32_derivation
+        .insert(pk, (fingerprint.unwrap_or(reference.fingerprint()), path));
+
+    let mut pset = PartiallySignedTransaction::new_v2();
+    pset.add_input(input);
+    pset.add_output(pset::Output::new_explicit(
+        change_script,
+        INPUT_VALUE - FEE,
+        asset,
+        None,
+    ));
+    pset.add_output(pset::Output::new_explicit(Script::new(), FEE, asset, None));
+    Ok(pset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OTHER_MNEMONIC: &str = "zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo zoo wrong";
+
+    #[test]
+    fn test_software_signer_conforms() {
+        for is_mainnet in [false, true] {
+            let signer = SwSigner::new(lwk_test_util::TEST_MNEMONIC, is_mainnet).unwrap();
+            assert_conformance(&signer, lwk_test_util::TEST_MNEMONIC, is_mainnet);
+        }
+    }
+
+    #[test]
+    fn test_divergences() {
+        let signer = SwSigner::new(OTHER_MNEMONIC, false).unwrap();
+        let divergences = check(&signer, lwk_test_util::TEST_MNEMONIC, false).unwrap();
+        let scenarios: Vec<&str> = divergences.iter().map(|d| d.scenario.as_str()).collect();
+        assert!(scenarios.contains(&"xpub"));
+        assert!(scenarios.contains(&"derive_xpub(m/84'/1'/0')"));
+        assert!(scenarios.contains(&"slip77_master_blinding_key"));
+        assert!(scenarios.contains(&"singlesig_desc(Wpkh, Slip77)"));
+        // The other signer does not sign inputs of the reference wallet
+        assert!(scenarios.contains(&"sign(wpkh/0)"));
+        assert!(!scenarios.contains(&"sign(foreign fingerprint)"));
+
+        // A signer without seed cannot derive the slip77 master blinding key
+        let xprv = SwSigner::new(lwk_test_util::TEST_MNEMONIC, false)
+            .unwrap()
+            .derive_xprv(&DerivationPath::master())
+            .unwrap();
+        let signer = SwSigner::from_xprv(xprv);
+        let divergences = check(&signer, lwk_test_util::TEST_MNEMONIC, false).unwrap();
+        let scenarios: Vec<&str> = divergences.iter().map(|d| d.scenario.as_str()).collect();
+        assert!(scenarios.contains(&"slip77_master_blinding_key"));
+        assert!(!scenarios.contains(&"xpub"));
+        assert!(!scenarios.contains(&"sign(wpkh/0)"));
+    }
+}
@@ -0,0 +1,625 @@
+//! SLIP-39 Shamir's secret sharing of a master secret in mnemonic shares
+//!
+//! See the [specification](https://github.com/satoshilabs/slips/blob/master/slip-0039.md).
+
+mod wordlist;
+
+use std::collections::BTreeMap;
+
+use bip39::rand::{thread_rng, RngCore};
+use elements_miniscript::bitcoin::hashes::{sha256, Hash, HashEngine, Hmac, HmacEngine};
+
+use wordlist::WORDLIST;
+
+const RADIX_BITS: usize = 10;
+const ID_BITS: usize = 15;
+const CHECKSUM_WORDS: usize = 3;
+const PREFIX_WORDS: usize = 4;
+const MIN_STRENGTH_BYTES: usize = 16;
+const MIN_MNEMONIC_WORDS: usize = 20;
+const MAX_SHARE_COUNT: usize = 16;
+const BASE_ITERATION_COUNT: u32 = 10_000;
+const ROUND_COUNT: u8 = 4;
+const SECRET_INDEX: u8 = 255;
+const DIGEST_INDEX: u8 = 254;
+const DIGEST_LENGTH: usize = 4;
+
+/// Possible errors when splitting or combining SLIP-39 shares
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum Slip39Error {
+    #[error("Invalid word '{0}'")]
+    InvalidWord(String),
+
+    #[error("Invalid mnemonic length")]
+    InvalidLength,
+
+    #[error("Invalid mnemonic checksum")]
+    InvalidChecksum,
+
+    #[error("Invalid mnemonic padding")]
+    InvalidPadding,
+
+    #[error("Shares do not belong to the same set")]
+    MismatchingShares,
+
+    #[error("Insufficient shares, {required} required, {provided} provided")]
+    InsufficientShares { required: u8, provided: u8 },
+
+    #[error("Invalid digest, shares are corrupted or not from the same secret")]
+    InvalidDigest,
+
+    #[error("Invalid parameters: {0}")]
+    InvalidParameters(String),
+}
+
+/// A single decoded mnemonic share
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Share {
+    identifier: u16,
+    extendable: bool,
+    iteration_exponent: u8,
+    group_index: u8,
+    group_threshold: u8,
+    group_count: u8,
+    member_index: u8,
+    member_threshold: u8,
+    value: Vec<u8>,
+}
+
+impl Share {
+    fn from_mnemonic(mnemonic: &str) -> Result<Self, Slip39Error> {
+        let indices = mnemonic
+            .split_whitespace()
+            .map(word_index)
+            .collect::<Result<Vec<_>, _>>()?;
+        if indices.len() < MIN_MNEMONIC_WORDS {
+            return Err(Slip39Error::InvalidLength);
+        }
+        let value_words = &indices[PREFIX_WORDS..indices.len() - CHECKSUM_WORDS];
+        let padding = (value_words.len() * RADIX_BITS) % 16;
+        if padding > 8 {
+            return Err(Slip39Error::InvalidLength);
+        }
+
+        let prefix = indices[..PREFIX_WORDS]
+            .iter()
+            .fold(0u64, |acc, i| (acc << RADIX_BITS) | *i as u64);
+        let extendable = (prefix >> 24) & 1 == 1;
+        if polymod(customization(extendable), &indices) != 1 {
+            return Err(Slip39Error::InvalidChecksum);
+        }
+        let nibble = |shift: u32| ((prefix >> shift) & 0xf) as u8;
+        let share = Share {
+            identifier: (prefix >> 25) as u16,
+            extendable,
+            iteration_exponent: nibble(20),
+            group_index: nibble(16),
+            group_threshold: nibble(12) + 1,
+            group_count: nibble(8) + 1,
+            member_index: nibble(4),
+            member_threshold: nibble(0) + 1,
+            value: words_to_bytes(value_words, padding)?,
+        };
+        if share.group_threshold > share.group_count {
+            return Err(Slip39Error::InvalidParameters(
+                "group threshold greater than group count".to_string(),
+            ));
+        }
+        Ok(share)
+    }
+
+    fn to_mnemonic(&self) -> String {
+        let prefix = (self.identifier as u64) << 25
+            | (self.extendable as u64) << 24
+            | (self.iteration_exponent as u64) << 20
+            | (self.group_index as u64) << 16
+            | ((self.group_threshold - 1) as u64) << 12
+            | ((self.group_count - 1) as u64) << 8
+            | (self.member_index as u64) << 4
+            | (self.member_threshold - 1) as u64;
+        let mut indices: Vec<u16> = (0..PREFIX_WORDS)
+            .rev()
+            .map(|i| ((prefix >> (i * RADIX_BITS)) & 0x3ff) as u16)
+            .collect();
+        indices.extend(bytes_to_words(&self.value));
+
+        let checksum = polymod(
+            customization(self.extendable),
+            &indices
+                .iter()
+                .copied()
+                .chain([0; CHECKSUM_WORDS])
+                .collect::<Vec<_>>(),
+        ) ^ 1;
+        indices.extend(
+            (0..CHECKSUM_WORDS)
+                .rev()
+                .map(|i| ((checksum >> (i * RADIX_BITS)) & 0x3ff) as u16),
+        );
+
+        indices
+            .iter()
+            .map(|i| WORDLIST[*i as usize])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Combine SLIP-39 mnemonic shares returning the master secret
+///
+/// The shares must contain at least the threshold number of members for the threshold number
+/// of groups. The passphrase is not verified, a different passphrase returns a different secret.
+pub fn combine(mnemonics: &[&str], passphrase: &str) -> Result<Vec<u8>, Slip39Error> {
+    let shares = mnemonics
+        .iter()
+        .map(|m| Share::from_mnemonic(m))
+        .collect::<Result<Vec<_>, _>>()?;
+    let first = shares.first().ok_or(Slip39Error::InsufficientShares {
+        required: 1,
+        provided: 0,
+    })?;
+    let same_set = |s: &Share| {
+        (
+            s.identifier,
+            s.extendable,
+            s.iteration_exponent,
+            s.group_threshold,
+            s.group_count,
+        )
+    };
+    if shares.iter().any(|s| same_set(s) != same_set(first)) {
+        return Err(Slip39Error::MismatchingShares);
+    }
+
+    let mut groups: BTreeMap<u8, BTreeMap<u8, &Share>> = BTreeMap::new();
+    for share in shares.iter() {
+        groups
+            .entry(share.group_index)
+            .or_default()
+            .insert(share.member_index, share);
+    }
+    let mut group_secrets = vec![];
+    let mut incomplete = None;
+    for (group_index, members) in groups.iter() {
+        let member_threshold = members
+            .values()
+            .next()
+            .map(|s| s.member_threshold)
+            .unwrap_or_default();
+        if members
+            .values()
+            .any(|s| s.member_threshold != member_threshold)
+        {
+            return Err(Slip39Error::MismatchingShares);
+        }
+        if members.len() < member_threshold as usize {
+            // The secret may still be recovered from other groups
+            incomplete = Some(Slip39Error::InsufficientShares {
+                required: member_threshold,
+                provided: members.len() as u8,
+            });
+            continue;
+        }
+        let points: Vec<_> = members
+            .values()
+            .take(member_threshold as usize)
+            .map(|s| (s.member_index, s.value.clone()))
+            .collect();
+        group_secrets.push((*group_index, recover_secret(member_threshold, &points)?));
+    }
+    if group_secrets.len() < first.group_threshold as usize {
+        return Err(incomplete.unwrap_or(Slip39Error::InsufficientShares {
+            required: first.group_threshold,
+            provided: group_secrets.len() as u8,
+        }));
+    }
+    group_secrets.truncate(first.group_threshold as usize);
+
+    let encrypted = recover_secret(first.group_threshold, &group_secrets)?;
+    Ok(feistel(
+        &encrypted,
+        passphrase,
+        first.iteration_exponent,
+        first.identifier,
+        first.extendable,
+        false,
+    ))
+}
+
+/// Split a master secret in SLIP-39 mnemonic shares, encrypted with the given passphrase
+///
+/// `groups` contains the member threshold and the member count of every group,
+/// `group_threshold` groups are needed to recover the secret.
+/// The key derivation used for encryption is repeated `10000 * 2^iteration_exponent` times.
+///
+/// Returns the mnemonics of every group.
+pub fn split(
+    master_secret: &[u8],
+    passphrase: &str,
+    group_threshold: u8,
+    groups: &[(u8, u8)],
+    iteration_exponent: u8,
+) -> Result<Vec<Vec<String>>, Slip39Error> {
+    let invalid = |s: &str| Slip39Error::InvalidParameters(s.to_string());
+    if master_secret.len() < MIN_STRENGTH_BYTES || master_secret.len() % 2 != 0 {
+        return Err(invalid(
+            "master secret must be at least 16 bytes and of even length",
+        ));
+    }
+    if !passphrase.bytes().all(|b| (32..=126).contains(&b)) {
+        return Err(invalid(
+            "passphrase must contain printable ASCII characters",
+        ));
+    }
+    if group_threshold == 0 || group_threshold as usize > groups.len() {
+        return Err(invalid(
+            "group threshold must be between 1 and the group count",
+        ));
+    }
+    if groups.len() > MAX_SHARE_COUNT {
+        return Err(invalid("too many groups"));
+    }
+    if iteration_exponent > 15 {
+        return Err(invalid("iteration exponent must be at most 15"));
+    }
+    for (member_threshold, member_count) in groups {
+        if *member_threshold == 0
+            || member_threshold > member_count
+            || *member_count as usize > MAX_SHARE_COUNT
+        {
+            return Err(invalid(
+                "member threshold must be between 1 and the member count, at most 16",
+            ));
+        }
+        if *member_threshold == 1 && *member_count > 1 {
+            return Err(invalid("use 1-of-1 member sharing instead of 1-of-N"));
+        }
+    }
+
+    let identifier = (thread_rng().next_u32() & ((1 << ID_BITS) - 1)) as u16;
+    let extendable = true;
+    let encrypted = feistel(
+        master_secret,
+        passphrase,
+        iteration_exponent,
+        identifier,
+        extendable,
+        true,
+    );
+
+    let group_secrets = split_secret(group_threshold, groups.len() as u8, &encrypted)?;
+    let mut result = vec![];
+    for ((member_threshold, member_count), (group_index, group_secret)) in
+        groups.iter().zip(group_secrets)
+    {
+        let members = split_secret(*member_threshold, *member_count, &group_secret)?;
+        result.push(
+            members
+                .into_iter()
+                .map(|(member_index, value)| {
+                    Share {
+                        identifier,
+                        extendable,
+                        iteration_exponent,
+                        group_index,
+                        group_threshold,
+                        group_count: groups.len() as u8,
+                        member_index,
+                        member_threshold: *member_threshold,
+                        value,
+                    }
+                    .to_mnemonic()
+                })
+                .collect(),
+        );
+    }
+    Ok(result)
+}
+
+fn word_index(word: &str) -> Result<u16, Slip39Error> {
+    let word = word.to_lowercase();
+    WORDLIST
+        .binary_search(&word.as_str())
+        .map(|i| i as u16)
+        .map_err(|_| Slip39Error::InvalidWord(word))
+}
+
+fn customization(extendable: bool) -> &'static [u8] {
+    if extendable {
+        b"shamir_extendable"
+    } else {
+        b"shamir"
+    }
+}
+
+/// The Reed-Solomon code over GF(1024) used for the checksum
+fn polymod(customization: &[u8], values: &[u16]) -> u32 {
+    const GEN: [u32; 10] = [
+        0xE0E040, 0x1C1C080, 0x3838100, 0x7070200, 0xE0E0009, 0x1C0C2412, 0x38086C24, 0x3090FC48,
+        0x21B1F890, 0x3F3F120,
+    ];
+    let mut chk = 1u32;
+    for v in customization
+        .iter()
+        .map(|c| *c as u32)
+        .chain(values.iter().map(|v| *v as u32))
+    {
+        let b = chk >> 20;
+        chk = ((chk & 0xFFFFF) << 10) ^ v;
+        for (i, gen) in GEN.iter().enumerate() {
+            if (b >> i) & 1 == 1 {
+                chk ^= gen;
+            }
+        }
+    }
+    chk
+}
+
+/// Encode bytes in 10 bits words, left padding with zero bits
+fn bytes_to_words(bytes: &[u8]) -> Vec<u16> {
+    let word_count = (bytes.len() * 8 + RADIX_BITS - 1) / RADIX_BITS;
+    let padding = word_count * RADIX_BITS - bytes.len() * 8;
+    let bits: Vec<bool> = std::iter::repeat(false)
+        .take(padding)
+        .chain(
+            bytes
+                .iter()
+                .flat_map(|b| (0..8).rev().map(move |i| (b >> i) & 1 == 1)),
+        )
+        .collect();
+    bits.chunks(RADIX_BITS)
+        .map(|c| c.iter().fold(0u16, |acc, b| (acc << 1) | *b as u16))
+        .collect()
+}
+
+/// Decode 10 bits words in bytes, the first `padding` bits must be zero
+fn words_to_bytes(words: &[u16], padding: usize) -> Result<Vec<u8>, Slip39Error> {
+    let bits: Vec<bool> = words
+        .iter()
+        .flat_map(|w| (0..RADIX_BITS).rev().map(move |i| (w >> i) & 1 == 1))
+        .collect();
+    if bits[..padding].iter().any(|b| *b) {
+        return Err(Slip39Error::InvalidPadding);
+    }
+    Ok(bits[padding..]
+        .chunks(8)
+        .map(|c| c.iter().fold(0u8, |acc, b| (acc << 1) | *b as u8))
+        .collect())
+}
+
+/// Exponent and logarithm tables of GF(256) with the Rijndael polynomial
+struct Gf256 {
+    exp: [u8; 255],
+    log: [u8; 256],
+}
+
+impl Gf256 {
+    fn new() -> Self {
+        let mut exp = [0u8; 255];
+        let mut log = [0u8; 256];
+        let mut poly: u16 = 1;
+        for (i, e) in exp.iter_mut().enumerate() {
+            *e = poly as u8;
+            log[poly as usize] = i as u8;
+            // multiply by the generator x + 1
+            poly = (poly << 1) ^ poly;
+            if poly & 0x100 != 0 {
+                poly ^= 0x11B;
+            }
+        }
+        Self { exp, log }
+    }
+}
+
+/// Evaluate at `x` the polynomial passing through the given points
+fn interpolate(points: &[(u8, Vec<u8>)], x: u8) -> Result<Vec<u8>, Slip39Error> {
+    if let Some((_, value)) = points.iter().find(|(px, _)| *px == x) {
+        return Ok(value.clone());
+    }
+    let len = points.first().map(|(_, v)| v.len()).unwrap_or_default();
+    if points.iter().any(|(_, v)| v.len() != len) {
+        return Err(Slip39Error::MismatchingShares);
+    }
+    let gf = Gf256::new();
+    let log = |v: u8| gf.log[v as usize] as usize;
+
+    let log_prod: usize = points.iter().map(|(px, _)| log(px ^ x)).sum();
+    let mut result = vec![0u8; len];
+    for (px, value) in points {
+        let others: usize = points.iter().map(|(ox, _)| log(px ^ ox)).sum();
+        let log_basis = (log_prod + 255 * (points.len() + 1) - log(px ^ x) - others) % 255;
+        for (r, v) in result.iter_mut().zip(value) {
+            if *v != 0 {
+                *r ^= gf.exp[(log(*v) + log_basis) % 255];
+            }
+        }
+    }
+    Ok(result)
+}
+
+fn digest(random: &[u8], secret: &[u8]) -> Vec<u8> {
+    let mut engine = HmacEngine::<sha256::Hash>::new(random);
+    engine.input(secret);
+    Hmac::<sha256::Hash>::from_engine(engine)[..DIGEST_LENGTH].to_vec()
+}
+
+fn split_secret(
+    threshold: u8,
+    count: u8,
+    secret: &[u8],
+) -> Result<Vec<(u8, Vec<u8>)>, Slip39Error> {
+    if threshold == 1 {
+        return Ok((0..count).map(|i| (i, secret.to_vec())).collect());
+    }
+    let random = |len: usize| {
+        let mut bytes = vec![0u8; len];
+        thread_rng().fill_bytes(&mut bytes);
+        bytes
+    };
+
+    let random_count = threshold - 2;
+    let mut shares: Vec<_> = (0..random_count)
+        .map(|i| (i, random(secret.len())))
+        .collect();
+    let random_part = random(secret.len() - DIGEST_LENGTH);
+    let mut digest_share = digest(&random_part, secret);
+    digest_share.extend(random_part);
+
+    let mut base = shares.clone();
+    base.push((DIGEST_INDEX, digest_share));
+    base.push((SECRET_INDEX, secret.to_vec()));
+    for i in random_count..count {
+        shares.push((i, interpolate(&base, i)?));
+    }
+    Ok(shares)
+}
+
+fn recover_secret(threshold: u8, points: &[(u8, Vec<u8>)]) -> Result<Vec<u8>, Slip39Error> {
+    if threshold == 1 {
+        return points
+            .first()
+            .map(|(_, v)| v.clone())
+            .ok_or(Slip39Error::InsufficientShares {
+                required: 1,
+                provided: 0,
+            });
+    }
+    let secret = interpolate(points, SECRET_INDEX)?;
+    let digest_share = interpolate(points, DIGEST_INDEX)?;
+    let (expected, random_part) = digest_share.split_at(DIGEST_LENGTH);
+    if digest(random_part, &secret) != expected {
+        return Err(Slip39Error::InvalidDigest);
+    }
+    Ok(secret)
+}
+
+/// The 4 rounds Feistel network encrypting the master secret
+fn feistel(
+    input: &[u8],
+    passphrase: &str,
+    iteration_exponent: u8,
+    identifier: u16,
+    extendable: bool,
+    encrypt: bool,
+) -> Vec<u8> {
+    let mut salt = vec![];
+    if !extendable {
+        salt.extend(customization(false));
+        salt.extend(identifier.to_be_bytes());
+    }
+    let iterations = (BASE_ITERATION_COUNT << iteration_exponent) / ROUND_COUNT as u32;
+
+    let (l, r) = input.split_at(input.len() / 2);
+    let (mut l, mut r) = (l.to_vec(), r.to_vec());
+    let rounds: Vec<u8> = if encrypt {
+        (0..ROUND_COUNT).collect()
+    } else {
+        (0..ROUND_COUNT).rev().collect()
+    };
+    for i in rounds {
+        let mut password = vec![i];
+        password.extend(passphrase.as_bytes());
+        let mut round_salt = salt.clone();
+        round_salt.extend(&r);
+        let f = pbkdf2_sha256(&password, &round_salt, iterations, r.len());
+        let xored = l.iter().zip(f).map(|(a, b)| a ^ b).collect();
+        l = std::mem::replace(&mut r, xored);
+    }
+    r.extend(l);
+    r
+}
+
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32, len: usize) -> Vec<u8> {
+    let keyed = HmacEngine::<sha256::Hash>::new(password);
+    let mut result = vec![];
+    let mut block = 1u32;
+    while result.len() < len {
+        let mut engine = keyed.clone();
+        engine.input(salt);
+        engine.input(&block.to_be_bytes());
+        let mut u = Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+        let mut t = u;
+        for _ in 1..iterations {
+            let mut engine = keyed.clone();
+            engine.input(&u);
+            u = Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+            t.iter_mut().zip(u.iter()).for_each(|(t, u)| *t ^= u);
+        }
+        result.extend(t);
+        block += 1;
+    }
+    result.truncate(len);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements_miniscript::elements::hex::ToHex;
+
+    #[test]
+    fn test_wordlist() {
+        assert_eq!(WORDLIST.len(), 1024);
+        assert!(WORDLIST.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(word_index("ACADEMIC").unwrap(), 0);
+        assert!(word_index("bitcoin").is_err());
+    }
+
+    #[test]
+    fn test_slip39_vectors() {
+        // Test vectors from the specification
+        let single = "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision keyboard";
+        let secret = combine(&[single], "TREZOR").unwrap();
+        assert_eq!(secret.to_hex(), "bb54aac4b89dc868ba37d9cc21b2cece");
+        assert_eq!(Share::from_mnemonic(single).unwrap().to_mnemonic(), single);
+
+        let invalid_checksum = "duckling enlarge academic academic agency result length solution fridge kidney coal piece deal husband erode duke ajar critical decision kidney";
+        assert_eq!(
+            combine(&[invalid_checksum], "TREZOR"),
+            Err(Slip39Error::InvalidChecksum)
+        );
+
+        let shares = [
+            "shadow pistol academic always adequate wildlife fancy gross oasis cylinder mustang wrist rescue view short owner flip making coding armed",
+            "shadow pistol academic acid actress prayer class unknown daughter sweater depict flip twice unkind craft early superior advocate guest smoking",
+        ];
+        let secret = combine(&shares, "TREZOR").unwrap();
+        assert_eq!(secret.to_hex(), "b43ceb7e57a0ea8766221624d01b0864");
+        assert_eq!(
+            combine(&shares[..1], "TREZOR"),
+            Err(Slip39Error::InsufficientShares {
+                required: 2,
+                provided: 1
+            })
+        );
+        assert_eq!(
+            combine(&[single, shares[0]], "TREZOR"),
+            Err(Slip39Error::MismatchingShares)
+        );
+    }
+
+    #[test]
+    fn test_slip39_split_combine() {
+        let secret = [7u8; 32];
+        let groups = split(&secret, "pass", 2, &[(1, 1), (2, 3), (3, 5)], 0).unwrap();
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[1].len(), 3);
+        assert_eq!(groups[0][0].split_whitespace().count(), 33);
+
+        let g = |i: usize, j: usize| groups[i][j].as_str();
+        assert_eq!(
+            combine(&[g(0, 0), g(1, 2), g(1, 0)], "pass").unwrap(),
+            secret
+        );
+        assert_eq!(
+            combine(&[g(2, 4), g(1, 1), g(2, 0), g(1, 2), g(2, 2)], "pass").unwrap(),
+            secret
+        );
+        assert_ne!(combine(&[g(0, 0), g(1, 2), g(1, 0)], "").unwrap(), secret);
+        assert!(combine(&[g(0, 0), g(1, 2)], "pass").is_err());
+
+        assert!(split(&secret[..15], "", 1, &[(1, 1)], 0).is_err());
+        assert!(split(&secret, "", 2, &[(1, 1)], 0).is_err());
+        assert!(split(&secret, "", 1, &[(1, 3)], 0).is_err());
+    }
+}
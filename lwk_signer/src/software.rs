@@ -15,7 +15,7 @@ use elements_miniscript::{
     psbt::PsbtExt,
     slip77::MasterBlindingKey,
 };
-use lwk_common::Signer;
+use lwk_common::{ErrorCategory, Signer};
 
 /// Possible errors when signing with the software signer [`SwSigner`]
 #[derive(thiserror::Error, Debug)]
@@ -35,6 +35,9 @@ pub enum SignError {
     #[error(transparent)]
     Bip32(#[from] bip32::Error),
 
+    #[error(transparent)]
+    Common(#[from] lwk_common::Error),
+
     #[error("Cannot derive slip77 key (mnemonic/seed not available)")]
     DeterministicSlip77NotAvailable,
 }
@@ -47,6 +50,30 @@ pub enum NewError {
 
     #[error(transparent)]
     Bip32(#[from] bip32::Error),
+
+    #[error(transparent)]
+    Slip39(#[from] crate::slip39::Slip39Error),
+}
+
+impl SignError {
+    /// The category of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            SignError::PsetParse(_) | SignError::ElementsEncode(_) => ErrorCategory::Input,
+            SignError::Common(e) => e.category(),
+            SignError::Pset(_)
+            | SignError::Sighash(_)
+            | SignError::Bip32(_)
+            | SignError::DeterministicSlip77NotAvailable => ErrorCategory::Signer,
+        }
+    }
+}
+
+impl NewError {
+    /// The category of this error
+    pub fn category(&self) -> ErrorCategory {
+        ErrorCategory::Input
+    }
 }
 
 /// A software signer
@@ -55,6 +82,9 @@ pub struct SwSigner {
     pub(crate) xprv: Xpriv,
     pub(crate) secp: Secp256k1<All>, // could be sign only, but it is likely the caller already has the All context.
     pub(crate) mnemonic: Option<Mnemonic>,
+
+    /// The master secret, if the signer has been created from SLIP-39 shares
+    pub(crate) slip39_secret: Option<Vec<u8>>,
 }
 
 impl core::fmt::Debug for SwSigner {
@@ -85,9 +115,58 @@ impl SwSigner {
             xprv,
             secp,
             mnemonic: Some(mnemonic),
+            slip39_secret: None,
         })
     }
 
+    /// Creates a new software signer from SLIP-39 mnemonic shares, combined with the given
+    /// passphrase to obtain the master secret used as BIP32 seed
+    pub fn from_slip39(
+        shares: &[&str],
+        passphrase: &str,
+        is_mainnet: bool,
+    ) -> Result<Self, NewError> {
+        let secret = crate::slip39::combine(shares, passphrase)?;
+        let network = if is_mainnet {
+            bitcoin::Network::Bitcoin
+        } else {
+            bitcoin::Network::Testnet
+        };
+
+        let xprv = Xpriv::new_master(network, &secret)?;
+
+        Ok(Self {
+            xprv,
+            secp: Secp256k1::new(),
+            mnemonic: None,
+            slip39_secret: Some(secret),
+        })
+    }
+
+    /// Split the seed of this signer in SLIP-39 mnemonic shares, see [`crate::slip39::split()`]
+    ///
+    /// The seed of signers created from a BIP39 mnemonic is 64 bytes long, resulting in 59
+    /// words shares.
+    pub fn slip39_shares(
+        &self,
+        passphrase: &str,
+        group_threshold: u8,
+        groups: &[(u8, u8)],
+    ) -> Result<Vec<Vec<String>>, NewError> {
+        let secret = self.seed_bytes().ok_or_else(|| {
+            NewError::Slip39(crate::slip39::Slip39Error::InvalidParameters(
+                "seed not available".to_string(),
+            ))
+        })?;
+        Ok(crate::slip39::split(
+            &secret,
+            passphrase,
+            group_threshold,
+            groups,
+            1,
+        )?)
+    }
+
     pub fn random(is_mainnet: bool) -> Result<(Self, Mnemonic), NewError> {
         let mnemonic = Mnemonic::generate(12)?;
         Ok((SwSigner::new(&mnemonic.to_string(), is_mainnet)?, mnemonic))
@@ -98,6 +177,7 @@ impl SwSigner {
             xprv,
             secp: Secp256k1::new(),
             mnemonic: None,
+            slip39_secret: None,
         }
     }
 
@@ -109,6 +189,14 @@ impl SwSigner {
         self.mnemonic.as_ref().map(|m| m.to_seed(""))
     }
 
+    /// The BIP32 seed, from the BIP39 mnemonic or the SLIP-39 shares
+    fn seed_bytes(&self) -> Option<Vec<u8>> {
+        match (&self.mnemonic, &self.slip39_secret) {
+            (Some(m), _) => Some(m.to_seed("").to_vec()),
+            (None, secret) => secret.clone(),
+        }
+    }
+
     pub fn mnemonic(&self) -> Option<Mnemonic> {
         self.mnemonic.clone()
     }
@@ -176,7 +264,7 @@ impl Signer for SwSigner {
 
     fn slip77_master_blinding_key(&self) -> Result<MasterBlindingKey, Self::Error> {
         let seed = self
-            .seed()
+            .seed_bytes()
             .ok_or_else(|| SignError::DeterministicSlip77NotAvailable)?;
         Ok(MasterBlindingKey::from_seed(&seed[..]))
     }
@@ -225,4 +313,24 @@ mod tests {
         assert!(signer.mnemonic().is_none());
         assert!(signer.seed().is_none());
     }
+
+    #[test]
+    fn from_slip39() {
+        let signer = SwSigner::new(lwk_test_util::TEST_MNEMONIC, false).unwrap();
+        let groups = signer.slip39_shares("", 1, &[(2, 3)]).unwrap();
+        let shares: Vec<&str> = groups[0].iter().skip(1).map(|s| s.as_str()).collect();
+        let restored = SwSigner::from_slip39(&shares, "", false).unwrap();
+        assert_eq!(restored.xpub(), signer.xpub());
+        assert_eq!(
+            restored.slip77_master_blinding_key().unwrap(),
+            signer.slip77_master_blinding_key().unwrap()
+        );
+        assert!(restored.mnemonic().is_none());
+
+        let err = SwSigner::from_slip39(&shares[..1], "", false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Insufficient shares, 2 required, 1 provided"
+        );
+    }
 }
@@ -14,10 +14,13 @@ use pulldown_cmark::{CodeBlockKind, Event, Tag};
 use rand::{thread_rng, Rng};
 use serde_json::Value;
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Once;
+use std::sync::{Mutex, Once};
 use std::thread;
 use std::time::Duration;
+use tempfile::TempDir;
 use tracing::metadata::LevelFilter;
 
 const DEFAULT_FEE_RATE: f32 = 100.0;
@@ -77,6 +80,9 @@ fn node_generate(client: &Client, block_num: u32) {
         .unwrap();
 }
 
+/// Extract the content of the fenced code blocks tagged with `code_kind` (e.g. "python")
+///
+/// Blocks tagged differently, like "shell", and indented code blocks are ignored.
 pub fn parse_code_from_markdown(markdown_input: &str, code_kind: &str) -> Vec<String> {
     let parser = pulldown_cmark::Parser::new(markdown_input);
     let mut result = vec![];
@@ -109,6 +115,58 @@ pub fn parse_code_from_markdown(markdown_input: &str, code_kind: &str) -> Vec<St
     result
 }
 
+/// A language of the bindings whose snippets can be run as tests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnippetLanguage {
+    Python,
+    Kotlin,
+    Swift,
+}
+
+impl SnippetLanguage {
+    /// The tag of the markdown fenced code blocks in this language
+    pub fn code_kind(&self) -> &'static str {
+        match self {
+            SnippetLanguage::Python => "python",
+            SnippetLanguage::Kotlin => "kotlin",
+            SnippetLanguage::Swift => "swift",
+        }
+    }
+
+    /// The extension of the scripts in this language, as expected by the uniffi test runners
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SnippetLanguage::Python => "py",
+            SnippetLanguage::Kotlin => "kts",
+            SnippetLanguage::Swift => "swift",
+        }
+    }
+}
+
+/// Write each `lang` code block of `markdown_input` to a script file in `dir`
+///
+/// Files are named `<name>_<n>.<extension>`, where `n` is the position of the block,
+/// and the paths are returned in the same order of the blocks in the document.
+/// Every block must be a complete script, so it can be executed on its own;
+/// the blocks that need a regtest environment can launch one with `TestEnv`.
+pub fn write_code_from_markdown(
+    markdown_input: &str,
+    lang: SnippetLanguage,
+    dir: &Path,
+    name: &str,
+) -> Vec<PathBuf> {
+    fs::create_dir_all(dir).unwrap();
+    parse_code_from_markdown(markdown_input, lang.code_kind())
+        .into_iter()
+        .enumerate()
+        .map(|(i, code)| {
+            let path = dir.join(format!("{name}_{i}.{}", lang.extension()));
+            fs::write(&path, code).unwrap();
+            path
+        })
+        .collect()
+}
+
 /// Serialize and deserialize a PSET
 ///
 /// This allows us to catch early (de)serialization issues,
@@ -120,74 +178,219 @@ pub fn pset_rt(pset: &PartiallySignedTransaction) -> PartiallySignedTransaction
 pub struct TestElectrumServer {
     node: electrsd::bitcoind::BitcoinD,
     pub electrs: electrsd::ElectrsD,
+
+    /// Keeps alive the copy of the snapshot the servers are running on, if any.
+    ///
+    /// Declared last so that it's removed after the processes are stopped.
+    _snapshot_copy: Option<TempDir>,
 }
 
-impl TestElectrumServer {
-    pub fn new(electrs_exec: String, node_exec: String, enable_esplora_http: bool) -> Self {
-        let filter = LevelFilter::from_str(&std::env::var("RUST_LOG").unwrap_or("off".to_string()))
-            .unwrap_or(LevelFilter::OFF);
+/// Height of the chain after the regtest environment has been prepared
+const BOOTSTRAP_HEIGHT: usize = 101;
 
-        init_logging();
+/// Environment variable pointing to a directory where the prepared regtest chain is snapshotted.
+///
+/// If set, [`setup()`] restores the node and electrs data directories from the snapshot instead of
+/// bootstrapping a new chain, creating the snapshot first if it does not exist yet.
+pub const SNAPSHOT_DIR_ENV: &str = "LWK_TEST_SNAPSHOT_DIR";
+
+/// Marker file written once a snapshot is complete, partial snapshots are never restored.
+const SNAPSHOT_READY: &str = "READY";
+
+static SNAPSHOT_LOCK: Mutex<()> = Mutex::new(());
+
+fn node_conf(view_stdout: bool, staticdir: Option<PathBuf>) -> electrsd::bitcoind::Conf<'static> {
+    let mut conf = electrsd::bitcoind::Conf::default();
+    conf.args = vec![
+        "-fallbackfee=0.0001",
+        "-dustrelayfee=0.00000001",
+        "-chain=liquidregtest",
+        "-initialfreecoins=2100000000",
+        "-validatepegin=0",
+        // blocks in a restored snapshot can be old, do not fall back in initial block download
+        "-maxtipage=2147483647",
+    ];
+    conf.view_stdout = view_stdout;
+    conf.p2p = electrsd::bitcoind::P2P::Yes;
+    conf.network = "liquidregtest";
+    conf.staticdir = staticdir;
+    conf
+}
+
+fn electrs_conf(
+    view_stdout: bool,
+    enable_esplora_http: bool,
+    staticdir: Option<PathBuf>,
+) -> electrsd::Conf<'static> {
+    let args = if view_stdout { vec!["-v"] } else { vec![] };
+    let mut conf = electrsd::Conf::default();
+    conf.args = args;
+    conf.view_stderr = view_stdout;
+    conf.http_enabled = enable_esplora_http;
+    conf.network = "liquidregtest";
+    conf.staticdir = staticdir;
+    conf
+}
+
+fn view_stdout() -> bool {
+    let filter = LevelFilter::from_str(&std::env::var("RUST_LOG").unwrap_or("off".to_string()))
+        .unwrap_or(LevelFilter::OFF);
+
+    init_logging();
+
+    filter == LevelFilter::TRACE
+}
+
+/// Mine the first block, fund the node wallet with the initial free coins and mine enough blocks
+/// to have them mature
+fn bootstrap_node(node: &electrsd::bitcoind::BitcoinD) {
+    node_generate(&node.client, 1);
+    node.client.call::<Value>("rescanblockchain", &[]).unwrap();
+    // send initialfreecoins to the node wallet
+    let address = node_getnewaddress(&node.client, None);
+    node.client
+        .call::<Value>(
+            "sendtoaddress",
+            &[
+                address.to_string().into(),
+                "21".into(),
+                "".into(),
+                "".into(),
+                true.into(),
+            ],
+        )
+        .unwrap();
+}
 
-        let view_stdout = filter == LevelFilter::TRACE;
+fn wait_electrs_height(electrs: &electrsd::ElectrsD, expected: usize) {
+    electrs.trigger().unwrap();
 
-        let args = vec![
-            "-fallbackfee=0.0001",
-            "-dustrelayfee=0.00000001",
-            "-chain=liquidregtest",
-            "-initialfreecoins=2100000000",
-            "-validatepegin=0",
-        ];
-        let network = "liquidregtest";
+    let mut i = 120;
+    loop {
+        assert!(i > 0, "1 minute without updates");
+        i -= 1;
+        let height = electrs.client.block_headers_subscribe_raw().unwrap().height;
+        if height == expected {
+            break;
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+}
 
-        let mut conf = electrsd::bitcoind::Conf::default();
-        conf.args = args;
-        conf.view_stdout = view_stdout;
-        conf.p2p = electrsd::bitcoind::P2P::Yes;
-        conf.network = network;
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let dest = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &dest)?;
+        } else {
+            fs::copy(entry.path(), dest)?;
+        }
+    }
+    Ok(())
+}
 
-        let node = electrsd::bitcoind::BitcoinD::with_conf(node_exec, &conf).unwrap();
+impl TestElectrumServer {
+    pub fn new(electrs_exec: String, node_exec: String, enable_esplora_http: bool) -> Self {
+        let view_stdout = view_stdout();
 
-        node_generate(&node.client, 1);
-        node.client.call::<Value>("rescanblockchain", &[]).unwrap();
-        // send initialfreecoins to the node wallet
-        let address = node_getnewaddress(&node.client, None);
-        node.client
-            .call::<Value>(
-                "sendtoaddress",
-                &[
-                    address.to_string().into(),
-                    "21".into(),
-                    "".into(),
-                    "".into(),
-                    true.into(),
-                ],
-            )
-            .unwrap();
+        let node =
+            electrsd::bitcoind::BitcoinD::with_conf(node_exec, &node_conf(view_stdout, None))
+                .unwrap();
+        bootstrap_node(&node);
 
-        let args = if view_stdout { vec!["-v"] } else { vec![] };
-        let mut conf = electrsd::Conf::default();
-        conf.args = args;
-        conf.view_stderr = view_stdout;
-        conf.http_enabled = enable_esplora_http;
-        conf.network = network;
+        let conf = electrs_conf(view_stdout, enable_esplora_http, None);
         let electrs = electrsd::ElectrsD::with_conf(electrs_exec, &node, &conf).unwrap();
 
         node_generate(&node.client, 100);
-        electrs.trigger().unwrap();
-
-        let mut i = 120;
-        loop {
-            assert!(i > 0, "1 minute without updates");
-            i -= 1;
-            let height = electrs.client.block_headers_subscribe_raw().unwrap().height;
-            if height == 101 {
-                break;
-            }
-            thread::sleep(Duration::from_millis(500));
+        wait_electrs_height(&electrs, BOOTSTRAP_HEIGHT);
+
+        Self {
+            node,
+            electrs,
+            _snapshot_copy: None,
+        }
+    }
+
+    /// Prepare the regtest chain as [`TestElectrumServer::new()`] does, then stop the servers and
+    /// keep their data directories in `snapshot_dir`.
+    ///
+    /// Does nothing if a complete snapshot already exists in `snapshot_dir`.
+    pub fn create_snapshot(electrs_exec: String, node_exec: String, snapshot_dir: &Path) {
+        let _lock = SNAPSHOT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        if snapshot_dir.join(SNAPSHOT_READY).exists() {
+            return;
         }
 
-        Self { node, electrs }
+        // Prepare the snapshot in a sibling directory and move it in place when done, so that
+        // concurrent test processes never see a partial snapshot
+        let parent = snapshot_dir.parent().unwrap_or_else(|| Path::new("."));
+        fs::create_dir_all(parent).unwrap();
+        let staging = tempfile::tempdir_in(parent).unwrap();
+        let view_stdout = view_stdout();
+
+        {
+            let node_dir = staging.path().join("node");
+            let conf = node_conf(view_stdout, Some(node_dir));
+            let mut node = electrsd::bitcoind::BitcoinD::with_conf(node_exec, &conf).unwrap();
+            bootstrap_node(&node);
+
+            let electrs_dir = staging.path().join("electrs");
+            let conf = electrs_conf(view_stdout, false, Some(electrs_dir));
+            let mut electrs = electrsd::ElectrsD::with_conf(electrs_exec, &node, &conf).unwrap();
+
+            node_generate(&node.client, 100);
+            wait_electrs_height(&electrs, BOOTSTRAP_HEIGHT);
+
+            electrs.kill().unwrap();
+            node.stop().unwrap();
+        }
+
+        fs::write(staging.path().join(SNAPSHOT_READY), "").unwrap();
+        let staging = staging.into_path();
+        if fs::rename(&staging, snapshot_dir).is_err() {
+            // Another process completed the snapshot first
+            assert!(snapshot_dir.join(SNAPSHOT_READY).exists());
+            fs::remove_dir_all(staging).unwrap();
+        }
+    }
+
+    /// Start the servers from a copy of a snapshot created with
+    /// [`TestElectrumServer::create_snapshot()`].
+    ///
+    /// The snapshot itself is never modified so it can be restored by multiple tests.
+    pub fn from_snapshot(
+        electrs_exec: String,
+        node_exec: String,
+        enable_esplora_http: bool,
+        snapshot_dir: &Path,
+    ) -> Self {
+        assert!(
+            snapshot_dir.join(SNAPSHOT_READY).exists(),
+            "missing snapshot in {}",
+            snapshot_dir.display()
+        );
+        let view_stdout = view_stdout();
+        let copy = TempDir::new().unwrap();
+        copy_dir(snapshot_dir, copy.path()).unwrap();
+
+        let conf = node_conf(view_stdout, Some(copy.path().join("node")));
+        let node = electrsd::bitcoind::BitcoinD::with_conf(node_exec, &conf).unwrap();
+
+        let conf = electrs_conf(
+            view_stdout,
+            enable_esplora_http,
+            Some(copy.path().join("electrs")),
+        );
+        let electrs = electrsd::ElectrsD::with_conf(electrs_exec, &node, &conf).unwrap();
+        wait_electrs_height(&electrs, BOOTSTRAP_HEIGHT);
+
+        Self {
+            node,
+            electrs,
+            _snapshot_copy: Some(copy),
+        }
     }
 
     pub fn generate(&self, blocks: u32) {
@@ -251,6 +454,14 @@ impl TestElectrumServer {
         let raw: serde_json::Value = self.node.client.call("getblockchaininfo", &[]).unwrap();
         raw.get("blocks").unwrap().as_u64().unwrap()
     }
+
+    /// The txids committed by a `gettxoutproof` proof, failing if the proof is invalid
+    pub fn node_verifytxoutproof(&self, proof: &str) -> Vec<String> {
+        self.node
+            .client
+            .call("verifytxoutproof", &[proof.into()])
+            .unwrap()
+    }
 }
 
 fn regtest_policy_asset() -> AssetId {
@@ -260,7 +471,23 @@ fn regtest_policy_asset() -> AssetId {
 pub fn setup(enable_esplora_http: bool) -> TestElectrumServer {
     let electrs_exec = env::var("ELECTRS_LIQUID_EXEC").expect("set ELECTRS_LIQUID_EXEC");
     let node_exec = env::var("ELEMENTSD_EXEC").expect("set ELEMENTSD_EXEC");
-    TestElectrumServer::new(electrs_exec, node_exec, enable_esplora_http)
+    match env::var(SNAPSHOT_DIR_ENV) {
+        Ok(snapshot_dir) => {
+            let snapshot_dir = Path::new(&snapshot_dir);
+            TestElectrumServer::create_snapshot(
+                electrs_exec.clone(),
+                node_exec.clone(),
+                snapshot_dir,
+            );
+            TestElectrumServer::from_snapshot(
+                electrs_exec,
+                node_exec,
+                enable_esplora_http,
+                snapshot_dir,
+            )
+        }
+        Err(_) => TestElectrumServer::new(electrs_exec, node_exec, enable_esplora_http),
+    }
 }
 
 pub fn init_logging() {
@@ -276,6 +503,122 @@ pub fn init_logging() {
     });
 }
 
+/// A request received by [`http_server()`]
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercase
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// The value of the header `name`, case insensitive
+    pub fn header(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.headers
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// A response returned to [`http_server()`]
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    /// Status code and reason, e.g. "200 OK"
+    pub status: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn new(status: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status: status.to_string(),
+            headers: vec![],
+            body: body.into(),
+        }
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Self {
+        Self::new("200 OK", body)
+    }
+
+    pub fn not_found() -> Self {
+        Self::new("404 Not Found", vec![])
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Serve HTTP on a random local port, answering every request with `handler`
+///
+/// Returns the url of the server, e.g. "http://127.0.0.1:1234". Connections are served one at a
+/// time and closed after the response. The server runs until the end of the test process.
+pub fn http_server<F>(mut handler: F) -> String
+where
+    F: FnMut(HttpRequest) -> HttpResponse + Send + 'static,
+{
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                continue;
+            }
+            let mut parts = line.split_whitespace();
+            let method = parts.next().unwrap_or_default().to_string();
+            let path = parts.next().unwrap_or_default().to_string();
+            let mut headers = vec![];
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                match header.trim_end().split_once(':') {
+                    Some((name, value)) => {
+                        headers.push((name.to_lowercase(), value.trim().to_string()))
+                    }
+                    None => break,
+                }
+            }
+            let mut request = HttpRequest {
+                method,
+                path,
+                headers,
+                body: vec![],
+            };
+            let content_length = request
+                .header("content-length")
+                .map_or(0, |v| v.parse().unwrap());
+            request.body = vec![0u8; content_length];
+            reader.read_exact(&mut request.body).unwrap();
+
+            let response = handler(request);
+            let mut head = format!("HTTP/1.1 {}\r\n", response.status);
+            for (name, value) in response.headers.iter() {
+                head.push_str(&format!("{name}: {value}\r\n"));
+            }
+            head.push_str(&format!(
+                "Content-Length: {}\r\nConnection: close\r\n\r\n",
+                response.body.len()
+            ));
+            // The client may have given up already
+            let _ = stream.write_all(head.as_bytes());
+            let _ = stream.write_all(&response.body);
+        }
+    });
+    url
+}
+
 #[allow(dead_code)]
 pub fn prune_proofs(pset: &PartiallySignedTransaction) -> PartiallySignedTransaction {
     let mut pset = pset.clone();
@@ -395,7 +738,10 @@ pub fn wollet_descriptor_string() -> String {
 #[cfg(test)]
 mod test {
 
-    use crate::parse_code_from_markdown;
+    use crate::{
+        http_server, parse_code_from_markdown, write_code_from_markdown, HttpResponse,
+        SnippetLanguage,
+    };
 
     #[test]
     fn test_parse_code_from_markdown() {
@@ -425,4 +771,46 @@ python code
         let res = parse_code_from_markdown(mkdown, "rust");
         assert_eq!(res, vec!["rust\ncode\n".to_string()])
     }
+
+    #[test]
+    fn test_http_server() {
+        use std::io::{Read, Write};
+
+        let url = http_server(|r| {
+            HttpResponse::ok(format!("{} {} {}", r.method, r.path, r.body.len()))
+                .with_header("X-Test", r.header("X-TEST").unwrap_or_default())
+        });
+        let mut stream = std::net::TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+        stream
+            .write_all(b"POST /tx HTTP/1.1\r\nx-test: a\r\nContent-Length: 3\r\n\r\nabc")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("X-Test: a\r\n"));
+        assert!(response.ends_with("\r\n\r\nPOST /tx 3"));
+    }
+
+    #[test]
+    fn test_write_code_from_markdown() {
+        let mkdown = r#"
+```kotlin
+val a = 1
+```
+```shell
+cargo test
+```
+```kotlin
+val b = 2
+```
+"#;
+        let dir = tempfile::tempdir().unwrap();
+        let paths = write_code_from_markdown(mkdown, SnippetLanguage::Kotlin, dir.path(), "doc");
+        assert_eq!(paths.len(), 2);
+        assert_eq!(paths[1], dir.path().join("doc_1.kts"));
+        assert_eq!(std::fs::read_to_string(&paths[1]).unwrap(), "val b = 2\n");
+
+        let paths = write_code_from_markdown(mkdown, SnippetLanguage::Swift, dir.path(), "doc");
+        assert!(paths.is_empty());
+    }
 }
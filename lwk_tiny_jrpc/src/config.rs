@@ -10,6 +10,9 @@ pub struct Config {
     pub num_threads: NonZeroU8,
     /// The path to serve HTTP GET requests from.
     pub serve_dir: Option<PathBuf>,
+    /// If some, all requests must authenticate with this token, either as bearer token or as
+    /// password of the basic authentication.
+    pub auth_token: Option<String>,
 }
 
 impl Config {
@@ -24,6 +27,7 @@ impl Default for Config {
             headers: Vec::new(),
             num_threads: NonZeroU8::new(4).expect("non-zero"),
             serve_dir: None,
+            auth_token: None,
         }
     }
 }
@@ -32,6 +36,7 @@ pub struct ConfigBuilder {
     headers: Vec<Header>,
     num_threads: NonZeroU8,
     serve_dir: Option<PathBuf>,
+    auth_token: Option<String>,
 }
 
 impl ConfigBuilder {
@@ -54,11 +59,17 @@ impl ConfigBuilder {
         self
     }
 
+    pub fn with_auth_token(mut self, token: Option<String>) -> Self {
+        self.auth_token = token;
+        self
+    }
+
     pub fn build(self) -> Config {
         Config {
             headers: self.headers,
             num_threads: self.num_threads,
             serve_dir: self.serve_dir,
+            auth_token: self.auth_token,
         }
     }
 }
@@ -69,6 +80,7 @@ impl Default for ConfigBuilder {
             headers: Vec::new(),
             num_threads: NonZeroU8::new(4).expect("non-zero"),
             serve_dir: None,
+            auth_token: None,
         }
     }
 }
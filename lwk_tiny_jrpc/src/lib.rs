@@ -1,6 +1,7 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(not(test), deny(clippy::unwrap_used))]
 
+use base64::prelude::{Engine, BASE64_STANDARD};
 use std::{
     fmt::Display,
     fs::File,
@@ -94,6 +95,14 @@ impl JsonRpcServer {
                         }
                     };
 
+                    // check the authentication token, if required, before serving any method
+                    if let Some(token) = config.auth_token.as_deref() {
+                        if !is_authorized(&http_request, token) {
+                            send_unauthorized(http_request, &config.headers);
+                            continue;
+                        }
+                    }
+
                     // check request method
                     match http_request.method() {
                         tiny_http::Method::Get => {
@@ -258,10 +267,9 @@ where
 
 fn validate_jsonrpc_request(http_request: &mut tiny_http::Request) -> Result<Request, InnerError> {
     tracing::debug!(
-        "received request - method: {:?}, url: {:?}, headers: {:?}",
+        "received request - method: {:?}, url: {:?}",
         http_request.method(),
         http_request.url(),
-        http_request.headers()
     );
 
     // check content-type header exists
@@ -291,6 +299,54 @@ fn validate_jsonrpc_request(http_request: &mut tiny_http::Request) -> Result<Req
     Ok(request)
 }
 
+// answers a JSON-RPC error to POST requests, so that clients can report it, and 401 to the others
+fn send_unauthorized(http_request: tiny_http::Request, headers: &[Header]) {
+    if http_request.method() == &tiny_http::Method::Post {
+        let response = Response::from_error(None, InnerError::Unauthorized);
+        if let Err(err) = send_jsonrpc_response(http_request, response, headers) {
+            tracing::error!("send_response error: {}", err);
+        }
+    } else {
+        let message = "401: Unauthorized";
+        let response = HttpResponse::from_string(message).with_status_code(401);
+        send_http_response(http_request, response, message);
+    }
+}
+
+// accepts `Authorization: Bearer <token>` or basic authentication with `token` as password,
+// which is what most JSON-RPC clients send when configured with a cookie
+fn is_authorized(http_request: &tiny_http::Request, token: &str) -> bool {
+    let Some(header) = http_request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("authorization"))
+    else {
+        return false;
+    };
+    let value = header.value.as_str().trim();
+    let Some((scheme, credentials)) = value.split_once(' ') else {
+        return false;
+    };
+    let credentials = credentials.trim();
+    if scheme.eq_ignore_ascii_case("bearer") {
+        constant_time_eq(credentials.as_bytes(), token.as_bytes())
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        let Ok(decoded) = BASE64_STANDARD.decode(credentials) else {
+            return false;
+        };
+        match decoded.iter().position(|b| *b == b':') {
+            Some(i) => constant_time_eq(&decoded[i + 1..], token.as_bytes()),
+            None => false,
+        }
+    } else {
+        false
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 fn handle_jsonrpc_request<F, T>(
     request: Request,
     state: Arc<Mutex<T>>,
@@ -487,6 +543,92 @@ mod test {
         assert!(response.error.is_some());
     }
 
+    #[test]
+    fn auth_token() {
+        let addr = "127.0.0.1:0";
+        let server = Server::http(addr).unwrap();
+        let state = Arc::new(Mutex::new(()));
+        let config = Config::builder()
+            .with_auth_token(Some("secret".into()))
+            .build();
+        let rpc = JsonRpcServer::new(server, config, state, process);
+        let port = rpc.port().unwrap();
+        let url = format!("127.0.0.1:{}", port);
+        let params = to_raw_value("hello").unwrap();
+
+        // missing or wrong token
+        for pass in [None, Some("wrong".to_string())] {
+            let client = Client::simple_http(&url, Some("user".into()), pass).unwrap();
+            let request = client.build_request("echo", Some(&params));
+            let response = client.send_request(request).unwrap();
+            let err = response.error.unwrap();
+            assert_eq!(err.code, -32_097);
+            assert!(response.result.is_none());
+        }
+
+        // token as password of the basic authentication
+        let client = Client::simple_http(&url, Some("user".into()), Some("secret".into())).unwrap();
+        let request = client.build_request("echo", Some(&params));
+        let response = client.send_request(request).unwrap();
+        assert_eq!(response.result.unwrap().get(), "\"hello\"");
+
+        // bearer token
+        let http = reqwest::blocking::Client::new();
+        let body = json!({"jsonrpc": "2.0", "id": 1, "method": "echo", "params": "hello"});
+        let url = format!("http://127.0.0.1:{}", port);
+        let resp: Value = http
+            .post(&url)
+            .bearer_auth("secret")
+            .json(&body)
+            .send()
+            .unwrap()
+            .json()
+            .unwrap();
+        assert_eq!(resp["result"], "hello");
+        let resp: Value = http
+            .post(&url)
+            .bearer_auth("wrong")
+            .json(&body)
+            .send()
+            .unwrap()
+            .json()
+            .unwrap();
+        assert_eq!(resp["error"]["code"], -32_097);
+    }
+
+    #[test]
+    fn https() {
+        let cert = include_bytes!("../test/data/cert.pem");
+        let key = include_bytes!("../test/data/key.pem");
+        let ssl = tiny_http::SslConfig {
+            certificate: cert.to_vec(),
+            private_key: key.to_vec(),
+        };
+        let server = Server::https("127.0.0.1:0", ssl).unwrap();
+        let state = Arc::new(Mutex::new(()));
+        let rpc = JsonRpcServer::new(server, Config::default(), state, process);
+        let port = rpc.port().unwrap();
+
+        let root = reqwest::Certificate::from_pem(cert).unwrap();
+        let http = reqwest::blocking::Client::builder()
+            .add_root_certificate(root)
+            .build()
+            .unwrap();
+        let body = json!({"jsonrpc": "2.0", "id": 1, "method": "echo", "params": "hello"});
+        let resp: Value = http
+            .post(format!("https://localhost:{}", port))
+            .json(&body)
+            .send()
+            .unwrap()
+            .json()
+            .unwrap();
+        assert_eq!(resp["result"], "hello");
+
+        // plain http is not served
+        let url = format!("http://127.0.0.1:{}", port);
+        assert!(http.post(url).json(&body).send().is_err());
+    }
+
     #[test]
     fn response_serialization() {
         // result response must not include error key
@@ -615,4 +757,35 @@ mod test {
         assert_eq!(resp.status(), 404);
         assert_eq!(resp.text().unwrap(), "404: File not found");
     }
+
+    #[test]
+    fn http_get_auth_token() {
+        let addr = "127.0.0.1:0";
+        let server = Server::http(addr).unwrap();
+        let state = Arc::new(Mutex::new(()));
+        let dir = tempfile::tempdir().unwrap();
+        let dir_path = dir.into_path();
+        make_file(dir_path.clone(), "file.html".into(), b"<!doctype html>");
+        let config = Config {
+            serve_dir: Some(dir_path),
+            auth_token: Some("secret".into()),
+            ..Default::default()
+        };
+        let rpc = JsonRpcServer::new(server, config, state, process);
+        let port = rpc.port().unwrap();
+        let url = format!("http://127.0.0.1:{}/file.html", port);
+        let http = reqwest::blocking::Client::new();
+
+        // missing or wrong token
+        let resp = http.get(&url).send().unwrap();
+        assert_eq!(resp.status(), 401);
+        let resp = http.get(&url).bearer_auth("wrong").send().unwrap();
+        assert_eq!(resp.status(), 401);
+        let resp = http.request(reqwest::Method::OPTIONS, &url).send().unwrap();
+        assert_eq!(resp.status(), 401);
+
+        let resp = http.get(&url).bearer_auth("secret").send().unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.text().unwrap(), "<!doctype html>");
+    }
 }
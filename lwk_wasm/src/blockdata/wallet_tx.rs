@@ -111,6 +111,10 @@ mod tests {
             timestamp: Some(124),
             inputs: vec![Some(tx_out.clone())],
             outputs: vec![None, Some(tx_out.clone())],
+            zero_conf: None,
+            address_reused: vec![false, true],
+            assets: Default::default(),
+            status: lwk_wollet::TxStatus::Confirmed(4),
         };
 
         let wallet_tx: WalletTx = el.clone().into();
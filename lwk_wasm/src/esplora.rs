@@ -22,6 +22,13 @@ impl EsploraClient {
         Ok(update.map(Into::into))
     }
 
+    /// Fee rates estimated by the server in sat/kvb, by confirmation target in blocks
+    #[wasm_bindgen(js_name = feeEstimates)]
+    pub async fn fee_estimates(&self) -> Result<JsValue, Error> {
+        let estimates = self.inner.fee_estimates().await?;
+        Ok(serde_wasm_bindgen::to_value(&estimates)?)
+    }
+
     pub async fn broadcast(&mut self, pset: &Pset) -> Result<Txid, Error> {
         let tx = pset.extract_tx()?;
         let txid = self.inner.broadcast(&(tx.into())).await?;
@@ -80,6 +80,22 @@ impl PsetDetails {
             .map(Into::into)
             .collect()
     }
+
+    /// Descriptions of the suspicious characteristics of the PSET with respect to the wallet
+    pub fn warnings(&self) -> Vec<String> {
+        self.inner
+            .warnings
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    /// The indexes of the outputs going back to the wallet change chain, which can be hidden
+    /// when asking the user to confirm the transaction
+    #[wasm_bindgen(js_name = changeOutputs)]
+    pub fn change_outputs(&self) -> Vec<u32> {
+        self.inner.change_outputs()
+    }
 }
 
 #[wasm_bindgen]
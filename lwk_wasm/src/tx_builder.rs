@@ -57,6 +57,14 @@ impl TxBuilder {
         self.inner.drain_lbtc_to(address.into()).into()
     }
 
+    /// Spend all the UTXOs of `asset` and send the excess to `address`
+    #[wasm_bindgen(js_name = drainAsset)]
+    pub fn drain_asset(self, asset: &AssetId, address: Address) -> TxBuilder {
+        self.inner
+            .drain_asset((*asset).into(), address.into())
+            .into()
+    }
+
     /// Add a recipient receiving L-BTC
     ///
     /// Errors if address's network is incompatible
@@ -81,7 +81,10 @@ mod tests {
     fn test_update() {
         let bytes = update_test_vector_bytes();
         let update = crate::Update::new(&bytes).unwrap();
-        assert_eq!(update.serialize().unwrap(), bytes);
+        // The test vector has been encoded with a previous version
+        let serialized = update.serialize().unwrap();
+        assert_eq!(serialized[4], lwk_wollet::UPDATE_VERSION);
+        assert_eq!(crate::Update::new(&serialized).unwrap(), update);
         assert!(!update.only_tip());
 
         let base64 = include_str!("../test_data/update.base64");
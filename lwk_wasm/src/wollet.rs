@@ -1,4 +1,6 @@
-use crate::{AddressResult, Error, Network, Pset, PsetDetails, Update, WalletTx, WolletDescriptor};
+use crate::{
+    AddressResult, Error, Network, OutPoint, Pset, PsetDetails, Update, WalletTx, WolletDescriptor,
+};
 use lwk_jade::derivation_path_to_vec;
 use lwk_wollet::elements::pset::PartiallySignedTransaction;
 use lwk_wollet::elements_miniscript::ForEachKey;
@@ -108,6 +110,24 @@ impl Wollet {
         self.inner.tip().into()
     }
 
+    /// Lock a wallet UTXO, so that it is not spent by new transactions until unlocked
+    #[wasm_bindgen(js_name = lockUtxo)]
+    pub fn lock_utxo(&mut self, outpoint: &OutPoint) -> Result<(), Error> {
+        Ok(self.inner.lock_utxo(outpoint.into())?)
+    }
+
+    /// Unlock a UTXO locked with `lockUtxo`
+    #[wasm_bindgen(js_name = unlockUtxo)]
+    pub fn unlock_utxo(&mut self, outpoint: &OutPoint) -> Result<(), Error> {
+        Ok(self.inner.unlock_utxo(outpoint.into())?)
+    }
+
+    /// Whether the given outpoint is a locked UTXO
+    #[wasm_bindgen(js_name = isUtxoLocked)]
+    pub fn is_utxo_locked(&self, outpoint: &OutPoint) -> bool {
+        self.inner.is_utxo_locked(&outpoint.into())
+    }
+
     /// wraps [lwk_wollet::Wollet::never_scanned()]
     #[wasm_bindgen(js_name = neverScanned)]
     pub fn never_scanned(&self) -> bool {
@@ -0,0 +1,485 @@
+//! Client-side encrypted backups of the wallet metadata
+//!
+//! Funds can be recovered from the blockchain with the descriptor, but labels and memos exist
+//! only on the device. A [`WalletBackup`] bundles them and is encrypted with a key derived from
+//! the descriptor, so that it can be stored on a third party server, for instance with a
+//! [`BackupClient`], and restored by whoever can reconstruct the descriptor.
+//!
+//! The serialization is the format version, the big endian revision, the nonce and the
+//! ciphertext. The format version and the revision are authenticated as associated data, so that
+//! a server can't pass off a revision as another one.
+
+use std::collections::BTreeMap;
+
+use aes_gcm_siv::aead::generic_array::GenericArray;
+use aes_gcm_siv::aead::{AeadMutInPlace, NewAead};
+use aes_gcm_siv::Aes256GcmSiv;
+use elements::hashes::{sha256t_hash_newtype, Hash};
+use rand::{thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Wollet, WolletDescriptor};
+
+/// The format version of the encrypted backups, the first byte of the serialization
+pub const BACKUP_FORMAT_VERSION: u8 = 1;
+
+/// Length of the format version and of the revision preceding the nonce
+const HEADER_LEN: usize = 5;
+
+/// Length of the nonce preceding the ciphertext
+const NONCE_LEN: usize = 12;
+
+/// Length of the authentication tag at the end of the ciphertext
+const TAG_LEN: usize = 16;
+
+sha256t_hash_newtype! {
+    /// The tag of the hash
+    pub struct BackupIdTag = hash_str("LWK-Backup-Id/1.0");
+
+    /// A tagged hash identifying the backups of a wallet without revealing its descriptor
+    #[hash_newtype(forward)]
+    pub struct BackupIdHash(_);
+}
+
+sha256t_hash_newtype! {
+    /// The tag of the hash
+    pub struct BackupKeyTag = hash_str("LWK-Backup-Encryption-Key/1.0");
+
+    /// A tagged hash to generate the key encrypting the backups, distinct from the key used to
+    /// persist the updates, see [`WolletDescriptor::cipher()`]
+    #[hash_newtype(forward)]
+    pub struct BackupKeyHash(_);
+}
+
+fn cipher(desc: &WolletDescriptor) -> Aes256GcmSiv {
+    let key_bytes = BackupKeyHash::hash(desc.to_string().as_bytes()).to_byte_array();
+    Aes256GcmSiv::new(GenericArray::from_slice(&key_bytes))
+}
+
+/// The wallet metadata which cannot be recovered from the blockchain
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WalletBackup {
+    /// The revision of the backup, set by [`BackupClient::upload()`]
+    pub revision: u32,
+
+    /// The wallet descriptor
+    pub descriptor: String,
+
+    /// The wallet labels in the BIP329 JSON lines format, see [`Wollet::export_labels()`]
+    pub labels: String,
+
+    /// Memos kept by the application, for instance keyed by txid or address
+    #[serde(default)]
+    pub memos: BTreeMap<String, String>,
+}
+
+impl WalletBackup {
+    /// Encrypt the backup with the key derived from `desc`
+    pub fn encrypt(&self, desc: &WolletDescriptor) -> Result<Vec<u8>, Error> {
+        let mut result = vec![BACKUP_FORMAT_VERSION];
+        result.extend(self.revision.to_be_bytes());
+
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        thread_rng().fill(&mut nonce_bytes);
+        let nonce = GenericArray::from_slice(&nonce_bytes);
+        let mut plaintext = serde_json::to_vec(self)?;
+        cipher(desc).encrypt_in_place(nonce, &result, &mut plaintext)?;
+
+        result.extend(nonce_bytes);
+        result.extend(plaintext);
+        Ok(result)
+    }
+
+    /// Decrypt a backup encrypted with [`WalletBackup::encrypt()`]
+    pub fn decrypt(bytes: &[u8], desc: &WolletDescriptor) -> Result<Self, Error> {
+        match bytes.first() {
+            Some(&BACKUP_FORMAT_VERSION) => {}
+            Some(v) => {
+                return Err(Error::InvalidBackup(format!(
+                    "unsupported format version {v}"
+                )))
+            }
+            None => return Err(Error::InvalidBackup("empty".into())),
+        }
+        if bytes.len() < HEADER_LEN + NONCE_LEN + TAG_LEN {
+            return Err(Error::InvalidBackup("too short".into()));
+        }
+        let (header, rest) = bytes.split_at(HEADER_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let mut plaintext = ciphertext.to_vec();
+        cipher(desc).decrypt_in_place(GenericArray::from_slice(nonce), header, &mut plaintext)?;
+
+        let backup: WalletBackup = serde_json::from_slice(&plaintext)?;
+        if backup.descriptor != desc.to_string() {
+            return Err(Error::BackupWalletMismatch);
+        }
+        let revision = u32::from_be_bytes(header[1..].try_into().expect("4 bytes"));
+        if backup.revision != revision {
+            return Err(Error::InvalidBackup(format!(
+                "revision {} encrypted as {revision}",
+                backup.revision
+            )));
+        }
+        Ok(backup)
+    }
+
+    /// The identifier of the backups of the wallet with descriptor `desc`
+    pub fn id(desc: &WolletDescriptor) -> String {
+        BackupIdHash::hash(desc.to_string().as_bytes()).to_string()
+    }
+}
+
+impl Wollet {
+    /// Bundle the wallet metadata in a backup, `memos` are the application memos to include
+    pub fn backup(&self, memos: BTreeMap<String, String>) -> WalletBackup {
+        WalletBackup {
+            revision: 0,
+            descriptor: self.wollet_descriptor().to_string(),
+            labels: self.export_labels(),
+            memos,
+        }
+    }
+
+    /// Restore the labels of a backup of this wallet, returns the number of labels imported
+    ///
+    /// The wallet must have been synced at least once. The memos of the backup are left to the
+    /// application, see [`WalletBackup::memos`].
+    pub fn restore_backup(&mut self, backup: &WalletBackup) -> Result<usize, Error> {
+        if backup.descriptor != self.wollet_descriptor().to_string() {
+            return Err(Error::BackupWalletMismatch);
+        }
+        self.import_labels(&backup.labels)
+    }
+}
+
+/// A client syncing the encrypted wallet backups to a remote endpoint
+///
+/// Any endpoint storing objects with HTTP `PUT`, honoring `If-None-Match: *`, and returning them
+/// with `GET` is supported, for instance a WebDAV server or an S3 bucket. Requests are not signed
+/// with AWS Signature Version 4, so an S3 bucket must accept the requests as they are, for
+/// instance through a proxy adding the credentials, or the credentials must be passed as headers
+/// with [`BackupClient::with_header()`].
+///
+/// Every upload is stored as a new revision at `<base_url>/<id>/<revision>`, created only if it
+/// doesn't exist, so that concurrent uploads from several devices don't overwrite each other.
+/// `<base_url>/<id>/latest` contains the number of the last one, where `<id>` is
+/// [`WalletBackup::id()`]. The server only sees encrypted data.
+#[cfg(feature = "cloud_backup")]
+pub struct BackupClient {
+    base_url: String,
+    headers: Vec<(String, String)>,
+    timeout: std::time::Duration,
+    client: reqwest::blocking::Client,
+}
+
+/// The default timeout of the requests of a [`BackupClient`]
+#[cfg(feature = "cloud_backup")]
+pub const BACKUP_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// The revisions tried by [`BackupClient::upload()`] when other devices upload concurrently
+#[cfg(feature = "cloud_backup")]
+const MAX_UPLOAD_ATTEMPTS: u32 = 10;
+
+#[cfg(feature = "cloud_backup")]
+impl BackupClient {
+    /// Create a client storing the backups under `base_url`
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            headers: vec![],
+            timeout: BACKUP_TIMEOUT,
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    /// Add a header to every request, for instance `Authorization`
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Fail the requests not completed within `timeout`, [`BACKUP_TIMEOUT`] by default
+    pub fn with_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn url(&self, desc: &WolletDescriptor, object: &str) -> String {
+        format!("{}/{}/{}", self.base_url, WalletBackup::id(desc), object)
+    }
+
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::blocking::RequestBuilder {
+        let mut request = self.client.request(method, url).timeout(self.timeout);
+        for (name, value) in self.headers.iter() {
+            request = request.header(name, value);
+        }
+        request
+    }
+
+    fn get(&self, url: &str) -> Result<Option<Vec<u8>>, Error> {
+        let response = self.request(reqwest::Method::GET, url).send()?;
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(Error::BackupServer {
+                status: status.as_u16(),
+                url: url.to_string(),
+            });
+        }
+        Ok(Some(response.bytes()?.to_vec()))
+    }
+
+    fn exists(&self, url: &str) -> Result<bool, Error> {
+        let status = self.request(reqwest::Method::HEAD, url).send()?.status();
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        if !status.is_success() {
+            return Err(Error::BackupServer {
+                status: status.as_u16(),
+                url: url.to_string(),
+            });
+        }
+        Ok(true)
+    }
+
+    /// Store `body` at `url`, if `create` only if there is no object yet
+    ///
+    /// Returns false if `create` and there is already an object at `url`
+    fn put(&self, url: &str, body: Vec<u8>, create: bool) -> Result<bool, Error> {
+        let mut request = self.request(reqwest::Method::PUT, url).body(body);
+        if create {
+            request = request.header(reqwest::header::IF_NONE_MATCH, "*");
+        }
+        let status = request.send()?.status();
+        if create && status == reqwest::StatusCode::PRECONDITION_FAILED {
+            return Ok(false);
+        }
+        if !status.is_success() {
+            return Err(Error::BackupServer {
+                status: status.as_u16(),
+                url: url.to_string(),
+            });
+        }
+        Ok(true)
+    }
+
+    /// The last revision uploaded for the wallet with descriptor `desc`, if any
+    ///
+    /// The revisions following the one in `latest` are checked too, since `latest` is updated
+    /// after the revision and it may be stale if another device uploaded concurrently.
+    pub fn latest_revision(&self, desc: &WolletDescriptor) -> Result<Option<u32>, Error> {
+        let url = self.url(desc, "latest");
+        let mut revision: u32 = match self.get(&url)? {
+            Some(bytes) => {
+                let s = String::from_utf8_lossy(&bytes);
+                s.trim().parse().map_err(|_| {
+                    Error::InvalidBackup(format!("invalid latest revision at {url}"))
+                })?
+            }
+            None => 0,
+        };
+        while let Some(next) = revision.checked_add(1) {
+            if !self.exists(&self.url(desc, &next.to_string()))? {
+                break;
+            }
+            revision = next;
+        }
+        Ok((revision > 0).then_some(revision))
+    }
+
+    /// Encrypt and upload `backup` as a new revision, returns the revision number
+    ///
+    /// If another device uploaded the same revision in the meantime, the backup is uploaded as
+    /// the following one.
+    pub fn upload(&self, backup: &WalletBackup) -> Result<u32, Error> {
+        let desc: WolletDescriptor = backup.descriptor.parse()?;
+        let mut revision = self
+            .latest_revision(&desc)?
+            .map_or(1, |r| r.saturating_add(1));
+        for _ in 0..MAX_UPLOAD_ATTEMPTS {
+            let backup = WalletBackup {
+                revision,
+                ..backup.clone()
+            };
+            let url = self.url(&desc, &revision.to_string());
+            if self.put(&url, backup.encrypt(&desc)?, true)? {
+                self.put(
+                    &self.url(&desc, "latest"),
+                    revision.to_string().into_bytes(),
+                    false,
+                )?;
+                return Ok(revision);
+            }
+            revision = revision.saturating_add(1);
+        }
+        Err(Error::BackupServer {
+            status: reqwest::StatusCode::PRECONDITION_FAILED.as_u16(),
+            url: self.url(&desc, &revision.to_string()),
+        })
+    }
+
+    /// Download and decrypt the backup of the wallet with descriptor `desc` at `revision`, or the
+    /// latest one if `None`
+    pub fn download(
+        &self,
+        desc: &WolletDescriptor,
+        revision: Option<u32>,
+    ) -> Result<WalletBackup, Error> {
+        let revision = match revision {
+            Some(r) => r,
+            None => self
+                .latest_revision(desc)?
+                .ok_or_else(|| Error::BackupNotFound(self.url(desc, "latest")))?,
+        };
+        let url = self.url(desc, &revision.to_string());
+        let bytes = self.get(&url)?.ok_or(Error::BackupNotFound(url))?;
+        let backup = WalletBackup::decrypt(&bytes, desc)?;
+        // The server could serve an older revision to roll back the wallet metadata
+        if backup.revision != revision {
+            return Err(Error::InvalidBackup(format!(
+                "requested revision {revision}, received {}",
+                backup.revision
+            )));
+        }
+        Ok(backup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wollet::tests::receive_update;
+    use crate::ElementsNetwork;
+
+    fn synced_wollet(desc: WolletDescriptor) -> Wollet {
+        let mut wollet = Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let update = receive_update(&wollet, 10_000);
+        wollet.apply_update(update).unwrap();
+        wollet
+    }
+
+    fn wollet() -> Wollet {
+        synced_wollet(lwk_test_util::wollet_descriptor_string().parse().unwrap())
+    }
+
+    #[test]
+    fn test_backup_roundtrip() {
+        let mut wollet = wollet();
+        let desc = wollet.wollet_descriptor();
+        let txid = "0000000000000000000000000000000000000000000000000000000000000001";
+        let labels = format!("{{\"type\":\"tx\",\"ref\":\"{txid}\",\"label\":\"rent\"}}\n");
+        wollet.import_labels(&labels).unwrap();
+        let memos = BTreeMap::from([(txid.to_string(), "paid to Alice".to_string())]);
+        let backup = wollet.backup(memos);
+
+        let bytes = backup.encrypt(&desc).unwrap();
+        assert_eq!(bytes[0], BACKUP_FORMAT_VERSION);
+        assert_eq!(WalletBackup::decrypt(&bytes, &desc).unwrap(), backup);
+
+        let mut restored = synced_wollet(desc.clone());
+        assert_eq!(restored.restore_backup(&backup).unwrap(), 1);
+        assert_eq!(restored.labels(), wollet.labels());
+
+        let err = WalletBackup::decrypt(&bytes[..5], &wollet.wollet_descriptor()).unwrap_err();
+        assert!(matches!(err, Error::InvalidBackup(_)));
+
+        // The revision is authenticated
+        let mut tampered = bytes.clone();
+        tampered[4] ^= 1;
+        assert!(WalletBackup::decrypt(&tampered, &desc).is_err());
+
+        // The key is not the one encrypting the updates
+        let update_encrypted = crate::update::encrypt(serde_json::to_vec(&backup).unwrap(), &desc);
+        let mut bytes = bytes[..5].to_vec();
+        bytes.extend(update_encrypted.unwrap());
+        assert!(WalletBackup::decrypt(&bytes, &desc).is_err());
+    }
+
+    #[test]
+    fn test_backup_other_wallet() {
+        let backup = wollet().backup(BTreeMap::new());
+        let other = lwk_test_util::wollet_descriptor_string2();
+        let other: WolletDescriptor = other.parse().unwrap();
+        let mut other_wollet = synced_wollet(other.clone());
+        let err = other_wollet.restore_backup(&backup).unwrap_err();
+        assert!(matches!(err, Error::BackupWalletMismatch));
+
+        let bytes = backup.encrypt(&wollet().wollet_descriptor()).unwrap();
+        assert!(WalletBackup::decrypt(&bytes, &other).is_err());
+        assert_ne!(
+            WalletBackup::id(&other),
+            WalletBackup::id(&wollet().wollet_descriptor())
+        );
+    }
+
+    /// Serve objects stored with `PUT` and returned with `GET`, as a WebDAV server would, a `PUT`
+    /// with `If-None-Match: *` fails if the object exists
+    #[cfg(feature = "cloud_backup")]
+    fn object_server() -> String {
+        use lwk_test_util::HttpResponse;
+        use std::collections::HashMap;
+
+        let mut objects: HashMap<String, Vec<u8>> = HashMap::new();
+        let url = lwk_test_util::http_server(move |request| {
+            let if_none_match = request.header("If-None-Match").is_some();
+            match (request.method.as_str(), objects.get(&request.path)) {
+                ("PUT", Some(_)) if if_none_match => {
+                    HttpResponse::new("412 Precondition Failed", vec![])
+                }
+                ("PUT", _) => {
+                    objects.insert(request.path, request.body);
+                    HttpResponse::new("201 Created", vec![])
+                }
+                ("GET", Some(object)) => HttpResponse::ok(object.clone()),
+                ("HEAD", Some(_)) => HttpResponse::ok(vec![]),
+                _ => HttpResponse::not_found(),
+            }
+        });
+        format!("{url}/backups")
+    }
+
+    #[cfg(feature = "cloud_backup")]
+    #[test]
+    fn test_backup_client() {
+        let wollet = wollet();
+        let desc = wollet.wollet_descriptor();
+        let client = BackupClient::new(&object_server()).with_header("Authorization", "Bearer x");
+        assert_eq!(client.latest_revision(&desc).unwrap(), None);
+        let err = client.download(&desc, None).unwrap_err();
+        assert!(matches!(err, Error::BackupNotFound(_)));
+
+        let first = wollet.backup(BTreeMap::new());
+        assert_eq!(client.upload(&first).unwrap(), 1);
+        let memos = BTreeMap::from([("k".to_string(), "v".to_string())]);
+        let second = wollet.backup(memos);
+        assert_eq!(client.upload(&second).unwrap(), 2);
+        assert_eq!(client.latest_revision(&desc).unwrap(), Some(2));
+
+        let latest = client.download(&desc, None).unwrap();
+        assert_eq!(latest.revision, 2);
+        assert_eq!(latest.memos, second.memos);
+        let old = client.download(&desc, Some(1)).unwrap();
+        assert_eq!(old.revision, 1);
+        assert!(old.memos.is_empty());
+
+        // Revisions are never overwritten
+        let url = client.url(&desc, "1");
+        assert!(!client.put(&url, vec![], true).unwrap());
+
+        // A stale latest revision, as if another device uploaded concurrently
+        let latest = client.url(&desc, "latest");
+        assert!(client.put(&latest, b"1".to_vec(), false).unwrap());
+        assert_eq!(client.latest_revision(&desc).unwrap(), Some(2));
+        assert_eq!(client.upload(&first).unwrap(), 3);
+        assert_eq!(client.download(&desc, None).unwrap().revision, 3);
+
+        // The server serves an old revision as the latest one
+        let bytes = client.get(&client.url(&desc, "1")).unwrap().unwrap();
+        client.put(&client.url(&desc, "4"), bytes, true).unwrap();
+        let err = client.download(&desc, None).unwrap_err();
+        assert!(matches!(err, Error::InvalidBackup(_)));
+    }
+}
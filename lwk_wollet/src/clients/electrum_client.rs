@@ -1,15 +1,27 @@
+use crate::fee::FEE_TARGETS;
 use crate::store::Height;
 use crate::Error;
-use electrum_client::ScriptStatus;
 use electrum_client::{Client, ConfigBuilder, ElectrumApi, GetHistoryRes};
+use electrum_client::{Param, ScriptStatus};
 use elements::encode::deserialize as elements_deserialize;
 use elements::encode::serialize as elements_serialize;
 use elements::Address;
 use elements::{bitcoin, BlockHash, BlockHeader, Script, Transaction, Txid};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, PoisonError};
 
-use super::History;
+use super::{spv, BlockchainBackend, History};
+
+/// Electrum protocol versions requested to the server, in order of preference
+///
+/// Servers accepting only an exact version may reject the first ones,
+/// in that case a new connection is opened to request the next one.
+const PROTOCOL_VERSIONS: [&str; 2] = ["1.4.2", "1.4"];
+
+/// Headers before a dynafed transition searched for the proposal of the new federation
+const MAX_PROPOSAL_SEARCH: Height = 10;
 
 /// A client to issue TCP requests to an electrum server.
 pub struct ElectrumClient {
@@ -17,7 +29,29 @@ pub struct ElectrumClient {
 
     tip: BlockHeader,
 
-    script_status: HashMap<Script, ScriptStatus>,
+    /// The last status of the scripts subscribed, `None` if the script has no history
+    script_status: HashMap<Script, Option<ScriptStatus>>,
+
+    /// Headers already downloaded by height, cleared on reorgs
+    headers: Mutex<HashMap<Height, BlockHeader>>,
+
+    /// The protocol version agreed with the server, `None` if the negotiation failed
+    protocol_version: Option<String>,
+
+    /// Whether to batch requests, disabled if the server rejects batches
+    batching: AtomicBool,
+
+    /// If set, headers must be signed by these federations
+    federations: Option<Mutex<spv::Federations>>,
+
+    /// Transactions whose inclusion in the block at the given height has been verified
+    verified_txs: Mutex<HashSet<(Txid, Height)>>,
+
+    /// The asset registry queried for the assets received in scans
+    registry_url: Option<String>,
+
+    /// Number of batches of scripts of every chain requested together while scanning
+    scan_batches: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -59,6 +93,7 @@ impl Debug for ElectrumClient {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ElectrumClient")
             .field("tip", &self.tip)
+            .field("protocol_version", &self.protocol_version)
             .finish()
     }
 }
@@ -66,6 +101,49 @@ impl Debug for ElectrumClient {
 #[derive(Default)]
 pub struct ElectrumOptions {
     timeout: Option<u8>,
+    signblockscript: Option<Script>,
+    registry_url: Option<String>,
+    scan_batches: Option<u32>,
+}
+
+impl ElectrumOptions {
+    /// Verify the data returned by the server, like an SPV client
+    ///
+    /// Headers must be signed according to `signblockscript`, the script of the federation
+    /// signing the blocks, and confirmed wallet transactions must have a valid merkle proof.
+    /// Requests fail with [`Error::InvalidSpvProof`] if the server data doesn't verify.
+    ///
+    /// `signblockscript` must be the script of the federation signing the current tip. Headers
+    /// signed by other federations are verified following the dynamic federation transitions:
+    /// a following federation is trusted if proposed in the headers preceding the transition,
+    /// a previous one if its last header is committed by the first header after the transition.
+    pub fn verify_headers(mut self, signblockscript: Script) -> Self {
+        self.signblockscript = Some(signblockscript);
+        self
+    }
+
+    /// Fail the requests not answered within `secs` seconds, instead of waiting indefinitely for
+    /// a server that stopped responding
+    pub fn timeout(mut self, secs: u8) -> Self {
+        self.timeout = Some(secs);
+        self
+    }
+
+    /// Fetch and verify the registry info of the assets received in scans from the asset
+    /// registry at `registry_url`, see [`BlockchainBackend::registry_url()`]
+    ///
+    /// The registry is queried only with the `esplora` feature enabled.
+    pub fn registry_url(mut self, registry_url: &str) -> Self {
+        self.registry_url = Some(registry_url.to_string());
+        self
+    }
+
+    /// Request `scan_batches` batches of scripts of every chain with a single batch request
+    /// while scanning, see [`BlockchainBackend::scan_batches()`]
+    pub fn scan_batches(mut self, scan_batches: u32) -> Self {
+        self.scan_batches = Some(scan_batches.max(1));
+        self
+    }
 }
 
 impl ElectrumClient {
@@ -76,15 +154,185 @@ impl ElectrumClient {
 
     /// Creates an Electrum client specifying non default options like timeout
     pub fn with_options(url: &ElectrumUrl, options: ElectrumOptions) -> Result<Self, Error> {
-        let client = url.build_client(&options)?;
+        let (client, protocol_version) = negotiate_version(url, &options)?;
         let header = client.block_headers_subscribe_raw()?;
         let tip: BlockHeader = elements_deserialize(&header.header)?;
 
-        Ok(Self {
+        let client = Self {
             client,
-            tip,
+            tip: tip.clone(),
             script_status: HashMap::new(),
-        })
+            headers: Mutex::new(HashMap::new()),
+            // Servers not implementing `server.version` are likely minimal, don't risk batching
+            batching: AtomicBool::new(protocol_version.is_some()),
+            protocol_version,
+            federations: options
+                .signblockscript
+                .map(|s| Mutex::new(spv::Federations::new(s))),
+            verified_txs: Mutex::new(HashSet::new()),
+            registry_url: options.registry_url,
+            scan_batches: options.scan_batches.unwrap_or(1),
+        };
+        client.verify_header(header.height as Height, &tip)?;
+        Ok(client)
+    }
+
+    /// The electrum protocol version agreed with the server
+    ///
+    /// `None` if the server didn't accept any of the versions supported by this client,
+    /// or doesn't implement the version negotiation.
+    pub fn protocol_version(&self) -> Option<&str> {
+        self.protocol_version.as_deref()
+    }
+
+    /// Verify the header at `height`, if headers verification is enabled
+    fn verify_header(&self, height: Height, header: &BlockHeader) -> Result<(), Error> {
+        if let Some(federations) = self.federations.as_ref() {
+            if header.height != height {
+                return Err(Error::InvalidSpvProof(format!(
+                    "requested header {height}, got {}",
+                    header.height
+                )));
+            }
+            let mut federations = federations.lock().map_err(lock_err)?;
+            // Every transition followed trusts a new federation, otherwise it fails
+            while !federations.is_trusted(header) {
+                self.follow_transition(&mut federations, header)?;
+            }
+            federations.verify(header)?;
+        }
+        Ok(())
+    }
+
+    /// Trust the federation of the transition closest to a verified header, between it and
+    /// `header`, signed by an untrusted federation
+    ///
+    /// A following federation is trusted if proposed in the verified headers preceding the
+    /// transition, a previous one if its last header is committed by the first verified header
+    /// after the transition.
+    fn follow_transition(
+        &self,
+        federations: &mut spv::Federations,
+        header: &BlockHeader,
+    ) -> Result<(), Error> {
+        let unknown = || {
+            Error::InvalidSpvProof(format!(
+                "header {}: signed by an unknown federation",
+                header.height
+            ))
+        };
+        let fetch = |height: Height| -> Result<BlockHeader, Error> {
+            let header: BlockHeader =
+                elements_deserialize(&self.client.block_header_raw(height as usize)?)?;
+            if header.height != height {
+                return Err(Error::InvalidSpvProof(format!(
+                    "requested header {height}, got {}",
+                    header.height
+                )));
+            }
+            Ok(header)
+        };
+
+        if let Some(mut trusted) = federations.verified_below(header.height) {
+            // Bisect the first header signed by an untrusted federation
+            let mut first = header.clone();
+            while first.height - trusted > 1 {
+                let middle = fetch(trusted + (first.height - trusted) / 2)?;
+                if federations.is_trusted(&middle) {
+                    federations.verify(&middle)?;
+                    trusted = middle.height;
+                } else {
+                    first = middle;
+                }
+            }
+            let next = spv::signblockscript(&first).ok_or_else(unknown)?;
+
+            // Most of the headers before a transition propose the new federation
+            let start = (trusted + 1).saturating_sub(MAX_PROPOSAL_SEARCH);
+            for height in (start..=trusted).rev() {
+                let candidate = fetch(height)?;
+                if !federations.is_trusted(&candidate) {
+                    continue;
+                }
+                federations.verify(&candidate)?;
+                if federations.follow_proposal(&candidate, next) {
+                    return Ok(());
+                }
+            }
+            Err(unknown())
+        } else {
+            let trusted = federations
+                .verified_above(header.height)
+                .ok_or_else(unknown)?;
+            // Bisect the last header signed by an untrusted federation
+            let mut trusted = fetch(trusted)?;
+            let mut last = header.clone();
+            while trusted.height - last.height > 1 {
+                let middle = fetch(last.height + (trusted.height - last.height) / 2)?;
+                if federations.is_trusted(&middle) {
+                    trusted = middle;
+                } else {
+                    last = middle;
+                }
+            }
+            federations.verify(&trusted)?;
+            if federations.follow_parent(&trusted, &last) {
+                Ok(())
+            } else {
+                Err(unknown())
+            }
+        }
+    }
+
+    /// Verify the confirmed transactions in `history` are included in their blocks, if headers
+    /// verification is enabled
+    fn verify_history(&self, history: &[History]) -> Result<(), Error> {
+        if self.federations.is_none() {
+            return Ok(());
+        }
+        for el in history.iter().filter(|el| el.height > 0) {
+            let key = (el.txid, el.height as Height);
+            if self.verified_txs.lock().map_err(lock_err)?.contains(&key) {
+                continue;
+            }
+            let txid = bitcoin::Txid::from_raw_hash(el.txid.to_raw_hash());
+            let proof = self
+                .client
+                .transaction_get_merkle(&txid, el.height as usize)?;
+            let header = self.header(el.height as Height)?;
+            spv::verify_merkle_proof(&el.txid, &header, proof.pos, &proof.merkle)?;
+            self.verified_txs.lock().map_err(lock_err)?.insert(key);
+        }
+        Ok(())
+    }
+
+    /// Issue a batch request for `items`, or a request for each item if the server can't batch
+    ///
+    /// If a batch fails but single requests succeed, the server is considered not supporting
+    /// batches and batching is disabled for the following requests.
+    fn batch_or_single<T, R>(
+        &self,
+        items: &[T],
+        batch: impl Fn(&Client, &[T]) -> Result<Vec<R>, electrum_client::Error>,
+        single: impl Fn(&Client, &T) -> Result<R, electrum_client::Error>,
+    ) -> Result<Vec<R>, Error> {
+        let batch_err = if self.batching.load(Ordering::Relaxed) {
+            match batch(&self.client, items) {
+                Ok(result) => return Ok(result),
+                Err(e) => Some(e),
+            }
+        } else {
+            None
+        };
+        let result = items
+            .iter()
+            .map(|item| single(&self.client, item))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some(e) = batch_err {
+            tracing::warn!("batch request failed with {e}, disabling batching");
+            self.batching.store(false, Ordering::Relaxed);
+        }
+        Ok(result)
     }
 
     /// Return the status of an address as defined by the electrum protocol
@@ -96,22 +344,75 @@ impl ElectrumClient {
         let elements_script = address.script_pubkey();
         let bitcoin_script = bitcoin::ScriptBuf::from(elements_script.to_bytes());
 
-        let val = match self.client.script_subscribe(&bitcoin_script) {
-            Ok(val) => val,
+        match self.client.script_subscribe(&bitcoin_script) {
+            Ok(val) => {
+                self.script_status.insert(elements_script.clone(), val);
+            }
             Err(electrum_client::Error::AlreadySubscribed(_)) => {
                 self.client.script_get_history(&bitcoin_script)?; // it seems it must be called, otherwise the server don't update the status
-                self.client.script_pop(&bitcoin_script)?
+                if let Some(val) = self.client.script_pop(&bitcoin_script)? {
+                    self.script_status
+                        .insert(elements_script.clone(), Some(val));
+                }
             }
             Err(e) => return Err(e.into()),
         };
 
-        if let Some(val) = val {
-            self.script_status.insert(elements_script.clone(), val);
+        Ok(self.script_status.get(&elements_script).cloned().flatten())
+    }
+
+    /// Subscribe to `scripts`, storing their status
+    fn subscribe(&mut self, scripts: &[&Script]) -> Result<(), Error> {
+        let bitcoin_scripts: Vec<&bitcoin::Script> = scripts
+            .iter()
+            .map(|s| bitcoin::Script::from_bytes(s.as_bytes()))
+            .collect();
+        // A failed batch leaves the scripts registered as subscribed, thus there is no fallback
+        // to single requests
+        let statuses = if self.batching.load(Ordering::Relaxed) {
+            self.client.batch_script_subscribe(&bitcoin_scripts)?
+        } else {
+            bitcoin_scripts
+                .iter()
+                .map(|s| self.client.script_subscribe(s))
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        for (script, status) in scripts.iter().zip(statuses) {
+            self.script_status.insert((*script).clone(), status);
         }
-        Ok(self.script_status.get(&elements_script).cloned())
+        Ok(())
+    }
+}
+#[cfg(feature = "tokio")]
+impl ElectrumClient {
+    /// Async variant of [`BlockchainBackend::full_scan()`]
+    ///
+    /// The electrum client is blocking, the scan runs with [`tokio::task::block_in_place()`] so
+    /// that the other tasks of the runtime keep running on other threads. It must be called from
+    /// a multi-threaded runtime.
+    pub async fn full_scan_async(
+        &mut self,
+        wollet: &crate::Wollet,
+    ) -> Result<Option<crate::Update>, Error> {
+        tokio::task::block_in_place(|| self.full_scan(wollet))
+    }
+
+    /// Async variant of [`BlockchainBackend::broadcast()`], see
+    /// [`ElectrumClient::full_scan_async()`]
+    pub async fn broadcast_async(&self, tx: &Transaction) -> Result<Txid, Error> {
+        tokio::task::block_in_place(|| self.broadcast(tx))
     }
 }
+
 impl super::BlockchainBackend for ElectrumClient {
+    fn registry_url(&self) -> Option<&str> {
+        self.registry_url.as_deref()
+    }
+
+    fn scan_batches(&self) -> u32 {
+        self.scan_batches
+    }
+
     fn tip(&mut self) -> Result<BlockHeader, Error> {
         let mut popped_header = None;
         while let Some(header) = self.client.block_headers_pop_raw()? {
@@ -120,6 +421,11 @@ impl super::BlockchainBackend for ElectrumClient {
 
         if let Some(popped_header) = popped_header {
             let tip: BlockHeader = elements_deserialize(&popped_header.header)?;
+            self.verify_header(popped_header.height as Height, &tip)?;
+            if tip.prev_blockhash != self.tip.block_hash() {
+                // The new tip may not extend the previous one, cached headers could be stale
+                self.headers.lock().map_err(lock_err)?.clear();
+            }
             self.tip = tip;
         }
 
@@ -139,8 +445,13 @@ impl super::BlockchainBackend for ElectrumClient {
             .map(|t| bitcoin::Txid::from_raw_hash(t.to_raw_hash()))
             .collect();
 
+        let txs = self.batch_or_single(
+            &txids,
+            |c, txids| c.batch_transaction_get_raw(txids),
+            |c, txid| c.transaction_get_raw(txid),
+        )?;
         let mut result = vec![];
-        for tx in self.client.batch_transaction_get_raw(&txids)? {
+        for tx in txs {
             let tx: Transaction = elements::encode::deserialize(&tx)?;
             result.push(tx);
         }
@@ -152,9 +463,15 @@ impl super::BlockchainBackend for ElectrumClient {
         heights: &[Height],
         _: &HashMap<Height, BlockHash>,
     ) -> Result<Vec<BlockHeader>, Error> {
+        let headers = self.batch_or_single(
+            heights,
+            |c, heights| c.batch_block_header_raw(heights.iter().copied()),
+            |c, height| c.block_header_raw(*height as usize),
+        )?;
         let mut result = vec![];
-        for header in self.client.batch_block_header_raw(heights)? {
+        for (height, header) in heights.iter().zip(headers) {
             let header: BlockHeader = elements::encode::deserialize(&header)?;
+            self.verify_header(*height, &header)?;
             result.push(header);
         }
         Ok(result)
@@ -166,15 +483,126 @@ impl super::BlockchainBackend for ElectrumClient {
             .map(|t| bitcoin::Script::from_bytes(t.as_bytes()))
             .collect();
 
-        Ok(self
-            .client
-            .batch_script_get_history(&scripts)?
+        let histories: Vec<Vec<History>> = self
+            .batch_or_single(
+                &scripts,
+                |c, scripts| c.batch_script_get_history(scripts),
+                |c, script| c.script_get_history(script),
+            )?
             .into_iter()
             .map(|e| e.into_iter().map(Into::into).collect())
+            .collect();
+        for history in histories.iter() {
+            self.verify_history(history)?;
+        }
+        Ok(histories)
+    }
+
+    /// The scripts are subscribed the first time, then their status changes are received as
+    /// notifications, thus only the scripts not subscribed yet are requested
+    fn get_scripts_status(&mut self, scripts: &[&Script]) -> Result<Vec<Option<[u8; 32]>>, Error> {
+        // Notifications are read while waiting for a response
+        self.client.ping()?;
+        let mut to_subscribe = vec![];
+        for script in scripts {
+            if !self.script_status.contains_key(*script) {
+                to_subscribe.push(*script);
+                continue;
+            }
+            let bitcoin_script = bitcoin::Script::from_bytes(script.as_bytes());
+            let mut last = None;
+            loop {
+                match self.client.script_pop(bitcoin_script) {
+                    Ok(Some(status)) => last = Some(status),
+                    Ok(None) => break,
+                    // The connection has been opened again, losing the subscriptions
+                    Err(electrum_client::Error::NotSubscribed(_)) => {
+                        to_subscribe.push(*script);
+                        break;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            if let Some(status) = last {
+                self.script_status.insert((*script).clone(), Some(status));
+            }
+        }
+        if !to_subscribe.is_empty() {
+            self.subscribe(&to_subscribe)?;
+        }
+        Ok(scripts
+            .iter()
+            .map(|s| self.script_status.get(*s).cloned().flatten().map(|s| *s))
+            .collect())
+    }
+
+    fn header(&self, height: Height) -> Result<BlockHeader, Error> {
+        if let Some(header) = self.headers.lock().map_err(lock_err)?.get(&height) {
+            return Ok(header.clone());
+        }
+        let header: BlockHeader =
+            elements_deserialize(&self.client.block_header_raw(height as usize)?)?;
+        self.verify_header(height, &header)?;
+        self.headers
+            .lock()
+            .map_err(lock_err)?
+            .insert(height, header.clone());
+        Ok(header)
+    }
+
+    fn merkle_proof(&self, txid: &Txid, height: Height) -> Result<(usize, Vec<[u8; 32]>), Error> {
+        let txid = bitcoin::Txid::from_raw_hash(txid.to_raw_hash());
+        let proof = self.client.transaction_get_merkle(&txid, height as usize)?;
+        Ok((proof.pos, proof.merkle))
+    }
+
+    fn fee_estimates(&self) -> Result<BTreeMap<u16, f32>, Error> {
+        let estimates = self.batch_or_single(
+            &FEE_TARGETS,
+            |c, targets| c.batch_estimate_fee(targets.iter().map(|t| *t as usize)),
+            |c, target| c.estimate_fee(*target as usize),
+        )?;
+        // Electrum returns BTC/kvB, or -1 if the server can't estimate
+        Ok(FEE_TARGETS
+            .iter()
+            .zip(estimates)
+            .filter(|(_, btc_kvb)| *btc_kvb > 0.0)
+            .map(|(target, btc_kvb)| (*target, (btc_kvb * 100_000_000.0) as f32))
             .collect())
     }
 }
 
+/// Connect to the server and agree on the protocol version to use
+///
+/// Errors only if the server can't be reached, a server rejecting all the versions is still used.
+fn negotiate_version(
+    url: &ElectrumUrl,
+    options: &ElectrumOptions,
+) -> Result<(Client, Option<String>), Error> {
+    let client_name = format!("lwk_wollet {}", env!("CARGO_PKG_VERSION"));
+    for version in PROTOCOL_VERSIONS {
+        // `server.version` can be sent only once per session, so use a new connection each time
+        let client = url.build_client(options)?;
+        let params = [
+            Param::String(client_name.clone()),
+            Param::String(version.into()),
+        ];
+        match client.raw_call("server.version", params) {
+            Ok(response) => {
+                // The response is `[server_software_version, protocol_version]`
+                let protocol_version = response.get(1).and_then(|v| v.as_str());
+                return Ok((client, protocol_version.map(ToString::to_string)));
+            }
+            Err(e) => tracing::debug!("server rejected protocol version {version}: {e}"),
+        }
+    }
+    Ok((url.build_client(options)?, None))
+}
+
+fn lock_err<T>(e: PoisonError<T>) -> Error {
+    Error::Generic(e.to_string())
+}
+
 impl From<GetHistoryRes> for History {
     fn from(value: GetHistoryRes) -> Self {
         History {
@@ -185,3 +613,29 @@ impl From<GetHistoryRes> for History {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    use super::{ElectrumClient, ElectrumOptions, ElectrumUrl};
+
+    #[test]
+    fn test_electrum_timeout() {
+        // A server accepting connections but never answering
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = ElectrumUrl::new(&listener.local_addr().unwrap().to_string(), false, false);
+        std::thread::spawn(move || {
+            let mut streams = vec![];
+            for stream in listener.incoming() {
+                streams.push(stream);
+            }
+        });
+
+        let start = Instant::now();
+        let options = ElectrumOptions::default().timeout(1);
+        ElectrumClient::with_options(&url, options).unwrap_err();
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+}
@@ -1,72 +1,291 @@
-use std::{collections::HashMap, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    str::FromStr,
+    sync::{Mutex, OnceLock, PoisonError},
+};
 
 use elements::{
     encode::Decodable,
     hashes::{hex::FromHex, sha256, Hash},
     hex::ToHex,
     pset::serialize::Serialize,
-    BlockHash, Script, Txid,
+    AssetId, Block, BlockHash, BlockHeader, Script, Txid,
 };
-use reqwest::blocking::Response;
+use reqwest::blocking::{Client, Response};
 use serde::Deserialize;
 
-use crate::{store::Height, BlockchainBackend, Error};
+use crate::{fee::FEE_TARGETS, store::Height, AssetStats, BlockRef, BlockchainBackend, Error};
+
+use super::{History, ESPLORA_CONFIRMED_PAGE_SIZE};
 
-use super::History;
+/// Default number of concurrent requests made to the server
+const DEFAULT_CONCURRENCY: usize = 4;
 
 #[derive(Debug)]
 /// A blockchain backend implementation based on the
 /// [esplora HTTP API](https://github.com/blockstream/esplora/blob/master/API.md)
+///
+/// Works with any esplora instance, for example `https://blockstream.info/liquid/api`.
+/// Requests for multiple scripts, transactions or headers are made concurrently.
 pub struct EsploraClient {
+    /// The blocking http client, created on first use since it cannot be dropped in an async
+    /// context
+    client: OnceLock<Client>,
     base_url: String,
     tip_hash_url: String,
     broadcast_url: String,
+
+    /// Maximum number of requests in flight at the same time
+    concurrency: usize,
+
+    /// Header of the last tip returned, to avoid downloading it again if the tip didn't change
+    tip: Option<BlockHeader>,
+
+    /// Blocks already downloaded, a block content never changes for a given hash
+    blocks: Mutex<HashMap<BlockHash, Block>>,
+
+    /// The asset registry queried for the assets received in scans
+    registry_url: Option<String>,
+
+    /// Number of batches of scripts of every chain requested together while scanning
+    scan_batches: u32,
 }
 
 impl EsploraClient {
     pub fn new(url: &str) -> Self {
+        let url = url.trim_end_matches('/');
         Self {
+            client: OnceLock::new(),
             base_url: url.to_string(),
             tip_hash_url: format!("{url}/blocks/tip/hash"),
             broadcast_url: format!("{url}/tx"),
+            concurrency: DEFAULT_CONCURRENCY,
+            tip: None,
+            blocks: Mutex::new(HashMap::new()),
+            registry_url: None,
+            scan_batches: 1,
         }
     }
 
+    /// Set the maximum number of concurrent requests, at least 1
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Request `scan_batches` batches of scripts of every chain together while scanning, see
+    /// [`BlockchainBackend::scan_batches()`]
+    ///
+    /// The requests for the scripts are made in parallel up to the concurrency set with
+    /// [`EsploraClient::with_concurrency()`], thus it should be increased as well.
+    pub fn with_scan_batches(mut self, scan_batches: u32) -> Self {
+        self.scan_batches = scan_batches.max(1);
+        self
+    }
+
+    /// Fetch and verify the registry info of the assets received in scans from the asset
+    /// registry at `registry_url`, see [`BlockchainBackend::registry_url()`]
+    pub fn with_registry_url(mut self, registry_url: &str) -> Self {
+        self.registry_url = Some(registry_url.to_string());
+        self
+    }
+
+    fn client(&self) -> &Client {
+        self.client.get_or_init(Client::new)
+    }
+
     fn last_block_hash(&mut self) -> Result<elements::BlockHash, crate::Error> {
-        let response = get_with_retry(&self.tip_hash_url, 0)?;
+        let response = self.get_with_retry(&self.tip_hash_url)?;
+        Ok(BlockHash::from_str(&response.text()?)?)
+    }
+
+    fn block_hash(&self, height: Height) -> Result<BlockHash, Error> {
+        let url = format!("{}/block-height/{}", self.base_url, height);
+        let response = self.get_with_retry(&url)?;
         Ok(BlockHash::from_str(&response.text()?)?)
     }
+
+    fn block_header(&self, block_hash: BlockHash) -> Result<BlockHeader, Error> {
+        let url = format!("{}/block/{}/header", self.base_url, block_hash);
+        let response = self.get_with_retry(&url)?;
+        let header_bytes = Vec::<u8>::from_hex(&response.text()?)?;
+        Ok(BlockHeader::consensus_decode(&header_bytes[..])?)
+    }
+
+    /// Get the whole history of a script, following the pages of confirmed transactions
+    fn script_history(&self, script: &Script) -> Result<Vec<History>, Error> {
+        let script_hash = sha256::Hash::hash(script.as_bytes()).to_byte_array();
+        let url = format!("{}/scripthash/{}/txs", self.base_url, script_hash.to_hex());
+
+        // The first page contains the mempool transactions followed by the first confirmed ones,
+        // the following pages are requested with the last confirmed txid seen
+        let mut history: Vec<History> = vec![];
+        let mut page: Vec<EsploraTx> = self.get_with_retry(&url)?.json()?;
+        loop {
+            let confirmed: Vec<_> = page.iter().filter(|tx| tx.status.confirmed).collect();
+            let last_seen = match confirmed.last() {
+                Some(tx) if confirmed.len() >= ESPLORA_CONFIRMED_PAGE_SIZE => Some(tx.txid),
+                _ => None,
+            };
+            history.extend(page.into_iter().map(History::from));
+            match last_seen {
+                Some(txid) => {
+                    let url = format!("{url}/chain/{txid}");
+                    page = self.get_with_retry(&url)?.json()?;
+                }
+                None => break,
+            }
+        }
+        Ok(history)
+    }
+
+    /// Apply `f` to every item, with at most `self.concurrency` calls running at the same time
+    ///
+    /// Results are returned in the same order of `items`.
+    fn batch<T, R, F>(&self, items: &[T], f: F) -> Result<Vec<R>, Error>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> Result<R, Error> + Sync,
+    {
+        let mut result = Vec::with_capacity(items.len());
+        for chunk in items.chunks(self.concurrency) {
+            if chunk.len() == 1 {
+                result.push(f(&chunk[0])?);
+                continue;
+            }
+            let f = &f;
+            let chunk_result = std::thread::scope(|s| {
+                let handles: Vec<_> = chunk.iter().map(|item| s.spawn(move || f(item))).collect();
+                handles
+                    .into_iter()
+                    .map(|h| {
+                        h.join()
+                            .map_err(|_| Error::Generic("esplora request panicked".into()))?
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            })?;
+            result.extend(chunk_result);
+        }
+        Ok(result)
+    }
+
+    fn get_with_retry(&self, url: &str) -> Result<Response, Error> {
+        let mut attempt = 0;
+        loop {
+            let response = self.client().get(url).send()?;
+            tracing::debug!(
+                "{} status_code:{} body bytes:{:?}",
+                &url,
+                response.status(),
+                response.content_length(),
+            );
+
+            // 429 Too many requests
+            // 503 Service Temporarily Unavailable
+            if response.status() == 429 || response.status() == 503 {
+                if attempt > 6 {
+                    return Err(Error::Generic("Too many retry".to_string()));
+                }
+                let secs = 1 << attempt;
+
+                tracing::debug!("waiting {secs}");
+                std::thread::sleep(std::time::Duration::from_secs(secs));
+                attempt += 1;
+            } else if !response.status().is_success() {
+                return Err(Error::Generic(format!(
+                    "{url} returned {}: {}",
+                    response.status(),
+                    response.text().unwrap_or_default()
+                )));
+            } else {
+                return Ok(response);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl EsploraClient {
+    /// Async variant of [`BlockchainBackend::full_scan()`]
+    ///
+    /// Requests are made with a non-blocking http client, thus many scans can run concurrently on
+    /// the same runtime without occupying a thread each.
+    pub async fn full_scan_async(
+        &self,
+        wollet: &crate::Wollet,
+    ) -> Result<Option<crate::Update>, Error> {
+        let mut client = crate::EsploraWasmClient::new(wollet.network(), &self.base_url, false);
+        client.full_scan(wollet).await
+    }
+
+    /// Async variant of [`BlockchainBackend::broadcast()`]
+    pub async fn broadcast_async(
+        &self,
+        tx: &elements::Transaction,
+    ) -> Result<elements::Txid, crate::Error> {
+        let tx_hex = tx.serialize().to_hex();
+        let client = reqwest::Client::new();
+        let response = client.post(&self.broadcast_url).body(tx_hex).send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(Error::Generic(format!(
+                "broadcast failed ({status}): {text}"
+            )));
+        }
+        let txid = elements::Txid::from_str(text.trim())?;
+        Ok(txid)
+    }
 }
 
 impl BlockchainBackend for EsploraClient {
-    fn tip(&mut self) -> Result<elements::BlockHeader, crate::Error> {
-        let last_block_hash = self.last_block_hash()?;
+    fn registry_url(&self) -> Option<&str> {
+        self.registry_url.as_deref()
+    }
 
-        let header_url = format!("{}/block/{}/header", self.base_url, last_block_hash);
-        let response = get_with_retry(&header_url, 0)?;
-        let header_bytes = Vec::<u8>::from_hex(&response.text()?)?;
+    fn scan_batches(&self) -> u32 {
+        self.scan_batches
+    }
 
-        let header = elements::BlockHeader::consensus_decode(&header_bytes[..])?;
+    fn tip(&mut self) -> Result<elements::BlockHeader, crate::Error> {
+        let last_block_hash = self.last_block_hash()?;
+        if let Some(tip) = self.tip.as_ref() {
+            if tip.block_hash() == last_block_hash {
+                return Ok(tip.clone());
+            }
+        }
+        let header = self.block_header(last_block_hash)?;
+        self.tip = Some(header.clone());
         Ok(header)
     }
 
     fn broadcast(&self, tx: &elements::Transaction) -> Result<elements::Txid, crate::Error> {
-        let tx_bytes = tx.serialize();
-        let client = reqwest::blocking::Client::new();
-        let response = client.post(&self.broadcast_url).body(tx_bytes).send()?;
-        let txid = elements::Txid::from_str(&response.text()?)?;
+        let tx_hex = tx.serialize().to_hex();
+        let response = self
+            .client()
+            .post(&self.broadcast_url)
+            .body(tx_hex)
+            .send()?;
+        let status = response.status();
+        let text = response.text()?;
+        if !status.is_success() {
+            return Err(Error::Generic(format!(
+                "broadcast failed ({status}): {text}"
+            )));
+        }
+        let txid = elements::Txid::from_str(text.trim())?;
         Ok(txid)
     }
 
     fn get_transactions(&self, txids: &[Txid]) -> Result<Vec<elements::Transaction>, Error> {
-        let mut result = vec![];
-        for txid in txids.iter() {
+        self.batch(txids, |txid| {
             let tx_url = format!("{}/tx/{}/raw", self.base_url, txid);
-            let response = get_with_retry(&tx_url, 0)?;
-            let tx = elements::Transaction::consensus_decode(&response.bytes()?[..])?;
-            result.push(tx);
-        }
-        Ok(result)
+            let response = self.get_with_retry(&tx_url)?;
+            Ok(elements::Transaction::consensus_decode(
+                &response.bytes()?[..],
+            )?)
+        })
     }
 
     fn get_headers(
@@ -74,70 +293,74 @@ impl BlockchainBackend for EsploraClient {
         heights: &[Height],
         height_blockhash: &HashMap<Height, BlockHash>,
     ) -> Result<Vec<elements::BlockHeader>, Error> {
-        let mut result = vec![];
-        for height in heights.iter() {
+        self.batch(heights, |height| {
             let block_hash = match height_blockhash.get(height) {
                 Some(block_hash) => *block_hash,
-                None => {
-                    let block_height = format!("{}/block-height/{}", self.base_url, height);
-                    let response = get_with_retry(&block_height, 0)?;
-                    BlockHash::from_str(&response.text()?)?
-                }
+                None => self.block_hash(*height)?,
             };
-
-            let block_header = format!("{}/block/{}/header", self.base_url, block_hash);
-            let response = get_with_retry(&block_header, 0)?;
-            let header_bytes = Vec::<u8>::from_hex(&response.text()?)?;
-
-            let header = elements::BlockHeader::consensus_decode(&header_bytes[..])?;
-
-            result.push(header);
-        }
-        Ok(result)
+            self.block_header(block_hash)
+        })
     }
 
     // examples:
     // https://blockstream.info/liquidtestnet/api/address/tex1qntw9m0j2e93n84x975t47ddhgkzx3x8lhfv2nj/txs
     // https://blockstream.info/liquidtestnet/api/scripthash/b50a2a798d876db54acfa0d8dfdc49154ea8defed37b225ec4c9ec7415358ba3/txs
     fn get_scripts_history(&self, scripts: &[&Script]) -> Result<Vec<Vec<History>>, Error> {
-        let mut result: Vec<_> = vec![];
-        for script in scripts.iter() {
-            let script = elements::bitcoin::Script::from_bytes(script.as_bytes());
-            let script_hash = sha256::Hash::hash(script.as_bytes()).to_byte_array();
-            let url = format!("{}/scripthash/{}/txs", self.base_url, script_hash.to_hex());
-            // TODO must handle paging -> https://github.com/blockstream/esplora/blob/master/API.md#addresses
-            let response = get_with_retry(&url, 0)?;
-            let json: Vec<EsploraTx> = response.json()?;
-
-            let history: Vec<History> = json.into_iter().map(Into::into).collect();
-            result.push(history)
+        self.batch(scripts, |script| self.script_history(script))
+    }
+
+    fn block(&self, block: BlockRef) -> Result<Block, Error> {
+        let hash = match block {
+            BlockRef::Height(height) => self.block_hash(height)?,
+            BlockRef::Hash(hash) => hash,
+        };
+        let lock_err = |e: PoisonError<_>| Error::Generic(e.to_string());
+        if let Some(block) = self.blocks.lock().map_err(lock_err)?.get(&hash) {
+            return Ok(block.clone());
         }
-        Ok(result)
+        let url = format!("{}/block/{}/raw", self.base_url, hash);
+        let response = self.get_with_retry(&url)?;
+        let block = Block::consensus_decode(&response.bytes()?[..])?;
+        self.blocks
+            .lock()
+            .map_err(lock_err)?
+            .insert(hash, block.clone());
+        Ok(block)
     }
-}
 
-fn get_with_retry(url: &str, attempt: usize) -> Result<Response, Error> {
-    let response = reqwest::blocking::get(url)?;
-    tracing::debug!(
-        "{} status_code:{} body bytes:{:?}",
-        &url,
-        response.status(),
-        response.content_length(),
-    );
-
-    // 429 Too many requests
-    // 503 Service Temporarily Unavailable
-    if response.status() == 429 || response.status() == 503 {
-        if attempt > 6 {
-            return Err(Error::Generic("Too many retry".to_string()));
+    fn merkle_proof(&self, txid: &Txid, height: Height) -> Result<(usize, Vec<[u8; 32]>), Error> {
+        let url = format!("{}/tx/{}/merkle-proof", self.base_url, txid);
+        let proof: EsploraMerkleProof = self.get_with_retry(&url)?.json()?;
+        if proof.block_height != height {
+            return Err(Error::Generic(format!(
+                "transaction {txid} is confirmed at height {}, not {height}",
+                proof.block_height
+            )));
         }
-        let secs = 1 << attempt;
+        let merkle = proof
+            .merkle
+            .iter()
+            .map(|h| <[u8; 32]>::from_hex(h))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok((proof.pos, merkle))
+    }
+
+    fn asset_stats(&self, asset: &AssetId) -> Result<AssetStats, Error> {
+        let url = format!("{}/asset/{}", self.base_url, asset);
+        let response: EsploraAsset = self.get_with_retry(&url)?.json()?;
+        Ok(response.into())
+    }
 
-        tracing::debug!("waiting {secs}");
-        std::thread::sleep(std::time::Duration::from_secs(secs));
-        get_with_retry(url, attempt + 1)
-    } else {
-        Ok(response)
+    fn fee_estimates(&self) -> Result<BTreeMap<u16, f32>, Error> {
+        let url = format!("{}/fee-estimates", self.base_url);
+        let response = self.get_with_retry(&url)?;
+        // Esplora returns sat/vB by confirmation target
+        let json: HashMap<String, f32> = response.json()?;
+        Ok(json
+            .into_iter()
+            .filter_map(|(target, sat_vb)| Some((target.parse().ok()?, sat_vb * 1000.0)))
+            .filter(|(target, _)| FEE_TARGETS.contains(target))
+            .collect())
     }
 }
 
@@ -145,8 +368,9 @@ impl From<EsploraTx> for History {
     fn from(value: EsploraTx) -> Self {
         History {
             txid: value.txid,
-            height: value.status.block_height,
-            block_hash: Some(value.status.block_hash),
+            // -1 means unconfirmed, as in electrum
+            height: value.status.block_height.unwrap_or(-1),
+            block_hash: value.status.block_hash,
             block_timestamp: None,
         }
     }
@@ -158,26 +382,194 @@ struct EsploraTx {
     status: Status,
 }
 
-// TODO some of this fields may be Option in unconfirmed
+/// Response of the `/asset/:asset_id` endpoint
+#[derive(Deserialize)]
+struct EsploraAsset {
+    asset_id: AssetId,
+    chain_stats: EsploraAssetStats,
+}
+
+/// Chain statistics of an asset, the fields depend on whether the asset is the policy asset
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct EsploraAssetStats {
+    tx_count: u64,
+    issuance_count: u64,
+    issued_amount: u64,
+    burned_amount: u64,
+    has_blinded_issuances: bool,
+
+    // policy asset only
+    peg_in_count: Option<u64>,
+    peg_in_amount: u64,
+    peg_out_amount: u64,
+}
+
+impl From<EsploraAsset> for AssetStats {
+    fn from(value: EsploraAsset) -> Self {
+        let s = value.chain_stats;
+        let (issuance_count, reissuance_count, issued_amount, burned_amount) = match s.peg_in_count
+        {
+            Some(peg_in_count) => (
+                peg_in_count,
+                0,
+                Some(s.peg_in_amount),
+                s.burned_amount.saturating_add(s.peg_out_amount),
+            ),
+            None => (
+                s.issuance_count,
+                s.issuance_count.saturating_sub(1),
+                (!s.has_blinded_issuances).then_some(s.issued_amount),
+                s.burned_amount,
+            ),
+        };
+        AssetStats {
+            asset: value.asset_id,
+            tx_count: s.tx_count,
+            issuance_count,
+            reissuance_count,
+            issued_amount,
+            burned_amount,
+            circulating_amount: issued_amount.map(|i| i.saturating_sub(burned_amount)),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct EsploraMerkleProof {
+    block_height: Height,
+    merkle: Vec<String>,
+    pos: usize,
+}
 
 #[derive(Deserialize)]
 struct Status {
-    block_height: i32,
-    block_hash: BlockHash,
+    confirmed: bool,
+    block_height: Option<i32>,
+    block_hash: Option<BlockHash>,
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashMap;
 
-    use super::EsploraClient;
-    use crate::BlockchainBackend;
-    use elements::{encode::Decodable, BlockHash};
+    use super::{EsploraAsset, EsploraClient, EsploraTx};
+    use crate::{clients::History, AssetStats, BlockRef, BlockchainBackend, Error};
+
+    #[test]
+    fn test_esplora_tx_status() {
+        let txid = "c6e3187f028942973ad27224ca79baa8382e90ad686e927fc29896e8a2edf3f3";
+        let hash = "1466275836220db2944ca059a3a10ef6fd2ea684b0688d2c379296888a206003";
+        let confirmed = format!(
+            r#"{{"txid":"{txid}","status":{{"confirmed":true,"block_height":10,"block_hash":"{hash}","block_time":1}}}}"#
+        );
+        let tx: EsploraTx = serde_json::from_str(&confirmed).unwrap();
+        assert!(tx.status.confirmed);
+        let history = History::from(tx);
+        assert_eq!(history.height, 10);
+        assert_eq!(history.block_hash.unwrap().to_string(), hash);
+
+        let unconfirmed = format!(r#"{{"txid":"{txid}","status":{{"confirmed":false}}}}"#);
+        let tx: EsploraTx = serde_json::from_str(&unconfirmed).unwrap();
+        assert!(!tx.status.confirmed);
+        let history = History::from(tx);
+        assert_eq!(history.txid.to_string(), txid);
+        assert_eq!(history.height, -1);
+        assert!(history.block_hash.is_none());
+    }
 
-    fn get_block(base_url: &str, hash: BlockHash) -> elements::Block {
-        let url = format!("{}/block/{}/raw", base_url, hash);
-        let response = reqwest::blocking::get(url).unwrap();
-        elements::Block::consensus_decode(&response.bytes().unwrap()[..]).unwrap()
+    #[test]
+    fn test_esplora_asset_stats() {
+        let asset = "ce091c998b83c78bb71a632313ba3760f1763d9cfcffae02258ffa9865a37bd2";
+        let json = format!(
+            r#"{{"asset_id":"{asset}","chain_stats":{{"tx_count":12,"issuance_count":3,"issued_amount":2100,"burned_amount":100,"has_blinded_issuances":false,"reissuance_tokens":1,"burned_reissuance_tokens":0}},"mempool_stats":{{"tx_count":0}},"precision":2}}"#
+        );
+        let stats: AssetStats = serde_json::from_str::<EsploraAsset>(&json).unwrap().into();
+        assert_eq!(stats.asset.to_string(), asset);
+        assert_eq!(stats.tx_count, 12);
+        assert_eq!(stats.issuance_count, 3);
+        assert_eq!(stats.reissuance_count, 2);
+        assert_eq!(stats.issued_amount, Some(2100));
+        assert_eq!(stats.burned_amount, 100);
+        assert_eq!(stats.circulating_amount, Some(2000));
+
+        let json = json.replace(
+            r#""has_blinded_issuances":false"#,
+            r#""has_blinded_issuances":true"#,
+        );
+        let stats: AssetStats = serde_json::from_str::<EsploraAsset>(&json).unwrap().into();
+        assert_eq!(stats.issued_amount, None);
+        assert_eq!(stats.circulating_amount, None);
+
+        // Policy asset
+        let policy_asset = "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d";
+        let json = format!(
+            r#"{{"asset_id":"{policy_asset}","chain_stats":{{"tx_count":100,"peg_in_count":5,"peg_in_amount":5000,"peg_out_count":2,"peg_out_amount":1000,"burn_count":1,"burned_amount":10}}}}"#
+        );
+        let stats: AssetStats = serde_json::from_str::<EsploraAsset>(&json).unwrap().into();
+        assert_eq!(stats.issuance_count, 5);
+        assert_eq!(stats.reissuance_count, 0);
+        assert_eq!(stats.issued_amount, Some(5000));
+        assert_eq!(stats.burned_amount, 1010);
+        assert_eq!(stats.circulating_amount, Some(3990));
+    }
+
+    #[test]
+    fn test_esplora_batch() {
+        let client = EsploraClient::new("http://127.0.0.1/").with_concurrency(3);
+        assert_eq!(client.base_url, "http://127.0.0.1");
+        let items: Vec<u32> = (0..10).collect();
+        let result = client.batch(&items, |i| Ok(i * 2)).unwrap();
+        assert_eq!(result, (0..10).map(|i| i * 2).collect::<Vec<_>>());
+
+        let err = client
+            .batch(&items, |i| match i {
+                7 => Err(Error::Generic("fail".into())),
+                _ => Ok(*i),
+            })
+            .unwrap_err();
+        assert_eq!(err.to_string(), "fail");
+
+        let client = client.with_concurrency(0);
+        assert_eq!(client.batch(&items, |i| Ok(*i)).unwrap(), items);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_esplora_async() {
+        let txid = "c6e3187f028942973ad27224ca79baa8382e90ad686e927fc29896e8a2edf3f3";
+        let (sender, requests) = std::sync::mpsc::channel();
+        let url = lwk_test_util::http_server(move |request| {
+            let _ = sender.send(request);
+            lwk_test_util::HttpResponse::ok(txid)
+        });
+
+        let client = std::sync::Arc::new(EsploraClient::new(&url));
+        let tx = elements::Transaction {
+            version: 2,
+            lock_time: elements::LockTime::ZERO,
+            input: vec![],
+            output: vec![],
+        };
+        // Futures can be spawned on a multi-threaded runtime
+        let broadcast = tokio::spawn({
+            let client = client.clone();
+            async move { client.broadcast_async(&tx).await }
+        });
+        assert_eq!(broadcast.await.unwrap().unwrap().to_string(), txid);
+        let request = requests.recv().unwrap();
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("POST", "/tx")
+        );
+
+        let wollet = crate::Wollet::without_persist(
+            crate::ElementsNetwork::default_regtest(),
+            lwk_test_util::TEST_DESCRIPTOR.parse().unwrap(),
+        )
+        .unwrap();
+        fn assert_send<T: Send>(_: T) {}
+        assert_send(client.full_scan_async(&wollet));
     }
 
     #[ignore = "Should be integration test, but it is testing private function"]
@@ -207,7 +599,12 @@ mod tests {
         let genesis_header = &headers[0];
         assert_eq!(genesis_header.height, 0);
 
-        let genesis_block = get_block(esplora_url, genesis_header.block_hash());
+        assert_eq!(&client.header(0).unwrap(), genesis_header);
+
+        let genesis_block = client.block(BlockRef::Height(0)).unwrap();
+        assert_eq!(&genesis_block.header, genesis_header);
+        let cached = client.block(genesis_header.block_hash().into()).unwrap();
+        assert_eq!(cached, genesis_block);
         let genesis_tx = &genesis_block.txdata[0];
 
         let txid = genesis_tx.txid();
@@ -1,15 +1,16 @@
 //! NOTE This module is temporary, as soon we make the other clients async this will be merged in
 //! the standard esplora client of which contain a lot of duplicated code.
 
-use super::{try_unblind, History};
+use super::{check_genesis, unblind_outputs, History, ESPLORA_CONFIRMED_PAGE_SIZE};
 use crate::{
-    store::{Height, Store, Timestamp, BATCH_SIZE},
+    fee::FEE_TARGETS,
+    store::{Height, Store, Timestamp},
     update::DownloadTxResult,
     Chain, ElementsNetwork, Error, Update, Wollet, WolletDescriptor,
 };
 use age::x25519::Recipient;
 use base64::Engine;
-use elements::{bitcoin::bip32::ChildNumber, Address, OutPoint};
+use elements::{bitcoin::bip32::ChildNumber, Address};
 use elements::{
     encode::Decodable, hashes::hex::FromHex, hex::ToHex, pset::serialize::Serialize, BlockHash,
     Script, Txid,
@@ -18,7 +19,7 @@ use elements_miniscript::DescriptorPublicKey;
 use reqwest::Response;
 use serde::Deserialize;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     io::Write,
     ops::{Index, IndexMut},
     str::FromStr,
@@ -92,6 +93,7 @@ impl EsploraWasmClient {
     /// In both cases, the server can see transactions that are involved in the wallet but it knows nothing about the
     /// assets and amount exchanged due to the nature of confidential transactions.
     pub fn new(network: ElementsNetwork, url: &str, waterfalls: bool) -> Self {
+        let url = url.trim_end_matches('/');
         Self {
             base_url: url.to_string(),
             tip_hash_url: format!("{url}/blocks/tip/hash"),
@@ -126,10 +128,30 @@ impl EsploraWasmClient {
         let tx_hex = tx.serialize().to_hex();
         let client = reqwest::Client::new();
         let response = client.post(&self.broadcast_url).body(tx_hex).send().await?;
-        let txid = elements::Txid::from_str(&response.text().await?)?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(Error::Generic(format!(
+                "broadcast failed ({status}): {text}"
+            )));
+        }
+        let txid = elements::Txid::from_str(text.trim())?;
         Ok(txid)
     }
 
+    /// Fee rates estimated by the server, see [`super::BlockchainBackend::fee_estimates()`]
+    pub async fn fee_estimates(&self) -> Result<BTreeMap<u16, f32>, Error> {
+        let url = format!("{}/fee-estimates", self.base_url);
+        let response = get_with_retry(&url).await?;
+        // Esplora returns sat/vB by confirmation target
+        let json: HashMap<String, f32> = serde_json::from_str(&response.text().await?)?;
+        Ok(json
+            .into_iter()
+            .filter_map(|(target, sat_vb)| Some((target.parse().ok()?, sat_vb * 1000.0)))
+            .filter(|(target, _)| FEE_TARGETS.contains(target))
+            .collect())
+    }
+
     async fn get_transaction(&self, txid: Txid) -> Result<elements::Transaction, Error> {
         let tx_url = format!("{}/tx/{}/raw", self.base_url, txid);
         let response = get_with_retry(&tx_url).await?;
@@ -175,17 +197,46 @@ impl EsploraWasmClient {
                 Error::Generic("script generated is not a known template".to_owned()),
             )?;
             let url = format!("{}/address/{}/txs", self.base_url, address);
-            // TODO must handle paging -> https://github.com/blockstream/esplora/blob/master/API.md#addresses
-            let response = get_with_retry(&url).await?;
-            let json: Vec<EsploraTx> = serde_json::from_str(&response.text().await?)?;
 
-            let history: Vec<History> = json.into_iter().map(Into::into).collect();
+            // The first page contains the mempool transactions followed by the first confirmed
+            // ones, the following pages are requested with the last confirmed txid seen
+            let mut history: Vec<History> = vec![];
+            let response = get_with_retry(&url).await?;
+            let mut page: Vec<EsploraTx> = serde_json::from_str(&response.text().await?)?;
+            loop {
+                let confirmed: Vec<_> = page.iter().filter(|tx| tx.status.confirmed).collect();
+                let last_seen = match confirmed.last() {
+                    Some(tx) if confirmed.len() >= ESPLORA_CONFIRMED_PAGE_SIZE => Some(tx.txid),
+                    _ => None,
+                };
+                history.extend(page.into_iter().map(History::from));
+                match last_seen {
+                    Some(txid) => {
+                        let url = format!("{url}/chain/{txid}");
+                        let response = get_with_retry(&url).await?;
+                        page = serde_json::from_str(&response.text().await?)?;
+                    }
+                    None => break,
+                }
+            }
             result.push(history)
         }
         Ok(result)
     }
 
+    /// Check the server is on the network of the client, see [`super::BlockchainBackend::check_network()`]
+    async fn check_network(&self) -> Result<(), Error> {
+        if self.network.genesis_hash().is_some() {
+            let url = format!("{}/block-height/0", self.base_url);
+            let response = get_with_retry(&url).await?;
+            let genesis = BlockHash::from_str(&response.text().await?)?;
+            check_genesis(self.network, genesis)?;
+        }
+        Ok(())
+    }
+
     pub async fn full_scan(&mut self, wollet: &Wollet) -> Result<Option<Update>, Error> {
+        self.check_network().await?;
         let descriptor = wollet.wollet_descriptor();
         let store = &wollet.store;
 
@@ -199,12 +250,14 @@ impl EsploraWasmClient {
             match self.get_history_waterfalls(&descriptor, store).await {
                 Ok(d) => d,
                 Err(Error::UsingWaterfallsWithElip151) => {
-                    self.get_history(&descriptor, store).await?
+                    self.get_history(&descriptor, store, wollet.gap_limit())
+                        .await?
                 }
                 Err(e) => return Err(e),
             }
         } else {
-            self.get_history(&descriptor, store).await?
+            self.get_history(&descriptor, store, wollet.gap_limit())
+                .await?
         };
 
         let tip = self.tip().await?;
@@ -239,14 +292,14 @@ impl EsploraWasmClient {
         let last_unused_changed = store_last_unused_external != last_unused.external
             || store_last_unused_internal != last_unused.internal;
 
-        let changed = !new_txs.txs.is_empty()
+        let changed = !new_txs.is_empty()
             || last_unused_changed
             || !scripts.is_empty()
             || !timestamps.is_empty()
             || store.cache.tip != (tip.height, tip.block_hash());
 
         if changed {
-            tracing::debug!("something changed: !new_txs.txs.is_empty():{} last_unused_changed:{} !scripts.is_empty():{} !timestamps.is_empty():{}", !new_txs.txs.is_empty(), last_unused_changed, !scripts.is_empty(), !timestamps.is_empty() );
+            tracing::debug!("something changed: !new_txs.is_empty():{} last_unused_changed:{} !scripts.is_empty():{} !timestamps.is_empty():{}", !new_txs.is_empty(), last_unused_changed, !scripts.is_empty(), !timestamps.is_empty() );
 
             let txid_height_new: Vec<_> = txid_height
                 .iter()
@@ -271,6 +324,11 @@ impl EsploraWasmClient {
                 timestamps,
                 scripts,
                 tip,
+                utxo_locks: vec![],
+                utxo_unlocks: vec![],
+                labels: vec![],
+                assets: vec![],
+                scripts_status: vec![],
             };
             Ok(Some(update))
         } else {
@@ -282,13 +340,14 @@ impl EsploraWasmClient {
         &mut self,
         descriptor: &WolletDescriptor,
         store: &Store,
+        gap_limit: u32,
     ) -> Result<Data, Error> {
         let mut data = Data::default();
         for descriptor in descriptor.descriptor().clone().into_single_descriptors()? {
             let mut batch_count = 0;
             let chain: Chain = (&descriptor).try_into().unwrap_or(Chain::External);
             loop {
-                let batch = store.get_script_batch(batch_count, &descriptor)?;
+                let batch = store.get_script_batch(batch_count, gap_limit, &descriptor)?;
 
                 let s: Vec<_> = batch.value.iter().map(|e| &e.0).collect();
                 let result: Vec<Vec<History>> = self.get_scripts_history(&s).await?;
@@ -304,10 +363,10 @@ impl EsploraWasmClient {
                 if let Some(max) = max {
                     match chain {
                         Chain::External => {
-                            data.last_unused.external = 1 + max + batch_count * BATCH_SIZE
+                            data.last_unused.external = 1 + max + batch_count * gap_limit
                         }
                         Chain::Internal => {
-                            data.last_unused.internal = 1 + max + batch_count * BATCH_SIZE
+                            data.last_unused.internal = 1 + max + batch_count * gap_limit
                         }
                     }
                 };
@@ -450,36 +509,31 @@ impl EsploraWasmClient {
         store: &Store,
         descriptor: &WolletDescriptor,
     ) -> Result<DownloadTxResult, Error> {
-        let mut txs = vec![];
-        let mut unblinds = vec![];
+        let mut result = DownloadTxResult::default();
 
-        let mut txs_in_db = store.cache.all_txs.keys().cloned().collect();
+        let txs_in_db: HashSet<Txid> = store.cache.all_txs.keys().cloned().collect();
         let txs_to_download: Vec<Txid> = history_txs_id.difference(&txs_in_db).cloned().collect();
 
+        let mut txs_downloaded = vec![];
         for txid in txs_to_download {
-            let tx = self.get_transaction(txid).await?;
-
-            txs_in_db.insert(txid);
-
-            for (i, output) in tx.output.iter().enumerate() {
-                // could be the searched script it's not yet in the store, because created in the current run, thus it's searched also in the `scripts`
-                if store.cache.paths.contains_key(&output.script_pubkey)
-                    || scripts.contains_key(&output.script_pubkey)
-                {
-                    let vout = i as u32;
-                    let outpoint = OutPoint { txid, vout };
-
-                    match try_unblind(output.clone(), descriptor) {
-                            Ok(unblinded) => unblinds.push((outpoint, unblinded)),
-                            Err(_) => tracing::info!("{} cannot unblind, ignoring (could be sender messed up with the blinding process)", outpoint),
-                        }
-                }
-            }
-
-            txs.push((txid, tx));
+            txs_downloaded.push(self.get_transaction(txid).await?);
         }
+        let txs_stored = history_txs_id
+            .intersection(&txs_in_db)
+            .filter_map(|txid| store.cache.all_txs.get(txid));
+        unblind_outputs(
+            txs_downloaded.iter().chain(txs_stored),
+            scripts,
+            store,
+            descriptor,
+            &mut result,
+        );
+        result.txs = txs_downloaded
+            .into_iter()
+            .map(|tx| (tx.txid(), tx))
+            .collect();
 
-        Ok(DownloadTxResult { txs, unblinds })
+        Ok(result)
     }
 
     async fn download_headers(
@@ -543,6 +597,12 @@ async fn get_with_retry(url: &str) -> Result<Response, Error> {
 
             async_sleep(secs * 1000).await;
             attempt += 1;
+        } else if !response.status().is_success() {
+            let status = response.status();
+            return Err(Error::Generic(format!(
+                "{url} returned {status}: {}",
+                response.text().await.unwrap_or_default()
+            )));
         } else {
             return Ok(response);
         }
@@ -602,6 +662,7 @@ struct EsploraTx {
 
 #[derive(Deserialize)]
 struct Status {
+    confirmed: bool,
     block_height: Option<i32>,
     block_hash: Option<BlockHash>,
 }
@@ -630,6 +691,52 @@ mod tests {
         test_esplora_url(&esplora_url).await;
     }
 
+    #[tokio::test]
+    async fn test_history_paging() {
+        let hash = "1466275836220db2944ca059a3a10ef6fd2ea684b0688d2c379296888a206003";
+        let tx = |i: u8, confirmed: bool| {
+            let txid =
+                elements::Txid::from_raw_hash(elements::hashes::Hash::from_byte_array([i; 32]));
+            let status = if confirmed {
+                format!(r#"{{"confirmed":true,"block_height":{i},"block_hash":"{hash}"}}"#)
+            } else {
+                r#"{"confirmed":false}"#.to_string()
+            };
+            (txid, format!(r#"{{"txid":"{txid}","status":{status}}}"#))
+        };
+        // A mempool transaction and a full page of confirmed ones, then the last page
+        let first: Vec<_> = std::iter::once(tx(100, false))
+            .chain((1..=25).map(|i| tx(i, true)))
+            .collect();
+        let second: Vec<_> = (26..=28).map(|i| tx(i, true)).collect();
+        let last_seen = first.last().unwrap().0;
+        let page = |txs: &[(elements::Txid, String)]| {
+            let body: Vec<_> = txs.iter().map(|(_, json)| json.clone()).collect();
+            format!("[{}]", body.join(","))
+        };
+        let mut pages = vec![page(&first), page(&second)].into_iter();
+        let (sender, paths) = std::sync::mpsc::channel();
+        let url = lwk_test_util::http_server(move |request| {
+            let _ = sender.send(request.path);
+            lwk_test_util::HttpResponse::ok(pages.next().unwrap_or_default())
+        });
+
+        let network = ElementsNetwork::default_regtest();
+        let client = EsploraWasmClient::new(network, &format!("{url}/"), false);
+        let script =
+            elements::Script::new_v0_wpkh(&elements::hashes::Hash::from_byte_array([0; 20]));
+        let histories = client.get_scripts_history(&[&script]).await.unwrap();
+        let history = &histories[0];
+        assert_eq!(history.len(), 29);
+        assert_eq!(history[0].height, -1);
+        assert_eq!(history[28].height, 28);
+
+        let paths: Vec<String> = paths.try_iter().collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths[0].ends_with("/txs"));
+        assert!(paths[1].ends_with(&format!("/txs/chain/{last_seen}")));
+    }
+
     #[tokio::test]
     async fn sleep_test() {
         // TODO this doesn't last a second when run, is it right?
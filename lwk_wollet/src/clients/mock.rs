@@ -0,0 +1,127 @@
+//! A blockchain backend serving data set by the caller, for tests
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use elements::{BlockHash, BlockHeader, Script, Transaction, Txid};
+
+use crate::clients::{BlockchainBackend, History};
+use crate::store::Height;
+use crate::Error;
+
+/// A [`BlockchainBackend`] serving some transactions, headers and script histories
+///
+/// Requests of data which has not been set fail, as every request if `fail` is set, so that
+/// tests can't reach a real server. Scripts without history have an empty one.
+#[derive(Debug)]
+pub struct MockBackend {
+    /// The blockchain latest block
+    pub tip: Option<BlockHeader>,
+
+    /// The transactions served
+    pub txs: Vec<Transaction>,
+
+    /// The headers served, by their height
+    pub headers: Vec<BlockHeader>,
+
+    /// The history of the scripts
+    pub histories: HashMap<Script, Vec<History>>,
+
+    /// See [`BlockchainBackend::scan_batches()`]
+    pub scan_batches: u32,
+
+    /// Fail every request, like a backend which cannot be reached
+    pub fail: bool,
+
+    /// The number of script history requests received
+    pub requests: AtomicUsize,
+
+    /// The number of scripts whose history has been requested
+    pub scripts: AtomicUsize,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self {
+            tip: None,
+            txs: vec![],
+            headers: vec![],
+            histories: HashMap::new(),
+            scan_batches: 1,
+            fail: false,
+            requests: AtomicUsize::new(0),
+            scripts: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl MockBackend {
+    fn check_fail(&self) -> Result<(), Error> {
+        match self.fail {
+            true => Err(Error::Generic("MockBackend is failing".into())),
+            false => Ok(()),
+        }
+    }
+}
+
+impl BlockchainBackend for MockBackend {
+    fn tip(&mut self) -> Result<BlockHeader, Error> {
+        self.check_fail()?;
+        self.tip
+            .clone()
+            .ok_or_else(|| Error::Generic("MockBackend::tip is not mocked".into()))
+    }
+
+    fn broadcast(&self, _tx: &Transaction) -> Result<Txid, Error> {
+        self.check_fail()?;
+        Err(Error::Generic(
+            "MockBackend::broadcast is not mocked".into(),
+        ))
+    }
+
+    fn get_transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>, Error> {
+        self.check_fail()?;
+        txids
+            .iter()
+            .map(|txid| {
+                self.txs
+                    .iter()
+                    .find(|tx| tx.txid() == *txid)
+                    .cloned()
+                    .ok_or_else(|| Error::Generic(format!("MockBackend has no tx {txid}")))
+            })
+            .collect()
+    }
+
+    fn get_headers(
+        &self,
+        heights: &[Height],
+        _height_blockhash: &HashMap<Height, BlockHash>,
+    ) -> Result<Vec<BlockHeader>, Error> {
+        self.check_fail()?;
+        heights
+            .iter()
+            .map(|height| {
+                self.headers
+                    .iter()
+                    .find(|h| h.height == *height)
+                    .cloned()
+                    .ok_or_else(|| Error::Generic(format!("MockBackend has no header {height}")))
+            })
+            .collect()
+    }
+
+    fn get_scripts_history(&self, scripts: &[&Script]) -> Result<Vec<Vec<History>>, Error> {
+        self.check_fail()?;
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        self.scripts.fetch_add(scripts.len(), Ordering::Relaxed);
+        Ok(scripts
+            .iter()
+            .map(|s| self.histories.get(*s).cloned().unwrap_or_default())
+            .collect())
+    }
+
+    fn scan_batches(&self) -> u32 {
+        self.scan_batches
+    }
+}
@@ -1,28 +1,45 @@
 use crate::{
+    fee::FeePolicy,
     store::{Height, Store, Timestamp, BATCH_SIZE},
     update::{DownloadTxResult, Update},
-    Chain, Error, Wollet, WolletDescriptor, EC,
+    AssetStats, Chain, ElementsNetwork, Error, Wollet, WolletDescriptor, EC,
 };
+use elements::hashes::{sha256, Hash, HashEngine};
 use elements::{
     bitcoin::bip32::ChildNumber,
-    confidential::{Asset, Nonce, Value},
-    OutPoint, Script, TxOut, TxOutSecrets,
+    confidential::{Asset, AssetBlindingFactor, Nonce, Value, ValueBlindingFactor},
+    AssetId, OutPoint, Script, TxOut, TxOutSecrets,
 };
-use elements::{BlockHash, BlockHeader, Transaction, Txid};
-use lwk_common::derive_blinding_key;
+use elements::{Block, BlockHash, BlockHeader, Transaction, Txid};
+use lwk_common::{derive_blinding_key, CancellationToken};
 use serde::Deserialize;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::atomic;
 
+/// Number of confirmed transactions returned by esplora servers in a page of a script history
+#[cfg(any(feature = "esplora", feature = "esplora_wasm"))]
+const ESPLORA_CONFIRMED_PAGE_SIZE: usize = 25;
+
 #[cfg(feature = "esplora")]
 pub(crate) mod esplora_client;
 
+#[cfg(feature = "esplora")]
+pub(crate) mod update_client;
+
+mod txout_proof;
+
 #[cfg(feature = "electrum")]
 pub(crate) mod electrum_client;
 
+#[cfg(feature = "electrum")]
+mod spv;
+
 #[cfg(feature = "esplora_wasm")]
 pub(crate) mod esplora_wasm_client;
 
+#[cfg(any(test, feature = "mock_backend"))]
+pub(crate) mod mock;
+
 /// Trait implemented by types that can fetch data from a blockchain data source.
 pub trait BlockchainBackend {
     /// Get the blockchain latest block
@@ -31,6 +48,16 @@ pub trait BlockchainBackend {
     /// Broadcast a transaction
     fn broadcast(&self, tx: &Transaction) -> Result<Txid, Error>;
 
+    /// Broadcast a transaction, unless the operation has been cancelled before sending it
+    fn broadcast_cancellable(
+        &self,
+        tx: &Transaction,
+        cancel: &CancellationToken,
+    ) -> Result<Txid, Error> {
+        check_cancelled(cancel)?;
+        self.broadcast(tx)
+    }
+
     /// Get a list of transactions
     fn get_transactions(&self, txids: &[Txid]) -> Result<Vec<Transaction>, Error>;
 
@@ -46,75 +73,152 @@ pub trait BlockchainBackend {
     /// Get the transactions involved in a list of scripts
     fn get_scripts_history(&self, scripts: &[&Script]) -> Result<Vec<Vec<History>>, Error>;
 
-    /// Scan the blockchain for the scripts generated by a watch-only wallet
-    fn full_scan(&mut self, wollet: &Wollet) -> Result<Option<Update>, Error> {
-        let descriptor = wollet.wollet_descriptor();
-        let store = &wollet.store;
-        let mut txid_height = HashMap::new();
-        let mut scripts = HashMap::new();
+    /// Get the status of a list of scripts, as defined by the electrum protocol, `None` if the
+    /// script has no history
+    ///
+    /// Backends able to notify changes, like Electrum servers, return it without requesting the
+    /// history of the scripts, the others compute it from the history, see [`script_status()`].
+    fn get_scripts_status(&mut self, scripts: &[&Script]) -> Result<Vec<Option<[u8; 32]>>, Error> {
+        Ok(self
+            .get_scripts_history(scripts)?
+            .iter()
+            .map(|history| script_status(history))
+            .collect())
+    }
 
-        let mut last_unused_external = 0;
-        let mut last_unused_internal = 0;
-        let mut height_blockhash = HashMap::new();
+    /// Get the header of the block at `height` in the best chain
+    fn header(&self, height: Height) -> Result<BlockHeader, Error> {
+        self.get_headers(&[height], &HashMap::new())?
+            .pop()
+            .ok_or_else(|| Error::Generic(format!("missing header at height {height}")))
+    }
 
-        for descriptor in descriptor.descriptor().clone().into_single_descriptors()? {
-            let mut batch_count = 0;
-            let chain: Chain = (&descriptor).try_into().unwrap_or(Chain::External);
-            loop {
-                let batch = store.get_script_batch(batch_count, &descriptor)?;
+    /// Check the backend is on `network` by comparing the genesis block
+    ///
+    /// Regtest networks are not checked, see [`ElementsNetwork::genesis_hash()`]. Called at the
+    /// beginning of every scan, since a backend on another network returns empty histories
+    /// instead of failing.
+    fn check_network(&self, network: ElementsNetwork) -> Result<(), Error> {
+        if network.genesis_hash().is_some() {
+            check_genesis(network, self.header(0)?.block_hash())?;
+        }
+        Ok(())
+    }
 
-                let s: Vec<_> = batch.value.iter().map(|e| &e.0).collect();
-                let result: Vec<Vec<History>> = self.get_scripts_history(&s)?;
-                if !batch.cached {
-                    scripts.extend(batch.value);
-                }
-                let max = result
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, v)| !v.is_empty())
-                    .map(|(i, _)| i as u32)
-                    .max();
-                if let Some(max) = max {
-                    match chain {
-                        Chain::External => {
-                            last_unused_external = 1 + max + batch_count * BATCH_SIZE
-                        }
-                        Chain::Internal => {
-                            last_unused_internal = 1 + max + batch_count * BATCH_SIZE
-                        }
-                    }
-                };
-
-                let flattened: Vec<History> = result.into_iter().flatten().collect();
-
-                if flattened.is_empty() {
-                    break;
-                }
+    /// Get a full block, by height in the best chain or by hash
+    ///
+    /// Backends not able to serve full blocks, like Electrum servers, return an error.
+    fn block(&self, block: BlockRef) -> Result<Block, Error> {
+        Err(Error::Generic(format!(
+            "cannot get block {block}, not supported by the backend"
+        )))
+    }
 
-                for el in flattened {
-                    // el.height = -1 means unconfirmed with unconfirmed parents
-                    // el.height =  0 means unconfirmed with confirmed parents
-                    // but we threat those tx the same
-                    let height = el.height.max(0);
-                    let txid = el.txid;
-                    if height == 0 {
-                        txid_height.insert(txid, None);
-                    } else {
-                        txid_height.insert(txid, Some(height as u32));
-                        if let Some(block_hash) = el.block_hash {
-                            height_blockhash.insert(height as u32, block_hash);
-                        }
-                    }
-                }
+    /// Get the merkle proof of the transaction `txid` confirmed at `height`
+    ///
+    /// Returns the position of the transaction in the block and the merkle branch, with the
+    /// hashes in the byte order returned by electrum servers.
+    fn merkle_proof(&self, txid: &Txid, height: Height) -> Result<(usize, Vec<[u8; 32]>), Error> {
+        Err(Error::Generic(format!(
+            "cannot get merkle proof of {txid} at height {height}, not supported by the backend"
+        )))
+    }
 
-                batch_count += 1;
-            }
-        }
+    /// Statistics of `asset` over the whole chain, like the amount in circulation
+    ///
+    /// Only backends indexing the assets support it, such as esplora.
+    fn asset_stats(&self, asset: &AssetId) -> Result<AssetStats, Error> {
+        Err(Error::Generic(format!(
+            "cannot get statistics of asset {asset}, not supported by the backend"
+        )))
+    }
+
+    /// Get the proof the transaction `txid` is confirmed at `height`, in the format returned by
+    /// the `gettxoutproof` RPC of elements core and accepted by `importprunedfunds`
+    fn txout_proof(&self, txid: &Txid, height: Height) -> Result<Vec<u8>, Error> {
+        let (pos, merkle) = self.merkle_proof(txid, height)?;
+        let header = self.header(height)?;
+        txout_proof::txout_proof(&header, txid, pos, &merkle)
+    }
+
+    /// Get the fee rates estimated by the backend, by confirmation target in blocks
+    ///
+    /// Fee rates are in satoshi per 1000 virtual bytes, backends not supporting estimation return
+    /// an empty map.
+    fn fee_estimates(&self) -> Result<BTreeMap<u16, f32>, Error> {
+        Ok(BTreeMap::new())
+    }
+
+    /// The fee rate to confirm a transaction within `target_blocks` according to `policy`
+    ///
+    /// If the backend estimates are unavailable, because of an error or absurd values, the
+    /// fallback fee rates of the policy are used.
+    fn fee_rate(&self, target_blocks: u16, policy: &FeePolicy) -> f32 {
+        let estimates = self.fee_estimates().unwrap_or_else(|e| {
+            tracing::warn!("fee estimates unavailable, using fallback: {e}");
+            BTreeMap::new()
+        });
+        policy.fee_rate(target_blocks, &estimates)
+    }
+
+    /// Number of batches of scripts of every chain requested together while scanning
+    ///
+    /// Requesting more batches at once reduces the round trips needed to scan wallets with many
+    /// used addresses, at the cost of requesting some unused scripts at the end of the scan.
+    fn scan_batches(&self) -> u32 {
+        1
+    }
+
+    /// The asset registry queried for the assets received in scans, if any
+    ///
+    /// When set, [`BlockchainBackend::full_scan()`] fetches the registry entry of the assets the
+    /// wallet receives for the first time, verifying the contract commits to the asset with the
+    /// issuance transaction, see [`crate::AssetInfo::verified`].
+    fn registry_url(&self) -> Option<&str> {
+        None
+    }
+
+    /// Scan the blockchain for the scripts generated by a watch-only wallet
+    fn full_scan(&mut self, wollet: &Wollet) -> Result<Option<Update>, Error> {
+        self.full_scan_cancellable(wollet, &CancellationToken::new())
+    }
 
+    /// Like [`BlockchainBackend::full_scan()`], but returns [`Error::Cancelled`] once `cancel` is
+    /// cancelled
+    ///
+    /// The token is checked between the batches of requests to the server, a batch already sent
+    /// is completed, or fails with the client timeout, before the scan is aborted.
+    /// The wallet is only changed when the returned update is applied, thus a cancelled scan
+    /// leaves the wallet in the same state it was before the scan.
+    fn full_scan_cancellable(
+        &mut self,
+        wollet: &Wollet,
+        cancel: &CancellationToken,
+    ) -> Result<Option<Update>, Error> {
+        check_cancelled(cancel)?;
+        self.check_network(wollet.network())?;
+        let descriptor = wollet.wollet_descriptor();
+        let store = &wollet.store;
+        let ScanHistory {
+            txid_height,
+            scripts,
+            last_unused_external,
+            last_unused_internal,
+            height_blockhash,
+            statuses,
+        } = scan_history(self, wollet, cancel)?;
+
+        check_cancelled(cancel)?;
         let tip = self.tip()?;
 
         let history_txs_id: HashSet<Txid> = txid_height.keys().cloned().collect();
         let new_txs = self.download_txs(&history_txs_id, &scripts, store, &descriptor)?;
+        check_cancelled(cancel)?;
+        #[cfg(feature = "esplora")]
+        let assets = new_assets_info(self, wollet, &new_txs);
+        #[cfg(not(feature = "esplora"))]
+        let assets = vec![];
+        check_cancelled(cancel)?;
         let history_txs_heights_plus_tip: HashSet<Height> = txid_height
             .values()
             .filter_map(|e| *e)
@@ -122,6 +226,7 @@ pub trait BlockchainBackend {
             .collect();
         let timestamps =
             self.download_headers(&history_txs_heights_plus_tip, &height_blockhash, store)?;
+        check_cancelled(cancel)?;
 
         let store_last_unused_external = store
             .cache
@@ -135,14 +240,18 @@ pub trait BlockchainBackend {
         let last_unused_changed = store_last_unused_external != last_unused_external
             || store_last_unused_internal != last_unused_internal;
 
-        let changed = !new_txs.txs.is_empty()
+        let scripts_status = scripts_status_changes(&statuses, store);
+
+        let changed = !new_txs.is_empty()
+            || !assets.is_empty()
             || last_unused_changed
             || !scripts.is_empty()
+            || !scripts_status.is_empty()
             || !timestamps.is_empty()
             || store.cache.tip != (tip.height, tip.block_hash());
 
         if changed {
-            tracing::debug!("something changed: !new_txs.txs.is_empty():{} last_unused_changed:{} !scripts.is_empty():{} !timestamps.is_empty():{}", !new_txs.txs.is_empty(), last_unused_changed, !scripts.is_empty(), !timestamps.is_empty() );
+            tracing::debug!("something changed: !new_txs.is_empty():{} last_unused_changed:{} !scripts.is_empty():{} !timestamps.is_empty():{}", !new_txs.is_empty(), last_unused_changed, !scripts.is_empty(), !timestamps.is_empty() );
 
             let txid_height_new: Vec<_> = txid_height
                 .iter()
@@ -167,6 +276,11 @@ pub trait BlockchainBackend {
                 timestamps,
                 scripts,
                 tip,
+                utxo_locks: vec![],
+                utxo_unlocks: vec![],
+                labels: vec![],
+                assets,
+                scripts_status,
             };
             Ok(Some(update))
         } else {
@@ -174,7 +288,289 @@ pub trait BlockchainBackend {
         }
     }
 
+    /// Scan the blockchain like [`BlockchainBackend::full_scan()`], applying the result to
+    /// `wollet` with multiple updates
+    ///
+    /// New transactions are downloaded in batches and applied as soon as their size reaches
+    /// `memory_budget` bytes, so that the scan never holds all the new transactions in memory at
+    /// once. This is useful for very large wallets on devices with little memory, note that the
+    /// wallet still keeps in memory the transactions already applied.
+    ///
+    /// If the scan fails midway the wallet contains only part of the new transactions, a
+    /// following scan completes it. The wallet tip is advanced only by the last update, so that
+    /// a wallet left with part of the history does not claim to be synced.
+    ///
+    /// Returns whether the wallet changed.
+    fn full_scan_chunked(
+        &mut self,
+        wollet: &mut Wollet,
+        memory_budget: usize,
+    ) -> Result<bool, Error> {
+        self.check_network(wollet.network())?;
+        let descriptor = wollet.wollet_descriptor();
+        let ScanHistory {
+            txid_height,
+            scripts,
+            height_blockhash,
+            statuses,
+            ..
+        } = scan_history(self, wollet, &CancellationToken::new())?;
+        let tip = self.tip()?;
+        // The intermediate updates keep the wallet tip, the genesis for a wallet never synced
+        let wollet_tip = match wollet.store.tip_header.clone() {
+            Some(header) => header,
+            None => self.header(0)?,
+        };
+        let update = |new_txs, txid_height_new, scripts| Update {
+            new_txs,
+            txid_height_new,
+            txid_height_delete: vec![],
+            timestamps: vec![],
+            scripts,
+            tip: wollet_tip.clone(),
+            utxo_locks: vec![],
+            utxo_unlocks: vec![],
+            labels: vec![],
+            assets: vec![],
+            scripts_status: vec![],
+        };
+        let mut changed = false;
+
+        // New scripts are applied first, so that outputs sent to them are unblinded
+        if !scripts.is_empty() {
+            wollet.apply_update(update(DownloadTxResult::default(), vec![], scripts))?;
+            changed = true;
+        }
+
+        let txs_to_download: Vec<Txid> = txid_height
+            .keys()
+            .filter(|txid| !wollet.store.cache.all_txs.contains_key(*txid))
+            .cloned()
+            .collect();
+        let mut pending = DownloadTxResult::default();
+        let mut pending_size = 0;
+        let mut batches = txs_to_download.chunks(BATCH_SIZE as usize).peekable();
+        while let Some(batch) = batches.next() {
+            let batch: HashSet<Txid> = batch.iter().cloned().collect();
+            let downloaded =
+                self.download_txs(&batch, &HashMap::new(), &wollet.store, &descriptor)?;
+            pending_size += downloaded
+                .txs
+                .iter()
+                .map(|(_, tx)| tx.size())
+                .sum::<usize>();
+            pending.txs.extend(downloaded.txs);
+            pending.unblinds.extend(downloaded.unblinds);
+            pending.unblind_failures.extend(downloaded.unblind_failures);
+
+            if pending_size >= memory_budget || batches.peek().is_none() {
+                // Heights are added only together with their transactions
+                let txid_height_new = pending
+                    .txs
+                    .iter()
+                    .map(|(txid, _)| (*txid, txid_height.get(txid).cloned().flatten()))
+                    .collect();
+                let new_txs = std::mem::take(&mut pending);
+                wollet.apply_update(update(new_txs, txid_height_new, HashMap::new()))?;
+                pending_size = 0;
+                changed = true;
+            }
+        }
+
+        let store = &wollet.store;
+        let txid_height_new: Vec<_> = txid_height
+            .iter()
+            .filter(|(k, v)| store.cache.heights.get(*k) != Some(*v))
+            .map(|(k, v)| (*k, *v))
+            .collect();
+        let txid_height_delete: Vec<_> = store
+            .cache
+            .heights
+            .keys()
+            .filter(|k| !txid_height.contains_key(*k))
+            .cloned()
+            .collect();
+        let history_txs_heights_plus_tip: HashSet<Height> = txid_height
+            .values()
+            .filter_map(|e| *e)
+            .chain(std::iter::once(tip.height))
+            .collect();
+        let timestamps =
+            self.download_headers(&history_txs_heights_plus_tip, &height_blockhash, store)?;
+        // The status is applied last, so that a scan failing midway is not considered complete
+        let scripts_status = scripts_status_changes(&statuses, store);
+
+        if !txid_height_new.is_empty()
+            || !txid_height_delete.is_empty()
+            || !timestamps.is_empty()
+            || !scripts_status.is_empty()
+            || store.cache.tip != (tip.height, tip.block_hash())
+        {
+            wollet.apply_update(Update {
+                new_txs: DownloadTxResult::default(),
+                txid_height_new,
+                txid_height_delete,
+                timestamps,
+                scripts: HashMap::new(),
+                tip,
+                utxo_locks: vec![],
+                utxo_unlocks: vec![],
+                labels: vec![],
+                assets: vec![],
+                scripts_status,
+            })?;
+            changed = true;
+        }
+
+        Ok(changed)
+    }
+
+    /// Scan the blockchain like [`BlockchainBackend::full_scan()`], requesting only the history of
+    /// the scripts whose status changed since the previous scan
+    ///
+    /// New scripts are derived only if the last batch derived by the previous scans is now used.
+    /// Wallets scanned by versions not storing the status of the scripts request the history of
+    /// all their used scripts once.
+    fn incremental_scan(&mut self, wollet: &Wollet) -> Result<Option<Update>, Error> {
+        let store = &wollet.store;
+        if store.cache.paths.is_empty() {
+            // Never scanned
+            return self.full_scan(wollet);
+        }
+        self.check_network(wollet.network())?;
+        let descriptor = wollet.wollet_descriptor();
+        let IncrementalHistory {
+            txid_height,
+            txid_height_delete,
+            scripts,
+            scripts_status,
+            height_blockhash,
+        } = incremental_history(self, wollet)?;
+
+        let tip = self.tip()?;
+
+        let history_txs_id: HashSet<Txid> = txid_height.keys().cloned().collect();
+        let new_txs = self.download_txs(&history_txs_id, &scripts, store, &descriptor)?;
+        #[cfg(feature = "esplora")]
+        let assets = new_assets_info(self, wollet, &new_txs);
+        #[cfg(not(feature = "esplora"))]
+        let assets = vec![];
+        let history_txs_heights_plus_tip: HashSet<Height> = txid_height
+            .values()
+            .filter_map(|e| *e)
+            .chain(std::iter::once(tip.height))
+            .collect();
+        let timestamps =
+            self.download_headers(&history_txs_heights_plus_tip, &height_blockhash, store)?;
+
+        let txid_height_new: Vec<_> = txid_height
+            .into_iter()
+            .filter(|(k, v)| store.cache.heights.get(k) != Some(v))
+            .collect();
+
+        let changed = !new_txs.is_empty()
+            || !assets.is_empty()
+            || !txid_height_new.is_empty()
+            || !txid_height_delete.is_empty()
+            || !scripts.is_empty()
+            || !scripts_status.is_empty()
+            || !timestamps.is_empty()
+            || store.cache.tip != (tip.height, tip.block_hash());
+
+        if changed {
+            Ok(Some(Update {
+                new_txs,
+                txid_height_new,
+                txid_height_delete,
+                timestamps,
+                scripts,
+                tip,
+                utxo_locks: vec![],
+                utxo_unlocks: vec![],
+                labels: vec![],
+                assets,
+                scripts_status,
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Scan the blockchain for the transactions involving a single script of a watch-only wallet
+    ///
+    /// This is much faster than [`BlockchainBackend::full_scan()`] and it's useful to detect
+    /// payments to a specific address. The script must have been already derived by the wallet,
+    /// which happens for the addresses up to the last used plus a batch during a full scan.
+    ///
+    /// The returned update removes only the transactions involving the script which are no
+    /// longer in its history, and keeps the wallet tip, which is advanced by full scans only.
+    fn script_scan(&mut self, wollet: &Wollet, script: &Script) -> Result<Option<Update>, Error> {
+        self.check_network(wollet.network())?;
+        let descriptor = wollet.wollet_descriptor();
+        let store = &wollet.store;
+        if !store.cache.paths.contains_key(script) {
+            return Err(Error::ScriptNotMine);
+        }
+        let tip = store
+            .tip_header
+            .clone()
+            .ok_or_else(|| Error::Generic("The wallet has never been synced".into()))?;
+
+        let mut txid_height = HashMap::new();
+        let mut height_blockhash = HashMap::new();
+        for el in self.get_scripts_history(&[script])?.into_iter().flatten() {
+            add_history(el, &mut txid_height, &mut height_blockhash);
+        }
+
+        let history_txs_id: HashSet<Txid> = txid_height.keys().cloned().collect();
+        let new_txs = self.download_txs(&history_txs_id, &HashMap::new(), store, &descriptor)?;
+        let history_txs_heights: HashSet<Height> =
+            txid_height.values().filter_map(|e| *e).collect();
+        let timestamps = self.download_headers(&history_txs_heights, &height_blockhash, store)?;
+
+        // Wallet transactions involving the script missing from its history have been evicted
+        // or replaced
+        let txid_height_delete: Vec<_> = store
+            .cache
+            .heights
+            .keys()
+            .filter(|txid| !history_txs_id.contains(*txid))
+            .filter(|txid| tx_involves_script(store, txid, script))
+            .cloned()
+            .collect();
+        let txid_height_new: Vec<_> = txid_height
+            .into_iter()
+            .filter(|(k, v)| store.cache.heights.get(k) != Some(v))
+            .collect();
+
+        let changed = !new_txs.is_empty()
+            || !txid_height_new.is_empty()
+            || !txid_height_delete.is_empty()
+            || !timestamps.is_empty();
+
+        if changed {
+            Ok(Some(Update {
+                new_txs,
+                txid_height_new,
+                txid_height_delete,
+                timestamps,
+                scripts: HashMap::new(),
+                tip,
+                utxo_locks: vec![],
+                utxo_unlocks: vec![],
+                labels: vec![],
+                assets: vec![],
+                scripts_status: vec![],
+            }))
+        } else {
+            Ok(None)
+        }
+    }
+
     /// Download and unblind the transactions
+    ///
+    /// Outputs of transactions already downloaded are unblinded only if they were never
+    /// processed, for instance if the transactions were downloaded by a previous version.
     fn download_txs(
         &self,
         history_txs_id: &HashSet<Txid>,
@@ -182,40 +578,28 @@ pub trait BlockchainBackend {
         store: &Store,
         descriptor: &WolletDescriptor,
     ) -> Result<DownloadTxResult, Error> {
-        let mut txs = vec![];
-        let mut unblinds = vec![];
+        let mut result = DownloadTxResult::default();
 
-        let mut txs_in_db = store.cache.all_txs.keys().cloned().collect();
+        let txs_in_db: HashSet<Txid> = store.cache.all_txs.keys().cloned().collect();
         let txs_to_download: Vec<Txid> = history_txs_id.difference(&txs_in_db).cloned().collect();
 
         let txs_downloaded = self.get_transactions(&txs_to_download)?;
+        let txs_stored = history_txs_id
+            .intersection(&txs_in_db)
+            .filter_map(|txid| store.cache.all_txs.get(txid));
+        unblind_outputs(
+            txs_downloaded.iter().chain(txs_stored),
+            scripts,
+            store,
+            descriptor,
+            &mut result,
+        );
+        result.txs = txs_downloaded
+            .into_iter()
+            .map(|tx| (tx.txid(), tx))
+            .collect();
 
-        for tx in txs_downloaded.into_iter() {
-            let txid = tx.txid();
-            txs_in_db.insert(txid);
-
-            for (i, output) in tx.output.iter().enumerate() {
-                // could be the searched script it's not yet in the store, because created in the current run, thus it's searched also in the `scripts`
-                if store.cache.paths.contains_key(&output.script_pubkey)
-                    || scripts.contains_key(&output.script_pubkey)
-                {
-                    let vout = i as u32;
-                    let outpoint = OutPoint {
-                        txid: tx.txid(),
-                        vout,
-                    };
-
-                    match try_unblind(output.clone(), descriptor) {
-                            Ok(unblinded) => unblinds.push((outpoint, unblinded)),
-                            Err(_) => tracing::info!("{} cannot unblind, ignoring (could be sender messed up with the blinding process)", outpoint),
-                        }
-                }
-            }
-
-            txs.push((txid, tx));
-        }
-
-        Ok(DownloadTxResult { txs, unblinds })
+        Ok(result)
     }
 
     /// Download the headers if not available in the store
@@ -244,7 +628,411 @@ pub trait BlockchainBackend {
     }
 }
 
-#[derive(Deserialize)]
+/// The result of querying the history of the wallet scripts
+struct ScanHistory {
+    txid_height: HashMap<Txid, Option<Height>>,
+    scripts: HashMap<Script, (Chain, ChildNumber)>,
+    last_unused_external: u32,
+    last_unused_internal: u32,
+    height_blockhash: HashMap<Height, BlockHash>,
+
+    /// The status of the scripts with history
+    statuses: HashMap<Script, [u8; 32]>,
+}
+
+/// Get the history of the wallet scripts, deriving new scripts until a batch without history
+///
+/// Every round requests [`BlockchainBackend::scan_batches()`] batches of every chain not completed
+/// yet with a single call, the batches following the first one without history are ignored, so
+/// that the result doesn't depend on the number of batches requested together.
+fn scan_history<B: BlockchainBackend + ?Sized>(
+    client: &B,
+    wollet: &Wollet,
+    cancel: &CancellationToken,
+) -> Result<ScanHistory, Error> {
+    scan_history_from(client, wollet, |_| Some(0), cancel)
+}
+
+/// Like [`scan_history()`], starting from the batch returned by `first_batch` for every chain
+///
+/// Chains for which `first_batch` returns `None` are not scanned.
+fn scan_history_from<B: BlockchainBackend + ?Sized>(
+    client: &B,
+    wollet: &Wollet,
+    first_batch: impl Fn(Chain) -> Option<u32>,
+    cancel: &CancellationToken,
+) -> Result<ScanHistory, Error> {
+    let descriptor = wollet.wollet_descriptor();
+    let store = &wollet.store;
+    let mut txid_height = HashMap::new();
+    let mut scripts = HashMap::new();
+    let mut statuses = HashMap::new();
+
+    let mut last_unused_external = 0;
+    let mut last_unused_internal = 0;
+    let mut height_blockhash = HashMap::new();
+
+    let descriptors = descriptor.descriptor().clone().into_single_descriptors()?;
+    let scan_batches = client.scan_batches().max(1);
+    let gap_limit = wollet.gap_limit();
+    // The next batch to request for every descriptor, `None` once completed
+    let mut next_batch: Vec<Option<u32>> = descriptors
+        .iter()
+        .map(|d| first_batch(d.try_into().unwrap_or(Chain::External)))
+        .collect();
+
+    while next_batch.iter().any(Option::is_some) {
+        check_cancelled(cancel)?;
+        let mut round = vec![];
+        for (i, descriptor) in descriptors.iter().enumerate() {
+            if let Some(first) = next_batch[i] {
+                for batch_count in first..first + scan_batches {
+                    round.push((
+                        i,
+                        batch_count,
+                        store.get_script_batch(batch_count, gap_limit, descriptor)?,
+                    ));
+                }
+            }
+        }
+
+        let s: Vec<_> = round
+            .iter()
+            .flat_map(|(_, _, batch)| batch.value.iter().map(|e| &e.0))
+            .collect();
+        let mut results = client.get_scripts_history(&s)?.into_iter();
+
+        for (i, batch_count, batch) in round {
+            let result: Vec<Vec<History>> = results.by_ref().take(batch.value.len()).collect();
+            if next_batch[i].is_none() {
+                // A previous batch of this round had no history
+                continue;
+            }
+            let chain: Chain = (&descriptors[i]).try_into().unwrap_or(Chain::External);
+            for ((script, _), history) in batch.value.iter().zip(result.iter()) {
+                if let Some(status) = script_status(history) {
+                    statuses.insert(script.clone(), status);
+                }
+            }
+            if !batch.cached {
+                scripts.extend(batch.value);
+            }
+            let max = result
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| !v.is_empty())
+                .map(|(i, _)| i as u32)
+                .max();
+            if let Some(max) = max {
+                match chain {
+                    Chain::External => last_unused_external = 1 + max + batch_count * gap_limit,
+                    Chain::Internal => last_unused_internal = 1 + max + batch_count * gap_limit,
+                }
+            };
+
+            let flattened: Vec<History> = result.into_iter().flatten().collect();
+
+            if flattened.is_empty() {
+                next_batch[i] = None;
+                continue;
+            }
+
+            for el in flattened {
+                add_history(el, &mut txid_height, &mut height_blockhash);
+            }
+
+            next_batch[i] = Some(batch_count + 1);
+        }
+    }
+
+    Ok(ScanHistory {
+        txid_height,
+        scripts,
+        last_unused_external,
+        last_unused_internal,
+        height_blockhash,
+        statuses,
+    })
+}
+
+/// Add the transaction of a script history to the transactions found by a scan
+fn add_history(
+    el: History,
+    txid_height: &mut HashMap<Txid, Option<Height>>,
+    height_blockhash: &mut HashMap<Height, BlockHash>,
+) {
+    // el.height = -1 means unconfirmed with unconfirmed parents
+    // el.height =  0 means unconfirmed with confirmed parents
+    // but we threat those tx the same
+    let height = el.height.max(0);
+    if height == 0 {
+        txid_height.insert(el.txid, None);
+    } else {
+        txid_height.insert(el.txid, Some(height as u32));
+        if let Some(block_hash) = el.block_hash {
+            height_blockhash.insert(height as u32, block_hash);
+        }
+    }
+}
+
+/// The status of a script with `history`, as defined by the electrum protocol
+///
+/// It's the sha256 of the concatenation of `txid:height:` for every transaction of the history,
+/// `None` if the history is empty.
+pub fn script_status(history: &[History]) -> Option<[u8; 32]> {
+    if history.is_empty() {
+        return None;
+    }
+    let mut engine = sha256::Hash::engine();
+    for el in history {
+        engine.input(format!("{}:{}:", el.txid, el.height).as_bytes());
+    }
+    Some(sha256::Hash::from_engine(engine).to_byte_array())
+}
+
+/// The changes of the scripts status found by a full scan with respect to `store`
+fn scripts_status_changes(
+    statuses: &HashMap<Script, [u8; 32]>,
+    store: &Store,
+) -> Vec<(Script, Option<[u8; 32]>)> {
+    let mut changes: Vec<_> = statuses
+        .iter()
+        .filter(|(script, status)| store.cache.scripts_status.get(*script) != Some(*status))
+        .map(|(script, status)| (script.clone(), Some(*status)))
+        .collect();
+    changes.extend(
+        store
+            .cache
+            .scripts_status
+            .keys()
+            .filter(|script| !statuses.contains_key(*script))
+            .map(|script| (script.clone(), None)),
+    );
+    changes
+}
+
+/// The result of querying the history of the wallet scripts changed since the previous scan
+struct IncrementalHistory {
+    /// The transactions of the changed scripts and of the new scripts
+    txid_height: HashMap<Txid, Option<Height>>,
+
+    /// The transactions involving only changed scripts and no longer in their history
+    txid_height_delete: Vec<Txid>,
+
+    /// The scripts derived beyond the ones already in the store
+    scripts: HashMap<Script, (Chain, ChildNumber)>,
+
+    scripts_status: Vec<(Script, Option<[u8; 32]>)>,
+    height_blockhash: HashMap<Height, BlockHash>,
+}
+
+/// Get the history of the wallet scripts whose status changed since the previous scan, deriving
+/// new scripts if the last batch of a chain is now used
+fn incremental_history<B: BlockchainBackend + ?Sized>(
+    client: &mut B,
+    wollet: &Wollet,
+) -> Result<IncrementalHistory, Error> {
+    let store = &wollet.store;
+    let known: Vec<&Script> = store.cache.paths.keys().collect();
+    let statuses = client.get_scripts_status(&known)?;
+    let changed: HashMap<&Script, Option<[u8; 32]>> = known
+        .into_iter()
+        .zip(statuses)
+        .filter(|(script, status)| store.cache.scripts_status.get(*script) != status.as_ref())
+        .collect();
+
+    let mut txid_height = HashMap::new();
+    let mut height_blockhash = HashMap::new();
+    let changed_scripts: Vec<&Script> = changed.keys().copied().collect();
+    if !changed_scripts.is_empty() {
+        for el in client
+            .get_scripts_history(&changed_scripts)?
+            .into_iter()
+            .flatten()
+        {
+            add_history(el, &mut txid_height, &mut height_blockhash);
+        }
+    }
+    let mut scripts_status: Vec<_> = changed
+        .iter()
+        .map(|(script, status)| ((*script).clone(), *status))
+        .collect();
+
+    let used = |script: &Script| match changed.get(script) {
+        Some(status) => status.is_some(),
+        None => store.cache.scripts_status.contains_key(script),
+    };
+    // Scripts are derived by whole batches, up to the first one without history
+    let gap_limit = wollet.gap_limit();
+    let first_batch = |chain: Chain| {
+        let batches = store
+            .cache
+            .paths
+            .values()
+            .filter(|(c, _)| *c == chain)
+            .count() as u32
+            / gap_limit;
+        if batches == 0 {
+            return Some(0);
+        }
+        let last_batch_used = ((batches - 1) * gap_limit..batches * gap_limit).any(|j| {
+            ChildNumber::from_normal_idx(j)
+                .ok()
+                .and_then(|child| store.cache.scripts.get(&(chain, child)))
+                .map_or(false, used)
+        });
+        last_batch_used.then_some(batches)
+    };
+    let new = scan_history_from(&*client, wollet, first_batch, &CancellationToken::new())?;
+    txid_height.extend(new.txid_height);
+    height_blockhash.extend(new.height_blockhash);
+    scripts_status.extend(
+        new.statuses
+            .into_iter()
+            .map(|(script, status)| (script, Some(status))),
+    );
+
+    // Transactions involving unchanged scripts are still in their history
+    let txid_height_delete = store
+        .cache
+        .heights
+        .keys()
+        .filter(|txid| !txid_height.contains_key(*txid))
+        .filter(|txid| {
+            let Some(tx) = store.cache.all_txs.get(*txid) else {
+                return false;
+            };
+            let mut mine = tx_scripts(tx, store)
+                .filter(|script| store.cache.paths.contains_key(*script))
+                .peekable();
+            mine.peek().is_some() && mine.all(|script| changed.contains_key(script))
+        })
+        .cloned()
+        .collect();
+
+    Ok(IncrementalHistory {
+        txid_height,
+        txid_height_delete,
+        scripts: new.scripts,
+        scripts_status,
+        height_blockhash,
+    })
+}
+
+/// The scripts of the outputs of `tx` and of the outputs it spends, if known
+fn tx_scripts<'a>(tx: &'a Transaction, store: &'a Store) -> impl Iterator<Item = &'a Script> {
+    let spent = tx.input.iter().filter_map(|input| {
+        let prevout = input.previous_output;
+        let prev_tx = store.cache.all_txs.get(&prevout.txid)?;
+        prev_tx
+            .output
+            .get(prevout.vout as usize)
+            .map(|o| &o.script_pubkey)
+    });
+    tx.output.iter().map(|o| &o.script_pubkey).chain(spent)
+}
+
+/// The registry info of the assets received with `new_txs` or in the wallet balance the wallet
+/// has no info of
+///
+/// Assets missing in the registry or whose info cannot be fetched are skipped, so that registry
+/// errors do not fail the scan, they are fetched again in the next scans while in the balance.
+#[cfg(feature = "esplora")]
+fn new_assets_info<B: BlockchainBackend + ?Sized>(
+    client: &B,
+    wollet: &Wollet,
+    new_txs: &DownloadTxResult,
+) -> Vec<(AssetId, crate::AssetInfo)> {
+    let Some(registry_url) = client.registry_url() else {
+        return vec![];
+    };
+    let policy_asset = wollet.policy_asset();
+    let balance = wollet.balance().unwrap_or_default();
+    let assets: std::collections::BTreeSet<AssetId> = new_txs
+        .unblinds
+        .iter()
+        .map(|(_, secrets)| secrets.asset)
+        .chain(balance.into_keys())
+        .filter(|asset| *asset != policy_asset && wollet.asset_info(asset).is_none())
+        .collect();
+    let mut result = vec![];
+    for asset in assets {
+        match crate::registry::fetch_asset_info(client, registry_url, &asset) {
+            Ok(Some(info)) => result.push((asset, info)),
+            Ok(None) => tracing::debug!("asset {asset} not in the registry"),
+            Err(e) => tracing::warn!("cannot fetch the registry info of {asset}: {e}"),
+        }
+    }
+    result
+}
+
+/// Check `genesis` is the genesis block hash of `network`, if known
+pub(crate) fn check_genesis(network: ElementsNetwork, genesis: BlockHash) -> Result<(), Error> {
+    match network.genesis_hash() {
+        Some(expected) if expected != genesis => Err(Error::NetworkMismatch {
+            network: network.as_str(),
+            genesis,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Whether the stored transaction `txid` has an output paying `script` or spends one
+fn tx_involves_script(store: &Store, txid: &Txid, script: &Script) -> bool {
+    let Some(tx) = store.cache.all_txs.get(txid) else {
+        return false;
+    };
+    tx.output.iter().any(|o| &o.script_pubkey == script)
+        || tx.input.iter().any(|i| {
+            store
+                .cache
+                .all_txs
+                .get(&i.previous_output.txid)
+                .and_then(|prev| prev.output.get(i.previous_output.vout as usize))
+                .map_or(false, |o| &o.script_pubkey == script)
+        })
+}
+
+fn check_cancelled(cancel: &CancellationToken) -> Result<(), Error> {
+    if cancel.is_cancelled() {
+        Err(Error::Cancelled)
+    } else {
+        Ok(())
+    }
+}
+
+/// A reference to a block, used in [`BlockchainBackend::block()`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockRef {
+    /// The block at this height in the best chain
+    Height(Height),
+
+    /// The block with this hash
+    Hash(BlockHash),
+}
+
+impl std::fmt::Display for BlockRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BlockRef::Height(height) => write!(f, "at height {height}"),
+            BlockRef::Hash(hash) => write!(f, "{hash}"),
+        }
+    }
+}
+
+impl From<Height> for BlockRef {
+    fn from(height: Height) -> Self {
+        BlockRef::Height(height)
+    }
+}
+
+impl From<BlockHash> for BlockRef {
+    fn from(hash: BlockHash) -> Self {
+        BlockRef::Hash(hash)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
 /// Position of a transaction involving a certain script
 pub struct History {
     /// Transaction ID
@@ -263,6 +1051,45 @@ pub struct History {
     pub block_timestamp: Option<Timestamp>,
 }
 
+/// Unblind the outputs of `txs` on the wallet scripts, adding the results to `result`
+///
+/// Rewinding the range proofs is expensive, thus outputs already unblinded or that failed to
+/// unblind are skipped. Failures are kept as well, so that they are not retried by the following
+/// scans.
+pub(crate) fn unblind_outputs<'a>(
+    txs: impl Iterator<Item = &'a Transaction>,
+    scripts: &HashMap<Script, (Chain, ChildNumber)>,
+    store: &Store,
+    descriptor: &WolletDescriptor,
+    result: &mut DownloadTxResult,
+) {
+    for tx in txs {
+        let txid = tx.txid();
+        for (i, output) in tx.output.iter().enumerate() {
+            // could be the searched script it's not yet in the store, because created in the current run, thus it's searched also in the `scripts`
+            if !store.cache.paths.contains_key(&output.script_pubkey)
+                && !scripts.contains_key(&output.script_pubkey)
+            {
+                continue;
+            }
+            let outpoint = OutPoint::new(txid, i as u32);
+            if store.cache.unblinded.contains_key(&outpoint)
+                || store.cache.unblind_failures.contains(&outpoint)
+            {
+                continue;
+            }
+
+            match try_unblind(output.clone(), descriptor) {
+                Ok(unblinded) => result.unblinds.push((outpoint, unblinded)),
+                Err(_) => {
+                    tracing::info!("{} cannot unblind, ignoring (could be sender messed up with the blinding process)", outpoint);
+                    result.unblind_failures.push(outpoint);
+                }
+            }
+        }
+    }
+}
+
 pub fn try_unblind(output: TxOut, descriptor: &WolletDescriptor) -> Result<TxOutSecrets, Error> {
     match (output.asset, output.value, output.nonce) {
         (Asset::Confidential(_), Value::Confidential(_), Nonce::Confidential(_)) => {
@@ -272,6 +1099,14 @@ pub fn try_unblind(output: TxOut, descriptor: &WolletDescriptor) -> Result<TxOut
 
             Ok(txout_secrets)
         }
+        // Explicit outputs, such as the ones of unblinded regtest transactions, have null
+        // blinding factors
+        (Asset::Explicit(asset), Value::Explicit(value), _) => Ok(TxOutSecrets::new(
+            asset,
+            AssetBlindingFactor::zero(),
+            value,
+            ValueBlindingFactor::zero(),
+        )),
         _ => Err(Error::Generic(
             "received unconfidential or null asset/value/nonce".into(),
         )),
@@ -283,10 +1118,396 @@ mod tests {
     use std::time::Instant;
 
     use crate::{
-        clients::esplora_client::EsploraClient, BlockchainBackend, ElectrumClient, ElectrumUrl,
-        ElementsNetwork,
+        clients::esplora_client::EsploraClient, BlockchainBackend, CancellationToken,
+        ElectrumClient, ElectrumUrl, ElementsNetwork, Error, MockBackend,
     };
 
+    #[test]
+    fn full_scan_cancelled() {
+        let desc = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let wollet = crate::Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        // a failing backend, the scan must stop before any request
+        let mut client = MockBackend {
+            fail: true,
+            ..Default::default()
+        };
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let err = client.full_scan_cancellable(&wollet, &cancel).unwrap_err();
+        assert!(matches!(err, Error::Cancelled));
+    }
+
+    #[test]
+    fn test_new_assets_info() {
+        use crate::update::DownloadTxResult;
+        use elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
+        use elements::hex::FromHex;
+        use lwk_test_util::HttpResponse;
+        use std::str::FromStr;
+
+        // https://blockstream.info/liquid/api/tx/abb4080d91849e933ee2ed65da6b436f7c385cf363fb4aa08399f1e27c58ff3d/hex
+        let tx_hex = include_str!("../../tests/data/usdt-issuance-tx.hex");
+        let tx_bytes = Vec::<u8>::from_hex(tx_hex.trim()).unwrap();
+        let txid = elements::encode::deserialize::<elements::Transaction>(&tx_bytes)
+            .unwrap()
+            .txid();
+        let contract = r#"{"entity":{"domain":"tether.to"},"issuer_pubkey":"0337cceec0beea0232ebe14cba0197a9fbd45fcf2ec946749de920e71434c2b904","name":"Tether USD","precision":8,"ticker":"USDt","version":0}"#;
+        let entry =
+            format!(r#"{{"contract":{contract},"issuance_txin":{{"txid":"{txid}","vin":0}}}}"#);
+        let usdt = elements::AssetId::from_str(
+            "ce091c998b83c78bb71a632313ba3760f1763d9cfcffae02258ffa9865a37bd2",
+        )
+        .unwrap();
+        // Claims the ticker with the contract of another asset
+        let spoofed = elements::AssetId::from_slice(&[1; 32]).unwrap();
+        let missing = elements::AssetId::from_slice(&[2; 32]).unwrap();
+
+        let url = lwk_test_util::http_server(move |request| {
+            if request.path == format!("/tx/{txid}/raw") {
+                HttpResponse::ok(tx_bytes.clone())
+            } else if request.path.ends_with(&missing.to_string()) {
+                HttpResponse::not_found()
+            } else {
+                HttpResponse::ok(entry.as_bytes())
+            }
+        });
+
+        let desc = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let mut wollet = crate::Wollet::without_persist(ElementsNetwork::Liquid, desc).unwrap();
+        let secrets = |asset| {
+            elements::TxOutSecrets::new(
+                asset,
+                AssetBlindingFactor::zero(),
+                1,
+                ValueBlindingFactor::zero(),
+            )
+        };
+        let new_txs = DownloadTxResult {
+            unblinds: [usdt, spoofed, missing, wollet.policy_asset()]
+                .into_iter()
+                .map(|asset| (elements::OutPoint::default(), secrets(asset)))
+                .collect(),
+            ..Default::default()
+        };
+
+        // Without a registry nothing is fetched
+        let client = EsploraClient::new(&url);
+        assert!(super::new_assets_info(&client, &wollet, &new_txs).is_empty());
+
+        let client = client.with_registry_url(&format!("{url}/registry/"));
+        let assets = super::new_assets_info(&client, &wollet, &new_txs);
+        // The spoofed info is rejected
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].0, usdt);
+        assert_eq!(assets[0].1.ticker, "USDt");
+        assert!(assets[0].1.verified);
+
+        // Assets in the balance without info are fetched again, even if not in new transactions
+        let update = crate::wollet::tests::receive_asset_update(&wollet, usdt, 1_000);
+        wollet.apply_update(update).unwrap();
+        let assets = super::new_assets_info(&client, &wollet, &DownloadTxResult::default());
+        assert_eq!(assets.len(), 1);
+        assert_eq!(assets[0].0, usdt);
+        assert!(assets[0].1.verified);
+    }
+
+    #[test]
+    fn test_scan_batches() {
+        use elements::hashes::Hash;
+
+        let desc = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let wollet = crate::Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let history = |i: u8| {
+            vec![super::History {
+                txid: elements::Txid::from_byte_array([i; 32]),
+                height: i as i32,
+                block_hash: None,
+                block_timestamp: None,
+            }]
+        };
+        let mut histories = std::collections::HashMap::new();
+        // External batches 0 to 3 are used, batch 4 is empty and batch 6 is beyond the gap limit
+        for (i, index) in [0, 25, 45, 70, 130].into_iter().enumerate() {
+            let script = wollet
+                .address(Some(index))
+                .unwrap()
+                .address()
+                .script_pubkey();
+            histories.insert(script, history(i as u8 + 1));
+        }
+        let script = wollet.change(Some(3)).unwrap().address().script_pubkey();
+        histories.insert(script, history(10));
+
+        let mut results = vec![];
+        for scan_batches in [1, 2, 3, 8] {
+            let client = MockBackend {
+                histories: histories.clone(),
+                scan_batches,
+                ..Default::default()
+            };
+            let scan = super::scan_history(&client, &wollet, &CancellationToken::new()).unwrap();
+            let requests = client.requests.into_inner();
+            results.push((scan_batches, requests, scan));
+        }
+
+        for (scan_batches, requests, scan) in results.iter() {
+            assert_eq!(scan.txid_height.len(), 5, "scan_batches {scan_batches}");
+            assert_eq!(scan.last_unused_external, 71);
+            assert_eq!(scan.last_unused_internal, 4);
+            // The scripts up to the first empty batch are returned
+            let external = scan
+                .scripts
+                .values()
+                .filter(|(chain, _)| *chain == crate::Chain::External)
+                .count();
+            assert_eq!(external, 5 * super::BATCH_SIZE as usize);
+            let expected_requests = match scan_batches {
+                1 => 5, // the 5 external batches, the 2 internal ones in the first 2 requests
+                2 => 3,
+                3 => 2,
+                _ => 1,
+            };
+            assert_eq!(*requests, expected_requests, "scan_batches {scan_batches}");
+        }
+    }
+
+    #[test]
+    fn test_script_scan() {
+        let desc = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let network = ElementsNetwork::default_regtest();
+        let mut wollet = crate::Wollet::without_persist(network, desc).unwrap();
+        let update = crate::wollet::tests::receive_update(&wollet, 10_000);
+        let txid = update.new_txs.txs[0].0;
+        wollet.apply_update(update).unwrap();
+        let script = wollet.address(Some(0)).unwrap().address().script_pubkey();
+        let tip = lwk_test_util::liquid_block_1().header;
+        // The server is ahead of the wallet
+        let mut server_tip = tip.clone();
+        server_tip.height = 10;
+        let history = super::History {
+            txid,
+            height: 1,
+            block_hash: None,
+            block_timestamp: None,
+        };
+
+        let mut client = MockBackend {
+            tip: Some(server_tip.clone()),
+            histories: [(script.clone(), vec![history])].into(),
+            ..Default::default()
+        };
+        assert!(client.script_scan(&wollet, &script).unwrap().is_none());
+
+        // The transaction is no longer in the script history, the wallet tip is kept
+        let mut client = MockBackend {
+            tip: Some(server_tip),
+            ..Default::default()
+        };
+        let update = client.script_scan(&wollet, &script).unwrap().unwrap();
+        assert_eq!(update.txid_height_delete, vec![txid]);
+        assert_eq!(update.tip, tip);
+        wollet.apply_update(update).unwrap();
+        assert!(wollet.transactions().unwrap().is_empty());
+        assert_eq!(wollet.tip().height(), 1);
+    }
+
+    #[test]
+    fn test_full_scan_chunked_tip() {
+        let desc = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let network = ElementsNetwork::default_regtest();
+        let mut wollet = crate::Wollet::without_persist(network, desc).unwrap();
+        let script = wollet.address(Some(0)).unwrap().address().script_pubkey();
+        let mut tx = lwk_test_util::liquid_block_1().txdata.pop().unwrap();
+        tx.output[0].script_pubkey = script.clone();
+        let history = super::History {
+            txid: tx.txid(),
+            height: 5,
+            block_hash: None,
+            block_timestamp: None,
+        };
+        let mut tip = lwk_test_util::liquid_block_1().header;
+        tip.height = 10;
+        let mut genesis = tip.clone();
+        genesis.height = 0;
+
+        // The header of the block confirming the transaction is missing, the scan fails after
+        // applying the transaction
+        let mut client = MockBackend {
+            tip: Some(tip),
+            txs: vec![tx.clone()],
+            headers: vec![genesis],
+            histories: [(script, vec![history])].into(),
+            ..Default::default()
+        };
+        assert!(client.full_scan_chunked(&mut wollet, 1).is_err());
+        assert!(wollet.store.cache.all_txs.contains_key(&tx.txid()));
+        assert_eq!(wollet.tip().height(), 0);
+    }
+
+    #[test]
+    fn test_gap_limit() {
+        use elements::hashes::Hash;
+
+        let desc = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let mut wollet =
+            crate::Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let mut histories = std::collections::HashMap::new();
+        // More than 20 unused addresses between the first and the second payment
+        for (i, index) in [0, 45].into_iter().enumerate() {
+            let address = wollet.address(Some(index)).unwrap();
+            let history = vec![super::History {
+                txid: elements::Txid::from_byte_array([i as u8; 32]),
+                height: 1,
+                block_hash: None,
+                block_timestamp: None,
+            }];
+            histories.insert(address.address().script_pubkey(), history);
+        }
+        let client = MockBackend {
+            histories,
+            ..Default::default()
+        };
+
+        let scan = super::scan_history(&client, &wollet, &CancellationToken::new()).unwrap();
+        assert_eq!(scan.txid_height.len(), 1);
+        assert_eq!(scan.last_unused_external, 1);
+
+        for gap_limit in [0, crate::store::MAX_GAP_LIMIT + 1, u32::MAX] {
+            assert!(matches!(
+                wollet.set_gap_limit(gap_limit),
+                Err(Error::InvalidGapLimit)
+            ));
+        }
+        wollet.set_gap_limit(crate::store::MAX_GAP_LIMIT).unwrap();
+        wollet.set_gap_limit(30).unwrap();
+        let scan = super::scan_history(&client, &wollet, &CancellationToken::new()).unwrap();
+        assert_eq!(scan.txid_height.len(), 2);
+        assert_eq!(scan.last_unused_external, 46);
+    }
+
+    #[test]
+    fn test_incremental_history() {
+        use elements::hashes::Hash;
+
+        let desc = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let mut wollet =
+            crate::Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let scripts: Vec<_> = (0..80)
+            .map(|i| wollet.address(Some(i)).unwrap().address().script_pubkey())
+            .collect();
+        let script = |index: usize| scripts[index].clone();
+        let history = |txid: elements::Txid| {
+            vec![super::History {
+                txid,
+                height: 1,
+                block_hash: None,
+                block_timestamp: None,
+            }]
+        };
+        let txid = |i: u8| elements::Txid::from_byte_array([i; 32]);
+        let mock = |histories| MockBackend {
+            histories,
+            ..Default::default()
+        };
+
+        // A transaction paying the first address, replaced later
+        let mut tx = lwk_test_util::liquid_block_1().txdata.pop().unwrap();
+        tx.output[0].script_pubkey = script(0);
+        let replaced = tx.txid();
+
+        // External batches 0 and 1 are used
+        let mut histories = std::collections::HashMap::new();
+        histories.insert(script(0), history(replaced));
+        histories.insert(script(25), history(txid(1)));
+        let client = mock(histories.clone());
+        let scan = super::scan_history(&client, &wollet, &CancellationToken::new()).unwrap();
+        wollet
+            .apply_update(crate::Update {
+                new_txs: crate::DownloadTxResult {
+                    txs: vec![(replaced, tx)],
+                    unblinds: vec![],
+                    unblind_failures: vec![],
+                },
+                txid_height_new: scan.txid_height.into_iter().collect(),
+                txid_height_delete: vec![],
+                timestamps: vec![],
+                scripts: scan.scripts,
+                tip: lwk_test_util::liquid_block_1().header,
+                utxo_locks: vec![],
+                utxo_unlocks: vec![],
+                labels: vec![],
+                assets: vec![],
+                scripts_status: super::scripts_status_changes(&scan.statuses, &wollet.store),
+            })
+            .unwrap();
+        // 3 external batches, up to the first empty one, and the empty internal batch
+        let known = 4 * super::BATCH_SIZE as usize;
+        assert_eq!(wollet.store.cache.paths.len(), known);
+
+        // Nothing changed, only the status is requested
+        let mut client = mock(histories.clone());
+        let result = super::incremental_history(&mut client, &wollet).unwrap();
+        assert!(result.txid_height.is_empty());
+        assert!(result.txid_height_delete.is_empty());
+        assert!(result.scripts.is_empty());
+        assert!(result.scripts_status.is_empty());
+        assert_eq!(client.requests.into_inner(), 1);
+
+        // The first transaction is replaced, the last batch derived is now used and the next one
+        // as well
+        histories.insert(script(0), history(txid(2)));
+        histories.insert(script(55), history(txid(3)));
+        histories.insert(script(70), history(txid(4)));
+        let mut client = mock(histories);
+        let result = super::incremental_history(&mut client, &wollet).unwrap();
+        let mut txids: Vec<_> = result.txid_height.keys().cloned().collect();
+        txids.sort();
+        assert_eq!(txids, vec![txid(2), txid(3), txid(4)]);
+        assert_eq!(result.txid_height_delete, vec![replaced]);
+        // External batches 3 and 4, the latter empty
+        assert_eq!(result.scripts.len(), 2 * super::BATCH_SIZE as usize);
+        assert_eq!(result.scripts_status.len(), 3);
+        // The status of the known scripts, the history of the changed ones and the new batches
+        assert_eq!(client.requests.into_inner(), 4);
+        assert_eq!(
+            client.scripts.into_inner(),
+            known + 2 + 2 * super::BATCH_SIZE as usize
+        );
+    }
+
+    #[test]
+    fn test_script_status() {
+        use elements::hashes::Hash;
+
+        assert_eq!(super::script_status(&[]), None);
+        let history = super::History {
+            txid: elements::Txid::all_zeros(),
+            height: 0,
+            block_hash: None,
+            block_timestamp: None,
+        };
+        let status = super::script_status(&[history]).unwrap();
+        assert_eq!(
+            status,
+            elements::hashes::sha256::Hash::hash(format!("{}:0:", "0".repeat(64)).as_bytes())
+                .to_byte_array()
+        );
+    }
+
+    #[test]
+    fn check_genesis() {
+        let liquid = ElementsNetwork::Liquid;
+        let testnet = ElementsNetwork::LiquidTestnet;
+        let testnet_genesis = testnet.genesis_hash().unwrap();
+        super::check_genesis(testnet, testnet_genesis).unwrap();
+        super::check_genesis(ElementsNetwork::default_regtest(), testnet_genesis).unwrap();
+        let err = super::check_genesis(liquid, testnet_genesis).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("The server is not on liquid, its genesis block is {testnet_genesis}")
+        );
+    }
+
     #[test]
     #[ignore = "test with prod servers"]
     fn esplora_electrum_compare() {
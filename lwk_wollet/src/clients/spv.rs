@@ -0,0 +1,471 @@
+//! Simplified payment verification of the data returned by untrusted servers
+//!
+//! Liquid blocks are signed by the federation instead of being mined, thus a header is valid if
+//! its signature satisfies the script of the federation, which must be known in advance.
+//! With dynamic federations the script is part of the parameters committed in every header,
+//! and the federation proposes the script of the next one in the headers preceding a transition.
+//!
+//! A transaction is confirmed in a block if its merkle proof links its id to the merkle root of
+//! the verified header.
+
+use std::collections::BTreeSet;
+
+use crate::elements::hashes::{sha256, sha256d, Hash};
+use crate::elements::opcodes::{self, Class, ClassifyContext};
+use crate::elements::script::Instruction;
+use crate::elements::{BlockExtData, BlockHash, BlockHeader, Script, Txid};
+use crate::secp256k1::ecdsa::Signature;
+use crate::secp256k1::{Message, PublicKey};
+use crate::store::Height;
+use crate::util::EC;
+use crate::Error;
+
+/// The block weight limit allows far fewer than 2^20 transactions in a block
+const MAX_MERKLE_DEPTH: usize = 20;
+
+/// The federations trusted to sign the headers
+///
+/// Starting from the script given by the user, the script of a new federation is trusted if it's
+/// proposed in a verified header, signed by a trusted federation.
+pub(crate) struct Federations {
+    scripts: Vec<Script>,
+
+    /// Heights of the headers verified so far
+    verified: BTreeSet<Height>,
+}
+
+impl Federations {
+    pub(crate) fn new(signblockscript: Script) -> Self {
+        Self {
+            scripts: vec![signblockscript],
+            verified: BTreeSet::new(),
+        }
+    }
+
+    /// Whether `header` is signed by a trusted federation, without verifying the signature
+    pub(crate) fn is_trusted(&self, header: &BlockHeader) -> bool {
+        signblockscript(header).map_or(false, |s| self.scripts.contains(s))
+    }
+
+    /// Verify `header` is signed by a trusted federation
+    pub(crate) fn verify(&mut self, header: &BlockHeader) -> Result<(), Error> {
+        let script = signblockscript(header)
+            .filter(|s| self.scripts.contains(s))
+            .ok_or_else(|| {
+                Error::InvalidSpvProof(format!(
+                    "header {}: signed by an unknown federation",
+                    header.height
+                ))
+            })?;
+        verify_header(header, script)?;
+        self.verified.insert(header.height);
+        Ok(())
+    }
+
+    /// The height of the last header verified below `height`
+    pub(crate) fn verified_below(&self, height: Height) -> Option<Height> {
+        self.verified.range(..height).next_back().copied()
+    }
+
+    /// The height of the first header verified above `height`
+    pub(crate) fn verified_above(&self, height: Height) -> Option<Height> {
+        self.verified
+            .range(height.saturating_add(1)..)
+            .next()
+            .copied()
+    }
+
+    /// Trust the federation signing `parent` if it's committed by the verified `header`
+    ///
+    /// Returns whether the federation is now trusted.
+    pub(crate) fn follow_parent(&mut self, header: &BlockHeader, parent: &BlockHeader) -> bool {
+        if !self.verified.contains(&header.height)
+            || header.prev_blockhash != parent.block_hash()
+            || parent.height + 1 != header.height
+        {
+            return false;
+        }
+        match signblockscript(parent) {
+            Some(script) => {
+                if !self.scripts.contains(script) {
+                    self.scripts.push(script.clone());
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Trust `next` if it's the script proposed by the verified `header`
+    ///
+    /// Returns whether the federation is now trusted.
+    pub(crate) fn follow_proposal(&mut self, header: &BlockHeader, next: &Script) -> bool {
+        let proposed = match &header.ext {
+            BlockExtData::Dynafed { proposed, .. } => proposed.signblockscript(),
+            BlockExtData::Proof { .. } => None,
+        };
+        if !self.verified.contains(&header.height) || proposed != Some(next) {
+            return false;
+        }
+        if !self.scripts.contains(next) {
+            self.scripts.push(next.clone());
+        }
+        true
+    }
+}
+
+/// The script of the federation signing `header`
+pub(crate) fn signblockscript(header: &BlockHeader) -> Option<&Script> {
+    match &header.ext {
+        BlockExtData::Proof { challenge, .. } => Some(challenge),
+        BlockExtData::Dynafed { current, .. } => current.signblockscript(),
+    }
+}
+
+/// Verify `header` is signed according to `signblockscript`
+pub(crate) fn verify_header(header: &BlockHeader, signblockscript: &Script) -> Result<(), Error> {
+    let invalid = |msg: &str| Error::InvalidSpvProof(format!("header {}: {msg}", header.height));
+    let hash = header.block_hash();
+    match &header.ext {
+        BlockExtData::Proof {
+            challenge,
+            solution,
+        } => {
+            if challenge != signblockscript {
+                return Err(invalid("unexpected challenge"));
+            }
+            let stack = solution
+                .instructions()
+                .map(|i| match i {
+                    Ok(Instruction::PushBytes(data)) => Ok(data.to_vec()),
+                    _ => Err(invalid("solution must contain only pushes")),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            verify_script(signblockscript, &stack, &hash).map_err(|e| invalid(&e))
+        }
+        BlockExtData::Dynafed {
+            current,
+            signblock_witness,
+            ..
+        } => {
+            if current.signblockscript() != Some(signblockscript) {
+                return Err(invalid("unexpected dynafed signblockscript"));
+            }
+            let witness_size = crate::elements::encode::serialize(signblock_witness).len();
+            if let Some(limit) = current.signblock_witness_limit() {
+                if witness_size > limit as usize {
+                    return Err(invalid("signblock witness exceeds the limit"));
+                }
+            }
+            verify_script(signblockscript, signblock_witness, &hash).map_err(|e| invalid(&e))
+        }
+    }
+}
+
+/// Verify `txid` is included in the block with `header` according to the electrum merkle proof
+///
+/// The `merkle` branch hashes are in the byte order returned by the server.
+pub(crate) fn verify_merkle_proof(
+    txid: &Txid,
+    header: &BlockHeader,
+    pos: usize,
+    merkle: &[[u8; 32]],
+) -> Result<(), Error> {
+    let invalid = |msg: &str| {
+        Error::InvalidSpvProof(format!(
+            "transaction {txid} in block {}: {msg}",
+            header.height
+        ))
+    };
+    if merkle.len() > MAX_MERKLE_DEPTH {
+        return Err(invalid("merkle branch too long"));
+    }
+    let mut index = pos;
+    let mut current = txid.to_raw_hash().to_byte_array();
+    for step in merkle {
+        let mut step = *step;
+        step.reverse();
+        let (left, right) = if index % 2 == 0 {
+            (current, step)
+        } else {
+            (step, current)
+        };
+        current = sha256d::Hash::hash(&[left, right].concat()).to_byte_array();
+        index /= 2;
+    }
+    // Otherwise the same proof would be valid for several positions
+    if index != 0 {
+        return Err(invalid("position beyond the merkle branch"));
+    }
+    if current != header.merkle_root.to_byte_array() {
+        return Err(invalid("not in block"));
+    }
+    Ok(())
+}
+
+/// Check the `stack` satisfies `script`, signing `hash`
+///
+/// Only the scripts used by federations are supported: `OP_TRUE` and a multisig, bare or
+/// wrapped in P2WSH.
+fn verify_script(script: &Script, stack: &[Vec<u8>], hash: &BlockHash) -> Result<(), String> {
+    if script.as_bytes() == [opcodes::all::OP_PUSHNUM_1.into_u8()] {
+        return Ok(());
+    }
+    if script.is_v0_p2wsh() {
+        let (witness_script, stack) = stack.split_last().ok_or("empty witness")?;
+        if sha256::Hash::hash(witness_script).as_byte_array()[..] != script.as_bytes()[2..] {
+            return Err("witness script does not match".to_string());
+        }
+        return verify_multisig(&Script::from(witness_script.clone()), stack, hash);
+    }
+    verify_multisig(script, stack, hash)
+}
+
+/// Check the `stack` satisfies the multisig `script` like `OP_CHECKMULTISIG`
+fn verify_multisig(script: &Script, stack: &[Vec<u8>], hash: &BlockHash) -> Result<(), String> {
+    let (threshold, pubkeys) = parse_multisig(script).ok_or("unsupported signblockscript")?;
+    // The first element is the dummy consumed by `OP_CHECKMULTISIG`
+    let signatures = match stack.split_first() {
+        Some((dummy, signatures)) if dummy.is_empty() => signatures,
+        _ => return Err("missing multisig dummy element".to_string()),
+    };
+    if signatures.len() != threshold {
+        return Err(format!(
+            "expected {threshold} signatures, got {}",
+            signatures.len()
+        ));
+    }
+    let message = Message::from_digest(hash.to_byte_array());
+    // Signatures must be in the same order of the public keys
+    let mut pubkeys = pubkeys.iter();
+    for signature in signatures {
+        let mut signature = Signature::from_der(signature).map_err(|e| e.to_string())?;
+        signature.normalize_s();
+        if !pubkeys.any(|pk| EC.verify_ecdsa(&message, &signature, pk).is_ok()) {
+            return Err("invalid signature".to_string());
+        }
+    }
+    Ok(())
+}
+
+/// Parse a `<threshold> <pubkeys>... <n> OP_CHECKMULTISIG` script
+fn parse_multisig(script: &Script) -> Option<(usize, Vec<PublicKey>)> {
+    let push_num = |i: &Instruction| match i {
+        Instruction::Op(op) => match op.classify(ClassifyContext::Legacy) {
+            Class::PushNum(n) if n > 0 => Some(n as usize),
+            _ => None,
+        },
+        _ => None,
+    };
+    let instructions = script.instructions().collect::<Result<Vec<_>, _>>().ok()?;
+    let (first, rest) = instructions.split_first()?;
+    let (last, rest) = rest.split_last()?;
+    let (n, keys) = rest.split_last()?;
+    if *last != Instruction::Op(opcodes::all::OP_CHECKMULTISIG) {
+        return None;
+    }
+    let threshold = push_num(first)?;
+    let pubkeys = keys
+        .iter()
+        .map(|i| match i {
+            Instruction::PushBytes(data) => PublicKey::from_slice(data).ok(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    if push_num(n)? != pubkeys.len() || threshold > pubkeys.len() {
+        return None;
+    }
+    Some((threshold, pubkeys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elements::dynafed;
+    use crate::elements::script::Builder;
+    use crate::elements::TxMerkleNode;
+    use crate::secp256k1::SecretKey;
+
+    fn header(ext: BlockExtData) -> BlockHeader {
+        BlockHeader {
+            version: 0x2000_0000,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 0,
+            height: 10,
+            ext,
+        }
+    }
+
+    #[test]
+    fn test_verify_header() {
+        let keys: Vec<SecretKey> = (1..=3u8)
+            .map(|i| SecretKey::from_slice(&[i; 32]).unwrap())
+            .collect();
+        let mut builder = Builder::new().push_int(2);
+        for key in &keys {
+            builder = builder.push_slice(&key.public_key(&EC).serialize());
+        }
+        let witness_script = builder
+            .push_int(3)
+            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+            .into_script();
+        let signblockscript = witness_script.to_v0_p2wsh();
+
+        let params = dynafed::Params::Compact {
+            signblockscript: signblockscript.clone(),
+            signblock_witness_limit: 1000,
+            elided_root: sha256::Midstate::from_byte_array([0; 32]),
+        };
+        let mut header = header(BlockExtData::Dynafed {
+            current: params,
+            proposed: dynafed::Params::Null,
+            signblock_witness: vec![],
+        });
+        let message = Message::from_digest(header.block_hash().to_byte_array());
+        let sign = |key: &SecretKey| EC.sign_ecdsa(&message, key).serialize_der().to_vec();
+        let witness = |signatures: Vec<Vec<u8>>| {
+            let mut witness = vec![vec![]];
+            witness.extend(signatures);
+            witness.push(witness_script.to_bytes());
+            witness
+        };
+        let set_witness = |header: &mut BlockHeader, w: Vec<Vec<u8>>| {
+            if let BlockExtData::Dynafed {
+                signblock_witness, ..
+            } = &mut header.ext
+            {
+                *signblock_witness = w;
+            }
+        };
+
+        set_witness(&mut header, witness(vec![sign(&keys[0]), sign(&keys[2])]));
+        verify_header(&header, &signblockscript).unwrap();
+
+        // Signatures out of order
+        set_witness(&mut header, witness(vec![sign(&keys[2]), sign(&keys[0])]));
+        verify_header(&header, &signblockscript).unwrap_err();
+
+        // Not enough signatures
+        set_witness(&mut header, witness(vec![sign(&keys[1])]));
+        verify_header(&header, &signblockscript).unwrap_err();
+
+        // Different federation
+        set_witness(&mut header, witness(vec![sign(&keys[0]), sign(&keys[1])]));
+        verify_header(&header, &Script::from(vec![0x51])).unwrap_err();
+
+        // Changing the header invalidates the signatures
+        header.time = 1;
+        let err = verify_header(&header, &signblockscript).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid SPV proof: header 10: invalid signature"
+        );
+
+        // Regtest blocks without signatures
+        let op_true = Script::from(vec![0x51]);
+        let header = super::tests::header(BlockExtData::Proof {
+            challenge: op_true.clone(),
+            solution: Script::new(),
+        });
+        verify_header(&header, &op_true).unwrap();
+    }
+
+    #[test]
+    fn test_verify_merkle_proof() {
+        let txids: Vec<Txid> = (0..3u8).map(|i| Txid::hash(&[i])).collect();
+        let node = |a: [u8; 32], b: [u8; 32]| sha256d::Hash::hash(&[a, b].concat()).to_byte_array();
+        let leaf = |i: usize| txids[i].to_raw_hash().to_byte_array();
+        let reversed = |mut h: [u8; 32]| {
+            h.reverse();
+            h
+        };
+        // The last leaf is duplicated when odd
+        let left = node(leaf(0), leaf(1));
+        let right = node(leaf(2), leaf(2));
+        let mut header = header(BlockExtData::Proof {
+            challenge: Script::new(),
+            solution: Script::new(),
+        });
+        header.merkle_root = TxMerkleNode::from_byte_array(node(left, right));
+
+        let branch = [reversed(leaf(2)), reversed(left)];
+        verify_merkle_proof(&txids[2], &header, 2, &branch).unwrap();
+        verify_merkle_proof(&txids[2], &header, 1, &branch).unwrap_err();
+        verify_merkle_proof(&txids[1], &header, 2, &branch).unwrap_err();
+
+        let branch = [reversed(leaf(0)), reversed(right)];
+        verify_merkle_proof(&txids[1], &header, 1, &branch).unwrap();
+
+        // Positions beyond the branch would alias the same leaf
+        let err = verify_merkle_proof(&txids[1], &header, 1 + 4, &branch).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("position beyond the merkle branch"));
+
+        let branch = vec![reversed(leaf(0)); MAX_MERKLE_DEPTH + 1];
+        let err = verify_merkle_proof(&txids[1], &header, 1, &branch).unwrap_err();
+        assert!(err.to_string().contains("merkle branch too long"));
+    }
+
+    #[test]
+    fn test_federations() {
+        let key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let op_true = Script::from(vec![0x51]);
+        let multisig = Builder::new()
+            .push_int(1)
+            .push_slice(&key.public_key(&EC).serialize())
+            .push_int(1)
+            .push_opcode(opcodes::all::OP_CHECKMULTISIG)
+            .into_script();
+        let params = |signblockscript: &Script| dynafed::Params::Compact {
+            signblockscript: signblockscript.clone(),
+            signblock_witness_limit: 1000,
+            elided_root: sha256::Midstate::from_byte_array([0; 32]),
+        };
+        let mut before = header(BlockExtData::Dynafed {
+            current: params(&op_true),
+            proposed: params(&multisig),
+            signblock_witness: vec![],
+        });
+        before.height = 9;
+        let mut after = header(BlockExtData::Dynafed {
+            current: params(&multisig),
+            proposed: dynafed::Params::Null,
+            signblock_witness: vec![],
+        });
+        after.prev_blockhash = before.block_hash();
+        let message = Message::from_digest(after.block_hash().to_byte_array());
+        let signature = EC.sign_ecdsa(&message, &key).serialize_der().to_vec();
+        if let BlockExtData::Dynafed {
+            signblock_witness, ..
+        } = &mut after.ext
+        {
+            *signblock_witness = vec![vec![], signature];
+        }
+
+        // Following the proposal of the previous federation
+        let mut federations = Federations::new(op_true.clone());
+        assert!(!federations.is_trusted(&after));
+        let err = federations.verify(&after).unwrap_err();
+        assert!(err.to_string().contains("signed by an unknown federation"));
+        assert!(!federations.follow_proposal(&before, &multisig)); // not verified
+        federations.verify(&before).unwrap();
+        assert_eq!(federations.verified_below(10), Some(9));
+        assert!(!federations.follow_proposal(&before, &Script::new()));
+        assert!(federations.follow_proposal(&before, &multisig));
+        federations.verify(&after).unwrap();
+
+        // Following the commitment of the previous header
+        let mut federations = Federations::new(multisig);
+        assert!(!federations.follow_parent(&after, &before)); // not verified
+        federations.verify(&after).unwrap();
+        assert_eq!(federations.verified_above(9), Some(10));
+        let mut other = before.clone();
+        other.time = 1;
+        assert!(!federations.follow_parent(&after, &other));
+        assert!(!federations.is_trusted(&before));
+        assert!(federations.follow_parent(&after, &before));
+        federations.verify(&before).unwrap();
+    }
+}
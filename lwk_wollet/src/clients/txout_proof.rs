@@ -0,0 +1,238 @@
+//! Proofs of inclusion of a transaction in a block in the format of elements core
+//!
+//! The `gettxoutproof` RPC returns a serialized `CMerkleBlock`: the block header followed by a
+//! partial merkle tree containing the transaction, which is what `importprunedfunds` expects.
+//!
+//! Servers return only the merkle branch of the transaction, thus the number of transactions in
+//! the block is unknown. The smallest number consistent with the branch is used instead, since
+//! the partial merkle tree depends on it only for the nodes which are not in the branch.
+
+use crate::bitcoin::VarInt;
+use crate::elements::encode::serialize;
+use crate::elements::hashes::{sha256d, Hash};
+use crate::elements::{BlockHeader, Txid};
+use crate::Error;
+
+/// Serialize the proof that `txid` is included in the block with `header`, from its position
+/// `pos` in the block and the `merkle` branch, in the byte order returned by electrum servers
+pub(crate) fn txout_proof(
+    header: &BlockHeader,
+    txid: &Txid,
+    pos: usize,
+    merkle: &[[u8; 32]],
+) -> Result<Vec<u8>, Error> {
+    let invalid = || Error::InvalidSpvProof(format!("invalid merkle proof for {txid}"));
+
+    // The hashes of the path from the transaction to the root and of their siblings
+    let mut path = vec![txid.to_raw_hash().to_byte_array()];
+    let mut siblings = vec![];
+    for (height, step) in merkle.iter().enumerate() {
+        let mut sibling = *step;
+        sibling.reverse();
+        let current = path[height];
+        let (left, right) = if (pos >> height) % 2 == 0 {
+            (current, sibling)
+        } else {
+            (sibling, current)
+        };
+        path.push(sha256d::Hash::hash(&[left, right].concat()).to_byte_array());
+        siblings.push(sibling);
+    }
+    if path[merkle.len()] != header.merkle_root.to_byte_array() {
+        return Err(invalid());
+    }
+
+    // A right sibling equal to the node is the duplicate of a missing node, so the node is the
+    // last of its level, otherwise the right sibling exists.
+    let tree_height = merkle.len();
+    let mut n_tx = (pos + 1).max(if tree_height > 0 {
+        (1 << (tree_height - 1)) + 1
+    } else {
+        1
+    });
+    let mut max_n_tx = 1 << tree_height;
+    for height in 0..tree_height {
+        let index = pos >> height;
+        if index % 2 == 0 {
+            if siblings[height] == path[height] {
+                max_n_tx = max_n_tx.min((index + 1) << height);
+            } else {
+                n_tx = n_tx.max(((index + 1) << height) + 1);
+            }
+        }
+    }
+    if n_tx > max_n_tx {
+        return Err(invalid());
+    }
+
+    let width = |height: usize| (n_tx + (1 << height) - 1) >> height;
+    let mut bits = vec![];
+    let mut hashes = vec![];
+    let mut stack = vec![(tree_height, 0usize)];
+    while let Some((height, index)) = stack.pop() {
+        let on_path = index == pos >> height;
+        bits.push(on_path);
+        if !on_path {
+            hashes.push(siblings[height]);
+        } else if height == 0 {
+            hashes.push(path[0]);
+        } else {
+            // Depth first, left child first
+            if 2 * index + 1 < width(height - 1) {
+                stack.push((height - 1, 2 * index + 1));
+            }
+            stack.push((height - 1, 2 * index));
+        }
+    }
+
+    let mut bytes = vec![0u8; (bits.len() + 7) / 8];
+    for (i, bit) in bits.iter().enumerate() {
+        bytes[i / 8] |= (*bit as u8) << (i % 8);
+    }
+
+    let mut result = serialize(header);
+    result.extend((n_tx as u32).to_le_bytes());
+    result.extend(serialize(&VarInt(hashes.len() as u64)));
+    for hash in hashes {
+        result.extend(hash);
+    }
+    result.extend(serialize(&bytes));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elements::encode::Decodable;
+
+    fn hash_pair(left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        sha256d::Hash::hash(&[left, right].concat()).to_byte_array()
+    }
+
+    /// The merkle levels of `txids`, from the leaves to the root
+    fn levels(txids: &[[u8; 32]]) -> Vec<Vec<[u8; 32]>> {
+        let mut levels = vec![txids.to_vec()];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let next = level
+                .chunks(2)
+                .map(|c| hash_pair(c[0], *c.get(1).unwrap_or(&c[0])))
+                .collect();
+            levels.push(next);
+        }
+        levels
+    }
+
+    /// The electrum merkle branch of the transaction at `pos`
+    fn branch(levels: &[Vec<[u8; 32]>], pos: usize) -> Vec<[u8; 32]> {
+        levels[..levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(height, level)| {
+                let index = (pos >> height) ^ 1;
+                let mut hash = *level.get(index).unwrap_or(&level[index ^ 1]);
+                hash.reverse();
+                hash
+            })
+            .collect()
+    }
+
+    /// Extract the root and the matched hashes like `CPartialMerkleTree::ExtractMatches` of
+    /// elements core, failing on the same conditions
+    fn extract(proof: &[u8]) -> (BlockHeader, Vec<[u8; 32]>) {
+        let mut cursor = std::io::Cursor::new(proof);
+        let header = BlockHeader::consensus_decode(&mut cursor).unwrap();
+        let mut n_tx = [0u8; 4];
+        std::io::Read::read_exact(&mut cursor, &mut n_tx).unwrap();
+        let n_tx = u32::from_le_bytes(n_tx) as usize;
+        let n_hashes = VarInt::consensus_decode(&mut cursor).unwrap().0 as usize;
+        let hashes: Vec<[u8; 32]> = (0..n_hashes)
+            .map(|_| <[u8; 32]>::consensus_decode(&mut cursor).unwrap())
+            .collect();
+        let bytes = Vec::<u8>::consensus_decode(&mut cursor).unwrap();
+        assert_eq!(cursor.position() as usize, proof.len());
+        let bits: Vec<bool> = (0..bytes.len() * 8)
+            .map(|i| bytes[i / 8] & (1 << (i % 8)) != 0)
+            .collect();
+        assert!(n_tx > 0 && hashes.len() <= n_tx && bits.len() >= hashes.len());
+
+        let width = |height: usize| (n_tx + (1 << height) - 1) >> height;
+        let mut tree_height = 0;
+        while width(tree_height) > 1 {
+            tree_height += 1;
+        }
+        struct State<'a> {
+            bits: &'a [bool],
+            hashes: &'a [[u8; 32]],
+            bits_used: usize,
+            hashes_used: usize,
+            matches: Vec<[u8; 32]>,
+        }
+        fn traverse(
+            s: &mut State,
+            width: &dyn Fn(usize) -> usize,
+            height: usize,
+            pos: usize,
+        ) -> [u8; 32] {
+            let parent_of_match = s.bits[s.bits_used];
+            s.bits_used += 1;
+            if height == 0 || !parent_of_match {
+                let hash = s.hashes[s.hashes_used];
+                s.hashes_used += 1;
+                if height == 0 && parent_of_match {
+                    s.matches.push(hash);
+                }
+                return hash;
+            }
+            let left = traverse(s, width, height - 1, pos * 2);
+            let right = if pos * 2 + 1 < width(height - 1) {
+                let right = traverse(s, width, height - 1, pos * 2 + 1);
+                assert_ne!(left, right, "identical branches");
+                right
+            } else {
+                left
+            };
+            hash_pair(left, right)
+        }
+        let mut s = State {
+            bits: &bits,
+            hashes: &hashes,
+            bits_used: 0,
+            hashes_used: 0,
+            matches: vec![],
+        };
+        let root = traverse(&mut s, &width, tree_height, 0);
+        assert_eq!(s.hashes_used, hashes.len());
+        assert_eq!((s.bits_used + 7) / 8, bytes.len());
+        assert_eq!(root, header.merkle_root.to_byte_array());
+        (header, s.matches)
+    }
+
+    #[test]
+    fn test_txout_proof() {
+        let block = lwk_test_util::liquid_block_1();
+        for n_tx in [1usize, 2, 3, 4, 5, 6, 7, 8, 9, 13] {
+            let txids: Vec<[u8; 32]> = (0..n_tx)
+                .map(|i| sha256d::Hash::hash(&i.to_le_bytes()).to_byte_array())
+                .collect();
+            let levels = levels(&txids);
+            let mut header = block.header.clone();
+            header.merkle_root =
+                crate::elements::TxMerkleNode::from_byte_array(levels.last().unwrap()[0]);
+            for (pos, txid) in txids.iter().enumerate() {
+                let txid = Txid::from_byte_array(*txid);
+                let merkle = branch(&levels, pos);
+                let proof = txout_proof(&header, &txid, pos, &merkle).unwrap();
+                let (decoded_header, matches) = extract(&proof);
+                assert_eq!(decoded_header, header);
+                assert_eq!(matches, vec![txid.to_byte_array()]);
+            }
+
+            if n_tx > 1 {
+                let txid = Txid::from_byte_array(txids[0]);
+                let merkle = branch(&levels, 1);
+                assert!(txout_proof(&header, &txid, 0, &merkle).is_err());
+            }
+        }
+    }
+}
@@ -0,0 +1,315 @@
+//! Client fetching wallet updates computed by a hosted scan service
+//!
+//! Thin clients can avoid scanning the blockchain by asking a service knowing their descriptor
+//! for the [`Update`]s of the wallet. The protocol is a single HTTP request:
+//!
+//! * `GET <url>?status=<status>`, where `status` is [`Wollet::status()`], with the header
+//!   `Authorization: Bearer <token>` if the client has an authentication token.
+//! * The service replies `204 No Content` if the wallet is up to date, otherwise `200 OK` with
+//!   the update encrypted with the wallet descriptor and base64 encoded, as in
+//!   [`Update::serialize_encrypted_base64()`].
+//! * If the service has a signing key, the [`SIGNATURE_HEADER`] header contains an ECDSA
+//!   signature of the requested status, of the wallet and of the body, see
+//!   [`UpdateClient::sign_update()`]. Clients configured with the service public key reject
+//!   unsigned or tampered responses, and responses for another status or wallet.
+//!
+//! The service must be reached over https, plain http is accepted only on the loopback interface.
+
+use std::time::Duration;
+
+use reqwest::blocking::Client;
+use reqwest::{StatusCode, Url};
+
+use crate::hashes::{sha256t_hash_newtype, Hash, HashEngine};
+use crate::secp256k1::{ecdsa::Signature, Message, PublicKey, SecretKey};
+use crate::{Error, Update, Wollet, WolletDescriptor, EC};
+
+/// The response header containing the signature of the update
+pub const SIGNATURE_HEADER: &str = "X-Lwk-Signature";
+
+/// The default timeout of the requests of an [`UpdateClient`]
+pub const UPDATE_TIMEOUT: Duration = Duration::from_secs(30);
+
+sha256t_hash_newtype! {
+    /// The tag of the hash
+    pub struct UpdateSigTag = hash_str("LWK-Signed-Update/1.0");
+
+    /// A tagged hash of the status, of the wallet and of the update, signed by the service
+    #[hash_newtype(forward)]
+    pub struct UpdateSigHash(_);
+}
+
+sha256t_hash_newtype! {
+    /// The tag of the hash
+    pub struct UpdateWalletIdTag = hash_str("LWK-Update-Wallet-Id/1.0");
+
+    /// A tagged hash identifying the wallet of a signed update without revealing its descriptor
+    #[hash_newtype(forward)]
+    pub struct UpdateWalletIdHash(_);
+}
+
+/// A client fetching the updates of a wallet from a hosted scan service
+#[derive(Debug)]
+pub struct UpdateClient {
+    client: Client,
+    url: String,
+
+    /// Token sent in the `Authorization` header of the requests
+    auth_token: Option<String>,
+
+    /// Public key of the service, if set the updates must be signed with the corresponding key
+    server_key: Option<PublicKey>,
+}
+
+impl UpdateClient {
+    /// Create a client fetching the updates from `url`, with a timeout of [`UPDATE_TIMEOUT`]
+    ///
+    /// The `url` must use https, unless the service is on the loopback interface.
+    pub fn new(url: &str) -> Result<Self, Error> {
+        Self::with_timeout(url, UPDATE_TIMEOUT)
+    }
+
+    /// Create a client fetching the updates from `url`, failing the requests not completed
+    /// within `timeout`
+    pub fn with_timeout(url: &str, timeout: Duration) -> Result<Self, Error> {
+        let parsed = Url::parse(url).map_err(|_| Error::InsecureUpdateUrl(url.to_string()))?;
+        let host = parsed.host_str().unwrap_or_default();
+        let loopback = host == "localhost"
+            || host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<std::net::IpAddr>()
+                .map_or(false, |ip| ip.is_loopback());
+        if parsed.scheme() != "https" && !(parsed.scheme() == "http" && loopback) {
+            return Err(Error::InsecureUpdateUrl(url.to_string()));
+        }
+        Ok(Self {
+            client: Client::builder().timeout(timeout).build()?,
+            url: url.to_string(),
+            auth_token: None,
+            server_key: None,
+        })
+    }
+
+    /// Authenticate the requests with the given bearer token
+    pub fn with_auth_token(mut self, token: &str) -> Self {
+        self.auth_token = Some(token.to_string());
+        self
+    }
+
+    /// Accept only updates signed by the service with the key corresponding to `key`
+    pub fn with_server_key(mut self, key: PublicKey) -> Self {
+        self.server_key = Some(key);
+        self
+    }
+
+    /// Fetch the update of `wollet`, none if the wallet is up to date
+    pub fn get_update(&self, wollet: &Wollet) -> Result<Option<Update>, Error> {
+        let status = wollet.status();
+        let mut request = self
+            .client
+            .get(&self.url)
+            .query(&[("status", status.to_string())]);
+        if let Some(token) = self.auth_token.as_ref() {
+            request = request.bearer_auth(token);
+        }
+        let response = request.send()?.error_for_status()?;
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        let signature = response
+            .headers()
+            .get(SIGNATURE_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(ToString::to_string);
+        let body = response.text()?;
+        let body = body.trim();
+
+        if let Some(key) = self.server_key.as_ref() {
+            let signature = signature.ok_or(Error::MissingUpdateSignature)?;
+            verify_update(key, status, &wollet.wollet_descriptor(), body, &signature)?;
+        }
+        let update = Update::deserialize_decrypted_base64(body, &wollet.wollet_descriptor())?;
+        Ok(Some(update))
+    }
+
+    /// Fetch the update of `wollet` and apply it, returns true if the wallet changed
+    pub fn sync(&self, wollet: &mut Wollet) -> Result<bool, Error> {
+        match self.get_update(wollet)? {
+            Some(update) => {
+                wollet.apply_update(update)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// The signature made by the service with `key` of the response `body` to the request of the
+    /// wallet with descriptor `desc` at `status`, to be returned in the [`SIGNATURE_HEADER`] header
+    pub fn sign_update(
+        key: &SecretKey,
+        status: u64,
+        desc: &WolletDescriptor,
+        body: &str,
+    ) -> String {
+        EC.sign_ecdsa(&message(status, desc, body), key).to_string()
+    }
+}
+
+/// The hash of the big endian `status`, of the wallet id and of the `body`
+fn message(status: u64, desc: &WolletDescriptor, body: &str) -> Message {
+    let wallet_id = UpdateWalletIdHash::hash(desc.to_string().as_bytes());
+    let mut engine = UpdateSigHash::engine();
+    engine.input(&status.to_be_bytes());
+    engine.input(wallet_id.as_byte_array());
+    engine.input(body.as_bytes());
+    Message::from_digest(UpdateSigHash::from_engine(engine).to_byte_array())
+}
+
+fn verify_update(
+    key: &PublicKey,
+    status: u64,
+    desc: &WolletDescriptor,
+    body: &str,
+    signature: &str,
+) -> Result<(), Error> {
+    let signature: Signature = signature
+        .parse()
+        .map_err(|_| Error::InvalidUpdateSignature)?;
+    EC.verify_ecdsa(&message(status, desc, body), &signature, key)
+        .map_err(|_| Error::InvalidUpdateSignature)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::mpsc::{channel, Receiver};
+
+    use lwk_test_util::{http_server, HttpRequest, HttpResponse};
+
+    use super::*;
+    use crate::wollet::tests::{new_wollet, receive_update};
+
+    const DESC: &str = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+
+    /// Serve `response`, returning the url and the requests received
+    fn serve(response: HttpResponse) -> (String, Receiver<HttpRequest>) {
+        let (sender, receiver) = channel();
+        let url = http_server(move |request| {
+            let _ = sender.send(request);
+            response.clone()
+        });
+        (format!("{url}/update"), receiver)
+    }
+
+    fn ok_response(body: &str, signature: Option<&str>) -> HttpResponse {
+        let response = HttpResponse::ok(body);
+        match signature {
+            Some(signature) => response.with_header(SIGNATURE_HEADER, signature),
+            None => response,
+        }
+    }
+
+    #[test]
+    fn test_update_client() {
+        let mut wollet = new_wollet(DESC);
+        let desc = wollet.wollet_descriptor();
+        let update = receive_update(&wollet, 10_000);
+        let body = update.serialize_encrypted_base64(&desc).unwrap();
+        let server_key = SecretKey::from_slice(&[1u8; 32]).unwrap();
+        let server_pubkey = server_key.public_key(&EC);
+        let status = wollet.status();
+        let signature = UpdateClient::sign_update(&server_key, status, &desc, &body);
+
+        // Signed update
+        let (url, requests) = serve(ok_response(&body, Some(&signature)));
+        let client = UpdateClient::new(&url)
+            .unwrap()
+            .with_auth_token("secret")
+            .with_server_key(server_pubkey);
+        assert!(client.sync(&mut wollet).unwrap());
+        assert_eq!(wollet.balance().unwrap()[&wollet.policy_asset()], 10_000);
+        let request = requests.recv().unwrap();
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.path, format!("/update?status={status}"));
+        assert_eq!(request.header("Authorization"), Some("Bearer secret"));
+
+        // Up to date
+        let (url, _) = serve(HttpResponse::new("204 No Content", vec![]));
+        let client = UpdateClient::new(&url)
+            .unwrap()
+            .with_server_key(server_pubkey);
+        assert!(!client.sync(&mut wollet).unwrap());
+
+        // Missing signature
+        let (url, _) = serve(ok_response(&body, None));
+        let client = UpdateClient::new(&url)
+            .unwrap()
+            .with_server_key(server_pubkey);
+        let err = client.get_update(&wollet).unwrap_err();
+        assert!(matches!(err, Error::MissingUpdateSignature));
+
+        // Signature of another key
+        let other_key = SecretKey::from_slice(&[2u8; 32]).unwrap();
+        let status = wollet.status();
+        let signature = UpdateClient::sign_update(&other_key, status, &desc, &body);
+        let (url, _) = serve(ok_response(&body, Some(&signature)));
+        let client = UpdateClient::new(&url)
+            .unwrap()
+            .with_server_key(server_pubkey);
+        let err = client.get_update(&wollet).unwrap_err();
+        assert!(matches!(err, Error::InvalidUpdateSignature));
+
+        // Stale update, signed for the status requested before
+        let stale = UpdateClient::sign_update(&server_key, status.wrapping_sub(1), &desc, &body);
+        let (url, _) = serve(ok_response(&body, Some(&stale)));
+        let client = UpdateClient::new(&url)
+            .unwrap()
+            .with_server_key(server_pubkey);
+        let err = client.get_update(&wollet).unwrap_err();
+        assert!(matches!(err, Error::InvalidUpdateSignature));
+
+        // Update signed for another wallet
+        let other: WolletDescriptor = lwk_test_util::wollet_descriptor_string().parse().unwrap();
+        let foreign = UpdateClient::sign_update(&server_key, status, &other, &body);
+        let (url, _) = serve(ok_response(&body, Some(&foreign)));
+        let client = UpdateClient::new(&url)
+            .unwrap()
+            .with_server_key(server_pubkey);
+        let err = client.get_update(&wollet).unwrap_err();
+        assert!(matches!(err, Error::InvalidUpdateSignature));
+
+        // Without the server key signatures are not checked
+        let (url, _) = serve(ok_response(&body, None));
+        let client = UpdateClient::new(&url).unwrap();
+        assert!(client.get_update(&wollet).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_update_client_url() {
+        for url in [
+            "https://example.com/update",
+            "http://127.0.0.1:3000/update",
+            "http://localhost/update",
+            "http://[::1]/update",
+        ] {
+            assert!(UpdateClient::new(url).is_ok(), "{url}");
+        }
+        for url in ["http://example.com/update", "ftp://127.0.0.1/", "update"] {
+            let err = UpdateClient::new(url).unwrap_err();
+            assert!(matches!(err, Error::InsecureUpdateUrl(_)), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_update_client_timeout() {
+        let wollet = new_wollet(DESC);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/update", listener.local_addr().unwrap());
+        let client = UpdateClient::with_timeout(&url, Duration::from_millis(200)).unwrap();
+        // The connection is accepted by the OS but never answered
+        let err = client.get_update(&wollet).unwrap_err();
+        assert!(matches!(err, Error::Reqwest(e) if e.is_timeout()));
+        drop(listener);
+    }
+}
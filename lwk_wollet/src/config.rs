@@ -1,4 +1,4 @@
-use crate::elements::{AddressParams, AssetId};
+use crate::elements::{AddressParams, AssetId, BlockHash};
 use crate::error::Error;
 use std::str::FromStr;
 
@@ -6,6 +6,10 @@ const LIQUID_POLICY_ASSET_STR: &str =
     "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d";
 const LIQUID_TESTNET_POLICY_ASSET_STR: &str =
     "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49";
+const LIQUID_GENESIS_HASH_STR: &str =
+    "1466275836220db2944ca059a3a10ef6fd2ea684b0688d2c379296888a206003";
+const LIQUID_TESTNET_GENESIS_HASH_STR: &str =
+    "a771da8e52ee6ad581ed1e9a99825e5b3b7992225534eaa2ae23244fe26ab1c1";
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy, Hash)]
 pub enum ElementsNetwork {
@@ -27,6 +31,19 @@ impl ElementsNetwork {
         }
     }
 
+    /// The hash of the genesis block, `None` for regtest since every regtest chain has its own
+    pub fn genesis_hash(&self) -> Option<BlockHash> {
+        match self {
+            ElementsNetwork::Liquid => {
+                Some(BlockHash::from_str(LIQUID_GENESIS_HASH_STR).expect("can't fail on const"))
+            }
+            ElementsNetwork::LiquidTestnet => Some(
+                BlockHash::from_str(LIQUID_TESTNET_GENESIS_HASH_STR).expect("can't fail on const"),
+            ),
+            ElementsNetwork::ElementsRegtest { .. } => None,
+        }
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             ElementsNetwork::Liquid => "liquid",
@@ -1,11 +1,12 @@
-use std::{fmt::Display, str::FromStr};
+use std::{fmt::Display, ops::Range, str::FromStr};
 
 use aes_gcm_siv::aead::generic_array::GenericArray;
 use aes_gcm_siv::aead::NewAead;
 use aes_gcm_siv::Aes256GcmSiv;
 use elements::bitcoin::{bip32::ChildNumber, WitnessVersion};
 use elements::hashes::{sha256t_hash_newtype, Hash};
-use elements::{Address, AddressParams};
+use elements::secp256k1_zkp::{PublicKey, SecretKey};
+use elements::{Address, AddressParams, Script};
 use elements_miniscript::{
     confidential::Key,
     descriptor::{DescriptorSecretKey, Wildcard},
@@ -24,11 +25,18 @@ sha256t_hash_newtype! {
 
 #[derive(Debug, Clone)]
 /// A wrapper that contains only the subset of CT descriptors handled by wollet
+///
+/// Descriptors without the `ct()` wrapper are accepted too, for wallets with all the outputs
+/// explicit. Internally they have a bare blinding key, which is otherwise rejected.
 pub struct WolletDescriptor(ConfidentialDescriptor<DescriptorPublicKey>);
 
 impl Display for WolletDescriptor {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(&self.0, f)
+        if self.is_confidential() {
+            Display::fmt(&self.0, f)
+        } else {
+            Display::fmt(&self.0.descriptor, f)
+        }
     }
 }
 
@@ -53,35 +61,59 @@ impl TryFrom<ConfidentialDescriptor<DescriptorPublicKey>> for WolletDescriptor {
                 return Err(Self::Error::BlindingViewWildcardUnsupported);
             }
         }
+        check_descriptor(desc)
+    }
+}
 
-        if !desc.descriptor.has_wildcard() {
-            return Err(Self::Error::UnsupportedDescriptorWithoutWildcard);
-        }
-        if desc.descriptor.is_multipath() {
-            let descriptors = desc.descriptor.clone().into_single_descriptors()?;
+impl TryFrom<Descriptor<DescriptorPublicKey>> for WolletDescriptor {
+    type Error = crate::error::Error;
 
-            if descriptors.len() > 2 {
-                return Err(Self::Error::UnsupportedMultipathDescriptor);
-            }
+    /// A descriptor without blinding key, all its outputs are explicit
+    fn try_from(descriptor: Descriptor<DescriptorPublicKey>) -> Result<Self, Self::Error> {
+        let mut key = None;
+        descriptor.for_each_key(|k| {
+            key.get_or_insert_with(|| k.clone());
+            true
+        });
+        let key = key.ok_or(crate::error::Error::UnsupportedDescriptorWithoutWildcard)?;
+        check_descriptor(ConfidentialDescriptor {
+            key: Key::Bare(key),
+            descriptor,
+        })
+    }
+}
+
+fn check_descriptor(
+    desc: ConfidentialDescriptor<DescriptorPublicKey>,
+) -> Result<WolletDescriptor, crate::error::Error> {
+    use crate::error::Error;
+    if !desc.descriptor.has_wildcard() {
+        return Err(Error::UnsupportedDescriptorWithoutWildcard);
+    }
+    if desc.descriptor.is_multipath() {
+        let descriptors = desc.descriptor.clone().into_single_descriptors()?;
+
+        if descriptors.len() > 2 {
+            return Err(Error::UnsupportedMultipathDescriptor);
+        }
 
-            for (i, desc) in descriptors.iter().enumerate() {
-                let r = desc.for_each_key(|k| {
-                    if let Some(path) = k.full_derivation_path() {
-                        if let Some(val) = path.into_iter().last() {
-                            return val == &ChildNumber::from(i as u32);
-                        }
+        for (i, desc) in descriptors.iter().enumerate() {
+            let r = desc.for_each_key(|k| {
+                if let Some(path) = k.full_derivation_path() {
+                    if let Some(val) = path.into_iter().last() {
+                        return val == &ChildNumber::from(i as u32);
                     }
-                    false
-                });
-                if !r {
-                    return Err(Self::Error::UnsupportedMultipathDescriptor);
                 }
+                false
+            });
+            if !r {
+                return Err(Error::UnsupportedMultipathDescriptor);
             }
         }
-        match desc.descriptor.desc_type().segwit_version() {
-            Some(WitnessVersion::V0) => Ok(WolletDescriptor(desc)),
-            _ => Err(Self::Error::UnsupportedDescriptorNonV0),
-        }
+    }
+    match desc.descriptor.desc_type().segwit_version() {
+        Some(WitnessVersion::V0) => Ok(WolletDescriptor(desc)),
+        _ => Err(Error::UnsupportedDescriptorNonV0),
     }
 }
 
@@ -89,7 +121,11 @@ impl FromStr for WolletDescriptor {
     type Err = crate::error::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        ConfidentialDescriptor::<DescriptorPublicKey>::from_str(s)?.try_into()
+        if s.trim_start().starts_with("ct(") {
+            ConfidentialDescriptor::<DescriptorPublicKey>::from_str(s)?.try_into()
+        } else {
+            Descriptor::<DescriptorPublicKey>::from_str(s)?.try_into()
+        }
     }
 }
 
@@ -103,6 +139,25 @@ pub enum Chain {
     Internal,
 }
 
+/// A script of the wallet with its blinding keys, see [`WolletDescriptor::scripts()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedScript {
+    pub ext_int: Chain,
+    pub wildcard_index: u32,
+    pub script_pubkey: Script,
+
+    /// The blinding public key of the addresses of the script, `None` for non-confidential
+    /// descriptors
+    pub blinding_pubkey: Option<PublicKey>,
+
+    /// The blinding private key, to unblind the outputs on the script, `None` for
+    /// non-confidential descriptors
+    pub blinding_key: Option<SecretKey>,
+}
+
+/// Maximum number of wildcard indexes derived by [`WolletDescriptor::scripts()`] in one call
+pub const MAX_SCRIPTS_RANGE: u32 = 10_000;
+
 impl TryFrom<&Descriptor<DescriptorPublicKey>> for Chain {
     type Error = ();
 
@@ -138,6 +193,12 @@ impl WolletDescriptor {
         &self.0.descriptor
     }
 
+    /// Whether the descriptor has a blinding key, false for descriptors without `ct()` whose
+    /// addresses are unconfidential
+    pub fn is_confidential(&self) -> bool {
+        !matches!(self.0.key, Key::Bare(_))
+    }
+
     /// Return wether the descriptor has a blinding key derived with [Elip151](https://github.com/ElementsProject/ELIPs/blob/main/elip-0151.mediawiki)
     pub fn is_elip151(&self) -> bool {
         if let Ok(elip151_key) = Key::from_elip151(&self.0.descriptor) {
@@ -218,11 +279,63 @@ impl WolletDescriptor {
         params: &'static AddressParams,
         ext_int: Chain,
     ) -> Result<Address, crate::error::Error> {
-        Ok(self
-            .inner_descriptor_if_available(ext_int)
-            .0
-            .at_derivation_index(index)?
-            .address(&crate::EC, params)?)
+        let desc = self.inner_descriptor_if_available(ext_int);
+        if self.is_confidential() {
+            Ok(desc
+                .0
+                .at_derivation_index(index)?
+                .address(&crate::EC, params)?)
+        } else {
+            Ok(desc
+                .descriptor()
+                .at_derivation_index(index)?
+                .address(params)?)
+        }
+    }
+
+    /// Derive the scripts of the wallet at the wildcard indexes in `range` with their blinding
+    /// keys, first the external scripts and then the internal ones
+    ///
+    /// Useful to register the wallet scripts on external indexers. Descriptors without the
+    /// `<0;1>` multipath have only external scripts. The range can't be longer than
+    /// [`MAX_SCRIPTS_RANGE`].
+    pub fn scripts(&self, range: Range<u32>) -> Result<Vec<DerivedScript>, crate::Error> {
+        if range.len() > MAX_SCRIPTS_RANGE as usize {
+            return Err(crate::Error::ScriptsRangeTooLarge);
+        }
+        let chains: &[Chain] = if self.0.descriptor.is_multipath() {
+            &[Chain::External, Chain::Internal]
+        } else {
+            &[Chain::External]
+        };
+        let mut scripts = vec![];
+        for ext_int in chains {
+            let desc = self.inner_descriptor_if_available(*ext_int);
+            for wildcard_index in range.clone() {
+                let script_pubkey = desc
+                    .descriptor()
+                    .at_derivation_index(wildcard_index)?
+                    .script_pubkey();
+                let blinding_key = lwk_common::derive_blinding_key(&self.0, &script_pubkey);
+                let blinding_pubkey = if self.is_confidential() {
+                    // The network doesn't change the blinding public key
+                    desc.0
+                        .at_derivation_index(wildcard_index)?
+                        .address(&crate::EC, &AddressParams::ELEMENTS)?
+                        .blinding_pubkey
+                } else {
+                    None
+                };
+                scripts.push(DerivedScript {
+                    ext_int: *ext_int,
+                    wildcard_index,
+                    script_pubkey,
+                    blinding_pubkey,
+                    blinding_key,
+                });
+            }
+        }
+        Ok(scripts)
     }
 
     /// Get a definite descriptor
@@ -249,7 +362,7 @@ mod test {
         hash::{Hash, Hasher},
     };
 
-    use crate::WolletDescriptor;
+    use crate::{Chain, WolletDescriptor};
 
     #[test]
     fn test_wollet_hash() {
@@ -261,6 +374,53 @@ mod test {
         assert_eq!(12055616352728229988, hasher.finish());
     }
 
+    #[test]
+    fn test_scripts() {
+        let desc_str = "ct(slip77(ab5824f4477b4ebb00a132adfd8eb0b7935cf24f6ac151add5d1913db374ce92),elwpkh([759db348/84'/1'/0']tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/<0;1>/*))#cch6wrnp";
+        let desc: WolletDescriptor = desc_str.parse().unwrap();
+        let params = &elements::AddressParams::LIQUID_TESTNET;
+        let scripts = desc.scripts(5..8).unwrap();
+        assert_eq!(scripts.len(), 6);
+        for (i, s) in scripts.iter().enumerate() {
+            let (ext_int, index) = (s.ext_int, s.wildcard_index);
+            assert_eq!(index, 5 + (i as u32 % 3));
+            let address = match ext_int {
+                Chain::External => desc.address(index, params).unwrap(),
+                Chain::Internal => desc.change(index, params).unwrap(),
+            };
+            assert_eq!(ext_int == Chain::External, i < 3);
+            assert_eq!(s.script_pubkey, address.script_pubkey());
+            assert_eq!(s.blinding_pubkey, address.blinding_pubkey);
+            let blinding_key = s.blinding_key.unwrap();
+            assert_eq!(s.blinding_pubkey, Some(blinding_key.public_key(&crate::EC)));
+        }
+        assert!(desc.scripts(5..5).unwrap().is_empty());
+        assert!(matches!(
+            desc.scripts(0..super::MAX_SCRIPTS_RANGE + 1),
+            Err(crate::Error::ScriptsRangeTooLarge)
+        ));
+
+        // Descriptors without multipath have only external scripts
+        let desc_str = "ct(ab5824f4477b4ebb00a132adfd8eb0b7935cf24f6ac151add5d1913db374ce92,elwpkh(tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/0/*))";
+        let desc: WolletDescriptor = desc_str.parse().unwrap();
+        let scripts = desc.scripts(0..2).unwrap();
+        assert_eq!(scripts.len(), 2);
+        assert!(scripts.iter().all(|s| s.ext_int == Chain::External));
+        let address = desc.address(1, params).unwrap();
+        assert_eq!(scripts[1].script_pubkey, address.script_pubkey());
+        assert_eq!(scripts[1].blinding_pubkey, address.blinding_pubkey);
+        assert!(scripts[1].blinding_key.is_some());
+
+        // Non-confidential descriptors have no blinding keys
+        let desc_str = "elwpkh(tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/0/*)";
+        let desc: WolletDescriptor = desc_str.parse().unwrap();
+        let scripts = desc.scripts(0..2).unwrap();
+        let address = desc.address(1, params).unwrap();
+        assert_eq!(scripts[1].script_pubkey, address.script_pubkey());
+        assert!(scripts[1].blinding_pubkey.is_none());
+        assert!(scripts[1].blinding_key.is_none());
+    }
+
     #[test]
     fn test_is_elip151() {
         let desc_str = "ct(slip77(ab5824f4477b4ebb00a132adfd8eb0b7935cf24f6ac151add5d1913db374ce92),elwpkh([759db348/84'/1'/0']tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/<0;1>/*))#cch6wrnp";
@@ -281,4 +441,32 @@ mod test {
         let expected = "elwpkh(tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/<0;1>/*)";
         assert_eq!(expected, desc.bitcoin_descriptor_without_key_origin());
     }
+
+    #[test]
+    fn test_unconfidential() {
+        let desc_str = "elwpkh([759db348/84'/1'/0']tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/<0;1>/*)";
+        let desc: WolletDescriptor = desc_str.parse().unwrap();
+        assert!(!desc.is_confidential());
+        assert!(desc.to_string().starts_with(desc_str));
+        let desc_again: WolletDescriptor = desc.to_string().parse().unwrap();
+        assert_eq!(desc.to_string(), desc_again.to_string());
+
+        let params = &elements::AddressParams::LIQUID_TESTNET;
+        let address = desc.address(0, params).unwrap();
+        assert!(!address.is_blinded());
+        let change = desc.change(0, params).unwrap();
+        assert!(!change.is_blinded());
+        assert_ne!(address, change);
+
+        // The same script of the confidential wallet
+        let ct_str = format!("ct(slip77(ab5824f4477b4ebb00a132adfd8eb0b7935cf24f6ac151add5d1913db374ce92),{desc_str})");
+        let ct: WolletDescriptor = ct_str.parse().unwrap();
+        assert!(ct.is_confidential());
+        let ct_address = ct.address(0, params).unwrap();
+        assert_eq!(ct_address.to_unconfidential(), address);
+
+        // Bare blinding keys are still rejected in CT descriptors
+        let bare = "ct(02ab89406d9cf32ff1819838136eecb65c07add8e8ef1cd2d6c64bab1d85606453,elwpkh(tpubDCRMaF33e44pcJj534LXVhFbHibPbJ5vuLhSSPFAw57kYURv4tzXFL6LSnd78bkjqdmE3USedkbpXJUPA1tdzKfuYSL7PianceqAhwL2UkA/*))";
+        assert!(bare.parse::<WolletDescriptor>().is_err());
+    }
 }
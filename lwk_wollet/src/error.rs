@@ -1,3 +1,5 @@
+use lwk_common::ErrorCategory;
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("{0}")]
@@ -64,9 +66,15 @@ pub enum Error {
     #[error(transparent)]
     AddressError(#[from] crate::elements::AddressError),
 
+    #[error(transparent)]
+    Verification(#[from] crate::elements::VerificationError),
+
     #[error(transparent)]
     PsetDetailsError(#[from] lwk_common::Error),
 
+    #[error(transparent)]
+    Amount(#[from] lwk_common::amount::Error),
+
     #[error(transparent)]
     UtxoUpdateError(#[from] elements_miniscript::psbt::UtxoUpdateError),
 
@@ -101,6 +109,9 @@ pub enum Error {
     #[error("Missing vout")]
     MissingVout,
 
+    #[error("Input {0} is missing the witness utxo")]
+    MissingWitnessUtxo(usize),
+
     #[error("Invalid amount")]
     InvalidAmount,
 
@@ -148,15 +159,69 @@ pub enum Error {
     #[error("Contract does not commit to asset id")]
     ContractDoesNotCommitToAssetId,
 
+    #[error("Outpoint {0} is not a wallet UTXO")]
+    UtxoNotMine(crate::elements::OutPoint),
+
+    #[error("Outpoint {0} is a locked wallet UTXO")]
+    UtxoLocked(crate::elements::OutPoint),
+
+    #[error("Invalid label {0}")]
+    InvalidLabel(String),
+
+    #[error("Invalid proof of reserves: {0}")]
+    InvalidReservesProof(String),
+
+    #[error("Invalid SPV proof: {0}")]
+    InvalidSpvProof(String),
+
+    #[error("Invalid peg-in: {0}")]
+    InvalidPegin(String),
+
+    #[error("The mainchain transaction does not send to a peg-in address of this wallet")]
+    PeginNotMine,
+
+    #[error("Invalid peg-out: {0}")]
+    InvalidPegout(String),
+
+    #[error("Invalid OP_RETURN memo: {0}")]
+    InvalidMemo(String),
+
+    #[error("The server is not on {network}, its genesis block is {genesis}")]
+    NetworkMismatch {
+        network: &'static str,
+        genesis: crate::elements::BlockHash,
+    },
+
+    #[error("Domain proof at {0} is missing or does not match the expected content")]
+    InvalidDomainProof(String),
+
     #[error("Update height {update_tip_height} too old (internal height {store_tip_height})")]
     UpdateHeightTooOld {
         update_tip_height: u32,
         store_tip_height: u32,
     },
 
+    #[error("Update version {version} is not supported (up to {supported}), it has been written by a newer version of this library")]
+    UnsupportedUpdateVersion { version: u8, supported: u8 },
+
     #[error("An issuance has already being set on this tx builder")]
     IssuanceAlreadySet,
 
+    #[error("Unblinded transactions can be created only on regtest")]
+    UnblindedNotRegtest,
+
+    #[error("Reissuances cannot be unblinded")]
+    UnblindedReissuance,
+
+    #[error("The wallet has no blinding key, paying the confidential script {0} requires to opt in with `unblinded`")]
+    ConfidentialRecipientExplicitWallet(String),
+
+    #[error("The input of the fee provider must be L-BTC")]
+    FeeProviderNotLbtc,
+
+    #[error("Cannot send L-BTC, issue assets or claim peg-ins when the fee is paid by a provider")]
+    FeeProviderWithLbtc,
+
     #[error("Cannot use waterfalls scan with elip151 because it would reveal the blinding key to the server")]
     UsingWaterfallsWithElip151,
 
@@ -165,6 +230,39 @@ pub enum Error {
 
     #[error("Cannot parse server recipient key")]
     CannotParseRecipientKey,
+
+    #[error("The operation has been cancelled")]
+    Cancelled,
+
+    #[error("The update is not signed by the server")]
+    MissingUpdateSignature,
+
+    #[error("The update signature does not match the server key")]
+    InvalidUpdateSignature,
+
+    #[error("The update service url must use https: {0}")]
+    InsecureUpdateUrl(String),
+
+    #[error("The gap limit must be between 1 and {}", crate::store::MAX_GAP_LIMIT)]
+    InvalidGapLimit,
+
+    #[error(
+        "Cannot derive more than {} scripts per chain at once",
+        crate::descriptor::MAX_SCRIPTS_RANGE
+    )]
+    ScriptsRangeTooLarge,
+
+    #[error("The backup is of another wallet")]
+    BackupWalletMismatch,
+
+    #[error("Invalid backup: {0}")]
+    InvalidBackup(String),
+
+    #[error("No backup found at {0}")]
+    BackupNotFound(String),
+
+    #[error("The backup server returned status {status} for {url}")]
+    BackupServer { status: u16, url: String },
 }
 
 // cannot derive automatically with this error because of trait bound
@@ -173,3 +271,118 @@ impl From<aes_gcm_siv::aead::Error> for Error {
         Self::Aes(err.to_string())
     }
 }
+
+impl Error {
+    /// The category of this error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            #[cfg(feature = "electrum")]
+            Error::ClientError(_) => ErrorCategory::Network,
+            #[cfg(any(feature = "esplora_wasm", feature = "esplora"))]
+            Error::Reqwest(_) => ErrorCategory::Network,
+            Error::InvalidDomainProof(_) | Error::Cancelled | Error::BackupServer { .. } => {
+                ErrorCategory::Network
+            }
+
+            Error::PsetBlindError(_)
+            | Error::Unblind(_)
+            | Error::Verification(_)
+            | Error::ContractDoesNotCommitToAssetId
+            | Error::InvalidReservesProof(_)
+            | Error::InvalidSpvProof(_)
+            | Error::MissingUpdateSignature
+            | Error::InvalidUpdateSignature => ErrorCategory::Consensus,
+
+            Error::PsetDetailsError(e) => e.category(),
+
+            Error::Generic(_)
+            | Error::Aes(_)
+            | Error::StdIOError(_)
+            | Error::PersistError(_)
+            | Error::Secp256k1(_)
+            | Error::InsufficientFunds
+            | Error::MissingIssuance
+            | Error::MissingTransaction
+            | Error::MissingVin
+            | Error::MissingVout
+            | Error::ScriptNotMine
+            | Error::MissingPrivateBlindingKey
+            | Error::UtxoNotMine(_)
+            | Error::UtxoLocked(_)
+            | Error::PeginNotMine
+            | Error::UpdateHeightTooOld { .. }
+            | Error::UnsupportedUpdateVersion { .. }
+            | Error::CannotEncrypt => ErrorCategory::Wallet,
+
+            Error::BlindingBareUnsupported
+            | Error::BlindingViewWildcardUnsupported
+            | Error::BlindingViewMultiUnsupported
+            | Error::BitcoinBIP32Error(_)
+            | Error::JsonFrom(_)
+            | Error::ElementsEncode(_)
+            | Error::Hashes(_)
+            | Error::ElementsPset(_)
+            | Error::HexToBytesError(_)
+            | Error::HexToArrayError(_)
+            | Error::ElementsMiniscript(_)
+            | Error::ElementsMiniscriptPset(_)
+            | Error::DescConversion(_)
+            | Error::AddressError(_)
+            | Error::UtxoUpdateError(_)
+            | Error::OutputUpdateError(_)
+            | Error::ParseInt(_)
+            | Error::NotConfidentialAddress
+            | Error::MissingWitnessUtxo(_)
+            | Error::InvalidAmount
+            | Error::InvalidDomain
+            | Error::InvalidVersion
+            | Error::InvalidPrecision
+            | Error::InvalidName
+            | Error::InvalidTicker
+            | Error::InvalidIssuerPubkey
+            | Error::UnsupportedDescriptorWithoutWildcard
+            | Error::UnsupportedMultipathDescriptor
+            | Error::UnsupportedDescriptorNonV0
+            | Error::MissingPset
+            | Error::SendManyEmptyAddressee
+            | Error::IssuanceAlreadySet
+            | Error::Amount(_)
+            | Error::UnblindedNotRegtest
+            | Error::UnblindedReissuance
+            | Error::ConfidentialRecipientExplicitWallet(_)
+            | Error::FeeProviderNotLbtc
+            | Error::FeeProviderWithLbtc
+            | Error::UsingWaterfallsWithElip151
+            | Error::CannotParseRecipientKey
+            | Error::InvalidLabel(_)
+            | Error::InvalidPegin(_)
+            | Error::InvalidPegout(_)
+            | Error::InvalidMemo(_)
+            | Error::InvalidGapLimit
+            | Error::ScriptsRangeTooLarge
+            | Error::InsecureUpdateUrl(_)
+            | Error::BackupWalletMismatch
+            | Error::InvalidBackup(_)
+            | Error::BackupNotFound(_)
+            | Error::NetworkMismatch { .. } => ErrorCategory::Input,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_category() {
+        assert_eq!(Error::InsufficientFunds.category(), ErrorCategory::Wallet);
+        assert_eq!(Error::InvalidAmount.category(), ErrorCategory::Input);
+        let e = Error::PsetDetailsError(lwk_common::Error::MissingFee);
+        assert_eq!(e.category(), ErrorCategory::Input);
+        assert_eq!(e.category().to_string(), "input");
+        assert_eq!(
+            Error::InvalidReservesProof("".to_string()).category(),
+            ErrorCategory::Consensus
+        );
+    }
+}
@@ -0,0 +1,108 @@
+//! Fee rate estimation
+//!
+//! Fee rates are in satoshi per 1000 virtual bytes, like [`crate::TxBuilder::fee_rate()`].
+
+use std::collections::BTreeMap;
+
+/// Confirmation targets, in number of blocks, requested to the backends
+#[cfg(any(feature = "electrum", feature = "esplora", feature = "esplora_wasm"))]
+pub(crate) const FEE_TARGETS: [u16; 6] = [1, 2, 3, 6, 12, 144];
+
+/// Bounds and fallback values applied to the fee rates estimated by a backend
+///
+/// Estimators can be unavailable or return absurd values, for instance when the mempool is empty or
+/// the server is misbehaving. Estimates above `max_fee_rate` are ignored as missing, resulting fee
+/// rates are always between `min_fee_rate` and `max_fee_rate`, preventing both stuck transactions
+/// and fee overpayment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeePolicy {
+    /// The minimum fee rate, transactions paying less are not relayed
+    pub min_fee_rate: f32,
+
+    /// The maximum fee rate, higher estimates are considered a mistake of the estimator and the
+    /// fallback values are used instead
+    pub max_fee_rate: f32,
+
+    /// Fee rates used when the backend estimates are unavailable, by confirmation target
+    pub fallback: BTreeMap<u16, f32>,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        Self {
+            min_fee_rate: 100.0,
+            max_fee_rate: 10_000.0,
+            // Liquid blocks are rarely full, the minimum fee rate is usually enough
+            fallback: [(1, 250.0), (2, 200.0), (6, 150.0), (144, 100.0)]
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+impl FeePolicy {
+    /// The fee rate to confirm a transaction within `target_blocks`, using the backend `estimates`
+    /// if valid, otherwise the fallback table
+    pub fn fee_rate(&self, target_blocks: u16, estimates: &BTreeMap<u16, f32>) -> f32 {
+        let valid: BTreeMap<u16, f32> = estimates
+            .iter()
+            .filter(|(_, rate)| rate.is_finite() && **rate > 0.0 && **rate <= self.max_fee_rate)
+            .map(|(target, rate)| (*target, *rate))
+            .collect();
+        let fee_rate = lookup(&valid, target_blocks)
+            .or_else(|| lookup(&self.fallback, target_blocks))
+            .unwrap_or(self.min_fee_rate);
+        fee_rate.max(self.min_fee_rate).min(self.max_fee_rate)
+    }
+}
+
+/// The fee rate of the closest target confirming within `target_blocks`, or of the fastest target
+/// if none does
+fn lookup(table: &BTreeMap<u16, f32>, target_blocks: u16) -> Option<f32> {
+    table
+        .range(..=target_blocks)
+        .next_back()
+        .or_else(|| table.iter().next())
+        .map(|(_, rate)| *rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fee_policy() {
+        let policy = FeePolicy::default();
+        let none = BTreeMap::new();
+        assert_eq!(policy.fee_rate(1, &none), 250.0);
+        assert_eq!(policy.fee_rate(5, &none), 200.0);
+        assert_eq!(policy.fee_rate(1000, &none), 100.0);
+
+        let estimates: BTreeMap<u16, f32> = [(2, 300.0), (6, 50.0)].into_iter().collect();
+        // Faster target than any estimate
+        assert_eq!(policy.fee_rate(1, &estimates), 300.0);
+        assert_eq!(policy.fee_rate(3, &estimates), 300.0);
+        // Floor
+        assert_eq!(policy.fee_rate(6, &estimates), 100.0);
+
+        // Absurd estimates
+        let estimates: BTreeMap<u16, f32> = [(1, f32::NAN), (2, -1.0), (3, 1_000_000.0)]
+            .into_iter()
+            .collect();
+        assert_eq!(policy.fee_rate(1, &estimates), 250.0);
+        assert_eq!(policy.fee_rate(3, &estimates), 200.0);
+        let estimates: BTreeMap<u16, f32> = [(1, f32::INFINITY)].into_iter().collect();
+        assert_eq!(policy.fee_rate(1, &estimates), 250.0);
+        // Out of range estimates don't hide the valid ones
+        let estimates: BTreeMap<u16, f32> = [(1, 20_000.0), (2, 400.0)].into_iter().collect();
+        assert_eq!(policy.fee_rate(1, &estimates), 400.0);
+        let estimates: BTreeMap<u16, f32> = [(1, 10_000.0)].into_iter().collect();
+        assert_eq!(policy.fee_rate(1, &estimates), 10_000.0);
+
+        let policy = FeePolicy {
+            fallback: BTreeMap::new(),
+            ..Default::default()
+        };
+        assert_eq!(policy.fee_rate(1, &none), 100.0);
+    }
+}
@@ -0,0 +1,90 @@
+//! Fees paid in an asset other than L-BTC through a fee provider service
+//!
+//! Wallets holding only an asset, such as a stablecoin, cannot pay the L-BTC fee of their
+//! transactions. A fee provider quotes a rate between the asset and L-BTC and contributes an L-BTC
+//! input paying the fee, in exchange for an output of the asset. The wallet builds the transaction
+//! with [`crate::TxBuilder::fee_provider()`], signs its inputs and sends the PSET to the provider,
+//! which checks the payment with [`FeeQuote::asset_fee()`] and signs its own input.
+
+use std::collections::HashMap;
+
+use elements::pset::{Input, PartiallySignedTransaction};
+use elements::{Address, AssetId, OutPoint, TxOut, TxOutSecrets};
+
+use crate::{Error, Wollet};
+
+/// The price of the L-BTC fee in another asset, as quoted by a fee provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeeQuote {
+    /// The asset accepted by the provider
+    pub asset: AssetId,
+
+    /// Satoshi of `asset` for each satoshi of L-BTC fee
+    pub rate: f64,
+}
+
+impl FeeQuote {
+    /// The amount of the asset to pay for a fee of `fee` satoshi of L-BTC, rounded up
+    pub fn asset_amount(&self, fee: u64) -> u64 {
+        (fee as f64 * self.rate).ceil() as u64
+    }
+
+    /// The amount of the asset to pay for the fee of `pset`
+    ///
+    /// The fee is the sum of the fee outputs, which are always explicit.
+    pub fn asset_fee(&self, pset: &PartiallySignedTransaction) -> u64 {
+        let fee = pset
+            .outputs()
+            .iter()
+            .filter(|o| o.script_pubkey.is_empty())
+            .filter_map(|o| o.amount)
+            .fold(0u64, u64::saturating_add);
+        self.asset_amount(fee)
+    }
+}
+
+/// A fee provider paying the L-BTC fee of a transaction in exchange for the asset of its quote
+#[derive(Debug, Clone)]
+pub struct FeeProvider {
+    /// The quote of the provider
+    pub quote: FeeQuote,
+
+    /// The L-BTC output of the provider funding the fee, signed by the provider
+    pub outpoint: OutPoint,
+
+    /// The output at `outpoint`
+    pub txout: TxOut,
+
+    /// The unblinded value and blinding factors of `txout`, needed to blind the transaction
+    pub secrets: TxOutSecrets,
+
+    /// The weight of the witness spending `txout`
+    pub satisfaction_weight: usize,
+
+    /// The address receiving the asset payment and the L-BTC change of the provider
+    pub address: Address,
+}
+
+impl Wollet {
+    /// Add the input of the fee `provider` to `pset`, returning its value
+    pub(crate) fn add_fee_provider_input(
+        &self,
+        pset: &mut PartiallySignedTransaction,
+        inp_txout_sec: &mut HashMap<usize, TxOutSecrets>,
+        inp_weight: &mut usize,
+        provider: &FeeProvider,
+    ) -> Result<u64, Error> {
+        if provider.secrets.asset != self.policy_asset() {
+            return Err(Error::FeeProviderNotLbtc);
+        }
+        let mut input = Input::from_prevout(provider.outpoint);
+        let mut txout = provider.txout.clone();
+        input.in_utxo_rangeproof = txout.witness.rangeproof.take();
+        input.witness_utxo = Some(txout);
+        pset.add_input(input);
+        let idx = pset.inputs().len() - 1;
+        inp_txout_sec.insert(idx, provider.secrets);
+        *inp_weight += provider.satisfaction_weight;
+        Ok(provider.secrets.value)
+    }
+}
@@ -0,0 +1,186 @@
+//! Labels of wallet items, importable and exportable in the
+//! [BIP329](https://github.com/bitcoin/bips/blob/master/bip-0329.mediawiki) format
+
+use std::fmt;
+use std::str::FromStr;
+
+use elements::encode::{Decodable, Encodable};
+use elements::{Address, OutPoint, Txid};
+use serde::{Deserialize, Serialize};
+
+use crate::util::decode_string;
+use crate::Error;
+
+/// The wallet item a label refers to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum LabelRef {
+    /// A transaction
+    Tx(Txid),
+
+    /// An address
+    Address(Address),
+
+    /// A transaction output
+    Output(OutPoint),
+}
+
+impl LabelRef {
+    /// The BIP329 type of the item
+    pub fn type_(&self) -> &'static str {
+        match self {
+            LabelRef::Tx(_) => "tx",
+            LabelRef::Address(_) => "addr",
+            LabelRef::Output(_) => "output",
+        }
+    }
+
+    /// Create the reference from the BIP329 `type` and `ref` fields
+    ///
+    /// Returns `None` for the types not supported by the wallet, like `pubkey` or `xpub`.
+    pub fn from_type_ref(type_: &str, ref_: &str) -> Result<Option<Self>, Error> {
+        let invalid = |e: &dyn fmt::Display| Error::InvalidLabel(format!("{ref_}: {e}"));
+        Ok(Some(match type_ {
+            "tx" => LabelRef::Tx(Txid::from_str(ref_).map_err(|e| invalid(&e))?),
+            "addr" => LabelRef::Address(Address::from_str(ref_).map_err(|e| invalid(&e))?),
+            "output" => LabelRef::Output(OutPoint::from_str(ref_).map_err(|e| invalid(&e))?),
+            _ => return Ok(None),
+        }))
+    }
+}
+
+/// The BIP329 `ref` field
+impl fmt::Display for LabelRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LabelRef::Tx(txid) => write!(f, "{txid}"),
+            LabelRef::Address(address) => write!(f, "{address}"),
+            // Without the "[elements]" prefix of the `OutPoint` display
+            LabelRef::Output(outpoint) => write!(f, "{}:{}", outpoint.txid, outpoint.vout),
+        }
+    }
+}
+
+/// A line of a BIP329 export
+#[derive(Debug, Serialize, Deserialize)]
+struct Record {
+    #[serde(rename = "type")]
+    type_: String,
+
+    #[serde(rename = "ref")]
+    ref_: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+}
+
+/// Serialize the labels as BIP329 JSON lines
+pub(crate) fn to_jsonl<'a>(labels: impl IntoIterator<Item = (&'a LabelRef, &'a String)>) -> String {
+    let mut lines: Vec<String> = labels
+        .into_iter()
+        .map(|(item, label)| {
+            let record = Record {
+                type_: item.type_().to_string(),
+                ref_: item.to_string(),
+                label: Some(label.clone()),
+            };
+            serde_json::to_string(&record).expect("record contains only strings")
+        })
+        .collect();
+    lines.sort();
+    lines.join("\n")
+}
+
+/// Parse BIP329 JSON lines, skipping records of unsupported types and without a label
+pub(crate) fn from_jsonl(jsonl: &str) -> Result<Vec<(LabelRef, String)>, Error> {
+    let mut labels = vec![];
+    for (i, line) in jsonl.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: Record = serde_json::from_str(line)
+            .map_err(|e| Error::InvalidLabel(format!("line {}: {e}", i + 1)))?;
+        let item = LabelRef::from_type_ref(&record.type_, &record.ref_)?;
+        if let (Some(item), Some(label)) = (item, record.label) {
+            labels.push((item, label));
+        }
+    }
+    Ok(labels)
+}
+
+impl Encodable for LabelRef {
+    fn consensus_encode<W: std::io::Write>(
+        &self,
+        mut w: W,
+    ) -> Result<usize, elements::encode::Error> {
+        Ok(match self {
+            LabelRef::Tx(txid) => 0u8.consensus_encode(&mut w)? + txid.consensus_encode(&mut w)?,
+            LabelRef::Address(address) => {
+                1u8.consensus_encode(&mut w)?
+                    + address.to_string().into_bytes().consensus_encode(&mut w)?
+            }
+            LabelRef::Output(outpoint) => {
+                2u8.consensus_encode(&mut w)? + outpoint.consensus_encode(&mut w)?
+            }
+        })
+    }
+}
+
+impl Decodable for LabelRef {
+    fn consensus_decode<D: std::io::Read>(mut d: D) -> Result<Self, elements::encode::Error> {
+        Ok(match u8::consensus_decode(&mut d)? {
+            0 => LabelRef::Tx(Txid::consensus_decode(&mut d)?),
+            1 => {
+                let address = decode_string(&mut d)?;
+                let address = Address::from_str(&address)
+                    .map_err(|_| elements::encode::Error::ParseFailed("Invalid label address"))?;
+                LabelRef::Address(address)
+            }
+            2 => LabelRef::Output(OutPoint::consensus_decode(&mut d)?),
+            _ => return Err(elements::encode::Error::ParseFailed("Invalid label type")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bip329_roundtrip() {
+        let txid = "f91d0a8a78462bc59398f2c5d7a84fcff491c26ba54c4833478b202796c8aafd";
+        let address = "tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn";
+        let jsonl = format!(
+            r#"{{"type":"tx","ref":"{txid}","label":"Transaction","origin":"wpkh([d34db33f/84'/0'/0'])"}}
+{{"type":"addr","ref":"{address}","label":"Address"}}
+
+{{"type":"output","ref":"{txid}:1","label":"Output","spendable":false}}
+{{"type":"xpub","ref":"xpub661MyMwAqRbcF","label":"Unsupported type"}}
+{{"type":"tx","ref":"{txid}"}}"#
+        );
+        let labels = from_jsonl(&jsonl).unwrap();
+        assert_eq!(labels.len(), 3);
+        let txid = Txid::from_str(txid).unwrap();
+        assert_eq!(labels[0], (LabelRef::Tx(txid), "Transaction".to_string()));
+        assert_eq!(labels[2].0, LabelRef::Output(OutPoint::new(txid, 1)));
+
+        let map: std::collections::HashMap<_, _> = labels.iter().cloned().collect();
+        let exported = to_jsonl(&map);
+        assert_eq!(exported.lines().count(), 3);
+        let mut back = from_jsonl(&exported).unwrap();
+        back.sort_by_key(|(i, _)| i.type_());
+        let mut expected = labels.clone();
+        expected.sort_by_key(|(i, _)| i.type_());
+        assert_eq!(back, expected);
+
+        for (item, _) in labels {
+            let bytes = elements::encode::serialize(&item);
+            let decoded: LabelRef = elements::encode::deserialize(&bytes).unwrap();
+            assert_eq!(decoded, item);
+        }
+
+        let err = from_jsonl(r#"{"type":"tx","ref":"not a txid","label":"a"}"#).unwrap_err();
+        assert!(matches!(err, Error::InvalidLabel(_)));
+        let err = from_jsonl("not json").unwrap_err();
+        assert!(err.to_string().contains("line 1"));
+    }
+}
@@ -82,34 +82,58 @@
 //! # }
 //! ```
 
+mod backup;
 mod clients;
 mod config;
 mod descriptor;
 mod domain;
 mod error;
+mod fee;
+mod fee_provider;
+mod label;
+mod migrate;
 mod model;
+mod pegin;
+mod pegout;
 mod persister;
 mod pset_create;
 mod registry;
+mod reserves;
+#[cfg(feature = "sqlite")]
+mod sqlite_persister;
 mod store;
 mod tx_builder;
 mod update;
 mod util;
 mod wollet;
 
-pub use crate::clients::{BlockchainBackend, History};
+#[cfg(feature = "cloud_backup")]
+pub use crate::backup::{BackupClient, BACKUP_TIMEOUT};
+pub use crate::backup::{WalletBackup, BACKUP_FORMAT_VERSION};
+pub use crate::clients::{script_status, BlockRef, BlockchainBackend, History};
 pub use crate::config::ElementsNetwork;
-pub use crate::descriptor::{Chain, WolletDescriptor};
+pub use crate::descriptor::{Chain, DerivedScript, WolletDescriptor, MAX_SCRIPTS_RANGE};
 pub use crate::error::Error;
+pub use crate::fee::FeePolicy;
+pub use crate::fee_provider::{FeeProvider, FeeQuote};
+pub use crate::label::LabelRef;
+pub use crate::migrate::{MigrationPlan, MigrationProgress};
 pub use crate::model::{
-    AddressResult, IssuanceDetails, Recipient, UnvalidatedRecipient, WalletTx, WalletTxOut,
+    AddressDetails, AddressResult, AssetStats, Balance, CombinedPset, IssuanceDetails, Recipient,
+    ReissuanceToken, ReusedAddress, ServiceFee, TxDirection, TxFilter, TxPackageInfo, TxStatus,
+    UnvalidatedRecipient, WalletSummary, WalletTx, WalletTxOut, ZeroConfRisk, MAX_MEMO_OPRETURN,
 };
+pub use crate::pegin::{pegin_script_pubkey, tweak_fedpegscript, PeginAddress};
+pub use crate::pegout::pegout_script;
 pub use crate::persister::{FsPersister, NoPersist, PersistError, Persister};
-pub use crate::registry::{asset_ids, issuance_ids, Contract, Entity};
-pub use crate::tx_builder::{TxBuilder, WolletTxBuilder};
-pub use crate::update::{DownloadTxResult, Update};
+pub use crate::registry::{asset_ids, issuance_ids, AssetInfo, Contract, Entity};
+pub use crate::reserves::{challenge_outpoint, ReservesProof};
+pub use crate::store::MAX_GAP_LIMIT;
+pub use crate::tx_builder::{TxBuilder, Warning, WolletTxBuilder};
+pub use crate::update::{DownloadTxResult, Update, UPDATE_VERSION};
 pub use crate::util::EC;
 pub use crate::wollet::{Tip, Wollet};
+pub use lwk_common::{AssetAmount, CancellationToken};
 
 #[cfg(feature = "electrum")]
 pub use crate::wollet::full_scan_with_electrum_client;
@@ -119,6 +143,18 @@ pub use clients::electrum_client::{ElectrumClient, ElectrumOptions, ElectrumUrl}
 #[cfg(feature = "esplora")]
 pub use clients::esplora_client::EsploraClient;
 
+#[cfg(feature = "esplora")]
+pub use clients::update_client::{UpdateClient, SIGNATURE_HEADER, UPDATE_TIMEOUT};
+
+#[cfg(feature = "esplora")]
+pub use crate::registry::{fetch_asset_info, DOMAIN_PROOF_TIMEOUT};
+
+#[cfg(feature = "sqlite")]
+pub use crate::sqlite_persister::SqlitePersister;
+
+#[cfg(any(test, feature = "mock_backend"))]
+pub use clients::mock::MockBackend;
+
 #[cfg(feature = "esplora_wasm")]
 pub use clients::esplora_wasm_client::EsploraWasmClient;
 
@@ -0,0 +1,149 @@
+//! Key rotation
+//!
+//! Move all the funds of a wallet to a new wallet, for instance when the keys of the old wallet
+//! are compromised or when upgrading a legacy wallet.
+//! The funds are moved with sweep transactions spending a limited number of inputs each, so that
+//! signers with a maximum number of inputs per transaction can sign them.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::elements::pset::PartiallySignedTransaction;
+use crate::elements::OutPoint;
+use crate::error::Error;
+use crate::tx_builder::TxBuilder;
+use crate::wollet::Wollet;
+use crate::{WalletTxOut, WolletDescriptor};
+use lwk_common::amount::sum_by_asset;
+
+/// The sweep transactions moving all the funds of a wallet to a new wallet
+///
+/// Create it with [`Wollet::migrate_to()`], sign and broadcast every PSET, and follow the
+/// progress with [`Wollet::migration_progress()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationPlan {
+    /// The sweep transactions, they spend different UTXOs and can be broadcast in any order
+    pub psets: Vec<PartiallySignedTransaction>,
+}
+
+/// The progress of a [`MigrationPlan`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MigrationProgress {
+    /// The number of sweep transactions in the plan
+    pub total: usize,
+
+    /// The number of sweep transactions whose inputs have been spent
+    pub broadcast: usize,
+
+    /// The number of sweep transactions whose inputs have been spent by confirmed transactions
+    pub confirmed: usize,
+}
+
+impl MigrationProgress {
+    /// Whether all the sweep transactions are confirmed
+    pub fn is_complete(&self) -> bool {
+        self.confirmed == self.total
+    }
+}
+
+impl Wollet {
+    /// Plan the sweep transactions moving all the wallet funds to `new_descriptor`
+    ///
+    /// Every sweep spends at most `max_inputs` UTXOs, including the L-BTC UTXO paying its fee,
+    /// and sends all the funds to a single address of the new wallet. The sweep with index `i`
+    /// uses the external address with index `start_index + i` of the new wallet, pass the first
+    /// unused index of the new wallet to avoid reusing its addresses. Locked UTXOs are not moved.
+    ///
+    /// Moving funds between wallets of the same user, the sweeps do not pay the service fee set
+    /// with [`Wollet::set_service_fee()`].
+    ///
+    /// Returns [`Error::InsufficientFunds`] if there are not enough L-BTC UTXOs to pay the fee of
+    /// the sweeps moving the other assets.
+    pub fn migrate_to(
+        &self,
+        new_descriptor: &WolletDescriptor,
+        start_index: u32,
+        fee_rate: Option<f32>,
+        max_inputs: usize,
+    ) -> Result<MigrationPlan, Error> {
+        if max_inputs == 0 {
+            return Err(Error::Generic("max_inputs must be at least 1".to_string()));
+        }
+        let policy_asset = self.policy_asset();
+        // UTXOs are sorted by decreasing value
+        let (lbtc, assets): (Vec<_>, Vec<_>) = self
+            .utxos()?
+            .into_iter()
+            .filter(|u| !self.is_utxo_locked(&u.outpoint))
+            .partition(|u| u.unblinded.asset == policy_asset);
+        let mut lbtc = VecDeque::from(lbtc);
+        let mut assets = VecDeque::from(assets);
+
+        let mut psets = vec![];
+        while !lbtc.is_empty() || !assets.is_empty() {
+            // The largest L-BTC UTXO left pays the fee, then assets are moved first so that
+            // every following sweep still has an L-BTC UTXO
+            let fee_utxo = lbtc.pop_front().ok_or(Error::InsufficientFunds)?;
+            let mut inputs = vec![fee_utxo];
+            while inputs.len() < max_inputs {
+                match assets.pop_front().or_else(|| lbtc.pop_back()) {
+                    Some(utxo) => inputs.push(utxo),
+                    None => break,
+                }
+            }
+
+            let index = start_index
+                .checked_add(psets.len() as u32)
+                .ok_or(Error::Generic("address index overflow".to_string()))?;
+            let address = new_descriptor.address(index, self.config.address_params())?;
+            let amounts = sum_by_asset(
+                inputs
+                    .iter()
+                    .filter(|u| u.unblinded.asset != policy_asset)
+                    .map(WalletTxOut::amount),
+            )?;
+
+            let mut builder = TxBuilder::new(self.network())
+                .without_service_fee()
+                .set_wallet_utxos(inputs.iter().map(|u| u.outpoint).collect())
+                .drain_lbtc_to(address.clone())
+                .fee_rate(fee_rate);
+            for (asset, satoshi) in amounts {
+                builder = builder.add_recipient(&address, satoshi, asset)?;
+            }
+            psets.push(builder.finish(self)?);
+        }
+
+        Ok(MigrationPlan { psets })
+    }
+
+    /// The progress of a plan created with [`Wollet::migrate_to()`], according to the
+    /// transactions known by the wallet
+    pub fn migration_progress(&self, plan: &MigrationPlan) -> Result<MigrationProgress, Error> {
+        let mut spent_at: HashMap<OutPoint, Option<u32>> = HashMap::new();
+        for tx in self.transactions()? {
+            for input in tx.inputs.iter().flatten() {
+                spent_at.insert(input.outpoint, tx.height);
+            }
+        }
+
+        let mut progress = MigrationProgress {
+            total: plan.psets.len(),
+            broadcast: 0,
+            confirmed: 0,
+        };
+        for pset in plan.psets.iter() {
+            let heights: Vec<_> = pset
+                .inputs()
+                .iter()
+                .map(|i| spent_at.get(&OutPoint::new(i.previous_txid, i.previous_output_index)))
+                .collect();
+            if heights.iter().all(|h| h.is_some()) {
+                progress.broadcast += 1;
+            }
+            if heights.iter().all(|h| matches!(h, Some(Some(_)))) {
+                progress.confirmed += 1;
+            }
+        }
+        Ok(progress)
+    }
+}
@@ -1,10 +1,15 @@
+use crate::bitcoin::bip32::Fingerprint;
 use crate::descriptor::Chain;
+use crate::elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
+use crate::elements::opcodes::all::OP_RETURN;
+use crate::elements::pset::PartiallySignedTransaction;
+use crate::elements::script::{Builder, Instruction};
 use crate::elements::{Address, AssetId, OutPoint, Script, Transaction, TxOutSecrets, Txid};
 use crate::pset_create::validate_address;
 use crate::secp256k1::PublicKey;
-use crate::store::Timestamp;
-use crate::{ElementsNetwork, Error};
-use lwk_common::burn_script;
+use crate::store::{Height, Timestamp};
+use crate::{AssetInfo, ElementsNetwork, Error};
+use lwk_common::{burn_script, AssetAmount};
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fmt::Debug;
@@ -21,6 +26,121 @@ pub struct WalletTxOut {
     pub ext_int: Chain,
 }
 
+impl WalletTxOut {
+    /// The amount of the output
+    pub fn amount(&self) -> AssetAmount {
+        AssetAmount::new(self.unblinded.asset, self.unblinded.value)
+    }
+
+    /// Whether the output is explicit, i.e. its asset and value are not blinded
+    pub fn is_explicit(&self) -> bool {
+        self.unblinded.asset_bf == AssetBlindingFactor::zero()
+            && self.unblinded.value_bf == ValueBlindingFactor::zero()
+    }
+}
+
+/// Whether a wallet transaction is confirmed, waiting in the mempool or dropped from it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    /// Unconfirmed, the transaction is in the mempool
+    #[default]
+    Mempool,
+
+    /// Confirmed in the block at the given height
+    Confirmed(Height),
+
+    /// The transaction is no longer in the mempool, it has been evicted or, if `replaced_by`
+    /// is present, replaced by a wallet transaction spending some of the same inputs
+    Conflicted { replaced_by: Option<Txid> },
+}
+
+impl TxStatus {
+    pub(crate) fn from_height(height: Option<Height>) -> Self {
+        match height {
+            Some(height) => TxStatus::Confirmed(height),
+            None => TxStatus::Mempool,
+        }
+    }
+}
+
+/// The balance of an asset split by confirmation state, see [`crate::Wollet::detailed_balance()`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Balance {
+    /// Amount of the outputs created by confirmed transactions and not spent by confirmed
+    /// transactions
+    pub confirmed: u64,
+
+    /// Amount of the outputs created by unconfirmed transactions and not spent yet
+    pub unconfirmed_incoming: u64,
+
+    /// Amount of the outputs created by confirmed transactions and spent by unconfirmed
+    /// transactions
+    pub unconfirmed_outgoing: u64,
+}
+
+impl Balance {
+    /// The amount of the unspent outputs, as returned by [`crate::Wollet::balance()`]
+    pub fn total(&self) -> u64 {
+        (self.confirmed + self.unconfirmed_incoming).saturating_sub(self.unconfirmed_outgoing)
+    }
+}
+
+/// Whether a transaction moves funds into or out of the wallet, see [`TxFilter`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TxDirection {
+    /// The transaction does not decrease the wallet balance of any asset
+    Incoming,
+
+    /// The transaction decreases the wallet balance of at least one asset, for example by
+    /// sending funds or paying a fee
+    Outgoing,
+}
+
+/// Criteria to select the wallet transactions returned by
+/// [`crate::Wollet::transactions_paginated()`], the default selects all of them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TxFilter {
+    /// Only transactions changing the wallet balance of this asset
+    pub asset: Option<AssetId>,
+
+    /// Only transactions confirmed at this height or above, unconfirmed transactions included
+    pub min_height: Option<Height>,
+
+    /// Only transactions confirmed at this height or below, unconfirmed transactions excluded
+    pub max_height: Option<Height>,
+
+    /// Only transactions moving funds in this direction
+    pub direction: Option<TxDirection>,
+}
+
+impl TxFilter {
+    pub(crate) fn matches_height(&self, height: Option<Height>) -> bool {
+        match height {
+            Some(h) => {
+                self.min_height.map_or(true, |min| h >= min)
+                    && self.max_height.map_or(true, |max| h <= max)
+            }
+            None => self.max_height.is_none(),
+        }
+    }
+
+    pub(crate) fn matches_balance(&self, balance: &BTreeMap<AssetId, i64>) -> bool {
+        if let Some(asset) = self.asset.as_ref() {
+            if !balance.contains_key(asset) {
+                return false;
+            }
+        }
+        let outgoing = balance.values().any(|v| *v < 0);
+        match self.direction {
+            Some(TxDirection::Incoming) => !outgoing,
+            Some(TxDirection::Outgoing) => outgoing,
+            None => true,
+        }
+    }
+}
+
 /// Value returned by [`crate::Wollet::transactions()`] containing details about a transaction
 /// from the perspective of the wallet, for example the net-balance of the wallet.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -34,6 +154,164 @@ pub struct WalletTx {
     pub timestamp: Option<Timestamp>,
     pub inputs: Vec<Option<WalletTxOut>>,
     pub outputs: Vec<Option<WalletTxOut>>,
+
+    /// Heuristics on the risk of relying on this transaction, present only if unconfirmed
+    pub zero_conf: Option<ZeroConfRisk>,
+
+    /// Whether the output with the same index has been received on a wallet address that
+    /// already received funds in a previous transaction, see [`crate::Wollet::reused_addresses()`]
+    #[serde(default)]
+    pub address_reused: Vec<bool>,
+
+    /// Registry info of the assets in `balance`, if cached in the wallet,
+    /// see [`crate::Wollet::asset_info()`]
+    #[serde(default)]
+    pub assets: BTreeMap<AssetId, AssetInfo>,
+
+    /// Whether the transaction is confirmed, in the mempool or conflicted, see
+    /// [`crate::Wollet::conflicted_transactions()`]
+    #[serde(default)]
+    pub status: TxStatus,
+}
+
+/// A wallet address with its usage, see [`crate::Wollet::addresses()`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AddressDetails {
+    pub address: Address,
+    pub ext_int: Chain,
+    pub wildcard_index: u32,
+
+    /// Whether the address received funds
+    pub used: bool,
+
+    /// The number of wallet transactions paying to the address
+    pub tx_count: u32,
+
+    /// The amount of the unspent outputs of the address, by asset
+    pub balance: BTreeMap<AssetId, u64>,
+}
+
+/// A wallet address that received funds in more than one transaction
+///
+/// Reusing an address links the transactions together, hurting the privacy of the wallet and of
+/// its counterparties.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReusedAddress {
+    pub address: Address,
+    pub ext_int: Chain,
+    pub wildcard_index: u32,
+
+    /// The transactions receiving funds on the address, in order of confirmation with unconfirmed
+    /// transactions last
+    pub txids: Vec<Txid>,
+}
+
+/// Aggregate figures of the wallet activity, see [`crate::Wollet::summary()`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct WalletSummary {
+    /// Number of wallet transactions
+    pub txs: usize,
+
+    /// Number of unspent outputs, including the locked ones
+    pub utxos: usize,
+
+    /// Number of wallet addresses that received funds
+    pub used_addresses: usize,
+
+    /// Time of the block of the first confirmed wallet transaction
+    pub first_activity: Option<Timestamp>,
+
+    /// Time of the block of the last confirmed wallet transaction
+    pub last_activity: Option<Timestamp>,
+
+    /// Total amount received by the wallet for every asset
+    pub received: BTreeMap<AssetId, u64>,
+
+    /// Total amount sent by the wallet for every asset, fees included
+    pub sent: BTreeMap<AssetId, u64>,
+
+    /// Height of the last block known by the wallet
+    pub tip_height: Height,
+}
+
+/// The result of combining the PSETs of several signers, see
+/// [`crate::Wollet::combine_and_finalize()`]
+#[derive(Debug, Clone)]
+pub struct CombinedPset {
+    /// The combined PSET, not finalized
+    pub pset: PartiallySignedTransaction,
+
+    /// The final transaction, if all the inputs have enough signatures
+    pub tx: Option<Transaction>,
+
+    /// The inputs that cannot be finalized yet, with the fingerprints of the signers that did not
+    /// sign them
+    ///
+    /// With a multisig some of these signatures may be enough, inputs not owned by the wallet
+    /// have no fingerprints.
+    pub missing_signatures: BTreeMap<usize, Vec<Fingerprint>>,
+}
+
+/// Heuristics helping to decide whether to accept an unconfirmed incoming transaction
+///
+/// None of these values is a guarantee, they should be weighted against the amount received.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ZeroConfRisk {
+    /// Fee rate in satoshi per 1000 virtual bytes, rounded down
+    ///
+    /// Same unit of [`crate::TxBuilder::fee_rate()`], compare it with the current mempool fee rates
+    /// to estimate if the transaction could stay unconfirmed for long.
+    pub fee_rate: u64,
+
+    /// Whether any input signals replaceability, a replaceable transaction can be double spent
+    /// more easily
+    pub replaceable: bool,
+
+    /// The length of the longest chain of unconfirmed ancestors known by the wallet
+    ///
+    /// Ancestors not involving the wallet are not known and not counted.
+    pub unconfirmed_depth: u32,
+}
+
+/// Unconfirmed ancestors and descendants of a transaction among the wallet transactions
+///
+/// Returned by [`crate::Wollet::tx_package_info()`]. Miners consider a transaction together with
+/// its unconfirmed ancestors: if the ancestors fee rate is lower than the fee rate of the
+/// transaction, the ancestors are slowing it down; a new transaction spending one of its outputs
+/// (CPFP) with a high fee can raise the descendants fee rate and speed up the whole package.
+///
+/// Transactions not involving the wallet are not known and not counted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TxPackageInfo {
+    /// The unconfirmed transactions this transaction depends on
+    pub ancestors: Vec<Txid>,
+
+    /// The unconfirmed transactions depending on this transaction
+    pub descendants: Vec<Txid>,
+
+    /// Fee of the transaction and its ancestors
+    pub ancestors_fee: u64,
+
+    /// Virtual size of the transaction and its ancestors
+    pub ancestors_vsize: u64,
+
+    /// Fee of the transaction and its descendants
+    pub descendants_fee: u64,
+
+    /// Virtual size of the transaction and its descendants
+    pub descendants_vsize: u64,
+}
+
+impl TxPackageInfo {
+    /// Fee rate of the transaction and its ancestors, in satoshi per 1000 virtual bytes
+    pub fn ancestors_fee_rate(&self) -> u64 {
+        self.ancestors_fee * 1000 / self.ancestors_vsize.max(1)
+    }
+
+    /// Fee rate of the transaction and its descendants, in satoshi per 1000 virtual bytes
+    pub fn descendants_fee_rate(&self) -> u64 {
+        self.descendants_fee * 1000 / self.descendants_vsize.max(1)
+    }
 }
 
 /// A recipient of a transaction.
@@ -57,6 +335,11 @@ impl Recipient {
             asset,
         }
     }
+
+    /// The amount received by the recipient
+    pub fn amount(&self) -> AssetAmount {
+        AssetAmount::new(self.asset, self.satoshi)
+    }
 }
 
 /// A not-yet validated recipient of a transaction.
@@ -99,7 +382,8 @@ impl TryFrom<String> for UnvalidatedRecipient {
     type Error = crate::Error;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        let pieces: Vec<_> = value.split(':').collect();
+        // Split from the end, so that the address can contain ':', e.g. "contact:alice"
+        let pieces: Vec<_> = value.rsplitn(3, ':').collect();
         if pieces.len() != 3 {
             // TODO make specific error
             return Err(Error::Generic(format!(
@@ -109,8 +393,8 @@ impl TryFrom<String> for UnvalidatedRecipient {
         }
         Ok(UnvalidatedRecipient {
             satoshi: pieces[1].parse()?,
-            address: pieces[0].to_string(),
-            asset: pieces[2].to_string(),
+            address: pieces[2].to_string(),
+            asset: pieces[0].to_string(),
         })
     }
 }
@@ -186,6 +470,77 @@ pub struct IssuanceDetails {
     // token_blinder
 }
 
+/// Value returned from [`crate::Wollet::reissuance_tokens()`] containing details about a
+/// reissuance token owned by the wallet
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReissuanceToken {
+    /// The reissuance token id
+    pub token: AssetId,
+
+    /// The asset that can be reissued spending the token
+    pub asset: AssetId,
+
+    /// The amount of tokens owned by the wallet
+    pub satoshi: u64,
+}
+
+/// Statistics of an asset over the whole chain, returned by
+/// [`crate::BlockchainBackend::asset_stats()`]
+///
+/// For the policy asset the issuances are the peg-ins and the peg-outs are counted as burned.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AssetStats {
+    /// The asset
+    pub asset: AssetId,
+
+    /// Number of confirmed transactions involving the asset
+    pub tx_count: u64,
+
+    /// Number of issuances, the initial one included
+    pub issuance_count: u64,
+
+    /// Number of reissuances
+    pub reissuance_count: u64,
+
+    /// Total amount issued, none if the amount of some issuance is confidential
+    pub issued_amount: Option<u64>,
+
+    /// Total amount burned
+    pub burned_amount: u64,
+
+    /// Amount issued and not burned, none if the issued amount is unknown
+    pub circulating_amount: Option<u64>,
+}
+
+/// A fee paid to the application embedding the wallet, added to every transaction built with
+/// [`crate::TxBuilder`]
+///
+/// The fee is proportional to the L-BTC sent to the recipients, when draining L-BTC to an
+/// address the drained amount counts as sent. Transactions sending only other assets pay no
+/// service fee.
+///
+/// Set it with [`crate::Wollet::set_service_fee()`], the output is reported in
+/// [`lwk_common::PsetDetails::service_fee`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ServiceFee {
+    /// The address receiving the fee
+    pub address: Address,
+
+    /// The fee in basis points (hundredths of a percent) of the L-BTC sent to the recipients
+    pub basis_points: u32,
+
+    /// The maximum fee in satoshi
+    pub max_satoshi: u64,
+}
+
+impl ServiceFee {
+    /// The fee for a transaction sending `satoshi` L-BTC to the recipients
+    pub fn amount(&self, satoshi: u64) -> u64 {
+        let fee = satoshi as u128 * self.basis_points as u128 / 10_000;
+        fee.min(self.max_satoshi as u128) as u64
+    }
+}
+
 pub(crate) struct DisplayTxOutSecrets<'a>(&'a TxOutSecrets);
 impl<'a> std::fmt::Display for DisplayTxOutSecrets<'a> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -234,6 +589,45 @@ impl WalletTx {
             DisplayWalletTxInputOutputs(self)
         )
     }
+
+    /// The data of the OP_RETURN memo outputs of the transaction, as added by
+    /// [`crate::TxBuilder::add_memo_opreturn()`]
+    pub fn memos_opreturn(&self) -> Vec<Vec<u8>> {
+        self.tx
+            .output
+            .iter()
+            .filter_map(|o| memo_data(&o.script_pubkey))
+            .collect()
+    }
+}
+
+/// The maximum size in bytes of the data of an OP_RETURN memo, the largest relayed by default
+pub const MAX_MEMO_OPRETURN: usize = 80;
+
+/// The script of an output carrying `data` in an OP_RETURN
+pub(crate) fn memo_script(data: &[u8]) -> Script {
+    Builder::new()
+        .push_opcode(OP_RETURN)
+        .push_slice(data)
+        .into_script()
+}
+
+/// The data of a script made by [`memo_script()`], `None` for other scripts such as burns and
+/// peg-outs
+pub(crate) fn memo_data(script: &Script) -> Option<Vec<u8>> {
+    let mut instructions = script.instructions();
+    match (
+        instructions.next(),
+        instructions.next(),
+        instructions.next(),
+    ) {
+        (Some(Ok(Instruction::Op(op))), Some(Ok(Instruction::PushBytes(data))), None)
+            if op == OP_RETURN && !data.is_empty() =>
+        {
+            Some(data.to_vec())
+        }
+        _ => None,
+    }
 }
 
 #[cfg(test)]
@@ -257,4 +651,49 @@ mod tests {
             "https://blockstream.info/liquidtestnet/tx/c6e3187f028942973ad27224ca79baa8382e90ad686e927fc29896e8a2edf3f3#blinded=5000,38fca2d939696061a8f76d4e6b5eecd54e3b4221c846f24a6b279e79952850a5,ab9a42053c7a6ae0d55b774f3d462b1adfaa630e5d0f9b3c0f16640d55b8f6ab,6c5c2b44a0777e463d25eecb70adee84b316c2597b8a28108ffeea38c7acf45d"
         );
     }
+
+    #[test]
+    fn test_unvalidated_recipient_from_string() {
+        let r: UnvalidatedRecipient = "burn:1000:".to_string().try_into().unwrap();
+        assert_eq!(r.address, "burn");
+        assert_eq!(r.satoshi, 1000);
+        assert_eq!(r.asset, "");
+
+        let r: UnvalidatedRecipient = "contact:alice:1000:".to_string().try_into().unwrap();
+        assert_eq!(r.address, "contact:alice");
+        assert_eq!(r.satoshi, 1000);
+
+        let r: Result<UnvalidatedRecipient, _> = "burn:1000".to_string().try_into();
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn test_service_fee_amount() {
+        let address = Address::from_str("tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn").unwrap();
+        let fee = ServiceFee {
+            address,
+            basis_points: 50,
+            max_satoshi: 1_000,
+        };
+        assert_eq!(fee.amount(0), 0);
+        assert_eq!(fee.amount(10_000), 50);
+        assert_eq!(fee.amount(199), 0);
+        assert_eq!(fee.amount(1_000_000), 1_000);
+        assert_eq!(fee.amount(u64::MAX), 1_000);
+    }
+
+    #[test]
+    fn test_memo_script() {
+        let data = [7u8; MAX_MEMO_OPRETURN];
+        let script = memo_script(&data);
+        assert!(script.is_provably_unspendable());
+        assert_eq!(memo_data(&script), Some(data.to_vec()));
+
+        assert_eq!(memo_data(&burn_script()), None);
+        let script = crate::bitcoin::ScriptBuf::from_bytes(vec![0x51]);
+        let address = crate::bitcoin::Address::p2wsh(&script, crate::bitcoin::Network::Regtest);
+        let address = address.to_string().parse().unwrap();
+        let pegout = crate::pegout_script(ElementsNetwork::default_regtest(), &address).unwrap();
+        assert_eq!(memo_data(&pegout), None);
+    }
 }
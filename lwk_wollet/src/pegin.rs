@@ -0,0 +1,440 @@
+//! Peg-in of bitcoin from the mainchain
+//!
+//! Bitcoin sent to a peg-in address are locked by the federation and can be claimed on the
+//! sidechain as L-BTC. The peg-in address commits to a claim script, a script of the wallet, by
+//! tweaking the keys of the federation script (the fedpegscript) with it. The claim transaction
+//! proves the deposit with the mainchain transaction and its merkle proof, and it is signed like
+//! a spend of the claim script.
+
+use std::collections::HashMap;
+
+use crate::bitcoin::opcodes::all::{OP_DEPTH, OP_ELSE};
+use crate::bitcoin::{self, blockdata::constants::genesis_block, MerkleBlock};
+use crate::elements::confidential::{
+    Asset, AssetBlindingFactor, Nonce, Value, ValueBlindingFactor,
+};
+use crate::elements::pset::{Input, PartiallySignedTransaction};
+use crate::elements::{BlockHash, OutPoint, Script, TxOut, TxOutSecrets, Txid};
+use crate::hashes::{hmac, sha256, Hash, HashEngine};
+use crate::secp256k1::{PublicKey, Scalar};
+use crate::{Chain, ElementsNetwork, Error, Wollet, EC};
+use elements_miniscript::psbt::PsbtExt;
+
+/// A mainchain address to peg-in bitcoin to a wallet, see [`Wollet::pegin_address()`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeginAddress {
+    /// The mainchain address to send bitcoin to
+    pub mainchain_address: bitcoin::Address,
+
+    /// The wallet script committed by the address, which signs the claim
+    pub claim_script: Script,
+
+    /// The derivation index of the claim script in the external chain
+    pub index: u32,
+}
+
+/// A request to claim a peg-in, see [`crate::TxBuilder::claim_pegin()`]
+#[derive(Debug, Clone)]
+pub(crate) struct PeginRequest {
+    pub fedpegscript: bitcoin::ScriptBuf,
+    pub mainchain_tx: bitcoin::Transaction,
+    pub txout_proof: Vec<u8>,
+}
+
+impl ElementsNetwork {
+    /// The network of the mainchain pegged to this network
+    pub fn mainchain_network(&self) -> bitcoin::Network {
+        match self {
+            ElementsNetwork::Liquid => bitcoin::Network::Bitcoin,
+            ElementsNetwork::LiquidTestnet => bitcoin::Network::Testnet,
+            ElementsNetwork::ElementsRegtest { .. } => bitcoin::Network::Regtest,
+        }
+    }
+}
+
+/// Tweak the keys of `fedpegscript` with `claim_script`, like `calculate_contract` in elements
+///
+/// In the Liquid watchmen template, starting with `OP_DEPTH`, the emergency keys after `OP_ELSE`
+/// are not tweaked.
+pub fn tweak_fedpegscript(
+    fedpegscript: &bitcoin::Script,
+    claim_script: &Script,
+) -> Result<bitcoin::ScriptBuf, Error> {
+    let bytes = fedpegscript.as_bytes();
+    let watchmen = bytes.first() == Some(&OP_DEPTH.to_u8());
+    let mut tweaked = bytes.to_vec();
+    for instruction in fedpegscript.instruction_indices() {
+        let (idx, instruction) = instruction.map_err(|e| Error::InvalidPegin(e.to_string()))?;
+        match instruction {
+            bitcoin::script::Instruction::Op(OP_ELSE) if watchmen => break,
+            bitcoin::script::Instruction::PushBytes(data) if data.len() == 33 => {
+                let key = PublicKey::from_slice(data.as_bytes())?;
+                let mut engine = hmac::HmacEngine::<sha256::Hash>::new(data.as_bytes());
+                engine.input(claim_script.as_bytes());
+                let tweak = hmac::Hmac::from_engine(engine).to_byte_array();
+                let tweak = Scalar::from_be_bytes(tweak)
+                    .map_err(|_| Error::InvalidPegin("invalid key tweak".into()))?;
+                let key = key.add_exp_tweak(&EC, &tweak)?;
+                // The key is at the end of the push, after the opcode and the length
+                let start = idx + push_header_len(bytes[idx]);
+                tweaked[start..start + 33].copy_from_slice(&key.serialize());
+            }
+            _ => {}
+        }
+    }
+    Ok(bitcoin::ScriptBuf::from(tweaked))
+}
+
+/// The length of the opcode and the length prefix of a push starting with `opcode`
+fn push_header_len(opcode: u8) -> usize {
+    match opcode {
+        0x4c => 2,
+        0x4d => 3,
+        0x4e => 5,
+        _ => 1,
+    }
+}
+
+/// The mainchain script pubkey of the peg-in address committing to `claim_script`
+pub fn pegin_script_pubkey(
+    fedpegscript: &bitcoin::Script,
+    claim_script: &Script,
+) -> Result<bitcoin::ScriptBuf, Error> {
+    let tweaked = tweak_fedpegscript(fedpegscript, claim_script)?;
+    Ok(bitcoin::ScriptBuf::new_p2wsh(&tweaked.wscript_hash()).to_p2sh())
+}
+
+impl Wollet {
+    /// Get the mainchain address to peg-in bitcoin to the wallet address at `index`
+    ///
+    /// `fedpegscript` is the script of the federation, returned by `getsidechaininfo` of elementsd.
+    /// If `index` is `None` the last unused address is used, note that a deposit to a peg-in
+    /// address does not mark the address as used until it's claimed.
+    pub fn pegin_address(
+        &self,
+        fedpegscript: &bitcoin::Script,
+        index: Option<u32>,
+    ) -> Result<PeginAddress, Error> {
+        let address = self.address(index)?;
+        let claim_script = address.address().script_pubkey();
+        let tweaked = tweak_fedpegscript(fedpegscript, &claim_script)?;
+        let network = self.network().mainchain_network();
+        Ok(PeginAddress {
+            mainchain_address: bitcoin::Address::p2shwsh(&tweaked, network),
+            claim_script,
+            index: address.index(),
+        })
+    }
+
+    /// Find the output of `mainchain_tx` sent to a peg-in address of the wallet
+    ///
+    /// Peg-in addresses are searched up to a batch after the last unused address. Returns the
+    /// output index, the claim script and its derivation index.
+    fn pegin_output(
+        &self,
+        fedpegscript: &bitcoin::Script,
+        mainchain_tx: &bitcoin::Transaction,
+    ) -> Result<(usize, Script, u32), Error> {
+        let outputs: HashMap<&bitcoin::Script, usize> = mainchain_tx
+            .output
+            .iter()
+            .enumerate()
+            .map(|(vout, output)| (output.script_pubkey.as_script(), vout))
+            .collect();
+        let last_unused = self.address(None)?.index();
+        for index in 0..last_unused + self.gap_limit() {
+            let claim_script = self.address(Some(index))?.address().script_pubkey();
+            let script_pubkey = pegin_script_pubkey(fedpegscript, &claim_script)?;
+            if let Some(vout) = outputs.get(script_pubkey.as_script()) {
+                return Ok((*vout, claim_script, index));
+            }
+        }
+        Err(Error::PeginNotMine)
+    }
+
+    /// Add an input claiming the peg-in to `pset`, returning its value
+    ///
+    /// The input index is not flagged as peg-in, since the flag would be committed by the
+    /// signature hash, [`Wollet::finalize()`] sets it in the final transaction.
+    pub(crate) fn add_pegin(
+        &self,
+        pset: &mut PartiallySignedTransaction,
+        inp_txout_sec: &mut HashMap<usize, TxOutSecrets>,
+        inp_weight: &mut usize,
+        pegin: &PeginRequest,
+    ) -> Result<u64, Error> {
+        let txid = pegin.mainchain_tx.txid();
+        let merkle_block: MerkleBlock = bitcoin::consensus::deserialize(&pegin.txout_proof)
+            .map_err(|e| Error::InvalidPegin(e.to_string()))?;
+        let mut matches = vec![];
+        let mut indexes = vec![];
+        let root = merkle_block
+            .txn
+            .extract_matches(&mut matches, &mut indexes)
+            .map_err(|e| Error::InvalidPegin(e.to_string()))?;
+        if root != merkle_block.header.merkle_root || !matches.contains(&txid) {
+            return Err(Error::InvalidPegin(format!(
+                "the proof does not include transaction {txid}"
+            )));
+        }
+
+        let (vout, claim_script, index) =
+            self.pegin_output(&pegin.fedpegscript, &pegin.mainchain_tx)?;
+        let value = pegin.mainchain_tx.output[vout].value.to_sat();
+        let policy_asset = self.policy_asset();
+        let genesis_hash = genesis_block(self.network().mainchain_network()).block_hash();
+        // Witnesses are not committed by the txid, thus they are not needed to verify the proof
+        let mut mainchain_tx = pegin.mainchain_tx.clone();
+        for input in mainchain_tx.input.iter_mut() {
+            input.witness.clear();
+        }
+
+        let previous_txid = Txid::from_byte_array(txid.to_byte_array());
+        let mut input = Input::from_prevout(OutPoint::new(previous_txid, vout as u32));
+        input.witness_utxo = Some(TxOut {
+            asset: Asset::Explicit(policy_asset),
+            value: Value::Explicit(value),
+            nonce: Nonce::Null,
+            script_pubkey: claim_script.clone(),
+            witness: Default::default(),
+        });
+        input.pegin_witness = Some(vec![
+            bitcoin::consensus::serialize(&value),
+            elements::encode::serialize(&policy_asset),
+            bitcoin::consensus::serialize(&genesis_hash),
+            claim_script.to_bytes(),
+            bitcoin::consensus::serialize(&mainchain_tx),
+            pegin.txout_proof.clone(),
+        ]);
+        input.pegin_tx = Some(mainchain_tx);
+        input.pegin_txout_proof = Some(pegin.txout_proof.clone());
+        input.pegin_genesis_hash = Some(BlockHash::from_byte_array(genesis_hash.to_byte_array()));
+        input.pegin_claim_script = Some(claim_script.clone());
+        input.pegin_value = Some(value);
+
+        pset.add_input(input);
+        let idx = pset.inputs().len() - 1;
+        // The claim script could be not cached yet, thus the descriptor is derived by index
+        let desc = self
+            .wollet_descriptor()
+            .definite_descriptor(Chain::External, index)?;
+        pset.update_input_with_descriptor(idx, &desc)?;
+        let secrets = TxOutSecrets::new(
+            policy_asset,
+            AssetBlindingFactor::zero(),
+            value,
+            ValueBlindingFactor::zero(),
+        );
+        inp_txout_sec.insert(idx, secrets);
+        // The peg-in witness is already part of the transaction weight
+        *inp_weight += desc.max_weight_to_satisfy()?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::blockdata::script::Builder;
+    use crate::bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_CSV, OP_DROP, OP_IF};
+    use crate::bitcoin::opcodes::{OP_0, OP_TRUE};
+    use crate::bitcoin::{absolute, block, transaction, Amount, CompactTarget, TxMerkleNode};
+    use crate::store::BATCH_SIZE;
+    use crate::{NoPersist, WolletDescriptor};
+    use lwk_common::{singlesig_desc, DescriptorBlindingKey, Signer, Singlesig};
+    use lwk_signer::SwSigner;
+
+    fn key(i: u8) -> PublicKey {
+        let sk = crate::secp256k1::SecretKey::from_slice(&[i; 32]).unwrap();
+        sk.public_key(&EC)
+    }
+
+    fn multisig(keys: &[PublicKey]) -> bitcoin::ScriptBuf {
+        let mut builder = Builder::new().push_int(1);
+        for key in keys {
+            builder = builder.push_slice(key.serialize());
+        }
+        builder
+            .push_int(keys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    #[test]
+    fn test_tweak_fedpegscript() {
+        let claim_script = Script::from(vec![0x00, 0x14, 1, 2, 3]);
+        let other_claim_script = Script::from(vec![0x00, 0x14, 3, 2, 1]);
+
+        // Scripts without keys are not tweaked
+        let op_true = Builder::new().push_opcode(OP_TRUE).into_script();
+        assert_eq!(
+            tweak_fedpegscript(&op_true, &claim_script).unwrap(),
+            op_true
+        );
+
+        let keys = [key(1), key(2)];
+        let script = multisig(&keys);
+        let tweaked = tweak_fedpegscript(&script, &claim_script).unwrap();
+        assert_eq!(tweaked.len(), script.len());
+        assert_ne!(tweaked, script);
+        assert_ne!(
+            tweaked,
+            tweak_fedpegscript(&script, &other_claim_script).unwrap()
+        );
+        let tweak = |key: &PublicKey| {
+            let mut engine = hmac::HmacEngine::<sha256::Hash>::new(&key.serialize());
+            engine.input(claim_script.as_bytes());
+            let tweak = hmac::Hmac::from_engine(engine).to_byte_array();
+            key.add_exp_tweak(&EC, &Scalar::from_be_bytes(tweak).unwrap())
+                .unwrap()
+        };
+        assert_eq!(tweaked, multisig(&[tweak(&keys[0]), tweak(&keys[1])]));
+
+        // The emergency keys of the watchmen template are not tweaked
+        let watchmen = |keys: &[PublicKey]| {
+            Builder::new()
+                .push_opcode(OP_DEPTH)
+                .push_opcode(OP_0)
+                .push_opcode(OP_IF)
+                .push_slice(keys[0].serialize())
+                .push_opcode(OP_CHECKSIG)
+                .push_opcode(OP_ELSE)
+                .push_int(4032)
+                .push_opcode(OP_CSV)
+                .push_opcode(OP_DROP)
+                .push_slice(keys[1].serialize())
+                .push_opcode(OP_CHECKSIG)
+                .push_opcode(bitcoin::opcodes::all::OP_ENDIF)
+                .into_script()
+        };
+        let tweaked = tweak_fedpegscript(&watchmen(&keys), &claim_script).unwrap();
+        assert_eq!(tweaked, watchmen(&[tweak(&keys[0]), keys[1]]));
+    }
+
+    #[test]
+    fn test_claim_pegin() {
+        let network = ElementsNetwork::default_regtest();
+        let signer = SwSigner::new(lwk_test_util::TEST_MNEMONIC, false).unwrap();
+        let desc = singlesig_desc(
+            &signer,
+            Singlesig::Wpkh,
+            DescriptorBlindingKey::Slip77,
+            false,
+        )
+        .unwrap();
+        let desc: WolletDescriptor = desc.parse().unwrap();
+        let mut wollet = Wollet::new(network, NoPersist::new(), desc).unwrap();
+        // Make the wallet know its first scripts, like after a scan
+        let mut scripts = std::collections::HashMap::new();
+        for index in 0..BATCH_SIZE {
+            let address = wollet.address(Some(index)).unwrap();
+            let change = wollet.change(Some(index)).unwrap();
+            let child = index.into();
+            scripts.insert(address.address().script_pubkey(), (Chain::External, child));
+            scripts.insert(change.address().script_pubkey(), (Chain::Internal, child));
+        }
+        wollet
+            .apply_update(crate::Update {
+                new_txs: Default::default(),
+                txid_height_new: vec![],
+                txid_height_delete: vec![],
+                timestamps: vec![],
+                scripts,
+                tip: lwk_test_util::liquid_block_1().header,
+                utxo_locks: vec![],
+                utxo_unlocks: vec![],
+                labels: vec![],
+                assets: vec![],
+                scripts_status: vec![],
+            })
+            .unwrap();
+        let fedpegscript = multisig(&[key(1), key(2)]);
+
+        let pegin_address = wollet.pegin_address(&fedpegscript, Some(3)).unwrap();
+        assert_eq!(pegin_address.index, 3);
+        assert_eq!(
+            pegin_address.claim_script,
+            wollet.address(Some(3)).unwrap().address().script_pubkey()
+        );
+        assert!(pegin_address.mainchain_address.to_string().starts_with('2'));
+        assert_eq!(
+            pegin_address.mainchain_address.script_pubkey(),
+            pegin_script_pubkey(&fedpegscript, &pegin_address.claim_script).unwrap()
+        );
+
+        // A mainchain block with the deposit
+        let satoshi = 100_000;
+        let mainchain_tx = bitcoin::Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn::default()],
+            output: vec![bitcoin::TxOut {
+                value: Amount::from_sat(satoshi),
+                script_pubkey: pegin_address.mainchain_address.script_pubkey(),
+            }],
+        };
+        let header = block::Header {
+            version: block::Version::TWO,
+            prev_blockhash: bitcoin::BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::from_byte_array(mainchain_tx.txid().to_byte_array()),
+            time: 0,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        };
+        let txid = mainchain_tx.txid();
+        let proof = MerkleBlock::from_header_txids_with_predicate(&header, &[txid], |_| true);
+        let proof = bitcoin::consensus::serialize(&proof);
+
+        let err = wollet
+            .tx_builder()
+            .claim_pegin(
+                fedpegscript.clone(),
+                mainchain_tx.clone(),
+                proof[..80].to_vec(),
+            )
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidPegin(_)));
+        let other_fedpegscript = multisig(&[key(3)]);
+        let err = wollet
+            .tx_builder()
+            .claim_pegin(other_fedpegscript, mainchain_tx.clone(), proof.clone())
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::PeginNotMine));
+
+        let mut pset = wollet
+            .tx_builder()
+            .claim_pegin(fedpegscript, mainchain_tx, proof)
+            .finish()
+            .unwrap();
+        assert_eq!(pset.inputs().len(), 1);
+        assert_eq!(pset.inputs()[0].pegin_value, Some(satoshi));
+
+        // The claimed value is received by the wallet, minus the fee
+        let details = wollet.get_details(&pset).unwrap();
+        let fee = details.balance.fee;
+        let policy_asset = wollet.policy_asset();
+        assert_eq!(
+            details.balance.balances[&policy_asset],
+            (satoshi - fee) as i64
+        );
+
+        assert_eq!(signer.sign(&mut pset).unwrap(), 1);
+        let tx = wollet.finalize(&mut pset).unwrap();
+        assert!(tx.input[0].is_pegin());
+        let pegin = tx.input[0].pegin_data().unwrap();
+        assert_eq!(pegin.value, satoshi);
+        assert_eq!(pegin.asset, policy_asset);
+        assert_eq!(pegin.outpoint.txid, txid);
+        assert_eq!(
+            pegin.genesis_hash,
+            genesis_block(bitcoin::Network::Regtest).block_hash()
+        );
+
+        // The peg-in flag survives serialization
+        let tx: crate::elements::Transaction =
+            elements::encode::deserialize(&elements::encode::serialize(&tx)).unwrap();
+        assert!(tx.input[0].is_pegin());
+    }
+}
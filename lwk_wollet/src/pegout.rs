@@ -0,0 +1,100 @@
+//! Peg-out of L-BTC to the mainchain
+//!
+//! A peg-out output destroys L-BTC with an explicit `OP_RETURN` output committing to the genesis
+//! block of the mainchain and to the mainchain script receiving the bitcoin, which the federation
+//! pays once the transaction is confirmed.
+//!
+//! Networks enforcing a PAK (peg-out authorization keys) list, Liquid and Liquid Testnet, also
+//! require a proof appended to the output script. Producing it is not supported, so peg-outs are
+//! refused on those networks: without the proof the transaction is rejected or, worse, the L-BTC
+//! is destroyed without a mainchain payout.
+
+use crate::bitcoin::{self, address::NetworkUnchecked, blockdata::constants::genesis_block};
+use crate::elements::opcodes::all::OP_RETURN;
+use crate::elements::script::Builder;
+use crate::elements::Script;
+use crate::hashes::Hash;
+use crate::{ElementsNetwork, Error};
+
+/// The script of an output of `network` pegging out to `address` on its mainchain
+///
+/// Fails on networks enforcing PAK, see the [module documentation](self).
+pub fn pegout_script(
+    network: ElementsNetwork,
+    address: &bitcoin::Address<NetworkUnchecked>,
+) -> Result<Script, Error> {
+    if !matches!(network, ElementsNetwork::ElementsRegtest { .. }) {
+        return Err(Error::InvalidPegout(
+            "PAK proofs are not supported, peg-outs are possible only on regtest".to_string(),
+        ));
+    }
+    let mainchain_network = network.mainchain_network();
+    let address = address
+        .clone()
+        .require_network(mainchain_network)
+        .map_err(|_| {
+            Error::InvalidPegout(format!(
+                "address is not for the {mainchain_network} network"
+            ))
+        })?;
+    let genesis_hash = genesis_block(mainchain_network).block_hash();
+    Ok(Builder::new()
+        .push_opcode(OP_RETURN)
+        .push_slice(genesis_hash.as_byte_array())
+        .push_slice(address.script_pubkey().as_bytes())
+        .into_script())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::elements::confidential::{Asset, Nonce, Value};
+    use crate::elements::TxOut;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_pegout_script() {
+        let network = ElementsNetwork::default_regtest();
+        let address = |network| {
+            let script = bitcoin::ScriptBuf::from_bytes(vec![0x51]);
+            let address = bitcoin::Address::p2wsh(&script, network).to_string();
+            bitcoin::Address::from_str(&address).unwrap()
+        };
+        let regtest = address(bitcoin::Network::Regtest);
+        let script_pubkey = pegout_script(network, &regtest).unwrap();
+        let txout = TxOut {
+            asset: Asset::Explicit(network.policy_asset()),
+            value: Value::Explicit(10_000),
+            nonce: Nonce::Null,
+            script_pubkey,
+            witness: Default::default(),
+        };
+        let data = txout.pegout_data().unwrap();
+        assert_eq!(data.value, 10_000);
+        assert_eq!(
+            data.genesis_hash,
+            genesis_block(bitcoin::Network::Regtest).block_hash()
+        );
+        assert_eq!(
+            data.script_pubkey,
+            regtest.assume_checked_ref().script_pubkey()
+        );
+        assert!(data.extra_data.is_empty());
+
+        let err = pegout_script(network, &address(bitcoin::Network::Bitcoin)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid peg-out: address is not for the regtest network"
+        );
+
+        // Networks enforcing PAK
+        for (network, mainchain) in [
+            (ElementsNetwork::Liquid, bitcoin::Network::Bitcoin),
+            (ElementsNetwork::LiquidTestnet, bitcoin::Network::Testnet),
+        ] {
+            let err = pegout_script(network, &address(mainchain)).unwrap_err();
+            assert!(matches!(err, Error::InvalidPegout(_)));
+            assert!(err.to_string().contains("PAK"));
+        }
+    }
+}
@@ -9,6 +9,7 @@ use std::{
 
 use elements::{bitcoin::hashes::Hash, hashes::sha256t_hash_newtype};
 
+use crate::update::{decrypt, UPDATE_VERSION};
 use crate::{ElementsNetwork, Error, Update, WolletDescriptor};
 
 #[derive(thiserror::Error, Debug)]
@@ -19,6 +20,10 @@ pub enum PersistError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
+    #[cfg(feature = "sqlite")]
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+
     #[error("{0}")]
     Other(String),
 }
@@ -138,19 +143,41 @@ impl FsPersisterInner {
         let next = self.next.0;
         if index < next {
             let path = self.path(&Counter::from(index));
-            let bytes = fs::read(path)?;
-
-            Ok(Some(
-                Update::deserialize_decrypted(&bytes, &self.desc)
-                    .map_err(|e| PersistError::Other(e.to_string()))?,
-            ))
+            let bytes = fs::read(&path)?;
+            let (update, migrated) = decode_persisted(&bytes, &self.desc)?;
+            if let Some(ciphertext) = migrated {
+                fs::write(path, ciphertext)?;
+            }
+            Ok(Some(update))
         } else {
             Ok(None)
         }
     }
 }
 
-fn to_other<D: std::fmt::Debug>(d: D) -> PersistError {
+/// Decrypt and decode a persisted update
+///
+/// If the update was encoded by an older version, returns also the update encrypted with the
+/// current encoding, that should replace the persisted one so that the decoding of old versions
+/// can eventually be dropped.
+pub(crate) fn decode_persisted(
+    bytes: &[u8],
+    desc: &WolletDescriptor,
+) -> Result<(Update, Option<Vec<u8>>), PersistError> {
+    let to_persist_error = |e: Error| PersistError::Other(e.to_string());
+
+    let plaintext = decrypt(bytes, desc).map_err(to_persist_error)?;
+    let version = Update::version(&plaintext).map_err(to_persist_error)?;
+    let update = Update::deserialize(&plaintext)?;
+    let migrated = if version != UPDATE_VERSION {
+        Some(update.serialize_encrypted(desc).map_err(to_persist_error)?)
+    } else {
+        None
+    };
+    Ok((update, migrated))
+}
+
+pub(crate) fn to_other<D: std::fmt::Debug>(d: D) -> PersistError {
     PersistError::Other(format!("{d:?}"))
 }
 
@@ -234,6 +261,7 @@ mod test {
         sync::{Arc, Mutex},
     };
 
+    use crate::update::{decrypt, encrypt, UPDATE_VERSION};
     use crate::{ElementsNetwork, FsPersister, PersistError, Update, WolletDescriptor};
 
     use super::{Counter, NoPersist, Persister};
@@ -311,6 +339,99 @@ mod test {
         inner_test_persister(persister, false);
     }
 
+    #[test]
+    fn test_fs_persister_newer_version() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let desc = wollet_descriptor_test_vector();
+        let n = ElementsNetwork::LiquidTestnet;
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        let update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        persister.push(update).unwrap();
+
+        // Simulate an update written by a newer version of the library
+        let mut newer = lwk_test_util::update_test_vector_bytes();
+        newer[4] = UPDATE_VERSION + 1;
+        let path = persister.inner.lock().unwrap().path(&Counter::from(0));
+        std::fs::write(path, encrypt(newer, &desc).unwrap()).unwrap();
+
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        let err = persister.get(0).unwrap_err();
+        assert!(err.to_string().contains("newer version"));
+    }
+
+    #[test]
+    fn test_fs_persister_migrate_version() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let desc = wollet_descriptor_test_vector();
+        let n = ElementsNetwork::LiquidTestnet;
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        let update = Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        persister.push(update.clone()).unwrap();
+
+        // Simulate an update written with version 0
+        let v0 = lwk_test_util::update_test_vector_bytes();
+        assert_eq!(Update::version(&v0).unwrap(), 0);
+        let path = persister.inner.lock().unwrap().path(&Counter::from(0));
+        std::fs::write(&path, encrypt(v0, &desc).unwrap()).unwrap();
+
+        let persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        assert_eq!(persister.get(0).unwrap().unwrap(), update);
+
+        // The file has been rewritten with the current version
+        let plaintext = decrypt(&std::fs::read(&path).unwrap(), &desc).unwrap();
+        assert_eq!(Update::version(&plaintext).unwrap(), UPDATE_VERSION);
+        assert_eq!(Update::deserialize(&plaintext).unwrap(), update);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_persister() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("wollet.sqlite");
+        let desc = wollet_descriptor_test_vector();
+        let n = ElementsNetwork::LiquidTestnet;
+        let persister = crate::SqlitePersister::new(&path, n, &desc).unwrap();
+        inner_test_persister(persister, true);
+        let persister = crate::SqlitePersister::new(&path, n, &desc).unwrap();
+        inner_test_persister(persister, false);
+
+        // Another network is a different wallet
+        let n = ElementsNetwork::Liquid;
+        let persister = crate::SqlitePersister::new(&path, n, &desc).unwrap();
+        assert!(persister.get(0).unwrap().is_none());
+
+        // Consecutive tip only updates are coalesced
+        let mut tip_update =
+            Update::deserialize(&lwk_test_util::update_test_vector_bytes()).unwrap();
+        tip_update.new_txs = Default::default();
+        tip_update.txid_height_new.clear();
+        tip_update.txid_height_delete.clear();
+        tip_update.scripts.clear();
+        assert!(tip_update.only_tip());
+        persister.push(tip_update.clone()).unwrap();
+        persister.push(tip_update.clone()).unwrap();
+        assert!(persister.get(0).unwrap().is_some());
+        assert!(persister.get(1).unwrap().is_none());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn test_sqlite_persister_import_fs() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("wollet.sqlite");
+        let desc = wollet_descriptor_test_vector();
+        let n = ElementsNetwork::LiquidTestnet;
+        let fs_persister = FsPersister::new(&tempdir, n, &desc).unwrap();
+        inner_test_persister(fs_persister, true);
+
+        let persister = crate::SqlitePersister::new(path, n, &desc).unwrap();
+        assert_eq!(persister.import_fs(&tempdir).unwrap(), 2);
+        inner_test_persister(persister.clone(), false);
+
+        let err = persister.import_fs(&tempdir).unwrap_err();
+        assert!(err.to_string().contains("non-empty"));
+    }
+
     #[test]
     fn test_counter() {
         let c = Counter::default();
@@ -13,7 +13,7 @@ use elements::pset::elip100::AssetMetadata;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 // We make issuance and reissuance are mutually exclusive for simplicity
 pub enum IssuanceRequest {
     None,
@@ -22,11 +22,13 @@ pub enum IssuanceRequest {
 }
 
 impl Wollet {
+    /// The UTXOs of `asset` available for coin selection, locked UTXOs are excluded
     pub(crate) fn asset_utxos(&self, asset: &AssetId) -> Result<Vec<WalletTxOut>, Error> {
         Ok(self
             .utxos()?
             .into_iter()
             .filter(|utxo| &utxo.unblinded.asset == asset)
+            .filter(|utxo| !self.store.locked.contains(&utxo.outpoint))
             .collect())
     }
 
@@ -40,7 +42,7 @@ impl Wollet {
             .clone())
     }
 
-    fn get_txout(&self, outpoint: &OutPoint) -> Result<TxOut, Error> {
+    pub(crate) fn get_txout(&self, outpoint: &OutPoint) -> Result<TxOut, Error> {
         Ok(self
             .get_tx(&outpoint.txid)?
             .output
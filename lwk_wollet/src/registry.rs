@@ -1,10 +1,15 @@
 use std::str::FromStr;
 
 use crate::domain::verify_domain_name;
+use crate::elements::encode::{Decodable, Encodable};
 use crate::elements::hashes::{sha256, Hash};
 use crate::elements::{AssetId, ContractHash, OutPoint};
+#[cfg(feature = "esplora")]
+use crate::elements::{Transaction, Txid};
 use crate::error::Error;
-use crate::util::{serde_from_hex, serde_to_hex, verify_pubkey};
+use crate::util::{decode_string, serde_from_hex, serde_to_hex, verify_pubkey};
+#[cfg(feature = "esplora")]
+use crate::BlockchainBackend;
 use once_cell::sync::Lazy;
 use regex_lite::Regex;
 use serde::{Deserialize, Serialize};
@@ -82,6 +87,47 @@ impl Contract {
         let value = serde_json::to_value(self)?;
         contract_json_hash(&value)
     }
+
+    /// The URL where the issuer domain must serve the proof linking it to `asset_id`
+    pub fn domain_proof_url(&self, asset_id: &AssetId) -> String {
+        let domain = self.entity.domain();
+        format!("https://{domain}/.well-known/liquid-asset-proof-{asset_id}")
+    }
+
+    /// The content of the file the issuer domain must serve at [`Contract::domain_proof_url()`]
+    /// for the registry to accept `asset_id`
+    pub fn domain_proof(&self, asset_id: &AssetId) -> String {
+        let domain = self.entity.domain();
+        format!("Authorize linking the domain name {domain} to the Liquid asset {asset_id}")
+    }
+
+    /// Verify the issuer domain currently serves the expected proof for `asset_id`
+    ///
+    /// Useful to check the registry will accept the asset before publishing it.
+    /// The issuer domain must answer within [`DOMAIN_PROOF_TIMEOUT`].
+    #[cfg(feature = "esplora")]
+    pub fn verify_domain_proof(&self, asset_id: &AssetId) -> Result<(), Error> {
+        let url = self.domain_proof_url(asset_id);
+        verify_served_proof(&url, &self.domain_proof(asset_id), DOMAIN_PROOF_TIMEOUT)
+    }
+}
+
+/// Requests to the issuer domain not completed within this time fail
+#[cfg(feature = "esplora")]
+pub const DOMAIN_PROOF_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Verify `url` serves `proof`, failing if the server doesn't answer within `timeout`
+#[cfg(feature = "esplora")]
+fn verify_served_proof(url: &str, proof: &str, timeout: std::time::Duration) -> Result<(), Error> {
+    let client = reqwest::blocking::Client::builder()
+        .connect_timeout(timeout)
+        .timeout(timeout)
+        .build()?;
+    let response = client.get(url).send()?;
+    if !response.status().is_success() || response.text()?.trim() != proof {
+        return Err(Error::InvalidDomainProof(url.to_string()));
+    }
+    Ok(())
 }
 
 impl FromStr for Contract {
@@ -95,6 +141,180 @@ impl FromStr for Contract {
     }
 }
 
+/// Human-readable metadata of an asset, from the contract published in the asset registry
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AssetInfo {
+    pub name: String,
+    pub ticker: String,
+    pub precision: u8,
+    pub domain: String,
+
+    /// Whether the contract has been checked to commit to the asset id with the issuance
+    /// transaction, an unverified info could be spoofing the ticker of another asset
+    #[serde(default)]
+    pub verified: bool,
+}
+
+impl From<&Contract> for AssetInfo {
+    fn from(contract: &Contract) -> Self {
+        Self {
+            name: contract.name.clone(),
+            ticker: contract.ticker.clone(),
+            precision: contract.precision,
+            domain: contract.entity.domain().to_string(),
+            verified: false,
+        }
+    }
+}
+
+impl Encodable for AssetInfo {
+    fn consensus_encode<W: std::io::Write>(
+        &self,
+        mut w: W,
+    ) -> Result<usize, elements::encode::Error> {
+        let mut bytes_written = 0;
+        for s in [&self.name, &self.ticker, &self.domain] {
+            bytes_written += s.as_bytes().to_vec().consensus_encode(&mut w)?;
+        }
+        bytes_written += self.precision.consensus_encode(&mut w)?;
+        bytes_written += (self.verified as u8).consensus_encode(&mut w)?;
+        Ok(bytes_written)
+    }
+}
+
+impl AssetInfo {
+    /// Decode the info encoded before the verification was introduced, as unverified
+    pub(crate) fn consensus_decode_unverified<D: std::io::Read>(
+        mut d: D,
+    ) -> Result<Self, elements::encode::Error> {
+        Ok(Self {
+            name: decode_string(&mut d)?,
+            ticker: decode_string(&mut d)?,
+            domain: decode_string(&mut d)?,
+            precision: u8::consensus_decode(&mut d)?,
+            verified: false,
+        })
+    }
+}
+
+impl Decodable for AssetInfo {
+    fn consensus_decode<D: std::io::Read>(mut d: D) -> Result<Self, elements::encode::Error> {
+        let mut info = Self::consensus_decode_unverified(&mut d)?;
+        info.verified = match u8::consensus_decode(&mut d)? {
+            0 => false,
+            1 => true,
+            _ => {
+                return Err(elements::encode::Error::ParseFailed(
+                    "Invalid verified flag",
+                ))
+            }
+        };
+        Ok(info)
+    }
+}
+
+/// An entry of the asset registry
+#[cfg(feature = "esplora")]
+#[derive(Debug, Deserialize)]
+pub(crate) struct RegistryEntry {
+    contract: Contract,
+
+    /// The input of the issuance transaction, missing in registries not tracking it
+    #[serde(default)]
+    issuance_txin: Option<IssuanceTxin>,
+}
+
+#[cfg(feature = "esplora")]
+#[derive(Debug, Deserialize)]
+struct IssuanceTxin {
+    txid: Txid,
+    vin: u32,
+}
+
+#[cfg(feature = "esplora")]
+impl RegistryEntry {
+    /// The txid of the transaction issuing the asset
+    pub(crate) fn issuance_txid(&self) -> Option<Txid> {
+        self.issuance_txin.as_ref().map(|txin| txin.txid)
+    }
+
+    /// The info of `asset_id`, verified if the contract commits to it with the issuance input of
+    /// `issuance_tx`
+    ///
+    /// The info is not verified if the registry doesn't track the issuance input or if the
+    /// issuance transaction is missing. Fails if the issuance input doesn't issue `asset_id` with
+    /// the contract.
+    pub(crate) fn asset_info(
+        &self,
+        asset_id: &AssetId,
+        issuance_tx: Option<&Transaction>,
+    ) -> Result<AssetInfo, Error> {
+        let info = AssetInfo::from(&self.contract);
+        let (Some(issuance_txin), Some(tx)) = (self.issuance_txin.as_ref(), issuance_tx) else {
+            return Ok(info);
+        };
+        let (asset, _token) = Some(tx)
+            .filter(|tx| tx.txid() == issuance_txin.txid)
+            .and_then(|tx| tx.input.get(issuance_txin.vin as usize))
+            .filter(|txin| txin.has_issuance())
+            .ok_or(Error::ContractDoesNotCommitToAssetId)
+            .and_then(|txin| asset_ids(txin, &self.contract))?;
+        if asset != *asset_id {
+            return Err(Error::ContractDoesNotCommitToAssetId);
+        }
+        Ok(AssetInfo {
+            verified: true,
+            ..info
+        })
+    }
+}
+
+/// Fetch the entry of `asset_id` from the asset registry at `registry_url`, `None` if missing
+#[cfg(feature = "esplora")]
+pub(crate) fn fetch_registry_entry(
+    registry_url: &str,
+    asset_id: &AssetId,
+) -> Result<Option<RegistryEntry>, Error> {
+    let url = format!("{registry_url}{asset_id}");
+    tracing::debug!("fetching asset info {url}");
+    let response = reqwest::blocking::get(url)?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let entry: RegistryEntry = response.error_for_status()?.json()?;
+    entry.contract.validate()?;
+    Ok(Some(entry))
+}
+
+/// Fetch the info of `asset_id` from the asset registry at `registry_url`,
+/// like "https://assets.blockstream.info/", verifying it with the issuance transaction fetched
+/// with `client`
+///
+/// Returns `None` if the registry does not have the asset. Fails if the contract doesn't commit to
+/// the asset id with the issuance transaction. If the registry doesn't track the issuance
+/// transaction, or if it cannot be fetched, the info is not [`AssetInfo::verified`].
+#[cfg(feature = "esplora")]
+pub fn fetch_asset_info<B: BlockchainBackend + ?Sized>(
+    client: &B,
+    registry_url: &str,
+    asset_id: &AssetId,
+) -> Result<Option<AssetInfo>, Error> {
+    let Some(entry) = fetch_registry_entry(registry_url, asset_id)? else {
+        return Ok(None);
+    };
+    let issuance_tx = match entry.issuance_txid() {
+        Some(txid) => match client.get_transactions(&[txid]) {
+            Ok(mut txs) => txs.pop(),
+            Err(e) => {
+                tracing::debug!("cannot fetch the issuance of {asset_id}: {e}");
+                None
+            }
+        },
+        None => None,
+    };
+    Ok(Some(entry.asset_info(asset_id, issuance_tx.as_ref())?))
+}
+
 /// The asset id and reissuance token of the input
 ///
 /// Fails if they do not commit to the contract.
@@ -203,5 +423,94 @@ mod tests {
         // Error cases
         contract.version = 1;
         assert!(asset_ids(&tx.input[0], &contract).is_err());
+
+        // Domain proof
+        let asset = AssetId::from_str(asset_usdt).unwrap();
+        assert_eq!(
+            contract.domain_proof_url(&asset),
+            format!("https://tether.to/.well-known/liquid-asset-proof-{asset_usdt}")
+        );
+        assert_eq!(
+            contract.domain_proof(&asset),
+            format!("Authorize linking the domain name tether.to to the Liquid asset {asset_usdt}")
+        );
+
+        // Asset info
+        contract.version = 0;
+        let info = AssetInfo::from(&contract);
+        assert_eq!(info.ticker, "USDt");
+        assert_eq!(info.domain, "tether.to");
+        let bytes = elements::encode::serialize(&info);
+        assert_eq!(bytes.len(), (1 + 10) + (1 + 4) + (1 + 9) + 1 + 1);
+        let back: AssetInfo = elements::encode::deserialize(&bytes).unwrap();
+        assert_eq!(back, info);
+        let back = AssetInfo::consensus_decode_unverified(&bytes[..bytes.len() - 1]).unwrap();
+        assert_eq!(back, info);
+    }
+
+    #[cfg(feature = "esplora")]
+    #[test]
+    fn test_registry_entry() {
+        let contract_string = "{\"entity\":{\"domain\":\"tether.to\"},\"issuer_pubkey\":\"0337cceec0beea0232ebe14cba0197a9fbd45fcf2ec946749de920e71434c2b904\",\"name\":\"Tether USD\",\"precision\":8,\"ticker\":\"USDt\",\"version\":0}";
+        let tx_hex = include_str!("../tests/data/usdt-issuance-tx.hex");
+        let tx: elements::Transaction =
+            elements::encode::deserialize(&Vec::<u8>::from_hex(tx_hex).unwrap()).unwrap();
+        let asset_usdt = "ce091c998b83c78bb71a632313ba3760f1763d9cfcffae02258ffa9865a37bd2";
+        let token_usdt = "59fe4d2127ba9f16bd6850a3e6271a166e7ed2e1669f6c107d655791c94ee98f";
+        let asset = AssetId::from_str(asset_usdt).unwrap();
+
+        // Registry entry verified with the issuance transaction
+        let txid = tx.txid();
+        let entry = format!(
+            r#"{{"asset_id":"{asset_usdt}","contract":{contract_string},"issuance_txin":{{"txid":"{txid}","vin":0}}}}"#
+        );
+        let entry: RegistryEntry = serde_json::from_str(&entry).unwrap();
+        assert_eq!(entry.issuance_txid(), Some(txid));
+        let info = entry.asset_info(&asset, Some(&tx)).unwrap();
+        assert!(info.verified);
+        assert_eq!(info.ticker, "USDt");
+        let bytes = elements::encode::serialize(&info);
+        assert_eq!(
+            elements::encode::deserialize::<AssetInfo>(&bytes).unwrap(),
+            info
+        );
+
+        // Without the issuance transaction the info is not verified
+        assert!(!entry.asset_info(&asset, None).unwrap().verified);
+
+        // The same contract claimed by another asset
+        let other = AssetId::from_str(token_usdt).unwrap();
+        let err = entry.asset_info(&other, Some(&tx)).unwrap_err();
+        assert!(matches!(err, Error::ContractDoesNotCommitToAssetId));
+
+        // A contract not committed by the issuance
+        let spoofed = entry_with_ticker(contract_string, &txid, "USDT");
+        let err = spoofed.asset_info(&asset, Some(&tx)).unwrap_err();
+        assert!(matches!(err, Error::ContractDoesNotCommitToAssetId));
+
+        // Missing issuance input
+        let entry = format!(r#"{{"asset_id":"{asset_usdt}","contract":{contract_string}}}"#);
+        let entry: RegistryEntry = serde_json::from_str(&entry).unwrap();
+        assert_eq!(entry.issuance_txid(), None);
+        assert!(!entry.asset_info(&asset, Some(&tx)).unwrap().verified);
+    }
+
+    #[cfg(feature = "esplora")]
+    #[test]
+    fn test_verify_served_proof_timeout() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/proof", listener.local_addr().unwrap());
+        // The connection is accepted by the OS but never answered
+        let timeout = std::time::Duration::from_millis(200);
+        let err = verify_served_proof(&url, "proof", timeout).unwrap_err();
+        assert!(matches!(err, Error::Reqwest(e) if e.is_timeout()));
+        drop(listener);
+    }
+
+    fn entry_with_ticker(contract: &str, txid: &Txid, ticker: &str) -> RegistryEntry {
+        let contract = contract.replace("USDt", ticker);
+        let entry =
+            format!(r#"{{"contract":{contract},"issuance_txin":{{"txid":"{txid}","vin":0}}}}"#);
+        serde_json::from_str(&entry).unwrap()
     }
 }
@@ -0,0 +1,398 @@
+//! Proof of reserves
+//!
+//! A proof is an unspendable transaction spending the wallet UTXOs together with a challenge input
+//! committing to a message chosen by the auditor. The transaction can never be broadcast since the
+//! challenge input does not exist, but its signatures prove control of the spent UTXOs.
+//! The unblinding data of the UTXOs is shared alongside the transaction so that the auditor can
+//! verify the amounts per asset.
+
+use std::collections::{BTreeMap, HashSet};
+
+use crate::clients::BlockchainBackend;
+use crate::elements::confidential::{Asset, Value};
+use crate::elements::pset::{Input, Output, PartiallySignedTransaction};
+use crate::elements::{
+    AssetId, BlockHash, EcdsaSighashType, OutPoint, Transaction, TxOut, TxOutSecrets, Txid,
+};
+use crate::error::Error;
+use crate::hashes::Hash;
+use crate::util::EC;
+use crate::wollet::Wollet;
+use elements_miniscript::interpreter::{Interpreter, SatisfiedConstraint};
+use elements_miniscript::psbt::PsbtExt;
+use elements_miniscript::TxEnv;
+use lwk_common::amount::add_by_asset;
+use lwk_common::{burn_script, AssetAmount};
+use serde::{Deserialize, Serialize};
+
+const CHALLENGE_PREFIX: &str = "Proof-of-Reserves: ";
+
+/// The non-existing outpoint committing to the given message, spent by the first input of the proof
+pub fn challenge_outpoint(message: &str) -> OutPoint {
+    let txid = Txid::hash(format!("{CHALLENGE_PREFIX}{message}").as_bytes());
+    OutPoint::new(txid, 0)
+}
+
+/// A proof that the owner of some UTXOs is able to spend them
+///
+/// Create it with [`Wollet::reserves_proof_pset()`] and [`Wollet::reserves_proof()`],
+/// check it with [`ReservesProof::verify()`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReservesProof {
+    /// The message committed in the challenge input
+    pub message: String,
+
+    /// The unspendable transaction spending the challenge input followed by the UTXOs
+    pub tx: Transaction,
+
+    /// The unblinding data of the UTXOs, in the same order of the transaction inputs excluding the
+    /// challenge input
+    pub secrets: Vec<TxOutSecrets>,
+}
+
+impl Wollet {
+    /// Create a PSET for a proof of reserves committing to `message`
+    ///
+    /// If `utxos` is `None` all the wallet UTXOs are included in the proof.
+    ///
+    /// The PSET must be signed and then passed to [`Wollet::reserves_proof()`].
+    pub fn reserves_proof_pset(
+        &self,
+        message: &str,
+        utxos: Option<&[OutPoint]>,
+    ) -> Result<PartiallySignedTransaction, Error> {
+        let wallet_utxos = self.utxos()?;
+        let utxos = match utxos {
+            None => wallet_utxos.iter().map(|u| u.outpoint).collect(),
+            Some(utxos) => {
+                for outpoint in utxos {
+                    if !wallet_utxos.iter().any(|u| &u.outpoint == outpoint) {
+                        return Err(Error::UtxoNotMine(*outpoint));
+                    }
+                }
+                utxos.to_vec()
+            }
+        };
+        if utxos.is_empty() {
+            return Err(Error::InsufficientFunds);
+        }
+
+        let mut pset = PartiallySignedTransaction::new_v2();
+
+        let mut challenge = Input::from_prevout(challenge_outpoint(message));
+        challenge.witness_utxo = Some(TxOut::default());
+        pset.add_input(challenge);
+
+        for outpoint in utxos {
+            let mut input = Input::from_prevout(outpoint);
+            let mut txout = self.get_txout(&outpoint)?;
+            input.in_utxo_rangeproof = txout.witness.rangeproof.take();
+            input.witness_utxo = Some(txout);
+            pset.add_input(input);
+        }
+
+        pset.add_output(Output {
+            script_pubkey: burn_script(),
+            amount: Some(0),
+            asset: Some(self.policy_asset()),
+            ..Default::default()
+        });
+
+        self.add_details(&mut pset)?;
+        Ok(pset)
+    }
+
+    /// Create a proof of reserves from a PSET created with [`Wollet::reserves_proof_pset()`]
+    /// and signed by the wallet signers
+    pub fn reserves_proof(
+        &self,
+        message: &str,
+        pset: &PartiallySignedTransaction,
+    ) -> Result<ReservesProof, Error> {
+        let mut pset = pset.clone();
+        let inputs = pset.inputs().len();
+        if inputs < 2 || pset.inputs()[0].previous_txid != challenge_outpoint(message).txid {
+            return Err(Error::InvalidReservesProof(
+                "missing challenge input".to_string(),
+            ));
+        }
+
+        let mut secrets = vec![];
+        for idx in 1..inputs {
+            // genesis_hash is only used for BIP341 (taproot) sighash computation
+            pset.finalize_inp_mut(&EC, idx, BlockHash::all_zeros())?;
+            let input = &pset.inputs()[idx];
+            let outpoint = OutPoint::new(input.previous_txid, input.previous_output_index);
+            let unblinded = self
+                .store
+                .cache
+                .unblinded
+                .get(&outpoint)
+                .ok_or(Error::UtxoNotMine(outpoint))?;
+            secrets.push(*unblinded);
+        }
+
+        Ok(ReservesProof {
+            message: message.to_string(),
+            tx: pset.extract_tx()?,
+            secrets,
+        })
+    }
+}
+
+impl ReservesProof {
+    /// Verify the proof commits to `message` and spends UTXOs confirmed and currently unspent in
+    /// the chain accessed via `client`
+    ///
+    /// Returns the amount proven for every asset.
+    pub fn verify<B: BlockchainBackend>(
+        &self,
+        message: &str,
+        client: &B,
+    ) -> Result<BTreeMap<AssetId, u64>, Error> {
+        let invalid = |s: &str| Error::InvalidReservesProof(s.to_string());
+
+        if self.message != message {
+            return Err(invalid("unexpected message"));
+        }
+        let (challenge, inputs) = self
+            .tx
+            .input
+            .split_first()
+            .ok_or_else(|| invalid("missing inputs"))?;
+        if challenge.previous_output != challenge_outpoint(message) {
+            return Err(invalid("challenge input does not commit to the message"));
+        }
+        if inputs.is_empty() || inputs.len() != self.secrets.len() {
+            return Err(invalid("unexpected number of inputs"));
+        }
+        // A UTXO spent twice would be counted twice
+        let mut outpoints = HashSet::new();
+        if !self
+            .tx
+            .input
+            .iter()
+            .all(|i| outpoints.insert(i.previous_output))
+        {
+            return Err(invalid("an outpoint is spent more than once"));
+        }
+        if self.tx.output.len() != 1 || !self.tx.output[0].script_pubkey.is_provably_unspendable() {
+            return Err(invalid("transaction must have a single unspendable output"));
+        }
+
+        let txids: Vec<Txid> = inputs
+            .iter()
+            .map(|i| i.previous_output.txid)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let txs = client.get_transactions(&txids)?;
+        let mut spent_utxos = vec![TxOut::default()];
+        for input in inputs {
+            let txout = txs
+                .iter()
+                .find(|tx| tx.txid() == input.previous_output.txid)
+                .and_then(|tx| tx.output.get(input.previous_output.vout as usize))
+                .ok_or(Error::MissingTransaction)?;
+            spent_utxos.push(txout.clone());
+        }
+
+        let mut balances = BTreeMap::new();
+        for (i, (input, secrets)) in inputs.iter().zip(self.secrets.iter()).enumerate() {
+            let idx = i + 1;
+            let txout = &spent_utxos[idx];
+
+            let asset = match txout.asset {
+                Asset::Explicit(_) => Asset::Explicit(secrets.asset),
+                _ => Asset::new_confidential(&EC, secrets.asset, secrets.asset_bf),
+            };
+            let value = match (txout.value, asset.into_asset_gen(&EC)) {
+                (Value::Explicit(_), _) => Value::Explicit(secrets.value),
+                (_, Some(generator)) => {
+                    Value::new_confidential(&EC, secrets.value, generator, secrets.value_bf)
+                }
+                (_, None) => return Err(invalid("invalid asset")),
+            };
+            if (asset, value) != (txout.asset, txout.value) {
+                return Err(invalid("unblinding data does not match the commitments"));
+            }
+
+            let interpreter = Interpreter::from_txdata(
+                &txout.script_pubkey,
+                &input.script_sig,
+                &input.witness.script_witness,
+                input.sequence,
+                self.tx.lock_time,
+            )
+            .map_err(|e| Error::InvalidReservesProof(e.to_string()))?;
+            let txenv = TxEnv::new(&self.tx, &spent_utxos, idx)
+                .ok_or_else(|| invalid("unexpected number of inputs"))?;
+            let mut signed = false;
+            for constraint in interpreter.iter(&EC, &txenv, BlockHash::all_zeros()) {
+                let constraint =
+                    constraint.map_err(|e| Error::InvalidReservesProof(e.to_string()))?;
+                let key_sig = match constraint {
+                    SatisfiedConstraint::PublicKey { key_sig } => key_sig,
+                    SatisfiedConstraint::PublicKeyHash { key_sig, .. } => key_sig,
+                    _ => continue,
+                };
+                // The signature must commit to the challenge input
+                match key_sig.as_ecdsa() {
+                    Some((_, (_, EcdsaSighashType::All))) => signed = true,
+                    _ => return Err(invalid("signatures must use sighash all")),
+                }
+            }
+            if !signed {
+                return Err(invalid("input is not signed"));
+            }
+
+            add_by_asset(
+                &mut balances,
+                [AssetAmount::new(secrets.asset, secrets.value)],
+            )?;
+        }
+
+        let scripts: Vec<_> = spent_utxos[1..].iter().map(|t| &t.script_pubkey).collect();
+        let history = client.get_scripts_history(&scripts)?;
+
+        // Unconfirmed UTXOs could be double spent, they are not reserves
+        let confirmed: HashSet<Txid> = history
+            .iter()
+            .flatten()
+            .filter(|h| h.height > 0)
+            .map(|h| h.txid)
+            .collect();
+        if !inputs
+            .iter()
+            .all(|i| confirmed.contains(&i.previous_output.txid))
+        {
+            return Err(invalid("a proven utxo is not confirmed"));
+        }
+
+        // Check the UTXOs are still unspent
+        let history_txids: Vec<Txid> = history
+            .into_iter()
+            .flatten()
+            .map(|h| h.txid)
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        for tx in client.get_transactions(&history_txids)? {
+            if tx.input.iter().any(|i| {
+                inputs
+                    .iter()
+                    .any(|p| p.previous_output == i.previous_output)
+            }) {
+                return Err(invalid("a proven utxo has been spent"));
+            }
+        }
+
+        Ok(balances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clients::History;
+    use crate::elements::confidential::{AssetBlindingFactor, ValueBlindingFactor};
+    use crate::elements::TxIn;
+    use crate::wollet::tests::{new_wollet, receive_update};
+    use crate::MockBackend;
+    use lwk_common::Signer;
+    use lwk_signer::SwSigner;
+
+    #[test]
+    fn test_verify_confirmed_unspent() {
+        let message = "auditor nonce";
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let update = receive_update(&wollet, 10_000);
+        wollet.apply_update(update).unwrap();
+        let utxo = wollet.utxos().unwrap()[0].clone();
+        let funding = wollet.transaction(&utxo.outpoint.txid).unwrap().unwrap().tx;
+
+        let mut pset = wollet.reserves_proof_pset(message, None).unwrap();
+        let signer = SwSigner::new(lwk_test_util::TEST_MNEMONIC, false).unwrap();
+        signer.sign(&mut pset).unwrap();
+        let proof = wollet.reserves_proof(message, &pset).unwrap();
+
+        let history = |txid: Txid, height: i32| History {
+            txid,
+            height,
+            block_hash: None,
+            block_timestamp: None,
+        };
+        let script_pubkey = utxo.script_pubkey.clone();
+        let backend = |txs: Vec<Transaction>, history: Vec<History>| MockBackend {
+            txs,
+            histories: [(script_pubkey.clone(), history)].into(),
+            ..Default::default()
+        };
+
+        let client = backend(vec![funding.clone()], vec![history(funding.txid(), 1)]);
+        let balances = proof.verify(message, &client).unwrap();
+        assert_eq!(balances.get(&wollet.policy_asset()), Some(&10_000));
+
+        let client = backend(vec![funding.clone()], vec![history(funding.txid(), 0)]);
+        let err = proof.verify(message, &client).unwrap_err();
+        assert!(err.to_string().contains("not confirmed"), "{err}");
+
+        // The backend reports a transaction spending the proven UTXO
+        let spending = Transaction {
+            version: 2,
+            lock_time: elements::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: utxo.outpoint,
+                ..Default::default()
+            }],
+            output: vec![],
+        };
+        let client = backend(
+            vec![funding.clone(), spending.clone()],
+            vec![history(funding.txid(), 1), history(spending.txid(), 2)],
+        );
+        let err = proof.verify(message, &client).unwrap_err();
+        assert!(err.to_string().contains("has been spent"), "{err}");
+    }
+
+    #[test]
+    fn test_verify_duplicate_inputs() {
+        let message = "auditor nonce";
+        let input = |previous_output| TxIn {
+            previous_output,
+            ..Default::default()
+        };
+        let utxo = OutPoint::new(Txid::all_zeros(), 0);
+        let secrets = TxOutSecrets::new(
+            AssetId::default(),
+            AssetBlindingFactor::zero(),
+            1,
+            ValueBlindingFactor::zero(),
+        );
+        let proof = ReservesProof {
+            message: message.to_string(),
+            tx: Transaction {
+                version: 2,
+                lock_time: elements::LockTime::ZERO,
+                input: vec![input(challenge_outpoint(message)), input(utxo), input(utxo)],
+                output: vec![TxOut {
+                    script_pubkey: burn_script(),
+                    ..Default::default()
+                }],
+            },
+            secrets: vec![secrets; 2],
+        };
+
+        // Fails before any request to the backend
+        let client = MockBackend {
+            fail: true,
+            ..Default::default()
+        };
+        let err = proof.verify(message, &client).unwrap_err();
+        assert!(
+            matches!(&err, Error::InvalidReservesProof(e) if e.contains("more than once")),
+            "{err}"
+        );
+    }
+}
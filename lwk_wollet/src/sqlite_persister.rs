@@ -0,0 +1,178 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use elements::bitcoin::hashes::Hash;
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::persister::{decode_persisted, to_other, DirectoryIdHash};
+use crate::{
+    ElementsNetwork, Error, FsPersister, PersistError, Persister, Update, WolletDescriptor,
+};
+
+struct SqlitePersisterInner {
+    conn: Connection,
+
+    /// The network of the wallet
+    network: ElementsNetwork,
+
+    /// The identifier of the wallet in the database, one-way derived from the descriptor
+    wallet: String,
+
+    /// Next free position to write an update
+    next: usize,
+
+    /// used to create the cipher to encrypt data
+    desc: WolletDescriptor,
+}
+
+/// A SQLite persister that writes encrypted incremental updates
+///
+/// Every update is a row of the `updates` table, so that pushing an update does not rewrite the
+/// others. Multiple wallets can share the same database file.
+pub struct SqlitePersister {
+    inner: Mutex<SqlitePersisterInner>,
+}
+
+impl SqlitePersister {
+    /// Creates a persister of updates in the SQLite database at `path`, created if missing.
+    /// While being written updates are encrypted using a key derived from the given descriptor.
+    pub fn new<P: AsRef<Path>>(
+        path: P,
+        network: ElementsNetwork,
+        desc: &WolletDescriptor,
+    ) -> Result<Arc<Self>, Error> {
+        let conn = Connection::open(path).map_err(PersistError::from)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS updates (
+                network TEXT NOT NULL,
+                wallet TEXT NOT NULL,
+                idx INTEGER NOT NULL,
+                data BLOB NOT NULL,
+                PRIMARY KEY (network, wallet, idx)
+            )",
+            [],
+        )
+        .map_err(PersistError::from)?;
+        let wallet = DirectoryIdHash::hash(desc.to_string().as_bytes()).to_string();
+        let next: Option<i64> = conn
+            .query_row(
+                "SELECT MAX(idx) FROM updates WHERE network = ?1 AND wallet = ?2",
+                params![network.as_str(), wallet],
+                |row| row.get(0),
+            )
+            .map_err(PersistError::from)?;
+        let next = next.map_or(0, |last| last as usize + 1);
+
+        Ok(Arc::new(Self {
+            inner: Mutex::new(SqlitePersisterInner {
+                conn,
+                network,
+                wallet,
+                next,
+                desc: desc.clone(),
+            }),
+        }))
+    }
+
+    /// Copy the updates of the wallet persisted by a [`FsPersister`] in `datadir`
+    ///
+    /// Returns the number of copied updates. Fails if this persister already contains updates
+    /// of the wallet, the files in `datadir` are left untouched.
+    pub fn import_fs<P: AsRef<Path>>(&self, datadir: P) -> Result<usize, Error> {
+        let mut inner = self.inner.lock().map_err(to_other)?;
+        if inner.next != 0 {
+            return Err(Error::Generic(
+                "Cannot import into a non-empty sqlite persister".into(),
+            ));
+        }
+        let SqlitePersisterInner {
+            conn,
+            network,
+            wallet,
+            desc,
+            ..
+        } = &mut *inner;
+        let fs_persister = FsPersister::new(datadir, *network, desc)?;
+        let tx = conn.transaction().map_err(PersistError::from)?;
+        let mut count = 0;
+        while let Some(update) = fs_persister.get(count)? {
+            let ciphertext = update.serialize_encrypted(desc)?;
+            tx.execute(
+                "INSERT INTO updates (network, wallet, idx, data) VALUES (?1, ?2, ?3, ?4)",
+                params![network.as_str(), &*wallet, count as i64, ciphertext],
+            )
+            .map_err(PersistError::from)?;
+            count += 1;
+        }
+        tx.commit().map_err(PersistError::from)?;
+        inner.next = count;
+        Ok(count)
+    }
+}
+
+impl SqlitePersisterInner {
+    fn last(&self) -> Result<Option<Update>, PersistError> {
+        if self.next == 0 {
+            return Ok(None);
+        }
+        self.get(self.next - 1)
+    }
+
+    fn get(&self, index: usize) -> Result<Option<Update>, PersistError> {
+        let bytes: Option<Vec<u8>> = self
+            .conn
+            .query_row(
+                "SELECT data FROM updates WHERE network = ?1 AND wallet = ?2 AND idx = ?3",
+                params![self.network.as_str(), self.wallet, index as i64],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match bytes {
+            Some(bytes) => {
+                let (update, migrated) = decode_persisted(&bytes, &self.desc)?;
+                if let Some(ciphertext) = migrated {
+                    self.write(index, ciphertext)?;
+                }
+                Ok(Some(update))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn write(&self, index: usize, ciphertext: Vec<u8>) -> Result<(), PersistError> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO updates (network, wallet, idx, data) VALUES (?1, ?2, ?3, ?4)",
+            params![self.network.as_str(), self.wallet, index as i64, ciphertext],
+        )?;
+        Ok(())
+    }
+}
+
+impl Persister for SqlitePersister {
+    fn get(&self, index: usize) -> Result<Option<Update>, PersistError> {
+        let inner = self.inner.lock().map_err(to_other)?;
+        inner.get(index)
+    }
+
+    fn push(&self, update: Update) -> Result<(), PersistError> {
+        let mut inner = self.inner.lock().map_err(to_other)?;
+        if update.only_tip() {
+            if let Ok(Some(prev_update)) = inner.last() {
+                if prev_update.only_tip() {
+                    // since this update and the last are only an update of the tip, we can
+                    // overwrite last update instead of adding a new row.
+                    inner.next -= 1; // safety: next is at least 1 or last() would be None
+                }
+            }
+        }
+        let ciphertext = update
+            .serialize_encrypted(&inner.desc)
+            .map_err(|e| PersistError::Other(e.to_string()))?;
+
+        inner.write(inner.next, ciphertext)?;
+        inner.next += 1;
+        Ok(())
+    }
+}
@@ -1,14 +1,20 @@
 use crate::descriptor::Chain;
-use crate::elements::{BlockHash, OutPoint, Script, Transaction, TxOutSecrets, Txid};
+use crate::elements::{
+    AssetId, BlockHash, BlockHeader, OutPoint, Script, Transaction, TxOutSecrets, Txid,
+};
 use crate::hashes::Hash;
-use crate::Error;
+use crate::{AssetInfo, Error, LabelRef};
 use elements::bitcoin::bip32::ChildNumber;
 use elements_miniscript::{Descriptor, DescriptorPublicKey};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::atomic::{AtomicU32, Ordering};
 
+/// Default gap limit, the number of scripts derived together while scanning
 pub const BATCH_SIZE: u32 = 20;
+
+/// Maximum gap limit, so that scans don't derive and request an unbounded number of scripts
+pub const MAX_GAP_LIMIT: u32 = 1_000;
 pub type Height = u32;
 pub type Timestamp = u32;
 
@@ -31,6 +37,10 @@ pub struct RawCache {
     /// unblinded values
     pub unblinded: HashMap<OutPoint, TxOutSecrets>,
 
+    /// outputs on my scripts that cannot be unblinded, not to try again at every scan
+    #[serde(default)]
+    pub unblind_failures: HashSet<OutPoint>,
+
     /// height and hash of tip of the blockchain
     pub tip: (Height, BlockHash),
 
@@ -42,6 +52,15 @@ pub struct RawCache {
 
     /// last unused index for internal addresses (changes) for current descriptor
     pub last_unused_internal: AtomicU32,
+
+    /// status of my scripts with history at the last scan, as defined by the electrum protocol
+    #[serde(default)]
+    pub scripts_status: HashMap<Script, [u8; 32]>,
+
+    /// wallet txs dropped from the history, evicted from the mempool or replaced by the tx in
+    /// the value
+    #[serde(default)]
+    pub conflicts: HashMap<Txid, Option<Txid>>,
 }
 
 impl Default for RawCache {
@@ -52,10 +71,13 @@ impl Default for RawCache {
             scripts: HashMap::default(),
             heights: HashMap::default(),
             unblinded: HashMap::default(),
+            unblind_failures: HashSet::default(),
             tip: (0, BlockHash::all_zeros()),
             last_unused_internal: 0.into(),
             last_unused_external: 0.into(),
             timestamps: HashMap::default(),
+            scripts_status: HashMap::default(),
+            conflicts: HashMap::default(),
         }
     }
 }
@@ -82,6 +104,13 @@ impl std::hash::Hash for RawCache {
         vec.sort_by_key(|kv| kv.0);
         vec.hash(state);
 
+        // Keep the status of wallets without failures unchanged
+        if !self.unblind_failures.is_empty() {
+            let mut vec: Vec<_> = self.unblind_failures.iter().collect();
+            vec.sort();
+            vec.hash(state);
+        }
+
         self.tip.hash(state);
 
         let mut vec: Vec<_> = self.timestamps.iter().collect();
@@ -95,12 +124,66 @@ impl std::hash::Hash for RawCache {
         self.last_unused_internal
             .load(Ordering::Relaxed)
             .hash(state);
+
+        // Keep the status of wallets scanned before the scripts status was stored unchanged
+        if !self.scripts_status.is_empty() {
+            let mut vec: Vec<_> = self.scripts_status.iter().collect();
+            vec.sort();
+            vec.hash(state);
+        }
+
+        // Keep the status of wallets without conflicts unchanged
+        if !self.conflicts.is_empty() {
+            let mut vec: Vec<_> = self.conflicts.iter().collect();
+            vec.sort();
+            vec.hash(state);
+        }
     }
 }
 
-#[derive(Default, Hash)]
+#[derive(Default)]
 pub struct Store {
     pub cache: RawCache,
+
+    /// UTXOs excluded from coin selection, not reconstructable from the blockchain
+    pub locked: BTreeSet<OutPoint>,
+
+    /// Labels set by the user, not reconstructable from the blockchain
+    pub labels: HashMap<LabelRef, String>,
+
+    /// Registry info of the assets, cached to avoid fetching it again
+    pub assets: HashMap<AssetId, AssetInfo>,
+
+    /// Header of the tip of the last update applied, used by updates created locally
+    pub(crate) tip_header: Option<BlockHeader>,
+}
+
+impl std::hash::Hash for Store {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.cache.hash(state);
+        // Keep the status of wallets without locks, labels and assets info unchanged, the tip
+        // header is already in the cache as block hash
+        if !self.locked.is_empty() {
+            self.locked.hash(state);
+        }
+        if !self.labels.is_empty() {
+            let mut vec: Vec<_> = self
+                .labels
+                .iter()
+                .map(|(item, label)| (item.type_(), item.to_string(), label))
+                .collect();
+            vec.sort();
+            vec.hash(state);
+        }
+        if !self.assets.is_empty() {
+            let mut vec: Vec<_> = self.assets.iter().collect();
+            vec.sort_by_key(|(asset, _)| *asset);
+            for (asset, info) in vec {
+                asset.hash(state);
+                elements::encode::serialize(info).hash(state);
+            }
+        }
+    }
 }
 
 #[derive(Default, Debug)]
@@ -113,6 +196,7 @@ impl Store {
     pub fn get_script_batch(
         &self,
         batch: u32,
+        batch_size: u32,
         descriptor: &Descriptor<DescriptorPublicKey>, // non confidential (we need only script_pubkey), non multipath (we need to be able to derive with index)
     ) -> Result<ScriptBatch, Error> {
         let mut result = ScriptBatch {
@@ -120,8 +204,14 @@ impl Store {
             ..Default::default()
         };
 
-        let start = batch * BATCH_SIZE;
-        let end = start + BATCH_SIZE;
+        let (start, end) = batch
+            .checked_mul(batch_size)
+            .and_then(|start| Some((start, start.checked_add(batch_size)?)))
+            .ok_or_else(|| {
+                Error::Generic(format!(
+                    "script batch {batch} of size {batch_size} out of range"
+                ))
+            })?;
         let ext_int: Chain = descriptor.try_into().unwrap_or(Chain::External);
         for j in start..end {
             let child = ChildNumber::from_normal_idx(j)?;
@@ -156,11 +246,65 @@ impl Store {
         Ok(self
             .cache
             .all_txs
-            .values()
-            .flat_map(|tx| tx.input.iter())
+            .iter()
+            .filter(|(txid, _)| !self.cache.conflicts.contains_key(*txid))
+            .flat_map(|(_, tx)| tx.input.iter())
             .map(|i| i.previous_output)
             .collect())
     }
+
+    /// Set the wallet tx replacing the conflicted txs without one, if any spends their inputs
+    pub(crate) fn update_replacements(&mut self) {
+        if self.cache.conflicts.values().all(Option::is_some) {
+            return;
+        }
+        let mut spending = HashMap::new();
+        for txid in self.cache.heights.keys() {
+            if let Some(tx) = self.cache.all_txs.get(txid) {
+                for input in tx.input.iter() {
+                    spending.insert(input.previous_output, *txid);
+                }
+            }
+        }
+        for (txid, replaced_by) in self.cache.conflicts.iter_mut() {
+            if replaced_by.is_none() {
+                *replaced_by = self
+                    .cache
+                    .all_txs
+                    .get(txid)
+                    .and_then(|tx| {
+                        tx.input
+                            .iter()
+                            .find_map(|i| spending.get(&i.previous_output))
+                    })
+                    .cloned();
+            }
+        }
+    }
+
+    /// Drop the conflicted txs whose inputs are spent by a confirmed tx, they can't be
+    /// broadcast again
+    pub(crate) fn prune_conflicts(&mut self) {
+        if self.cache.conflicts.is_empty() {
+            return;
+        }
+        let confirmed_spent: HashSet<OutPoint> = self
+            .cache
+            .heights
+            .iter()
+            .filter(|(_, height)| height.is_some())
+            .filter_map(|(txid, _)| self.cache.all_txs.get(txid))
+            .flat_map(|tx| tx.input.iter().map(|i| i.previous_output))
+            .collect();
+        let all_txs = &self.cache.all_txs;
+        self.cache.conflicts.retain(|txid, _| {
+            all_txs.get(txid).map_or(true, |tx| {
+                !tx.input
+                    .iter()
+                    .any(|i| confirmed_spent.contains(&i.previous_output))
+            })
+        });
+    }
 }
 
 #[cfg(test)]
@@ -195,10 +339,14 @@ mod tests {
         let store = Store::default();
 
         let x = store
-            .get_script_batch(0, &desc.as_ref().descriptor)
+            .get_script_batch(0, super::BATCH_SIZE, &desc.as_ref().descriptor)
             .unwrap();
         assert_eq!(format!("{:?}", x.value[0]), "(Script(OP_0 OP_PUSHBYTES_20 d11ef9e68385138627b09d52d6fe12662d049224), (External, Normal { index: 0 }))");
         assert_ne!(x.value[0], x.value[1]);
+
+        let descriptor = &desc.as_ref().descriptor;
+        assert!(store.get_script_batch(u32::MAX, 2, descriptor).is_err());
+        assert!(store.get_script_batch(1, u32::MAX, descriptor).is_err());
     }
 
     #[test]
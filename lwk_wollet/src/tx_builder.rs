@@ -1,19 +1,29 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Display;
 
 use elements::{
     confidential::Value,
     issuance::ContractHash,
     pset::{Output, PartiallySignedTransaction},
     secp256k1_zkp::ZERO_TWEAK,
-    Address, AssetId, Script, Transaction,
+    Address, AssetId, LockTime, OutPoint, Script, Sequence, Transaction, TxOutSecrets,
 };
-use rand::thread_rng;
+use rand::{seq::SliceRandom, thread_rng, CryptoRng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
-    hashes::Hash,
-    model::{IssuanceDetails, Recipient},
+    bitcoin::{self, address::NetworkUnchecked},
+    hashes::{
+        hmac::{Hmac, HmacEngine},
+        sha256, Hash, HashEngine,
+    },
+    model::{memo_data, memo_script, IssuanceDetails, Recipient, WalletTxOut, MAX_MEMO_OPRETURN},
+    pegin::PeginRequest,
+    pegout::pegout_script,
     pset_create::{validate_address, IssuanceRequest},
-    Contract, ElementsNetwork, Error, UnvalidatedRecipient, Wollet, EC,
+    AssetAmount, Chain, Contract, ElementsNetwork, Error, FeePolicy, FeeProvider,
+    UnvalidatedRecipient, Wollet, EC,
 };
 
 pub fn extract_issuances(tx: &Transaction) -> Vec<IssuanceDetails> {
@@ -50,6 +60,47 @@ pub fn extract_issuances(tx: &Transaction) -> Vec<IssuanceDetails> {
     r
 }
 
+/// L-BTC change below this amount is not worth the fee to spend it and is added to the fee
+const DUST_CHANGE: u64 = 546;
+
+/// Fee rates in sats/kvB above ten times the minimum relay fee are considered unintended
+const HIGH_FEE_RATE: f32 = 1000.0;
+
+/// An advisory about a built transaction, which is nevertheless valid
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// The L-BTC change of `satoshi` was too small for an output and was added to the fee
+    DustChangeFolded { satoshi: u64 },
+
+    /// The output paying `script_pubkey` is not confidential, its amount and asset are public
+    UnconfidentialRecipient { script_pubkey: Script },
+
+    /// The fee rate in sats/kvB is higher than usual
+    HighFeeRate { fee_rate: f32 },
+
+    /// The `script_pubkey` already received funds in transactions of the wallet
+    ReusedAddress { script_pubkey: Script },
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::DustChangeFolded { satoshi } => {
+                write!(f, "Change of {satoshi} sats added to the fee")
+            }
+            Warning::UnconfidentialRecipient { script_pubkey } => {
+                write!(f, "Output paying {script_pubkey:x} is not confidential")
+            }
+            Warning::HighFeeRate { fee_rate } => {
+                write!(f, "High fee rate of {fee_rate} sats/kvB")
+            }
+            Warning::ReusedAddress { script_pubkey } => {
+                write!(f, "Script {script_pubkey:x} already received funds")
+            }
+        }
+    }
+}
+
 /// A transaction builder
 ///
 /// See [`WolletTxBuilder`] for usage from rust.
@@ -60,7 +111,7 @@ pub fn extract_issuances(tx: &Transaction) -> Vec<IssuanceDetails> {
 /// of the wallet in the finish methods because this it more friendly for bindings implementation.
 /// Moreover, we could have an alternative finish which don't use a wallet at all.
 /// * We are consuming and returning self to build the tx with method chaining
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TxBuilder {
     network: ElementsNetwork,
     recipients: Vec<Recipient>,
@@ -68,6 +119,16 @@ pub struct TxBuilder {
     issuance_request: IssuanceRequest,
     drain_lbtc: bool,
     drain_to: Option<Address>,
+    drain_assets: HashMap<AssetId, Address>,
+    paranoid: bool,
+    unblinded: bool,
+    wallet_utxos: Option<Vec<OutPoint>>,
+    pegins: Vec<PeginRequest>,
+    locktime: Option<LockTime>,
+    replaced_scripts: Vec<Script>,
+    fee_provider: Option<FeeProvider>,
+    service_fee: bool,
+    internal_scripts: Vec<Script>,
 }
 
 impl TxBuilder {
@@ -80,6 +141,16 @@ impl TxBuilder {
             issuance_request: IssuanceRequest::None,
             drain_lbtc: false,
             drain_to: None,
+            drain_assets: HashMap::new(),
+            paranoid: false,
+            unblinded: false,
+            wallet_utxos: None,
+            pegins: vec![],
+            locktime: None,
+            replaced_scripts: vec![],
+            fee_provider: None,
+            service_fee: true,
+            internal_scripts: vec![],
         }
     }
 
@@ -130,6 +201,24 @@ impl TxBuilder {
         Ok(self)
     }
 
+    /// Add a recipient receiving on the first unused address of `account`
+    ///
+    /// Use it to move funds to another wallet of the same user, for instance another account of
+    /// the same signer. The transaction is classified as a self-transfer by
+    /// [`Wollet::transactions_with_accounts()`]. The amount sent is not charged the service fee
+    /// set with [`Wollet::set_service_fee()`].
+    pub fn add_internal_recipient(
+        mut self,
+        account: &Wollet,
+        satoshi: u64,
+        asset_id: AssetId,
+    ) -> Result<Self, Error> {
+        let address = account.address(None)?;
+        self.internal_scripts
+            .push(address.address().script_pubkey());
+        self.add_recipient(address.address(), satoshi, asset_id)
+    }
+
     /// Add L-BTC recipient to the internal list
     pub fn add_lbtc_recipient(self, address: &Address, satoshi: u64) -> Result<Self, Error> {
         let rec = UnvalidatedRecipient::lbtc(address.to_string(), satoshi);
@@ -142,6 +231,54 @@ impl TxBuilder {
         self.add_unvalidated_recipient(&rec)
     }
 
+    /// Add an output pegging out `satoshi` of L-BTC to `address` on the mainchain
+    ///
+    /// See [`crate::pegout_script()`] for the limitations.
+    pub fn add_pegout(
+        mut self,
+        address: &bitcoin::Address<NetworkUnchecked>,
+        satoshi: u64,
+    ) -> Result<Self, Error> {
+        if satoshi == 0 {
+            return Err(Error::InvalidAmount);
+        }
+        self.recipients.push(Recipient {
+            satoshi,
+            script_pubkey: pegout_script(self.network(), address)?,
+            blinding_pubkey: None,
+            asset: self.network().policy_asset(),
+        });
+        Ok(self)
+    }
+
+    /// Add an output without value carrying `data` in an OP_RETURN, for instance an order
+    /// identifier, which the recipient finds with [`crate::WalletTx::memos_opreturn()`]
+    ///
+    /// `data` must have from 1 to [`MAX_MEMO_OPRETURN`] bytes. Only one memo per transaction is
+    /// allowed, since transactions with more OP_RETURN outputs are not relayed by default.
+    pub fn add_memo_opreturn(mut self, data: &[u8]) -> Result<Self, Error> {
+        if data.is_empty() || data.len() > MAX_MEMO_OPRETURN {
+            return Err(Error::InvalidMemo(format!(
+                "{} bytes, it must have from 1 to {MAX_MEMO_OPRETURN}",
+                data.len()
+            )));
+        }
+        if self
+            .recipients
+            .iter()
+            .any(|r| memo_data(&r.script_pubkey).is_some())
+        {
+            return Err(Error::InvalidMemo("only one memo is allowed".to_string()));
+        }
+        self.recipients.push(Recipient {
+            satoshi: 0,
+            script_pubkey: memo_script(data),
+            blinding_pubkey: None,
+            asset: self.network().policy_asset(),
+        });
+        Ok(self)
+    }
+
     /// Set custom fee rate
     pub fn fee_rate(mut self, fee_rate: Option<f32>) -> Self {
         if let Some(fee_rate) = fee_rate {
@@ -150,6 +287,28 @@ impl TxBuilder {
         self
     }
 
+    /// Set the fee rate to confirm the transaction within `target_blocks`
+    ///
+    /// The fee rate is taken from `estimates`, as returned by
+    /// [`crate::BlockchainBackend::fee_estimates()`], bounded and with fallback values as
+    /// in the default [`FeePolicy`].
+    pub fn fee_target(mut self, target_blocks: u16, estimates: &BTreeMap<u16, f32>) -> Self {
+        self.fee_rate = FeePolicy::default().fee_rate(target_blocks, estimates);
+        self
+    }
+
+    /// Set the transaction locktime, as a block height or a unix timestamp
+    ///
+    /// The transaction can't be included in a block before the locktime is reached,
+    /// useful to create pre-signed transactions valid only in the future.
+    /// Inputs are given a non-final sequence so that the locktime is enforced.
+    pub fn locktime(mut self, locktime: Option<LockTime>) -> Self {
+        if let Some(locktime) = locktime {
+            self.locktime = Some(locktime)
+        }
+        self
+    }
+
     /// Issue an asset
     ///
     /// There will be `asset_sats` units of this asset that will be received by
@@ -228,6 +387,15 @@ impl TxBuilder {
         Ok(self)
     }
 
+    /// Reissue `satoshi` units of `asset`, receiving them in the wallet
+    ///
+    /// Shorthand for [`TxBuilder::reissue_asset()`] for an asset issued by a transaction of the
+    /// wallet: the reissuance token UTXO and the issuance entropy are found among the wallet
+    /// transactions, see [`Wollet::reissuance_tokens()`] for the tokens that can be used.
+    pub fn reissue(self, asset: AssetId, satoshi: u64) -> Result<Self, Error> {
+        self.reissue_asset(asset, satoshi, None, None)
+    }
+
     /// Select all available L-BTC inputs
     pub fn drain_lbtc_wallet(mut self) -> Self {
         self.drain_lbtc = true;
@@ -240,37 +408,300 @@ impl TxBuilder {
         self
     }
 
+    /// Spend all the UTXOs of `asset` and send what is not sent to the recipients to `address`
+    ///
+    /// Can be called multiple times to drain several assets. Draining the policy asset is like
+    /// calling [`TxBuilder::drain_lbtc_wallet()`] and [`TxBuilder::drain_lbtc_to()`], the fee is
+    /// subtracted from the drained amount.
+    pub fn drain_asset(mut self, asset: AssetId, address: Address) -> Self {
+        if asset == self.network().policy_asset() {
+            return self.drain_lbtc_wallet().drain_lbtc_to(address);
+        }
+        self.drain_assets.insert(asset, address);
+        self
+    }
+
+    /// Pay the change to the same addresses of `pset`, a previous version of this transaction
+    ///
+    /// By default the change goes to the first unused internal addresses, thus every rebuild of
+    /// a transaction which has been broadcast, for instance to bump its fee, would derive new
+    /// addresses, wasting them and possibly exceeding the gap limit.
+    pub fn reuse_change_of(mut self, pset: &PartiallySignedTransaction) -> Self {
+        self.replaced_scripts = pset
+            .outputs()
+            .iter()
+            .map(|o| o.script_pubkey.clone())
+            .collect();
+        self
+    }
+
+    /// Verify the rangeproofs and surjection proofs of the blinded outputs before returning the PSET
+    ///
+    /// This is an additional check against blinding bugs, which makes the transaction creation
+    /// slower.
+    pub fn paranoid(mut self, paranoid: bool) -> Self {
+        self.paranoid = paranoid;
+        self
+    }
+
+    /// Do not pay the service fee of the wallet, for transactions moving funds between wallets
+    /// of the same user
+    pub(crate) fn without_service_fee(mut self) -> Self {
+        self.service_fee = false;
+        self
+    }
+
+    /// Create a transaction without confidential transactions, only on regtest
+    ///
+    /// All the outputs are explicit and only explicit wallet UTXOs are spent, since blinded
+    /// inputs cannot balance explicit outputs. Blinding is unnecessary on test chains, skipping
+    /// it makes the transaction creation faster and the transaction easier to inspect.
+    /// Reissuances are not supported, since they require a blinded reissuance token.
+    ///
+    /// Wallets whose descriptor has no blinding key always create such transactions, on any
+    /// network. They refuse to pay confidential addresses, unless this is set to `true`
+    /// acknowledging that the outputs are explicit anyway.
+    pub fn unblinded(mut self, unblinded: bool) -> Self {
+        self.unblinded = unblinded;
+        self
+    }
+
+    /// Spend only the given wallet UTXOs, instead of selecting them among all the wallet UTXOs
+    ///
+    /// L-BTC UTXOs in the list are all spent, like with [`TxBuilder::drain_lbtc_wallet()`].
+    /// Locked UTXOs cannot be selected, see [`Wollet::lock_utxo()`].
+    pub fn set_wallet_utxos(mut self, utxos: Vec<OutPoint>) -> Self {
+        self.wallet_utxos = Some(utxos);
+        self
+    }
+
+    /// Claim the bitcoin sent to a peg-in address of the wallet, see [`Wollet::pegin_address()`]
+    ///
+    /// `mainchain_tx` is the mainchain transaction sending to the peg-in address and
+    /// `txout_proof` its merkle proof, as returned by `gettxoutproof` of bitcoind. The claimed
+    /// L-BTC are sent to the wallet as change, minus the fee. The deposit must be buried enough
+    /// in the mainchain for the claim to be accepted, 102 blocks for Liquid.
+    pub fn claim_pegin(
+        mut self,
+        fedpegscript: bitcoin::ScriptBuf,
+        mainchain_tx: bitcoin::Transaction,
+        txout_proof: Vec<u8>,
+    ) -> Self {
+        self.pegins.push(PeginRequest {
+            fedpegscript,
+            mainchain_tx,
+            txout_proof,
+        });
+        self
+    }
+
+    /// Pay the fee with the asset of the `provider` quote instead of L-BTC
+    ///
+    /// The provider input funds the fee, the provider receives the L-BTC change and the amount
+    /// of the asset computed with [`crate::FeeQuote::asset_fee()`]. The transaction cannot send
+    /// L-BTC, issue assets or claim peg-ins.
+    pub fn fee_provider(mut self, provider: FeeProvider) -> Self {
+        self.fee_provider = Some(provider);
+        self
+    }
+
     /// Finish building the transaction
     pub fn finish(self, wollet: &Wollet) -> Result<PartiallySignedTransaction, Error> {
+        Ok(self.finish_with_warnings(wollet)?.0)
+    }
+
+    /// Finish building the transaction, also returning the [`Warning`]s about it
+    pub fn finish_with_warnings(
+        self,
+        wollet: &Wollet,
+    ) -> Result<(PartiallySignedTransaction, Vec<Warning>), Error> {
+        let Some(provider) = self.fee_provider.clone() else {
+            return self.build(wollet, 0);
+        };
+        // The payment depends on the fee, which depends on the inputs selected for the payment,
+        // thus build again until the payment covers the fee
+        let mut payment = provider.quote.asset_amount(1).max(1);
+        loop {
+            let (pset, warnings) = self.clone().build(wollet, payment)?;
+            let required = provider.quote.asset_fee(&pset);
+            if required <= payment {
+                return Ok((pset, warnings));
+            }
+            payment = required;
+        }
+    }
+
+    /// Build the transaction, paying `provider_payment` to the fee provider if any
+    fn build(
+        self,
+        wollet: &Wollet,
+        provider_payment: u64,
+    ) -> Result<(PartiallySignedTransaction, Vec<Warning>), Error> {
+        // Wallets without blinding key can only create explicit transactions, on any network
+        let explicit_wallet = !wollet.wollet_descriptor().is_confidential();
+        let unblinded = self.unblinded || explicit_wallet;
+        if unblinded {
+            if !explicit_wallet && !matches!(self.network, ElementsNetwork::ElementsRegtest { .. })
+            {
+                return Err(Error::UnblindedNotRegtest);
+            }
+            if matches!(self.issuance_request, IssuanceRequest::Reissuance(..)) {
+                return Err(Error::UnblindedReissuance);
+            }
+        }
+        let wallet_utxos = self.wallet_utxos.clone();
+        if let Some(selected) = wallet_utxos.as_ref() {
+            let utxos = wollet.utxos()?;
+            for outpoint in selected {
+                if !utxos.iter().any(|u| &u.outpoint == outpoint) {
+                    return Err(Error::UtxoNotMine(*outpoint));
+                }
+                if wollet.is_utxo_locked(outpoint) {
+                    return Err(Error::UtxoLocked(*outpoint));
+                }
+            }
+        }
+        let asset_utxos = |asset: &AssetId| -> Result<Vec<WalletTxOut>, Error> {
+            Ok(wollet
+                .asset_utxos(asset)?
+                .into_iter()
+                .filter(|u| !unblinded || u.is_explicit())
+                .filter(|u| {
+                    wallet_utxos
+                        .as_ref()
+                        .map_or(true, |selected| selected.contains(&u.outpoint))
+                })
+                .collect())
+        };
+
+        let mut warnings = recipients_warnings(
+            wollet,
+            &self.recipients,
+            self.drain_to.iter().chain(self.drain_assets.values()),
+        );
+        if self.fee_rate > HIGH_FEE_RATE {
+            warnings.push(Warning::HighFeeRate {
+                fee_rate: self.fee_rate,
+            });
+        }
+        if explicit_wallet && !self.unblinded {
+            // Don't silently drop the privacy the receiver asked for
+            let confidential = self
+                .recipients
+                .iter()
+                .filter(|r| r.blinding_pubkey.is_some())
+                .map(|r| r.script_pubkey.clone())
+                .chain(
+                    self.drain_to
+                        .iter()
+                        .chain(self.drain_assets.values())
+                        .filter(|a| a.is_blinded())
+                        .map(|a| a.script_pubkey()),
+                )
+                .next();
+            if let Some(script_pubkey) = confidential {
+                return Err(Error::ConfidentialRecipientExplicitWallet(format!(
+                    "{script_pubkey:x}"
+                )));
+            }
+        }
+        if unblinded {
+            // Requested explicitly
+            warnings.retain(|w| !matches!(w, Warning::UnconfidentialRecipient { .. }));
+        }
+
         // Init PSET
         let mut pset = PartiallySignedTransaction::new_v2();
         let mut inp_txout_sec = HashMap::new();
         let mut last_unused_internal = wollet.change(None)?.index();
+        if let Some(index) = replaced_change_index(wollet, &self.replaced_scripts)? {
+            last_unused_internal = index;
+        }
         let mut last_unused_external = wollet.address(None)?.index();
 
         let mut inp_weight = 0;
 
         let policy_asset = self.network().policy_asset();
-        let (addressees_lbtc, addressees_asset): (Vec<_>, Vec<_>) = self
+        let (mut addressees_lbtc, mut addressees_asset): (Vec<_>, Vec<_>) = self
             .recipients
             .into_iter()
             .partition(|a| a.asset == policy_asset);
 
+        // Service fee configured by the application, proportional to the L-BTC sent to others,
+        // when draining it is computed once the L-BTC inputs are known
+        let service_fee = wollet.service_fee().filter(|_| self.service_fee);
+        let mut sent_internal = AssetAmount::zero(policy_asset);
+        for addressee in addressees_lbtc.iter() {
+            if self.internal_scripts.contains(&addressee.script_pubkey) {
+                sent_internal = sent_internal.checked_add(addressee.amount())?;
+            }
+        }
+        if let Some(service_fee) = service_fee.filter(|_| self.drain_to.is_none()) {
+            let mut sent = AssetAmount::zero(policy_asset);
+            for addressee in addressees_lbtc.iter() {
+                sent = sent.checked_add(addressee.amount())?;
+            }
+            let satoshi = service_fee.amount(sent.checked_sub(sent_internal)?.sats);
+            if satoshi > 0 {
+                addressees_lbtc.push(Recipient::from_address(
+                    satoshi,
+                    &service_fee.address,
+                    policy_asset,
+                ));
+            }
+        }
+
+        // Payment of the fee in another asset
+        if let Some(provider) = self.fee_provider.as_ref() {
+            if !addressees_lbtc.is_empty()
+                || self.drain_to.is_some()
+                || !self.pegins.is_empty()
+                || !matches!(self.issuance_request, IssuanceRequest::None)
+            {
+                return Err(Error::FeeProviderWithLbtc);
+            }
+            addressees_asset.push(Recipient::from_address(
+                provider_payment,
+                &provider.address,
+                provider.quote.asset,
+            ));
+        }
+
         // Assets inputs and outputs
-        let assets: HashSet<_> = addressees_asset.iter().map(|a| a.asset).collect();
+        let assets: HashSet<_> = addressees_asset
+            .iter()
+            .map(|a| a.asset)
+            .chain(self.drain_assets.keys().cloned())
+            .collect();
         for asset in assets {
-            let mut satoshi_out = 0;
-            let mut satoshi_in = 0;
+            let mut amount_out = AssetAmount::zero(asset);
+            let mut amount_in = AssetAmount::zero(asset);
             for addressee in addressees_asset.iter().filter(|a| a.asset == asset) {
                 wollet.add_output(&mut pset, addressee)?;
-                satoshi_out += addressee.satoshi;
+                amount_out = amount_out.checked_add(addressee.amount())?;
+            }
+            if let Some(address) = self.drain_assets.get(&asset) {
+                for utxo in asset_utxos(&asset)? {
+                    wollet.add_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, &utxo)?;
+                    amount_in = amount_in.checked_add(utxo.amount())?;
+                }
+                // Nothing to drain if the wallet has no UTXO of the asset
+                if amount_in.sats == 0 || amount_in.sats < amount_out.sats {
+                    return Err(Error::InsufficientFunds);
+                }
+                if amount_in.sats > amount_out.sats {
+                    let satoshi_drain = amount_in.checked_sub(amount_out)?.sats;
+                    let addressee = Recipient::from_address(satoshi_drain, address, asset);
+                    wollet.add_output(&mut pset, &addressee)?;
+                }
+                continue;
             }
-            for utxo in wollet.asset_utxos(&asset)? {
+            for utxo in asset_utxos(&asset)? {
                 wollet.add_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, &utxo)?;
-                satoshi_in += utxo.unblinded.value;
-                if satoshi_in >= satoshi_out {
-                    if satoshi_in > satoshi_out {
-                        let satoshi_change = satoshi_in - satoshi_out;
+                amount_in = amount_in.checked_add(utxo.amount())?;
+                if amount_in.sats >= amount_out.sats {
+                    if amount_in.sats > amount_out.sats {
+                        let satoshi_change = amount_in.checked_sub(amount_out)?.sats;
                         let addressee = wollet.addressee_change(
                             satoshi_change,
                             asset,
@@ -281,25 +712,51 @@ impl TxBuilder {
                     break;
                 }
             }
-            if satoshi_in < satoshi_out {
+            if amount_in.sats < amount_out.sats {
                 return Err(Error::InsufficientFunds);
             }
         }
 
         // L-BTC inputs and outputs
         // Fee and L-BTC change after (re)issuance
-        let mut satoshi_out = 0;
-        let mut satoshi_in = 0;
+        let mut amount_out = AssetAmount::zero(policy_asset);
+        let mut amount_in = AssetAmount::zero(policy_asset);
         for addressee in addressees_lbtc {
             wollet.add_output(&mut pset, &addressee)?;
-            satoshi_out += addressee.satoshi;
+            amount_out = amount_out.checked_add(addressee.amount())?;
         }
 
-        // FIXME: For implementation simplicity now we always add all L-BTC inputs
-        for utxo in wollet.asset_utxos(&wollet.policy_asset())? {
-            wollet.add_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, &utxo)?;
-            satoshi_in += utxo.unblinded.value;
+        if let Some(provider) = self.fee_provider.as_ref() {
+            let satoshi = wollet.add_fee_provider_input(
+                &mut pset,
+                &mut inp_txout_sec,
+                &mut inp_weight,
+                provider,
+            )?;
+            amount_in = amount_in.checked_add_sats(satoshi)?;
+        } else {
+            // FIXME: For implementation simplicity now we always add all L-BTC inputs
+            for utxo in asset_utxos(&wollet.policy_asset())? {
+                wollet.add_input(&mut pset, &mut inp_txout_sec, &mut inp_weight, &utxo)?;
+                amount_in = amount_in.checked_add(utxo.amount())?;
+            }
+        }
+        for pegin in self.pegins.iter() {
+            let satoshi =
+                wollet.add_pegin(&mut pset, &mut inp_txout_sec, &mut inp_weight, pegin)?;
+            amount_in = amount_in.checked_add_sats(satoshi)?;
         }
+        // All the L-BTC in goes to the recipients and the drain address, but the network fee
+        if let Some(service_fee) = service_fee.filter(|_| self.drain_to.is_some()) {
+            let satoshi = service_fee.amount(amount_in.sats.saturating_sub(sent_internal.sats));
+            if satoshi > 0 {
+                let addressee =
+                    Recipient::from_address(satoshi, &service_fee.address, policy_asset);
+                wollet.add_output(&mut pset, &addressee)?;
+                amount_out = amount_out.checked_add(addressee.amount())?;
+            }
+        }
+        let (satoshi_in, satoshi_out) = (amount_in.sats, amount_out.sats);
 
         // Set (re)issuance data
         match self.issuance_request {
@@ -355,7 +812,7 @@ impl TxBuilder {
                         Some((idx, u)) => (*idx, u.asset_bf),
                         None => {
                             // Add an input sending the token,
-                            let utxos_token = wollet.asset_utxos(&token)?;
+                            let utxos_token = asset_utxos(&token)?;
                             let utxo_token = utxos_token
                                 .first()
                                 .ok_or_else(|| Error::InsufficientFunds)?;
@@ -400,14 +857,25 @@ impl TxBuilder {
             }
         }
 
+        if let Some(locktime) = self.locktime {
+            pset.global.tx_data.fallback_locktime = Some(locktime);
+            for input in pset.inputs_mut() {
+                input
+                    .sequence
+                    .get_or_insert(Sequence::ENABLE_LOCKTIME_NO_RBF);
+            }
+        }
+
         // Add a temporary fee, and always add a change or drain output,
         // then we'll tweak those values to match the given fee rate.
         let temp_fee = 1;
-        if satoshi_in <= (satoshi_out + temp_fee) {
-            return Err(Error::InsufficientFunds);
-        }
-        let satoshi_change = satoshi_in - satoshi_out - temp_fee;
-        let addressee = if let Some(address) = self.drain_to {
+        let satoshi_change = lbtc_change(satoshi_in, satoshi_out, temp_fee)?;
+        // The L-BTC change of the fee provider input goes back to the provider
+        let drain_to = self
+            .drain_to
+            .or_else(|| self.fee_provider.as_ref().map(|p| p.address.clone()));
+        let change_to_wallet = drain_to.is_none();
+        let addressee = if let Some(address) = drain_to {
             Recipient::from_address(satoshi_change, &address, wollet.policy_asset())
         } else {
             wollet.addressee_change(
@@ -420,39 +888,173 @@ impl TxBuilder {
         let fee_output =
             Output::new_explicit(Script::default(), temp_fee, wollet.policy_asset(), None);
         pset.add_output(fee_output);
+        if unblinded {
+            for output in pset.outputs_mut() {
+                output.blinding_key = None;
+                output.blinder_index = None;
+            }
+        }
 
         let weight = {
             let mut rng = thread_rng();
             let mut temp_pset = pset.clone();
-            temp_pset.blind_last(&mut rng, &EC, &inp_txout_sec)?;
+            if !unblinded {
+                temp_pset.blind_last(&mut rng, &EC, &inp_txout_sec)?;
+            }
             inp_weight + temp_pset.extract_tx()?.weight()
         };
 
         let vsize = (weight + 4 - 1) / 4;
-        let fee = (vsize as f32 * self.fee_rate / 1000.0).ceil() as u64;
-        if satoshi_in <= (satoshi_out + fee) {
-            return Err(Error::InsufficientFunds);
-        }
-        let satoshi_change = satoshi_in - satoshi_out - fee;
+        let mut fee = (vsize as f32 * self.fee_rate / 1000.0).ceil() as u64;
+        let satoshi_change = lbtc_change(satoshi_in, satoshi_out, fee)?;
         // Replace change and fee outputs
-        let n_outputs = pset.n_outputs();
-        let outputs = pset.outputs_mut();
-        let change_output = &mut outputs[n_outputs - 2]; // index check: we always have the lbtc change and the fee output at least
-        change_output.amount = Some(satoshi_change);
-        let fee_output = &mut outputs[n_outputs - 1];
-        fee_output.amount = Some(fee);
+        let mut n_outputs = pset.n_outputs();
+        // index check: we always have the lbtc change and the fee output at least
+        let change_idx = n_outputs - 2;
+        // Fold a dust change in the fee, if another output can carry the blinding
+        let other_blinded = pset.outputs()[..change_idx]
+            .iter()
+            .any(|o| o.blinding_key.is_some());
+        if change_to_wallet && satoshi_change < DUST_CHANGE && (unblinded || other_blinded) {
+            pset.remove_output(change_idx);
+            n_outputs -= 1;
+            fee += satoshi_change;
+            warnings.push(Warning::DustChangeFolded {
+                satoshi: satoshi_change,
+            });
+        } else {
+            pset.outputs_mut()[change_idx].amount = Some(satoshi_change);
+        }
+        pset.outputs_mut()[n_outputs - 1].amount = Some(fee);
 
-        // TODO inputs/outputs(except fee) randomization, not trivial because of blinder_index on inputs
+        // Randomness for shuffling and blinding is derived from the wallet private blinding key
+        // and the unblinded transaction, so retrying the same payment produces the same
+        // transaction.
+        match pset_rng(wollet, &pset) {
+            Some(mut rng) => shuffle_and_blind(&mut pset, &mut rng, unblinded, &inp_txout_sec)?,
+            None => shuffle_and_blind(&mut pset, &mut thread_rng(), unblinded, &inp_txout_sec)?,
+        }
 
-        // Blind the transaction
-        let mut rng = thread_rng();
-        pset.blind_last(&mut rng, &EC, &inp_txout_sec)?;
+        if self.paranoid {
+            let spent_utxos = pset
+                .inputs()
+                .iter()
+                .enumerate()
+                .map(|(idx, i)| i.witness_utxo.clone().ok_or(Error::MissingWitnessUtxo(idx)))
+                .collect::<Result<Vec<_>, _>>()?;
+            pset.extract_tx()?.verify_tx_amt_proofs(&EC, &spent_utxos)?;
+        }
 
         // Add details to the pset from our descriptor, like bip32derivation and keyorigin
         wollet.add_details(&mut pset)?;
 
-        Ok(pset)
+        Ok((pset, warnings))
+    }
+}
+
+/// Warnings about the scripts paid by the transaction, `drain_to` addresses included
+fn recipients_warnings<'a>(
+    wollet: &Wollet,
+    recipients: &[Recipient],
+    drain_to: impl Iterator<Item = &'a Address>,
+) -> Vec<Warning> {
+    let received: HashSet<&Script> = wollet
+        .store
+        .cache
+        .all_txs
+        .values()
+        .flat_map(|tx| tx.output.iter().map(|o| &o.script_pubkey))
+        .collect();
+    let paid = recipients
+        .iter()
+        .map(|r| (r.script_pubkey.clone(), r.blinding_pubkey.is_some()))
+        .chain(drain_to.map(|a| (a.script_pubkey(), a.is_blinded())));
+    let mut warnings = vec![];
+    for (script_pubkey, confidential) in paid {
+        // Burn and peg-out outputs are explicit by design
+        if script_pubkey.is_provably_unspendable() {
+            continue;
+        }
+        if received.contains(&script_pubkey) {
+            warnings.push(Warning::ReusedAddress {
+                script_pubkey: script_pubkey.clone(),
+            });
+        }
+        if !confidential {
+            warnings.push(Warning::UnconfidentialRecipient { script_pubkey });
+        }
+    }
+    warnings
+}
+
+/// The first internal index paid by `scripts`, the outputs of a replaced transaction
+///
+/// Change addresses of a transaction which has not been broadcast are not in the wallet store
+/// yet, so they are looked for among the following internal addresses.
+fn replaced_change_index(wollet: &Wollet, scripts: &[Script]) -> Result<Option<u32>, Error> {
+    let first_unused = wollet.change(None)?.index();
+    let mut indexes = vec![];
+    for script in scripts {
+        match wollet.index(script) {
+            Ok((Chain::Internal, index)) => indexes.push(index),
+            Ok((Chain::External, _)) => {}
+            Err(_) => {
+                for index in first_unused..first_unused + wollet.gap_limit() {
+                    if &wollet.change(Some(index))?.address().script_pubkey() == script {
+                        indexes.push(index);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+    Ok(indexes.into_iter().min())
+}
+
+/// The L-BTC change after sending `satoshi_out` and paying `fee`, it must be positive
+fn lbtc_change(satoshi_in: u64, satoshi_out: u64, fee: u64) -> Result<u64, Error> {
+    satoshi_in
+        .checked_sub(satoshi_out)
+        .and_then(|s| s.checked_sub(fee))
+        .filter(|change| *change > 0)
+        .ok_or(Error::InsufficientFunds)
+}
+
+/// A deterministic rng keyed with the wallet descriptor, which contains the blinding key, and
+/// seeded with the unblinded PSET
+///
+/// `None` if the blinding key is not private: ELIP151 keys are derived from the public
+/// descriptor, thus anyone knowing it could predict the blinding factors.
+///
+/// `ChaCha20Rng` is used because, unlike `StdRng`, its output is stable across `rand` versions and
+/// platforms, so the same PSET is always built the same way.
+fn pset_rng(wollet: &Wollet, pset: &PartiallySignedTransaction) -> Option<ChaCha20Rng> {
+    let descriptor = wollet.wollet_descriptor();
+    if !descriptor.is_confidential() || descriptor.is_elip151() {
+        return None;
     }
+    let key = wollet.descriptor().to_string();
+    let mut engine = HmacEngine::<sha256::Hash>::new(key.as_bytes());
+    engine.input(&elements::encode::serialize(pset));
+    Some(ChaCha20Rng::from_seed(
+        Hmac::from_engine(engine).to_byte_array(),
+    ))
+}
+
+/// Shuffle the outputs, except the fee which is always last, and blind them unless `unblinded`
+fn shuffle_and_blind<R: RngCore + CryptoRng>(
+    pset: &mut PartiallySignedTransaction,
+    rng: &mut R,
+    unblinded: bool,
+    inp_txout_sec: &HashMap<usize, TxOutSecrets>,
+) -> Result<(), Error> {
+    // TODO inputs randomization, not trivial because of blinder_index on outputs
+    let n_outputs = pset.n_outputs();
+    pset.outputs_mut()[..n_outputs - 1].shuffle(rng);
+    if !unblinded {
+        pset.blind_last(rng, &EC, inp_txout_sec)?;
+    }
+    Ok(())
 }
 
 /// A transaction builder.
@@ -476,6 +1078,11 @@ impl<'a> WolletTxBuilder<'a> {
         self.inner.finish(self.wollet)
     }
 
+    /// Wrapper of [`TxBuilder::finish_with_warnings()`]
+    pub fn finish_with_warnings(self) -> Result<(PartiallySignedTransaction, Vec<Warning>), Error> {
+        self.inner.finish_with_warnings(self.wollet)
+    }
+
     /// Wrapper of [`TxBuilder::add_recipient()`]
     pub fn add_recipient(
         self,
@@ -519,6 +1126,21 @@ impl<'a> WolletTxBuilder<'a> {
         })
     }
 
+    /// Wrapper of [`TxBuilder::add_internal_recipient()`]
+    pub fn add_internal_recipient(
+        self,
+        account: &Wollet,
+        satoshi: u64,
+        asset_id: AssetId,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            wollet: self.wollet,
+            inner: self
+                .inner
+                .add_internal_recipient(account, satoshi, asset_id)?,
+        })
+    }
+
     /// Wrapper of [`TxBuilder::add_lbtc_recipient()`]
     pub fn add_lbtc_recipient(self, address: &Address, satoshi: u64) -> Result<Self, Error> {
         Ok(Self {
@@ -535,6 +1157,26 @@ impl<'a> WolletTxBuilder<'a> {
         })
     }
 
+    /// Wrapper of [`TxBuilder::add_memo_opreturn()`]
+    pub fn add_memo_opreturn(self, data: &[u8]) -> Result<Self, Error> {
+        Ok(Self {
+            wollet: self.wollet,
+            inner: self.inner.add_memo_opreturn(data)?,
+        })
+    }
+
+    /// Wrapper of [`TxBuilder::add_pegout()`]
+    pub fn add_pegout(
+        self,
+        address: &bitcoin::Address<NetworkUnchecked>,
+        satoshi: u64,
+    ) -> Result<Self, Error> {
+        Ok(Self {
+            wollet: self.wollet,
+            inner: self.inner.add_pegout(address, satoshi)?,
+        })
+    }
+
     /// Wrapper of [`TxBuilder::fee_rate()`]
     pub fn fee_rate(self, fee_rate: Option<f32>) -> Self {
         Self {
@@ -543,6 +1185,22 @@ impl<'a> WolletTxBuilder<'a> {
         }
     }
 
+    /// Wrapper of [`TxBuilder::fee_target()`]
+    pub fn fee_target(self, target_blocks: u16, estimates: &BTreeMap<u16, f32>) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.fee_target(target_blocks, estimates),
+        }
+    }
+
+    /// Wrapper of [`TxBuilder::locktime()`]
+    pub fn locktime(self, locktime: Option<LockTime>) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.locktime(locktime),
+        }
+    }
+
     /// Wrapper of [`TxBuilder::issue_asset()`]
     pub fn issue_asset(
         self,
@@ -564,6 +1222,14 @@ impl<'a> WolletTxBuilder<'a> {
         })
     }
 
+    /// Wrapper of [`TxBuilder::reissue()`]
+    pub fn reissue(self, asset: AssetId, satoshi: u64) -> Result<Self, Error> {
+        Ok(Self {
+            wollet: self.wollet,
+            inner: self.inner.reissue(asset, satoshi)?,
+        })
+    }
+
     /// Wrapper of [`TxBuilder::reissue_asset()`]
     pub fn reissue_asset(
         self,
@@ -591,6 +1257,22 @@ impl<'a> WolletTxBuilder<'a> {
         }
     }
 
+    /// Wrapper of [`TxBuilder::reuse_change_of()`]
+    pub fn reuse_change_of(self, pset: &PartiallySignedTransaction) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.reuse_change_of(pset),
+        }
+    }
+
+    /// Wrapper of [`TxBuilder::drain_asset()`]
+    pub fn drain_asset(self, asset: AssetId, address: Address) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.drain_asset(asset, address),
+        }
+    }
+
     /// Wrapper of [`TxBuilder::drain_lbtc_to()`]
     pub fn drain_lbtc_to(self, address: Address) -> Self {
         Self {
@@ -598,4 +1280,51 @@ impl<'a> WolletTxBuilder<'a> {
             inner: self.inner.drain_lbtc_to(address),
         }
     }
+
+    /// Wrapper of [`TxBuilder::fee_provider()`]
+    pub fn fee_provider(self, provider: FeeProvider) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.fee_provider(provider),
+        }
+    }
+
+    /// Wrapper of [`TxBuilder::claim_pegin()`]
+    pub fn claim_pegin(
+        self,
+        fedpegscript: bitcoin::ScriptBuf,
+        mainchain_tx: bitcoin::Transaction,
+        txout_proof: Vec<u8>,
+    ) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self
+                .inner
+                .claim_pegin(fedpegscript, mainchain_tx, txout_proof),
+        }
+    }
+
+    /// Wrapper of [`TxBuilder::set_wallet_utxos()`]
+    pub fn set_wallet_utxos(self, utxos: Vec<OutPoint>) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.set_wallet_utxos(utxos),
+        }
+    }
+
+    /// Wrapper of [`TxBuilder::unblinded()`]
+    pub fn unblinded(self, unblinded: bool) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.unblinded(unblinded),
+        }
+    }
+
+    /// Wrapper of [`TxBuilder::paranoid()`]
+    pub fn paranoid(self, paranoid: bool) -> Self {
+        Self {
+            wollet: self.wollet,
+            inner: self.inner.paranoid(paranoid),
+        }
+    }
 }
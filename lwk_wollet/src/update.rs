@@ -1,7 +1,10 @@
 use crate::descriptor::Chain;
-use crate::elements::{OutPoint, Script, Transaction, TxOutSecrets, Txid};
+use crate::elements::{AssetId, OutPoint, Script, Transaction, TxOutSecrets, Txid};
 use crate::error::Error;
+use crate::label::LabelRef;
+use crate::registry::AssetInfo;
 use crate::store::{Height, Timestamp};
+use crate::util::decode_string;
 use crate::{Wollet, WolletDescriptor};
 use aes_gcm_siv::aead::generic_array::GenericArray;
 use aes_gcm_siv::aead::AeadMutInPlace;
@@ -14,6 +17,17 @@ use rand::{thread_rng, Rng};
 use std::collections::HashMap;
 use std::sync::atomic;
 
+/// Version of the [`Update`] encoding written by this library
+///
+/// When the encoding changes this is bumped and the decoding keeps supporting the previous
+/// versions, so that existing persisted updates are migrated forward instead of being dropped.
+/// [`crate::FsPersister`] rewrites the updates written with previous versions when opened.
+///
+/// Version 1 added the UTXO locks, version 2 the labels, version 3 the assets info, version 4 the
+/// outputs that failed to unblind, version 5 the verification of the assets info, version 6 the
+/// status of the scripts.
+pub const UPDATE_VERSION: u8 = 6;
+
 /// Transactions downloaded and unblinded
 #[derive(Default, Clone, PartialEq, Eq, Debug)]
 pub struct DownloadTxResult {
@@ -22,11 +36,16 @@ pub struct DownloadTxResult {
 
     /// Unblinded outputs of the downloaded transactions
     pub unblinds: Vec<(OutPoint, TxOutSecrets)>,
+
+    /// Outputs on the wallet scripts that cannot be unblinded, kept to avoid trying again
+    ///
+    /// Since these have been added later, they are encoded at the end of the [`Update`].
+    pub unblind_failures: Vec<OutPoint>,
 }
 
 impl DownloadTxResult {
-    fn is_empty(&self) -> bool {
-        self.txs.is_empty() && self.unblinds.is_empty()
+    pub(crate) fn is_empty(&self) -> bool {
+        self.txs.is_empty() && self.unblinds.is_empty() && self.unblind_failures.is_empty()
     }
 }
 
@@ -40,6 +59,24 @@ pub struct Update {
     pub timestamps: Vec<(Height, Timestamp)>,
     pub scripts: HashMap<Script, (Chain, ChildNumber)>, // TODO should be Vec<(Script,(Chain,ChildNumber))>
     pub tip: BlockHeader,
+
+    /// UTXOs locked by [`Wollet::lock_utxo()`]
+    pub utxo_locks: Vec<OutPoint>,
+
+    /// UTXOs unlocked by [`Wollet::unlock_utxo()`]
+    pub utxo_unlocks: Vec<OutPoint>,
+
+    /// Labels set by [`Wollet::set_label()`], or removed if `None`
+    pub labels: Vec<(LabelRef, Option<String>)>,
+
+    /// Registry info of assets cached by [`Wollet::set_asset_info()`]
+    pub assets: Vec<(AssetId, AssetInfo)>,
+
+    /// Status of the scripts whose history changed, `None` if the history is now empty
+    ///
+    /// Used by [`crate::BlockchainBackend::incremental_scan()`] to request only the history of the
+    /// scripts changed since the previous scan.
+    pub scripts_status: Vec<(Script, Option<[u8; 32]>)>,
 }
 
 impl Update {
@@ -48,6 +85,11 @@ impl Update {
             && self.txid_height_new.is_empty()
             && self.txid_height_delete.is_empty()
             && self.scripts.is_empty()
+            && self.utxo_locks.is_empty()
+            && self.utxo_unlocks.is_empty()
+            && self.labels.is_empty()
+            && self.assets.is_empty()
+            && self.scripts_status.is_empty()
     }
     pub fn serialize(&self) -> Result<Vec<u8>, elements::encode::Error> {
         let mut vec = vec![];
@@ -58,21 +100,26 @@ impl Update {
         Update::consensus_decode(bytes)
     }
 
-    pub fn serialize_encrypted(&self, desc: &WolletDescriptor) -> Result<Vec<u8>, Error> {
-        let mut plaintext = self.serialize()?;
-
-        let mut nonce_bytes = [0u8; 12];
-        thread_rng().fill(&mut nonce_bytes);
-        let nonce = GenericArray::from_slice(&nonce_bytes);
-
-        desc.cipher().encrypt_in_place(nonce, b"", &mut plaintext)?;
-        let ciphertext = plaintext;
-
-        let mut result = Vec::with_capacity(ciphertext.len() + 12);
-        result.extend(nonce.as_slice());
-        result.extend(&ciphertext);
+    /// The version of the encoded update, without decoding it
+    ///
+    /// Returns [`Error::UnsupportedUpdateVersion`] if the update has been encoded by a newer
+    /// version of this library.
+    pub fn version(bytes: &[u8]) -> Result<u8, Error> {
+        if bytes.len() < 5 || bytes[..4] != UPDATE_MAGIC_BYTES {
+            return Err(elements::encode::Error::ParseFailed("Invalid magic bytes").into());
+        }
+        let version = bytes[4];
+        if version > UPDATE_VERSION {
+            return Err(Error::UnsupportedUpdateVersion {
+                version,
+                supported: UPDATE_VERSION,
+            });
+        }
+        Ok(version)
+    }
 
-        Ok(result)
+    pub fn serialize_encrypted(&self, desc: &WolletDescriptor) -> Result<Vec<u8>, Error> {
+        encrypt(self.serialize()?, desc)
     }
 
     pub fn serialize_encrypted_base64(&self, desc: &WolletDescriptor) -> Result<String, Error> {
@@ -81,15 +128,8 @@ impl Update {
     }
 
     pub fn deserialize_decrypted(bytes: &[u8], desc: &WolletDescriptor) -> Result<Update, Error> {
-        let nonce_bytes = &bytes[..12];
-        let mut ciphertext = bytes[12..].to_vec();
-
-        let nonce = GenericArray::from_slice(nonce_bytes);
-
-        desc.cipher()
-            .decrypt_in_place(nonce, b"", &mut ciphertext)?;
-        let plaintext = ciphertext;
-
+        let plaintext = decrypt(bytes, desc)?;
+        Update::version(&plaintext)?;
         Ok(Update::deserialize(&plaintext)?)
     }
 
@@ -104,6 +144,32 @@ impl Update {
     }
 }
 
+pub(crate) fn encrypt(mut plaintext: Vec<u8>, desc: &WolletDescriptor) -> Result<Vec<u8>, Error> {
+    let mut nonce_bytes = [0u8; 12];
+    thread_rng().fill(&mut nonce_bytes);
+    let nonce = GenericArray::from_slice(&nonce_bytes);
+
+    desc.cipher().encrypt_in_place(nonce, b"", &mut plaintext)?;
+    let ciphertext = plaintext;
+
+    let mut result = Vec::with_capacity(ciphertext.len() + 12);
+    result.extend(nonce.as_slice());
+    result.extend(&ciphertext);
+
+    Ok(result)
+}
+
+pub(crate) fn decrypt(bytes: &[u8], desc: &WolletDescriptor) -> Result<Vec<u8>, Error> {
+    let nonce_bytes = &bytes[..12];
+    let mut ciphertext = bytes[12..].to_vec();
+
+    let nonce = GenericArray::from_slice(nonce_bytes);
+
+    desc.cipher()
+        .decrypt_in_place(nonce, b"", &mut ciphertext)?;
+    Ok(ciphertext)
+}
+
 impl Wollet {
     pub fn apply_update(&mut self, update: Update) -> Result<(), Error> {
         self.apply_update_inner(update, true)
@@ -124,6 +190,11 @@ impl Wollet {
             timestamps,
             scripts,
             tip,
+            utxo_locks,
+            utxo_unlocks,
+            labels,
+            assets,
+            scripts_status,
         } = update.clone();
 
         if tip.height + 1 < store.cache.tip.0 {
@@ -135,19 +206,55 @@ impl Wollet {
         }
 
         store.cache.tip = (tip.height, tip.block_hash());
+        store.tip_header = Some(tip);
+        store.locked.extend(utxo_locks);
+        for outpoint in utxo_unlocks.iter() {
+            store.locked.remove(outpoint);
+        }
+        for (item, label) in labels {
+            match label {
+                Some(label) => store.labels.insert(item, label),
+                None => store.labels.remove(&item),
+            };
+        }
+        store.assets.extend(assets);
         store.cache.unblinded.extend(new_txs.unblinds);
+        store
+            .cache
+            .unblind_failures
+            .extend(new_txs.unblind_failures);
         store.cache.all_txs.extend(new_txs.txs);
+        // Wallet txs dropped from the history have been evicted or replaced
+        let dropped: Vec<Txid> = txid_height_delete
+            .iter()
+            .filter(|txid| store.cache.heights.contains_key(*txid))
+            .cloned()
+            .collect();
         store
             .cache
             .heights
             .retain(|k, _| !txid_height_delete.contains(k));
         store.cache.heights.extend(txid_height_new.clone());
+        for (txid, _) in txid_height_new.iter() {
+            store.cache.conflicts.remove(txid);
+        }
+        for txid in dropped {
+            store.cache.conflicts.insert(txid, None);
+        }
+        store.update_replacements();
+        store.prune_conflicts();
         store.cache.timestamps.extend(timestamps);
         store
             .cache
             .scripts
             .extend(scripts.clone().into_iter().map(|(a, b)| (b, a)));
         store.cache.paths.extend(scripts);
+        for (script, status) in scripts_status {
+            match status {
+                Some(status) => store.cache.scripts_status.insert(script, status),
+                None => store.cache.scripts_status.remove(&script),
+            };
+        }
         let mut last_used_internal = None;
         let mut last_used_external = None;
         for (txid, _) in txid_height_new {
@@ -180,17 +287,18 @@ impl Wollet {
                 }
             }
         }
+        // Updates may contain only part of the transactions, never move the index backwards
         if let Some(last_used_external) = last_used_external {
             store
                 .cache
                 .last_unused_external
-                .store(last_used_external + 1, atomic::Ordering::Relaxed);
+                .fetch_max(last_used_external + 1, atomic::Ordering::Relaxed);
         }
         if let Some(last_used_internal) = last_used_internal {
             store
                 .cache
                 .last_unused_internal
-                .store(last_used_internal + 1, atomic::Ordering::Relaxed);
+                .fetch_max(last_used_internal + 1, atomic::Ordering::Relaxed);
         }
 
         if do_persist {
@@ -248,7 +356,11 @@ impl Decodable for DownloadTxResult {
             unblinds.push((out_point, encodable_tx_out_secrets.inner))
         }
 
-        Ok(DownloadTxResult { txs, unblinds })
+        Ok(DownloadTxResult {
+            txs,
+            unblinds,
+            unblind_failures: vec![],
+        })
     }
 }
 
@@ -312,7 +424,7 @@ impl Encodable for Update {
         let mut bytes_written = 0;
 
         bytes_written += UPDATE_MAGIC_BYTES.consensus_encode(&mut w)?; // Magic bytes
-        bytes_written += 0u8.consensus_encode(&mut w)?; // Version
+        bytes_written += UPDATE_VERSION.consensus_encode(&mut w)?;
 
         bytes_written += self.new_txs.consensus_encode(&mut w)?;
 
@@ -348,6 +460,47 @@ impl Encodable for Update {
 
         bytes_written += self.tip.consensus_encode(&mut w)?;
 
+        for outpoints in [&self.utxo_locks, &self.utxo_unlocks] {
+            bytes_written += elements::VarInt(outpoints.len() as u64).consensus_encode(&mut w)?;
+            for outpoint in outpoints.iter() {
+                bytes_written += outpoint.consensus_encode(&mut w)?;
+            }
+        }
+
+        bytes_written += elements::VarInt(self.labels.len() as u64).consensus_encode(&mut w)?;
+        for (item, label) in self.labels.iter() {
+            bytes_written += item.consensus_encode(&mut w)?;
+            bytes_written += match label {
+                Some(label) => {
+                    1u8.consensus_encode(&mut w)?
+                        + label.as_bytes().to_vec().consensus_encode(&mut w)?
+                }
+                None => 0u8.consensus_encode(&mut w)?,
+            };
+        }
+
+        bytes_written += elements::VarInt(self.assets.len() as u64).consensus_encode(&mut w)?;
+        for (asset, info) in self.assets.iter() {
+            bytes_written += asset.consensus_encode(&mut w)?;
+            bytes_written += info.consensus_encode(&mut w)?;
+        }
+
+        let failures = &self.new_txs.unblind_failures;
+        bytes_written += elements::VarInt(failures.len() as u64).consensus_encode(&mut w)?;
+        for outpoint in failures.iter() {
+            bytes_written += outpoint.consensus_encode(&mut w)?;
+        }
+
+        bytes_written +=
+            elements::VarInt(self.scripts_status.len() as u64).consensus_encode(&mut w)?;
+        for (script, status) in self.scripts_status.iter() {
+            bytes_written += script.consensus_encode(&mut w)?;
+            bytes_written += match status {
+                Some(status) => 1u8.consensus_encode(&mut w)? + status.consensus_encode(&mut w)?,
+                None => 0u8.consensus_encode(&mut w)?,
+            };
+        }
+
         Ok(bytes_written)
     }
 }
@@ -359,12 +512,13 @@ impl Decodable for Update {
             return Err(elements::encode::Error::ParseFailed("Invalid magic bytes"));
         }
 
+        // When the encoding changes, fields must be decoded according to `version`
         let version = u8::consensus_decode(&mut d)?;
-        if version != 0 {
+        if version > UPDATE_VERSION {
             return Err(elements::encode::Error::ParseFailed("Unsupported version"));
         }
 
-        let new_txs = DownloadTxResult::consensus_decode(&mut d)?;
+        let mut new_txs = DownloadTxResult::consensus_decode(&mut d)?;
 
         let txid_height_new = {
             let len = elements::VarInt::consensus_decode(&mut d)?.0;
@@ -418,6 +572,79 @@ impl Decodable for Update {
 
         let tip = BlockHeader::consensus_decode(&mut d)?;
 
+        let mut decode_outpoints = || -> Result<Vec<OutPoint>, elements::encode::Error> {
+            if version < 1 {
+                return Ok(vec![]);
+            }
+            let len = elements::VarInt::consensus_decode(&mut d)?.0;
+            let mut vec = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                vec.push(OutPoint::consensus_decode(&mut d)?);
+            }
+            Ok(vec)
+        };
+        let utxo_locks = decode_outpoints()?;
+        let utxo_unlocks = decode_outpoints()?;
+
+        let labels = if version < 2 {
+            vec![]
+        } else {
+            let len = elements::VarInt::consensus_decode(&mut d)?.0;
+            let mut vec = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                let item = LabelRef::consensus_decode(&mut d)?;
+                let label = match u8::consensus_decode(&mut d)? {
+                    0 => None,
+                    1 => Some(decode_string(&mut d)?),
+                    _ => return Err(elements::encode::Error::ParseFailed("Invalid label")),
+                };
+                vec.push((item, label));
+            }
+            vec
+        };
+
+        let assets = if version < 3 {
+            vec![]
+        } else {
+            let len = elements::VarInt::consensus_decode(&mut d)?.0;
+            let mut vec = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                let asset = AssetId::consensus_decode(&mut d)?;
+                let info = if version < 5 {
+                    AssetInfo::consensus_decode_unverified(&mut d)?
+                } else {
+                    AssetInfo::consensus_decode(&mut d)?
+                };
+                vec.push((asset, info));
+            }
+            vec
+        };
+
+        if version >= 4 {
+            let len = elements::VarInt::consensus_decode(&mut d)?.0;
+            for _ in 0..len {
+                let outpoint = OutPoint::consensus_decode(&mut d)?;
+                new_txs.unblind_failures.push(outpoint);
+            }
+        }
+
+        let scripts_status = if version < 6 {
+            vec![]
+        } else {
+            let len = elements::VarInt::consensus_decode(&mut d)?.0;
+            let mut vec = Vec::with_capacity(len as usize);
+            for _ in 0..len {
+                let script = Script::consensus_decode(&mut d)?;
+                let status = match u8::consensus_decode(&mut d)? {
+                    0 => None,
+                    1 => Some(<[u8; 32]>::consensus_decode(&mut d)?),
+                    _ => return Err(elements::encode::Error::ParseFailed("Invalid status")),
+                };
+                vec.push((script, status));
+            }
+            vec
+        };
+
         Ok(Self {
             new_txs,
             txid_height_new,
@@ -425,6 +652,11 @@ impl Decodable for Update {
             timestamps,
             scripts,
             tip,
+            utxo_locks,
+            utxo_unlocks,
+            labels,
+            assets,
+            scripts_status,
         })
     }
 }
@@ -439,9 +671,23 @@ mod test {
         Script,
     };
 
-    use crate::{update::DownloadTxResult, Chain, Update, WolletDescriptor};
+    use crate::elements::AssetId;
+    use crate::hashes::Hash;
+    use crate::{
+        update::DownloadTxResult, AssetInfo, Chain, Error, LabelRef, Update, WolletDescriptor,
+    };
+
+    use super::{EncodableTxOutSecrets, UPDATE_VERSION};
 
-    use super::EncodableTxOutSecrets;
+    fn asset_info() -> AssetInfo {
+        AssetInfo {
+            name: "Name".into(),
+            ticker: "TCK".into(),
+            precision: 2,
+            domain: "example.com".into(),
+            verified: false,
+        }
+    }
 
     pub fn download_tx_result_test_vector() -> DownloadTxResult {
         // there are issue in moving this in test_util
@@ -453,7 +699,11 @@ mod test {
 
         txs.push((tx.txid(), tx));
 
-        DownloadTxResult { txs, unblinds }
+        DownloadTxResult {
+            txs,
+            unblinds,
+            unblind_failures: vec![],
+        }
     }
 
     #[test]
@@ -466,8 +716,28 @@ mod test {
             timestamps: Default::default(),
             scripts: Default::default(),
             tip,
+            utxo_locks: Default::default(),
+            utxo_unlocks: Default::default(),
+            labels: Default::default(),
+            assets: Default::default(),
+            scripts_status: vec![],
         };
         assert!(update.only_tip());
+        let mut locks = update.clone();
+        locks.utxo_unlocks.push(Default::default());
+        assert!(!locks.only_tip());
+        let mut labels = update.clone();
+        labels.labels.push((LabelRef::Tx(Hash::all_zeros()), None));
+        assert!(!labels.only_tip());
+        let mut assets = update.clone();
+        assets.assets.push((AssetId::default(), asset_info()));
+        assert!(!assets.only_tip());
+        let mut failures = update.clone();
+        failures.new_txs.unblind_failures.push(Default::default());
+        assert!(!failures.only_tip());
+        let mut status = update.clone();
+        status.scripts_status.push((Script::default(), None));
+        assert!(!status.only_tip());
         update
             .txid_height_delete
             .push(<elements::Txid as elements::hashes::Hash>::all_zeros());
@@ -518,16 +788,87 @@ mod test {
             timestamps: vec![(12, 44), (12, 44)],
             scripts,
             tip,
+            utxo_locks: vec![],
+            utxo_unlocks: vec![],
+            labels: vec![],
+            assets: vec![],
+            scripts_status: vec![],
         };
 
+        // The test vector has been encoded with version 0, without the UTXO locks, labels, assets,
+        // unblind failures and scripts status
+        let v0 = lwk_test_util::update_test_vector_bytes();
+        assert_eq!(Update::version(&v0).unwrap(), 0);
+        assert_eq!(Update::consensus_decode(&v0[..]).unwrap(), update);
+
         let mut vec = vec![];
         let len = update.consensus_encode(&mut vec).unwrap();
-        assert_eq!(vec, lwk_test_util::update_test_vector_bytes());
-        assert_eq!(len, 2842);
+        let mut expected = v0.clone();
+        expected[4] = UPDATE_VERSION;
+        expected.extend([0, 0, 0, 0, 0, 0]);
+        assert_eq!(vec, expected);
+        assert_eq!(len, 2848);
         assert_eq!(vec.len(), len);
 
         let back = Update::consensus_decode(&vec[..]).unwrap();
-        assert_eq!(update, back)
+        assert_eq!(update, back);
+
+        let mut update = update;
+        update.utxo_locks.push(elements::OutPoint::new(txid, 1));
+        update.utxo_unlocks.push(elements::OutPoint::new(txid, 2));
+        update.utxo_unlocks.push(elements::OutPoint::new(txid, 3));
+        let vec = update.serialize().unwrap();
+        assert_eq!(vec.len(), 2848 + 3 * 36);
+        assert_eq!(Update::deserialize(&vec).unwrap(), update);
+
+        update
+            .labels
+            .push((LabelRef::Output(elements::OutPoint::new(txid, 1)), None));
+        update
+            .labels
+            .push((LabelRef::Tx(txid), Some("label".into())));
+        let vec = update.serialize().unwrap();
+        assert_eq!(vec.len(), 2848 + 3 * 36 + (1 + 36 + 1) + (1 + 32 + 1 + 6));
+        assert_eq!(Update::deserialize(&vec).unwrap(), update);
+
+        update.assets.push((AssetId::default(), asset_info()));
+        let vec = update.serialize().unwrap();
+        let labels_len = (1 + 36 + 1) + (1 + 32 + 1 + 6);
+        let asset_len = 32 + (1 + 4) + (1 + 3) + (1 + 11) + 1 + 1;
+        assert_eq!(vec.len(), 2848 + 3 * 36 + labels_len + asset_len);
+        assert_eq!(Update::deserialize(&vec).unwrap(), update);
+
+        // Version 4 assets info, without the verified flag before the unblind failures and without
+        // the scripts status
+        let mut v4 = vec.clone();
+        v4[4] = 4;
+        v4.pop();
+        v4.remove(v4.len() - 2);
+        assert_eq!(Update::deserialize(&v4).unwrap(), update);
+
+        update.assets[0].1.verified = true;
+        let vec = update.serialize().unwrap();
+        assert_eq!(Update::deserialize(&vec).unwrap(), update);
+
+        update
+            .new_txs
+            .unblind_failures
+            .push(elements::OutPoint::new(txid, 4));
+        let vec = update.serialize().unwrap();
+        assert_eq!(vec.len(), 2848 + 3 * 36 + labels_len + asset_len + 36);
+        assert_eq!(Update::deserialize(&vec).unwrap(), update);
+
+        update
+            .scripts_status
+            .push((Script::default(), Some([1; 32])));
+        update.scripts_status.push((Script::default(), None));
+        let vec = update.serialize().unwrap();
+        let status_len = (1 + 1 + 32) + (1 + 1);
+        assert_eq!(
+            vec.len(),
+            2848 + 3 * 36 + labels_len + asset_len + 36 + status_len
+        );
+        assert_eq!(Update::deserialize(&vec).unwrap(), update);
     }
 
     #[test]
@@ -543,6 +884,27 @@ mod test {
         Update::deserialize_decrypted(&enc_bytes2, &desc2).unwrap();
     }
 
+    #[test]
+    fn test_update_version() {
+        let bytes = lwk_test_util::update_test_vector_bytes();
+        assert_eq!(Update::version(&bytes).unwrap(), 0);
+        assert!(Update::version(&bytes[..3]).is_err());
+
+        let mut newer = bytes.clone();
+        newer[4] = UPDATE_VERSION + 1;
+        assert!(Update::deserialize(&newer).is_err());
+        let err = Update::version(&newer).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::UnsupportedUpdateVersion { version, supported: UPDATE_VERSION } if version == UPDATE_VERSION + 1
+        ));
+
+        let desc: WolletDescriptor = lwk_test_util::wollet_descriptor_string().parse().unwrap();
+        let enc = super::encrypt(newer, &desc).unwrap();
+        let err = Update::deserialize_decrypted(&enc, &desc).unwrap_err();
+        assert!(err.to_string().contains("newer version"));
+    }
+
     #[test]
     fn test_update_base64() {
         let base64 = lwk_test_util::update_test_vector_encrypted_base64();
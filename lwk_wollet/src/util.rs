@@ -37,3 +37,9 @@ pub fn verify_pubkey(pubkey: &[u8]) -> Result<(), Error> {
     PublicKey::from_slice(pubkey)?;
     Ok(())
 }
+
+/// Decode a string encoded as consensus encoded bytes
+pub(crate) fn decode_string<D: std::io::Read>(d: D) -> Result<String, elements::encode::Error> {
+    let bytes = <Vec<u8> as elements::encode::Decodable>::consensus_decode(d)?;
+    String::from_utf8(bytes).map_err(|_| elements::encode::Error::ParseFailed("Invalid UTF-8"))
+}
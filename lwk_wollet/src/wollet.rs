@@ -2,16 +2,22 @@ use crate::bitcoin::bip32::Fingerprint;
 use crate::config::{Config, ElementsNetwork};
 use crate::descriptor::Chain;
 use crate::elements::pset::PartiallySignedTransaction;
-use crate::elements::secp256k1_zkp::ZERO_TWEAK;
-use crate::elements::{AssetId, BlockHash, OutPoint, Script, Transaction, Txid};
+use crate::elements::secp256k1_zkp::{ecdsa::Signature, ZERO_TWEAK};
+use crate::elements::{Address, AssetId, BlockHash, OutPoint, Script, Transaction, Txid};
 use crate::error::Error;
 use crate::hashes::Hash;
-use crate::model::{AddressResult, IssuanceDetails, WalletTx, WalletTxOut};
+use crate::label::{self, LabelRef};
+use crate::model::{
+    AddressDetails, AddressResult, Balance, CombinedPset, IssuanceDetails, ReissuanceToken,
+    ReusedAddress, ServiceFee, TxFilter, TxPackageInfo, TxStatus, WalletSummary, WalletTx,
+    WalletTxOut, ZeroConfRisk,
+};
 use crate::persister::PersistError;
-use crate::store::{Height, Store, Timestamp};
+use crate::registry::AssetInfo;
+use crate::store::{Height, Store, Timestamp, BATCH_SIZE, MAX_GAP_LIMIT};
 use crate::tx_builder::{extract_issuances, WolletTxBuilder};
 use crate::util::EC;
-use crate::{FsPersister, NoPersist, Persister, Update, WolletDescriptor};
+use crate::{BlockchainBackend, FsPersister, NoPersist, Persister, Update, WolletDescriptor};
 use elements::bitcoin::bip32::ChildNumber;
 use elements_miniscript::psbt::PsbtExt;
 use elements_miniscript::{psbt, ForEachKey};
@@ -19,7 +25,12 @@ use elements_miniscript::{
     ConfidentialDescriptor, DefiniteDescriptorKey, Descriptor, DescriptorPublicKey,
 };
 use fxhash::FxHasher;
-use lwk_common::{burn_script, pset_balance, pset_issuances, pset_signatures, PsetDetails};
+use lwk_common::amount::{add_by_asset, net_by_asset, AssetAmount};
+use lwk_common::{
+    blinding_key_proof, burn_script, derive_blinding_key, pset_balance, pset_issuances,
+    pset_outputs, pset_signatures, verify_blinding_key_proof, PsetBalance, PsetDetails,
+    PsetWarning, ServiceFeeOutput,
+};
 use std::cmp::Ordering;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::Hasher;
@@ -32,6 +43,9 @@ pub struct Wollet {
     pub(crate) store: Store,
     pub(crate) persister: Arc<dyn Persister + Send + Sync>,
     descriptor: WolletDescriptor,
+    service_fee: Option<ServiceFee>,
+    max_fee_basis_points: Option<u32>,
+    gap_limit: u32,
 }
 
 impl std::fmt::Debug for Wollet {
@@ -63,6 +77,9 @@ impl Wollet {
             config,
             descriptor,
             persister,
+            service_fee: None,
+            max_fee_basis_points: None,
+            gap_limit: BATCH_SIZE,
         };
 
         for i in 0.. {
@@ -88,6 +105,20 @@ impl Wollet {
         )
     }
 
+    /// Create a new wallet persisting in the SQLite database at `path`
+    #[cfg(feature = "sqlite")]
+    pub fn with_sqlite_persist<P: AsRef<Path>>(
+        network: ElementsNetwork,
+        descriptor: WolletDescriptor,
+        path: P,
+    ) -> Result<Self, Error> {
+        Self::new(
+            network,
+            crate::SqlitePersister::new(path, network, &descriptor)?,
+            descriptor,
+        )
+    }
+
     /// Create a new wallet which not persist anything
     pub fn without_persist(
         network: ElementsNetwork,
@@ -152,6 +183,99 @@ impl Wollet {
         Ok(AddressResult::new(address, index))
     }
 
+    /// Same as [`Wollet::address()`], but if the address at `index` already received funds the
+    /// first following unused address is returned
+    ///
+    /// Avoids reusing addresses, which links the transactions paying to them.
+    pub fn address_skip_used(&self, index: Option<u32>) -> Result<AddressResult, Error> {
+        let used = self.used_indexes(Chain::External);
+        let mut index = match index {
+            Some(i) => i,
+            None => self
+                .store
+                .cache
+                .last_unused_external
+                .load(atomic::Ordering::Relaxed),
+        };
+        while used.contains(&index) {
+            index += 1;
+        }
+        self.address(Some(index))
+    }
+
+    /// The derivation indexes of the addresses of `chain` which received funds
+    fn used_indexes(&self, chain: Chain) -> HashSet<u32> {
+        self.address_receipts()
+            .keys()
+            .filter_map(|s| self.index(s).ok())
+            .filter(|(c, _)| *c == chain)
+            .map(|(_, i)| i)
+            .collect()
+    }
+
+    /// Get the derived addresses of `chain` with their usage, at most `limit` starting from the
+    /// derivation index `offset`
+    ///
+    /// The derived addresses are the ones up to the first unused one following all the used ones,
+    /// sorted by derivation index.
+    pub fn addresses(
+        &self,
+        chain: Chain,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<AddressDetails>, Error> {
+        let last_unused = match chain {
+            Chain::External => &self.store.cache.last_unused_external,
+            Chain::Internal => &self.store.cache.last_unused_internal,
+        };
+        let end = last_unused
+            .load(atomic::Ordering::Relaxed)
+            .saturating_add(1)
+            .min(offset.saturating_add(limit));
+
+        let receipts = self.address_receipts();
+        let mut balances: HashMap<Script, BTreeMap<AssetId, u64>> = HashMap::new();
+        for utxo in self.utxos()? {
+            let amount = utxo.amount();
+            add_by_asset(balances.entry(utxo.script_pubkey).or_default(), [amount])?;
+        }
+
+        let mut addresses = vec![];
+        for wildcard_index in offset..end {
+            let address = match chain {
+                Chain::External => self.address(Some(wildcard_index))?,
+                Chain::Internal => self.change(Some(wildcard_index))?,
+            };
+            let address = address.address().clone();
+            let script_pubkey = address.script_pubkey();
+            let tx_count = receipts.get(&script_pubkey).map_or(0, Vec::len) as u32;
+            addresses.push(AddressDetails {
+                address,
+                ext_int: chain,
+                wildcard_index,
+                used: tx_count > 0,
+                tx_count,
+                balance: balances.remove(&script_pubkey).unwrap_or_default(),
+            });
+        }
+        Ok(addresses)
+    }
+
+    /// Prove that the wallet knows the blinding private key of the given confidential address
+    ///
+    /// The proof commits to the whole confidential address and can be checked with
+    /// [`lwk_common::verify_blinding_key_proof()`]. The format is specific to LWK, see
+    /// [`lwk_common::blinding_key_proof()`].
+    pub fn blinding_key_proof(&self, address: &Address) -> Result<Signature, Error> {
+        let script_pubkey = address.script_pubkey();
+        let blinding_key = derive_blinding_key(self.descriptor(), &script_pubkey)
+            .ok_or(lwk_common::Error::MissingPrivateBlindingKey)?;
+        let proof = blinding_key_proof(&blinding_key, &script_pubkey);
+        // Fails if the address does not use the wallet blinding key
+        verify_blinding_key_proof(address, &proof)?;
+        Ok(proof)
+    }
+
     /// Get a wallet change address
     ///
     /// If a specific descriptor is given for change addresses  it's used to derive this address
@@ -189,40 +313,40 @@ impl Wollet {
                 .all_txs
                 .get(tx_id)
                 .ok_or_else(|| Error::Generic(format!("txos no tx {}", tx_id)))?;
-            let tx_txos = tx
-                .output
-                .iter()
-                .enumerate()
-                .map(|(vout, output)| {
-                    (
-                        OutPoint {
-                            txid: *tx_id,
-                            vout: vout as u32,
-                        },
-                        output,
-                    )
-                })
-                .filter(|(outpoint, _)| !spent.contains(outpoint))
-                .filter_map(|(outpoint, output)| {
-                    if let Some(unblinded) = self.store.cache.unblinded.get(&outpoint) {
-                        let index = self.index(&output.script_pubkey).ok()?;
-                        return Some(WalletTxOut {
-                            outpoint,
-                            script_pubkey: output.script_pubkey.clone(),
-                            height: *height,
-                            unblinded: *unblinded,
-                            wildcard_index: index.1,
-                            ext_int: index.0,
-                        });
-                    }
-                    None
-                });
+            let tx_txos = self
+                .tx_txos(*tx_id, tx, *height)
+                .filter(|txo| !spent.contains(&txo.outpoint));
             txos.extend(tx_txos);
         }
 
         Ok(txos)
     }
 
+    /// The outputs of `tx` that the wallet can unblind
+    fn tx_txos<'a>(
+        &'a self,
+        txid: Txid,
+        tx: &'a Transaction,
+        height: Option<u32>,
+    ) -> impl Iterator<Item = WalletTxOut> + 'a {
+        tx.output
+            .iter()
+            .enumerate()
+            .filter_map(move |(vout, output)| {
+                let outpoint = OutPoint::new(txid, vout as u32);
+                let unblinded = self.store.cache.unblinded.get(&outpoint)?;
+                let index = self.index(&output.script_pubkey).ok()?;
+                Some(WalletTxOut {
+                    outpoint,
+                    script_pubkey: output.script_pubkey.clone(),
+                    height,
+                    unblinded: *unblinded,
+                    wildcard_index: index.1,
+                    ext_int: index.0,
+                })
+            })
+    }
+
     /// Get the wallet UTXOs
     pub fn utxos(&self) -> Result<Vec<WalletTxOut>, Error> {
         let mut utxos = self.txos_inner(true)?;
@@ -230,6 +354,204 @@ impl Wollet {
         Ok(utxos)
     }
 
+    /// Lock a wallet UTXO, excluding it from coin selection until [`Wollet::unlock_utxo()`]
+    ///
+    /// Useful to reserve coins for a transaction still being negotiated, for instance a swap.
+    /// Locked UTXOs are still part of the balance. The lock is persisted.
+    pub fn lock_utxo(&mut self, outpoint: OutPoint) -> Result<(), Error> {
+        if !self.utxos()?.iter().any(|u| u.outpoint == outpoint) {
+            return Err(Error::UtxoNotMine(outpoint));
+        }
+        if self.is_utxo_locked(&outpoint) {
+            return Ok(());
+        }
+        let mut update = self.local_update()?;
+        update.utxo_locks.push(outpoint);
+        self.apply_update(update)
+    }
+
+    /// Unlock a UTXO locked with [`Wollet::lock_utxo()`], making it available for coin selection
+    pub fn unlock_utxo(&mut self, outpoint: OutPoint) -> Result<(), Error> {
+        if !self.is_utxo_locked(&outpoint) {
+            return Ok(());
+        }
+        let mut update = self.local_update()?;
+        update.utxo_unlocks.push(outpoint);
+        self.apply_update(update)
+    }
+
+    /// Whether the UTXO has been locked with [`Wollet::lock_utxo()`]
+    pub fn is_utxo_locked(&self, outpoint: &OutPoint) -> bool {
+        self.store.locked.contains(outpoint)
+    }
+
+    /// Get the locked wallet UTXOs which are not spent yet
+    pub fn locked_utxos(&self) -> Result<Vec<WalletTxOut>, Error> {
+        Ok(self
+            .utxos()?
+            .into_iter()
+            .filter(|u| self.is_utxo_locked(&u.outpoint))
+            .collect())
+    }
+
+    /// Set the label of a transaction, address or output, replacing the previous one
+    ///
+    /// Labels are persisted and can be exported with [`Wollet::export_labels()`].
+    pub fn set_label(&mut self, item: LabelRef, label: &str) -> Result<(), Error> {
+        self.check_label_network(&item)?;
+        if self.label(&item) == Some(label) {
+            return Ok(());
+        }
+        let mut update = self.local_update()?;
+        update.labels.push((item, Some(label.to_string())));
+        self.apply_update(update)
+    }
+
+    /// Remove the label set with [`Wollet::set_label()`]
+    pub fn remove_label(&mut self, item: &LabelRef) -> Result<(), Error> {
+        if self.label(item).is_none() {
+            return Ok(());
+        }
+        let mut update = self.local_update()?;
+        update.labels.push((item.clone(), None));
+        self.apply_update(update)
+    }
+
+    /// The label of a transaction, address or output, if set
+    pub fn label(&self, item: &LabelRef) -> Option<&str> {
+        self.store.labels.get(item).map(String::as_str)
+    }
+
+    /// All the labels set in the wallet
+    pub fn labels(&self) -> Vec<(LabelRef, String)> {
+        self.store
+            .labels
+            .iter()
+            .map(|(item, label)| (item.clone(), label.clone()))
+            .collect()
+    }
+
+    /// Export the labels in the [BIP329](https://github.com/bitcoin/bips/blob/master/bip-0329.mediawiki)
+    /// JSON lines format
+    pub fn export_labels(&self) -> String {
+        label::to_jsonl(self.store.labels.iter())
+    }
+
+    /// Import labels in the BIP329 JSON lines format, replacing the labels of the same items
+    ///
+    /// Records of types other than `tx`, `addr` and `output`, or without a label, are skipped.
+    /// Returns the number of labels imported.
+    pub fn import_labels(&mut self, jsonl: &str) -> Result<usize, Error> {
+        let labels = label::from_jsonl(jsonl)?;
+        for (item, _) in labels.iter() {
+            self.check_label_network(item)?;
+        }
+        if labels.is_empty() {
+            return Ok(0);
+        }
+        let count = labels.len();
+        let mut update = self.local_update()?;
+        update.labels = labels.into_iter().map(|(i, l)| (i, Some(l))).collect();
+        self.apply_update(update)?;
+        Ok(count)
+    }
+
+    fn check_label_network(&self, item: &LabelRef) -> Result<(), Error> {
+        match item {
+            LabelRef::Address(address) if address.params != self.config.address_params() => Err(
+                Error::InvalidLabel(format!("{address}: address of another network")),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// The registry info of `asset`, if cached in the wallet
+    ///
+    /// Info is cached with [`Wollet::set_asset_info()`] or [`Wollet::fetch_assets_info()`].
+    pub fn asset_info(&self, asset: &AssetId) -> Option<&AssetInfo> {
+        self.store.assets.get(asset)
+    }
+
+    /// Cache the registry info of `asset`, persisting it with the wallet
+    pub fn set_asset_info(&mut self, asset: AssetId, info: AssetInfo) -> Result<(), Error> {
+        if self.asset_info(&asset) == Some(&info) {
+            return Ok(());
+        }
+        let mut update = self.local_update()?;
+        update.assets.push((asset, info));
+        self.apply_update(update)
+    }
+
+    /// The assets in the wallet transactions without cached registry info, the policy asset
+    /// excluded
+    pub fn assets_without_info(&self) -> Result<Vec<AssetId>, Error> {
+        let policy_asset = self.policy_asset();
+        let assets: std::collections::BTreeSet<AssetId> = self
+            .transactions()?
+            .iter()
+            .flat_map(|tx| tx.balance.keys().cloned())
+            .filter(|asset| *asset != policy_asset && self.asset_info(asset).is_none())
+            .collect();
+        Ok(assets.into_iter().collect())
+    }
+
+    /// Fetch from the asset registry at `registry_url` the info of the wallet assets not cached
+    /// yet, caching it
+    ///
+    /// The info is verified with the issuance transactions fetched with `client`, see
+    /// [`crate::fetch_asset_info()`]. Assets not in the registry are fetched again on the next
+    /// call. Returns the number of assets whose info has been cached.
+    #[cfg(feature = "esplora")]
+    pub fn fetch_assets_info<B: BlockchainBackend + ?Sized>(
+        &mut self,
+        client: &B,
+        registry_url: &str,
+    ) -> Result<usize, Error> {
+        let mut assets = vec![];
+        for asset in self.assets_without_info()? {
+            if let Some(info) = crate::registry::fetch_asset_info(client, registry_url, &asset)? {
+                assets.push((asset, info));
+            }
+        }
+        if assets.is_empty() {
+            return Ok(0);
+        }
+        let count = assets.len();
+        let mut update = self.local_update()?;
+        update.assets = assets;
+        self.apply_update(update)?;
+        Ok(count)
+    }
+
+    fn assets_info(&self, balance: &BTreeMap<AssetId, i64>) -> BTreeMap<AssetId, AssetInfo> {
+        balance
+            .keys()
+            .filter_map(|asset| Some((*asset, self.asset_info(asset)?.clone())))
+            .collect()
+    }
+
+    /// An update changing nothing but the wallet local state, at the current tip
+    fn local_update(&self) -> Result<Update, Error> {
+        let tip = self
+            .store
+            .tip_header
+            .clone()
+            .ok_or_else(|| Error::Generic("The wallet has never been synced".into()))?;
+        Ok(Update {
+            new_txs: Default::default(),
+            txid_height_new: vec![],
+            txid_height_delete: vec![],
+            timestamps: vec![],
+            scripts: HashMap::new(),
+            tip,
+            utxo_locks: vec![],
+            utxo_unlocks: vec![],
+            labels: vec![],
+            assets: vec![],
+            scripts_status: vec![],
+        })
+    }
+
     fn txos(&self) -> Result<HashMap<OutPoint, WalletTxOut>, Error> {
         Ok(self
             .txos_inner(false)?
@@ -244,9 +566,7 @@ impl Wollet {
     ) -> Result<BTreeMap<AssetId, u64>, Error> {
         let mut r = BTreeMap::new();
         r.entry(self.policy_asset()).or_insert(0);
-        for u in utxos.iter() {
-            *r.entry(u.unblinded.asset).or_default() += u.unblinded.value;
-        }
+        add_by_asset(&mut r, utxos.iter().map(WalletTxOut::amount))?;
         Ok(r)
     }
 
@@ -256,8 +576,59 @@ impl Wollet {
         self.balance_from_utxos(&utxos)
     }
 
+    /// Get the wallet balance split by confirmation state
+    ///
+    /// For every asset [`Balance::total()`] matches the amount returned by [`Wollet::balance()`].
+    pub fn detailed_balance(&self) -> Result<BTreeMap<AssetId, Balance>, Error> {
+        let heights = &self.store.cache.heights;
+        // The wallet outputs spent by valid transactions, mapped to whether the spend is confirmed
+        let mut spent = HashMap::new();
+        for txid in heights.keys() {
+            if let Some(tx) = self.store.cache.all_txs.get(txid) {
+                let confirmed = matches!(heights.get(txid), Some(Some(_)));
+                for input in tx.input.iter() {
+                    spent.insert(input.previous_output, confirmed);
+                }
+            }
+        }
+
+        let mut r = BTreeMap::new();
+        r.entry(self.policy_asset())
+            .or_insert_with(Balance::default);
+        for txo in self.txos_inner(false)? {
+            let value = txo.unblinded.value;
+            let (confirmed, incoming, outgoing) =
+                match (txo.height.is_some(), spent.get(&txo.outpoint)) {
+                    (true, None) => (value, 0, 0),
+                    (true, Some(false)) => (value, 0, value),
+                    (false, None) => (0, value, 0),
+                    (true, Some(true)) | (false, Some(_)) => continue,
+                };
+            let asset = txo.unblinded.asset;
+            let balance: &mut Balance = r.entry(asset).or_default();
+            let add = |total: u64, sats| AssetAmount::new(asset, total).checked_add_sats(sats);
+            balance.confirmed = add(balance.confirmed, confirmed)?.sats;
+            balance.unconfirmed_incoming = add(balance.unconfirmed_incoming, incoming)?.sats;
+            balance.unconfirmed_outgoing = add(balance.unconfirmed_outgoing, outgoing)?.sats;
+        }
+        Ok(r)
+    }
+
     /// Get the wallet transactions
     pub fn transactions(&self) -> Result<Vec<WalletTx>, Error> {
+        self.transactions_paginated(0, usize::MAX, &TxFilter::default())
+    }
+
+    /// Get at most `limit` wallet transactions matching `filter`, skipping the first `offset`
+    ///
+    /// Transactions are in the same order of [`Wollet::transactions()`], unconfirmed first and
+    /// then from the most recent, so that consecutive pages can be requested increasing `offset`.
+    pub fn transactions_paginated(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &TxFilter,
+    ) -> Result<Vec<WalletTx>, Error> {
         let mut txs = vec![];
         let mut my_txids: Vec<(&Txid, &Option<u32>)> = self.store.cache.heights.iter().collect();
         my_txids.sort_by(|a, b| {
@@ -271,7 +642,12 @@ impl Wollet {
         });
 
         let txos = self.txos()?;
+        let receipts = self.address_receipts();
+        let mut skipped = 0;
         for (txid, height) in my_txids.iter() {
+            if txs.len() >= limit {
+                break;
+            }
             let tx = self
                 .store
                 .cache
@@ -279,65 +655,396 @@ impl Wollet {
                 .get(*txid)
                 .ok_or_else(|| Error::Generic(format!("list_tx no tx {}", txid)))?;
 
-            let balance = tx_balance(**txid, tx, &txos);
+            if !filter.matches_height(**height) {
+                continue;
+            }
+            // Computing the balance first avoids building the transactions which are not returned
+            let inputs = tx_inputs(tx, &txos);
+            let outputs = tx_outputs(**txid, tx, &txos);
+            let balance = tx_balance(&inputs, &outputs)?;
             if balance.is_empty() {
                 // Transaction has no output or input that the wollet can unblind,
                 // ignore this transaction
                 continue;
             }
-            let fee = tx_fee(tx);
-            let policy_asset = self.policy_asset();
-            let type_ = tx_type(tx, &policy_asset, &balance, fee);
-            let timestamp = height.and_then(|h| self.store.cache.timestamps.get(&h).cloned());
-            let inputs = tx_inputs(tx, &txos);
-            let outputs = tx_outputs(**txid, tx, &txos);
-            txs.push(WalletTx {
-                tx: tx.clone(),
-                txid: **txid,
-                height: **height,
-                balance,
-                fee,
-                type_,
-                timestamp,
-                inputs,
-                outputs,
-            });
+            if !filter.matches_balance(&balance) {
+                continue;
+            }
+            if skipped < offset {
+                skipped += 1;
+                continue;
+            }
+            txs.push(self.wallet_tx(**txid, tx, **height, inputs, outputs, &receipts)?);
+        }
+
+        Ok(txs)
+    }
+
+    /// Get the wallet transactions dropped from the mempool, because evicted or replaced by
+    /// another transaction, see [`TxStatus::Conflicted`]
+    ///
+    /// These transactions are not part of [`Wollet::transactions()`] and do not spend the wallet
+    /// UTXOs, if they are broadcast again and seen by a scan they are moved back. They are
+    /// dropped once their inputs are spent by a confirmed transaction.
+    pub fn conflicted_transactions(&self) -> Result<Vec<WalletTx>, Error> {
+        let mut txids: Vec<&Txid> = self.store.cache.conflicts.keys().collect();
+        txids.sort();
+
+        let txos = self.txos()?;
+        let receipts = self.address_receipts();
+        let mut txs = vec![];
+        for txid in txids {
+            if let Some(wallet_tx) = self.conflicted_wallet_tx(txid, &txos, &receipts)? {
+                txs.push(wallet_tx);
+            }
+        }
+        Ok(txs)
+    }
+
+    fn conflicted_wallet_tx(
+        &self,
+        txid: &Txid,
+        txos: &HashMap<OutPoint, WalletTxOut>,
+        receipts: &HashMap<&Script, Vec<Txid>>,
+    ) -> Result<Option<WalletTx>, Error> {
+        let Some(replaced_by) = self.store.cache.conflicts.get(txid).copied() else {
+            return Ok(None);
+        };
+        let Some(tx) = self.store.cache.all_txs.get(txid) else {
+            return Ok(None);
+        };
+        // The outputs of the conflicted tx are not wallet txos anymore
+        let mut outputs = vec![None; tx.output.len()];
+        for txo in self.tx_txos(*txid, tx, None) {
+            let vout = txo.outpoint.vout as usize;
+            outputs[vout] = Some(txo);
         }
+        let inputs = tx_inputs(tx, txos);
+        let mut wallet_tx = self.wallet_tx(*txid, tx, None, inputs, outputs, receipts)?;
+        wallet_tx.zero_conf = None;
+        wallet_tx.status = TxStatus::Conflicted { replaced_by };
+        Ok(Some(wallet_tx))
+    }
+
+    fn wallet_tx(
+        &self,
+        txid: Txid,
+        tx: &Transaction,
+        height: Option<u32>,
+        inputs: Vec<Option<WalletTxOut>>,
+        outputs: Vec<Option<WalletTxOut>>,
+        receipts: &HashMap<&Script, Vec<Txid>>,
+    ) -> Result<WalletTx, Error> {
+        let balance = tx_balance(&inputs, &outputs)?;
+        let fee = tx_fee(tx);
+        let policy_asset = self.policy_asset();
+        let type_ = tx_type(tx, &policy_asset, &balance, fee);
+        let timestamp = height.and_then(|h| self.store.cache.timestamps.get(&h).cloned());
+        let zero_conf = self.zero_conf_risk(tx, height, fee);
+        let address_reused = address_reused(&txid, tx, receipts);
+        let assets = self.assets_info(&balance);
+        Ok(WalletTx {
+            tx: tx.clone(),
+            txid,
+            height,
+            balance,
+            fee,
+            type_,
+            timestamp,
+            inputs,
+            outputs,
+            zero_conf,
+            address_reused,
+            assets,
+            status: TxStatus::from_height(height),
+        })
+    }
+
+    /// Get the wallet transactions, classifying as "self-transfer" the ones moving funds only
+    /// between this wallet and `accounts`
+    ///
+    /// `accounts` are other wallets of the same user, see
+    /// [`crate::TxBuilder::add_internal_recipient()`].
+    pub fn transactions_with_accounts(&self, accounts: &[&Wollet]) -> Result<Vec<WalletTx>, Error> {
+        self.transactions_paginated_with_accounts(0, usize::MAX, &TxFilter::default(), accounts)
+    }
 
+    /// Same as [`Wollet::transactions_paginated()`], classifying the transactions as
+    /// [`Wollet::transactions_with_accounts()`] does
+    pub fn transactions_paginated_with_accounts(
+        &self,
+        offset: usize,
+        limit: usize,
+        filter: &TxFilter,
+        accounts: &[&Wollet],
+    ) -> Result<Vec<WalletTx>, Error> {
+        let mut txs = self.transactions_paginated(offset, limit, filter)?;
+        for tx in txs.iter_mut() {
+            if (tx.type_ == "incoming" || tx.type_ == "outgoing")
+                && self.is_self_transfer(&tx.tx, accounts)
+            {
+                tx.type_ = "self-transfer".to_string();
+            }
+        }
         Ok(txs)
     }
 
-    /// Get a wallet transaction
+    /// Whether all the inputs and outputs of `tx` belong to this wallet or to `accounts`, with at
+    /// least one belonging to `accounts`
+    fn is_self_transfer(&self, tx: &Transaction, accounts: &[&Wollet]) -> bool {
+        let is_account = |s: &Script| accounts.iter().any(|a| a.store.cache.paths.contains_key(s));
+        // Previous transactions are downloaded only by the wallet spending them
+        let prevout_script = |o: &OutPoint| {
+            std::iter::once(self)
+                .chain(accounts.iter().copied())
+                .find_map(|w| w.store.cache.all_txs.get(&o.txid))
+                .and_then(|t| t.output.get(o.vout as usize))
+                .map(|o| &o.script_pubkey)
+        };
+        let mut scripts = vec![];
+        for input in tx.input.iter() {
+            match prevout_script(&input.previous_output) {
+                Some(script) => scripts.push(script),
+                None => return false,
+            }
+        }
+        scripts.extend(
+            tx.output
+                .iter()
+                .filter(|o| !o.is_fee())
+                .map(|o| &o.script_pubkey),
+        );
+        let mut involves_account = false;
+        for script in scripts {
+            if is_account(script) {
+                involves_account = true;
+            } else if !self.store.cache.paths.contains_key(script) {
+                return false;
+            }
+        }
+        involves_account
+    }
+
+    /// Get a wallet transaction, including the conflicted ones
     pub fn transaction(&self, txid: &Txid) -> Result<Option<WalletTx>, Error> {
         let height = self.store.cache.heights.get(txid);
         let tx = self.store.cache.all_txs.get(txid);
         if let (Some(height), Some(tx)) = (height, tx) {
             let txos = self.txos()?;
-
-            let balance = tx_balance(*txid, tx, &txos);
-            let fee = tx_fee(tx);
-            let policy_asset = self.policy_asset();
-            let type_ = tx_type(tx, &policy_asset, &balance, fee);
-            let timestamp = height.and_then(|h| self.store.cache.timestamps.get(&h).cloned());
+            let receipts = self.address_receipts();
             let inputs = tx_inputs(tx, &txos);
             let outputs = tx_outputs(*txid, tx, &txos);
-
-            Ok(Some(WalletTx {
-                tx: tx.clone(),
-                txid: *txid,
-                height: *height,
-                balance,
-                fee,
-                type_,
-                timestamp,
-                inputs,
-                outputs,
-            }))
+            Ok(Some(self.wallet_tx(
+                *txid, tx, *height, inputs, outputs, &receipts,
+            )?))
+        } else if self.store.cache.conflicts.contains_key(txid) {
+            let txos = self.txos()?;
+            let receipts = self.address_receipts();
+            self.conflicted_wallet_tx(txid, &txos, &receipts)
         } else {
             Ok(None)
         }
     }
 
+    fn zero_conf_risk(
+        &self,
+        tx: &Transaction,
+        height: Option<u32>,
+        fee: u64,
+    ) -> Option<ZeroConfRisk> {
+        if height.is_some() {
+            return None;
+        }
+        let vsize = tx.vsize().max(1) as u64;
+        let replaceable = tx.input.iter().any(|i| i.sequence.is_rbf());
+
+        // Walk the unconfirmed ancestors known by the wallet one generation at a time
+        let mut unconfirmed_depth = 0;
+        let mut seen = HashSet::new();
+        let mut generation = vec![tx];
+        loop {
+            let parents: Vec<_> = generation
+                .iter()
+                .flat_map(|t| t.input.iter().map(|i| i.previous_output.txid))
+                .filter(|txid| self.store.cache.heights.get(txid) == Some(&None))
+                .filter(|txid| seen.insert(*txid))
+                .filter_map(|txid| self.store.cache.all_txs.get(&txid))
+                .collect();
+            if parents.is_empty() {
+                break;
+            }
+            unconfirmed_depth += 1;
+            generation = parents;
+        }
+
+        Some(ZeroConfRisk {
+            fee_rate: fee * 1000 / vsize,
+            replaceable,
+            unconfirmed_depth,
+        })
+    }
+
+    /// The transactions receiving funds on every wallet script, in order of confirmation with
+    /// unconfirmed transactions last
+    fn address_receipts(&self) -> HashMap<&Script, Vec<Txid>> {
+        let mut txids: Vec<(&Txid, &Option<u32>)> = self.store.cache.heights.iter().collect();
+        txids.sort_by_key(|(txid, height)| (height.unwrap_or(u32::MAX), **txid));
+
+        let mut receipts: HashMap<&Script, Vec<Txid>> = HashMap::new();
+        for (txid, _) in txids {
+            let tx = match self.store.cache.all_txs.get(txid) {
+                Some(tx) => tx,
+                None => continue,
+            };
+            for output in tx.output.iter() {
+                if self.store.cache.paths.contains_key(&output.script_pubkey) {
+                    let txids = receipts.entry(&output.script_pubkey).or_default();
+                    if txids.last() != Some(txid) {
+                        txids.push(*txid);
+                    }
+                }
+            }
+        }
+        receipts
+    }
+
+    /// Get the wallet addresses that received funds in more than one transaction
+    ///
+    /// Sorted by external/internal chain and derivation index.
+    pub fn reused_addresses(&self) -> Result<Vec<ReusedAddress>, Error> {
+        let mut reused = vec![];
+        for (script_pubkey, txids) in self.address_receipts() {
+            if txids.len() < 2 {
+                continue;
+            }
+            let (ext_int, wildcard_index) = self.index(script_pubkey)?;
+            let address = match ext_int {
+                Chain::External => self.address(Some(wildcard_index))?,
+                Chain::Internal => self.change(Some(wildcard_index))?,
+            };
+            reused.push(ReusedAddress {
+                address: address.address().clone(),
+                ext_int,
+                wildcard_index,
+                txids,
+            });
+        }
+        reused.sort_by_key(|r| (r.ext_int, r.wildcard_index));
+        Ok(reused)
+    }
+
+    /// Get aggregate figures of the wallet activity
+    ///
+    /// Computed from the data already in the wallet, without network calls.
+    pub fn summary(&self) -> Result<WalletSummary, Error> {
+        let txs = self.transactions()?;
+        let mut received = BTreeMap::new();
+        let mut sent = BTreeMap::new();
+        for tx in txs.iter() {
+            for (asset, value) in tx.balance.iter() {
+                let total = match *value > 0 {
+                    true => &mut received,
+                    false => &mut sent,
+                };
+                add_by_asset(total, [AssetAmount::new(*asset, value.unsigned_abs())])?;
+            }
+        }
+        let timestamps = txs.iter().filter_map(|tx| tx.timestamp);
+        Ok(WalletSummary {
+            txs: txs.len(),
+            utxos: self.utxos()?.len(),
+            used_addresses: self.address_receipts().len(),
+            first_activity: timestamps.clone().min(),
+            last_activity: timestamps.max(),
+            received,
+            sent,
+            tip_height: self.tip().height(),
+        })
+    }
+
+    /// Get the unconfirmed ancestors and descendants of a wallet transaction, with their aggregate
+    /// fee and size
+    ///
+    /// Returns `None` if the transaction is confirmed.
+    pub fn tx_package_info(&self, txid: &Txid) -> Result<Option<TxPackageInfo>, Error> {
+        let height = self
+            .store
+            .cache
+            .heights
+            .get(txid)
+            .ok_or(Error::MissingTransaction)?;
+        if height.is_some() {
+            return Ok(None);
+        }
+        let unconfirmed: HashMap<Txid, &Transaction> = self
+            .store
+            .cache
+            .heights
+            .iter()
+            .filter(|(_, h)| h.is_none())
+            .filter_map(|(t, _)| self.store.cache.all_txs.get(t).map(|tx| (*t, tx)))
+            .collect();
+        let tx = unconfirmed.get(txid).ok_or(Error::MissingTransaction)?;
+
+        let mut children: HashMap<Txid, Vec<Txid>> = HashMap::new();
+        for (child, child_tx) in unconfirmed.iter() {
+            for input in child_tx.input.iter() {
+                children
+                    .entry(input.previous_output.txid)
+                    .or_default()
+                    .push(*child);
+            }
+        }
+
+        let mut ancestors = vec![];
+        let mut seen = HashSet::from([*txid]);
+        let mut queue = vec![*tx];
+        while let Some(t) = queue.pop() {
+            for input in t.input.iter() {
+                let parent = input.previous_output.txid;
+                if let Some(parent_tx) = unconfirmed.get(&parent) {
+                    if seen.insert(parent) {
+                        ancestors.push(parent);
+                        queue.push(parent_tx);
+                    }
+                }
+            }
+        }
+
+        let mut descendants = vec![];
+        let mut seen = HashSet::from([*txid]);
+        let mut queue = vec![*txid];
+        while let Some(t) = queue.pop() {
+            for child in children.get(&t).into_iter().flatten() {
+                if seen.insert(*child) {
+                    descendants.push(*child);
+                    queue.push(*child);
+                }
+            }
+        }
+
+        let fee_vsize = |txids: &[Txid]| {
+            txids
+                .iter()
+                .chain(std::iter::once(txid))
+                .filter_map(|t| unconfirmed.get(t))
+                .fold((0, 0), |(fee, vsize), t| {
+                    (fee + tx_fee(t), vsize + t.vsize() as u64)
+                })
+        };
+        let (ancestors_fee, ancestors_vsize) = fee_vsize(&ancestors);
+        let (descendants_fee, descendants_vsize) = fee_vsize(&descendants);
+
+        Ok(Some(TxPackageInfo {
+            ancestors,
+            descendants,
+            ancestors_fee,
+            ancestors_vsize,
+            descendants_fee,
+            descendants_vsize,
+        }))
+    }
+
     /// Get the wallet (re)issuances
     pub fn issuances(&self) -> Result<Vec<IssuanceDetails>, Error> {
         let mut r = vec![];
@@ -358,25 +1065,171 @@ impl Wollet {
             .ok_or_else(|| Error::MissingIssuance)
     }
 
+    /// Get the reissuance tokens owned by the wallet, with the asset they can reissue
+    ///
+    /// Tokens are mapped to their asset using the (re)issuances in the wallet transactions,
+    /// tokens received from issuances not involving this wallet are not reported.
+    pub fn reissuance_tokens(&self) -> Result<Vec<ReissuanceToken>, Error> {
+        let assets: HashMap<AssetId, AssetId> = self
+            .issuances()?
+            .iter()
+            .map(|i| (i.token, i.asset))
+            .collect();
+        Ok(self
+            .balance()?
+            .into_iter()
+            .filter(|(_, satoshi)| *satoshi > 0)
+            .filter_map(|(token, satoshi)| {
+                assets.get(&token).map(|asset| ReissuanceToken {
+                    token,
+                    asset: *asset,
+                    satoshi,
+                })
+            })
+            .collect())
+    }
+
+    /// Set a fee paid to the application in every transaction built by this wallet, `None` to
+    /// remove it
+    pub fn set_service_fee(&mut self, service_fee: Option<ServiceFee>) {
+        self.service_fee = service_fee;
+    }
+
+    /// The service fee paid in every transaction built by this wallet
+    pub fn service_fee(&self) -> Option<&ServiceFee> {
+        self.service_fee.as_ref()
+    }
+
+    /// Warn in [`PsetDetails::warnings`] if the fee of a PSET is higher than `basis_points`
+    /// (hundredths of a percent) of the L-BTC sent, `None` to disable the check
+    pub fn set_max_fee_basis_points(&mut self, basis_points: Option<u32>) {
+        self.max_fee_basis_points = basis_points;
+    }
+
+    /// The maximum fee in basis points of the L-BTC sent before warning in the PSET details
+    pub fn max_fee_basis_points(&self) -> Option<u32> {
+        self.max_fee_basis_points
+    }
+
+    /// Set the number of consecutive unused addresses after which scans stop looking for more
+    ///
+    /// The default is 20. Wallets that handed out many addresses not paid yet need a higher gap
+    /// limit to find the funds received on the following addresses, up to [`MAX_GAP_LIMIT`].
+    /// The gap limit is not persisted, it must be set every time the wallet is created.
+    pub fn set_gap_limit(&mut self, gap_limit: u32) -> Result<(), Error> {
+        if gap_limit == 0 || gap_limit > MAX_GAP_LIMIT {
+            return Err(Error::InvalidGapLimit);
+        }
+        self.gap_limit = gap_limit;
+        Ok(())
+    }
+
+    /// The number of consecutive unused addresses after which scans stop looking for more
+    pub fn gap_limit(&self) -> u32 {
+        self.gap_limit
+    }
+
     /// Get the PSET details with respect to the wallet
     pub fn get_details(&self, pset: &PartiallySignedTransaction) -> Result<PsetDetails, Error> {
+        let balance = pset_balance(pset, self.descriptor())?;
+        let warnings = self.pset_warnings(pset, &balance);
         Ok(PsetDetails {
-            balance: pset_balance(pset, self.descriptor())?,
+            balance,
             sig_details: pset_signatures(pset),
             issuances: pset_issuances(pset),
+            service_fee: self.service_fee_output(pset),
+            warnings,
+            outputs: pset_outputs(pset, self.descriptor()),
         })
     }
 
-    pub(crate) fn index(&self, script_pubkey: &Script) -> Result<(Chain, u32), Error> {
-        let (ext_int, index) = self
-            .store
-            .cache
-            .paths
-            .get(script_pubkey)
-            .ok_or_else(|| Error::ScriptNotMine)?;
-        let index = match index {
-            ChildNumber::Normal { index } => index,
-            ChildNumber::Hardened { index: _ } => {
+    fn pset_warnings(
+        &self,
+        pset: &PartiallySignedTransaction,
+        balance: &PsetBalance,
+    ) -> Vec<PsetWarning> {
+        let mut warnings = vec![];
+        let params = self.config.address_params();
+        let cache = &self.store.cache;
+        let last_unused_external = cache.last_unused_external.load(atomic::Ordering::Relaxed);
+        let last_unused_internal = cache.last_unused_internal.load(atomic::Ordering::Relaxed);
+        for (vout, output) in pset.outputs().iter().enumerate() {
+            let index = output.bip32_derivation.values().find_map(|(_, path)| {
+                match path.into_iter().last() {
+                    Some(ChildNumber::Normal { index }) => Some(*index),
+                    _ => None,
+                }
+            });
+            let index = match index {
+                Some(index) => index,
+                None => continue,
+            };
+            let is_script = |address: Result<Address, Error>| {
+                address.map_or(false, |a| a.script_pubkey() == output.script_pubkey)
+            };
+            let (change, last_unused) = if is_script(self.descriptor.address(index, params)) {
+                (false, last_unused_external)
+            } else if is_script(self.descriptor.change(index, params)) {
+                (true, last_unused_internal)
+            } else {
+                continue;
+            };
+            if index >= last_unused.saturating_add(self.gap_limit) {
+                warnings.push(PsetWarning::FarAddressIndex {
+                    vout: vout as u32,
+                    change,
+                    index,
+                    last_unused,
+                });
+            }
+        }
+
+        if let Some(max_basis_points) = self.max_fee_basis_points {
+            let policy_balance = balance
+                .balances
+                .get(&self.policy_asset())
+                .copied()
+                .unwrap_or(0);
+            let sent = policy_balance
+                .min(0)
+                .unsigned_abs()
+                .saturating_sub(balance.fee);
+            if sent > 0 && balance.fee as u128 * 10_000 > sent as u128 * max_basis_points as u128 {
+                warnings.push(PsetWarning::HighFee {
+                    fee: balance.fee,
+                    sent,
+                    max_basis_points,
+                });
+            }
+        }
+        warnings
+    }
+
+    fn service_fee_output(&self, pset: &PartiallySignedTransaction) -> Option<ServiceFeeOutput> {
+        let script_pubkey = self.service_fee.as_ref()?.address.script_pubkey();
+        let policy_asset = self.policy_asset();
+        pset.outputs()
+            .iter()
+            .enumerate()
+            .find(|(_, o)| o.script_pubkey == script_pubkey && o.asset == Some(policy_asset))
+            .and_then(|(vout, o)| {
+                Some(ServiceFeeOutput {
+                    vout: vout as u32,
+                    satoshi: o.amount?,
+                })
+            })
+    }
+
+    pub(crate) fn index(&self, script_pubkey: &Script) -> Result<(Chain, u32), Error> {
+        let (ext_int, index) = self
+            .store
+            .cache
+            .paths
+            .get(script_pubkey)
+            .ok_or_else(|| Error::ScriptNotMine)?;
+        let index = match index {
+            ChildNumber::Normal { index } => index,
+            ChildNumber::Hardened { index: _ } => {
                 return Err(Error::Generic("unexpected hardened derivation".into()));
             }
         };
@@ -456,10 +1309,46 @@ impl Wollet {
         Ok(res)
     }
 
+    /// Combine a vector of PSET and try to finalize the result
+    ///
+    /// Useful when the signers of a multisig wallet sign copies of the same PSET. The inputs that
+    /// cannot be finalized yet are reported with the signers that did not sign them.
+    pub fn combine_and_finalize(
+        &self,
+        psets: &[PartiallySignedTransaction],
+    ) -> Result<CombinedPset, Error> {
+        let pset = self.combine(psets)?;
+        let mut finalized = pset.clone();
+        let mut missing_signatures = BTreeMap::new();
+        for (vin, signatures) in pset_signatures(&pset).into_iter().enumerate() {
+            // genesis_hash is only used for BIP341 (taproot) sighash computation
+            if finalized
+                .finalize_inp_mut(&EC, vin, BlockHash::all_zeros())
+                .is_err()
+            {
+                let fingerprints = signatures
+                    .missing_signature
+                    .iter()
+                    .map(|(_, (fingerprint, _))| *fingerprint)
+                    .collect();
+                missing_signatures.insert(vin, fingerprints);
+            }
+        }
+        let tx = match missing_signatures.is_empty() {
+            true => Some(extract_tx(&finalized)?),
+            false => None,
+        };
+        Ok(CombinedPset {
+            pset,
+            tx,
+            missing_signatures,
+        })
+    }
+
     pub fn finalize(&self, pset: &mut PartiallySignedTransaction) -> Result<Transaction, Error> {
         // genesis_hash is only used for BIP341 (taproot) sighash computation
         psbt::finalize(pset, &EC, BlockHash::all_zeros())?;
-        Ok(pset.extract_tx()?)
+        extract_tx(pset)
     }
 
     pub fn updates(&self) -> Result<Vec<Update>, PersistError> {
@@ -489,27 +1378,50 @@ impl Wollet {
     pub fn never_scanned(&self) -> bool {
         self.store.cache.tip == (0, BlockHash::all_zeros())
     }
-}
 
-fn tx_balance(
-    txid: Txid,
-    tx: &Transaction,
-    txos: &HashMap<OutPoint, WalletTxOut>,
-) -> BTreeMap<AssetId, i64> {
-    debug_assert_eq!(txid, tx.txid());
-    let mut balance = BTreeMap::new();
-
-    for out_idx in 0..tx.output.len() {
-        if let Some(txout) = txos.get(&OutPoint::new(txid, out_idx as u32)) {
-            *balance.entry(txout.unblinded.asset).or_default() += txout.unblinded.value as i64;
+    /// Refresh only the transactions involving `script`, for instance the script pubkey of an
+    /// address waiting for a payment
+    ///
+    /// See [`BlockchainBackend::script_scan()`], returns true if the wallet changed.
+    pub fn sync_script<B: BlockchainBackend>(
+        &mut self,
+        client: &mut B,
+        script: &Script,
+    ) -> Result<bool, Error> {
+        match client.script_scan(self, script)? {
+            Some(update) => {
+                self.apply_update(update)?;
+                Ok(true)
+            }
+            None => Ok(false),
         }
     }
-    for input in &tx.input {
-        if let Some(txout) = txos.get(&input.previous_output) {
-            *balance.entry(txout.unblinded.asset).or_default() -= txout.unblinded.value as i64;
+
+    /// Refresh only the scripts changed since the previous scan, much faster than a full scan for
+    /// wallets already synced
+    ///
+    /// See [`BlockchainBackend::incremental_scan()`], returns true if the wallet changed.
+    pub fn incremental_scan<B: BlockchainBackend + ?Sized>(
+        &mut self,
+        client: &mut B,
+    ) -> Result<bool, Error> {
+        match client.incremental_scan(self)? {
+            Some(update) => {
+                self.apply_update(update)?;
+                Ok(true)
+            }
+            None => Ok(false),
         }
     }
-    balance
+}
+
+fn tx_balance(
+    inputs: &[Option<WalletTxOut>],
+    outputs: &[Option<WalletTxOut>],
+) -> Result<BTreeMap<AssetId, i64>, Error> {
+    let received = outputs.iter().flatten().map(WalletTxOut::amount);
+    let spent = inputs.iter().flatten().map(WalletTxOut::amount);
+    Ok(net_by_asset(received, spent)?)
 }
 
 #[cfg(feature = "electrum")]
@@ -517,8 +1429,6 @@ pub fn full_scan_with_electrum_client(
     wollet: &mut Wollet,
     electrum_client: &mut crate::ElectrumClient,
 ) -> Result<(), Error> {
-    use crate::BlockchainBackend;
-
     let update = electrum_client.full_scan(wollet)?;
     if let Some(update) = update {
         wollet.apply_update(update)?
@@ -560,6 +1470,8 @@ fn tx_type(
         "reissuance".to_string()
     } else if tx.output.iter().any(|o| o.script_pubkey == burn_script) {
         "burn".to_string()
+    } else if tx.output.iter().any(|o| o.is_pegout()) {
+        "pegout".to_string()
     } else if balance.len() == 1 && balance.get(policy_asset) == Some(&(fee as i64)) {
         "redeposit".to_string()
     } else if balance.is_empty() {
@@ -574,6 +1486,18 @@ fn tx_type(
     }
 }
 
+/// Extract the transaction from a finalized PSET
+fn extract_tx(pset: &PartiallySignedTransaction) -> Result<Transaction, Error> {
+    let mut tx = pset.extract_tx()?;
+    // Peg-in inputs are flagged only in the final transaction, see `TxBuilder::claim_pegin()`
+    for (txin, input) in tx.input.iter_mut().zip(pset.inputs()) {
+        if input.pegin_value.is_some() {
+            txin.is_pegin = true;
+        }
+    }
+    Ok(tx)
+}
+
 fn tx_inputs(tx: &Transaction, txos: &HashMap<OutPoint, WalletTxOut>) -> Vec<Option<WalletTxOut>> {
     tx.input
         .iter()
@@ -593,6 +1517,20 @@ fn tx_outputs(
         .collect()
 }
 
+fn address_reused(
+    txid: &Txid,
+    tx: &Transaction,
+    receipts: &HashMap<&Script, Vec<Txid>>,
+) -> Vec<bool> {
+    tx.output
+        .iter()
+        .map(|o| match receipts.get(&o.script_pubkey) {
+            Some(txids) => txids.first() != Some(txid),
+            None => false,
+        })
+        .collect()
+}
+
 /// Blockchain tip
 pub struct Tip {
     height: Height,
@@ -613,7 +1551,7 @@ impl Tip {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
     use std::str::FromStr;
@@ -622,12 +1560,12 @@ mod tests {
     use crate::elements::bitcoin::bip32::{Xpriv, Xpub};
     use crate::elements::bitcoin::network::Network;
     use crate::elements::AddressParams;
-    use crate::NoPersist;
+    use crate::{NoPersist, Recipient, Warning};
     use elements_miniscript::confidential::bare::tweak_private_key;
     use elements_miniscript::confidential::Key;
     use elements_miniscript::descriptor::checksum::desc_checksum;
     use elements_miniscript::descriptor::DescriptorSecretKey;
-    use lwk_common::{singlesig_desc, DescriptorBlindingKey, Singlesig};
+    use lwk_common::{singlesig_desc, DescriptorBlindingKey, OutputKind, Singlesig};
     use lwk_signer::SwSigner;
 
     #[test]
@@ -680,7 +1618,7 @@ mod tests {
         let _desc = ConfidentialDescriptor::<DefiniteDescriptorKey>::from_str(&desc_str).unwrap();
     }
 
-    fn new_wollet(desc: &str) -> Wollet {
+    pub(crate) fn new_wollet(desc: &str) -> Wollet {
         let desc: WolletDescriptor = format!("{}#{}", desc, desc_checksum(desc).unwrap())
             .parse()
             .unwrap();
@@ -716,6 +1654,24 @@ mod tests {
         assert_eq!(new_wollet(&desc_m_12).signers(), vec![fp1, fp2]);
     }
 
+    #[test]
+    fn test_blinding_key_proof() {
+        let view_key = "1111111111111111111111111111111111111111111111111111111111111111";
+        let xpub = "tpubDD7tXK8KeQ3YY83yWq755fHY2JW8Ha8Q765tknUM5rSvjPcGWfUppDFMpQ1ScziKfW3ZNtZvAD7M3u7bSs7HofjTD3KP3YxPK7X6hwV8Rk2";
+        let wollet = new_wollet(&format!("ct({view_key},elwpkh({xpub}/*))"));
+        let address = wollet.address(Some(3)).unwrap().address().clone();
+        let proof = wollet.blinding_key_proof(&address).unwrap();
+        verify_blinding_key_proof(&address, &proof).unwrap();
+
+        // Another wallet with the same script but a different blinding key
+        let other_view_key = "2222222222222222222222222222222222222222222222222222222222222222";
+        let other = new_wollet(&format!("ct({other_view_key},elwpkh({xpub}/*))"));
+        let other_address = other.address(Some(3)).unwrap().address().clone();
+        assert_eq!(address.script_pubkey(), other_address.script_pubkey());
+        assert!(verify_blinding_key_proof(&other_address, &proof).is_err());
+        assert!(wollet.blinding_key_proof(&other_address).is_err());
+    }
+
     #[test]
     fn test_apply_old_update() {
         let bytes = lwk_test_util::update_test_vector_bytes();
@@ -808,4 +1764,1262 @@ mod tests {
 
         assert_eq!(4667218140179748739, wollet.status());
     }
+
+    /// An update with a single transaction paying `satoshi` to the first external address
+    pub(crate) fn receive_update(wollet: &Wollet, satoshi: u64) -> Update {
+        receive_asset_update(wollet, wollet.policy_asset(), satoshi)
+    }
+
+    pub(crate) fn receive_asset_update(wollet: &Wollet, asset: AssetId, satoshi: u64) -> Update {
+        receive_script_update(wollet, asset, satoshi, Chain::External, 0)
+    }
+
+    /// An update with a single transaction paying `satoshi` to the address at `index` of `chain`
+    fn receive_script_update(
+        wollet: &Wollet,
+        asset: AssetId,
+        satoshi: u64,
+        chain: Chain,
+        index: u32,
+    ) -> Update {
+        let address = match chain {
+            Chain::External => wollet.address(Some(index)),
+            Chain::Internal => wollet.change(Some(index)),
+        };
+        let script_pubkey = address.unwrap().address().script_pubkey();
+        let tx = Transaction {
+            version: 2,
+            lock_time: elements::LockTime::ZERO,
+            input: vec![],
+            output: vec![elements::TxOut {
+                asset: elements::confidential::Asset::Explicit(asset),
+                value: elements::confidential::Value::Explicit(satoshi),
+                nonce: elements::confidential::Nonce::Null,
+                script_pubkey: script_pubkey.clone(),
+                witness: Default::default(),
+            }],
+        };
+        let txid = tx.txid();
+        let secrets = elements::TxOutSecrets::new(
+            asset,
+            elements::confidential::AssetBlindingFactor::zero(),
+            satoshi,
+            elements::confidential::ValueBlindingFactor::zero(),
+        );
+        Update {
+            new_txs: crate::update::DownloadTxResult {
+                txs: vec![(txid, tx)],
+                unblinds: vec![(OutPoint::new(txid, 0), secrets)],
+                unblind_failures: vec![],
+            },
+            txid_height_new: vec![(txid, Some(1))],
+            txid_height_delete: vec![],
+            timestamps: vec![],
+            scripts: [(script_pubkey, (chain, ChildNumber::from(index)))].into(),
+            tip: lwk_test_util::liquid_block_1().header,
+            utxo_locks: vec![],
+            utxo_unlocks: vec![],
+            labels: vec![],
+            assets: vec![],
+            scripts_status: vec![],
+        }
+    }
+
+    #[test]
+    fn test_amount_overflow() {
+        let half = u64::MAX / 2 + 1;
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let asset = wollet.policy_asset();
+        let update = receive_script_update(&wollet, asset, half, Chain::External, 0);
+        wollet.apply_update(update).unwrap();
+        let update = receive_script_update(&wollet, asset, half, Chain::External, 1);
+        wollet.apply_update(update).unwrap();
+
+        // Balances fail instead of wrapping around
+        let overflow = |e| matches!(e, Error::Amount(lwk_common::amount::Error::Overflow(..)));
+        assert!(overflow(wollet.balance().unwrap_err()));
+        assert!(overflow(wollet.detailed_balance().unwrap_err()));
+        let signed_overflow = |e| {
+            matches!(
+                e,
+                Error::Amount(lwk_common::amount::Error::SignedOverflow(..))
+            )
+        };
+        assert!(signed_overflow(wollet.transactions().unwrap_err()));
+        assert!(signed_overflow(wollet.summary().unwrap_err()));
+    }
+
+    #[test]
+    fn test_lock_utxo() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc: WolletDescriptor = format!("{}#{}", exp, desc_checksum(exp).unwrap())
+            .parse()
+            .unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let mut wollet = Wollet::with_fs_persist(network, desc.clone(), &tempdir).unwrap();
+
+        // Nothing to lock before the first sync
+        let outpoint = OutPoint::default();
+        let err = wollet.lock_utxo(outpoint).unwrap_err();
+        assert!(matches!(err, Error::UtxoNotMine(_)));
+
+        let update = receive_update(&wollet, 10_000);
+        wollet.apply_update(update).unwrap();
+        let utxo = wollet.utxos().unwrap()[0].clone();
+        let asset = utxo.unblinded.asset;
+        let balance = wollet.balance().unwrap();
+        let status = wollet.status();
+
+        wollet.lock_utxo(utxo.outpoint).unwrap();
+        wollet.lock_utxo(utxo.outpoint).unwrap(); // idempotent
+        assert!(wollet.is_utxo_locked(&utxo.outpoint));
+        assert_eq!(wollet.locked_utxos().unwrap(), vec![utxo.clone()]);
+        assert!(!wollet
+            .asset_utxos(&asset)
+            .unwrap()
+            .iter()
+            .any(|u| u.outpoint == utxo.outpoint));
+        assert_eq!(wollet.balance().unwrap(), balance);
+        assert_ne!(wollet.status(), status);
+
+        let err = wollet
+            .tx_builder()
+            .set_wallet_utxos(vec![utxo.outpoint])
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::UtxoLocked(o) if o == utxo.outpoint));
+
+        // The lock is persisted
+        let mut wollet = Wollet::with_fs_persist(network, desc.clone(), &tempdir).unwrap();
+        assert!(wollet.is_utxo_locked(&utxo.outpoint));
+
+        wollet.unlock_utxo(utxo.outpoint).unwrap();
+        assert!(!wollet.is_utxo_locked(&utxo.outpoint));
+        assert_eq!(wollet.status(), status);
+        let wollet = Wollet::with_fs_persist(network, desc, &tempdir).unwrap();
+        assert!(!wollet.is_utxo_locked(&utxo.outpoint));
+        assert!(wollet.locked_utxos().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_summary() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let summary = wollet.summary().unwrap();
+        assert_eq!(summary.txs, 0);
+        assert_eq!(summary.last_activity, None);
+        assert!(summary.received.is_empty());
+
+        let mut update = receive_update(&wollet, 10_000);
+        update.timestamps = vec![(1, 1_700_000_000)];
+        wollet.apply_update(update).unwrap();
+        let summary = wollet.summary().unwrap();
+        assert_eq!(summary.txs, 1);
+        assert_eq!(summary.utxos, 1);
+        assert_eq!(summary.used_addresses, 1);
+        assert_eq!(summary.first_activity, Some(1_700_000_000));
+        assert_eq!(summary.last_activity, Some(1_700_000_000));
+        assert_eq!(summary.received.get(&wollet.policy_asset()), Some(&10_000));
+        assert!(summary.sent.is_empty());
+        assert_eq!(summary.tip_height, 1);
+    }
+
+    #[test]
+    fn test_pegout() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let script = crate::bitcoin::ScriptBuf::from_bytes(vec![0x51]);
+        let address = |network| {
+            let address = crate::bitcoin::Address::p2wsh(&script, network);
+            address.to_string().parse().unwrap()
+        };
+
+        // Liquid Testnet enforces PAK
+        let testnet = new_wollet(exp);
+        let err = testnet
+            .tx_builder()
+            .add_pegout(&address(crate::bitcoin::Network::Testnet), 10_000)
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidPegout(_)));
+
+        let desc: WolletDescriptor = exp.parse().unwrap();
+        let mut wollet = Wollet::without_persist(ElementsNetwork::default_regtest(), desc).unwrap();
+        let update = receive_update(&wollet, 100_000);
+        wollet.apply_update(update).unwrap();
+        let address = address(crate::bitcoin::Network::Regtest);
+        let pset = wollet
+            .tx_builder()
+            .add_pegout(&address, 10_000)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let tx = pset.extract_tx().unwrap();
+        let pegout = tx.output.iter().find_map(|o| o.pegout_data()).unwrap();
+        assert_eq!(pegout.value, 10_000);
+        assert_eq!(pegout.script_pubkey, script.to_p2wsh());
+
+        // Peg-out, change and fee outputs
+        let fee = tx.fee_in(wollet.policy_asset());
+        assert!(fee > 0);
+        assert_eq!(tx.output.len(), 3);
+
+        let err = wollet.tx_builder().add_pegout(&address, 0).unwrap_err();
+        assert!(matches!(err, Error::InvalidAmount));
+    }
+
+    #[test]
+    fn test_pset_rng() {
+        let xpub = "[73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*";
+        let build = |blinding_key: &str| {
+            let mut wollet = new_wollet(&format!("ct({blinding_key},elwpkh({xpub}))"));
+            let update = receive_update(&wollet, 100_000);
+            wollet.apply_update(update).unwrap();
+            let address = wollet.address(Some(10)).unwrap().address().clone();
+            let build = || {
+                wollet
+                    .tx_builder()
+                    .add_lbtc_recipient(&address, 10_000)
+                    .unwrap()
+                    .finish()
+                    .unwrap()
+            };
+            (build(), build())
+        };
+
+        // A private blinding key makes the transaction reproducible
+        let slip77 = "slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023)";
+        let (pset1, pset2) = build(slip77);
+        assert_eq!(pset1, pset2);
+
+        // A blinding key derived from the public descriptor does not
+        let (pset1, pset2) = build("elip151");
+        assert_ne!(pset1, pset2);
+    }
+
+    #[test]
+    fn test_service_fee_drain() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let update = receive_update(&wollet, 100_000);
+        wollet.apply_update(update).unwrap();
+        let fee_address = Address::from_str("tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn").unwrap();
+        wollet.set_service_fee(Some(ServiceFee {
+            address: fee_address.clone(),
+            basis_points: 100,
+            max_satoshi: 10_000,
+        }));
+        let drain_address = wollet.address(Some(10)).unwrap().address().clone();
+
+        // 1% of the drained 100_000
+        let pset = wollet
+            .tx_builder()
+            .drain_lbtc_wallet()
+            .drain_lbtc_to(drain_address.clone())
+            .finish()
+            .unwrap();
+        let details = wollet.get_details(&pset).unwrap();
+        let service_fee = details.service_fee.unwrap();
+        assert_eq!(service_fee.satoshi, 1_000);
+        let output = &pset.outputs()[service_fee.vout as usize];
+        assert_eq!(output.script_pubkey, fee_address.script_pubkey());
+        let drained = pset
+            .outputs()
+            .iter()
+            .find(|o| o.script_pubkey == drain_address.script_pubkey())
+            .unwrap();
+        assert_eq!(
+            drained.amount.unwrap(),
+            100_000 - 1_000 - details.balance.fee
+        );
+
+        // The wallet keeps the change, only the recipient counts
+        let pset = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&drain_address, 10_000)
+            .unwrap()
+            .drain_lbtc_wallet()
+            .finish()
+            .unwrap();
+        let details = wollet.get_details(&pset).unwrap();
+        assert_eq!(details.service_fee.unwrap().satoshi, 100);
+    }
+
+    #[test]
+    fn test_service_fee_internal_recipient() {
+        let xpub = "[73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*";
+        let slip77 = "slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023)";
+        let mut wollet = new_wollet(&format!("ct({slip77},elwpkh({xpub}))"));
+        let desc = lwk_test_util::TEST_DESCRIPTOR.parse().unwrap();
+        let account = Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let update = receive_update(&wollet, 100_000);
+        wollet.apply_update(update).unwrap();
+        let policy_asset = wollet.policy_asset();
+        let fee_address = Address::from_str("tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn").unwrap();
+        wollet.set_service_fee(Some(ServiceFee {
+            address: fee_address,
+            basis_points: 100,
+            max_satoshi: 10_000,
+        }));
+        let other = wollet.address(Some(10)).unwrap().address().clone();
+
+        // Moving funds to another account of the user is not charged
+        let pset = wollet
+            .tx_builder()
+            .add_internal_recipient(&account, 10_000, policy_asset)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(wollet.get_details(&pset).unwrap().service_fee.is_none());
+
+        // Only the L-BTC sent to the other recipients counts
+        let pset = wollet
+            .tx_builder()
+            .add_internal_recipient(&account, 10_000, policy_asset)
+            .unwrap()
+            .add_lbtc_recipient(&other, 20_000)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let details = wollet.get_details(&pset).unwrap();
+        assert_eq!(details.service_fee.unwrap().satoshi, 200);
+
+        // When draining too
+        let pset = wollet
+            .tx_builder()
+            .add_internal_recipient(&account, 10_000, policy_asset)
+            .unwrap()
+            .drain_lbtc_wallet()
+            .drain_lbtc_to(other)
+            .finish()
+            .unwrap();
+        let details = wollet.get_details(&pset).unwrap();
+        assert_eq!(details.service_fee.unwrap().satoshi, 900);
+    }
+
+    #[test]
+    fn test_tx_builder_warnings() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let update = receive_update(&wollet, 100_000);
+        wollet.apply_update(update).unwrap();
+        let policy_asset = wollet.policy_asset();
+
+        // Paying again the unconfidential version of the address which received the funds
+        let used = wollet
+            .address(Some(0))
+            .unwrap()
+            .address()
+            .to_unconfidential();
+        let (_, warnings) = wollet
+            .tx_builder()
+            .add_validated_recipient(Recipient::from_address(10_000, &used, policy_asset))
+            .fee_rate(Some(2000.0))
+            .finish_with_warnings()
+            .unwrap();
+        let script_pubkey = used.script_pubkey();
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::ReusedAddress {
+                    script_pubkey: script_pubkey.clone()
+                },
+                Warning::UnconfidentialRecipient { script_pubkey },
+                Warning::HighFeeRate { fee_rate: 2000.0 },
+            ]
+        );
+
+        // A fresh confidential address and a change too small for an output
+        let fresh = wollet.address(None).unwrap().address().clone();
+        let (pset, warnings) = wollet
+            .tx_builder()
+            .add_recipient(&fresh, 99_700, policy_asset)
+            .unwrap()
+            .finish_with_warnings()
+            .unwrap();
+        let tx = pset.extract_tx().unwrap();
+        assert_eq!(tx.output.len(), 2);
+        assert_eq!(tx.fee_in(policy_asset), 300);
+        match &warnings[..] {
+            [Warning::DustChangeFolded { satoshi }] => assert!(*satoshi > 0 && *satoshi < 300),
+            w => panic!("unexpected warnings {w:?}"),
+        }
+    }
+
+    #[test]
+    fn test_tx_builder_locktime() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let update = receive_update(&wollet, 100_000);
+        wollet.apply_update(update).unwrap();
+        let address = wollet.address(None).unwrap().address().clone();
+
+        let tx = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 10_000)
+            .unwrap()
+            .finish()
+            .unwrap()
+            .extract_tx()
+            .unwrap();
+        assert_eq!(tx.lock_time, elements::LockTime::ZERO);
+
+        let locktime = elements::LockTime::from_height(2_000_000).unwrap();
+        let tx = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 10_000)
+            .unwrap()
+            .locktime(Some(locktime))
+            .finish()
+            .unwrap()
+            .extract_tx()
+            .unwrap();
+        assert_eq!(tx.lock_time, locktime);
+        assert!(tx
+            .input
+            .iter()
+            .all(|i| i.sequence.enables_absolute_lock_time()));
+    }
+
+    #[test]
+    fn test_tx_builder_fee_target() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let update = receive_update(&wollet, 100_000);
+        wollet.apply_update(update).unwrap();
+        let address = wollet.address(None).unwrap().address().clone();
+        let policy_asset = wollet.policy_asset();
+
+        let fee = |builder: WolletTxBuilder| {
+            builder
+                .add_lbtc_recipient(&address, 10_000)
+                .unwrap()
+                .finish()
+                .unwrap()
+                .extract_tx()
+                .unwrap()
+                .fee_in(policy_asset)
+        };
+        let estimates: BTreeMap<u16, f32> = [(2, 1000.0)].into_iter().collect();
+        let explicit = fee(wollet.tx_builder().fee_rate(Some(1000.0)));
+        let target = fee(wollet.tx_builder().fee_target(6, &estimates));
+        assert_eq!(target, explicit);
+
+        // Without estimates the fallback fee rates are used
+        let explicit = fee(wollet.tx_builder().fee_rate(Some(250.0)));
+        let target = fee(wollet.tx_builder().fee_target(1, &BTreeMap::new()));
+        assert_eq!(target, explicit);
+    }
+
+    #[test]
+    fn test_tx_builder_drain_asset() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let update = receive_update(&wollet, 100_000);
+        wollet.apply_update(update).unwrap();
+        let asset = AssetId::from_slice(&[1; 32]).unwrap();
+        let update = receive_asset_update(&wollet, asset, 1_000);
+        wollet.apply_update(update).unwrap();
+        let policy_asset = wollet.policy_asset();
+        let address = wollet.address(Some(5)).unwrap().address().clone();
+        let amounts = |pset: &PartiallySignedTransaction, asset: AssetId| {
+            let mut amounts: Vec<u64> = pset
+                .outputs()
+                .iter()
+                .filter(|o| o.asset == Some(asset) && !o.script_pubkey.is_empty())
+                .map(|o| o.amount.unwrap())
+                .collect();
+            amounts.sort();
+            amounts
+        };
+
+        // Recipients are paid, the rest goes to the drain address
+        let pset = wollet
+            .tx_builder()
+            .add_recipient(&address, 300, asset)
+            .unwrap()
+            .drain_asset(asset, address.clone())
+            .finish()
+            .unwrap();
+        assert_eq!(amounts(&pset, asset), vec![300, 700]);
+        assert_eq!(pset.inputs().len(), 2);
+
+        // Draining the policy asset subtracts the fee from the drained amount
+        let pset = wollet
+            .tx_builder()
+            .drain_asset(asset, address.clone())
+            .drain_asset(policy_asset, address.clone())
+            .finish()
+            .unwrap();
+        assert_eq!(amounts(&pset, asset), vec![1_000]);
+        let fee = pset.extract_tx().unwrap().fee_in(policy_asset);
+        assert_eq!(amounts(&pset, policy_asset), vec![100_000 - fee]);
+
+        let other_asset = AssetId::from_slice(&[2; 32]).unwrap();
+        let err = wollet
+            .tx_builder()
+            .drain_asset(other_asset, address)
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::InsufficientFunds));
+    }
+
+    #[test]
+    fn test_tx_builder_fee_provider() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let asset = AssetId::from_slice(&[1; 32]).unwrap();
+        let update = receive_asset_update(&wollet, asset, 100_000);
+        wollet.apply_update(update).unwrap();
+        let policy_asset = wollet.policy_asset();
+        let address = wollet.address(Some(5)).unwrap().address().clone();
+
+        // Without L-BTC the wallet cannot pay the fee
+        let err = wollet
+            .tx_builder()
+            .add_recipient(&address, 1_000, asset)
+            .unwrap()
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::InsufficientFunds));
+
+        let secp = elements::secp256k1_zkp::Secp256k1::new();
+        let key = elements::secp256k1_zkp::SecretKey::from_slice(&[3; 32]).unwrap();
+        let pubkey = elements::bitcoin::PublicKey::new(key.public_key(&secp));
+        let provider_address = Address::p2wpkh(&pubkey, None, &AddressParams::LIQUID_TESTNET);
+        let txout = elements::TxOut {
+            asset: elements::confidential::Asset::Explicit(policy_asset),
+            value: elements::confidential::Value::Explicit(10_000),
+            nonce: elements::confidential::Nonce::Null,
+            script_pubkey: provider_address.script_pubkey(),
+            witness: Default::default(),
+        };
+        let secrets = elements::TxOutSecrets::new(
+            policy_asset,
+            elements::confidential::AssetBlindingFactor::zero(),
+            10_000,
+            elements::confidential::ValueBlindingFactor::zero(),
+        );
+        let outpoint = OutPoint::new(elements::hashes::Hash::from_byte_array([4; 32]), 0);
+        let provider = crate::FeeProvider {
+            quote: crate::FeeQuote { asset, rate: 2.5 },
+            outpoint,
+            txout,
+            secrets,
+            satisfaction_weight: 108,
+            address: provider_address.clone(),
+        };
+
+        let pset = wollet
+            .tx_builder()
+            .add_recipient(&address, 1_000, asset)
+            .unwrap()
+            .fee_provider(provider.clone())
+            .finish()
+            .unwrap();
+        let fee = pset.extract_tx().unwrap().fee_in(policy_asset);
+        let paid = |asset: AssetId| -> Vec<u64> {
+            let script_pubkey = provider_address.script_pubkey();
+            pset.outputs()
+                .iter()
+                .filter(|o| o.asset == Some(asset) && o.script_pubkey == script_pubkey)
+                .map(|o| o.amount.unwrap())
+                .collect()
+        };
+        assert!(pset
+            .inputs()
+            .iter()
+            .any(|i| i.previous_txid == outpoint.txid));
+        assert_eq!(paid(policy_asset), vec![10_000 - fee]);
+        assert_eq!(paid(asset), vec![provider.quote.asset_fee(&pset)]);
+        assert_eq!(
+            provider.quote.asset_fee(&pset),
+            (fee as f64 * 2.5).ceil() as u64
+        );
+
+        // The provider input cannot fund L-BTC recipients
+        let err = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 1_000)
+            .unwrap()
+            .fee_provider(provider.clone())
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::FeeProviderWithLbtc));
+
+        let mut other = provider;
+        other.secrets.asset = asset;
+        let err = wollet
+            .tx_builder()
+            .add_recipient(&address, 1_000, asset)
+            .unwrap()
+            .fee_provider(other)
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::FeeProviderNotLbtc));
+    }
+
+    #[test]
+    fn test_tx_builder_reuse_change() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let mut wollet = new_wollet(exp);
+        let update = receive_update(&wollet, 100_000);
+        wollet.apply_update(update).unwrap();
+        let address = wollet.address(Some(5)).unwrap().address().clone();
+        let change_0 = wollet.change(Some(0)).unwrap().address().script_pubkey();
+        let change_1 = wollet.change(Some(1)).unwrap().address().script_pubkey();
+        let has_output = |pset: &PartiallySignedTransaction, script: &Script| {
+            pset.outputs().iter().any(|o| &o.script_pubkey == script)
+        };
+
+        let pset = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 10_000)
+            .unwrap()
+            .finish()
+            .unwrap();
+        assert!(has_output(&pset, &change_0));
+
+        // Once the first change address is used, a rebuild pays a new one
+        let policy_asset = wollet.policy_asset();
+        let update = receive_script_update(&wollet, policy_asset, 1_000, Chain::Internal, 0);
+        wollet.apply_update(update).unwrap();
+        let rebuilt = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 10_000)
+            .unwrap()
+            .fee_rate(Some(200.0))
+            .finish()
+            .unwrap();
+        assert!(has_output(&rebuilt, &change_1));
+
+        // unless the change of the previous version is reused
+        let rebuilt = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 10_000)
+            .unwrap()
+            .fee_rate(Some(200.0))
+            .reuse_change_of(&pset)
+            .finish()
+            .unwrap();
+        assert!(has_output(&rebuilt, &change_0));
+    }
+
+    #[test]
+    fn test_combine_and_finalize() {
+        use lwk_common::Signer;
+        let signers: Vec<SwSigner> = (0..2).map(|_| SwSigner::random(false).unwrap().0).collect();
+        let path = "84h/1h/0h";
+        let xpubs: Vec<String> = signers
+            .iter()
+            .map(|s| {
+                let derivation_path = format!("m/{path}").parse().unwrap();
+                let xpub = s.derive_xpub(&derivation_path).unwrap();
+                let fingerprint = s.fingerprint();
+                format!("[{fingerprint}/{path}]{xpub}/<0;1>/*")
+            })
+            .collect();
+        let exp = format!(
+            "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwsh(multi(2,{})))",
+            xpubs.join(",")
+        );
+        let mut wollet = new_wollet(&exp);
+        let update = receive_update(&wollet, 100_000);
+        wollet.apply_update(update).unwrap();
+
+        let address = wollet.address(Some(1)).unwrap();
+        let pset = wollet
+            .tx_builder()
+            .add_lbtc_recipient(address.address(), 10_000)
+            .unwrap()
+            .finish()
+            .unwrap();
+        let signed: Vec<_> = signers
+            .iter()
+            .map(|s| {
+                let mut pset = pset.clone();
+                assert_eq!(s.sign(&mut pset).unwrap(), 1);
+                pset
+            })
+            .collect();
+
+        let combined = wollet.combine_and_finalize(&signed[..1]).unwrap();
+        assert!(combined.tx.is_none());
+        let missing = BTreeMap::from([(0, vec![signers[1].fingerprint()])]);
+        assert_eq!(combined.missing_signatures, missing);
+
+        let combined = wollet.combine_and_finalize(&signed).unwrap();
+        assert!(combined.missing_signatures.is_empty());
+        let tx = combined.tx.unwrap();
+        assert_eq!(tx.txid(), pset.extract_tx().unwrap().txid());
+        assert_eq!(tx.input[0].witness.script_witness.len(), 4);
+        assert_eq!(
+            combined.pset.inputs()[0].partial_sigs.len(),
+            2,
+            "the combined PSET is not finalized"
+        );
+    }
+
+    #[test]
+    fn test_labels() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc: WolletDescriptor = format!("{}#{}", exp, desc_checksum(exp).unwrap())
+            .parse()
+            .unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let mut wollet = Wollet::with_fs_persist(network, desc.clone(), &tempdir).unwrap();
+        let update = receive_update(&wollet, 10_000);
+        wollet.apply_update(update).unwrap();
+        let status = wollet.status();
+
+        let utxo = wollet.utxos().unwrap()[0].clone();
+        let tx = LabelRef::Tx(utxo.outpoint.txid);
+        let output = LabelRef::Output(utxo.outpoint);
+        let address = LabelRef::Address(wollet.address(Some(0)).unwrap().address().clone());
+        wollet.set_label(tx.clone(), "tx").unwrap();
+        wollet.set_label(address.clone(), "address").unwrap();
+        wollet.set_label(output.clone(), "output").unwrap();
+        wollet.set_label(output.clone(), "utxo").unwrap();
+        assert_eq!(wollet.label(&output), Some("utxo"));
+        assert_ne!(wollet.status(), status);
+
+        let mainnet = Address::from_str("lq1qqvxk052kf3qtkxmrakx50a9gc3smqad2ync54hzntjt980kfej9kkfe0247rp5h4yzmdftsahhw64uy8pzfe7cpg4fgykm7cv").unwrap();
+        let err = wollet
+            .set_label(LabelRef::Address(mainnet), "mainnet")
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidLabel(_)));
+
+        // Labels are persisted
+        let mut wollet = Wollet::with_fs_persist(network, desc.clone(), &tempdir).unwrap();
+        assert_eq!(wollet.labels().len(), 3);
+        assert_eq!(wollet.label(&address), Some("address"));
+
+        let exported = wollet.export_labels();
+        assert_eq!(exported.lines().count(), 3);
+        assert!(exported.contains(r#""type":"output""#));
+
+        wollet.remove_label(&tx).unwrap();
+        assert_eq!(wollet.label(&tx), None);
+        let mut other = Wollet::without_persist(network, desc).unwrap();
+        let update = receive_update(&other, 10_000);
+        other.apply_update(update).unwrap();
+        assert_eq!(other.import_labels(&exported).unwrap(), 3);
+        assert_eq!(other.label(&tx), Some("tx"));
+        assert_eq!(other.label(&output), Some("utxo"));
+    }
+
+    /// An unconfirmed update with a transaction spending `prevout` to the change address at
+    /// `index`, paying `fee`
+    fn spend_update(
+        wollet: &Wollet,
+        prevout: OutPoint,
+        satoshi: u64,
+        fee: u64,
+        index: u32,
+    ) -> Update {
+        let asset = wollet.policy_asset();
+        let mut update = receive_script_update(wollet, asset, satoshi, Chain::Internal, index);
+        let (txid, tx) = &mut update.new_txs.txs[0];
+        tx.input.push(elements::TxIn {
+            previous_output: prevout,
+            ..Default::default()
+        });
+        tx.output.push(elements::TxOut::new_fee(fee, asset));
+        *txid = tx.txid();
+        let txid = *txid;
+        update.new_txs.unblinds[0].0 = OutPoint::new(txid, 0);
+        update.txid_height_new = vec![(txid, None)];
+        update
+    }
+
+    #[test]
+    fn test_conflicted_transactions() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc: WolletDescriptor = format!("{}#{}", exp, desc_checksum(exp).unwrap())
+            .parse()
+            .unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let mut wollet = Wollet::with_fs_persist(network, desc.clone(), &tempdir).unwrap();
+        let policy_asset = wollet.policy_asset();
+        let update = receive_update(&wollet, 10_000);
+        let funding = update.new_txs.txs[0].0;
+        wollet.apply_update(update).unwrap();
+        let prevout = OutPoint::new(funding, 0);
+
+        let update = spend_update(&wollet, prevout, 9_000, 1_000, 0);
+        let spend = update.new_txs.txs[0].0;
+        wollet.apply_update(update).unwrap();
+        let txs = wollet.transactions().unwrap();
+        assert_eq!(txs[0].txid, spend);
+        assert_eq!(txs[0].status, TxStatus::Mempool);
+        assert_eq!(txs[1].status, TxStatus::Confirmed(1));
+        assert_eq!(wollet.balance().unwrap()[&policy_asset], 9_000);
+        assert!(wollet.conflicted_transactions().unwrap().is_empty());
+
+        // The spending tx is evicted from the mempool
+        let mut update = receive_update(&wollet, 10_000);
+        update.new_txs = Default::default();
+        update.txid_height_new = vec![];
+        update.scripts.clear();
+        update.txid_height_delete = vec![spend];
+        wollet.apply_update(update).unwrap();
+        assert_eq!(wollet.transactions().unwrap().len(), 1);
+        assert_eq!(wollet.balance().unwrap()[&policy_asset], 10_000);
+        let conflicted = wollet.conflicted_transactions().unwrap();
+        assert_eq!(conflicted.len(), 1);
+        assert_eq!(conflicted[0].txid, spend);
+        assert_eq!(
+            conflicted[0].status,
+            TxStatus::Conflicted { replaced_by: None }
+        );
+        assert_eq!(conflicted[0].balance[&policy_asset], -1_000);
+        assert_eq!(wollet.transaction(&spend).unwrap().unwrap(), conflicted[0]);
+
+        // A replacement spending the same input is detected
+        let update = spend_update(&wollet, prevout, 8_000, 2_000, 1);
+        let replacement = update.new_txs.txs[0].0;
+        wollet.apply_update(update).unwrap();
+        assert_eq!(wollet.balance().unwrap()[&policy_asset], 8_000);
+        let expected = TxStatus::Conflicted {
+            replaced_by: Some(replacement),
+        };
+        assert_eq!(
+            wollet.conflicted_transactions().unwrap()[0].status,
+            expected
+        );
+
+        // Conflicts are restored when reloading the wallet
+        let wollet = Wollet::with_fs_persist(network, desc, &tempdir).unwrap();
+        assert_eq!(
+            wollet.conflicted_transactions().unwrap()[0].status,
+            expected
+        );
+        assert_eq!(wollet.transactions().unwrap().len(), 2);
+
+        // Once the replacement is confirmed the conflict is dropped
+        let mut wollet = wollet;
+        let mut update = receive_update(&wollet, 10_000);
+        update.new_txs = Default::default();
+        update.scripts.clear();
+        update.txid_height_new = vec![(replacement, Some(2))];
+        wollet.apply_update(update).unwrap();
+        assert!(wollet.conflicted_transactions().unwrap().is_empty());
+        assert!(wollet.transaction(&spend).unwrap().is_none());
+        assert_eq!(wollet.balance().unwrap()[&policy_asset], 8_000);
+    }
+
+    #[test]
+    fn test_addresses() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc: WolletDescriptor = format!("{}#{}", exp, desc_checksum(exp).unwrap())
+            .parse()
+            .unwrap();
+        let mut wollet = Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let policy_asset = wollet.policy_asset();
+        let addresses = wollet.addresses(Chain::External, 0, 10).unwrap();
+        assert_eq!(addresses.len(), 1);
+        assert!(!addresses[0].used);
+
+        // Receive twice on index 0 and once on index 2, leaving index 1 unused
+        for (index, satoshi) in [(0, 1_000), (0, 2_000), (2, 1_000)] {
+            let update =
+                receive_script_update(&wollet, policy_asset, satoshi, Chain::External, index);
+            wollet.apply_update(update).unwrap();
+        }
+        let addresses = wollet.addresses(Chain::External, 0, 10).unwrap();
+        let used: Vec<_> = addresses.iter().map(|a| a.used).collect();
+        assert_eq!(used, vec![true, false, true, false]);
+        assert_eq!(addresses[0].tx_count, 2);
+        assert_eq!(addresses[0].balance[&policy_asset], 3_000);
+        assert!(addresses[1].balance.is_empty());
+        assert_eq!(
+            addresses[2].address,
+            *wollet.address(Some(2)).unwrap().address()
+        );
+
+        let page = wollet.addresses(Chain::External, 1, 2).unwrap();
+        let indexes: Vec<_> = page.iter().map(|a| a.wildcard_index).collect();
+        assert_eq!(indexes, vec![1, 2]);
+        assert!(wollet.addresses(Chain::Internal, 0, 10).unwrap()[0]
+            .balance
+            .is_empty());
+
+        assert_eq!(wollet.address_skip_used(Some(0)).unwrap().index(), 1);
+        assert_eq!(wollet.address_skip_used(Some(2)).unwrap().index(), 3);
+        assert_eq!(wollet.address_skip_used(None).unwrap().index(), 3);
+        assert_eq!(wollet.address(Some(0)).unwrap().index(), 0);
+    }
+
+    #[test]
+    fn test_detailed_balance() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc: WolletDescriptor = format!("{}#{}", exp, desc_checksum(exp).unwrap())
+            .parse()
+            .unwrap();
+        let mut wollet = Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let policy_asset = wollet.policy_asset();
+        let update = receive_update(&wollet, 10_000);
+        let funding = update.new_txs.txs[0].0;
+        wollet.apply_update(update).unwrap();
+        let mut update = receive_script_update(&wollet, policy_asset, 3_000, Chain::External, 1);
+        let incoming = update.new_txs.txs[0].0;
+        update.txid_height_new = vec![(incoming, None)];
+        wollet.apply_update(update).unwrap();
+        let update = spend_update(&wollet, OutPoint::new(funding, 0), 9_000, 1_000, 0);
+        wollet.apply_update(update).unwrap();
+
+        let balance = wollet.detailed_balance().unwrap()[&policy_asset];
+        let expected = Balance {
+            confirmed: 10_000,
+            unconfirmed_incoming: 12_000,
+            unconfirmed_outgoing: 10_000,
+        };
+        assert_eq!(balance, expected);
+        assert_eq!(balance.total(), wollet.balance().unwrap()[&policy_asset]);
+    }
+
+    #[test]
+    fn test_transactions_paginated() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc: WolletDescriptor = format!("{}#{}", exp, desc_checksum(exp).unwrap())
+            .parse()
+            .unwrap();
+        let mut wollet = Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let update = receive_update(&wollet, 10_000);
+        let funding = update.new_txs.txs[0].0;
+        wollet.apply_update(update).unwrap();
+        let asset = AssetId::from_slice(&[1; 32]).unwrap();
+        let mut update = receive_script_update(&wollet, asset, 5, Chain::External, 1);
+        let issued = update.new_txs.txs[0].0;
+        update.txid_height_new = vec![(issued, Some(5))];
+        wollet.apply_update(update).unwrap();
+        let update = spend_update(&wollet, OutPoint::new(funding, 0), 9_000, 1_000, 0);
+        let spend = update.new_txs.txs[0].0;
+        wollet.apply_update(update).unwrap();
+
+        let txids = |offset, limit, filter: TxFilter| -> Vec<Txid> {
+            wollet
+                .transactions_paginated(offset, limit, &filter)
+                .unwrap()
+                .iter()
+                .map(|tx| tx.txid)
+                .collect()
+        };
+        let all = TxFilter::default();
+        assert_eq!(
+            txids(0, usize::MAX, all.clone()),
+            vec![spend, issued, funding]
+        );
+        assert_eq!(
+            wollet.transactions_paginated(0, 10, &all).unwrap(),
+            wollet.transactions().unwrap()
+        );
+        assert_eq!(txids(0, 2, all.clone()), vec![spend, issued]);
+        assert_eq!(txids(2, 2, all.clone()), vec![funding]);
+        assert!(txids(3, 2, all).is_empty());
+
+        let by_asset = TxFilter {
+            asset: Some(asset),
+            ..Default::default()
+        };
+        assert_eq!(txids(0, usize::MAX, by_asset), vec![issued]);
+        let outgoing = TxFilter {
+            direction: Some(crate::TxDirection::Outgoing),
+            ..Default::default()
+        };
+        assert_eq!(txids(0, usize::MAX, outgoing), vec![spend]);
+        let incoming = TxFilter {
+            direction: Some(crate::TxDirection::Incoming),
+            ..Default::default()
+        };
+        assert_eq!(txids(1, usize::MAX, incoming), vec![funding]);
+        let above = TxFilter {
+            min_height: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(txids(0, usize::MAX, above), vec![spend, issued]);
+        let below = TxFilter {
+            max_height: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(txids(0, usize::MAX, below), vec![funding]);
+    }
+
+    #[test]
+    fn test_unblind_failures() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc: WolletDescriptor = format!("{}#{}", exp, desc_checksum(exp).unwrap())
+            .parse()
+            .unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let mut wollet = Wollet::with_fs_persist(network, desc.clone(), &tempdir).unwrap();
+        let mut update = receive_update(&wollet, 10_000);
+        let (txid, tx) = &mut update.new_txs.txs[0];
+        // A second output to the wallet with a null value cannot be unblinded
+        let mut output = tx.output[0].clone();
+        output.value = elements::confidential::Value::Null;
+        tx.output.push(output);
+        *txid = tx.txid();
+        let tx = tx.clone();
+        update.new_txs.unblinds[0].0 = OutPoint::new(tx.txid(), 0);
+        update.txid_height_new = vec![(tx.txid(), Some(1))];
+
+        let mut result = crate::update::DownloadTxResult::default();
+        crate::clients::unblind_outputs(
+            std::iter::once(&tx),
+            &update.scripts,
+            &wollet.store,
+            &wollet.wollet_descriptor(),
+            &mut result,
+        );
+        assert_eq!(result.unblinds.len(), 1);
+        assert_eq!(result.unblind_failures, vec![OutPoint::new(tx.txid(), 1)]);
+        update.new_txs.unblind_failures = result.unblind_failures;
+        wollet.apply_update(update).unwrap();
+
+        // Outputs already processed are not unblinded again, also after reloading the wallet
+        let wollet = Wollet::with_fs_persist(network, desc, &tempdir).unwrap();
+        let mut result = crate::update::DownloadTxResult::default();
+        crate::clients::unblind_outputs(
+            std::iter::once(&tx),
+            &HashMap::new(),
+            &wollet.store,
+            &wollet.wollet_descriptor(),
+            &mut result,
+        );
+        assert!(result.is_empty());
+        assert_eq!(wollet.balance().unwrap()[&wollet.policy_asset()], 10_000);
+    }
+
+    #[test]
+    fn test_assets_info() {
+        let exp = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))";
+        let desc: WolletDescriptor = format!("{}#{}", exp, desc_checksum(exp).unwrap())
+            .parse()
+            .unwrap();
+        let tempdir = tempfile::tempdir().unwrap();
+        let network = ElementsNetwork::LiquidTestnet;
+        let mut wollet = Wollet::with_fs_persist(network, desc.clone(), &tempdir).unwrap();
+        let update = receive_update(&wollet, 10_000);
+        wollet.apply_update(update).unwrap();
+        let asset = AssetId::from_slice(&[1; 32]).unwrap();
+        let update = receive_asset_update(&wollet, asset, 100);
+        wollet.apply_update(update).unwrap();
+        assert_eq!(wollet.assets_without_info().unwrap(), vec![asset]);
+        let status = wollet.status();
+
+        let info = crate::AssetInfo {
+            name: "Test asset".into(),
+            ticker: "TEST".into(),
+            precision: 2,
+            domain: "example.com".into(),
+            verified: true,
+        };
+        wollet.set_asset_info(asset, info.clone()).unwrap();
+        assert_eq!(wollet.asset_info(&asset), Some(&info));
+        assert!(wollet.assets_without_info().unwrap().is_empty());
+        assert_ne!(wollet.status(), status);
+
+        let txs = wollet.transactions().unwrap();
+        let tx = txs
+            .iter()
+            .find(|tx| tx.balance.contains_key(&asset))
+            .unwrap();
+        assert_eq!(tx.assets.get(&asset), Some(&info));
+        let tx = txs
+            .iter()
+            .find(|tx| !tx.balance.contains_key(&asset))
+            .unwrap();
+        assert!(tx.assets.is_empty());
+
+        // Info is persisted
+        let wollet = Wollet::with_fs_persist(network, desc, &tempdir).unwrap();
+        assert_eq!(wollet.asset_info(&asset), Some(&info));
+    }
+
+    #[test]
+    fn test_unblinded_not_regtest() {
+        let desc: WolletDescriptor = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))".parse().unwrap();
+        let mut wollet = Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let update = receive_update(&wollet, 10_000);
+        wollet.apply_update(update).unwrap();
+        let address = wollet.address(None).unwrap().address().clone();
+        let err = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 1_000)
+            .unwrap()
+            .unblinded(true)
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::UnblindedNotRegtest));
+    }
+
+    #[test]
+    fn test_memo_opreturn() {
+        let mut wollet = new_wollet("ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))");
+        let update = receive_update(&wollet, 10_000);
+        wollet.apply_update(update).unwrap();
+        let address = wollet.address(Some(5)).unwrap().address().clone();
+
+        let err = wollet.tx_builder().add_memo_opreturn(&[]).err().unwrap();
+        assert!(matches!(err, Error::InvalidMemo(_)));
+        let too_long = [1u8; crate::MAX_MEMO_OPRETURN + 1];
+        let err = wollet
+            .tx_builder()
+            .add_memo_opreturn(&too_long)
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::InvalidMemo(_)));
+        let err = wollet
+            .tx_builder()
+            .add_memo_opreturn(b"order-1")
+            .unwrap()
+            .add_memo_opreturn(b"order-2")
+            .err()
+            .unwrap();
+        assert!(matches!(err, Error::InvalidMemo(_)));
+
+        let pset = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 1_000)
+            .unwrap()
+            .add_memo_opreturn(b"order-1")
+            .unwrap()
+            .finish()
+            .unwrap();
+        let tx = pset.extract_tx().unwrap();
+        let memos: Vec<_> = tx
+            .output
+            .iter()
+            .filter_map(|o| crate::model::memo_data(&o.script_pubkey))
+            .collect();
+        assert_eq!(memos, vec![b"order-1".to_vec()]);
+        let memo = tx
+            .output
+            .iter()
+            .find(|o| o.script_pubkey.is_provably_unspendable())
+            .unwrap();
+        assert_eq!(memo.value, elements::confidential::Value::Explicit(0));
+    }
+
+    #[test]
+    fn test_unconfidential_wallet() {
+        let desc: WolletDescriptor = "elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*)".parse().unwrap();
+        assert!(!desc.is_confidential());
+        let mut wollet = Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        assert!(!wollet.address(None).unwrap().address().is_blinded());
+        assert!(!wollet.change(None).unwrap().address().is_blinded());
+
+        let update = receive_update(&wollet, 10_000);
+        wollet.apply_update(update).unwrap();
+        assert_eq!(wollet.balance().unwrap()[&wollet.policy_asset()], 10_000);
+
+        // Paying a confidential address requires to opt in
+        let other = new_wollet("ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))");
+        let address = other.address(Some(5)).unwrap().address().clone();
+        let err = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 1_000)
+            .unwrap()
+            .finish()
+            .unwrap_err();
+        assert!(matches!(err, Error::ConfidentialRecipientExplicitWallet(_)));
+
+        // Then all the outputs are explicit, on any network
+        let (pset, warnings) = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&address, 1_000)
+            .unwrap()
+            .unblinded(true)
+            .finish_with_warnings()
+            .unwrap();
+        assert!(warnings.is_empty());
+        assert!(pset
+            .outputs()
+            .iter()
+            .all(|o| o.asset_comm.is_none() && o.blinding_key.is_none()));
+
+        let details = wollet.get_details(&pset).unwrap();
+        assert!(details.balance.fee > 0);
+        assert!(details.outputs.contains(&OutputKind::External));
+    }
+
+    #[test]
+    fn test_pset_warnings() {
+        let desc: WolletDescriptor = "ct(slip77(9c8e4f05c7711a98c838be228bcb84924d4570ca53f35fa1c793e58841d47023),elwpkh([73c5da0a/84'/1'/0']tpubDC8msFGeGuwnKG9Upg7DM2b4DaRqg3CUZa5g8v2SRQ6K4NSkxUgd7HsL2XVWbVm39yBA4LAxysQAm397zwQSQoQgewGiYZqrA9DsP4zbQ1M/<0;1>/*))".parse().unwrap();
+        let mut wollet = Wollet::without_persist(ElementsNetwork::LiquidTestnet, desc).unwrap();
+        let update = receive_update(&wollet, 10_000);
+        wollet.apply_update(update).unwrap();
+
+        let near = wollet.address(Some(BATCH_SIZE)).unwrap().address().clone();
+        let far = wollet
+            .address(Some(BATCH_SIZE + 1))
+            .unwrap()
+            .address()
+            .clone();
+        let mut pset = wollet
+            .tx_builder()
+            .add_lbtc_recipient(&near, 1_000)
+            .unwrap()
+            .add_lbtc_recipient(&far, 1_000)
+            .unwrap()
+            .finish()
+            .unwrap();
+        // Outputs not yet cached by the wallet have no key derivation, add it as a coordinator
+        // claiming they are addresses of the wallet would do
+        let vout = |pset: &PartiallySignedTransaction, address: &Address| {
+            let script_pubkey = address.script_pubkey();
+            pset.outputs()
+                .iter()
+                .position(|o| o.script_pubkey == script_pubkey)
+                .unwrap()
+        };
+        let (near_vout, far_vout) = (vout(&pset, &near), vout(&pset, &far));
+        let change = wollet.change(None).unwrap();
+        let change_vout = vout(&pset, change.address());
+        for (vout, chain, index) in [
+            (near_vout, Chain::External, BATCH_SIZE),
+            (far_vout, Chain::External, BATCH_SIZE + 1),
+            (change_vout, Chain::Internal, change.index()),
+        ] {
+            let desc = wollet.descriptor.definite_descriptor(chain, index).unwrap();
+            pset.update_output_with_descriptor(vout, &desc).unwrap();
+        }
+        let details = wollet.get_details(&pset).unwrap();
+        assert_eq!(details.change_outputs(), vec![change_vout as u32]);
+        assert_eq!(details.outputs[near_vout], OutputKind::Receive);
+        assert_eq!(
+            details.warnings,
+            vec![PsetWarning::FarAddressIndex {
+                vout: far_vout as u32,
+                change: false,
+                index: BATCH_SIZE + 1,
+                last_unused: 1,
+            }]
+        );
+        assert!(details.warnings[0].to_string().contains("far beyond"));
+
+        // The fee is less than 1% of the 2000 sats sent only if they go to someone else
+        wollet.set_max_fee_basis_points(Some(100));
+        let details = wollet.get_details(&pset).unwrap();
+        assert_eq!(details.warnings.len(), 1);
+        for vout in [near_vout, far_vout] {
+            pset.outputs_mut()[vout].bip32_derivation.clear();
+        }
+        let details = wollet.get_details(&pset).unwrap();
+        let fee = details.balance.fee;
+        assert_eq!(
+            details.warnings,
+            vec![PsetWarning::HighFee {
+                fee,
+                sent: 2_000,
+                max_basis_points: 100,
+            }]
+        );
+        wollet.set_max_fee_basis_points(Some(fee as u32 * 5));
+        assert!(wollet.get_details(&pset).unwrap().warnings.is_empty());
+    }
 }
@@ -872,6 +872,7 @@ fn address_status() {
     let server = setup(false);
     let electrum_url = ElectrumUrl::new(&server.electrs.electrum_url, false, false);
     let mut client = ElectrumClient::new(&electrum_url).unwrap();
+    assert_eq!(client.protocol_version(), Some("1.4"));
     let address = server.node_getnewaddress();
     let initial_status = client.address_status(&address).unwrap();
     assert_eq!(initial_status, None);
@@ -889,6 +890,29 @@ fn address_status() {
     assert_eq!(last_status, new_client_status);
 }
 
+#[test]
+fn electrum_verify_headers() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    wallet.fund_btc(&server);
+
+    // Regtest blocks are "signed" with `OP_TRUE`
+    let op_true = elements::Script::from(vec![0x51]);
+    let options = ElectrumOptions::default().verify_headers(op_true);
+    let mut client = ElectrumClient::with_options(&wallet.electrum_url, options).unwrap();
+    let update = client.full_scan(&wallet.wollet).unwrap().unwrap();
+    assert!(!update.only_tip());
+
+    // A server following a different federation is rejected
+    let other = elements::Script::from(vec![0x52]);
+    let options = ElectrumOptions::default().verify_headers(other);
+    let err = ElectrumClient::with_options(&wallet.electrum_url, options).unwrap_err();
+    assert!(matches!(err, Error::InvalidSpvProof(_)));
+}
+
 fn wait_status_change(
     client: &mut ElectrumClient,
     address: &elements::Address,
@@ -904,6 +928,60 @@ fn wait_status_change(
     panic!("status didn't change");
 }
 
+#[test]
+fn test_esplora_client() {
+    let server = setup(true);
+    let url = format!("http://{}", server.electrs.esplora_url.as_ref().unwrap());
+    let mut client = EsploraClient::new(&url).with_concurrency(2);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let descriptor = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let network = ElementsNetwork::default_regtest();
+
+    let descriptor: WolletDescriptor = descriptor.parse().unwrap();
+
+    let mut wollet = Wollet::new(network, NoPersist::new(), descriptor).unwrap();
+
+    let update = client.full_scan(&wollet).unwrap().unwrap();
+    wollet.apply_update(update).unwrap();
+
+    // More transactions than a page of the script history
+    let address = wollet.address(None).unwrap();
+    let txids: Vec<_> = (0..30)
+        .map(|_| server.node_sendtoaddress(address.address(), 10000, None))
+        .collect();
+    server.generate(1);
+
+    let update = wait_esplora_update(&mut client, &wollet, txids.len());
+    wollet.apply_update(update).unwrap();
+    for txid in txids {
+        let tx = wollet.transaction(&txid).unwrap().unwrap();
+        assert!(tx.height.is_some());
+    }
+    assert_eq!(wollet.balance().unwrap()[&network.policy_asset()], 300000);
+
+    let tip = client.tip().unwrap();
+    assert_eq!(client.tip().unwrap(), tip);
+}
+
+fn wait_esplora_update(client: &mut EsploraClient, wollet: &Wollet, txs: usize) -> Update {
+    for _ in 0..50 {
+        if let Some(update) = client.full_scan(wollet).unwrap() {
+            if update
+                .txid_height_new
+                .iter()
+                .filter(|(_, h)| h.is_some())
+                .count()
+                >= txs
+            {
+                return update;
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+    panic!("update didn't arrive");
+}
+
 #[cfg(feature = "esplora_wasm")]
 #[tokio::test]
 async fn test_esplora_wasm_client() {
@@ -1116,6 +1194,194 @@ fn drain() {
     }
 }
 
+#[test]
+fn unblinded() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let signer = AnySigner::Software(signer);
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    let policy_asset = wallet.policy_asset();
+
+    // Explicit outputs are part of the wallet
+    wallet.fund_btc(&server);
+    let address = wallet.address().to_unconfidential();
+    wallet.fund(&server, 100_000, Some(address), None);
+    let explicit: Vec<_> = wallet
+        .wollet
+        .utxos()
+        .unwrap()
+        .into_iter()
+        .filter(|u| u.is_explicit())
+        .collect();
+    assert_eq!(explicit.len(), 1);
+    let balance_before = wallet.balance(&policy_asset);
+
+    // Only the explicit UTXO is spent and all the outputs are explicit
+    let node_address = server.node_getnewaddress();
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 10_000)
+        .unwrap()
+        .unblinded(true)
+        .paranoid(true)
+        .finish()
+        .unwrap();
+    assert_eq!(pset.inputs().len(), 1);
+    assert!(pset.outputs().iter().all(|o| o.asset_comm.is_none()));
+    let details = wallet.wollet.get_details(&pset).unwrap();
+    let fee = details.balance.fee;
+    assert_eq!(
+        *details.balance.balances.get(&policy_asset).unwrap(),
+        -10_000 - fee as i64
+    );
+    wallet.sign(&signer, &mut pset);
+    let txid = wallet.send(&mut pset);
+    let tx = wallet.wollet.transaction(&txid).unwrap().unwrap();
+    assert!(tx.tx.output.iter().all(|o| o.value.is_explicit()));
+    assert_eq!(wallet.balance(&policy_asset), balance_before - 10_000 - fee);
+
+    // The explicit change can be spent again, also in issuances
+    let mut pset = wallet
+        .tx_builder()
+        .issue_asset(10, None, 1, None, None)
+        .unwrap()
+        .unblinded(true)
+        .finish()
+        .unwrap();
+    let asset = pset.inputs()[0].issuance_ids().0;
+    wallet.sign(&signer, &mut pset);
+    wallet.send(&mut pset);
+    assert_eq!(wallet.balance(&asset), 10);
+
+    let err = wallet
+        .tx_builder()
+        .reissue_asset(asset, 10, None, None)
+        .unwrap()
+        .unblinded(true)
+        .finish()
+        .unwrap_err();
+    assert!(matches!(err, Error::UnblindedReissuance));
+}
+
+#[test]
+fn paranoid() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let signer = AnySigner::Software(signer);
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    let policy_asset = wallet.policy_asset();
+    wallet.fund_btc(&server);
+    let asset = wallet.fund_asset(&server);
+
+    // Sending L-BTC and assets with the proofs of the blinded outputs verified
+    let node_address = server.node_getnewaddress();
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 10_000)
+        .unwrap()
+        .add_recipient(&node_address, 1, asset)
+        .unwrap()
+        .paranoid(true)
+        .finish()
+        .unwrap();
+    let balance_before = wallet.balance(&policy_asset);
+    let fee = wallet.wollet.get_details(&pset).unwrap().balance.fee;
+    wallet.sign(&signer, &mut pset);
+    wallet.send(&mut pset);
+    assert_eq!(wallet.balance(&policy_asset), balance_before - 10_000 - fee);
+
+    // Issuances are verified too
+    let mut pset = wallet
+        .tx_builder()
+        .issue_asset(10, None, 1, None, None)
+        .unwrap()
+        .paranoid(true)
+        .finish()
+        .unwrap();
+    let (issued, _token) = pset.inputs()[0].issuance_ids();
+    wallet.sign(&signer, &mut pset);
+    wallet.send(&mut pset);
+    assert_eq!(wallet.balance(&issued), 10);
+}
+
+#[test]
+fn unconfidential_wallet() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let desc = format!("elwpkh({}/*)", signer.xpub());
+    let signer = AnySigner::Software(signer);
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    assert!(!wallet.wollet.wollet_descriptor().is_confidential());
+    assert!(!wallet.address().is_blinded());
+    let policy_asset = wallet.policy_asset();
+
+    wallet.fund_btc(&server);
+    let balance_before = wallet.balance(&policy_asset);
+
+    // The node address is confidential, so the payment must opt in explicit outputs
+    let node_address = server.node_getnewaddress();
+    assert!(wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 10_000)
+        .unwrap()
+        .finish()
+        .is_err());
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 10_000)
+        .unwrap()
+        .unblinded(true)
+        .finish()
+        .unwrap();
+    assert!(pset.outputs().iter().all(|o| o.asset_comm.is_none()));
+    let details = wallet.wollet.get_details(&pset).unwrap();
+    let fee = details.balance.fee;
+    assert_eq!(
+        *details.balance.balances.get(&policy_asset).unwrap(),
+        -10_000 - fee as i64
+    );
+    assert_eq!(details.change_outputs().len(), 1);
+    wallet.sign(&signer, &mut pset);
+    let txid = wallet.send(&mut pset);
+    let tx = wallet.wollet.transaction(&txid).unwrap().unwrap();
+    assert!(tx.tx.output.iter().all(|o| o.value.is_explicit()));
+    assert_eq!(wallet.balance(&policy_asset), balance_before - 10_000 - fee);
+}
+
+#[test]
+fn memo_opreturn() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let signer = AnySigner::Software(signer);
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    let mut receiver = TestWollet::with_test_desc(&server.electrs.electrum_url);
+    wallet.fund_btc(&server);
+
+    // The receiver reconciles the payment with the order identifier
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&receiver.address(), 10_000)
+        .unwrap()
+        .add_memo_opreturn(b"order-42")
+        .unwrap()
+        .finish()
+        .unwrap();
+    wallet.sign(&signer, &mut pset);
+    let txid = wallet.send(&mut pset);
+    receiver.sync();
+    let tx = receiver.wollet.transaction(&txid).unwrap().unwrap();
+    assert_eq!(tx.memos_opreturn(), vec![b"order-42".to_vec()]);
+}
+
 fn wait_tx_update(wallet: &mut TestWollet) {
     let mut client = ElectrumClient::new(&wallet.electrum_url).unwrap();
     for _ in 0..50 {
@@ -1190,3 +1456,599 @@ pub fn new_unsupported_wallet(desc: &str, expected: lwk_wollet::Error) {
         Err(err) => assert_eq!(err.to_string(), expected.to_string()),
     }
 }
+
+#[test]
+fn proof_of_reserves() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let signers = [&AnySigner::Software(signer)];
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    wallet.fund_btc(&server);
+    let (asset, _token) = wallet.issueasset(&signers, 10, 1, None, None);
+    let balance = wallet.wollet.balance().unwrap();
+
+    let message = "auditor nonce";
+    let mut pset = wallet.wollet.reserves_proof_pset(message, None).unwrap();
+    for signer in signers {
+        wallet.sign(signer, &mut pset);
+    }
+    let proof = wallet.wollet.reserves_proof(message, &pset).unwrap();
+    assert_eq!(
+        proof.tx.input[0].previous_output,
+        challenge_outpoint(message)
+    );
+
+    // Unconfirmed utxos are not valid reserves
+    let client = ElectrumClient::new(&wallet.electrum_url).unwrap();
+    let err = proof.verify(message, &client).unwrap_err();
+    assert!(matches!(err, Error::InvalidReservesProof(_)));
+
+    server.generate(1);
+    let reserves = (0..50)
+        .find_map(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            proof.verify(message, &client).ok()
+        })
+        .unwrap();
+    assert_eq!(reserves.get(&asset), Some(&10));
+    for (asset, satoshi) in balance.iter() {
+        assert_eq!(reserves.get(asset).unwrap_or(&0), satoshi);
+    }
+
+    // Different message
+    let err = proof.verify("other nonce", &client).unwrap_err();
+    assert!(matches!(err, Error::InvalidReservesProof(_)));
+
+    // A utxo spent twice
+    let mut duplicated = proof.clone();
+    duplicated.tx.input.push(duplicated.tx.input[1].clone());
+    duplicated.secrets.push(duplicated.secrets[0]);
+    let err = duplicated.verify(message, &client).unwrap_err();
+    assert!(matches!(err, Error::InvalidReservesProof(_)));
+
+    // Not signed
+    let pset = wallet.wollet.reserves_proof_pset(message, None).unwrap();
+    assert!(wallet.wollet.reserves_proof(message, &pset).is_err());
+
+    // Spent utxos are not valid reserves
+    let node_address = server.node_getnewaddress();
+    wallet.send_all_btc(&signers, None, node_address);
+    let err = proof.verify(message, &client).unwrap_err();
+    assert!(matches!(err, Error::InvalidReservesProof(_)));
+}
+
+#[test]
+fn deterministic_tx() {
+    // Building the same payment twice gives the same transaction
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let signers = [&AnySigner::Software(signer)];
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    wallet.fund_btc(&server);
+    let asset = wallet.fund_asset(&server);
+
+    let node_address = server.node_getnewaddress();
+    let build = || {
+        let mut pset = wallet
+            .tx_builder()
+            .add_lbtc_recipient(&node_address, 1_000)
+            .unwrap()
+            .add_recipient(&node_address, 1_000, asset)
+            .unwrap()
+            .finish()
+            .unwrap();
+        for signer in signers {
+            wallet.sign(signer, &mut pset);
+        }
+        wallet.wollet.finalize(&mut pset).unwrap()
+    };
+    let tx1 = build();
+    let tx2 = build();
+    assert_eq!(tx1.txid(), tx2.txid());
+
+    // A different payment is shuffled and blinded differently
+    let pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 1_001)
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert_ne!(pset.extract_tx().unwrap().txid(), tx1.txid());
+}
+
+#[test]
+fn sync_script() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    wallet.fund_btc(&server);
+
+    // Refresh only the script of the address receiving the payment
+    let address = wallet.address();
+    let txid = server.node_sendtoaddress(&address, 10_000, None);
+    let mut client = ElectrumClient::new(&wallet.electrum_url).unwrap();
+    let script = address.script_pubkey();
+    for _ in 0..120 {
+        wallet.wollet.sync_script(&mut client, &script).unwrap();
+        let txs = wallet.wollet.transactions().unwrap();
+        if txs.iter().any(|tx| tx.txid == txid) {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(500));
+    }
+    let policy_asset = wallet.policy_asset();
+    assert_eq!(wallet.balance(&policy_asset), 1_010_000);
+    assert!(!wallet.wollet.sync_script(&mut client, &script).unwrap());
+
+    // The payment is unconfirmed
+    let tx = wallet.wollet.transaction(&txid).unwrap().unwrap();
+    let zero_conf = tx.zero_conf.unwrap();
+    assert!(zero_conf.fee_rate > 0);
+    assert_eq!(zero_conf.unconfirmed_depth, 0);
+
+    // Not a wallet script
+    let script = server.node_getnewaddress().script_pubkey();
+    let err = wallet.wollet.sync_script(&mut client, &script).unwrap_err();
+    assert!(matches!(err, Error::ScriptNotMine));
+}
+
+#[test]
+fn incremental_scan() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    wallet.fund_btc(&server);
+
+    // The scripts are subscribed by the first scan, the second one finds nothing changed
+    let mut client = ElectrumClient::new(&wallet.electrum_url).unwrap();
+    wallet.wollet.incremental_scan(&mut client).unwrap();
+    assert!(!wallet.wollet.incremental_scan(&mut client).unwrap());
+
+    let txid = server.node_sendtoaddress(&wallet.address(), 10_000, None);
+    let wait_tx = |wallet: &mut TestWollet, client: &mut ElectrumClient, confirmed: bool| {
+        for _ in 0..120 {
+            wallet.wollet.incremental_scan(client).unwrap();
+            let tx = wallet.wollet.transaction(&txid).unwrap();
+            if tx.map_or(false, |tx| tx.height.is_some() == confirmed) {
+                return;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(500));
+        }
+        panic!("tx {txid} not found");
+    };
+    wait_tx(&mut wallet, &mut client, false);
+    let policy_asset = wallet.policy_asset();
+    assert_eq!(wallet.balance(&policy_asset), 1_010_000);
+
+    server.generate(1);
+    wait_tx(&mut wallet, &mut client, true);
+
+    // Same transactions of a full scan
+    let other = TestWollet::new(&server.electrs.electrum_url, &desc);
+    let txs = |wallet: &TestWollet| {
+        let mut txs: Vec<_> = wallet
+            .wollet
+            .transactions()
+            .unwrap()
+            .into_iter()
+            .map(|tx| (tx.txid, tx.height))
+            .collect();
+        txs.sort();
+        txs
+    };
+    assert_eq!(txs(&wallet), txs(&other));
+}
+
+#[test]
+fn full_scan_chunked() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    for _ in 0..3 {
+        wallet.fund_btc(&server);
+    }
+
+    // Apply every transaction with a separate update
+    let descriptor: WolletDescriptor = desc.parse().unwrap();
+    let mut wollet =
+        Wollet::without_persist(ElementsNetwork::default_regtest(), descriptor).unwrap();
+    let mut client = ElectrumClient::new(&wallet.electrum_url).unwrap();
+    assert!(client.full_scan_chunked(&mut wollet, 1).unwrap());
+    assert_eq!(wollet.balance().unwrap(), wallet.wollet.balance().unwrap());
+    assert_eq!(
+        wollet.transactions().unwrap(),
+        wallet.wollet.transactions().unwrap()
+    );
+    assert_eq!(
+        wollet.address(None).unwrap().address(),
+        wallet.wollet.address(None).unwrap().address()
+    );
+    assert!(!client.full_scan_chunked(&mut wollet, 1).unwrap());
+}
+
+#[test]
+fn migrate_to() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let signer = AnySigner::Software(signer);
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    wallet.fund_btc(&server);
+    let (asset, _token) = wallet.issueasset(&[&signer], 10, 1, None, None);
+    wallet.fund_btc(&server);
+    wallet.fund_btc(&server);
+
+    let new_signer = generate_signer();
+    let new_desc = format!(
+        "ct({},elwpkh({}/*))",
+        generate_view_key(),
+        new_signer.xpub()
+    );
+    let mut new_wallet = TestWollet::new(&server.electrs.electrum_url, &new_desc);
+    let new_descriptor: WolletDescriptor = new_desc.parse().unwrap();
+    // The new wallet is already in use
+    new_wallet.fund_btc(&server);
+    let start_index = new_wallet.wollet.address(None).unwrap().index();
+    assert_eq!(start_index, 1);
+
+    // Moving funds to a wallet of the same user doesn't pay the service fee
+    let fee_address = server.node_getnewaddress();
+    wallet.wollet.set_service_fee(Some(ServiceFee {
+        address: fee_address.clone(),
+        basis_points: 100,
+        max_satoshi: 10_000,
+    }));
+
+    // 3 L-BTC UTXOs, asset and token, every sweep needs an L-BTC UTXO
+    assert_eq!(wallet.wollet.utxos().unwrap().len(), 5);
+    let plan = wallet
+        .wollet
+        .migrate_to(&new_descriptor, start_index, None, 2)
+        .unwrap();
+    assert_eq!(plan.psets.len(), 3);
+    for (i, pset) in plan.psets.iter().enumerate() {
+        let address = new_descriptor
+            .address(start_index + i as u32, &elements::AddressParams::ELEMENTS)
+            .unwrap();
+        let scripts: Vec<_> = pset.outputs().iter().map(|o| &o.script_pubkey).collect();
+        assert!(scripts.contains(&&address.script_pubkey()));
+        assert!(!scripts.contains(&&fee_address.script_pubkey()));
+    }
+    let progress = wallet.wollet.migration_progress(&plan).unwrap();
+    assert_eq!((progress.total, progress.broadcast), (3, 0));
+
+    for pset in plan.psets.iter() {
+        let mut pset = pset.clone();
+        wallet.sign(&signer, &mut pset);
+        wallet.send(&mut pset);
+    }
+    assert!(wallet.wollet.utxos().unwrap().is_empty());
+    let progress = wallet.wollet.migration_progress(&plan).unwrap();
+    assert_eq!(progress.broadcast, 3);
+    assert!(!progress.is_complete());
+
+    let height = wallet.wollet.tip().height();
+    server.generate(1);
+    wallet.wait_height(height + 1);
+    let progress = wallet.wollet.migration_progress(&plan).unwrap();
+    assert!(progress.is_complete());
+
+    new_wallet.sync();
+    assert_eq!(new_wallet.balance(&asset), 10);
+    let policy_asset = new_wallet.policy_asset();
+    assert!(new_wallet.balance(&policy_asset) > 0);
+}
+
+#[test]
+fn service_fee() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let signer = AnySigner::Software(signer);
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    wallet.fund_btc(&server);
+
+    let fee_address = server.node_getnewaddress();
+    wallet.wollet.set_service_fee(Some(ServiceFee {
+        address: fee_address.clone(),
+        basis_points: 100,
+        max_satoshi: 500,
+    }));
+
+    // 1% of 10_000 is below the cap
+    let node_address = server.node_getnewaddress();
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 10_000)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let details = wallet.wollet.get_details(&pset).unwrap();
+    let service_fee = details.service_fee.unwrap();
+    assert_eq!(service_fee.satoshi, 100);
+    let output = &pset.outputs()[service_fee.vout as usize];
+    assert_eq!(output.script_pubkey, fee_address.script_pubkey());
+    let policy_asset = wallet.policy_asset();
+    assert_eq!(
+        *details.balance.balances.get(&policy_asset).unwrap(),
+        -(10_000 + 100 + details.balance.fee as i64)
+    );
+    wallet.sign(&signer, &mut pset);
+    wallet.send(&mut pset);
+
+    // 1% of 100_000 is capped
+    let pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 100_000)
+        .unwrap()
+        .finish()
+        .unwrap();
+    let details = wallet.wollet.get_details(&pset).unwrap();
+    assert_eq!(details.service_fee.unwrap().satoshi, 500);
+
+    wallet.wollet.set_service_fee(None);
+    let pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 100_000)
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert!(wallet
+        .wollet
+        .get_details(&pset)
+        .unwrap()
+        .service_fee
+        .is_none());
+}
+
+#[test]
+fn tx_package_info() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let signer = AnySigner::Software(signer);
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    wallet.fund_btc(&server);
+    let parent = wallet.wollet.transactions().unwrap()[0].txid;
+
+    // Spend the unconfirmed output
+    let node_address = server.node_getnewaddress();
+    let mut pset = wallet
+        .tx_builder()
+        .add_lbtc_recipient(&node_address, 10_000)
+        .unwrap()
+        .finish()
+        .unwrap();
+    wallet.sign(&signer, &mut pset);
+    let child = wallet.send(&mut pset);
+
+    let child_tx = wallet.wollet.transaction(&child).unwrap().unwrap();
+    let info = wallet.wollet.tx_package_info(&child).unwrap().unwrap();
+    assert_eq!(info.ancestors, vec![parent]);
+    assert!(info.descendants.is_empty());
+    assert_eq!(info.descendants_fee, child_tx.fee);
+    assert_eq!(info.descendants_vsize, child_tx.tx.vsize() as u64);
+    assert!(info.ancestors_vsize > info.descendants_vsize);
+
+    let info = wallet.wollet.tx_package_info(&parent).unwrap().unwrap();
+    assert!(info.ancestors.is_empty());
+    assert_eq!(info.descendants, vec![child]);
+
+    let height = wallet.wollet.tip().height();
+    server.generate(1);
+    wallet.wait_height(height + 1);
+    assert!(wallet.wollet.tx_package_info(&child).unwrap().is_none());
+}
+
+#[test]
+fn reused_addresses() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    let address = wallet.address_result(Some(0));
+    wallet.fund(&server, 10_000, Some(address.address().clone()), None);
+    assert!(wallet.wollet.reused_addresses().unwrap().is_empty());
+    let first = wallet.wollet.transactions().unwrap()[0].clone();
+    assert!(first.address_reused.iter().all(|r| !r));
+
+    wallet.fund(&server, 20_000, Some(address.address().clone()), None);
+    let reused = wallet.wollet.reused_addresses().unwrap();
+    assert_eq!(reused.len(), 1);
+    assert_eq!(&reused[0].address, address.address());
+    assert_eq!(reused[0].ext_int, Chain::External);
+    assert_eq!(reused[0].wildcard_index, 0);
+    assert_eq!(reused[0].txids[0], first.txid);
+
+    let second = wallet
+        .wollet
+        .transaction(&reused[0].txids[1])
+        .unwrap()
+        .unwrap();
+    let vout = second.outputs.iter().position(|o| o.is_some()).unwrap();
+    assert!(second.address_reused[vout]);
+    let first = wallet.wollet.transaction(&first.txid).unwrap().unwrap();
+    assert!(first.address_reused.iter().all(|r| !r));
+}
+
+#[test]
+fn fee_rate_policy() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    let client = ElectrumClient::new(&wallet.electrum_url).unwrap();
+
+    let policy = FeePolicy::default();
+    for target in [1, 6, 1000] {
+        let fee_rate = client.fee_rate(target, &policy);
+        assert!(fee_rate >= policy.min_fee_rate);
+        assert!(fee_rate <= policy.max_fee_rate);
+    }
+
+    let policy = FeePolicy {
+        min_fee_rate: 500.0,
+        max_fee_rate: 500.0,
+        ..Default::default()
+    };
+    assert_eq!(client.fee_rate(1, &policy), 500.0);
+}
+
+#[test]
+fn chain_headers() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+    let mut client = ElectrumClient::new(&wallet.electrum_url).unwrap();
+
+    let tip = client.tip().unwrap();
+    let header = client.header(tip.height).unwrap();
+    assert_eq!(header, tip);
+    // Served from the cache
+    assert_eq!(client.header(tip.height).unwrap(), tip);
+    assert_eq!(client.header(0).unwrap().height, 0);
+
+    // Electrum servers can't serve full blocks
+    assert!(client.block(BlockRef::Height(0)).is_err());
+}
+
+#[test]
+fn self_transfer() {
+    let server = setup(false);
+    let signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+    let signer = AnySigner::Software(signer);
+    let mut wallet = TestWollet::new(&server.electrs.electrum_url, &desc);
+
+    let other_signer = generate_signer();
+    let view_key = generate_view_key();
+    let desc = format!("ct({},elwpkh({}/*))", view_key, other_signer.xpub());
+    let mut other = TestWollet::new(&server.electrs.electrum_url, &desc);
+
+    wallet.fund_btc(&server);
+    let mut pset = wallet
+        .tx_builder()
+        .add_internal_recipient(&other.wollet, 10_000, wallet.policy_asset())
+        .unwrap()
+        .finish()
+        .unwrap();
+    wallet.sign(&signer, &mut pset);
+    let txid = wallet.send(&mut pset);
+    other.sync();
+    assert_eq!(other.balance(&wallet.policy_asset()), 10_000);
+
+    // Without the other account the transaction is a regular payment
+    let tx = wallet.wollet.transaction(&txid).unwrap().unwrap();
+    assert_eq!(tx.type_, "outgoing");
+
+    let txs = wallet
+        .wollet
+        .transactions_with_accounts(&[&other.wollet])
+        .unwrap();
+    let tx = txs.iter().find(|tx| tx.txid == txid).unwrap();
+    assert_eq!(tx.type_, "self-transfer");
+    let txs = other
+        .wollet
+        .transactions_with_accounts(&[&wallet.wollet])
+        .unwrap();
+    assert_eq!(txs.len(), 1);
+    assert_eq!(txs[0].type_, "self-transfer");
+
+    // The funding transaction comes from outside
+    let txs = wallet
+        .wollet
+        .transactions_with_accounts(&[&other.wollet])
+        .unwrap();
+    let funding = txs.iter().find(|tx| tx.txid != txid).unwrap();
+    assert_eq!(funding.type_, "incoming");
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_clients_async() {
+    let server = setup(true);
+    let esplora_url = format!("http://{}", server.electrs.esplora_url.as_ref().unwrap());
+    let electrum_url = ElectrumUrl::new(&server.electrs.electrum_url, false, false);
+    let network = ElementsNetwork::default_regtest();
+
+    let mut wollets = vec![];
+    for _ in 0..3 {
+        let signer = generate_signer();
+        let view_key = generate_view_key();
+        let desc = format!("ct({},elwpkh({}/*))", view_key, signer.xpub());
+        let wollet = Wollet::without_persist(network, desc.parse().unwrap()).unwrap();
+        let address = wollet.address(None).unwrap();
+        server.node_sendtoaddress(address.address(), 10_000, None);
+        wollets.push(wollet);
+    }
+    server.generate(1);
+
+    // Scan all the wallets concurrently on the same runtime
+    let scans = wollets.into_iter().map(|mut wollet| {
+        let esplora_url = esplora_url.clone();
+        let electrum_url = electrum_url.clone();
+        tokio::spawn(async move {
+            let esplora_client = EsploraClient::new(&esplora_url);
+            let mut electrum_client = ElectrumClient::new(&electrum_url).unwrap();
+            for _ in 0..50 {
+                let update = esplora_client.full_scan_async(&wollet).await.unwrap();
+                if let Some(update) = update {
+                    wollet.apply_update(update).unwrap();
+                }
+                if !wollet.transactions().unwrap().is_empty() {
+                    break;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            }
+            let balance = *wollet
+                .balance()
+                .unwrap()
+                .get(&network.policy_asset())
+                .unwrap();
+            assert_eq!(balance, 10_000);
+
+            // The electrum scan finds the same transactions
+            let mut other = Wollet::without_persist(network, wollet.wollet_descriptor()).unwrap();
+            let update = electrum_client
+                .full_scan_async(&other)
+                .await
+                .unwrap()
+                .unwrap();
+            other.apply_update(update).unwrap();
+            assert_eq!(
+                other.transactions().unwrap(),
+                wollet.transactions().unwrap()
+            );
+        })
+    });
+    for scan in scans.collect::<Vec<_>>() {
+        scan.await.unwrap();
+    }
+}
@@ -113,6 +113,14 @@ fn jade_slip77() {
     assert!(desc_str.contains(lwk_test_util::TEST_MNEMONIC_SLIP77))
 }
 
+#[test]
+fn emul_signer_conformance() {
+    init_logging();
+    let docker = Cli::default();
+    let jade_init = jade_setup(&docker, TEST_MNEMONIC);
+    lwk_signer::signer_conformance::assert_conformance(&jade_init.jade, TEST_MNEMONIC, false);
+}
+
 fn multi_multisig(server: &TestElectrumServer, jade_signer: &AnySigner) {
     // Signers: jade, sw1, sw2
     let sw_signer1 = AnySigner::Software(generate_signer());
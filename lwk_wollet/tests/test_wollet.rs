@@ -430,6 +430,17 @@ impl TestWollet {
         let balance_after = self.balance_btc();
         assert!(balance_before > balance_after);
 
+        let tokens = self.wollet.reissuance_tokens().unwrap();
+        let owned = tokens.iter().find(|t| t.token == token);
+        if satoshi_token > 0 {
+            let owned = owned.unwrap();
+            assert_eq!(owned.asset, asset);
+            assert_eq!(owned.satoshi, satoshi_token);
+        } else {
+            assert!(owned.is_none());
+        }
+        assert!(tokens.iter().all(|t| t.token != asset));
+
         let issuance = self.wollet.issuance(&asset).unwrap();
         assert_eq!(issuance.vin, 0);
         assert!(!issuance.is_reissuance);
@@ -458,7 +469,7 @@ impl TestWollet {
         let balance_token_before = self.balance(&issuance.token);
         let mut pset = self
             .tx_builder()
-            .reissue_asset(*asset, satoshi_asset, None, None)
+            .reissue(*asset, satoshi_asset)
             .unwrap()
             .fee_rate(fee_rate)
             .finish()